@@ -1,9 +1,40 @@
 //! Reading data directly from a buffer
+//!
+//! The functions in this module turn a byte slice into references to structs
+//! that mirror the on-disk layout, without copying. This is the only place that
+//! needs `unsafe` to do so, and it relies on the following invariants:
+//!
+//! - Every `T` is [`MinimallyAligned`], i.e. has an alignment of `1`, so any
+//!   address in the buffer is a valid address for it. This is checked again
+//!   before every cast, so a wrong `unsafe impl` panics instead of creating a
+//!   misaligned reference.
+//! - The byte range of the result is bounds checked with checked arithmetic,
+//!   so it can't wrap around on 32-bit targets or for huge `len`s.
+//! - The pointer is derived from the bounds-checked subslice, not from the
+//!   start of the buffer, so it only ever has provenance over the bytes it
+//!   covers, and the result borrows from `buffer` for its whole lifetime.
+//! - `T` must be valid for any bit pattern and have no padding, which holds for
+//!   the byte array wrappers below and the structs built from them.
+//!
+//! For types that can't guarantee an alignment of `1`, use [`try_read`] instead,
+//! which copies the value out of the buffer with an unaligned read.
+//!
+//! ## Checking with Miri
+//!
+//! The tests of this module exercise unaligned and out-of-bounds offsets and
+//! can be run under [Miri](https://github.com/rust-lang/miri), without any CI
+//! setup, from a checkout of this crate:
+//!
+//! ```text
+//! cargo +nightly miri test --lib buffer
+//! ```
 use displaydoc::Display;
+use std::mem::{align_of, size_of};
 use thiserror::Error;
 
 /// Errors from casting a minimally-aligned type
 #[derive(Debug, Error, Display)]
+#[non_exhaustive]
 pub enum CastError {
     /// Some byte between start and end was outside of the given buffer
     OutOfBounds {
@@ -16,9 +47,20 @@ pub enum CastError {
 ///
 /// ## Safety
 ///
-/// Implementor need to verify that [`std::mem::align_of`]`::<Self>() == 1`
+/// Implementor need to verify that [`std::mem::align_of`]`::<Self>() == 1`,
+/// that the type has no padding and that every bit pattern is a valid value.
 pub unsafe trait MinimallyAligned: Sized {}
 
+/// Get the bytes of `len` values of `T` at `offset`
+fn bytes_of<T>(buffer: &[u8], offset: u32, len: usize) -> Result<&[u8], CastError> {
+    let start = offset as usize;
+    let end = size_of::<T>()
+        .checked_mul(len)
+        .and_then(|size| start.checked_add(size));
+    end.and_then(|end| buffer.get(start..end))
+        .ok_or(CastError::OutOfBounds { offset })
+}
+
 /// Cast a buffer to a reference
 ///
 /// ## Panics
@@ -30,17 +72,15 @@ pub fn cast<T: MinimallyAligned>(buffer: &[u8], offset: u32) -> &T {
 
 /// Try to cast a buffer to a reference
 pub fn try_cast<T: MinimallyAligned>(buffer: &[u8], offset: u32) -> Result<&T, CastError> {
-    let base = buffer.as_ptr();
-    let len = buffer.len();
-
-    if offset as usize + std::mem::size_of::<T>() <= len {
-        unsafe {
-            let addr = base.offset(offset as isize);
-            Ok(&*(addr as *const T))
-        }
-    } else {
-        Err(CastError::OutOfBounds { offset })
-    }
+    assert_eq!(
+        align_of::<T>(),
+        1,
+        "MinimallyAligned type with alignment > 1"
+    );
+    let bytes = bytes_of::<T>(buffer, offset, 1)?;
+    // SAFETY: `bytes` covers `size_of::<T>()` initialized bytes, `T` has an
+    // alignment of 1 and is valid for any bit pattern (see module docs).
+    unsafe { Ok(&*bytes.as_ptr().cast::<T>()) }
 }
 
 /// Cast a buffer to a slice
@@ -58,20 +98,46 @@ pub fn try_cast_slice<T: MinimallyAligned>(
     offset: u32,
     len: u32,
 ) -> Result<&[T], CastError> {
-    let base = buffer.as_ptr();
-    let buf_len = buffer.len();
-
+    assert_eq!(
+        align_of::<T>(),
+        1,
+        "MinimallyAligned type with alignment > 1"
+    );
     let ulen = len as usize;
-    let needed = std::mem::size_of::<T>() * ulen;
+    let bytes = bytes_of::<T>(buffer, offset, ulen)?;
+    // SAFETY: `bytes` covers `ulen * size_of::<T>()` initialized bytes, `T` has
+    // an alignment of 1 and is valid for any bit pattern (see module docs).
+    unsafe { Ok(std::slice::from_raw_parts(bytes.as_ptr().cast::<T>(), ulen)) }
+}
 
-    if offset as usize + needed <= buf_len {
-        unsafe {
-            let addr = base.offset(offset as isize) as *const T;
-            Ok(std::slice::from_raw_parts(addr, ulen))
-        }
-    } else {
-        Err(CastError::OutOfBounds { offset })
-    }
+/// Asserts that the type is valid for any bit pattern
+///
+/// ## Safety
+///
+/// Implementor need to verify that the type has no padding and that every bit
+/// pattern is a valid value. The alignment may be greater than `1`.
+pub unsafe trait Pod: Copy {}
+
+macro_rules! impl_pod {
+    ($($ty:ty),*) => {
+        $(unsafe impl Pod for $ty {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// Try to copy a value out of a buffer
+///
+/// Unlike [`try_cast`], this works for types of any alignment, because it never
+/// creates a reference into the buffer. Note that the value is read in native
+/// byte order.
+pub fn try_read<T: Pod>(buffer: &[u8], offset: u32) -> Result<T, CastError> {
+    let bytes = bytes_of::<T>(buffer, offset, 1)?;
+    // SAFETY: `bytes` covers `size_of::<T>()` initialized bytes, the read does
+    // not need to be aligned, and `T` is valid for any bit pattern.
+    unsafe { Ok(bytes.as_ptr().cast::<T>().read_unaligned()) }
 }
 
 /// Similar to `From<&U> for T`
@@ -144,4 +210,31 @@ mod tests {
 
         assert_eq!(std::mem::align_of::<LEU16>(), 1);
     }
+
+    #[test]
+    fn test_unaligned_and_out_of_bounds() {
+        // A buffer that starts at an address with an alignment of 8
+        let words = [0x0403_0201_u64.to_le(), !0];
+        let bytes: &[u8] = unsafe { std::slice::from_raw_parts(words.as_ptr().cast(), 16) };
+        for offset in 1..4 {
+            let le: &LEU32 = cast(bytes, offset);
+            assert_eq!(le.extract() & 0xFF, offset + 1);
+            assert_eq!(
+                try_read::<[u8; 2]>(bytes, offset).unwrap()[0] as u32,
+                offset + 1
+            );
+        }
+        assert_eq!(
+            try_read::<u64>(bytes, 3).unwrap(),
+            u64::from_ne_bytes([4, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF])
+        );
+        assert_eq!(try_cast_slice::<LEU32>(bytes, 1, 3).unwrap().len(), 3);
+
+        assert!(try_cast::<LEI64>(bytes, 9).is_err());
+        assert!(try_cast::<LEU16>(bytes, u32::MAX).is_err());
+        assert!(try_cast_slice::<LEU32>(bytes, 1, 4).is_err());
+        assert!(try_cast_slice::<LEI64>(bytes, 1, u32::MAX).is_err());
+        assert!(try_read::<u64>(bytes, 9).is_err());
+        assert!(try_cast_slice::<LEU32>(bytes, 16, 0).unwrap().is_empty());
+    }
 }
@@ -0,0 +1,159 @@
+//! # The hash functions used by the game formats
+//!
+//! This module collects the hashes that show up across multiple formats,
+//! so that the crates for the individual formats don't need to carry their
+//! own copies of them.
+//!
+//! - [`sfhash`] is Paul Hsieh's *SuperFastHash*, used to assign rows with a
+//!   text primary key to the buckets of an FDB table.
+//! - [`pk_crc`] is the CRC-32 variant that identifies files by their path in
+//!   pack index (`*.pki`) and pack (`*.pk`) files.
+
+/// The generator polynomial of the path CRC
+pub const CRC_POLY: u32 = 0x04C11DB7;
+/// The initial value of the path CRC
+pub const CRC_INIT: u32 = 0xFFFFFFFF;
+/// The final XOR value of the path CRC
+pub const CRC_FXOR: u32 = 0x00000000;
+
+#[inline]
+fn get16(data: &[u8], i: usize) -> u32 {
+    u32::from(data[i]) | (u32::from(data[i + 1]) << 8)
+}
+
+#[inline]
+fn signed(b: u8) -> u32 {
+    // The reference implementation reads the tail as `signed char`
+    b as i8 as u32
+}
+
+/// Calculate Paul Hsieh's *SuperFastHash* of some bytes
+///
+/// This is the hash that the FDB uses for text keys. Note that the game hashes
+/// the Latin-1 representation of a string, not its UTF-8 encoding.
+pub fn sfhash(data: &[u8]) -> u32 {
+    if data.is_empty() {
+        return 0;
+    }
+
+    let mut hash = data.len() as u32;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        hash = hash.wrapping_add(get16(chunk, 0));
+        let tmp = (get16(chunk, 2) << 11) ^ hash;
+        hash = (hash << 16) ^ tmp;
+        hash = hash.wrapping_add(hash >> 11);
+    }
+
+    let rem = chunks.remainder();
+    match rem.len() {
+        3 => {
+            hash = hash.wrapping_add(get16(rem, 0));
+            hash ^= hash << 16;
+            hash ^= signed(rem[2]) << 18;
+            hash = hash.wrapping_add(hash >> 11);
+        }
+        2 => {
+            hash = hash.wrapping_add(get16(rem, 0));
+            hash ^= hash << 11;
+            hash = hash.wrapping_add(hash >> 17);
+        }
+        1 => {
+            hash = hash.wrapping_add(signed(rem[0]));
+            hash ^= hash << 10;
+            hash = hash.wrapping_add(hash >> 1);
+        }
+        _ => {}
+    }
+
+    hash ^= hash << 3;
+    hash = hash.wrapping_add(hash >> 5);
+    hash ^= hash << 4;
+    hash = hash.wrapping_add(hash >> 17);
+    hash ^= hash << 25;
+    hash = hash.wrapping_add(hash >> 6);
+    hash
+}
+
+/// Calculate the FDB hash of an integer primary key
+///
+/// Integer keys are their own hash, reinterpreted as unsigned.
+pub fn fdb_int_hash(key: i32) -> u32 {
+    u32::from_ne_bytes(key.to_ne_bytes())
+}
+
+/// Calculate the FDB hash of a text primary key, given its Latin-1 bytes
+pub fn fdb_text_hash(latin1: &[u8]) -> u32 {
+    sfhash(latin1)
+}
+
+/// Get the index of the bucket for a hash in a table with `bucket_count` buckets
+pub fn fdb_bucket(hash: u32, bucket_count: usize) -> usize {
+    hash as usize % bucket_count
+}
+
+fn update_crc(crc: &mut u32, b: u8) {
+    *crc ^= u32::from(b) << 24; /* Move byte to MSB */
+    for _i in 0..8 {
+        if (*crc & 0x80000000) == 0 {
+            *crc <<= 1;
+        } else {
+            *crc = (*crc << 1) ^ CRC_POLY;
+        }
+    }
+}
+
+/// Calculate the CRC for a path in the pack files
+///
+/// The path is normalized to lowercase with `\` as a separator before hashing.
+pub fn pk_crc(path: &[u8]) -> u32 {
+    let mut crc: u32 = CRC_INIT;
+    /* Process the actual string */
+    for bp in path {
+        /* Perform some cleanup on the input */
+        let b = match *bp {
+            b'/' => b'\\',
+            b => b.to_ascii_lowercase(),
+        };
+
+        update_crc(&mut crc, b);
+    }
+    /* I have no clue why this was added */
+    for _i in 0..4 {
+        update_crc(&mut crc, 0);
+    }
+    crc ^= CRC_FXOR;
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sfhash() {
+        assert_eq!(sfhash(b""), 0);
+        assert_eq!(sfhash(b"a"), 0x115ea782);
+        assert_eq!(sfhash(b"ab"), 0x516b8b44);
+        assert_eq!(sfhash(b"abc"), 0xd2be198a);
+        assert_eq!(sfhash(b"abcd"), 0xdad8b8db);
+        assert_eq!(sfhash(b"Hello, World!"), 0x86956be9);
+        assert_eq!(sfhash(b"ObjectSkills"), 0x6b2b4a81);
+        assert_eq!(fdb_text_hash(b"r\xe9sum\xe9"), 0x5e1d878d);
+    }
+
+    #[test]
+    fn test_fdb_int_hash() {
+        assert_eq!(fdb_int_hash(1), 1);
+        assert_eq!(fdb_int_hash(-1), 0xFFFFFFFF);
+        assert_eq!(fdb_bucket(fdb_int_hash(130), 128), 2);
+    }
+
+    #[test]
+    fn test_pk_crc() {
+        assert_eq!(pk_crc(b""), 0xc704dd7b);
+        assert_eq!(pk_crc(b"res/a.txt"), 0x9f6efb92);
+        assert_eq!(pk_crc(b"RES\\A.TXT"), 0x9f6efb92);
+        assert_eq!(pk_crc(b"client\\res\\macros\\aaa.txt"), 0x5ef5bc71);
+    }
+}
@@ -0,0 +1,94 @@
+//! # Annotated hexdumps for error reports
+//!
+//! A [`Snippet`] is a small window of bytes around the position of an error.
+//! Its `Display` implementation prints a classic hexdump with a marker below
+//! the byte at which parsing failed.
+
+use std::fmt;
+
+/// A window of bytes around an error position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    /// The absolute position of the first byte in `bytes`
+    pub start: usize,
+    /// The absolute position of the error
+    pub offset: usize,
+    /// The captured bytes
+    pub bytes: Vec<u8>,
+}
+
+impl Snippet {
+    /// The default number of bytes captured before and after the offset
+    pub const DEFAULT_RADIUS: usize = 32;
+
+    /// Capture up to `radius` bytes before and after `offset` from `input`
+    pub fn capture(input: &[u8], offset: usize, radius: usize) -> Self {
+        let offset = offset.min(input.len());
+        let start = offset.saturating_sub(radius);
+        let end = offset.saturating_add(radius).min(input.len());
+        Self {
+            start,
+            offset,
+            bytes: input[start..end].to_vec(),
+        }
+    }
+
+    fn byte_at(&self, addr: usize) -> Option<u8> {
+        addr.checked_sub(self.start)
+            .and_then(|index| self.bytes.get(index))
+            .copied()
+    }
+}
+
+impl fmt::Display for Snippet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "near offset {:#x}:", self.offset)?;
+        let end = (self.start + self.bytes.len()).max(self.offset + 1);
+        let mut line = self.start & !0xF;
+        while line < end {
+            write!(f, "{:08x} ", line)?;
+            for addr in line..(line + 16) {
+                match self.byte_at(addr) {
+                    Some(byte) => write!(f, " {:02x}", byte)?,
+                    None => write!(f, "   ")?,
+                }
+            }
+            write!(f, "  |")?;
+            for addr in line..(line + 16) {
+                let c = match self.byte_at(addr) {
+                    Some(byte) if byte.is_ascii_graphic() || byte == b' ' => char::from(byte),
+                    Some(_) => '.',
+                    None => ' ',
+                };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f, "|")?;
+            if (line..(line + 16)).contains(&self.offset) {
+                let width = 10 + (self.offset - line) * 3;
+                writeln!(f, "{:width$}^^", "", width = width)?;
+            }
+            line += 16;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Snippet;
+
+    #[test]
+    fn test_snippet_display() {
+        let input: Vec<u8> = (0x40..0x60).collect();
+        let snippet = Snippet::capture(&input, 0x12, 4);
+        assert_eq!(snippet.start, 0x0e);
+        assert_eq!(snippet.bytes.len(), 8);
+        let text = snippet.to_string();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines[0], "near offset 0x12:");
+        assert!(lines[1].starts_with("00000000 "));
+        assert!(lines[1].ends_with("|              NO|"));
+        assert!(lines[2].starts_with("00000010  50 51 52 53 54 55"));
+        assert_eq!(lines[3], format!("{}^^", " ".repeat(16)));
+    }
+}
@@ -0,0 +1,142 @@
+//! # Case folding and collation for Latin-1 text
+//!
+//! Text in the game files is encoded as Windows-1252 (a superset of Latin-1),
+//! one byte per character. The functions in this module work on these bytes
+//! directly, so that comparing strings doesn't need to decode them first:
+//!
+//! - [`to_lowercase`] folds the case of all letters, including the accented
+//!   ones, unlike [`u8::to_ascii_lowercase`]
+//! - [`collate`] orders strings like a dictionary, i.e. ignoring case and
+//!   accents first and only then looking at them
+//!
+//! ```
+//! use assembly_core::latin1::{collate, eq_ignore_case};
+//! use std::cmp::Ordering;
+//!
+//! // "ÉQUIPE" and "équipe"
+//! assert!(eq_ignore_case(b"\xC9QUIPE", b"\xE9quipe"));
+//! // "école" sorts between "ecole" and "ecrire"
+//! assert_eq!(collate(b"\xE9cole", b"ecole"), Ordering::Greater);
+//! assert_eq!(collate(b"\xE9cole", b"ecrire"), Ordering::Less);
+//! ```
+
+use std::cmp::Ordering;
+
+const fn build_lowercase() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let b = byte as u8;
+        table[byte] = match b {
+            b'A'..=b'Z' | 0xC0..=0xD6 | 0xD8..=0xDE => b + 0x20,
+            0x8A | 0x8C | 0x8E => b + 0x10,
+            0x9F => 0xFF,
+            _ => b,
+        };
+        byte += 1;
+    }
+    table
+}
+
+const fn build_base() -> [u8; 256] {
+    let lowercase = build_lowercase();
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let b = lowercase[byte];
+        table[byte] = match b {
+            0xE0..=0xE6 => b'a',
+            0xE7 => b'c',
+            0xE8..=0xEB => b'e',
+            0xEC..=0xEF => b'i',
+            0xF0 => b'd',
+            0xF1 => b'n',
+            0xF2..=0xF6 | 0xF8 | 0x9C => b'o',
+            0xF9..=0xFC => b'u',
+            0xFD | 0xFF => b'y',
+            0xFE => b't',
+            0xDF | 0x9A => b's',
+            0x9E => b'z',
+            _ => b,
+        };
+        byte += 1;
+    }
+    table
+}
+
+/// The lower case variant of every byte, see [`to_lowercase`]
+pub static LOWERCASE: [u8; 256] = build_lowercase();
+
+/// The lower case ASCII letter for every letter, see [`base_letter`]
+pub static BASE_LETTER: [u8; 256] = build_base();
+
+/// Map an upper case letter to its lower case variant
+///
+/// This includes ASCII, the accented letters in `À..=Þ` (except `×`) as well as
+/// `Š`, `Œ`, `Ž` and `Ÿ`. All other bytes are returned unchanged.
+pub fn to_lowercase(byte: u8) -> u8 {
+    LOWERCASE[byte as usize]
+}
+
+/// Map a letter to the lower case ASCII letter it is based on
+///
+/// For example, `À`, `à` and `A` all map to `a`. Ligatures and letters that
+/// don't exist in ASCII map to the closest one (e.g. `æ` to `a`, `ß` to `s`
+/// and `þ` to `t`). All other bytes are returned unchanged.
+pub fn base_letter(byte: u8) -> u8 {
+    BASE_LETTER[byte as usize]
+}
+
+/// Check whether two strings are equal, ignoring case
+pub fn eq_ignore_case(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(x, y)| to_lowercase(*x) == to_lowercase(*y))
+}
+
+/// Compare two strings, ignoring case
+pub fn cmp_ignore_case(a: &[u8], b: &[u8]) -> Ordering {
+    a.iter()
+        .map(|x| to_lowercase(*x))
+        .cmp(b.iter().map(|y| to_lowercase(*y)))
+}
+
+/// Compare two strings in dictionary order
+///
+/// Strings are first compared by their [`base_letter`]s, then ignoring case
+/// and finally by their bytes, so that only strings with the same bytes are
+/// equal.
+pub fn collate(a: &[u8], b: &[u8]) -> Ordering {
+    a.iter()
+        .map(|x| base_letter(*x))
+        .cmp(b.iter().map(|y| base_letter(*y)))
+        .then_with(|| cmp_ignore_case(a, b))
+        .then_with(|| a.cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folding() {
+        for byte in 0..=255u8 {
+            let lower = to_lowercase(byte);
+            assert_eq!(to_lowercase(lower), lower);
+            assert_eq!(base_letter(lower), base_letter(byte));
+            if byte.is_ascii() {
+                assert_eq!(lower, byte.to_ascii_lowercase());
+            }
+        }
+        assert_eq!(to_lowercase(0xD7), 0xD7); // ×
+        assert_eq!(base_letter(0xC7), b'c'); // Ç
+
+        let mut words: Vec<&[u8]> = vec![b"zebra", b"\xC9cole", b"ecole", b"Ecole", b"eclair"];
+        words.sort_by(|a, b| collate(a, b));
+        assert_eq!(
+            words,
+            [&b"eclair"[..], b"Ecole", b"ecole", b"\xC9cole", b"zebra"]
+        );
+    }
+}
@@ -1,7 +1,84 @@
 //! The LEGO data format
+//!
+//! LDF is a list of typed key-value pairs. In files, it is usually stored as
+//! text with one `key=type:value` entry per line, where `type` is the number
+//! of a [`ValueType`]. Network packets use a binary form of the same data,
+//! see [`LDF::from_binary`] and [`LDF::write_binary`].
+//!
+//! Parsing is strict: a value that can't be read as its declared type is an
+//! error. Values that are valid, but look like they were declared with the
+//! wrong type, can be found with [`LDF::lint`].
 #[cfg(feature = "serde-derives")]
 use serde::Serialize;
-use std::{collections::BTreeMap, fmt::Debug, str::FromStr};
+use std::{
+    char::decode_utf16,
+    collections::BTreeMap,
+    convert::TryFrom,
+    fmt::{self, Debug},
+    io::{self, ErrorKind, Write},
+    str::FromStr,
+};
+
+use displaydoc::Display;
+use thiserror::Error;
+
+/// The type of an LDF value
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ValueType {
+    /// A user-facing (UTF-16) string
+    WString = 0,
+    /// A signed 32bit integer
+    I32 = 1,
+    /// A single precision floating point number
+    Float = 3,
+    /// An unsigned 32bit integer
+    U32 = 5,
+    /// A boolean
+    Bool = 7,
+    /// A signed 64bit integer
+    I64 = 8,
+    /// An object ID
+    ObjectID = 9,
+    /// An internal (UTF-8) string
+    Bytes = 13,
+}
+
+impl ValueType {
+    /// Get the type for the number used in the text and binary format
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::WString),
+            1 => Some(Self::I32),
+            3 => Some(Self::Float),
+            5 => Some(Self::U32),
+            7 => Some(Self::Bool),
+            8 => Some(Self::I64),
+            9 => Some(Self::ObjectID),
+            13 => Some(Self::Bytes),
+            _ => None,
+        }
+    }
+
+    /// Get the number used in the text and binary format
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::WString => "wstring",
+            Self::I32 => "i32",
+            Self::Float => "float",
+            Self::U32 => "u32",
+            Self::Bool => "bool",
+            Self::I64 => "i64",
+            Self::ObjectID => "id",
+            Self::Bytes => "bytes",
+        })
+    }
+}
 
 /// A LEGO-Data-Format value
 #[derive(PartialEq)]
@@ -16,10 +93,72 @@ pub enum Value {
     U32(u32),
     /// A boolean (0 or 1)
     Bool(bool),
+    /// A signed 64bit integer
+    I64(i64),
+    /// An object ID
+    ObjectID(i64),
     /// An internal string
     Bytes(String),
 }
 
+impl Value {
+    /// Get the type of this value
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Self::String(_) => ValueType::WString,
+            Self::I32(_) => ValueType::I32,
+            Self::F32(_) => ValueType::Float,
+            Self::U32(_) => ValueType::U32,
+            Self::Bool(_) => ValueType::Bool,
+            Self::I64(_) => ValueType::I64,
+            Self::ObjectID(_) => ValueType::ObjectID,
+            Self::Bytes(_) => ValueType::Bytes,
+        }
+    }
+
+    /// Parse the text form of a value of type `ty`
+    pub fn parse(ty: ValueType, text: &str) -> Option<Self> {
+        Some(match ty {
+            ValueType::WString => Self::String(text.into()),
+            ValueType::I32 => Self::I32(text.parse().ok()?),
+            ValueType::Float => Self::F32(text.parse().ok()?),
+            ValueType::U32 => Self::U32(text.parse().ok()?),
+            ValueType::Bool => Self::Bool(match text {
+                "0" => false,
+                "1" => true,
+                _ => return None,
+            }),
+            ValueType::I64 => Self::I64(text.parse().ok()?),
+            ValueType::ObjectID => Self::ObjectID(text.parse().ok()?),
+            ValueType::Bytes => Self::Bytes(text.into()),
+        })
+    }
+
+    /// Get the type that the content of a string value suggests
+    ///
+    /// Returns `None` for all other values, and for strings that don't look
+    /// like a number or boolean.
+    pub fn suggested_type(&self) -> Option<ValueType> {
+        let text = match self {
+            Self::String(s) | Self::Bytes(s) => s.trim(),
+            _ => return None,
+        };
+        if text.is_empty() {
+            None
+        } else if text == "true" || text == "false" {
+            Some(ValueType::Bool)
+        } else if text.parse::<i32>().is_ok() {
+            Some(ValueType::I32)
+        } else if text.parse::<i64>().is_ok() {
+            Some(ValueType::I64)
+        } else if text.parse::<f32>().map(f32::is_finite).unwrap_or(false) {
+            Some(ValueType::Float)
+        } else {
+            None
+        }
+    }
+}
+
 #[cfg(feature = "serde-derives")]
 impl Serialize for Value {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -32,6 +171,7 @@ impl Serialize for Value {
             Self::F32(i) => serializer.serialize_f32(*i),
             Self::U32(i) => serializer.serialize_u32(*i),
             Self::Bool(b) => serializer.serialize_bool(*b),
+            Self::I64(i) | Self::ObjectID(i) => serializer.serialize_i64(*i),
             Self::Bytes(b) => serializer.serialize_str(b.as_str()),
         }
     }
@@ -45,6 +185,8 @@ impl Debug for Value {
             Self::F32(l) => l.fmt(f),
             Self::U32(u) => u.fmt(f),
             Self::Bool(b) => b.fmt(f),
+            Self::I64(i) => i.fmt(f),
+            Self::ObjectID(i) => write!(f, "#{}", i),
             Self::Bytes(s) => {
                 write!(f, "b")?;
                 s.fmt(f)
@@ -53,6 +195,21 @@ impl Debug for Value {
     }
 }
 
+/// Formats the value as `type:value`, as it appears in the text format
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.value_type().id())?;
+        match self {
+            Self::String(s) | Self::Bytes(s) => f.write_str(s),
+            Self::I32(i) => write!(f, "{}", i),
+            Self::F32(v) => write!(f, "{}", v),
+            Self::U32(u) => write!(f, "{}", u),
+            Self::Bool(b) => write!(f, "{}", u8::from(*b)),
+            Self::I64(i) | Self::ObjectID(i) => write!(f, "{}", i),
+        }
+    }
+}
+
 /// A table of LDF values
 #[cfg_attr(feature = "serde-derives", derive(Serialize))]
 #[cfg_attr(feature = "serde-derives", serde(transparent))]
@@ -71,9 +228,68 @@ impl Debug for LDF {
     }
 }
 
+/// Formats the table in the text format, with one `key=type:value` per line
+impl fmt::Display for LDF {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (key, value)) in self.map.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
 /// Error when parsing LDF
-#[derive(Debug)]
-pub struct LDFError(u8, String);
+#[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
+pub enum LDFError {
+    /// Missing `=` after key {0:?}
+    MissingEquals(String),
+    /// Missing `:` after the type of key {0:?}
+    MissingColon(String),
+    /// Unknown type {ty:?} for key {key:?}
+    UnknownType {
+        /// The key of the entry
+        key: String,
+        /// The type as it appears in the input
+        ty: String,
+    },
+    /// Invalid {ty} value {value:?} for key {key:?}
+    InvalidValue {
+        /// The key of the entry
+        key: String,
+        /// The declared type
+        ty: ValueType,
+        /// The value as it appears in the input
+        value: String,
+    },
+    /// Unexpected end of binary data
+    UnexpectedEnd,
+    /// Invalid UTF-16 or UTF-8 in binary data
+    Encoding,
+}
+
+/// A value whose content doesn't match its declared type, see [`LDF::lint`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeMismatch {
+    /// The key of the entry
+    pub key: String,
+    /// The declared type
+    pub declared: ValueType,
+    /// The type that the content suggests
+    pub suggested: ValueType,
+}
+
+impl fmt::Display for TypeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is declared as {}, but looks like {}",
+            self.key, self.declared, self.suggested
+        )
+    }
+}
 
 impl FromStr for LDF {
     type Err = LDFError;
@@ -82,24 +298,28 @@ impl FromStr for LDF {
             .try_fold(BTreeMap::new(), |mut x, y| {
                 let mut out = y.splitn(2, '=');
                 let key = out.next().unwrap();
-                let val = out.next().ok_or_else(|| LDFError(0, key.to_string()))?;
+                let val = out
+                    .next()
+                    .ok_or_else(|| LDFError::MissingEquals(key.to_string()))?;
                 let mut inn = val.splitn(2, ':');
                 let typ = inn.next().unwrap();
-                let z = inn.next().ok_or_else(|| LDFError(1, key.to_string()))?;
-
-                let v = match typ {
-                    "0" => Value::String(z.into()),
-                    "1" => Value::I32(z.parse().map_err(|_| LDFError(2, key.to_string()))?),
-                    "3" => Value::F32(z.parse().map_err(|_| LDFError(3, z.to_string()))?),
-                    "5" => Value::U32(z.parse().map_err(|_| LDFError(4, key.to_string()))?),
-                    "7" => Value::Bool(match z {
-                        "0" => false,
-                        "1" => true,
-                        _ => return Err(LDFError(5, key.to_string())),
-                    }),
-                    "13" => Value::Bytes(z.into()),
-                    _ => return Err(LDFError(6, key.to_string())),
-                };
+                let z = inn
+                    .next()
+                    .ok_or_else(|| LDFError::MissingColon(key.to_string()))?;
+
+                let ty = typ
+                    .parse()
+                    .ok()
+                    .and_then(ValueType::from_id)
+                    .ok_or_else(|| LDFError::UnknownType {
+                        key: key.to_string(),
+                        ty: typ.to_string(),
+                    })?;
+                let v = Value::parse(ty, z).ok_or_else(|| LDFError::InvalidValue {
+                    key: key.to_string(),
+                    ty,
+                    value: z.to_string(),
+                })?;
                 x.insert(key.to_string(), v);
                 Ok(x)
             })
@@ -107,9 +327,164 @@ impl FromStr for LDF {
     }
 }
 
+struct BinaryReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> BinaryReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], LDFError> {
+        if self.bytes.len() < len {
+            return Err(LDFError::UnexpectedEnd);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], LDFError> {
+        let mut buf = [0; N];
+        buf.copy_from_slice(self.take(N)?);
+        Ok(buf)
+    }
+
+    fn u32(&mut self) -> Result<u32, LDFError> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn utf16(&mut self, units: usize) -> Result<String, LDFError> {
+        let bytes = self.take(units * 2)?;
+        let iter = bytes
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]));
+        decode_utf16(iter)
+            .collect::<Result<_, _>>()
+            .map_err(|_| LDFError::Encoding)
+    }
+}
+
+fn write_utf16<W: Write>(out: &mut W, text: &str) -> io::Result<()> {
+    for unit in text.encode_utf16() {
+        out.write_all(&unit.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn too_long(what: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, format!("{} is too long", what))
+}
+
+impl LDF {
+    /// Read the binary form
+    ///
+    /// The binary form starts with the number of entries (`u32`). Each entry
+    /// has the key as UTF-16 prefixed by its length in bytes (`u8`), the type
+    /// (`u8`) and the value. Strings are prefixed by their length (`u32`) in
+    /// code units, booleans take a single byte.
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, LDFError> {
+        let mut reader = BinaryReader { bytes };
+        let count = reader.u32()?;
+        let mut map = BTreeMap::new();
+        for _ in 0..count {
+            let [key_len] = reader.array()?;
+            let key = reader.utf16(usize::from(key_len / 2))?;
+            let [id] = reader.array()?;
+            let ty = ValueType::from_id(id).ok_or_else(|| LDFError::UnknownType {
+                key: key.clone(),
+                ty: id.to_string(),
+            })?;
+            let value = match ty {
+                ValueType::WString => {
+                    let len = reader.u32()? as usize;
+                    Value::String(reader.utf16(len)?)
+                }
+                ValueType::I32 => Value::I32(i32::from_le_bytes(reader.array()?)),
+                ValueType::Float => Value::F32(f32::from_le_bytes(reader.array()?)),
+                ValueType::U32 => Value::U32(reader.u32()?),
+                ValueType::Bool => {
+                    let [b] = reader.array()?;
+                    Value::Bool(b != 0)
+                }
+                ValueType::I64 => Value::I64(i64::from_le_bytes(reader.array()?)),
+                ValueType::ObjectID => Value::ObjectID(i64::from_le_bytes(reader.array()?)),
+                ValueType::Bytes => {
+                    let len = reader.u32()? as usize;
+                    let bytes = reader.take(len)?.to_vec();
+                    Value::Bytes(String::from_utf8(bytes).map_err(|_| LDFError::Encoding)?)
+                }
+            };
+            map.insert(key, value);
+        }
+        Ok(LDF { map })
+    }
+
+    /// Write the binary form, see [`LDF::from_binary`]
+    pub fn write_binary<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let count = u32::try_from(self.map.len()).map_err(|_| too_long("map"))?;
+        out.write_all(&count.to_le_bytes())?;
+        for (key, value) in &self.map {
+            let key_len = key.encode_utf16().count() * 2;
+            let key_len = u8::try_from(key_len).map_err(|_| too_long(key))?;
+            out.write_all(&[key_len])?;
+            write_utf16(out, key)?;
+            out.write_all(&[value.value_type().id()])?;
+            match value {
+                Value::String(s) => {
+                    let len = s.encode_utf16().count();
+                    let len = u32::try_from(len).map_err(|_| too_long(key))?;
+                    out.write_all(&len.to_le_bytes())?;
+                    write_utf16(out, s)?;
+                }
+                Value::I32(i) => out.write_all(&i.to_le_bytes())?,
+                Value::F32(v) => out.write_all(&v.to_le_bytes())?,
+                Value::U32(u) => out.write_all(&u.to_le_bytes())?,
+                Value::Bool(b) => out.write_all(&[u8::from(*b)])?,
+                Value::I64(i) | Value::ObjectID(i) => out.write_all(&i.to_le_bytes())?,
+                Value::Bytes(s) => {
+                    let len = u32::try_from(s.len()).map_err(|_| too_long(key))?;
+                    out.write_all(&len.to_le_bytes())?;
+                    out.write_all(s.as_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the binary form, see [`LDF::from_binary`]
+    pub fn to_binary(&self) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.write_binary(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Find string values that look like they should have a different type
+    ///
+    /// ```
+    /// use assembly_core::ldf::{ValueType, LDF};
+    ///
+    /// let ldf: LDF = "name=0:Pirate\nspawn_count=0:12".parse().unwrap();
+    /// let lints = ldf.lint();
+    /// assert_eq!(lints.len(), 1);
+    /// assert_eq!(lints[0].key, "spawn_count");
+    /// assert_eq!(lints[0].suggested, ValueType::I32);
+    /// ```
+    pub fn lint(&self) -> Vec<TypeMismatch> {
+        self.map
+            .iter()
+            .filter_map(|(key, value)| {
+                let suggested = value.suggested_type()?;
+                Some(TypeMismatch {
+                    key: key.clone(),
+                    declared: value.value_type(),
+                    suggested,
+                })
+            })
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Value, LDF};
+    use super::{LDFError, Value, ValueType, LDF};
 
     #[test]
     fn test_from_str() {
@@ -175,5 +550,38 @@ mod tests {
         }
 
         assert_eq!(r.len(), 0);
+        assert_eq!(map.to_string(), text);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = "bytes=13:scripts\\a.lua\nflag=7:1\nid=9:1152921504606846976\nlong=8:-5000000000\nname=0:Pirate \u{1f3f4}\nneg=1:-1\nscale=3:0.25\ncount=5:4000000000";
+        let map: LDF = text.parse().unwrap();
+        assert_eq!(map.map["id"], Value::ObjectID(1 << 60));
+        assert_eq!(map.map["long"], Value::I64(-5_000_000_000));
+
+        let mut lines: Vec<_> = text.split('\n').collect();
+        lines.sort_unstable();
+        assert_eq!(map.to_string(), lines.join("\n"));
+
+        let binary = map.to_binary().unwrap();
+        assert_eq!(&binary[..4], &8u32.to_le_bytes());
+        let again = LDF::from_binary(&binary).unwrap();
+        assert_eq!(again.map, map.map);
+        assert_eq!(
+            LDF::from_binary(&binary[..binary.len() - 1]).unwrap_err(),
+            LDFError::UnexpectedEnd
+        );
+
+        assert_eq!(
+            "flag=7:true".parse::<LDF>().unwrap_err(),
+            LDFError::InvalidValue {
+                key: "flag".to_string(),
+                ty: ValueType::Bool,
+                value: "true".to_string(),
+            }
+        );
+        assert!("x=2:0".parse::<LDF>().is_err());
+        assert!("x=5:-1".parse::<LDF>().is_err());
     }
 }
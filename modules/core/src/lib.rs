@@ -1,21 +1,34 @@
 //! # Common datastructures and methods
 //!
 //! This module implements core traits for this library
+//!
+//! The `nom` feature (enabled by default) provides the [`parser`] and [`reader`]
+//! modules. Without it, only the parts needed for in-memory access remain.
 #![warn(missing_docs)]
 use std::time::Instant;
 
 pub mod borrow;
 pub mod buffer;
+pub mod hash;
+pub mod hexdump;
+pub mod latin1;
 pub mod ldf;
+#[cfg(feature = "nom")]
 #[doc(hidden)]
 pub mod nom_ext;
+#[cfg(feature = "nom")]
 pub mod parser;
+pub mod progress;
+#[cfg(feature = "nom")]
 pub mod reader;
+pub mod source;
 pub mod types;
+pub mod writer;
 
 #[macro_use]
 #[doc(hidden)]
 pub extern crate num_derive;
+#[cfg(feature = "nom")]
 #[doc(hidden)]
 pub extern crate nom;
 #[doc(hidden)]
@@ -11,11 +11,14 @@ use nom::{
 use nom::{
     error::ParseError,
     number::complete::{le_f32, le_u32, le_u8},
-    IResult,
+    IResult, ToUsize,
 };
 use num_traits::FromPrimitive;
 use std::{char::decode_utf16, string::FromUtf8Error};
 
+use displaydoc::Display;
+use thiserror::Error;
+
 /// Helper method to dump some values
 #[allow(dead_code)]
 pub fn dump<T>(val: T) -> T
@@ -82,36 +85,95 @@ where
     map(tuple((le_u32, le_u32)), |(a, b)| ObjectID::new(b, a))(input)
 }
 
-fn map_wstring(val: &[u8]) -> Result<String, ()> {
-    let iter = val.chunks_exact(2);
-    if let [] = iter.remainder() {
-        let iter = iter.map(|s| (s[0] as u16) + ((s[1] as u16) << 8));
-        decode_utf16(iter).map(|r| r.or(Err(()))).collect()
-    } else {
-        Err(())
+/// Errors when decoding a length-prefixed string
+#[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StringError {
+    /// Byte {0} is not ASCII
+    NotAscii(usize),
+    /// Unpaired UTF-16 surrogate at code unit {0}
+    InvalidUtf16(usize),
+}
+
+fn map_ascii(val: &[u8]) -> Result<String, StringError> {
+    match val.iter().position(|b| !b.is_ascii()) {
+        Some(pos) => Err(StringError::NotAscii(pos)),
+        // All bytes are ASCII, so this is valid UTF-8
+        None => Ok(val.iter().map(|&b| char::from(b)).collect()),
+    }
+}
+
+fn map_wstring(val: &[u8]) -> Result<String, StringError> {
+    let iter = val
+        .chunks_exact(2)
+        .map(|s| u16::from_le_bytes([s[0], s[1]]));
+    let mut string = String::with_capacity(val.len() / 2);
+    for (index, r) in decode_utf16(iter).enumerate() {
+        string.push(r.map_err(|_| StringError::InvalidUtf16(index))?);
     }
+    Ok(string)
+}
+
+/// Parse an ASCII string after a length specifier
+///
+/// The `length` parser returns the number of bytes.
+pub fn length_ascii<'a, N, E, F>(length: F) -> impl Fn(&'a [u8]) -> Res<'a, String, E>
+where
+    F: Fn(&'a [u8]) -> Res<'a, N, E>,
+    N: ToUsize,
+    E: ParseError<&'a [u8]> + FromExternalError<&'a [u8], StringError>,
+{
+    move |input: &'a [u8]| {
+        let (input, count) = length(input)?;
+        map_res(take(count.to_usize()), map_ascii)(input)
+    }
+}
+
+/// Parse an UTF-16 (LE) string after a length specifier
+///
+/// The `length` parser returns the number of code units, i.e. half the number of bytes.
+pub fn length_wstring<'a, N, E, F>(length: F) -> impl Fn(&'a [u8]) -> Res<'a, String, E>
+where
+    F: Fn(&'a [u8]) -> Res<'a, N, E>,
+    N: ToUsize,
+    E: ParseError<&'a [u8]> + FromExternalError<&'a [u8], StringError>,
+{
+    move |input: &'a [u8]| {
+        let (input, count) = length(input)?;
+        map_res(take(count.to_usize() * 2), map_wstring)(input)
+    }
+}
+
+/// Parse an ASCII string after an u8 length specifier
+pub fn parse_u8_ascii<'a, E>(input: &'a [u8]) -> Res<'a, String, E>
+where
+    E: ParseError<&'a [u8]> + FromExternalError<&'a [u8], StringError>,
+{
+    length_ascii(le_u8)(input)
+}
+
+/// Parse an ASCII string after an u32 length specifier
+pub fn parse_u32_ascii<'a, E>(input: &'a [u8]) -> Res<'a, String, E>
+where
+    E: ParseError<&'a [u8]> + FromExternalError<&'a [u8], StringError>,
+{
+    length_ascii(le_u32)(input)
 }
 
 /// Parse a u8 wstring
 pub fn parse_u8_wstring<'a, E>(input: &'a [u8]) -> Res<'a, String, E>
 where
-    E: ParseError<&'a [u8]> + FromExternalError<&'a [u8], ()>,
+    E: ParseError<&'a [u8]> + FromExternalError<&'a [u8], StringError>,
 {
-    le_u8(input).and_then(|(input, count)| {
-        let len = usize::from(count) * 2;
-        map_res(take(len), map_wstring)(input)
-    })
+    length_wstring(le_u8)(input)
 }
 
 /// Parse a u32 wstring
 pub fn parse_u32_wstring<'a, E>(input: &'a [u8]) -> Res<'a, String, E>
 where
-    E: ParseError<&'a [u8]> + FromExternalError<&'a [u8], ()>,
+    E: ParseError<&'a [u8]> + FromExternalError<&'a [u8], StringError>,
 {
-    le_u32(input).and_then(|(input, count)| {
-        let len = count * 2;
-        map_res(take(len), map_wstring)(input)
-    })
+    length_wstring(le_u32)(input)
 }
 
 /// Parse a string with u16 length specifier
@@ -156,7 +218,7 @@ where
 
 #[cfg(test)]
 mod test {
-    use super::parse_u8_wstring;
+    use super::{parse_u32_ascii, parse_u8_wstring};
     use nom::error::ErrorKind;
 
     #[test]
@@ -165,5 +227,15 @@ mod test {
             parse_u8_wstring::<'_, (&[u8], ErrorKind)>(&[2, 65, 0, 66, 0]),
             Ok((&[][..], String::from("AB")))
         );
+        assert!(parse_u8_wstring::<'_, (&[u8], ErrorKind)>(&[1, 0x00, 0xDC]).is_err());
+    }
+
+    #[test]
+    fn test_ascii() {
+        assert_eq!(
+            parse_u32_ascii::<'_, (&[u8], ErrorKind)>(&[2, 0, 0, 0, 65, 66, 67]),
+            Ok((&[67][..], String::from("AB")))
+        );
+        assert!(parse_u32_ascii::<'_, (&[u8], ErrorKind)>(&[1, 0, 0, 0, 0xE4]).is_err());
     }
 }
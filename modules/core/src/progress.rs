@@ -0,0 +1,117 @@
+//! # Progress reporting and cancellation
+//!
+//! Long running operations such as loading a complete database, exporting it
+//! or extracting a pack file accept a [`ProgressSink`]. The sink is told how
+//! many steps there are and when a step is done, and it can ask for the
+//! operation to stop early.
+//!
+//! An [`AtomicBool`] is the simplest sink: it ignores the progress and cancels
+//! the operation once it is set to `true`.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Receives progress updates from a long running operation
+///
+/// All methods take `&self`, so that a sink can be shared between threads.
+/// Implementations should use interior mutability (e.g. atomics) to track state.
+pub trait ProgressSink {
+    /// Called when a phase called `label` with `total` steps starts
+    fn start(&self, _label: &str, _total: u64) {}
+
+    /// Called when `steps` more steps of the current phase are done
+    fn advance(&self, _steps: u64) {}
+
+    /// Whether the operation should be stopped as soon as possible
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// A sink that ignores all progress and never cancels
+impl ProgressSink for () {}
+
+/// A cancellation token
+impl ProgressSink for AtomicBool {
+    fn is_cancelled(&self) -> bool {
+        self.load(Ordering::Relaxed)
+    }
+}
+
+impl<P: ProgressSink + ?Sized> ProgressSink for &P {
+    fn start(&self, label: &str, total: u64) {
+        (**self).start(label, total)
+    }
+
+    fn advance(&self, steps: u64) {
+        (**self).advance(steps)
+    }
+
+    fn is_cancelled(&self) -> bool {
+        (**self).is_cancelled()
+    }
+}
+
+impl<P: ProgressSink + ?Sized> ProgressSink for Arc<P> {
+    fn start(&self, label: &str, total: u64) {
+        (**self).start(label, total)
+    }
+
+    fn advance(&self, steps: u64) {
+        (**self).advance(steps)
+    }
+
+    fn is_cancelled(&self) -> bool {
+        (**self).is_cancelled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU64;
+
+    #[derive(Default)]
+    struct Counter {
+        total: AtomicU64,
+        done: AtomicU64,
+    }
+
+    impl ProgressSink for Counter {
+        fn start(&self, _label: &str, total: u64) {
+            self.total.store(total, Ordering::Relaxed);
+        }
+
+        fn advance(&self, steps: u64) {
+            self.done.fetch_add(steps, Ordering::Relaxed);
+        }
+    }
+
+    fn run<P: ProgressSink + ?Sized>(progress: &P) -> u64 {
+        progress.start("steps", 5);
+        let mut steps = 0;
+        while steps < 5 && !progress.is_cancelled() {
+            progress.advance(1);
+            steps += 1;
+        }
+        steps
+    }
+
+    #[test]
+    fn test_progress_sinks() {
+        assert_eq!(run(&()), 5);
+        assert_eq!(run(&AtomicBool::new(false)), 5);
+        assert_eq!(run(&AtomicBool::new(true)), 0);
+
+        let counter = Arc::new(Counter::default());
+        assert_eq!(run(&&*counter), 5);
+        assert_eq!(run(&counter), 5);
+        assert_eq!(counter.total.load(Ordering::Relaxed), 5);
+        assert_eq!(counter.done.load(Ordering::Relaxed), 10);
+
+        let cancel: Arc<dyn ProgressSink> = Arc::new(AtomicBool::new(true));
+        assert_eq!(run(&cancel), 0);
+    }
+}
@@ -1,11 +1,13 @@
 //! Common error and result handling facilities
+use crate::hexdump::Snippet;
 use displaydoc::Display;
-use nom::{error::ErrorKind, Offset};
+use nom::{error::ErrorKind, IResult, Offset};
 use std::{io, num::TryFromIntError};
 use thiserror::Error;
 
 /// Error when parsing a file
 #[derive(Error, Debug, Display)]
+#[non_exhaustive]
 pub enum FileError {
     /// IO Error {0:?}
     IO(#[from] io::Error),
@@ -30,6 +32,43 @@ pub enum FileError {
     NotImplemented,
     /// {0}
     Custom(&'static str),
+    /// The operation was cancelled
+    Cancelled,
+    /// {error}, {snippet}
+    Context {
+        /// The original error
+        error: Box<FileError>,
+        /// The bytes around the position of the error
+        snippet: Snippet,
+    },
+}
+
+impl FileError {
+    /// Attach the bytes around the position of a parse error
+    ///
+    /// `input` must be the buffer that the `addr` of a [`FileError::Parse`] refers
+    /// to. All other errors are returned unchanged.
+    pub fn with_context(self, input: &[u8]) -> Self {
+        match self {
+            FileError::Parse { addr, offset, .. } => {
+                let pos = (addr as usize).saturating_add(offset);
+                let snippet = Snippet::capture(input, pos, Snippet::DEFAULT_RADIUS);
+                FileError::Context {
+                    error: Box::new(self),
+                    snippet,
+                }
+            }
+            other => other,
+        }
+    }
+
+    /// Returns the captured bytes around the error, if any
+    pub fn snippet(&self) -> Option<&Snippet> {
+        match self {
+            FileError::Context { snippet, .. } => Some(snippet),
+            _ => None,
+        }
+    }
 }
 
 /// Trait to hand over a parse error past a buffer
@@ -48,29 +87,118 @@ impl<T> ParseAt<T> for Result<T, nom::error::Error<&[u8]>> {
     }
 }
 
-/*/// Nom error
-#[derive(Debug, Error)]
+/// A nom error with the absolute position in the input
+///
+/// Unlike the nom error itself, this does not borrow the input, so it can be
+/// returned from functions that load a whole file.
+#[derive(Error, Debug, Display, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ParseError {
-    /// Parsing was not successful
-    #[error("Error at -{0}, {1:?}")]
-    Error(usize, ErrorKind),
-    /// A parse was recognized but invalid
-    #[error("Failure at -{0}, {1:?}")]
-    Failure(usize, ErrorKind),
-    /// Needs more data
-    #[error("Incomplete")]
+    /// Parse error at byte {offset}: {code:?}
+    Error {
+        /// The position in the input
+        offset: usize,
+        /// The nom error kind
+        code: ErrorKind,
+    },
+    /// Parse failure at byte {offset}: {code:?}
+    Failure {
+        /// The position in the input
+        offset: usize,
+        /// The nom error kind
+        code: ErrorKind,
+    },
+    /// Unexpected end of input
     Incomplete,
 }
 
-impl<I: InputLength> From<nom::Err<nom::error::Error<I>>> for ParseError {
-    fn from(error: nom::Err<nom::error::Error<I>>) -> Self {
+impl ParseError {
+    /// Create a new instance from an error of a parser that started at `input`
+    pub fn new(input: &[u8], error: nom::Err<nom::error::Error<&[u8]>>) -> Self {
         match error {
-            nom::Err::Error(e) => Self::Error(e.input.input_len(), e.code),
-            nom::Err::Failure(e) => Self::Failure(e.input.input_len(), e.code),
+            nom::Err::Error(e) => Self::Error {
+                offset: input.offset(e.input),
+                code: e.code,
+            },
+            nom::Err::Failure(e) => Self::Failure {
+                offset: input.offset(e.input),
+                code: e.code,
+            },
             nom::Err::Incomplete(_) => Self::Incomplete,
         }
     }
-}*/
+
+    /// Returns the position of the error in the input, if known
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Self::Error { offset, .. } | Self::Failure { offset, .. } => Some(*offset),
+            Self::Incomplete => None,
+        }
+    }
+
+    /// Capture up to `radius` bytes around the error from `input`
+    ///
+    /// `input` must be the same buffer that was passed to [`ParseError::new`].
+    pub fn snippet(&self, input: &[u8], radius: usize) -> Option<Snippet> {
+        self.offset()
+            .map(|offset| Snippet::capture(input, offset, radius))
+    }
+
+    /// Returns the nom error kind, if any
+    pub fn code(&self) -> Option<ErrorKind> {
+        match self {
+            Self::Error { code, .. } | Self::Failure { code, .. } => Some(*code),
+            Self::Incomplete => None,
+        }
+    }
+}
+
+impl From<ParseError> for FileError {
+    fn from(error: ParseError) -> Self {
+        match error {
+            ParseError::Error { offset, code } | ParseError::Failure { offset, code } => {
+                FileError::Parse {
+                    addr: 0,
+                    offset,
+                    code,
+                }
+            }
+            ParseError::Incomplete => FileError::Incomplete,
+        }
+    }
+}
+
+/// Trait to attach absolute positions to the result of a parser
+pub trait ParseOffset<'a, T>: Sized {
+    /// Convert the error, given the `input` the parser was called with
+    fn at_offset(self, input: &'a [u8]) -> Result<(&'a [u8], T), ParseError>;
+}
+
+impl<'a, T> ParseOffset<'a, T> for IResult<&'a [u8], T> {
+    fn at_offset(self, input: &'a [u8]) -> Result<(&'a [u8], T), ParseError> {
+        self.map_err(|e| ParseError::new(input, e))
+    }
+}
 
 /// Result when parsing a file
 pub type FileResult<T> = Result<T, FileError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::number::complete::le_u32;
+
+    #[test]
+    fn test_parse_offset() {
+        let input: &[u8] = &[1, 0, 0, 0, 2, 0];
+        let res = le_u32(&input[4..]).at_offset(input);
+        assert_eq!(
+            res,
+            Err(ParseError::Error {
+                offset: 4,
+                code: ErrorKind::Eof
+            })
+        );
+        assert_eq!(res.unwrap_err().to_string(), "Parse error at byte 4: Eof");
+    }
+}
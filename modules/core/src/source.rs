@@ -0,0 +1,119 @@
+//! # Sources of bytes for the file loaders
+//!
+//! Most file formats in this library are parsed from a complete buffer. The
+//! [`ByteSource`] trait abstracts over where that buffer comes from, so that the
+//! same loader works with slices, memory maps, files or custom sources such as
+//! decrypted archives.
+//!
+//! In-memory sources like `&[u8]` hand out their data without copying, all
+//! other sources are read into a new buffer.
+
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{self, BufReader, Cursor, Read},
+};
+
+/// A source that can provide all of its bytes at once
+pub trait ByteSource {
+    /// Get the (remaining) content of the source
+    fn read_bytes(&mut self) -> io::Result<Cow<'_, [u8]>>;
+}
+
+fn read_to_vec<R: Read>(reader: &mut R) -> io::Result<Cow<'static, [u8]>> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    Ok(Cow::Owned(bytes))
+}
+
+impl<S: ByteSource + ?Sized> ByteSource for &mut S {
+    fn read_bytes(&mut self) -> io::Result<Cow<'_, [u8]>> {
+        (**self).read_bytes()
+    }
+}
+
+impl ByteSource for &[u8] {
+    fn read_bytes(&mut self) -> io::Result<Cow<'_, [u8]>> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl ByteSource for Vec<u8> {
+    fn read_bytes(&mut self) -> io::Result<Cow<'_, [u8]>> {
+        Ok(Cow::Borrowed(&self[..]))
+    }
+}
+
+impl ByteSource for Box<[u8]> {
+    fn read_bytes(&mut self) -> io::Result<Cow<'_, [u8]>> {
+        Ok(Cow::Borrowed(&self[..]))
+    }
+}
+
+impl<T: AsRef<[u8]>> ByteSource for Cursor<T> {
+    fn read_bytes(&mut self) -> io::Result<Cow<'_, [u8]>> {
+        let bytes = self.get_ref().as_ref();
+        let start = (self.position() as usize).min(bytes.len());
+        Ok(Cow::Borrowed(&bytes[start..]))
+    }
+}
+
+impl ByteSource for File {
+    fn read_bytes(&mut self) -> io::Result<Cow<'_, [u8]>> {
+        read_to_vec(self)
+    }
+}
+
+impl<R: Read> ByteSource for BufReader<R> {
+    fn read_bytes(&mut self) -> io::Result<Cow<'_, [u8]>> {
+        read_to_vec(self)
+    }
+}
+
+/// Wrapper to use any buffer (e.g. a memory map) as a [`ByteSource`]
+///
+/// ```
+/// use assembly_core::source::{ByteSource, InMemory};
+///
+/// let mut src = InMemory([1u8, 2, 3]);
+/// assert_eq!(&src.read_bytes().unwrap()[..], &[1, 2, 3]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct InMemory<T>(pub T);
+
+impl<T: AsRef<[u8]>> ByteSource for InMemory<T> {
+    fn read_bytes(&mut self) -> io::Result<Cow<'_, [u8]>> {
+        Ok(Cow::Borrowed(self.0.as_ref()))
+    }
+}
+
+/// Wrapper to use any [`Read`] implementation as a [`ByteSource`]
+#[derive(Debug)]
+pub struct Reader<R>(pub R);
+
+impl<R: Read> ByteSource for Reader<R> {
+    fn read_bytes(&mut self) -> io::Result<Cow<'_, [u8]>> {
+        read_to_vec(&mut self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sources() {
+        let data: &[u8] = &[1, 2, 3, 4];
+        let mut slice = data;
+        assert!(matches!(slice.read_bytes().unwrap(), Cow::Borrowed(_)));
+
+        let mut cursor = Cursor::new(data);
+        cursor.set_position(2);
+        assert_eq!(&cursor.read_bytes().unwrap()[..], &[3, 4]);
+
+        let mut reader = Reader(data);
+        let bytes = reader.read_bytes().unwrap();
+        assert!(matches!(bytes, Cow::Owned(_)));
+        assert_eq!(&bytes[..], data);
+    }
+}
@@ -5,7 +5,7 @@ use derive_new::new;
 use serde::Serialize;
 
 /// Position in three dimensional space
-#[derive(Copy, Clone, Debug, new)]
+#[derive(Copy, Clone, Debug, PartialEq, new)]
 #[cfg_attr(feature = "serde-derives", derive(Serialize))]
 pub struct Vector3f {
     /// The X coordinate
@@ -33,7 +33,7 @@ impl Into<[f32; 3]> for Vector3f {
 }
 
 /// Rotation in three dimensional space
-#[derive(Debug, new)]
+#[derive(Copy, Clone, Debug, new)]
 #[cfg_attr(feature = "serde-derives", derive(Serialize))]
 pub struct Quaternion {
     /// The X component
@@ -47,7 +47,7 @@ pub struct Quaternion {
 }
 
 /// Position and rotation in three dimensional space
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug)]
 #[cfg_attr(feature = "serde-derives", derive(Serialize))]
 pub struct Placement3D {
     /// The position
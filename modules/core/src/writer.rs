@@ -0,0 +1,124 @@
+//! # Writer methods for the general types
+//!
+//! These are the inverse of the functions in the `parser` module.
+use super::types::{ObjectID, ObjectTemplate, Quaternion, Vector3f, WorldID};
+use num_traits::ToPrimitive;
+use std::convert::TryFrom;
+use std::io::{self, ErrorKind, Write};
+
+fn too_long(len: usize) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidInput,
+        format!("string of length {} is too long", len),
+    )
+}
+
+/// Write an u32 in little endian
+pub fn write_u32<W: Write>(out: &mut W, value: u32) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+/// Write an f32 in little endian
+pub fn write_f32<W: Write>(out: &mut W, value: f32) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+/// Write a Vector3f
+pub fn write_vec3f<W: Write>(out: &mut W, value: &Vector3f) -> io::Result<()> {
+    write_f32(out, value.x)?;
+    write_f32(out, value.y)?;
+    write_f32(out, value.z)
+}
+
+/// Write a Quaternion
+pub fn write_quat<W: Write>(out: &mut W, value: &Quaternion) -> io::Result<()> {
+    write_f32(out, value.x)?;
+    write_f32(out, value.y)?;
+    write_f32(out, value.z)?;
+    write_f32(out, value.w)
+}
+
+/// Write a Quaternion with the W component first
+pub fn write_quat_wxyz<W: Write>(out: &mut W, value: &Quaternion) -> io::Result<()> {
+    write_f32(out, value.w)?;
+    write_f32(out, value.x)?;
+    write_f32(out, value.y)?;
+    write_f32(out, value.z)
+}
+
+/// Write a WorldID
+pub fn write_world_id<W: Write>(out: &mut W, value: &WorldID) -> io::Result<()> {
+    write_u32(out, value.to_u32().unwrap_or_default())
+}
+
+/// Write an ObjectTemplate
+pub fn write_object_template<W: Write>(out: &mut W, value: &ObjectTemplate) -> io::Result<()> {
+    write_u32(out, value.to_u32().unwrap_or_default())
+}
+
+/// Write an ObjectID
+pub fn write_object_id<W: Write>(out: &mut W, value: &ObjectID) -> io::Result<()> {
+    write_u32(out, value.id)?;
+    write_u32(out, value.scope)
+}
+
+/// Write an u8 boolean
+pub fn write_u8_bool<W: Write>(out: &mut W, value: bool) -> io::Result<()> {
+    out.write_all(&[u8::from(value)])
+}
+
+/// Write a string after an u8 length specifier
+pub fn write_u8_string<W: Write>(out: &mut W, value: &str) -> io::Result<()> {
+    let len = u8::try_from(value.len()).map_err(|_| too_long(value.len()))?;
+    out.write_all(&[len])?;
+    out.write_all(value.as_bytes())
+}
+
+/// Write a string after an u32 length specifier
+pub fn write_u32_string<W: Write>(out: &mut W, value: &str) -> io::Result<()> {
+    let len = u32::try_from(value.len()).map_err(|_| too_long(value.len()))?;
+    write_u32(out, len)?;
+    out.write_all(value.as_bytes())
+}
+
+fn encode_wstring(value: &str) -> Vec<u8> {
+    value.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+/// Write an u8 wstring
+pub fn write_u8_wstring<W: Write>(out: &mut W, value: &str) -> io::Result<()> {
+    let bytes = encode_wstring(value);
+    let len = u8::try_from(bytes.len() / 2).map_err(|_| too_long(bytes.len() / 2))?;
+    out.write_all(&[len])?;
+    out.write_all(&bytes)
+}
+
+/// Write an u32 wstring
+pub fn write_u32_wstring<W: Write>(out: &mut W, value: &str) -> io::Result<()> {
+    let bytes = encode_wstring(value);
+    let len = u32::try_from(bytes.len() / 2).map_err(|_| too_long(bytes.len() / 2))?;
+    write_u32(out, len)?;
+    out.write_all(&bytes)
+}
+
+#[cfg(all(test, feature = "nom"))]
+mod test {
+    use super::*;
+    use crate::parser::{parse_object_id, parse_u8_string, parse_u8_wstring};
+    use nom::error::ErrorKind as NomErrorKind;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut out = Vec::new();
+        write_u8_wstring(&mut out, "Ä€").unwrap();
+        write_u8_string(&mut out, "AB").unwrap();
+        write_object_id(&mut out, &ObjectID::new(1 << 10, 42)).unwrap();
+        let (rest, wide) = parse_u8_wstring::<(&[u8], NomErrorKind)>(&out).unwrap();
+        let (rest, narrow) = parse_u8_string::<(&[u8], NomErrorKind)>(rest).unwrap();
+        let (rest, id) = parse_object_id::<(&[u8], NomErrorKind)>(rest).unwrap();
+        assert_eq!((wide.as_str(), narrow.as_str()), ("Ä€", "AB"));
+        assert_eq!((id.scope, id.id), (1 << 10, 42));
+        assert!(rest.is_empty());
+        assert!(write_u8_string(&mut out, &"a".repeat(256)).is_err());
+    }
+}
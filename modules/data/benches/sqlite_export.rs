@@ -0,0 +1,62 @@
+//! Compares the time of a serial and a parallel SQLite export
+//!
+//! Run with `cargo bench --bench sqlite_export --features testing`. The
+//! database is generated with 16 tables of `ROWS` rows each, and written to a
+//! file in the temporary folder, once on a single thread and once on as many
+//! threads as there are CPUs, or as set in the `EXPORT_THREADS` variable.
+
+use std::{env, fs, thread, time::Instant};
+
+use assembly_data::fdb::{
+    common::ValueType,
+    mem::Database,
+    sqlite::{try_export_db_with_options, Connection, ExportOptions},
+    testing::SampleDatabase,
+};
+
+const TABLES: usize = 16;
+const ROWS: usize = 20_000;
+
+fn export(buf: &[u8], threads: usize) -> f64 {
+    let path = env::temp_dir().join(format!("assembly-bench-{}.sqlite", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let mut conn = Connection::open(&path).unwrap();
+    let options = ExportOptions {
+        threads,
+        ..ExportOptions::default()
+    };
+    let start = Instant::now();
+    try_export_db_with_options(&mut conn, Database::new(buf), &options, &()).unwrap();
+    let elapsed = start.elapsed().as_secs_f64();
+    drop(conn);
+    fs::remove_file(&path).unwrap();
+    elapsed
+}
+
+fn main() {
+    let columns = [
+        ("id", ValueType::Integer),
+        ("name", ValueType::Text),
+        ("scale", ValueType::Float),
+        ("enabled", ValueType::Boolean),
+        ("data", ValueType::VarChar),
+    ];
+    let mut sample = SampleDatabase::new();
+    for index in 0..TABLES {
+        sample = sample
+            .table(&format!("Table{}", index), &columns)
+            .rows(ROWS);
+    }
+    let buf = sample.build();
+    let threads = match env::var("EXPORT_THREADS") {
+        Ok(threads) => threads.parse().expect("EXPORT_THREADS must be a number"),
+        Err(_) => thread::available_parallelism().map_or(4, |n| n.get()),
+    };
+
+    let serial = export(&buf, 1);
+    let parallel = export(&buf, threads);
+    println!("{} tables with {} rows each", TABLES, ROWS);
+    println!("1 thread:   {:>8.1} ms", serial * 1000.0);
+    println!("{} threads: {:>8.1} ms", threads, parallel * 1000.0);
+    println!("speedup:    {:>8.2}x", serial / parallel);
+}
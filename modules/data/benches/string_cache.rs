@@ -0,0 +1,101 @@
+//! Compares loading a schema with and without a string cache
+//!
+//! Run with `cargo bench --bench string_cache --features testing`. The table
+//! has `ROWS` rows with two text columns, and every text field points to one
+//! of `DISTINCT` shared strings, like the paths and group names of the game
+//! files. The file is read through a [`BufReader`] from the temporary folder.
+
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    env,
+    fs::{self, File},
+    io::BufReader,
+    time::Instant,
+};
+
+use assembly_data::fdb::{
+    common::ValueType,
+    core::Field,
+    io::{LoaderConfigImpl, SchemaLoader},
+    mem::raw,
+    testing::SampleDatabase,
+};
+
+const ROWS: usize = 200_000;
+const DISTINCT: usize = 550;
+const CAPACITY: usize = 4096;
+
+/// Generate the file, pointing all equal strings to the first copy
+fn generate() -> Vec<u8> {
+    let columns = [
+        ("id", ValueType::Integer),
+        ("path", ValueType::Text),
+        ("group", ValueType::Text),
+    ];
+    let mut sample = SampleDatabase::new().table("Objects", &columns);
+    for index in 0..ROWS {
+        sample = sample.row(vec![
+            Field::Integer(index as i32),
+            Field::Text(format!("res/objects/{}.nif", index % DISTINCT)),
+            Field::Text(format!("group{}", index % (DISTINCT / 10))),
+        ]);
+    }
+    let mut buf = sample.build();
+
+    let mut patches = Vec::new();
+    let mut first = HashMap::new();
+    let header = raw::header(&buf).unwrap().value;
+    for table in raw::table_headers(&buf, header).unwrap() {
+        let data = raw::table_data_header(&buf, table.value).unwrap().value;
+        for bucket in raw::bucket_headers(&buf, data).unwrap() {
+            let head = bucket.value.row_header_list_head_addr;
+            for entry in raw::row_list(&buf, head).unwrap() {
+                let row = raw::row_header(&buf, entry.value.row_header_addr).unwrap();
+                for field in raw::fields(&buf, row.value).unwrap() {
+                    if ValueType::try_from(field.value.data_type) == Ok(ValueType::Text) {
+                        let addr = u32::from_le_bytes(field.value.value);
+                        let text = raw::string(&buf, addr).unwrap().value.as_bytes().to_vec();
+                        let shared = *first.entry(text).or_insert(addr);
+                        patches.push((field.addr as usize + 4, shared));
+                    }
+                }
+            }
+        }
+    }
+    for (pos, addr) in patches {
+        buf[pos..pos + 4].copy_from_slice(&addr.to_le_bytes());
+    }
+    buf
+}
+
+fn load(path: &std::path::Path, cache: Option<usize>) -> f64 {
+    let mut reader = BufReader::new(File::open(path).unwrap());
+    let config = LoaderConfigImpl {
+        table_data_policy: |_| true,
+    };
+    let start = Instant::now();
+    let mut loader = SchemaLoader::open(&mut reader, config);
+    if let Some(capacity) = cache {
+        loader = loader.with_string_cache(capacity);
+    }
+    loader.try_load_schema().unwrap();
+    start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let path = env::temp_dir().join(format!("assembly-bench-{}.fdb", std::process::id()));
+    fs::write(&path, generate()).unwrap();
+
+    let uncached = load(&path, None);
+    let cached = load(&path, Some(CAPACITY));
+    fs::remove_file(&path).unwrap();
+    println!(
+        "{} rows, {} distinct strings",
+        ROWS,
+        DISTINCT + DISTINCT / 10
+    );
+    println!("without cache: {:>8.1} ms", uncached * 1000.0);
+    println!("with cache:    {:>8.1} ms", cached * 1000.0);
+    println!("speedup:       {:>8.2}x", uncached / cached);
+}
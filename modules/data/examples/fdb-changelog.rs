@@ -0,0 +1,62 @@
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::PathBuf,
+};
+
+use assembly_data::{
+    fdb::{
+        changelog::Changelog,
+        core::Schema,
+        merge::{diff, MergeOptions},
+    },
+    xml::localization::read_locale,
+};
+use structopt::StructOpt;
+
+use color_eyre::eyre::{self, WrapErr};
+
+#[derive(StructOpt)]
+/// Prints the changes between two FDB files as a Markdown list
+struct Options {
+    /// The old FDB file
+    old: PathBuf,
+    /// The new FDB file
+    new: PathBuf,
+    /// The `locale.xml` file to look up the names of rows in
+    #[structopt(long)]
+    locale: Option<PathBuf>,
+    /// The locale to use for the names
+    #[structopt(long, default_value = "en_US")]
+    language: String,
+}
+
+fn load(path: &PathBuf) -> eyre::Result<Schema> {
+    let file = File::open(path)
+        .wrap_err_with(|| format!("Failed to open input file '{}'", path.display()))?;
+    Schema::from_source(file)
+        .wrap_err_with(|| format!("Failed to load input file '{}'", path.display()))
+}
+
+fn main() -> eyre::Result<()> {
+    color_eyre::install()?;
+    let opts = Options::from_args();
+
+    let old = load(&opts.old)?;
+    let new = load(&opts.new)?;
+    let changes = diff(&old, &new, &MergeOptions::new())?;
+
+    let locale = match &opts.locale {
+        Some(path) => {
+            let file = File::open(path)
+                .wrap_err_with(|| format!("Failed to open locale file '{}'", path.display()))?;
+            read_locale(BufReader::new(file), &opts.language)?
+        }
+        None => Default::default(),
+    };
+    Changelog::cdclient()
+        .with_locale(&locale)
+        .write(&changes, io::stdout().lock())?;
+
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use assembly_data::fdb::{encoding::audit, mem::Database};
+use color_eyre::eyre::WrapErr;
+use mapr::Mmap;
+use std::{fs::File, path::PathBuf};
+use structopt::StructOpt;
+
+/// Classify the strings of all text columns of an FDB file
+#[derive(StructOpt)]
+struct Options {
+    /// The FDB file
+    file: PathBuf,
+    /// Only show the columns that may contain binary data
+    #[structopt(short, long)]
+    suspicious: bool,
+}
+
+fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+    let opts = Options::from_args();
+
+    let file = File::open(&opts.file)
+        .wrap_err_with(|| format!("Failed to open input file '{}'", opts.file.display()))?;
+    let mmap = unsafe { Mmap::map(&file)? };
+    let buffer: &[u8] = &mmap;
+
+    let audit = audit(Database::new(buffer))?;
+    if opts.suspicious {
+        for (table, column) in audit.suspicious() {
+            println!("{}.{}: {:?}", table, column.name, column);
+        }
+    } else {
+        println!("{}", audit);
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use assembly_data::fdb::{
+    common::IterOrder,
+    csv::CsvDialect,
+    mem::Database,
+    stream::{export_tables_streaming_with, StreamError, StreamFormat, StreamOptions},
+};
+use color_eyre::eyre::{eyre, WrapErr};
+use mapr::Mmap;
+use std::{fs::File, io, path::PathBuf};
+use structopt::StructOpt;
+
+/// Write the rows of an FDB file to stdout, as CSV or JSON lines
+#[derive(StructOpt)]
+struct Options {
+    /// The FDB file
+    file: PathBuf,
+    /// Only export this table
+    #[structopt(short, long)]
+    table: Option<String>,
+    /// Write CSV instead of JSON lines
+    #[structopt(long)]
+    csv: bool,
+    /// The CSV dialect: default, rfc4180, excel or tsv
+    #[structopt(long, default_value = "default")]
+    dialect: String,
+    /// The text of NULL fields in CSV, instead of the one of the dialect
+    #[structopt(long)]
+    null: Option<String>,
+    /// Write the rows sorted by primary key, for output that can be diffed
+    #[structopt(long)]
+    sorted: bool,
+}
+
+fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+    let opts = Options::from_args();
+
+    let file = File::open(&opts.file)
+        .wrap_err_with(|| format!("Failed to open input file '{}'", opts.file.display()))?;
+    let mmap = unsafe { Mmap::map(&file)? };
+    let buffer: &[u8] = &mmap;
+
+    let db = Database::new(buffer);
+    let format = if opts.csv {
+        StreamFormat::Csv
+    } else {
+        StreamFormat::JsonLines
+    };
+    let mut csv = match opts.dialect.as_str() {
+        "default" => CsvDialect::default(),
+        "rfc4180" => CsvDialect::rfc4180(),
+        "excel" => CsvDialect::excel(),
+        "tsv" => CsvDialect::tsv(),
+        other => return Err(eyre!("Unknown CSV dialect {:?}", other)),
+    };
+    if let Some(null) = opts.null {
+        csv.null = null;
+    }
+    let order = if opts.sorted {
+        IterOrder::PrimaryKey
+    } else {
+        IterOrder::File
+    };
+    let options = StreamOptions {
+        csv,
+        order,
+        index_strings: true,
+        ..StreamOptions::default()
+    };
+    let out = io::stdout().lock();
+    let tables = db.tables()?;
+    let result = match &opts.table {
+        Some(name) => {
+            let table = tables
+                .by_name(name)
+                .ok_or_else(|| eyre!("Failed to find table {:?}", name))?;
+            export_tables_streaming_with(Some(table), format, &options, out)
+        }
+        None => export_tables_streaming_with(tables.iter(), format, &options, out),
+    };
+    match result {
+        Ok(stats) => {
+            eprintln!("Exported {} rows from {} tables", stats.rows, stats.tables);
+            Ok(())
+        }
+        // The reader went away, e.g. `fdb-export cdclient.fdb | head`
+        Err(StreamError::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
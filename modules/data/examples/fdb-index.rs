@@ -29,6 +29,7 @@ fn field_to_cell(field: mem::Field) -> PCell {
         Field::Boolean(v) => PCell::new(if v { "true" } else { "false" }),
         Field::BigInt(v) => PCell::new(&format!("{} (i64)", v)),
         Field::VarChar(v) => PCell::new(&format!("{:?}", v)),
+        _ => PCell::new("?"),
     }
 }
 
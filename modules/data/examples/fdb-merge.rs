@@ -0,0 +1,61 @@
+use std::{fs::File, io::BufWriter, path::PathBuf, time::Instant};
+
+use assembly_data::fdb::{
+    core::Schema,
+    merge::{merge, MergeOptions},
+    store,
+};
+use structopt::StructOpt;
+
+use color_eyre::eyre::{self, WrapErr};
+
+#[derive(StructOpt)]
+/// Merges the changes of two edited copies of an FDB file
+struct Options {
+    /// The FDB file both copies were made from
+    base: PathBuf,
+    /// Our edited copy
+    ours: PathBuf,
+    /// Their edited copy
+    theirs: PathBuf,
+    /// The FDB file to create
+    dest: PathBuf,
+}
+
+fn load(path: &PathBuf) -> eyre::Result<Schema> {
+    let file = File::open(path)
+        .wrap_err_with(|| format!("Failed to open input file '{}'", path.display()))?;
+    Schema::from_source(file)
+        .wrap_err_with(|| format!("Failed to load input file '{}'", path.display()))
+}
+
+fn main() -> eyre::Result<()> {
+    color_eyre::install()?;
+    let opts = Options::from_args();
+    let start = Instant::now();
+
+    let base = load(&opts.base)?;
+    let ours = load(&opts.ours)?;
+    let theirs = load(&opts.theirs)?;
+
+    let merged = merge(&base, &ours, &theirs, &MergeOptions::new())?;
+    for conflict in &merged.conflicts {
+        println!("CONFLICT: {}", conflict);
+    }
+
+    let dest_file = File::create(&opts.dest)
+        .wrap_err_with(|| format!("Failed to create output file '{}'", opts.dest.display()))?;
+    store::Database::from(&merged.schema)
+        .write(&mut BufWriter::new(dest_file))
+        .wrap_err("Failed to write merged database")?;
+
+    let duration = start.elapsed();
+    println!(
+        "Finished in {}.{}s with {} conflicts, keeping our version for them",
+        duration.as_secs(),
+        duration.subsec_millis(),
+        merged.conflicts.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use std::{fs, fs::File, path::PathBuf, time::Instant};
+
+use assembly_data::fdb::{
+    mem::Database,
+    snapshot::{check_snapshot, Snapshot},
+};
+use color_eyre::eyre::WrapErr;
+use mapr::Mmap;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+/// Writes an archived snapshot of an FDB file, or prints the tables of one
+struct Options {
+    /// The FDB file, or the snapshot with `--load`
+    src: PathBuf,
+    /// The snapshot file to write
+    #[structopt(required_unless = "load")]
+    dest: Option<PathBuf>,
+    /// Map an existing snapshot and print its tables
+    #[structopt(long)]
+    load: bool,
+}
+
+fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+    let opts = Options::from_args();
+    let start = Instant::now();
+
+    let file = File::open(&opts.src)
+        .wrap_err_with(|| format!("Failed to open input file '{}'", opts.src.display()))?;
+    let mmap = unsafe { Mmap::map(&file)? };
+    let buffer: &[u8] = &mmap;
+
+    if opts.load {
+        let snapshot = check_snapshot(buffer)?;
+        for table in snapshot.tables() {
+            println!("{:<40} {:>8} rows", table.name(), table.row_count());
+        }
+    } else if let Some(dest) = &opts.dest {
+        let snapshot = Snapshot::from_database(Database::new(buffer))?;
+        let bytes = snapshot.to_bytes()?;
+        fs::write(dest, &bytes)
+            .wrap_err_with(|| format!("Failed to write snapshot '{}'", dest.display()))?;
+        println!("Wrote {} bytes", bytes.len());
+    }
+
+    let duration = start.elapsed();
+    println!(
+        "Finished in {}.{}s",
+        duration.as_secs(),
+        duration.subsec_millis()
+    );
+    Ok(())
+}
@@ -62,6 +62,7 @@ fn main() -> color_eyre::Result<()> {
                 ValueType::Boolean => bool_column_count += 1,
                 ValueType::BigInt => bigint_column_count += 1,
                 ValueType::VarChar => xml_column_count += 1,
+                _ => {}
             }
         }
 
@@ -82,6 +83,7 @@ fn main() -> color_eyre::Result<()> {
                         Value::Boolean(_) => bool_field_count += 1,
                         Value::BigInt(_) => bigint_field_count += 1,
                         Value::VarChar(_) => xml_field_count += 1,
+                        _ => {}
                     }
                 }
             }
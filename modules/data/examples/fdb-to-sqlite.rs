@@ -1,6 +1,11 @@
 use std::{fs::File, path::PathBuf, time::Instant};
 
-use assembly_data::fdb::{mem::Database, sqlite::try_export_db};
+use assembly_data::fdb::{
+    common::IterOrder,
+    float::FloatFormat,
+    mem::Database,
+    sqlite::{try_export_db_with_options, ExportOptions},
+};
 use color_eyre::eyre::WrapErr;
 use mapr::Mmap;
 use rusqlite::Connection;
@@ -13,6 +18,30 @@ struct Options {
     src: PathBuf,
     /// The SQLite destination file
     dest: PathBuf,
+    /// Write VARCHAR columns that contain base64 as decoded BLOBs
+    #[structopt(long)]
+    base64_as_blob: bool,
+    /// Use the narrowest column types that fit the data
+    #[structopt(long)]
+    infer_types: bool,
+    /// Add NOT NULL to columns without NULL values
+    #[structopt(long)]
+    constraints: bool,
+    /// Store the exact bits of FLOAT fields as hex TEXT
+    #[structopt(long)]
+    float_bits: bool,
+    /// Commit after this many rows (0 for a single transaction)
+    #[structopt(long, default_value = "0")]
+    batch_size: usize,
+    /// Use write-ahead logging for the destination
+    #[structopt(long)]
+    wal: bool,
+    /// Insert the rows sorted by primary key
+    #[structopt(long)]
+    sorted: bool,
+    /// Write the tables on this many threads
+    #[structopt(short = "j", long, default_value = "1")]
+    threads: usize,
 }
 
 fn main() -> color_eyre::Result<()> {
@@ -30,7 +59,27 @@ fn main() -> color_eyre::Result<()> {
     let db = Database::new(buffer);
     let mut conn = Connection::open(opts.dest)?;
 
-    try_export_db(&mut conn, db).wrap_err("Failed to export database to sqlite")?;
+    let options = ExportOptions {
+        base64_as_blob: opts.base64_as_blob,
+        infer_types: opts.infer_types,
+        constraints: opts.constraints,
+        floats: if opts.float_bits {
+            FloatFormat::HexBits
+        } else {
+            FloatFormat::Shortest
+        },
+        batch_size: opts.batch_size,
+        wal: opts.wal,
+        order: if opts.sorted {
+            IterOrder::PrimaryKey
+        } else {
+            IterOrder::File
+        },
+        threads: opts.threads,
+        index_strings: true,
+    };
+    try_export_db_with_options(&mut conn, db, &options, &())
+        .wrap_err("Failed to export database to sqlite")?;
 
     let duration = start.elapsed();
     println!(
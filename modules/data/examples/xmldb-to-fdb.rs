@@ -1,3 +1,4 @@
+use assembly_core::hash::sfhash;
 use assembly_data::{
     fdb::{
         common::{self, Latin1String},
@@ -11,7 +12,7 @@ use assembly_data::{
         },
     },
 };
-use color_eyre::eyre::WrapErr;
+use color_eyre::eyre::{eyre, WrapErr};
 use std::{
     collections::HashMap,
     fs::File,
@@ -121,6 +122,13 @@ fn main() -> color_eyre::Result<()> {
                     common::ValueType::Boolean => core::Field::Boolean(&src_value != "0"),
                     common::ValueType::BigInt => core::Field::BigInt(src_value.parse().unwrap()),
                     common::ValueType::VarChar => core::Field::VarChar(src_value),
+                    _ => {
+                        return Err(eyre!(
+                            "Unsupported value type {} for column '{}'",
+                            value_type,
+                            key
+                        ))
+                    }
                 };
 
                 if col_index == 0 {
@@ -132,7 +140,7 @@ fn main() -> color_eyre::Result<()> {
                             pk = Some((*i % 128) as usize);
                         }
                         core::Field::Text(text) => {
-                            pk = Some((hsieh_hash::digest(text.as_bytes()) % 128) as usize);
+                            pk = Some((sfhash(text.as_bytes()) % 128) as usize);
                         }
                         _ => panic!("Can't use {:?} as PK", &dest_value),
                     }
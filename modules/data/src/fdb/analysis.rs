@@ -0,0 +1,240 @@
+//! # Analysis of the space used by a database
+//!
+//! The core database is one of the largest files of the client. This module
+//! reports how many bytes each table and column takes up on disk, split into
+//! the structures of the file format, so that modders can see where the space
+//! goes and which columns would benefit from shorter or shared strings.
+//!
+//! Strings are counted once per address, so strings that are shared between
+//! fields of the same column (as some writers do) only count once.
+
+use std::{collections::HashSet, fmt, mem::size_of};
+
+use assembly_core::buffer::CastError;
+
+use super::{
+    common::ValueType,
+    file::{
+        FDBBucketHeader, FDBColumnHeader, FDBFieldData, FDBHeader, FDBRowHeader,
+        FDBRowHeaderListEntry, FDBTableDataHeader, FDBTableDefHeader, FDBTableHeader,
+    },
+    mem::{Database, Field, Table},
+};
+
+/// The space used by the fields of one column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnUsage {
+    /// The name of the column
+    pub name: String,
+    /// The declared type of the column
+    pub value_type: ValueType,
+    /// The number of fields that are `NULL`
+    pub null_count: usize,
+    /// The bytes used by the field data structures
+    pub field_bytes: usize,
+    /// The number of distinct strings (`TEXT` and `VARCHAR`)
+    pub string_count: usize,
+    /// The bytes used by the distinct strings, including terminator and padding
+    pub string_bytes: usize,
+    /// The bytes used by 64-bit integers
+    pub i64_bytes: usize,
+}
+
+impl ColumnUsage {
+    /// The total number of bytes used by the column
+    pub fn total(&self) -> usize {
+        self.field_bytes + self.string_bytes + self.i64_bytes
+    }
+}
+
+/// The space used by one table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableUsage {
+    /// The name of the table
+    pub name: String,
+    /// The number of rows
+    pub row_count: usize,
+    /// The bytes used by the table definition, i.e. the name and column headers
+    pub def_bytes: usize,
+    /// The bytes used by the data header and the bucket array
+    pub bucket_bytes: usize,
+    /// The bytes used by the row headers and row list entries
+    pub row_header_bytes: usize,
+    /// The space used by each column
+    pub columns: Vec<ColumnUsage>,
+}
+
+impl TableUsage {
+    /// The total number of bytes used by the table
+    pub fn total(&self) -> usize {
+        self.def_bytes
+            + self.bucket_bytes
+            + self.row_header_bytes
+            + self.columns.iter().map(ColumnUsage::total).sum::<usize>()
+    }
+}
+
+/// The space used by a database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseUsage {
+    /// The size of the file
+    pub file_size: usize,
+    /// The bytes used by the file header and the table header array
+    pub header_bytes: usize,
+    /// The space used by each table
+    pub tables: Vec<TableUsage>,
+}
+
+impl DatabaseUsage {
+    /// The total number of bytes that are accounted for
+    pub fn total(&self) -> usize {
+        self.header_bytes + self.tables.iter().map(TableUsage::total).sum::<usize>()
+    }
+
+    /// The number of bytes in the file that are not accounted for
+    ///
+    /// This includes padding and data that is not referenced from any table.
+    pub fn unaccounted(&self) -> usize {
+        self.file_size.saturating_sub(self.total())
+    }
+}
+
+fn str_bytes(len: usize) -> usize {
+    (len / 4 + 1) * 4
+}
+
+fn analyze_table(table: &Table<'_>) -> TableUsage {
+    let column_count = table.column_count();
+    let name_len = table.name_raw().len();
+    let def_bytes = size_of::<FDBTableDefHeader>()
+        + str_bytes(name_len)
+        + table
+            .column_iter()
+            .map(|c| size_of::<FDBColumnHeader>() + str_bytes(c.name_raw().len()))
+            .sum::<usize>();
+    let bucket_bytes =
+        size_of::<FDBTableDataHeader>() + size_of::<FDBBucketHeader>() * table.bucket_count();
+
+    let mut columns: Vec<_> = table
+        .column_iter()
+        .map(|c| ColumnUsage {
+            name: c.name().into_owned(),
+            value_type: c.value_type(),
+            null_count: 0,
+            field_bytes: 0,
+            string_count: 0,
+            string_bytes: 0,
+            i64_bytes: 0,
+        })
+        .collect();
+    let mut strings = vec![HashSet::new(); column_count];
+
+    let mut row_count = 0;
+    for row in table.row_iter() {
+        row_count += 1;
+        for (index, field) in row.field_iter().enumerate().take(column_count) {
+            let usage = &mut columns[index];
+            usage.field_bytes += size_of::<FDBFieldData>();
+            match field {
+                Field::Nothing => usage.null_count += 1,
+                Field::Text(s) | Field::VarChar(s) => {
+                    if !strings[index].insert(s.as_bytes().as_ptr()) {
+                        continue;
+                    }
+                    usage.string_count += 1;
+                    usage.string_bytes += str_bytes(s.len());
+                }
+                Field::BigInt(_) => usage.i64_bytes += size_of::<i64>(),
+                _ => {}
+            }
+        }
+    }
+
+    TableUsage {
+        name: table.name().into_owned(),
+        row_count,
+        def_bytes,
+        bucket_bytes,
+        row_header_bytes: row_count
+            * (size_of::<FDBRowHeaderListEntry>() + size_of::<FDBRowHeader>()),
+        columns,
+    }
+}
+
+/// Compute the space used by every table and column of a database
+pub fn analyze(db: Database<'_>) -> Result<DatabaseUsage, CastError> {
+    let tables = db.tables()?;
+    let header_bytes = size_of::<FDBHeader>() + size_of::<FDBTableHeader>() * tables.len();
+    let tables = tables
+        .iter()
+        .map(|table| table.map(|t| analyze_table(&t)))
+        .collect::<Result<_, _>>()?;
+    Ok(DatabaseUsage {
+        file_size: db.as_bytes().len(),
+        header_bytes,
+        tables,
+    })
+}
+
+impl fmt::Display for DatabaseUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<40} {:>8} {:>12}", "TABLE / COLUMN", "ROWS", "BYTES")?;
+        for table in &self.tables {
+            writeln!(
+                f,
+                "{:<40} {:>8} {:>12}",
+                table.name,
+                table.row_count,
+                table.total()
+            )?;
+            for column in &table.columns {
+                writeln!(f, "  {:<38} {:>8} {:>12}", column.name, "", column.total())?;
+            }
+        }
+        writeln!(f, "{:<40} {:>8} {:>12}", "(headers)", "", self.header_bytes)?;
+        writeln!(
+            f,
+            "{:<40} {:>8} {:>12}",
+            "(unaccounted)",
+            "",
+            self.unaccounted()
+        )?;
+        write!(f, "{:<40} {:>8} {:>12}", "TOTAL", "", self.file_size)
+    }
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{core, testing::SampleDatabase};
+
+    #[test]
+    fn test_analyze() {
+        let columns = [
+            ("id", ValueType::Integer),
+            ("name", ValueType::Text),
+            ("big", ValueType::BigInt),
+        ];
+        let name = core::Field::Text(String::from("Hello"));
+        let buf = SampleDatabase::new()
+            .table("Table", &columns)
+            .row(vec![core::Field::Integer(0), name, core::Field::BigInt(5)])
+            .row(vec![
+                core::Field::Integer(1),
+                core::Field::Nothing,
+                core::Field::Nothing,
+            ])
+            .build();
+
+        let usage = analyze(Database::new(&buf)).unwrap();
+        assert_eq!(usage.tables.len(), 1);
+        let table = &usage.tables[0];
+        assert_eq!(table.row_count, 2);
+        assert_eq!(table.columns[1].string_count, 1);
+        assert_eq!(table.columns[1].string_bytes, 8);
+        assert_eq!(table.columns[1].null_count, 1);
+        assert_eq!(table.columns[2].i64_bytes, 8);
+        assert_eq!(usage.total(), buf.len());
+        assert_eq!(usage.unaccounted(), 0);
+    }
+}
@@ -0,0 +1,173 @@
+//! # Base64 payloads in `VARCHAR` fields
+//!
+//! Some `VARCHAR` columns don't contain text, but binary data encoded as
+//! standard base64 (see the `encoding` module for finding them). This module
+//! implements the encoding, with padding, as used by
+//! [`Value::decode_varchar_base64`] and [`Value::encode_varchar_base64`].
+//!
+//! Exporters write these payloads as bytes where possible, e.g. as a `BLOB` in
+//! SQLite or as a hex string in JSON, so that they can be converted back to
+//! the same base64 string.
+//!
+//! ```
+//! use assembly_data::fdb::base64;
+//!
+//! assert_eq!(base64::encode(b"LEGO"), "TEVHTw==");
+//! assert_eq!(base64::decode(b"TEVHTw==").unwrap(), b"LEGO");
+//! assert_eq!(base64::to_hex(b"LEGO"), "4c45474f");
+//! ```
+//!
+//! [`Value::decode_varchar_base64`]: super::common::Value::decode_varchar_base64
+//! [`Value::encode_varchar_base64`]: super::common::Value::encode_varchar_base64
+
+use assembly_core::displaydoc::Display;
+use thiserror::Error;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Errors from decoding base64 or hex
+#[derive(Debug, Clone, PartialEq, Eq, Error, Display)]
+#[non_exhaustive]
+pub enum Base64Error {
+    /// The field is not a `VARCHAR`
+    NotVarChar,
+    /// The length {0} is not valid
+    InvalidLength(usize),
+    /// Invalid byte {byte:#04x} at position {pos}
+    InvalidByte {
+        /// The position of the byte
+        pos: usize,
+        /// The byte
+        byte: u8,
+    },
+    /// The last byte before the padding at position {0} has unused bits set
+    InvalidPadding(usize),
+}
+
+/// Encode bytes as base64, with padding
+pub fn encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, b[0], b[1], b[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(char::from(ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3F]));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn sextet(pos: usize, byte: u8) -> Result<u32, Base64Error> {
+    let value = match byte {
+        b'A'..=b'Z' => byte - b'A',
+        b'a'..=b'z' => byte - b'a' + 26,
+        b'0'..=b'9' => byte - b'0' + 52,
+        b'+' => 62,
+        b'/' => 63,
+        _ => return Err(Base64Error::InvalidByte { pos, byte }),
+    };
+    Ok(u32::from(value))
+}
+
+/// Decode base64 with padding
+///
+/// This is strict: the unused bits of the last character before the padding
+/// must be zero, so that every payload has exactly one encoding.
+pub fn decode(text: &[u8]) -> Result<Vec<u8>, Base64Error> {
+    if !text.len().is_multiple_of(4) {
+        return Err(Base64Error::InvalidLength(text.len()));
+    }
+    let padding = text.iter().rev().take_while(|b| **b == b'=').count();
+    if padding > 2 {
+        return Err(Base64Error::InvalidLength(text.len()));
+    }
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    for (index, chunk) in text.chunks(4).enumerate() {
+        let last = (index + 1) * 4 == text.len();
+        let data = if last { 4 - padding } else { 4 };
+        let mut n = 0;
+        for (i, &byte) in chunk[..data].iter().enumerate() {
+            n |= sextet(index * 4 + i, byte)? << (18 - 6 * i);
+        }
+        if last && n & ((1 << (8 * padding)) - 1) != 0 {
+            return Err(Base64Error::InvalidPadding(index * 4 + data - 1));
+        }
+        out.extend_from_slice(&n.to_be_bytes()[1..data]);
+    }
+    Ok(out)
+}
+
+/// Check whether a string looks like base64 encoded data
+///
+/// This requires at least 16 characters of the standard alphabet, a length
+/// that is a multiple of 4 and at most two padding characters at the end.
+/// Words like `AbcDefGhIjklMnop` match too, so this only makes sense for
+/// whole columns.
+pub fn looks_like_base64(bytes: &[u8]) -> bool {
+    if bytes.len() < 16 || !bytes.len().is_multiple_of(4) {
+        return false;
+    }
+    let data = bytes
+        .strip_suffix(b"==")
+        .or_else(|| bytes.strip_suffix(b"="))
+        .unwrap_or(bytes);
+    data.iter()
+        .all(|b| b.is_ascii_alphanumeric() || *b == b'+' || *b == b'/')
+}
+
+/// Encode bytes as lowercase hex
+pub fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decode hex, in upper or lower case
+pub fn from_hex(text: &[u8]) -> Result<Vec<u8>, Base64Error> {
+    if !text.len().is_multiple_of(2) {
+        return Err(Base64Error::InvalidLength(text.len()));
+    }
+    let digit = |pos: usize| {
+        let byte = text[pos];
+        char::from(byte)
+            .to_digit(16)
+            .map(|d| d as u8)
+            .ok_or(Base64Error::InvalidByte { pos, byte })
+    };
+    (0..text.len())
+        .step_by(2)
+        .map(|pos| Ok(digit(pos)? << 4 | digit(pos + 1)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        for len in 0..8usize {
+            let data: Vec<u8> = (0..len).map(|i| (i * 37 + 200) as u8).collect();
+            let text = encode(&data);
+            assert_eq!(text.len(), len.div_ceil(3) * 4);
+            assert_eq!(decode(text.as_bytes()).unwrap(), data);
+            assert_eq!(from_hex(to_hex(&data).as_bytes()).unwrap(), data);
+        }
+        assert_eq!(encode(b"Hello, World!"), "SGVsbG8sIFdvcmxkIQ==");
+        assert_eq!(decode(b"SGVsbG8"), Err(Base64Error::InvalidLength(7)));
+        assert_eq!(
+            decode(b"SGV!bG8="),
+            Err(Base64Error::InvalidByte { pos: 3, byte: b'!' })
+        );
+        assert_eq!(decode(b"S==="), Err(Base64Error::InvalidLength(4)));
+        assert_eq!(decode(b"TEVHTx=="), Err(Base64Error::InvalidPadding(5)));
+        assert_eq!(decode(b"TEVHTx9="), Err(Base64Error::InvalidPadding(6)));
+        assert_eq!(decode(b"TEVHTw==").unwrap(), b"LEGO");
+    }
+}
@@ -0,0 +1,467 @@
+//! # A persistent cache of secondary indexes (`*.fdbx`)
+//!
+//! Looking up a table by name or rows by a column other than the primary key
+//! requires a scan of the database. For tools that are run over and over on
+//! the same file, this module provides a companion cache file that stores
+//!
+//! - the names of all tables and columns, and
+//! - for every integer column that looks like a foreign key (see [`is_fk_column`]),
+//!   the position of every row by value.
+//!
+//! The cache is keyed by a hash of the source file, so it is rebuilt when the
+//! database changes. Use [`Database::with_cache`] to load or build it transparently.
+//!
+//! ## File format
+//!
+//! All integers are little-endian. Strings are stored as a `u32` length
+//! followed by the bytes.
+//!
+//! ```text
+//! magic: "FDBX", version: u32, source_hash: u64, source_len: u64
+//! table_count: u32, [name: str, column_count: u32, [column: str]]
+//! index_count: u32, [table: u32, column: u32, key_count: u32,
+//!                    [key: i32, row_count: u32, [bucket: u32, index: u32]]]
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use assembly_core::{buffer::CastError, displaydoc::Display};
+use thiserror::Error;
+
+use super::{
+    common::ValueType,
+    mem::{Database, Row, Table},
+};
+
+/// The magic bytes at the start of a cache file
+pub const MAGIC: [u8; 4] = *b"FDBX";
+/// The version of the cache format
+///
+/// Caches with a different version are rebuilt.
+pub const VERSION: u32 = 1;
+
+/// Errors when loading or building a cache
+#[derive(Debug, Error, Display)]
+#[non_exhaustive]
+pub enum CacheError {
+    /// Failed to read the database: {0}
+    Cast(#[from] CastError),
+    /// Failed to read or write the cache file: {0}
+    Io(#[from] io::Error),
+}
+
+/// Calculate the hash of a source file, used to detect stale caches
+///
+/// This is the 64-bit FNV-1a hash, which is stable across platforms.
+pub fn source_hash(buf: &[u8]) -> u64 {
+    buf.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Check whether a column name looks like a foreign key
+///
+/// This matches names ending in `ID`, `_id` or `LOT`, such as `itemID` or
+/// `component_id`.
+pub fn is_fk_column(name: &str) -> bool {
+    name.ends_with("ID") || name.ends_with("_id") || name.ends_with("LOT")
+}
+
+/// Get the default path of the cache for a database file
+pub fn cache_path<P: AsRef<Path>>(fdb_path: P) -> PathBuf {
+    fdb_path.as_ref().with_extension("fdbx")
+}
+
+/// The names of a table and its columns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableInfo {
+    /// The name of the table
+    pub name: String,
+    /// The names of the columns
+    pub columns: Vec<String>,
+}
+
+/// An index of the rows of a table by the value of one column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnIndex {
+    /// The position of the table in the database
+    pub table: usize,
+    /// The position of the column in the table
+    pub column: usize,
+    /// The `(bucket, index)` of all rows, by value
+    pub entries: BTreeMap<i32, Vec<(u32, u32)>>,
+}
+
+/// The secondary indexes for one database file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cache {
+    source_hash: u64,
+    source_len: u64,
+    tables: Vec<TableInfo>,
+    indexes: Vec<ColumnIndex>,
+}
+
+impl Cache {
+    /// Build the cache for a database, indexing all foreign key columns
+    pub fn build(db: Database<'_>) -> Result<Self, CastError> {
+        Self::build_with(db, |_, column| is_fk_column(column))
+    }
+
+    /// Build the cache for a database, indexing the integer columns for which
+    /// `filter(table, column)` returns true
+    ///
+    /// The primary key column is never indexed, as the database is already
+    /// hashed by it.
+    pub fn build_with<F>(db: Database<'_>, filter: F) -> Result<Self, CastError>
+    where
+        F: Fn(&str, &str) -> bool,
+    {
+        let buf = db.as_bytes();
+        let mut tables = Vec::new();
+        let mut indexes = Vec::new();
+        for (t, table) in db.tables()?.iter().enumerate() {
+            let table = table?;
+            let name = table.name().into_owned();
+            let mut columns = Vec::with_capacity(table.column_count());
+            let mut indexed = Vec::new();
+            for (c, column) in table.column_iter().enumerate() {
+                let column_name = column.name().into_owned();
+                if c > 0 && column.value_type() == ValueType::Integer && filter(&name, &column_name)
+                {
+                    indexed.push(c);
+                }
+                columns.push(column_name);
+            }
+            for column in indexed {
+                indexes.push(index_column(&table, t, column));
+            }
+            tables.push(TableInfo { name, columns });
+        }
+        Ok(Self {
+            source_hash: source_hash(buf),
+            source_len: buf.len() as u64,
+            tables,
+            indexes,
+        })
+    }
+
+    /// Check whether this cache was built for the given database file
+    pub fn is_valid_for(&self, buf: &[u8]) -> bool {
+        self.source_len == buf.len() as u64 && self.source_hash == source_hash(buf)
+    }
+
+    /// Get the names of all tables and columns
+    pub fn tables(&self) -> &[TableInfo] {
+        &self.tables
+    }
+
+    /// Get all column indexes
+    pub fn indexes(&self) -> &[ColumnIndex] {
+        &self.indexes
+    }
+
+    /// Get the position of a table by name
+    pub fn table_index(&self, name: &str) -> Option<usize> {
+        self.tables.iter().position(|t| t.name == name)
+    }
+
+    /// Get the position of a column by table and column name
+    pub fn column_index(&self, table: &str, column: &str) -> Option<usize> {
+        let table = &self.tables[self.table_index(table)?];
+        table.columns.iter().position(|c| c == column)
+    }
+
+    /// Get the index for a column, if there is one
+    pub fn index(&self, table: &str, column: &str) -> Option<&ColumnIndex> {
+        let t = self.table_index(table)?;
+        let c = self.column_index(table, column)?;
+        self.indexes.iter().find(|i| i.table == t && i.column == c)
+    }
+
+    /// Read a cache from a file
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if the magic bytes or the version
+    /// don't match.
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid_data("not a cache file"));
+        }
+        if read_u32(reader)? != VERSION {
+            return Err(invalid_data("unsupported cache version"));
+        }
+        let source_hash = read_u64(reader)?;
+        let source_len = read_u64(reader)?;
+
+        let table_count = read_u32(reader)?;
+        let mut tables = Vec::new();
+        for _ in 0..table_count {
+            let name = read_str(reader)?;
+            let column_count = read_u32(reader)?;
+            let columns = (0..column_count)
+                .map(|_| read_str(reader))
+                .collect::<io::Result<_>>()?;
+            tables.push(TableInfo { name, columns });
+        }
+
+        let index_count = read_u32(reader)?;
+        let mut indexes = Vec::new();
+        for _ in 0..index_count {
+            let table = read_u32(reader)? as usize;
+            let column = read_u32(reader)? as usize;
+            let mut entries = BTreeMap::new();
+            for _ in 0..read_u32(reader)? {
+                let key = read_u32(reader)? as i32;
+                let row_count = read_u32(reader)?;
+                let rows = (0..row_count)
+                    .map(|_| Ok((read_u32(reader)?, read_u32(reader)?)))
+                    .collect::<io::Result<_>>()?;
+                entries.insert(key, rows);
+            }
+            indexes.push(ColumnIndex {
+                table,
+                column,
+                entries,
+            });
+        }
+
+        Ok(Self {
+            source_hash,
+            source_len,
+            tables,
+            indexes,
+        })
+    }
+
+    /// Write the cache to a file
+    pub fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(&MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&self.source_hash.to_le_bytes())?;
+        out.write_all(&self.source_len.to_le_bytes())?;
+
+        write_len(out, self.tables.len())?;
+        for table in &self.tables {
+            write_str(out, &table.name)?;
+            write_len(out, table.columns.len())?;
+            for column in &table.columns {
+                write_str(out, column)?;
+            }
+        }
+
+        write_len(out, self.indexes.len())?;
+        for index in &self.indexes {
+            write_len(out, index.table)?;
+            write_len(out, index.column)?;
+            write_len(out, index.entries.len())?;
+            for (key, rows) in &index.entries {
+                out.write_all(&key.to_le_bytes())?;
+                write_len(out, rows.len())?;
+                for (bucket, row) in rows {
+                    out.write_all(&bucket.to_le_bytes())?;
+                    out.write_all(&row.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn index_column(table: &Table<'_>, t: usize, column: usize) -> ColumnIndex {
+    let mut entries: BTreeMap<i32, Vec<(u32, u32)>> = BTreeMap::new();
+    for (b, bucket) in table.bucket_iter().enumerate() {
+        for (r, row) in bucket.row_iter().enumerate() {
+            if let Some(key) = row.field_at(column).and_then(|f| f.as_integer()) {
+                entries.entry(key).or_default().push((b as u32, r as u32));
+            }
+        }
+    }
+    ColumnIndex {
+        table: t,
+        column,
+        entries,
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_str<R: Read>(reader: &mut R) -> io::Result<String> {
+    let len = read_u32(reader)?;
+    // Don't trust the length for the allocation, it may be larger than the file
+    let mut bytes = Vec::new();
+    reader.take(u64::from(len)).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes).map_err(|_| invalid_data("invalid string"))
+}
+
+fn write_len<W: Write>(out: &mut W, len: usize) -> io::Result<()> {
+    out.write_all(&(len as u32).to_le_bytes())
+}
+
+fn write_str<W: Write>(out: &mut W, text: &str) -> io::Result<()> {
+    write_len(out, text.len())?;
+    out.write_all(text.as_bytes())
+}
+
+/// A database together with its cache
+pub struct CachedDatabase<'a> {
+    db: Database<'a>,
+    cache: Cache,
+}
+
+impl<'a> CachedDatabase<'a> {
+    /// Get the database
+    pub fn database(&self) -> Database<'a> {
+        self.db
+    }
+
+    /// Get the cache
+    pub fn cache(&self) -> &Cache {
+        &self.cache
+    }
+
+    /// Get a table by name, using the cached table list
+    pub fn table(&self, name: &str) -> Option<Result<Table<'a>, CastError>> {
+        let index = self.cache.table_index(name)?;
+        match self.db.tables() {
+            Ok(tables) => tables.get(index),
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    /// Get all rows of `table` where the integer `column` has the value `key`
+    ///
+    /// Returns `None` if there is no index for that column.
+    pub fn lookup(
+        &self,
+        table: &str,
+        column: &str,
+        key: i32,
+    ) -> Option<Result<Vec<Row<'a>>, CastError>> {
+        let index = self.cache.index(table, column)?;
+        let table = match self.table(table)? {
+            Ok(table) => table,
+            Err(e) => return Some(Err(e)),
+        };
+        let rows = index.entries.get(&key).map(Vec::as_slice).unwrap_or(&[]);
+        let rows = rows
+            .iter()
+            .filter_map(|(bucket, row)| {
+                let bucket = table.bucket_at(*bucket as usize)?;
+                bucket.row_iter().nth(*row as usize)
+            })
+            .collect();
+        Some(Ok(rows))
+    }
+}
+
+impl<'a> Database<'a> {
+    /// Load the cache at `path` or build (and store) it if it is missing or stale
+    ///
+    /// Fails if the database can't be read, or if a new cache can't be stored
+    /// at `path`. An unreadable or stale cache file is rebuilt.
+    ///
+    /// This is only available with the `fdb-mem` feature.
+    pub fn with_cache<P: AsRef<Path>>(self, path: P) -> Result<CachedDatabase<'a>, CacheError> {
+        let path = path.as_ref();
+        let buf = self.as_bytes();
+        let cached = File::open(path)
+            .and_then(|file| Cache::read(&mut BufReader::new(file)))
+            .ok()
+            .filter(|cache| cache.is_valid_for(buf));
+        let cache = match cached {
+            Some(cache) => cache,
+            None => {
+                let cache = Cache::build(self)?;
+                store_cache(&cache, path)?;
+                cache
+            }
+        };
+        Ok(CachedDatabase { db: self, cache })
+    }
+}
+
+fn store_cache(cache: &Cache, path: &Path) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    cache.write(&mut out)?;
+    out.flush()
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{core::Field, testing::SampleDatabase};
+
+    #[test]
+    fn test_cache() {
+        let columns = [("id", ValueType::Integer), ("itemID", ValueType::Integer)];
+        let db = SampleDatabase::new().table("Rewards", &columns).buckets(2);
+        let db = [(1, 10), (2, 20), (3, 10)]
+            .iter()
+            .fold(db, |db, &(id, item)| {
+                db.row(vec![Field::Integer(id), Field::Integer(item)])
+            });
+        let mut buf = db.build();
+
+        let path = std::env::temp_dir().join(format!("assembly-{}.fdbx", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = Database::new(&buf);
+        let cached = db.with_cache(&path).unwrap();
+        assert!(path.exists());
+        let rows = cached.lookup("Rewards", "itemID", 10).unwrap().unwrap();
+        let mut ids: Vec<_> = rows
+            .iter()
+            .filter_map(|r| r.field_at(0)?.as_integer())
+            .collect();
+        ids.sort_unstable();
+        assert_eq!(ids, vec![1, 3]);
+        assert!(cached.lookup("Rewards", "id", 1).is_none());
+
+        let loaded = Cache::read(&mut BufReader::new(File::open(&path).unwrap())).unwrap();
+        assert_eq!(&loaded, cached.cache());
+
+        // A directory can't be written as a file
+        let dir = std::env::temp_dir();
+        let db = Database::new(&buf);
+        assert!(matches!(db.with_cache(&dir), Err(CacheError::Io(_))));
+
+        buf[8] ^= 1;
+        assert!(!loaded.is_valid_for(&buf));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_huge_length() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&MAGIC);
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend_from_slice(&[0; 16]);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        data.extend_from_slice(b"short");
+        let err = Cache::read(&mut data.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
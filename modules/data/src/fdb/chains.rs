@@ -0,0 +1,302 @@
+//! # The bucket chains of a table
+//!
+//! The rows of a table are spread over its buckets by the hash of their
+//! primary key, and each bucket links its rows in a list. [`table_chains`]
+//! reads these lists with the addresses of every entry and row, so that the
+//! hash layout of a file can be inspected: how long the chains are, and
+//! whether a row is in the bucket that its key hashes to (which a custom
+//! writer may get wrong).
+//!
+//! The result can be rendered as a [DOT] graph with [`TableChains::write_dot`],
+//! or as JSON with [`TableChains::write_json`] and the `serde-derives` feature.
+//!
+//! ```
+//! use assembly_data::fdb::{
+//!     chains::table_chains,
+//!     common::{Latin1String, ValueType},
+//!     core::Field,
+//!     store,
+//! };
+//!
+//! let mut table = store::Table::new(2);
+//! table.push_column(Latin1String::encode("id"), ValueType::Integer);
+//! for id in 0..5 {
+//!     table.push_row(id % 2, &[Field::Integer(id as i32)]);
+//! }
+//! let mut db = store::Database::new();
+//! db.push_table(Latin1String::encode("Objects"), table);
+//! let mut buf = Vec::new();
+//! db.write(&mut buf).unwrap();
+//!
+//! let chains = table_chains(&buf, "Objects").unwrap().unwrap();
+//! assert_eq!(chains.row_count(), 5);
+//! assert_eq!(chains.longest(), 3);
+//! assert_eq!(chains.misplaced().count(), 0);
+//! ```
+//!
+//! [DOT]: https://graphviz.org/doc/info/lang.html
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    io::{self, Write},
+};
+
+use assembly_core::{
+    buffer::CastError,
+    hash::{fdb_bucket, fdb_int_hash, fdb_text_hash},
+};
+
+use super::{common::ValueType, mem::raw};
+
+/// One entry of the list of rows of a bucket
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct ChainLink {
+    /// The address of the list entry
+    pub entry_addr: u32,
+    /// The address of the row header
+    pub row_addr: u32,
+    /// The primary key of the row, if it is an integer or a string
+    pub key: Option<String>,
+    /// The bucket that the key hashes to, if it is an integer or a string
+    pub expected_bucket: Option<usize>,
+}
+
+/// The list of rows of one bucket
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct Chain {
+    /// The index of the bucket
+    pub bucket: usize,
+    /// The address of the bucket header
+    pub header_addr: u32,
+    /// The entries, in the order of the list
+    pub links: Vec<ChainLink>,
+}
+
+impl Chain {
+    /// The entries whose key hashes to another bucket
+    pub fn misplaced(&self) -> impl Iterator<Item = &ChainLink> {
+        let bucket = self.bucket;
+        self.links
+            .iter()
+            .filter(move |l| l.expected_bucket.is_some_and(|b| b != bucket))
+    }
+}
+
+/// The bucket chains of a table, see the [module documentation](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct TableChains {
+    /// The name of the table
+    pub table: String,
+    /// One chain per bucket, in the order of the buckets
+    pub chains: Vec<Chain>,
+}
+
+/// Read the bucket chains of the table called `name`
+///
+/// Returns `None` if there is no such table.
+pub fn table_chains(buf: &[u8], name: &str) -> Result<Option<TableChains>, CastError> {
+    let header = raw::header(buf)?;
+    for table in raw::table_headers(buf, header.value)? {
+        let def = raw::table_def_header(buf, table.value)?;
+        let table_name = raw::string(buf, def.value.table_name_addr)?;
+        if table_name.value.decode() != name {
+            continue;
+        }
+        let data = raw::table_data_header(buf, table.value)?;
+        let buckets: Vec<_> = raw::bucket_headers(buf, data.value)?.collect();
+        let count = buckets.len();
+        let mut chains = Vec::with_capacity(count);
+        for (index, bucket) in buckets.into_iter().enumerate() {
+            let entries = raw::row_list(buf, bucket.value.row_header_list_head_addr)?;
+            let links = entries
+                .into_iter()
+                .map(|entry| link(buf, entry, count))
+                .collect::<Result<_, _>>()?;
+            chains.push(Chain {
+                bucket: index,
+                header_addr: bucket.addr,
+                links,
+            });
+        }
+        return Ok(Some(TableChains {
+            table: name.to_owned(),
+            chains,
+        }));
+    }
+    Ok(None)
+}
+
+fn link(
+    buf: &[u8],
+    entry: raw::At<crate::fdb::file::FDBRowHeaderListEntry>,
+    bucket_count: usize,
+) -> Result<ChainLink, CastError> {
+    let row = raw::row_header(buf, entry.value.row_header_addr)?;
+    let fields = raw::fields(buf, row.value)?;
+    let (key, hash) = match fields.first() {
+        Some(field) => {
+            let value = field.value.value;
+            match ValueType::try_from(field.value.data_type) {
+                Ok(ValueType::Integer) => {
+                    let key = i32::from_le_bytes(value);
+                    (Some(key.to_string()), Some(fdb_int_hash(key)))
+                }
+                Ok(ValueType::Text) | Ok(ValueType::VarChar) => {
+                    let text = raw::string(buf, u32::from_le_bytes(value))?.value;
+                    (
+                        Some(text.decode().into_owned()),
+                        Some(fdb_text_hash(text.as_bytes())),
+                    )
+                }
+                _ => (None, None),
+            }
+        }
+        None => (None, None),
+    };
+    Ok(ChainLink {
+        entry_addr: entry.addr,
+        row_addr: row.addr,
+        key,
+        expected_bucket: hash.map(|h| fdb_bucket(h, bucket_count)),
+    })
+}
+
+impl TableChains {
+    /// The number of rows in all chains
+    pub fn row_count(&self) -> usize {
+        self.chains.iter().map(|c| c.links.len()).sum()
+    }
+
+    /// The length of the longest chain
+    pub fn longest(&self) -> usize {
+        self.chains.iter().map(|c| c.links.len()).max().unwrap_or(0)
+    }
+
+    /// The number of buckets for each chain length
+    pub fn lengths(&self) -> BTreeMap<usize, usize> {
+        let mut lengths = BTreeMap::new();
+        for chain in &self.chains {
+            *lengths.entry(chain.links.len()).or_insert(0) += 1;
+        }
+        lengths
+    }
+
+    /// The entries whose key hashes to another bucket than the one they are in
+    pub fn misplaced(&self) -> impl Iterator<Item = &ChainLink> {
+        self.chains.iter().flat_map(Chain::misplaced)
+    }
+
+    /// Render the chains as a DOT graph
+    ///
+    /// Every non-empty bucket is a node that links to the entries of its
+    /// chain, which are labelled with the key and the addresses. Entries that
+    /// are in the wrong bucket are red.
+    pub fn write_dot<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "digraph \"{}\" {{", dot_escape(&self.table))?;
+        writeln!(out, "    rankdir=LR;")?;
+        writeln!(out, "    node [shape=box];")?;
+        for chain in self.chains.iter().filter(|c| !c.links.is_empty()) {
+            let (bucket, len) = (chain.bucket, chain.links.len());
+            writeln!(
+                out,
+                "    b{} [label=\"bucket {} ({})\\n{:#x}\", shape=folder];",
+                bucket, bucket, len, chain.header_addr
+            )?;
+            let mut prev = format!("b{}", bucket);
+            for link in &chain.links {
+                let node = format!("e{:x}", link.entry_addr);
+                let key = dot_escape(link.key.as_deref().unwrap_or("?"));
+                let color = match link.expected_bucket {
+                    Some(b) if b != bucket => ", color=red",
+                    _ => "",
+                };
+                writeln!(
+                    out,
+                    "    {} [label=\"{}\\nentry {:#x}\\nrow {:#x}\"{}];",
+                    node, key, link.entry_addr, link.row_addr, color
+                )?;
+                writeln!(out, "    {} -> {};", prev, node)?;
+                prev = node;
+            }
+        }
+        writeln!(out, "}}")
+    }
+
+    /// Write the chains as a JSON document
+    #[cfg(feature = "serde-derives")]
+    pub fn write_json<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *out, self)?;
+        writeln!(out)
+    }
+}
+
+/// Escape `text` for a quoted DOT string
+///
+/// Quotes and backslashes are escaped, and control characters are written as
+/// Rust escapes (e.g. `\u{1}`), so that they show up in the label instead of
+/// being interpreted by Graphviz.
+fn dot_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let rust = c.escape_default().to_string();
+                escaped.push_str(&rust.replace('\\', "\\\\"));
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{core::Field, testing::SampleDatabase};
+
+    #[test]
+    fn test_misplaced_row() {
+        let buf = SampleDatabase::new()
+            .table("Ids", &[("id", ValueType::Integer)])
+            .buckets(4)
+            .row(vec![Field::Integer(1)])
+            .row(vec![Field::Integer(5)])
+            .row_in_bucket(1, vec![Field::Integer(6)])
+            .build();
+
+        let chains = table_chains(&buf, "Ids").unwrap().unwrap();
+        assert_eq!(chains.longest(), 3);
+        let lengths: Vec<_> = chains.lengths().into_iter().collect();
+        assert_eq!(lengths, [(0, 3), (3, 1)]);
+        let misplaced: Vec<_> = chains.misplaced().map(|l| l.key.as_deref()).collect();
+        assert_eq!(misplaced, [Some("6")]);
+
+        let mut dot = Vec::new();
+        chains.write_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.contains("b1 [label=\"bucket 1 (3)"));
+        assert_eq!(dot.matches("color=red").count(), 1);
+        assert!(table_chains(&buf, "Other").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_dot_escape() {
+        let buf = SampleDatabase::new()
+            .table("Names", &[("name", ValueType::Text)])
+            .row(vec![Field::Text(String::from("a\"b\\c\nd\u{1}"))])
+            .build();
+        let chains = table_chains(&buf, "Names").unwrap().unwrap();
+        let mut dot = Vec::new();
+        chains.write_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        let label = r#"[label="a\"b\\c\\nd\\u{1}\nentry "#;
+        assert!(dot.contains(label), "{}", dot);
+        assert_eq!(dot_escape("\"\\\r"), r#"\"\\\\r"#);
+    }
+}
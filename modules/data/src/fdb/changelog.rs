@@ -0,0 +1,210 @@
+//! # Release notes from database changes
+//!
+//! A [`Changelog`] turns the [`Change`]s found by [`diff`][super::merge::diff]
+//! into lines like `Item Brick (1727): price 100 → 150`. Rows are named by
+//! their text in the locale, which is looked up by the key of the row (see
+//! [`LocaleKey`]), e.g. `Objects_1727_name`. The locale can be loaded with
+//! [`read_locale`][crate::xml::localization::read_locale].
+//!
+//! ```
+//! use assembly_data::fdb::{
+//!     changelog::Changelog,
+//!     csv::CsvDialect,
+//!     merge::{diff, MergeOptions},
+//!     source::{import_from_source, CsvSource},
+//! };
+//!
+//! let load = |text: &str| {
+//!     let source = CsvSource::new(CsvDialect::default()).with_table("ItemComponent", text);
+//!     import_from_source(source, &()).unwrap()
+//! };
+//! let old = load("id,baseValue\n1727,100\n");
+//! let new = load("id,baseValue\n1727,150\n");
+//! let changes = diff(&old, &new, &MergeOptions::new()).unwrap();
+//!
+//! let locale = vec![("ItemComponent_1727_name".to_owned(), "Brick".to_owned())];
+//! let locale = locale.into_iter().collect();
+//! let changelog = Changelog::new()
+//!     .with_locale(&locale)
+//!     .with_label("ItemComponent", "Item")
+//!     .with_column_label("ItemComponent", "baseValue", "price");
+//! assert_eq!(changelog.line(&changes[0]), "Item Brick (1727): price 100 → 150");
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    io::{self, Write},
+};
+
+use super::{
+    core::Field,
+    merge::{Change, RowKey},
+};
+use crate::xml::locale_key::LocaleKey;
+
+/// Renders [`Change`]s as text, see the [module documentation](self)
+#[derive(Debug, Clone, Default)]
+pub struct Changelog<'l> {
+    locale: Option<&'l BTreeMap<String, String>>,
+    labels: HashMap<String, String>,
+    names: HashMap<String, String>,
+    columns: HashMap<(String, String), String>,
+}
+
+impl<'l> Changelog<'l> {
+    /// Create a changelog that uses the names of the tables and columns
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a changelog with labels for the common tables of the `CDClient`
+    pub fn cdclient() -> Self {
+        Self::new()
+            .with_label("Objects", "Object")
+            .with_label("Missions", "Mission")
+            .with_label("ItemSets", "Item set")
+            .with_name_column("ItemSets", "kitName")
+    }
+
+    /// Look up the names of rows in `locale`
+    pub fn with_locale(mut self, locale: &'l BTreeMap<String, String>) -> Self {
+        self.locale = Some(locale);
+        self
+    }
+
+    /// Call the rows of `table` e.g. `Item` instead of the name of the table
+    pub fn with_label(mut self, table: &str, label: &str) -> Self {
+        self.labels.insert(table.to_owned(), label.to_owned());
+        self
+    }
+
+    /// Use the locale entries for `column` as the names of the rows of `table`
+    ///
+    /// The default is `name`, e.g. `Objects_1727_name`.
+    pub fn with_name_column(mut self, table: &str, column: &str) -> Self {
+        self.names.insert(table.to_owned(), column.to_owned());
+        self
+    }
+
+    /// Call `column` of `table` e.g. `price` instead of its name
+    pub fn with_column_label(mut self, table: &str, column: &str, label: &str) -> Self {
+        let key = (table.to_owned(), column.to_owned());
+        self.columns.insert(key, label.to_owned());
+        self
+    }
+
+    fn label<'a>(&'a self, table: &'a str) -> &'a str {
+        self.labels.get(table).map_or(table, String::as_str)
+    }
+
+    /// The name of a row from the locale, if the key is a single id
+    fn name(&self, table: &str, key: &RowKey) -> Option<&'l str> {
+        let id = match (key.fields.as_slice(), key.index) {
+            ([Field::Integer(id)], 0) => u32::try_from(*id).ok()?,
+            _ => return None,
+        };
+        let column = self.names.get(table).map_or("name", String::as_str);
+        let key = LocaleKey::Table {
+            table: table.to_owned(),
+            id,
+            column: column.to_owned(),
+        };
+        self.locale?.get(&key.to_string()).map(String::as_str)
+    }
+
+    fn row(&self, table: &str, key: &RowKey) -> String {
+        let id = match (key.fields.as_slice(), key.index) {
+            ([field], 0) => field.to_string(),
+            _ => key.to_string(),
+        };
+        match self.name(table, key) {
+            Some(name) => format!("{} {} ({})", self.label(table), name, id),
+            None => format!("{} {}", self.label(table), id),
+        }
+    }
+
+    /// Render a single change
+    pub fn line(&self, change: &Change) -> String {
+        match change {
+            Change::TableAdded { table, rows } => {
+                format!("Added table {} with {} rows", table, rows)
+            }
+            Change::TableRemoved { table, rows } => {
+                format!("Removed table {} with {} rows", table, rows)
+            }
+            Change::Columns { table } => format!("Changed the columns of table {}", table),
+            Change::RowAdded { table, key, .. } => format!("Added {}", self.row(table, key)),
+            Change::RowRemoved { table, key, .. } => format!("Removed {}", self.row(table, key)),
+            Change::RowChanged {
+                table, key, fields, ..
+            } => {
+                let fields: Vec<_> = fields
+                    .iter()
+                    .map(|(column, old, new)| {
+                        let key = (table.clone(), column.clone());
+                        let column = self.columns.get(&key).unwrap_or(column);
+                        format!("{} {} \u{2192} {}", column, old, new)
+                    })
+                    .collect();
+                format!("{}: {}", self.row(table, key), fields.join(", "))
+            }
+        }
+    }
+
+    /// Write the changes as a Markdown list, one line per change
+    pub fn write<W: Write>(&self, changes: &[Change], mut out: W) -> io::Result<()> {
+        for change in changes {
+            writeln!(out, "- {}", self.line(change))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fdb::{
+        core::Schema,
+        csv::CsvDialect,
+        merge::{diff, MergeOptions},
+        source::{import_from_source, CsvSource},
+    };
+
+    fn load(tables: &[(&str, &str)]) -> Schema {
+        let mut source = CsvSource::new(CsvDialect::default());
+        for (name, text) in tables {
+            source = source.with_table(name, text);
+        }
+        import_from_source(source, &()).unwrap()
+    }
+
+    #[test]
+    fn test_changelog() {
+        let old = load(&[
+            ("Objects", "id,name,price\n1,brick,10\n2,plate,20\n"),
+            ("Icons", "id,path\n1,a.dds\n"),
+        ]);
+        let new = load(&[
+            ("Objects", "id,name,price\n1,brick,15\n3,slope,30\n"),
+            ("Skills", "id,cost\n1,5\n"),
+        ]);
+        let changes = diff(&old, &new, &MergeOptions::new()).unwrap();
+        let locale = vec![
+            ("Objects_1_name".to_owned(), "Brick 1x1".to_owned()),
+            ("Objects_2_name".to_owned(), "Plate 2x2".to_owned()),
+        ];
+        let locale = locale.into_iter().collect();
+        let mut out = Vec::new();
+        let changelog = Changelog::cdclient().with_locale(&locale);
+        changelog.write(&changes, &mut out).unwrap();
+        let expected = "\
+- Object Brick 1x1 (1): price 10 \u{2192} 15
+- Added Object 3
+- Removed Object Plate 2x2 (2)
+- Added table Skills with 1 rows
+- Removed table Icons with 1 rows
+";
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+}
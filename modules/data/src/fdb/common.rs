@@ -5,17 +5,24 @@
 
 use std::{
     borrow::{Borrow, Cow},
+    cmp::Ordering,
     convert::TryFrom,
     error::Error,
     fmt,
+    hash::{Hash, Hasher},
+    mem,
     ops::Deref,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+use assembly_core::latin1;
 use encoding_rs::WINDOWS_1252;
 use memchr::memchr;
 
+use super::base64::{self, Base64Error};
+
 #[repr(transparent)]
-#[derive(Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// An owned latin-1 encoded string
 pub struct Latin1String {
     inner: Box<[u8]>,
@@ -73,7 +80,7 @@ impl From<&Latin1Str> for Latin1String {
 }
 
 #[repr(transparent)]
-#[derive(PartialEq, PartialOrd, Eq, Ord)]
+#[derive(PartialEq, PartialOrd, Eq, Ord, Hash)]
 /// A borrowed latin-1 encoded string (like `&str`)
 pub struct Latin1Str {
     #[allow(dead_code)]
@@ -108,6 +115,7 @@ impl ToOwned for Latin1Str {
 
 impl Latin1Str {
     /// Takes all bytes until before the first null byte or end of slice.
+    #[cfg(feature = "fdb-mem")]
     pub(super) fn new(bytes: &[u8]) -> &Self {
         let text = if let Some(index) = memchr(0x00, bytes) {
             bytes.split_at(index).0
@@ -151,6 +159,97 @@ impl Latin1Str {
     pub fn decode(&self) -> Cow<str> {
         WINDOWS_1252.decode(self.as_bytes()).0
     }
+
+    /// Check whether the string starts with `prefix`
+    pub fn starts_with(&self, prefix: &[u8]) -> bool {
+        self.inner.starts_with(prefix)
+    }
+
+    /// Check whether the string contains `needle`
+    pub fn contains(&self, needle: &[u8]) -> bool {
+        needle.is_empty() || self.inner.windows(needle.len()).any(|w| w == needle)
+    }
+
+    /// Check whether the string is equal to `other`, ignoring case
+    ///
+    /// See [`latin1_to_lowercase`] for the letters this considers.
+    pub fn eq_ignore_case(&self, other: &[u8]) -> bool {
+        latin1::eq_ignore_case(&self.inner, other)
+    }
+
+    /// Check whether the string starts with `prefix`, ignoring case
+    pub fn starts_with_ignore_case(&self, prefix: &[u8]) -> bool {
+        self.inner.len() >= prefix.len()
+            && latin1::eq_ignore_case(&self.inner[..prefix.len()], prefix)
+    }
+
+    /// Check whether the string contains `needle`, ignoring case
+    pub fn contains_ignore_case(&self, needle: &[u8]) -> bool {
+        needle.is_empty()
+            || self
+                .inner
+                .windows(needle.len())
+                .any(|w| latin1::eq_ignore_case(w, needle))
+    }
+}
+
+/// Map an upper case letter in Windows-1252 to its lower case variant
+///
+/// This is [`assembly_core::latin1::to_lowercase`], which includes ASCII, the
+/// accented letters in `À..=Þ` (except `×`) as well as `Š`, `Œ`, `Ž` and `Ÿ`.
+/// All other bytes are returned unchanged.
+pub fn latin1_to_lowercase(byte: u8) -> u8 {
+    latin1::to_lowercase(byte)
+}
+
+impl AsRef<[u8]> for Latin1Str {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+/// The separators used by [`Value::as_id_list`]
+pub const DEFAULT_ID_LIST_SEPARATORS: &[u8] = b",:";
+
+/// An iterator over the numbers in a list field such as `1:2:3` or `1,2,3`
+///
+/// Whitespace around each entry is ignored, as are empty entries and entries
+/// that are not valid 32 bit integers.
+#[derive(Debug, Clone)]
+pub struct IdList<'a> {
+    rest: &'a [u8],
+    separators: &'a [u8],
+}
+
+impl<'a> IdList<'a> {
+    /// Create a new iterator over `bytes`, splitting at any of the `separators`
+    pub fn new(bytes: &'a [u8], separators: &'a [u8]) -> Self {
+        Self {
+            rest: bytes,
+            separators,
+        }
+    }
+}
+
+impl<'a> Iterator for IdList<'a> {
+    type Item = i32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.rest.is_empty() {
+            let separators = self.separators;
+            let (entry, rest) = match self.rest.iter().position(|b| separators.contains(b)) {
+                Some(index) => (&self.rest[..index], &self.rest[(index + 1)..]),
+                None => (self.rest, &self.rest[self.rest.len()..]),
+            };
+            self.rest = rest;
+
+            let entry = std::str::from_utf8(entry).map(str::trim);
+            if let Ok(Ok(value)) = entry.map(str::parse::<i32>) {
+                return Some(value);
+            }
+        }
+        None
+    }
 }
 
 /// Type-Parameters to [`Value`]
@@ -188,9 +287,19 @@ where
 ///
 /// This is a generic enum that is the template for all
 /// other `Field` types in this crate.
-#[derive(Debug, PartialEq)]
+///
+/// ## Equality
+///
+/// Two values are equal if they have the same variant and the same content,
+/// so `Integer(1)` is not equal to `BigInt(1)`. Floats are compared by their
+/// bits, unlike `f32` itself: `NaN` is equal to a `NaN` with the same bits and
+/// `0.0` is not equal to `-0.0`. This makes the comparison an equivalence
+/// relation, so values (and [`Row`][super::core::Row]s) implement [`Eq`] and
+/// [`Hash`] and can be put in a `HashSet` to deduplicate or diff them.
+#[derive(Debug)]
 #[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
 #[cfg_attr(feature = "serde-derives", serde(untagged))]
+#[non_exhaustive]
 pub enum Value<T: Context> {
     /// The NULL value
     Nothing,
@@ -208,6 +317,54 @@ pub enum Value<T: Context> {
     VarChar(T::XML),
 }
 
+impl<T: Context> PartialEq for Value<T>
+where
+    T::String: PartialEq,
+    T::XML: PartialEq,
+    T::I64: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nothing, Value::Nothing) => true,
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+            (Value::Text(a), Value::Text(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::BigInt(a), Value::BigInt(b)) => a == b,
+            (Value::VarChar(a), Value::VarChar(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl<T: Context> Eq for Value<T>
+where
+    T::String: Eq,
+    T::XML: Eq,
+    T::I64: Eq,
+{
+}
+
+impl<T: Context> Hash for Value<T>
+where
+    T::String: Hash,
+    T::XML: Hash,
+    T::I64: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            Value::Nothing => {}
+            Value::Integer(v) => v.hash(state),
+            Value::Float(v) => v.to_bits().hash(state),
+            Value::Text(v) => v.hash(state),
+            Value::Boolean(v) => v.hash(state),
+            Value::BigInt(v) => v.hash(state),
+            Value::VarChar(v) => v.hash(state),
+        }
+    }
+}
+
 impl<T: Context> Clone for Value<T>
 where
     T::String: Clone,
@@ -253,6 +410,64 @@ impl<T: Context> Value<T> {
         }
     }
 
+    /// Returns the type of this value
+    pub fn value_type(&self) -> ValueType {
+        ValueType::from(self)
+    }
+
+    /// Returns `true` if this is [`Value::Nothing`]
+    pub fn is_nothing(&self) -> bool {
+        matches!(self, Self::Nothing)
+    }
+
+    /// Returns the value if the field contains an [`Value::Integer`].
+    pub fn as_integer(&self) -> Option<i32> {
+        match self {
+            Self::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if the field contains a [`Value::Float`].
+    pub fn as_float(&self) -> Option<f32> {
+        match self {
+            Self::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns a reference to the value if the field contains a [`Value::Text`].
+    pub fn as_text(&self) -> Option<&T::String> {
+        match self {
+            Self::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if the field contains a [`Value::Boolean`].
+    pub fn as_boolean(&self) -> Option<bool> {
+        match self {
+            Self::Boolean(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns a reference to the value if the field contains a [`Value::BigInt`].
+    pub fn as_big_int(&self) -> Option<&T::I64> {
+        match self {
+            Self::BigInt(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns a reference to the value if the field contains a [`Value::VarChar`].
+    pub fn as_varchar(&self) -> Option<&T::XML> {
+        match self {
+            Self::VarChar(value) => Some(value),
+            _ => None,
+        }
+    }
+
     /// Returns `Some` with the value if the field contains an [`Value::Integer`].
     pub fn into_opt_integer(self) -> Option<i32> {
         if let Self::Integer(value) = self {
@@ -306,6 +521,175 @@ impl<T: Context> Value<T> {
             None
         }
     }
+
+    /// Returns `Some` with an iterator over the IDs if the field contains a
+    /// [`Value::Text`] or [`Value::VarChar`] with a list like `1:2:3` or `1,2,3`.
+    ///
+    /// See [`Value::as_id_list_with`] to use different separators.
+    pub fn as_id_list(&self) -> Option<IdList<'_>>
+    where
+        T::String: AsRef<[u8]>,
+        T::XML: AsRef<[u8]>,
+    {
+        self.as_id_list_with(DEFAULT_ID_LIST_SEPARATORS)
+    }
+
+    /// Like [`Value::as_id_list`], but splits at any of the given `separators`
+    pub fn as_id_list_with<'a>(&'a self, separators: &'a [u8]) -> Option<IdList<'a>>
+    where
+        T::String: AsRef<[u8]>,
+        T::XML: AsRef<[u8]>,
+    {
+        match self {
+            Self::Text(text) => Some(IdList::new(text.as_ref(), separators)),
+            Self::VarChar(text) => Some(IdList::new(text.as_ref(), separators)),
+            _ => None,
+        }
+    }
+}
+
+/// Interpretation of numeric fields as times
+///
+/// The database stores durations and timestamps as plain numbers, so these
+/// helpers are opt-in and the caller has to know the unit of each column.
+impl<T: Context> Value<T>
+where
+    T::I64: Copy + Into<i64>,
+{
+    /// Returns the value of an [`Value::Integer`] or [`Value::BigInt`]
+    fn as_whole_number(&self) -> Option<i64> {
+        match self {
+            Self::Integer(value) => Some(i64::from(*value)),
+            Self::BigInt(value) => Some((*value).into()),
+            _ => None,
+        }
+    }
+
+    /// Interpret an integer or float field as a number of seconds
+    ///
+    /// Returns `None` for negative, NaN or too large values.
+    pub fn as_duration_secs(&self) -> Option<Duration> {
+        match self {
+            Self::Float(value) => Duration::try_from_secs_f32(*value).ok(),
+            _ => u64::try_from(self.as_whole_number()?)
+                .ok()
+                .map(Duration::from_secs),
+        }
+    }
+
+    /// Interpret an integer field as a number of milliseconds
+    ///
+    /// Returns `None` for negative values.
+    pub fn as_duration_millis(&self) -> Option<Duration> {
+        u64::try_from(self.as_whole_number()?)
+            .ok()
+            .map(Duration::from_millis)
+    }
+
+    /// Interpret an integer field as a UNIX timestamp in seconds
+    pub fn as_system_time_secs(&self) -> Option<SystemTime> {
+        let secs = self.as_whole_number()?;
+        let offset = Duration::from_secs(secs.unsigned_abs());
+        if secs < 0 {
+            UNIX_EPOCH.checked_sub(offset)
+        } else {
+            UNIX_EPOCH.checked_add(offset)
+        }
+    }
+
+    /// Interpret an integer field as a UNIX timestamp in milliseconds
+    pub fn as_system_time_millis(&self) -> Option<SystemTime> {
+        let millis = self.as_whole_number()?;
+        let offset = Duration::from_millis(millis.unsigned_abs());
+        if millis < 0 {
+            UNIX_EPOCH.checked_sub(offset)
+        } else {
+            UNIX_EPOCH.checked_add(offset)
+        }
+    }
+
+    /// Interpret an integer field as a UNIX timestamp in seconds
+    #[cfg(feature = "chrono")]
+    pub fn as_date_time_secs(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        use chrono::TimeZone;
+        chrono::Utc
+            .timestamp_opt(self.as_whole_number()?, 0)
+            .single()
+    }
+
+    /// Interpret an integer field as a UNIX timestamp in milliseconds
+    #[cfg(feature = "chrono")]
+    pub fn as_date_time_millis(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        use chrono::TimeZone;
+        chrono::Utc
+            .timestamp_millis_opt(self.as_whole_number()?)
+            .single()
+    }
+}
+
+/// Binary payloads in `VARCHAR` fields, see the [`base64`][super::base64] module
+impl<T: Context> Value<T>
+where
+    T::XML: AsRef<[u8]>,
+{
+    /// Decode a [`Value::VarChar`] that contains base64
+    pub fn decode_varchar_base64(&self) -> Result<Vec<u8>, Base64Error> {
+        match self {
+            Self::VarChar(text) => base64::decode(text.as_ref()),
+            _ => Err(Base64Error::NotVarChar),
+        }
+    }
+}
+
+impl<T: Context> Value<T>
+where
+    T::XML: From<String>,
+{
+    /// Create a [`Value::VarChar`] that contains `data` as base64
+    pub fn encode_varchar_base64(data: &[u8]) -> Self {
+        Self::VarChar(T::XML::from(base64::encode(data)))
+    }
+}
+
+impl<T: Context> Value<T>
+where
+    T::String: Ord,
+    T::I64: Ord,
+    T::XML: Ord,
+{
+    /// Compare two values for sorting
+    ///
+    /// This is a total order: values of different types are ordered by type,
+    /// with `NULL` first, and floats use [`f32::total_cmp`].
+    pub fn sort_cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Integer(a), Self::Integer(b)) => a.cmp(b),
+            (Self::Float(a), Self::Float(b)) => a.total_cmp(b),
+            (Self::Text(a), Self::Text(b)) => a.cmp(b),
+            (Self::Boolean(a), Self::Boolean(b)) => a.cmp(b),
+            (Self::BigInt(a), Self::BigInt(b)) => a.cmp(b),
+            (Self::VarChar(a), Self::VarChar(b)) => a.cmp(b),
+            _ => u8::from(ValueType::from(self)).cmp(&u8::from(ValueType::from(other))),
+        }
+    }
+}
+
+/// The order in which the rows of a table are visited
+///
+/// In [`IterOrder::File`] order, the rows are grouped by hash bucket, so the
+/// order depends on the number of buckets of the table. Two files with the
+/// same rows but a different layout produce different outputs. Use
+/// [`IterOrder::PrimaryKey`] when the output needs to be reproducible, e.g.
+/// for exports that are compared with a diff tool.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum IterOrder {
+    /// The order of the buckets, and of the rows within each bucket
+    #[default]
+    File,
+    /// Sorted by the value of the first column, as with [`Value::sort_cmp`]
+    ///
+    /// Rows with the same key keep their relative order from the file.
+    PrimaryKey,
 }
 
 impl<T: Context> From<&Value<T>> for ValueType {
@@ -325,6 +709,7 @@ impl<T: Context> From<&Value<T>> for ValueType {
 /// Value datatypes used in the database
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+#[non_exhaustive]
 pub enum ValueType {
     /// The NULL value
     Nothing,
@@ -343,6 +728,21 @@ pub enum ValueType {
 }
 
 impl ValueType {
+    /// Returns all known value types
+    ///
+    /// New value types may be added to this list in the future.
+    pub fn all() -> &'static [ValueType] {
+        &[
+            ValueType::Nothing,
+            ValueType::Integer,
+            ValueType::Float,
+            ValueType::Text,
+            ValueType::Boolean,
+            ValueType::BigInt,
+            ValueType::VarChar,
+        ]
+    }
+
     /// Get a static name for the type
     pub fn static_name(&self) -> &'static str {
         match self {
@@ -420,9 +820,56 @@ impl TryFrom<u32> for ValueType {
 
 #[cfg(test)]
 mod tests {
-    use super::Latin1Str;
+    use super::{IdList, Latin1Str, ValueType, DEFAULT_ID_LIST_SEPARATORS};
+    use std::{
+        convert::TryFrom,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    #[test]
+    fn test_id_list() {
+        let list = |s: &'static str| IdList::new(s.as_bytes(), DEFAULT_ID_LIST_SEPARATORS);
+        assert_eq!(list("1:2:3").collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(list("1, 2,3").collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(list("4,,-5:").collect::<Vec<_>>(), vec![4, -5]);
+        assert_eq!(list("").next(), None);
+        assert_eq!(list("a,7").collect::<Vec<_>>(), vec![7]);
+        let semi = IdList::new(b"1;2", b";");
+        assert_eq!(semi.collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    #[cfg(feature = "fdb-core")]
+    fn test_time_helpers() {
+        use crate::fdb::core::Field;
+        assert_eq!(
+            Field::Integer(90).as_duration_secs(),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(
+            Field::Float(1.5).as_duration_secs(),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(Field::Float(-1.0).as_duration_secs(), None);
+        assert_eq!(Field::Float(f32::NAN).as_duration_secs(), None);
+        assert_eq!(Field::Float(f32::MAX).as_duration_secs(), None);
+        assert_eq!(Field::Integer(-1).as_duration_millis(), None);
+        assert_eq!(
+            Field::BigInt(1_000).as_system_time_millis(),
+            Some(UNIX_EPOCH + Duration::from_secs(1))
+        );
+        assert_eq!(Field::Text(String::new()).as_system_time_secs(), None);
+    }
+
+    #[test]
+    fn test_value_type_roundtrip() {
+        for &value_type in ValueType::all() {
+            assert_eq!(ValueType::try_from(u32::from(value_type)), Ok(value_type));
+        }
+    }
 
     #[test]
+    #[cfg(feature = "fdb-mem")]
     fn test_latin1_req_bytes() {
         assert_eq!(1, Latin1Str::new(b"a").req_buf_len());
         assert_eq!(1, Latin1Str::new(b"ab").req_buf_len());
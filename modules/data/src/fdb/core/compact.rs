@@ -0,0 +1,465 @@
+//! # A compact in-memory representation of a [`Schema`]
+//!
+//! The regular [`Schema`] stores one `Vec` per row and one `String` per text
+//! field. [`CompactSchema`] instead stores all strings of a schema in a single
+//! arena, all fields of a table in a single vector and the row and bucket
+//! boundaries as lists of offsets. [`CompactSchema::heap_size`] reports how
+//! much memory that takes.
+//!
+//! The offsets are 32 bit, so packing a schema fails with a [`CompactError`]
+//! if the strings or the fields of a table exceed that.
+
+use std::{convert::TryFrom, marker::PhantomData, ops::Range};
+
+use assembly_core::displaydoc::Display;
+use thiserror::Error;
+
+use super::{Bucket, Column, Field, OwnedContext, Row, Schema, Table, TableData, TableDef};
+use crate::fdb::common::{Context, Value, ValueMapperMut};
+
+/// Errors when packing a [`Schema`] into a [`CompactSchema`]
+#[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
+pub enum CompactError {
+    /// The strings of the schema exceed 4 GiB
+    ArenaTooLarge,
+    /// The table {0} has more than 2^32 rows or fields
+    TableTooLarge(String),
+}
+
+/// A range of bytes in the string arena of a [`CompactSchema`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StrRef {
+    start: u32,
+    len: u32,
+}
+
+impl StrRef {
+    fn range(self) -> Range<usize> {
+        let start = self.start as usize;
+        start..(start + self.len as usize)
+    }
+}
+
+/// The `Value` context for [`PackedField`]
+#[derive(Debug, PartialEq, Eq)]
+pub struct PackedContext;
+
+impl Context for PackedContext {
+    type String = StrRef;
+    type I64 = i64;
+    type XML = StrRef;
+}
+
+/// A field that refers to the string arena of a [`CompactSchema`]
+pub type PackedField = Value<PackedContext>;
+
+/// The `Value` context for [`CompactField`]
+#[derive(Debug, PartialEq, Eq)]
+pub struct CompactContext<'a> {
+    _p: PhantomData<&'a ()>,
+}
+
+impl<'a> Context for CompactContext<'a> {
+    type String = &'a str;
+    type I64 = i64;
+    type XML = &'a str;
+}
+
+/// A field borrowed from a [`CompactSchema`]
+pub type CompactField<'a> = Value<CompactContext<'a>>;
+
+struct ArenaWriter<'a> {
+    arena: &'a mut String,
+    /// Set when a string didn't fit into the arena
+    overflow: bool,
+}
+
+impl ArenaWriter<'_> {
+    /// Add `text` to the arena
+    ///
+    /// The [`ValueMapperMut`] can't fail, so if the arena would exceed 4 GiB,
+    /// this sets `overflow` and returns an empty string instead.
+    fn push(&mut self, text: &str) -> StrRef {
+        let end = self.arena.len() + text.len();
+        if self.overflow || u32::try_from(end).is_err() {
+            self.overflow = true;
+            return StrRef { start: 0, len: 0 };
+        }
+        let (start, len) = (self.arena.len() as u32, text.len() as u32);
+        self.arena.push_str(text);
+        StrRef { start, len }
+    }
+}
+
+impl ValueMapperMut<OwnedContext, PackedContext> for ArenaWriter<'_> {
+    fn map_string(&mut self, from: &String) -> StrRef {
+        self.push(from)
+    }
+
+    fn map_i64(&mut self, from: &i64) -> i64 {
+        *from
+    }
+
+    fn map_xml(&mut self, from: &String) -> StrRef {
+        self.push(from)
+    }
+}
+
+struct ArenaReader<'a> {
+    arena: &'a str,
+}
+
+impl<'a> ValueMapperMut<PackedContext, CompactContext<'a>> for ArenaReader<'a> {
+    fn map_string(&mut self, from: &StrRef) -> &'a str {
+        &self.arena[from.range()]
+    }
+
+    fn map_i64(&mut self, from: &i64) -> i64 {
+        *from
+    }
+
+    fn map_xml(&mut self, from: &StrRef) -> &'a str {
+        &self.arena[from.range()]
+    }
+}
+
+impl From<CompactField<'_>> for Field {
+    fn from(src: CompactField<'_>) -> Self {
+        match src {
+            Value::Nothing => Field::Nothing,
+            Value::Integer(v) => Field::Integer(v),
+            Value::Float(v) => Field::Float(v),
+            Value::Text(v) => Field::Text(v.to_owned()),
+            Value::Boolean(v) => Field::Boolean(v),
+            Value::BigInt(v) => Field::BigInt(v),
+            Value::VarChar(v) => Field::VarChar(v.to_owned()),
+        }
+    }
+}
+
+fn offset(value: usize) -> Option<u32> {
+    u32::try_from(value).ok()
+}
+
+/// A table in a [`CompactSchema`]
+#[derive(Debug)]
+pub struct CompactTable {
+    name: String,
+    columns: Vec<Column>,
+    /// Index of the first row of each bucket, followed by the row count
+    buckets: Vec<u32>,
+    /// Index of the first field of each row, followed by the field count
+    rows: Vec<u32>,
+    fields: Vec<PackedField>,
+}
+
+impl CompactTable {
+    fn pack(table: Table, writer: &mut ArenaWriter) -> Result<Self, CompactError> {
+        let name = table.name().to_string();
+        let Table { definition, data } = table;
+        let too_large = || CompactError::TableTooLarge(name.clone());
+
+        let mut buckets = Vec::with_capacity(data.buckets.len() + 1);
+        let mut rows = Vec::new();
+        let mut fields = Vec::new();
+        for bucket in data.buckets {
+            buckets.push(offset(rows.len()).ok_or_else(too_large)?);
+            for row in bucket.0 {
+                rows.push(offset(fields.len()).ok_or_else(too_large)?);
+                fields.extend(row.fields().iter().map(|f| f.map(writer)));
+            }
+        }
+        buckets.push(offset(rows.len()).ok_or_else(too_large)?);
+        rows.push(offset(fields.len()).ok_or_else(too_large)?);
+        if writer.overflow {
+            return Err(CompactError::ArenaTooLarge);
+        }
+
+        fields.shrink_to_fit();
+        rows.shrink_to_fit();
+        Ok(Self {
+            name,
+            columns: definition.columns,
+            buckets,
+            rows,
+            fields,
+        })
+    }
+
+    /// Returns the name of the table
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the columns of the table
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Returns the number of buckets
+    pub fn bucket_count(&self) -> usize {
+        self.buckets.len() - 1
+    }
+
+    /// Returns the number of rows
+    pub fn row_count(&self) -> usize {
+        self.rows.len() - 1
+    }
+
+    fn row_range(&self, index: usize) -> Range<usize> {
+        self.rows[index] as usize..self.rows[index + 1] as usize
+    }
+
+    fn bucket_range(&self, index: usize) -> Range<usize> {
+        self.buckets[index] as usize..self.buckets[index + 1] as usize
+    }
+}
+
+/// A [`Schema`] with all strings in one arena and packed fields
+///
+/// Use `CompactSchema::try_from(schema)` to convert a loaded schema and
+/// `Schema::from(&compact)` to convert it back.
+#[derive(Debug, Default)]
+pub struct CompactSchema {
+    arena: String,
+    tables: Vec<CompactTable>,
+}
+
+impl CompactSchema {
+    /// Returns the number of tables
+    pub fn table_count(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// Get the table with that name, if it exists
+    pub fn table(&self, name: &str) -> Option<CompactTableRef<'_>> {
+        let index = self
+            .tables
+            .binary_search_by(|t| t.name.as_str().cmp(name))
+            .ok()?;
+        Some(self.table_at(index))
+    }
+
+    fn table_at(&self, index: usize) -> CompactTableRef<'_> {
+        CompactTableRef {
+            arena: &self.arena,
+            table: &self.tables[index],
+        }
+    }
+
+    /// Iterate over all tables, ordered by name
+    pub fn tables(&self) -> impl Iterator<Item = CompactTableRef<'_>> + '_ {
+        (0..self.tables.len()).map(move |i| self.table_at(i))
+    }
+
+    /// Returns the approximate number of bytes allocated on the heap
+    pub fn heap_size(&self) -> usize {
+        use std::mem::size_of;
+        let tables = self.tables.iter().map(|t| {
+            t.name.capacity()
+                + t.columns.capacity() * size_of::<Column>()
+                + t.columns.iter().map(|c| c.name.len()).sum::<usize>()
+                + (t.buckets.capacity() + t.rows.capacity()) * size_of::<u32>()
+                + t.fields.capacity() * size_of::<PackedField>()
+        });
+        self.arena.capacity()
+            + self.tables.capacity() * size_of::<CompactTable>()
+            + tables.sum::<usize>()
+    }
+}
+
+impl TryFrom<Schema> for CompactSchema {
+    type Error = CompactError;
+
+    fn try_from(schema: Schema) -> Result<Self, CompactError> {
+        let mut arena = String::new();
+        let mut writer = ArenaWriter {
+            arena: &mut arena,
+            overflow: false,
+        };
+        let tables = schema
+            .tables
+            .into_values()
+            .map(|table| CompactTable::pack(table, &mut writer))
+            .collect::<Result<_, _>>()?;
+        arena.shrink_to_fit();
+        Ok(Self { arena, tables })
+    }
+}
+
+/// A reference to a table in a [`CompactSchema`]
+#[derive(Debug, Copy, Clone)]
+pub struct CompactTableRef<'a> {
+    arena: &'a str,
+    table: &'a CompactTable,
+}
+
+impl<'a> CompactTableRef<'a> {
+    /// Returns the name of the table
+    pub fn name(&self) -> &'a str {
+        &self.table.name
+    }
+
+    /// Returns the columns of the table
+    pub fn columns(&self) -> &'a [Column] {
+        &self.table.columns
+    }
+
+    /// Returns the number of buckets
+    pub fn bucket_count(&self) -> usize {
+        self.table.bucket_count()
+    }
+
+    /// Returns the number of rows
+    pub fn row_count(&self) -> usize {
+        self.table.row_count()
+    }
+
+    /// Get the row at `index`, counting over all buckets
+    pub fn row(&self, index: usize) -> Option<CompactRow<'a>> {
+        if index < self.row_count() {
+            Some(CompactRow {
+                arena: self.arena,
+                fields: &self.table.fields[self.table.row_range(index)],
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Iterate over all rows in the table
+    pub fn row_iter(self) -> impl Iterator<Item = CompactRow<'a>> {
+        (0..self.row_count()).filter_map(move |i| self.row(i))
+    }
+
+    /// Iterate over the rows of the bucket at `index`
+    pub fn bucket(self, index: usize) -> impl Iterator<Item = CompactRow<'a>> {
+        let range = if index < self.bucket_count() {
+            self.table.bucket_range(index)
+        } else {
+            0..0
+        };
+        range.filter_map(move |i| self.row(i))
+    }
+}
+
+/// A row in a [`CompactSchema`]
+#[derive(Debug, Copy, Clone)]
+pub struct CompactRow<'a> {
+    arena: &'a str,
+    fields: &'a [PackedField],
+}
+
+impl<'a> CompactRow<'a> {
+    /// Returns the number of fields
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns whether the row has no fields
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Get the field at `index`
+    pub fn get(&self, index: usize) -> Option<CompactField<'a>> {
+        let mut reader = ArenaReader { arena: self.arena };
+        self.fields.get(index).map(|f| f.map(&mut reader))
+    }
+
+    /// Iterate over the fields
+    pub fn field_iter(self) -> impl Iterator<Item = CompactField<'a>> {
+        let mut reader = ArenaReader { arena: self.arena };
+        self.fields.iter().map(move |f| f.map(&mut reader))
+    }
+}
+
+impl From<CompactRow<'_>> for Row {
+    fn from(row: CompactRow<'_>) -> Self {
+        Row::from(row.field_iter().map(Field::from).collect::<Vec<_>>())
+    }
+}
+
+impl From<CompactTableRef<'_>> for Table {
+    fn from(table: CompactTableRef<'_>) -> Self {
+        let definition = TableDef {
+            columns: table.columns().to_vec(),
+            name: table.name().to_string(),
+        };
+        let buckets = (0..table.bucket_count())
+            .map(|i| Bucket(table.bucket(i).map(Row::from).collect()))
+            .collect();
+        Table::from(definition, TableData { buckets })
+    }
+}
+
+impl From<&CompactSchema> for Schema {
+    fn from(compact: &CompactSchema) -> Self {
+        Schema::from(
+            compact
+                .tables()
+                .map(<Table as From<_>>::from)
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fdb::common::ValueType;
+
+    #[test]
+    fn test_compact_roundtrip() {
+        let def = TableDef {
+            columns: vec![
+                Column::from(("id", ValueType::Integer)),
+                Column::from(("name", ValueType::Text)),
+            ],
+            name: String::from("Objects"),
+        };
+        let buckets = vec![
+            Bucket(vec![Row::from(vec![
+                Field::Integer(2),
+                Field::Text(String::from("Two")),
+            ])]),
+            Bucket(vec![]),
+            Bucket(vec![
+                Row::from(vec![Field::Integer(1), Field::Text(String::from("One"))]),
+                Row::from(vec![Field::Integer(3), Field::Nothing]),
+            ]),
+        ];
+        let schema = Schema::from(vec![Table::from(def, TableData { buckets })]);
+        let compact = CompactSchema::try_from(schema).unwrap();
+
+        let table = compact.table("Objects").unwrap();
+        assert_eq!(table.bucket_count(), 3);
+        assert_eq!(table.row_count(), 3);
+        assert_eq!(table.bucket(1).count(), 0);
+        let row = table.bucket(2).next().unwrap();
+        assert_eq!(row.get(1), Some(CompactField::Text("One")));
+
+        let schema = Schema::from(&compact);
+        let table = schema.table("Objects").unwrap();
+        assert_eq!(
+            table.buckets()[2].rows_ref()[1].fields()[0],
+            Field::Integer(3)
+        );
+        assert_eq!(table.columns()[1].name.as_ref(), "name");
+    }
+
+    #[test]
+    fn test_compact_overflow() {
+        let mut arena = String::from("Brick");
+        let mut writer = ArenaWriter {
+            arena: &mut arena,
+            overflow: false,
+        };
+        assert_eq!(writer.push("Plate"), StrRef { start: 5, len: 5 });
+        assert!(!writer.overflow);
+        writer.overflow = true;
+        assert_eq!(writer.push("Tile"), StrRef { start: 0, len: 0 });
+        assert_eq!(offset(u32::MAX as usize), Some(u32::MAX));
+        assert_eq!(offset(u32::MAX as usize + 1), None);
+    }
+}
@@ -1,7 +1,12 @@
 //! # Implementations of `IntoIterator` for the core model
 
-use super::{Bucket, Field, Row, Table};
-use std::{iter::FlatMap, slice::Iter as SliceIter, vec::IntoIter as VecIntoIter};
+use super::{Bucket, Field, Row, Schema, Table};
+use std::{
+    collections::btree_map::{IntoValues, Values},
+    iter::FlatMap,
+    slice::Iter as SliceIter,
+    vec::IntoIter as VecIntoIter,
+};
 
 /// An iterator over a vector of fields in a row.
 pub type FieldVecIter = VecIntoIter<Field>;
@@ -82,3 +87,27 @@ impl<'a> IntoIterator for &'a Table {
         self.buckets().iter().flat_map(<&Bucket>::into_iter)
     }
 }
+
+/// An iterator over the tables in a schema, ordered by name.
+pub type SchemaIntoIter = IntoValues<String, Table>;
+
+impl IntoIterator for Schema {
+    type Item = Table;
+    type IntoIter = SchemaIntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tables.into_values()
+    }
+}
+
+/// An iterator over references to the tables in a schema, ordered by name.
+pub type SchemaIter<'a> = Values<'a, String, Table>;
+
+impl<'a> IntoIterator for &'a Schema {
+    type Item = &'a Table;
+    type IntoIter = SchemaIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
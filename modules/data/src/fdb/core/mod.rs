@@ -16,15 +16,25 @@
 //! Each Table has a list of columns with the names and default data
 //! Types corresponding to the layout of each row.
 
+pub mod compact;
 pub mod iter;
 
+use iter::SchemaIter;
+
 use std::collections::BTreeMap;
 use std::fmt;
-
-use super::{
-    common::{Context, Value, ValueType},
-    mem::Field as MemField,
+use std::iter::FromIterator;
+use std::sync::Arc;
+
+use super::common::{Context, IterOrder, Latin1String, Value, ValueType};
+use super::float::{DisplayFloat, FloatFormat};
+#[cfg(feature = "fdb-mem")]
+use super::mem::Field as MemField;
+use assembly_core::{
+    displaydoc::Display,
+    hash::{fdb_bucket, fdb_int_hash, fdb_text_hash},
 };
+use thiserror::Error;
 
 /// The `Value` context for `core::Field`
 #[derive(Debug, PartialEq, Eq)]
@@ -39,6 +49,7 @@ impl Context for OwnedContext {
 /// An owned field value
 pub type Field = Value<OwnedContext>;
 
+#[cfg(feature = "fdb-mem")]
 impl From<MemField<'_>> for Field {
     fn from(src: MemField<'_>) -> Self {
         match src {
@@ -53,12 +64,13 @@ impl From<MemField<'_>> for Field {
     }
 }
 
+#[cfg(feature = "fdb-mem")]
 impl PartialEq<MemField<'_>> for Field {
     fn eq(&self, other: &MemField<'_>) -> bool {
         match other {
             Value::Nothing => matches!(self, Self::Nothing),
             Value::Integer(x) => matches!(self, Self::Integer(y) if x == y),
-            Value::Float(x) => matches!(self, Self::Float(y) if x == y),
+            Value::Float(x) => matches!(self, Self::Float(y) if x.to_bits() == y.to_bits()),
             Value::Text(x) => matches!(self, Self::Text(y) if x.decode().as_ref() == y),
             Value::Boolean(x) => matches!(self, Self::Boolean(y) if x == y),
             Value::BigInt(x) => matches!(self, Self::BigInt(y) if x == y),
@@ -67,11 +79,17 @@ impl PartialEq<MemField<'_>> for Field {
     }
 }
 
+/// Floats are written like the standard library does with `{}`, and as their
+/// bits in hex with `{:#}` (see [`FloatFormat::HexBits`]), which is the only
+/// form that is bit-exact for every value, including `NaN` payloads.
 impl fmt::Display for Field {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Field::Nothing => write!(f, "NULL"),
             Field::Integer(i) => write!(f, "{}", i),
+            Field::Float(v) if f.alternate() => {
+                write!(f, "{}", DisplayFloat(*v, FloatFormat::HexBits))
+            }
             Field::Float(v) => write!(f, "{}", v),
             Field::Text(t) => write!(f, "{:?}", t),
             Field::Boolean(b) => write!(f, "{}", b),
@@ -81,8 +99,25 @@ impl fmt::Display for Field {
     }
 }
 
+/// Compute the hash value of a primary key
+///
+/// Integer keys are their own hash, text keys use the Hsieh hash of their
+/// Latin-1 encoding.
+pub fn pk_hash(key: &Field) -> Option<u32> {
+    match key {
+        Field::Integer(i) => Some(fdb_int_hash(*i)),
+        Field::Text(s) | Field::VarChar(s) => {
+            Some(fdb_text_hash(Latin1String::encode(s).as_bytes()))
+        }
+        _ => None,
+    }
+}
+
 /// A sequence of fields
-#[derive(Debug, Default)]
+///
+/// Rows are equal if all their fields are, see the [`Value`] docs for how
+/// floats are compared.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct Row(Vec<Field>);
 
 impl From<Vec<Field>> for Row {
@@ -140,10 +175,12 @@ impl Bucket {
 }
 
 /// Name and default type for one field in each row
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Column {
     /// The name of the column
-    pub name: String,
+    ///
+    /// Column names are shared between tables when loaded with a [`SchemaLoader`][crate::fdb::io::SchemaLoader].
+    pub name: Arc<str>,
     /// The type of the column
     pub field_type: ValueType,
 }
@@ -151,7 +188,16 @@ pub struct Column {
 impl From<(&str, ValueType)> for Column {
     fn from(data: (&str, ValueType)) -> Self {
         Column {
-            name: String::from(data.0),
+            name: Arc::from(data.0),
+            field_type: data.1,
+        }
+    }
+}
+
+impl From<(Arc<str>, ValueType)> for Column {
+    fn from(data: (Arc<str>, ValueType)) -> Self {
+        Column {
+            name: data.0,
             field_type: data.1,
         }
     }
@@ -182,6 +228,16 @@ impl TableData {
     }
 }
 
+/// Errors when renaming a table or column
+#[derive(Error, Debug, Display, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RenameError {
+    /// There is no table or column named {0:?}
+    NotFound(String),
+    /// The name {0:?} is already taken
+    AlreadyExists(String),
+}
+
 /// A list of buckets and thus collection of rows with a name
 #[derive(Debug)]
 pub struct Table {
@@ -235,6 +291,79 @@ impl Table {
     pub fn name(&self) -> &str {
         self.definition.name.as_ref()
     }
+
+    /// Apply `mutation` to every row that matches `predicate`
+    ///
+    /// Returns the number of rows that matched. If the mutation changes the
+    /// first column of a row, the row is moved to the end of the bucket for
+    /// its new key (see [`pk_hash`]). Rows with a key that can't be hashed
+    /// stay in their bucket.
+    pub fn update_where<P, M>(&mut self, mut predicate: P, mut mutation: M) -> usize
+    where
+        P: FnMut(&Row) -> bool,
+        M: FnMut(&mut Row),
+    {
+        let buckets = &mut self.data.buckets;
+        let bucket_count = buckets.len();
+        let mut count = 0;
+        let mut moved = Vec::new();
+        for (index, bucket) in buckets.iter_mut().enumerate() {
+            for mut row in std::mem::take(bucket.rows_mut()) {
+                if predicate(&row) {
+                    let key = row.fields().first().cloned();
+                    mutation(&mut row);
+                    count += 1;
+                    let new_key = row.fields().first();
+                    if new_key != key.as_ref() {
+                        let hash = new_key.and_then(pk_hash);
+                        match hash.map(|hash| fdb_bucket(hash, bucket_count)) {
+                            Some(target) if target != index => {
+                                moved.push((target, row));
+                                continue;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                bucket.rows_mut().push(row);
+            }
+        }
+        for (target, row) in moved {
+            buckets[target].rows_mut().push(row);
+        }
+        count
+    }
+
+    /// Rename the column `old` to `new`
+    ///
+    /// Fails without changes if there is no column `old`, or if another
+    /// column is already named `new`.
+    pub fn rename_column(&mut self, old: &str, new: &str) -> Result<(), RenameError> {
+        let columns = &mut self.definition.columns;
+        let index = columns
+            .iter()
+            .position(|c| c.name.as_ref() == old)
+            .ok_or_else(|| RenameError::NotFound(old.to_string()))?;
+        if old != new && columns.iter().any(|c| c.name.as_ref() == new) {
+            return Err(RenameError::AlreadyExists(new.to_string()));
+        }
+        columns[index].name = Arc::from(new);
+        Ok(())
+    }
+
+    /// Returns references to all rows, in the given order
+    ///
+    /// Iterating over a `&Table` visits the rows in [`IterOrder::File`] order.
+    pub fn rows(&self, order: IterOrder) -> Vec<&Row> {
+        let mut rows: Vec<&Row> = self.into_iter().collect();
+        if order == IterOrder::PrimaryKey {
+            rows.sort_by(|a, b| match (a.fields().first(), b.fields().first()) {
+                (Some(a), Some(b)) => a.sort_cmp(b),
+                (a, b) => a.is_some().cmp(&b.is_some()),
+            });
+        }
+        rows
+    }
 }
 
 /// # An ordered map of tables
@@ -269,14 +398,204 @@ impl Schema {
     pub fn table_count(&self) -> usize {
         self.tables.len()
     }
+
+    /// Returns an iterator over the tables, ordered by name
+    pub fn iter(&self) -> SchemaIter<'_> {
+        self.tables.values()
+    }
+
+    /// Returns an iterator over mutable references to the tables, ordered by name
+    ///
+    /// Renaming a table through this iterator would break the lookup by name,
+    /// so the name of a table can't be changed this way.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Table> {
+        self.tables.values_mut()
+    }
+
+    /// Extract the tables, ordered by name
+    pub fn into_tables(self) -> Vec<Table> {
+        self.into_iter().collect()
+    }
+
+    /// Insert a table under its name
+    ///
+    /// Returns the table of the same name that was replaced, if any.
+    pub fn insert_table(&mut self, table: Table) -> Option<Table> {
+        self.tables.insert(table.name().to_string(), table)
+    }
+
+    /// Remove the table of that name, if it exists
+    pub fn remove_table(&mut self, name: &str) -> Option<Table> {
+        self.tables.remove(name)
+    }
+
+    /// Rename the table `old` to `new`, updating both the key and the definition
+    ///
+    /// Fails without changes if there is no table `old`, or if another table
+    /// is already named `new`.
+    pub fn rename_table(&mut self, old: &str, new: &str) -> Result<(), RenameError> {
+        if !self.tables.contains_key(old) {
+            return Err(RenameError::NotFound(old.to_string()));
+        }
+        if old != new && self.tables.contains_key(new) {
+            return Err(RenameError::AlreadyExists(new.to_string()));
+        }
+        let mut table = self.tables.remove(old).unwrap();
+        table.definition.name = new.to_string();
+        self.tables.insert(new.to_string(), table);
+        Ok(())
+    }
 }
 
 impl From<Vec<Table>> for Schema {
     fn from(tables: Vec<Table>) -> Self {
-        let mut tree = BTreeMap::new();
-        for table in tables {
-            tree.insert(table.name().to_string(), table);
+        tables.into_iter().collect()
+    }
+}
+
+impl FromIterator<Table> for Schema {
+    fn from_iter<I: IntoIterator<Item = Table>>(iter: I) -> Self {
+        let mut schema = Schema::new();
+        schema.extend(iter);
+        schema
+    }
+}
+
+impl Extend<Table> for Schema {
+    fn extend<I: IntoIterator<Item = Table>>(&mut self, iter: I) {
+        for table in iter {
+            self.insert_table(table);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(name: &str) -> Table {
+        Table::new(TableDef {
+            columns: vec![Column::from(("id", ValueType::Integer))],
+            name: String::from(name),
+        })
+    }
+
+    #[test]
+    fn test_schema_collection() {
+        let mut schema: Schema = vec![table("Objects"), table("Icons")].into_iter().collect();
+        let names: Vec<_> = schema.iter().map(Table::name).collect();
+        assert_eq!(names, vec!["Icons", "Objects"]);
+
+        assert!(schema.insert_table(table("Missions")).is_none());
+        assert!(schema.insert_table(table("Icons")).is_some());
+        assert_eq!(schema.table_count(), 3);
+
+        let removed = schema.remove_table("Objects").unwrap();
+        assert_eq!(removed.name(), "Objects");
+        assert!(schema.remove_table("Objects").is_none());
+
+        let names: Vec<_> = (&schema).into_iter().map(Table::name).collect();
+        assert_eq!(names, vec!["Icons", "Missions"]);
+        let tables = schema.into_tables();
+        assert_eq!(tables[1].name(), "Missions");
+    }
+
+    #[test]
+    fn test_update_where() {
+        let mut table = table("ItemComponent");
+        table
+            .columns_mut()
+            .push(Column::from(("baseValue", ValueType::Integer)));
+        let rows = (1..=4).map(|id| Row::from(vec![Field::Integer(id), Field::Integer(id * 10)]));
+        table.buckets_mut().push(Bucket(rows.collect()));
+
+        let changed = table.update_where(
+            |row| matches!(row.fields()[1], Field::Integer(v) if v >= 20),
+            |row| {
+                if let Field::Integer(v) = &mut row.fields_mut()[1] {
+                    *v *= 2;
+                }
+            },
+        );
+        assert_eq!(changed, 3);
+        let values: Vec<_> = table
+            .rows(IterOrder::File)
+            .iter()
+            .map(|row| row.fields()[1].clone())
+            .collect();
+        let expected = [10, 40, 60, 80].iter().map(|v| Field::Integer(*v));
+        assert_eq!(values, expected.collect::<Vec<_>>());
+        assert_eq!(table.update_where(|_| false, |_| unreachable!()), 0);
+
+        // Changing the key moves the row to its new bucket
+        let buckets = table.buckets_mut();
+        let rows = std::mem::take(buckets[0].rows_mut());
+        buckets.resize_with(4, Bucket::default);
+        for row in rows {
+            let hash = pk_hash(&row.fields()[0]).unwrap();
+            buckets[fdb_bucket(hash, 4)].rows_mut().push(row);
         }
-        Schema { tables: tree }
+        let is_two = |row: &Row| row.fields()[0] == Field::Integer(2);
+        let changed = table.update_where(is_two, |row| row.fields_mut()[0] = Field::Integer(7));
+        assert_eq!(changed, 1);
+        let bucket = fdb_bucket(pk_hash(&Field::Integer(7)).unwrap(), 4);
+        let keys: Vec<_> = table.buckets()[bucket]
+            .rows_ref()
+            .iter()
+            .map(|r| &r.fields()[0])
+            .collect();
+        assert!(keys.contains(&&Field::Integer(7)), "{:?}", keys);
+        assert_eq!(table.rows(IterOrder::File).len(), 4);
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut schema = Schema::from(vec![table("Objects"), table("Icons")]);
+        assert_eq!(
+            schema.rename_table("Objects", "Icons"),
+            Err(RenameError::AlreadyExists("Icons".into()))
+        );
+        assert_eq!(
+            schema.rename_table("Missions", "Quests"),
+            Err(RenameError::NotFound("Missions".into()))
+        );
+        schema.rename_table("Objects", "Things").unwrap();
+        assert!(schema.table("Objects").is_none());
+        let things = schema.table_mut("Things").unwrap();
+        assert_eq!(things.name(), "Things");
+
+        things
+            .columns_mut()
+            .push(Column::from(("name", ValueType::Text)));
+        assert_eq!(
+            things.rename_column("id", "name"),
+            Err(RenameError::AlreadyExists("name".into()))
+        );
+        things.rename_column("id", "lot").unwrap();
+        assert_eq!(things.columns()[0].name.as_ref(), "lot");
+    }
+
+    #[test]
+    fn test_row_equality() {
+        use std::collections::HashSet;
+
+        assert_eq!(Field::Float(f32::NAN), Field::Float(f32::NAN));
+        assert_ne!(Field::Float(0.0), Field::Float(-0.0));
+        assert_ne!(Field::Integer(1), Field::BigInt(1));
+
+        let row = |v: f32| Row::from(vec![Field::Integer(1), Field::Float(v)]);
+        let rows = vec![row(f32::NAN), row(0.0), row(-0.0), row(f32::NAN), row(0.0)];
+        let unique: HashSet<_> = rows.into_iter().collect();
+        assert_eq!(unique.len(), 3);
+        assert!(unique.contains(&row(f32::NAN)));
+    }
+
+    #[test]
+    fn test_display_float() {
+        assert_eq!(Field::Float(1.0).to_string(), "1");
+        assert_eq!(Field::Float(0.3).to_string(), "0.3");
+        assert_eq!(Field::Float(-0.5).to_string(), "-0.5");
+        assert_eq!(format!("{:#}", Field::Float(0.3)), "0x3e99999a");
+        assert_eq!(format!("{:#}", Field::Integer(3)), "3");
     }
 }
@@ -0,0 +1,325 @@
+//! # CSV dialects
+//!
+//! There is no single CSV format: spreadsheet programs and databases disagree
+//! on line endings, on when fields are quoted and on how to write `NULL`. A
+//! [`CsvDialect`] describes one of these formats. It is used by the CSV export
+//! in [`stream`][super::stream] and by [`CsvDialect::read_records`], which
+//! reads such a file back.
+//!
+//! - [`CsvDialect::default`] quotes only where needed and ends lines with `\n`
+//! - [`CsvDialect::rfc4180`] is the same, but with `\r\n` as in RFC 4180
+//! - [`CsvDialect::excel`] additionally starts with a byte order mark, so that
+//!   Excel detects the encoding
+//! - [`CsvDialect::tsv`] separates fields by tabs, never quotes but escapes
+//!   special characters with `\` and writes `NULL` as `\N`
+//!
+//! ```
+//! use assembly_data::fdb::csv::CsvDialect;
+//!
+//! let tsv = CsvDialect::tsv();
+//! let mut out = String::new();
+//! tsv.write_field(&mut out, "a\tb");
+//! tsv.write_delimiter(&mut out);
+//! tsv.write_null(&mut out);
+//! tsv.end_record(&mut out);
+//! assert_eq!(out, "a\\tb\t\\N\n");
+//! assert_eq!(tsv.read_records(&out).unwrap(), vec![vec![Some("a\tb".into()), None]]);
+//! ```
+
+use assembly_core::displaydoc::Display;
+use thiserror::Error;
+
+/// When to put a field in quotes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Quoting {
+    /// Only fields with special characters, and text that looks like `NULL`
+    Minimal,
+    /// Every field that isn't `NULL`
+    Always,
+    /// Never, special characters are escaped with `\` instead
+    Never,
+}
+
+/// The line ending after every record
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    CrLf,
+}
+
+impl LineEnding {
+    /// The text of the line ending
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Errors from [`CsvDialect::read_records`]
+#[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CsvError {
+    /// The quoted field that starts in line {0} has no end
+    UnterminatedQuote(usize),
+    /// Unexpected character {1:?} after the quoted field in line {0}
+    AfterQuote(usize, char),
+}
+
+/// A CSV format, see the [module documentation](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvDialect {
+    /// The character between fields
+    pub delimiter: char,
+    /// The character around quoted fields
+    pub quote: char,
+    /// When to quote fields
+    pub quoting: Quoting,
+    /// The line ending after every record
+    pub line_ending: LineEnding,
+    /// The text of a `NULL` field
+    pub null: String,
+    /// Write a UTF-8 byte order mark before the first record
+    pub bom: bool,
+}
+
+impl Default for CsvDialect {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            quote: '"',
+            quoting: Quoting::Minimal,
+            line_ending: LineEnding::Lf,
+            null: String::new(),
+            bom: false,
+        }
+    }
+}
+
+impl CsvDialect {
+    /// The format of RFC 4180
+    pub fn rfc4180() -> Self {
+        Self {
+            line_ending: LineEnding::CrLf,
+            ..Self::default()
+        }
+    }
+
+    /// The format that Excel reads and writes
+    pub fn excel() -> Self {
+        Self {
+            bom: true,
+            ..Self::rfc4180()
+        }
+    }
+
+    /// Tab separated values, as read by e.g. PostgreSQL's `COPY`
+    pub fn tsv() -> Self {
+        Self {
+            delimiter: '\t',
+            quoting: Quoting::Never,
+            null: String::from("\\N"),
+            ..Self::default()
+        }
+    }
+
+    fn needs_quotes(&self, text: &str) -> bool {
+        match self.quoting {
+            Quoting::Always => true,
+            Quoting::Never => false,
+            Quoting::Minimal => {
+                text == self.null
+                    || text.contains(|c| {
+                        c == self.delimiter || c == self.quote || c == '\n' || c == '\r'
+                    })
+            }
+        }
+    }
+
+    /// Append a field with the given text
+    pub fn write_field(&self, out: &mut String, text: &str) {
+        if self.quoting == Quoting::Never {
+            for c in text.chars() {
+                match c {
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\t' => out.push_str("\\t"),
+                    c if c == self.delimiter => {
+                        out.push('\\');
+                        out.push(c);
+                    }
+                    c => out.push(c),
+                }
+            }
+        } else if self.needs_quotes(text) {
+            out.push(self.quote);
+            for c in text.chars() {
+                if c == self.quote {
+                    out.push(c);
+                }
+                out.push(c);
+            }
+            out.push(self.quote);
+        } else {
+            out.push_str(text);
+        }
+    }
+
+    /// Append a `NULL` field
+    pub fn write_null(&self, out: &mut String) {
+        out.push_str(&self.null);
+    }
+
+    /// Append the delimiter between two fields
+    pub fn write_delimiter(&self, out: &mut String) {
+        out.push(self.delimiter);
+    }
+
+    /// Append the end of a record
+    pub fn end_record(&self, out: &mut String) {
+        out.push_str(self.line_ending.as_str());
+    }
+
+    /// Split `text` into records of fields, with `None` for `NULL`
+    ///
+    /// Both line endings are accepted, and a byte order mark at the start is
+    /// skipped. Empty lines (e.g. between the tables of an export) end up as
+    /// records with a single unquoted empty field.
+    pub fn read_records(&self, text: &str) -> Result<Vec<Vec<Option<String>>>, CsvError> {
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        let mut records = Vec::new();
+        let mut record = Vec::new();
+        let mut chars = text.chars().peekable();
+        let mut line = 1;
+        while chars.peek().is_some() {
+            let mut field = String::new();
+            let mut quoted = false;
+            if self.quoting != Quoting::Never && chars.peek() == Some(&self.quote) {
+                chars.next();
+                quoted = true;
+                let start = line;
+                loop {
+                    match chars.next() {
+                        None => return Err(CsvError::UnterminatedQuote(start)),
+                        Some(c) if c == self.quote => {
+                            if chars.peek() == Some(&self.quote) {
+                                chars.next();
+                                field.push(c);
+                            } else {
+                                break;
+                            }
+                        }
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            field.push(c);
+                        }
+                    }
+                }
+            }
+            // The text as written, to recognize `NULL`
+            let mut raw = String::new();
+            let mut end_of_record = true;
+            while let Some(c) = chars.next() {
+                match c {
+                    '\r' if chars.peek() == Some(&'\n') => {}
+                    '\n' => break,
+                    c if c == self.delimiter => {
+                        end_of_record = false;
+                        break;
+                    }
+                    c if quoted => return Err(CsvError::AfterQuote(line, c)),
+                    '\\' if self.quoting == Quoting::Never => {
+                        raw.push(c);
+                        let escaped = chars.next();
+                        raw.extend(escaped);
+                        field.push(match escaped {
+                            Some('n') => '\n',
+                            Some('r') => '\r',
+                            Some('t') => '\t',
+                            Some(c) => c,
+                            None => '\\',
+                        });
+                    }
+                    c => {
+                        raw.push(c);
+                        field.push(c);
+                    }
+                }
+            }
+            let is_null = !quoted && raw == self.null;
+            record.push(if is_null { None } else { Some(field) });
+            if end_of_record {
+                records.push(std::mem::take(&mut record));
+                line += 1;
+            }
+        }
+        if !record.is_empty() {
+            // The text ended with a delimiter, i.e. an empty field
+            record.push(if self.null.is_empty() {
+                None
+            } else {
+                Some(String::new())
+            });
+            records.push(record);
+        }
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let records = vec![
+            vec![Some("id".to_owned()), Some("name".to_owned())],
+            vec![Some("1".to_owned()), Some("Brick \"2x4\", red".to_owned())],
+            vec![Some("2".to_owned()), None],
+            vec![Some("3".to_owned()), Some(String::new())],
+            vec![Some("4".to_owned()), Some("tab\tand\nline \\N".to_owned())],
+        ];
+        let dialects = [
+            CsvDialect::default(),
+            CsvDialect::rfc4180(),
+            CsvDialect::excel(),
+            CsvDialect::tsv(),
+            CsvDialect {
+                quoting: Quoting::Always,
+                delimiter: ';',
+                ..CsvDialect::default()
+            },
+        ];
+        for dialect in &dialects {
+            let mut out = String::new();
+            if dialect.bom {
+                out.push('\u{feff}');
+            }
+            for record in &records {
+                for (index, field) in record.iter().enumerate() {
+                    if index > 0 {
+                        dialect.write_delimiter(&mut out);
+                    }
+                    match field {
+                        Some(text) => dialect.write_field(&mut out, text),
+                        None => dialect.write_null(&mut out),
+                    }
+                }
+                dialect.end_record(&mut out);
+            }
+            assert_eq!(dialect.read_records(&out).unwrap(), records, "{:?}", out);
+        }
+
+        let rfc = CsvDialect::rfc4180();
+        assert_eq!(
+            rfc.read_records("1,\"open\r\n"),
+            Err(CsvError::UnterminatedQuote(1))
+        );
+    }
+}
@@ -0,0 +1,297 @@
+//! # Heuristic discovery of foreign keys
+//!
+//! This module compares the values of the integer columns of a database to
+//! find columns that probably refer to another table. For every pair of a
+//! source column and a key-like target column, it computes
+//!
+//! - the *containment*, i.e. the share of the distinct positive values of the
+//!   source that also appear in the target, and
+//! - the *name similarity* between the source column and the target table or
+//!   column, e.g. `skillID` and `SkillBehavior.skillID`.
+//!
+//! [`suggest`] returns the pairs ranked by a combination of both, which can be
+//! reviewed and turned into a [`Relations`] configuration. [`Relations::discover`]
+//! does this automatically, keeping only the best target for each column.
+//!
+//! A target is key-like if it is the first column of its table (the one used
+//! for the hash buckets), or if no value appears in it twice.
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashSet},
+    fmt,
+};
+
+use assembly_core::buffer::CastError;
+
+use super::{
+    cache::is_fk_column,
+    common::ValueType,
+    mem::{Table, Tables},
+    relations::{ForeignKey, Relations},
+};
+
+/// Options for [`suggest`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SuggestOptions {
+    /// The minimum containment of a suggestion, between `0.0` and `1.0`
+    pub min_containment: f64,
+    /// The minimum number of distinct positive values in the source column
+    pub min_distinct: usize,
+}
+
+impl Default for SuggestOptions {
+    fn default() -> Self {
+        Self {
+            min_containment: 0.9,
+            min_distinct: 2,
+        }
+    }
+}
+
+/// A possible foreign key, with the evidence for it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FkSuggestion {
+    /// The suggested foreign key
+    pub key: ForeignKey,
+    /// The number of distinct positive values in the source column
+    pub distinct: usize,
+    /// The share of these values that appear in the target column
+    pub containment: f64,
+    /// The similarity of the names, between `0.0` and `1.0`
+    pub name_similarity: f64,
+}
+
+impl FkSuggestion {
+    /// The score used for ranking, between `0.0` and `1.0`
+    pub fn score(&self) -> f64 {
+        0.7 * self.containment + 0.3 * self.name_similarity
+    }
+}
+
+/// Formats the suggestion as `0.95 A.b -> C.d (100% contained, name 0.83)`
+impl fmt::Display for FkSuggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.2} {} ({:.0}% contained, name {:.2})",
+            self.score(),
+            self.key,
+            self.containment * 100.0,
+            self.name_similarity
+        )
+    }
+}
+
+/// The distinct values of one integer column
+struct ColumnValues {
+    table: String,
+    column: String,
+    values: HashSet<i32>,
+    is_key: bool,
+}
+
+fn integer_columns(table: &Table<'_>) -> Vec<ColumnValues> {
+    let mut columns = Vec::new();
+    for (index, column) in table.column_iter().enumerate() {
+        if column.value_type() != ValueType::Integer {
+            continue;
+        }
+        let mut values = HashSet::new();
+        let mut unique = true;
+        for row in table.row_iter() {
+            if let Some(value) = row.field_at(index).and_then(|f| f.into_opt_integer()) {
+                unique &= values.insert(value);
+            }
+        }
+        values.retain(|&v| v > 0);
+        columns.push(ColumnValues {
+            table: table.name().into_owned(),
+            column: column.name().into_owned(),
+            values,
+            is_key: index == 0 || unique,
+        });
+    }
+    columns
+}
+
+/// Reduce a name to its stem, e.g. `Objects` to `object` and `skill_id` to `skill`
+fn stem(name: &str) -> String {
+    let mut stem: String = name
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    for suffix in &["id", "lot"] {
+        if stem.len() > suffix.len() && stem.ends_with(suffix) {
+            stem.truncate(stem.len() - suffix.len());
+            break;
+        }
+    }
+    if stem.len() > 3 && stem.ends_with('s') {
+        stem.pop();
+    }
+    stem
+}
+
+/// The Sørensen–Dice coefficient of the character pairs of two strings
+fn dice(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let pairs =
+        |s: &str| -> Vec<(u8, u8)> { s.as_bytes().windows(2).map(|w| (w[0], w[1])).collect() };
+    let left = pairs(a);
+    let mut right = pairs(b);
+    let total = left.len() + right.len();
+    if total == 0 {
+        return 0.0;
+    }
+    let mut matches = 0;
+    for pair in left {
+        if let Some(pos) = right.iter().position(|p| *p == pair) {
+            right.swap_remove(pos);
+            matches += 1;
+        }
+    }
+    (2 * matches) as f64 / total as f64
+}
+
+/// Compare the name of a column to the name of a target table and column
+///
+/// Returns `1.0` if the stems are equal and `0.0` if they have no character
+/// pairs in common.
+pub fn name_similarity(column: &str, target_table: &str, target_column: &str) -> f64 {
+    let column = stem(column);
+    let by_column = dice(&column, &stem(target_column));
+    let by_table = dice(&column, &stem(target_table));
+    by_column.max(by_table)
+}
+
+/// Find likely foreign keys, ranked by [`FkSuggestion::score`]
+///
+/// A column is never suggested as a reference to itself. There may be
+/// multiple suggestions for the same column.
+pub fn suggest(
+    tables: Tables<'_>,
+    options: &SuggestOptions,
+) -> Result<Vec<FkSuggestion>, CastError> {
+    let mut columns = Vec::new();
+    for table in tables.iter() {
+        columns.extend(integer_columns(&table?));
+    }
+
+    let mut suggestions = Vec::new();
+    for source in &columns {
+        let distinct = source.values.len();
+        if distinct < options.min_distinct.max(1) {
+            continue;
+        }
+        for target in columns.iter().filter(|c| c.is_key) {
+            if target.table == source.table && target.column == source.column {
+                continue;
+            }
+            let found = source
+                .values
+                .iter()
+                .filter(|v| target.values.contains(v))
+                .count();
+            let containment = found as f64 / distinct as f64;
+            if containment < options.min_containment {
+                continue;
+            }
+            suggestions.push(FkSuggestion {
+                key: ForeignKey::new(&source.table, &source.column, &target.table, &target.column),
+                distinct,
+                containment,
+                name_similarity: name_similarity(&source.column, &target.table, &target.column),
+            });
+        }
+    }
+
+    suggestions.sort_by(|a, b| {
+        let by_score = b.score().partial_cmp(&a.score()).unwrap_or(Ordering::Equal);
+        by_score.then_with(|| a.key.cmp(&b.key))
+    });
+    Ok(suggestions)
+}
+
+impl Relations {
+    /// Discover foreign keys by comparing the values of columns
+    ///
+    /// This keeps the best [`suggest`]ion for each column whose name looks like
+    /// a foreign key (see [`is_fk_column`]) or is similar to the name of the
+    /// target, i.e. has a [`name_similarity`] of at least `0.5`.
+    pub fn discover(tables: Tables<'_>, min_ratio: f64) -> Result<Self, CastError> {
+        let options = SuggestOptions {
+            min_containment: min_ratio,
+            ..SuggestOptions::default()
+        };
+        let mut best = BTreeMap::new();
+        for suggestion in suggest(tables, &options)? {
+            if !is_fk_column(&suggestion.key.column) && suggestion.name_similarity < 0.5 {
+                continue;
+            }
+            let source = (suggestion.key.table.clone(), suggestion.key.column.clone());
+            best.entry(source).or_insert(suggestion.key);
+        }
+        Ok(best.into_values().collect())
+    }
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{common::ValueType, core::Field, mem, testing::SampleDatabase};
+
+    #[test]
+    fn test_name_similarity() {
+        assert_eq!(name_similarity("skillID", "SkillBehavior", "skillID"), 1.0);
+        assert_eq!(
+            name_similarity("objectTemplate", "Objects", "id"),
+            10.0 / 18.0
+        );
+        assert_eq!(name_similarity("count", "Objects", "id"), 0.0);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let db = SampleDatabase::new()
+            .table("SkillBehavior", &[("skillID", ValueType::Integer)])
+            .buckets(4);
+        let db = (1..=4).fold(db, |db, id| db.row(vec![Field::Integer(id)]));
+        let columns = [
+            ("objectTemplate", ValueType::Integer),
+            ("skillID", ValueType::Integer),
+            ("castOnType", ValueType::Integer),
+        ];
+        let db = db.table("ObjectSkills", &columns).buckets(4);
+        let db = [(7, 1), (8, 2), (9, 2)]
+            .iter()
+            .fold(db, |db, &(lot, skill)| {
+                db.row(vec![
+                    Field::Integer(lot),
+                    Field::Integer(skill),
+                    Field::Integer(skill),
+                ])
+            });
+        let buf = db.build();
+        let tables = mem::Database::new(&buf).tables().unwrap();
+
+        let suggestions = suggest(tables, &SuggestOptions::default()).unwrap();
+        let keys: Vec<String> = suggestions.iter().map(|s| s.key.to_string()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "ObjectSkills.skillID -> SkillBehavior.skillID",
+                "ObjectSkills.castOnType -> SkillBehavior.skillID",
+            ]
+        );
+        assert_eq!(suggestions[0].containment, 1.0);
+        assert_eq!(suggestions[0].score(), 1.0);
+
+        let relations = Relations::discover(tables, 0.9).unwrap();
+        let keys: Vec<String> = relations.iter().map(ToString::to_string).collect();
+        assert_eq!(keys, vec!["ObjectSkills.skillID -> SkillBehavior.skillID"]);
+    }
+}
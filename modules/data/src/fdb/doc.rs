@@ -0,0 +1,172 @@
+//! # Reference documentation for a database
+//!
+//! This module renders a list of all tables and their columns, optionally with
+//! the number of rows in every table, as Markdown or HTML. This can be used to
+//! publish documentation for a specific version of the client database.
+
+use std::io::{self, Write};
+
+use assembly_core::{buffer::CastError, displaydoc::Display};
+use thiserror::Error;
+
+use super::mem::{Table, Tables};
+
+/// The output format of the documentation
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DocFormat {
+    /// Markdown with one table per database table
+    Markdown,
+    /// A standalone HTML document
+    Html,
+}
+
+/// Options for [`render_markdown`] and [`render`]
+#[derive(Debug, Clone)]
+pub struct DocOptions {
+    /// The title of the document
+    pub title: String,
+    /// Whether to count the rows of every table
+    ///
+    /// Set this to `false` to only document the schema, which is much faster.
+    pub row_counts: bool,
+    /// The output format
+    pub format: DocFormat,
+}
+
+impl Default for DocOptions {
+    fn default() -> Self {
+        Self {
+            title: String::from("Database Reference"),
+            row_counts: true,
+            format: DocFormat::Markdown,
+        }
+    }
+}
+
+/// Errors when rendering the documentation
+#[derive(Debug, Error, Display)]
+#[non_exhaustive]
+pub enum DocError {
+    /// Failed to write the output: {0}
+    Io(#[from] io::Error),
+    /// Failed to read a table: {0}
+    Cast(#[from] CastError),
+}
+
+/// Render the Markdown documentation for `tables`
+///
+/// This ignores the `format` of `opts`.
+pub fn render_markdown<W: Write>(
+    out: &mut W,
+    tables: Tables<'_>,
+    opts: &DocOptions,
+) -> Result<(), DocError> {
+    writeln!(out, "# {}", opts.title)?;
+    writeln!(out)?;
+    writeln!(out, "{} tables", tables.len())?;
+    for table in tables.iter() {
+        let table = table?;
+        writeln!(out)?;
+        writeln!(out, "## {}", table.name())?;
+        writeln!(out)?;
+        write_summary(out, &table, opts)?;
+        writeln!(out)?;
+        writeln!(out, "| Column | Type |")?;
+        writeln!(out, "|--------|------|")?;
+        for column in table.column_iter() {
+            writeln!(out, "| `{}` | {} |", column.name(), column.value_type())?;
+        }
+    }
+    Ok(())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render the HTML documentation for `tables`
+///
+/// This ignores the `format` of `opts`.
+pub fn render_html<W: Write>(
+    out: &mut W,
+    tables: Tables<'_>,
+    opts: &DocOptions,
+) -> Result<(), DocError> {
+    let title = escape_html(&opts.title);
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(
+        out,
+        "<html><head><meta charset=\"utf-8\"><title>{}</title></head><body>",
+        title
+    )?;
+    writeln!(out, "<h1>{}</h1>", title)?;
+    writeln!(out, "<p>{} tables</p>", tables.len())?;
+    for table in tables.iter() {
+        let table = table?;
+        let name = escape_html(&table.name());
+        writeln!(out, "<h2 id=\"{0}\">{0}</h2>", name)?;
+        write!(out, "<p>")?;
+        write_summary(out, &table, opts)?;
+        writeln!(out, "</p>")?;
+        writeln!(out, "<table><tr><th>Column</th><th>Type</th></tr>")?;
+        for column in table.column_iter() {
+            let col = escape_html(&column.name());
+            writeln!(
+                out,
+                "<tr><td><code>{}</code></td><td>{}</td></tr>",
+                col,
+                column.value_type()
+            )?;
+        }
+        writeln!(out, "</table>")?;
+    }
+    writeln!(out, "</body></html>")?;
+    Ok(())
+}
+
+fn write_summary<W: Write>(out: &mut W, table: &Table<'_>, opts: &DocOptions) -> io::Result<()> {
+    write!(
+        out,
+        "{} columns, {} buckets",
+        table.column_count(),
+        table.bucket_count()
+    )?;
+    if opts.row_counts {
+        write!(out, ", {} rows", table.row_iter().count())?;
+    }
+    if opts.format == DocFormat::Markdown {
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Render the documentation for `tables` in the format given by `opts`
+pub fn render<W: Write>(
+    out: &mut W,
+    tables: Tables<'_>,
+    opts: &DocOptions,
+) -> Result<(), DocError> {
+    match opts.format {
+        DocFormat::Markdown => render_markdown(out, tables, opts),
+        DocFormat::Html => render_html(out, tables, opts),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fdb::mem::Database;
+
+    #[test]
+    fn test_render_markdown_empty() {
+        let file: &[u8] = &[0, 0, 0, 0, 8, 0, 0, 0];
+        let tables = Database::new(file).tables().unwrap();
+        let mut out = Vec::new();
+        render_markdown(&mut out, tables, &DocOptions::default()).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text, "# Database Reference\n\n0 tables\n");
+    }
+}
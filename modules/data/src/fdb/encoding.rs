@@ -0,0 +1,246 @@
+//! # Audit of the encoding of text fields
+//!
+//! Strings in the database are stored as null-terminated latin-1, but that is
+//! only a convention. In particular, it is not documented what `VARCHAR`
+//! columns actually contain: mostly plain text, but some of them hold XML,
+//! base64 or other binary data.
+//!
+//! This module classifies the value of every `TEXT` and `VARCHAR` field as a
+//! [`TextClass`], and collects the counts per column, so that this can be
+//! documented for each table. A column is flagged as suspicious if any of its
+//! values are binary, or if all of its non-empty values look like base64.
+
+use std::fmt;
+
+use assembly_core::buffer::CastError;
+
+pub use super::base64::looks_like_base64;
+use super::{
+    common::ValueType,
+    mem::{Database, Field, Table},
+};
+
+/// The kind of content of a string
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TextClass {
+    /// Only printable ASCII characters, tabs and line breaks
+    Ascii,
+    /// Only printable latin-1 characters, tabs and line breaks
+    Latin1,
+    /// Some control character (including `0x80` to `0x9F`)
+    Binary,
+}
+
+fn is_ascii_text(byte: u8) -> bool {
+    matches!(byte, b'\t' | b'\n' | b'\r' | 0x20..=0x7E)
+}
+
+/// Classify the bytes of a string
+pub fn classify(bytes: &[u8]) -> TextClass {
+    let mut class = TextClass::Ascii;
+    for &byte in bytes {
+        if is_ascii_text(byte) {
+            continue;
+        } else if byte >= 0xA0 {
+            class = TextClass::Latin1;
+        } else {
+            return TextClass::Binary;
+        }
+    }
+    class
+}
+
+/// The classes of the strings in one column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnEncoding {
+    /// The name of the column
+    pub name: String,
+    /// The declared type of the column
+    pub value_type: ValueType,
+    /// The number of empty strings
+    pub empty: usize,
+    /// The number of non-empty [`TextClass::Ascii`] strings
+    pub ascii: usize,
+    /// The number of [`TextClass::Latin1`] strings
+    pub latin1: usize,
+    /// The number of [`TextClass::Binary`] strings
+    pub binary: usize,
+    /// The number of strings that look like base64, see [`looks_like_base64`]
+    pub base64: usize,
+}
+
+impl ColumnEncoding {
+    /// The number of strings in the column
+    pub fn total(&self) -> usize {
+        self.empty + self.ascii + self.latin1 + self.binary
+    }
+
+    /// The most general class of all strings, if there are any
+    pub fn class(&self) -> Option<TextClass> {
+        if self.binary > 0 {
+            Some(TextClass::Binary)
+        } else if self.latin1 > 0 {
+            Some(TextClass::Latin1)
+        } else if self.total() > 0 {
+            Some(TextClass::Ascii)
+        } else {
+            None
+        }
+    }
+
+    /// Check whether all non-empty strings look like base64
+    pub fn is_base64(&self) -> bool {
+        self.base64 > 0 && self.base64 == self.total() - self.empty
+    }
+
+    /// Check whether the column may contain something other than text
+    pub fn is_suspicious(&self) -> bool {
+        self.binary > 0 || self.is_base64()
+    }
+}
+
+/// The encoding of the text columns of one table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableEncoding {
+    /// The name of the table
+    pub name: String,
+    /// The `TEXT` and `VARCHAR` columns, and columns with such fields
+    pub columns: Vec<ColumnEncoding>,
+}
+
+/// The encoding of the text columns of a database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodingAudit {
+    /// The tables with at least one text column
+    pub tables: Vec<TableEncoding>,
+}
+
+impl EncodingAudit {
+    /// Iterate over all suspicious columns, with the name of their table
+    pub fn suspicious(&self) -> impl Iterator<Item = (&str, &ColumnEncoding)> {
+        self.tables.iter().flat_map(|t| {
+            let columns = t.columns.iter().filter(|c| c.is_suspicious());
+            columns.map(move |c| (t.name.as_str(), c))
+        })
+    }
+}
+
+/// Classify the strings of every text column of a table
+pub fn audit_table(table: &Table<'_>) -> TableEncoding {
+    let mut columns: Vec<_> = table
+        .column_iter()
+        .map(|c| ColumnEncoding {
+            name: c.name().into_owned(),
+            value_type: c.value_type(),
+            empty: 0,
+            ascii: 0,
+            latin1: 0,
+            binary: 0,
+            base64: 0,
+        })
+        .collect();
+    for row in table.row_iter() {
+        for (column, field) in columns.iter_mut().zip(row.field_iter()) {
+            let bytes = match field {
+                Field::Text(s) | Field::VarChar(s) => s.as_bytes(),
+                _ => continue,
+            };
+            match classify(bytes) {
+                _ if bytes.is_empty() => column.empty += 1,
+                TextClass::Ascii => column.ascii += 1,
+                TextClass::Latin1 => column.latin1 += 1,
+                TextClass::Binary => column.binary += 1,
+            }
+            if looks_like_base64(bytes) {
+                column.base64 += 1;
+            }
+        }
+    }
+    let is_text = |t: ValueType| matches!(t, ValueType::Text | ValueType::VarChar);
+    columns.retain(|c| is_text(c.value_type) || c.total() > 0);
+    TableEncoding {
+        name: table.name().into_owned(),
+        columns,
+    }
+}
+
+/// Classify the strings of every text column of a database
+pub fn audit(db: Database<'_>) -> Result<EncodingAudit, CastError> {
+    let mut tables = Vec::new();
+    for table in db.tables()?.iter() {
+        let table = audit_table(&table?);
+        if !table.columns.is_empty() {
+            tables.push(table);
+        }
+    }
+    Ok(EncodingAudit { tables })
+}
+
+impl fmt::Display for EncodingAudit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<40} {:>8} {:>8} {:>8} {:>8} {:>8}",
+            "TABLE / COLUMN", "EMPTY", "ASCII", "LATIN-1", "BINARY", "BASE64"
+        )?;
+        for table in &self.tables {
+            write!(f, "\n{}", table.name)?;
+            for c in &table.columns {
+                write!(
+                    f,
+                    "\n  {:<38} {:>8} {:>8} {:>8} {:>8} {:>8}",
+                    c.name, c.empty, c.ascii, c.latin1, c.binary, c.base64
+                )?;
+                if c.is_suspicious() {
+                    write!(f, " (suspicious)")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{core, testing::SampleDatabase};
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(b"Hello\tWorld\r\n"), TextClass::Ascii);
+        assert_eq!(classify(b"Caf\xE9"), TextClass::Latin1);
+        assert_eq!(classify(b"\x01\x02"), TextClass::Binary);
+        assert_eq!(classify(b"\x93quoted\x94"), TextClass::Binary);
+        assert!(looks_like_base64(b"SGVsbG8sIFdvcmxkIQ=="));
+        assert!(!looks_like_base64(b"Hello, World!!!!"));
+        assert!(!looks_like_base64(b"SGVsbG8="));
+    }
+
+    #[test]
+    fn test_audit() {
+        let columns = [
+            ("id", ValueType::Integer),
+            ("name", ValueType::Text),
+            ("data", ValueType::VarChar),
+        ];
+        let db = SampleDatabase::new().table("Objects", &columns);
+        let rows = [("Brick", "SGVsbG8sIFdvcmxkIQ=="), ("", "AAAAAAAAAAAAAAAA")];
+        let db = rows.iter().enumerate().fold(db, |db, (id, (name, data))| {
+            db.row(vec![
+                core::Field::Integer(id as i32),
+                core::Field::Text(name.to_string()),
+                core::Field::VarChar(data.to_string()),
+            ])
+        });
+        let buf = db.build();
+
+        let audit = audit(Database::new(&buf)).unwrap();
+        let columns = &audit.tables[0].columns;
+        assert_eq!(columns.len(), 2);
+        assert_eq!((columns[0].empty, columns[0].ascii), (1, 1));
+        assert_eq!(columns[0].class(), Some(TextClass::Ascii));
+        assert!(columns[1].is_base64());
+        let suspicious: Vec<_> = audit.suspicious().map(|(t, c)| (t, &c.name[..])).collect();
+        assert_eq!(suspicious, vec![("Objects", "data")]);
+    }
+}
@@ -0,0 +1,364 @@
+//! # Nested exports of related rows
+//!
+//! Most tooling doesn't care about single tables, but about everything the
+//! database knows about one object template (LOT): the row in `Objects`, the
+//! components from `ComponentsRegistry` and the rows of the component tables,
+//! as well as the translated names from the locale.
+//!
+//! This module gathers that data into plain structures that implement
+//! [`serde::Serialize`], with rows as JSON objects keyed by column name.
+//!
+//! To make these dumps readable, the exporters accept an optional
+//! [`Localization`], which attaches the translated texts for the configured
+//! tables to each row, under the [`LOCALE_KEY`] entry. The texts are looked up
+//! in the locale as `{table}_{id}_{key}`, e.g. `Objects_1727_name`.
+//!
+//! Integer columns with symbolic values can be annotated with their names from
+//! a [`ValueNames`] registry using [`attach_names`].
+//!
+//! This module is only available with the `fdb-mem` and `serde-derives` features.
+
+use std::{collections::BTreeMap, convert::TryFrom};
+
+use assembly_core::buffer::CastError;
+use serde::Serialize;
+use serde_json::{Map, Value as JsonValue};
+
+use crate::xml::locale_key::LocaleKey;
+
+use super::{
+    mem::{Row, Table, Tables},
+    names::ValueNames,
+};
+
+/// A row, as a map from column name to value
+pub type Record = Map<String, JsonValue>;
+
+/// A map from locale key (e.g. `Objects_1727_name`) to the translated text
+pub type LocaleMap = BTreeMap<String, String>;
+
+/// The key under which the translated texts are attached to a [`Record`]
+pub const LOCALE_KEY: &str = "_locale";
+
+/// The key under which the names of symbolic values are attached to a [`Record`]
+pub const NAMES_KEY: &str = "_names";
+
+/// A locale with the list of entries to attach to the rows of each table
+#[derive(Debug, Clone)]
+pub struct Localization<'a> {
+    map: &'a LocaleMap,
+    tables: BTreeMap<String, Vec<String>>,
+}
+
+impl<'a> Localization<'a> {
+    /// Create a new instance that doesn't attach anything
+    pub fn new(map: &'a LocaleMap) -> Self {
+        Self {
+            map,
+            tables: BTreeMap::new(),
+        }
+    }
+
+    /// Attach the `name` and `description` of entries in `Objects`
+    pub fn objects(map: &'a LocaleMap) -> Self {
+        Self::new(map).with_table("Objects", &["name", "description"])
+    }
+
+    /// Attach the entries with the given keys to the rows of `table`
+    pub fn with_table(mut self, table: &str, keys: &[&str]) -> Self {
+        let keys = keys.iter().map(|k| k.to_string()).collect();
+        self.tables.insert(table.to_string(), keys);
+        self
+    }
+
+    /// Get the underlying locale
+    pub fn map(&self) -> &'a LocaleMap {
+        self.map
+    }
+
+    /// Get the keys that are attached to the rows of `table`
+    pub fn keys(&self, table: &str) -> &[String] {
+        self.tables.get(table).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Look up the translated text for a single key
+    pub fn get(&self, table: &str, id: &JsonValue, key: &str) -> Option<&'a str> {
+        let id = match id {
+            JsonValue::Number(n) => u32::try_from(n.as_u64()?).ok()?,
+            JsonValue::String(s) => s.parse().ok()?,
+            _ => return None,
+        };
+        let locale_key = LocaleKey::Table {
+            table: table.to_string(),
+            id,
+            column: key.to_string(),
+        };
+        self.map.get(&locale_key.to_string()).map(String::as_str)
+    }
+
+    /// Attach the configured entries for the row with `id` to the record
+    ///
+    /// Nothing is added if none of the entries exist in the locale.
+    pub fn attach(&self, table: &str, id: &JsonValue, record: &mut Record) {
+        let entries: Record = self
+            .keys(table)
+            .iter()
+            .filter_map(|key| {
+                let text = self.get(table, id, key)?;
+                Some((key.clone(), JsonValue::from(text)))
+            })
+            .collect();
+        if !entries.is_empty() {
+            record.insert(LOCALE_KEY.to_string(), JsonValue::Object(entries));
+        }
+    }
+}
+
+/// Attach the names of the integer values in `record` for which `names` has one
+///
+/// The names are added as a map from column name to value name under the
+/// [`NAMES_KEY`] entry. Nothing is added if none of the values has a name.
+pub fn attach_names(names: &ValueNames, table: &str, record: &mut Record) {
+    let entries: Record = record
+        .iter()
+        .filter_map(|(column, value)| {
+            let value = i32::try_from(value.as_i64()?).ok()?;
+            let name = names.get(table, column, value)?;
+            Some((column.clone(), JsonValue::from(name)))
+        })
+        .collect();
+    if !entries.is_empty() {
+        record.insert(NAMES_KEY.to_string(), JsonValue::Object(entries));
+    }
+}
+
+/// The tables of the well-known component types
+///
+/// Components that are not in this list (or have no table) are exported
+/// without rows.
+pub const COMPONENT_TABLES: &[(i32, &str)] = &[
+    (1, "ControllablePhysicsComponent"),
+    (2, "RenderComponent"),
+    (3, "SimplePhysicsComponent"),
+    (5, "ScriptComponent"),
+    (6, "BouncerComponent"),
+    (7, "DestructibleComponent"),
+    (11, "ItemComponent"),
+    (16, "VendorComponent"),
+    (17, "InventoryComponent"),
+    (23, "CollectibleComponent"),
+    (26, "PetComponent"),
+    (35, "MinifigComponent"),
+    (40, "PhantomPhysicsComponent"),
+    (48, "RebuildComponent"),
+    (53, "PackageComponent"),
+    (60, "BaseCombatAIComponent"),
+];
+
+/// Get the name of the table for a component type
+pub fn component_table(component_type: i32) -> Option<&'static str> {
+    COMPONENT_TABLES
+        .iter()
+        .find(|(t, _)| *t == component_type)
+        .map(|(_, name)| *name)
+}
+
+/// A component of an object, with the rows from its table
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ComponentBundle {
+    /// The type of the component
+    pub component_type: i32,
+    /// The ID of the component in its table
+    pub component_id: i32,
+    /// The name of the table, if the type is known
+    pub table: Option<&'static str>,
+    /// The rows of the table with the ID of the component
+    pub rows: Vec<Record>,
+}
+
+/// Everything the database knows about an object template
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ObjectBundle {
+    /// The ID of the object template (LOT)
+    pub lot: i32,
+    /// The row in the `Objects` table
+    pub object: Record,
+    /// The components from the `ComponentsRegistry` table
+    pub components: Vec<ComponentBundle>,
+    /// The locale entries for this object
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub locale: BTreeMap<String, String>,
+}
+
+/// Convert a row to a record, using the column names of the table
+pub fn record(table: &Table<'_>, row: Row<'_>) -> Record {
+    table
+        .column_iter()
+        .zip(row.field_iter())
+        .map(|(column, field)| (column.name().into_owned(), JsonValue::from(&field)))
+        .collect()
+}
+
+fn localized_record(
+    table: &Table<'_>,
+    row: Row<'_>,
+    localization: Option<&Localization<'_>>,
+) -> Record {
+    let mut record = record(table, row);
+    if let (Some(loc), Some(id)) = (localization, row.field_at(0)) {
+        loc.attach(&table.name(), &JsonValue::from(&id), &mut record);
+    }
+    record
+}
+
+/// Export all rows of a table, with the configured locale entries
+pub fn table_records(table: &Table<'_>, localization: Option<&Localization<'_>>) -> Vec<Record> {
+    table
+        .row_iter()
+        .map(|row| localized_record(table, row, localization))
+        .collect()
+}
+
+fn records(table: &Table<'_>, id: i32, localization: Option<&Localization<'_>>) -> Vec<Record> {
+    table
+        .index_iter(id as u32)
+        .map(|row| localized_record(table, row, localization))
+        .collect()
+}
+
+/// Gather the object template `lot` with its components and locale entries
+///
+/// Returns `None` if there is no `Objects` table, or no row for `lot` in it.
+/// If a `localization` is given, all entries that start with `Objects_{lot}_`
+/// are included in the bundle, and the configured entries are attached to the
+/// rows of the object and its components.
+pub fn object_bundle(
+    tables: Tables<'_>,
+    lot: i32,
+    localization: Option<&Localization<'_>>,
+) -> Result<Option<ObjectBundle>, CastError> {
+    let objects = match tables.by_name("Objects").transpose()? {
+        Some(table) => table,
+        None => return Ok(None),
+    };
+    let object = match objects.index_iter(lot as u32).next() {
+        Some(row) => localized_record(&objects, row, localization),
+        None => return Ok(None),
+    };
+
+    let mut components = Vec::new();
+    if let Some(registry) = tables.by_name("ComponentsRegistry").transpose()? {
+        for row in registry.index_iter(lot as u32) {
+            let (component_type, component_id) = match (row.field_at(1), row.field_at(2)) {
+                (Some(t), Some(i)) => match (t.as_integer(), i.as_integer()) {
+                    (Some(t), Some(i)) => (t, i),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let table = component_table(component_type);
+            let rows = match table.and_then(|name| tables.by_name(name)) {
+                Some(t) => records(&t?, component_id, localization),
+                None => Vec::new(),
+            };
+            components.push(ComponentBundle {
+                component_type,
+                component_id,
+                table,
+                rows,
+            });
+        }
+    }
+
+    // With an empty column, this is the common prefix of all keys of the row
+    let prefix = LocaleKey::Table {
+        table: String::from("Objects"),
+        id: lot as u32,
+        column: String::new(),
+    }
+    .to_string();
+    let locale = localization
+        .map(|loc| {
+            loc.map()
+                .range(prefix.clone()..)
+                .take_while(|(k, _)| k.starts_with(&prefix))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(Some(ObjectBundle {
+        lot,
+        object,
+        components,
+        locale,
+    }))
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{common::ValueType, core::Field, mem, testing::SampleDatabase};
+
+    #[test]
+    fn test_object_bundle() {
+        let int = Field::Integer;
+        let buf = SampleDatabase::new()
+            .table(
+                "Objects",
+                &[("id", ValueType::Integer), ("name", ValueType::Text)],
+            )
+            .buckets(4)
+            .row(vec![int(6), Field::Text("Brick".into())])
+            .table(
+                "ComponentsRegistry",
+                &[
+                    ("id", ValueType::Integer),
+                    ("component_type", ValueType::Integer),
+                    ("component_id", ValueType::Integer),
+                ],
+            )
+            .buckets(4)
+            .row(vec![int(6), int(2), int(10)])
+            .row(vec![int(6), int(99), int(1)])
+            .table(
+                "RenderComponent",
+                &[("id", ValueType::Integer), ("icon_asset", ValueType::Text)],
+            )
+            .buckets(4)
+            .row(vec![int(10), Field::Text("brick.dds".into())])
+            .build();
+        let tables = mem::Database::new(&buf).tables().unwrap();
+
+        let mut locale = LocaleMap::new();
+        locale.insert("Objects_6_name".into(), "Brick".into());
+        locale.insert("Objects_60_name".into(), "Other".into());
+
+        locale.insert("RenderComponent_10_name".into(), "Brick Render".into());
+        let loc = Localization::objects(&locale).with_table("RenderComponent", &["name"]);
+
+        let bundle = object_bundle(tables, 6, Some(&loc)).unwrap().unwrap();
+        assert_eq!(bundle.object["name"], JsonValue::from("Brick"));
+        assert_eq!(bundle.object[LOCALE_KEY]["name"], JsonValue::from("Brick"));
+        assert_eq!(
+            bundle.components[0].rows[0][LOCALE_KEY]["name"],
+            JsonValue::from("Brick Render")
+        );
+        assert_eq!(bundle.components.len(), 2);
+        assert_eq!(bundle.components[0].table, Some("RenderComponent"));
+        assert_eq!(
+            bundle.components[0].rows[0]["icon_asset"],
+            JsonValue::from("brick.dds")
+        );
+        assert_eq!(bundle.components[1].table, None);
+        assert!(bundle.components[1].rows.is_empty());
+        assert_eq!(bundle.locale.len(), 1);
+
+        assert!(object_bundle(tables, 7, None).unwrap().is_none());
+
+        let names = ValueNames::new().with_names("RenderComponent", "id", &[(10, "Brick")]);
+        let mut record = bundle.components[0].rows[0].clone();
+        attach_names(&names, "RenderComponent", &mut record);
+        assert_eq!(record[NAMES_KEY]["id"], JsonValue::from("Brick"));
+    }
+}
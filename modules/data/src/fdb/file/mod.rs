@@ -239,6 +239,27 @@ impl TryFrom<FDBFieldData> for FDBFieldValue {
     }
 }
 
+// SAFETY: the `bytemuck` derive checks that these structs are `repr(C)`, have
+// no padding and only contain fields that are valid for any bit pattern.
+macro_rules! impl_pod {
+    ($($ty:ty),*) => {
+        $(unsafe impl assembly_core::buffer::Pod for $ty {})*
+    };
+}
+
+impl_pod!(
+    ArrayHeader,
+    FDBHeader,
+    FDBTableHeader,
+    FDBTableDefHeader,
+    FDBColumnHeader,
+    FDBTableDataHeader,
+    FDBBucketHeader,
+    FDBRowHeaderListEntry,
+    FDBRowHeader,
+    FDBFieldData
+);
+
 #[cfg(test)]
 mod tests {
     use super::*;
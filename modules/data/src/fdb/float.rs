@@ -0,0 +1,114 @@
+//! # Lossless formatting of `FLOAT` fields
+//!
+//! The fields of a `FLOAT` column are 32 bit floats. Converting them to `f64`
+//! first (e.g. for JSON or SQLite) or printing them with too few digits makes
+//! `0.3` come out as `0.30000001192092896` or as a number that reads back as
+//! a different float. All exporters of this crate use the functions in this
+//! module instead, which follow a [`FloatFormat`] (the `Display` impl of
+//! [`Field`](super::core::Field) keeps the format of the standard library,
+//! and uses [`FloatFormat::HexBits`] with `{:#}`):
+//!
+//! - [`FloatFormat::Shortest`] writes the shortest decimal that reads back as
+//!   the same `f32` (using [`ryu`]), e.g. `0.3`.
+//! - [`FloatFormat::HexBits`] writes the exact bits of the float as hex, e.g.
+//!   `0x3e99999a`, which also keeps the sign of zero and the payload of `NaN`s.
+//!
+//! ```
+//! use assembly_data::fdb::float::{format_f32, parse_f32, widen, FloatFormat};
+//!
+//! assert_eq!(format_f32(0.3, FloatFormat::Shortest), "0.3");
+//! assert_eq!(format_f32(0.3, FloatFormat::HexBits), "0x3e99999a");
+//! assert_eq!(parse_f32("0x3e99999a"), Ok(0.3));
+//! assert_eq!(widen(0.3), 0.3);
+//! ```
+
+use std::{fmt, num::ParseFloatError};
+
+/// How to write the fields of a `FLOAT` column, see the [module documentation](self)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum FloatFormat {
+    /// The shortest decimal that reads back as the same value
+    #[default]
+    Shortest,
+    /// The bits of the value as 8 hex digits with a `0x` prefix
+    HexBits,
+}
+
+/// Format a float as text with the given policy
+pub fn format_f32(value: f32, format: FloatFormat) -> String {
+    match format {
+        FloatFormat::Shortest => ryu::Buffer::new().format(value).to_owned(),
+        FloatFormat::HexBits => format!("{:#010x}", value.to_bits()),
+    }
+}
+
+/// Parse a float written by [`format_f32`] in either format
+pub fn parse_f32(text: &str) -> Result<f32, ParseFloatError> {
+    match text.strip_prefix("0x") {
+        Some(hex) => match u32::from_str_radix(hex, 16) {
+            Ok(bits) => Ok(f32::from_bits(bits)),
+            // Use the error of the float parser for anything that isn't hex
+            Err(_) => text.parse(),
+        },
+        None => text.parse(),
+    }
+}
+
+/// Convert a float to the `f64` with the same shortest decimal
+///
+/// Unlike `f64::from`, which keeps the exact binary value, this returns the
+/// `f64` that is closest to the shortest decimal of `value`, so that it is
+/// printed like the original (e.g. `0.3` instead of `0.30000001192092896`).
+/// Converting the result back with `as f32` gives the original value.
+pub fn widen(value: f32) -> f64 {
+    if !value.is_finite() {
+        return f64::from(value);
+    }
+    ryu::Buffer::new().format_finite(value).parse().unwrap()
+}
+
+/// A float that is displayed with a [`FloatFormat`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DisplayFloat(pub f32, pub FloatFormat);
+
+impl fmt::Display for DisplayFloat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.1 {
+            FloatFormat::Shortest => f.write_str(ryu::Buffer::new().format(self.0)),
+            FloatFormat::HexBits => write!(f, "{:#010x}", self.0.to_bits()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let values = [
+            0.3,
+            0.1,
+            -0.0,
+            1.0,
+            16_777_216.0,
+            f32::MIN_POSITIVE,
+            f32::MAX,
+            1e-45,
+        ];
+        for &value in &values {
+            for &format in &[FloatFormat::Shortest, FloatFormat::HexBits] {
+                let text = format_f32(value, format);
+                assert_eq!(text, DisplayFloat(value, format).to_string());
+                let back = parse_f32(&text).unwrap();
+                assert_eq!(back.to_bits(), value.to_bits(), "{}", text);
+            }
+            assert_eq!((widen(value) as f32).to_bits(), value.to_bits());
+        }
+        assert_eq!(widen(0.3).to_string(), "0.3");
+        assert!(parse_f32(&format_f32(f32::NAN, FloatFormat::Shortest))
+            .unwrap()
+            .is_nan());
+        assert!(parse_f32("0xzz").is_err());
+    }
+}
@@ -0,0 +1,280 @@
+//! # Tables as aligned text
+//!
+//! [`print_table`] writes the rows of a table to the terminal, with one line
+//! per row and the fields aligned in columns. To keep the output readable for
+//! big tables, the text of a field is truncated to a maximum width (marked
+//! with `…`), columns that don't fit into the total width are left out and
+//! only the first rows are printed. All limits are set in [`TableFormat`],
+//! along with the [`FloatFormat`] for `FLOAT` fields.
+//!
+//! ```
+//! use assembly_data::{fdb::fmt::{write_table, TableFormat}, include_fdb};
+//!
+//! let db = include_fdb!("../../res/example.fdb");
+//! let table = db.tables().unwrap().by_name("Objects").unwrap().unwrap();
+//! let mut out = Vec::new();
+//! write_table(&mut out, &table, &TableFormat::default()).unwrap();
+//! assert_eq!(
+//!     String::from_utf8(out).unwrap(),
+//!     "id | name\n---+------\n 1 | Brick\n 2 | Plate\n 3 | Tile\n"
+//! );
+//! ```
+
+use std::io::{self, Write};
+
+use super::{
+    common::ValueType,
+    float::{format_f32, FloatFormat},
+    mem::{Field, Row, Table},
+};
+
+/// Limits for [`write_table`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableFormat {
+    /// The maximum number of characters per field
+    pub max_column_width: usize,
+    /// The maximum number of characters per line, if any
+    pub max_width: Option<usize>,
+    /// The maximum number of rows, if any
+    pub max_rows: Option<usize>,
+    /// How to write `FLOAT` fields
+    pub floats: FloatFormat,
+}
+
+impl Default for TableFormat {
+    fn default() -> Self {
+        Self {
+            max_column_width: 32,
+            max_width: Some(120),
+            max_rows: Some(50),
+            floats: FloatFormat::Shortest,
+        }
+    }
+}
+
+impl TableFormat {
+    /// No limits at all
+    pub fn unlimited() -> Self {
+        Self {
+            max_column_width: usize::MAX,
+            max_width: None,
+            max_rows: None,
+            floats: FloatFormat::Shortest,
+        }
+    }
+}
+
+const SEPARATOR: &str = " | ";
+
+/// Get the text of a field, without control characters and truncated to `max` characters
+fn cell_text(field: &Field<'_>, max: usize, floats: FloatFormat) -> String {
+    let text = match field {
+        Field::Nothing => String::from("NULL"),
+        Field::Integer(v) => v.to_string(),
+        Field::Float(v) => format_f32(*v, floats),
+        Field::Text(v) | Field::VarChar(v) => v.decode().into_owned(),
+        Field::Boolean(v) => v.to_string(),
+        Field::BigInt(v) => v.to_string(),
+    };
+    let text = text.replace(char::is_control, " ");
+    truncate(text, max)
+}
+
+fn truncate(text: String, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text;
+    }
+    let mut short: String = text.chars().take(max.saturating_sub(1)).collect();
+    if max > 0 {
+        short.push('…');
+    }
+    short
+}
+
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+fn is_numeric(value_type: ValueType) -> bool {
+    matches!(
+        value_type,
+        ValueType::Integer | ValueType::Float | ValueType::BigInt
+    )
+}
+
+fn write_line<W: Write>(
+    out: &mut W,
+    cells: &[String],
+    columns: &[(usize, bool)],
+) -> io::Result<()> {
+    let mut line = String::new();
+    for (index, (cell, &(width, right))) in cells.iter().zip(columns).enumerate() {
+        if index > 0 {
+            line.push_str(SEPARATOR);
+        }
+        if right {
+            line.push_str(&format!("{:>1$}", cell, width));
+        } else {
+            line.push_str(&format!("{:<1$}", cell, width));
+        }
+    }
+    writeln!(out, "{}", line.trim_end())
+}
+
+/// Write the rows of `table` to `out`, see the [module documentation](self)
+pub fn write_table<W: Write>(
+    out: &mut W,
+    table: &Table<'_>,
+    format: &TableFormat,
+) -> io::Result<()> {
+    write_rows(out, table, table.row_iter(), format)
+}
+
+/// Like [`write_table`], with the rows sorted by `keys`, see [`sort_rows`]
+///
+/// An unknown column in `keys` is an [`io::ErrorKind::InvalidInput`].
+///
+/// [`sort_rows`]: super::query::sort::sort_rows
+#[cfg(feature = "fdb-core")]
+pub fn write_sorted_table<W: Write>(
+    out: &mut W,
+    table: &Table<'_>,
+    format: &TableFormat,
+    keys: &[(&str, super::query::sort::Order)],
+) -> io::Result<()> {
+    let columns: Vec<_> = table.column_iter().map(|c| c.name()).collect();
+    let mut rows: Vec<_> = table.row_iter().collect();
+    super::query::sort::sort_rows(&mut rows, &columns, keys)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    write_rows(out, table, rows, format)
+}
+
+fn write_rows<'a, W, I>(
+    out: &mut W,
+    table: &Table<'a>,
+    rows: I,
+    format: &TableFormat,
+) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = Row<'a>>,
+{
+    let max = format.max_column_width;
+    let header: Vec<_> = table
+        .column_iter()
+        .map(|c| truncate(c.name().into_owned(), max))
+        .collect();
+    let numeric: Vec<_> = table
+        .column_iter()
+        .map(|c| is_numeric(c.value_type()))
+        .collect();
+
+    let limit = format.max_rows.unwrap_or(usize::MAX);
+    let mut cells = Vec::new();
+    let mut skipped = 0;
+    for row in rows {
+        if cells.len() < limit {
+            cells.push(
+                row.field_iter()
+                    .map(|f| cell_text(&f, max, format.floats))
+                    .collect::<Vec<_>>(),
+            );
+        } else {
+            skipped += 1;
+        }
+    }
+
+    let mut columns = Vec::new();
+    let mut total = 0;
+    for (index, name) in header.iter().enumerate() {
+        let width = cells
+            .iter()
+            .filter_map(|r: &Vec<String>| r.get(index))
+            .map(String::as_str)
+            .map(width)
+            .fold(width(name), usize::max);
+        let needed = if index > 0 {
+            SEPARATOR.len() + width
+        } else {
+            width
+        };
+        if format
+            .max_width
+            .is_some_and(|m| index > 0 && total + needed > m)
+        {
+            break;
+        }
+        total += needed;
+        columns.push((width, numeric[index]));
+    }
+    let hidden = header.len() - columns.len();
+
+    write_line(out, &header[..columns.len()], &columns)?;
+    let rule: Vec<_> = columns.iter().map(|(w, _)| "-".repeat(*w)).collect();
+    writeln!(out, "{}", rule.join("-+-"))?;
+    for row in &cells {
+        write_line(out, &row[..columns.len().min(row.len())], &columns)?;
+    }
+    if skipped > 0 || hidden > 0 {
+        writeln!(out, "({} more rows, {} more columns)", skipped, hidden)?;
+    }
+    Ok(())
+}
+
+/// Write the rows of `table` to stdout, see [`write_table`]
+pub fn print_table(table: &Table<'_>, format: &TableFormat) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    write_table(&mut out, table, format)?;
+    out.flush()
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{core, mem::Database, testing::SampleDatabase};
+
+    #[test]
+    fn test_limits() {
+        let buf = SampleDatabase::new()
+            .table(
+                "Objects",
+                &[("id", ValueType::Integer), ("description", ValueType::Text)],
+            )
+            .rows(3)
+            .row(vec![
+                core::Field::Integer(3),
+                core::Field::Text(String::from("A long\ntext")),
+            ])
+            .build();
+        let db = Database::new(&buf);
+        let table = db.tables().unwrap().get(0).unwrap().unwrap();
+
+        let format = TableFormat {
+            max_column_width: 8,
+            max_width: None,
+            max_rows: Some(2),
+            ..TableFormat::default()
+        };
+        let mut out = Vec::new();
+        write_table(&mut out, &table, &format).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(
+            text,
+            "id | descrip…\n---+---------\n 0 | descrip…\n 1 | descrip…\n(2 more rows, 0 more columns)\n"
+        );
+
+        let format = TableFormat {
+            max_width: Some(10),
+            ..TableFormat::unlimited()
+        };
+        let mut out = Vec::new();
+        write_table(&mut out, &table, &format).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(
+            text.ends_with("3\n(0 more rows, 1 more columns)\n"),
+            "{}",
+            text
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! # Per-column hooks for conversions
+//!
+//! A [`ColumnHooks`] registry holds closures that rewrite the fields of one
+//! column of one table, e.g. to strip a path prefix or to map ids through a
+//! lookup table. They are applied to every row while it is read, by wrapping
+//! a [`RowSource`] with [`ColumnHooks::wrap`], so that a large import doesn't
+//! need a separate pass afterwards. Loaded tables can be changed with
+//! [`ColumnHooks::apply_table`], and exports to CSV or JSON lines with
+//! [`StreamSink::with_hooks`][super::stream::StreamSink::with_hooks].
+//!
+//! ```
+//! use assembly_data::fdb::{
+//!     core::Field,
+//!     csv::CsvDialect,
+//!     hooks::{map_ids, strip_prefix, ColumnHooks},
+//!     source::{import_from_source, CsvSource},
+//! };
+//!
+//! let hooks = ColumnHooks::new()
+//!     .with("Icons", "path", strip_prefix("../../textures/"))
+//!     .with("Icons", "id", map_ids(vec![(1, 1001)].into_iter().collect()));
+//! let source = CsvSource::new(CsvDialect::default())
+//!     .with_table("Icons", "id,path\n1,../../textures/brick.dds\n");
+//! let schema = import_from_source(hooks.wrap(source), &()).unwrap();
+//! let row = &schema.table("Icons").unwrap().buckets()[0].rows_ref()[0];
+//! assert_eq!(row.fields(), &[Field::Integer(1001), Field::Text("brick.dds".into())]);
+//! ```
+
+use std::{borrow::Borrow, collections::HashMap, fmt, sync::Arc};
+
+use assembly_core::hash::fdb_bucket;
+
+use super::{
+    core::{pk_hash, Field, Row, Table, TableDef},
+    source::RowSource,
+};
+
+/// A closure that rewrites a field
+pub type Hook = Arc<dyn Fn(Field) -> Field + Send + Sync>;
+
+/// A registry of hooks by table and column, see the [module documentation](self)
+#[derive(Clone, Default)]
+pub struct ColumnHooks {
+    hooks: Vec<(String, String, Hook)>,
+}
+
+impl fmt::Debug for ColumnHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let columns = self.hooks.iter().map(|(table, column, _)| (table, column));
+        f.debug_list().entries(columns).finish()
+    }
+}
+
+impl ColumnHooks {
+    /// Create a registry without hooks
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a hook for `column` of `table`
+    ///
+    /// Hooks for the same column run in the order they were registered.
+    pub fn register<F>(&mut self, table: &str, column: &str, hook: F)
+    where
+        F: Fn(Field) -> Field + Send + Sync + 'static,
+    {
+        self.hooks
+            .push((table.to_owned(), column.to_owned(), Arc::new(hook)));
+    }
+
+    /// Like [`ColumnHooks::register`], for chaining
+    pub fn with<F>(mut self, table: &str, column: &str, hook: F) -> Self
+    where
+        F: Fn(Field) -> Field + Send + Sync + 'static,
+    {
+        self.register(table, column, hook);
+        self
+    }
+
+    /// Check whether there are no hooks
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// The column index and hook for all hooks of a table
+    fn resolve(&self, def: &TableDef) -> Vec<(usize, &Hook)> {
+        let columns: Vec<&str> = def.columns.iter().map(|c| &*c.name).collect();
+        self.resolve_columns(&def.name, &columns)
+    }
+
+    /// The column index and hook for all hooks of the table `name` with `columns`
+    pub(crate) fn resolve_columns<S: AsRef<str>>(
+        &self,
+        name: &str,
+        columns: &[S],
+    ) -> Vec<(usize, &Hook)> {
+        let hooks = self.hooks.iter().filter(|(table, _, _)| table == name);
+        hooks
+            .filter_map(|(_, column, hook)| {
+                let index = columns.iter().position(|c| c.as_ref() == column)?;
+                Some((index, hook))
+            })
+            .collect()
+    }
+
+    fn apply_row(hooks: &[(usize, &Hook)], row: &mut Row) {
+        Self::apply_fields(hooks, row.fields_mut());
+    }
+
+    /// Run the hooks on the fields of one row
+    pub(crate) fn apply_fields<H: Borrow<Hook>>(hooks: &[(usize, H)], fields: &mut [Field]) {
+        for (index, hook) in hooks {
+            if let Some(field) = fields.get_mut(*index) {
+                *field = hook.borrow()(std::mem::replace(field, Field::Nothing));
+            }
+        }
+    }
+
+    /// Apply the hooks to all rows of a loaded table
+    ///
+    /// If a hook changes the primary key (the first column), the row is moved
+    /// to the bucket for its new key. Rows with a key that can't be hashed
+    /// stay where they are.
+    pub fn apply_table(&self, table: &mut Table) {
+        let def = TableDef {
+            columns: table.columns().to_vec(),
+            name: table.name().to_owned(),
+        };
+        let hooks = self.resolve(&def);
+        if hooks.is_empty() {
+            return;
+        }
+        let buckets = table.buckets_mut();
+        if hooks.iter().all(|&(index, _)| index != 0) {
+            for row in buckets.iter_mut().flat_map(|b| b.rows_mut()) {
+                Self::apply_row(&hooks, row);
+            }
+            return;
+        }
+        let count = buckets.len();
+        let mut moved = Vec::new();
+        for (index, bucket) in buckets.iter_mut().enumerate() {
+            for mut row in std::mem::take(bucket.rows_mut()) {
+                Self::apply_row(&hooks, &mut row);
+                let hash = row.fields().first().and_then(pk_hash);
+                match hash.map(|hash| fdb_bucket(hash, count)) {
+                    Some(target) if target != index => moved.push((target, row)),
+                    _ => bucket.rows_mut().push(row),
+                }
+            }
+        }
+        for (target, row) in moved {
+            buckets[target].rows_mut().push(row);
+        }
+    }
+
+    /// Apply the hooks to every row read from `source`
+    pub fn wrap<S: RowSource>(&self, source: S) -> HookedSource<'_, S> {
+        HookedSource {
+            inner: source,
+            hooks: self,
+            current: Vec::new(),
+        }
+    }
+}
+
+/// A [`RowSource`] that applies [`ColumnHooks`] to the rows of another
+pub struct HookedSource<'h, S> {
+    inner: S,
+    hooks: &'h ColumnHooks,
+    current: Vec<(usize, &'h Hook)>,
+}
+
+impl<S: RowSource> RowSource for HookedSource<'_, S> {
+    type Error = S::Error;
+
+    fn next_table(&mut self) -> Result<Option<TableDef>, S::Error> {
+        let def = self.inner.next_table()?;
+        self.current = match &def {
+            Some(def) => self.hooks.resolve(def),
+            None => Vec::new(),
+        };
+        Ok(def)
+    }
+
+    fn next_row(&mut self) -> Result<Option<Row>, S::Error> {
+        let mut row = self.inner.next_row()?;
+        if let Some(row) = &mut row {
+            ColumnHooks::apply_row(&self.current, row);
+        }
+        Ok(row)
+    }
+}
+
+/// A hook that removes `prefix` from the start of text fields
+///
+/// Fields that don't start with `prefix` are not changed.
+pub fn strip_prefix(prefix: &str) -> impl Fn(Field) -> Field + Send + Sync + 'static {
+    let prefix = prefix.to_owned();
+    move |field| match field {
+        Field::Text(s) => match s.strip_prefix(prefix.as_str()) {
+            Some(rest) => Field::Text(rest.to_owned()),
+            None => Field::Text(s),
+        },
+        Field::VarChar(s) => match s.strip_prefix(prefix.as_str()) {
+            Some(rest) => Field::VarChar(rest.to_owned()),
+            None => Field::VarChar(s),
+        },
+        field => field,
+    }
+}
+
+/// A hook that replaces integer fields through `ids`
+///
+/// Fields that are not in `ids` are not changed.
+pub fn map_ids(ids: HashMap<i32, i32>) -> impl Fn(Field) -> Field + Send + Sync + 'static {
+    move |field| match field {
+        Field::Integer(id) => Field::Integer(ids.get(&id).copied().unwrap_or(id)),
+        field => field,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fdb::{
+        csv::CsvDialect,
+        source::{import_from_source, CsvSource},
+    };
+
+    #[test]
+    fn test_apply_table_moves_rows() {
+        let source = CsvSource::new(CsvDialect::default())
+            .with_table("Icons", "id,path\n1,a.dds\n2,b.dds\n3,c.dds\n4,d.dds\n");
+        let mut schema = import_from_source(source, &()).unwrap();
+        let ids = (1..=4).map(|id| (id, id + 1001)).collect();
+        let hooks = ColumnHooks::new().with("Icons", "id", map_ids(ids));
+        let table = schema.table_mut("Icons").unwrap();
+        hooks.apply_table(table);
+
+        let buckets = table.buckets();
+        for id in 1002..=1005 {
+            let key = Field::Integer(id);
+            let bucket = &buckets[fdb_bucket(pk_hash(&key).unwrap(), buckets.len())];
+            assert!(bucket.rows_ref().iter().any(|r| r.fields()[0] == key));
+        }
+    }
+}
@@ -0,0 +1,578 @@
+//! # Copy-on-write view of a database
+//!
+//! A [`HybridSchema`] serves all reads from a borrowed [`mem::Database`] buffer,
+//! but keeps edits in overlay maps per table. Only rows that are changed or
+//! inserted are materialized as owned [`Row`]s, so a large database can be
+//! edited without loading it completely into a [`Schema`][super::core::Schema].
+//!
+//! [`HybridSchema::write`] stores the result, copying all unchanged tables from
+//! the original buffer.
+//!
+//! ```
+//! use assembly_data::fdb::{hybrid::HybridSchema, mem::Database};
+//!
+//! let file: &[u8] = &[0,0,0,0,8,0,0,0];
+//! let schema = HybridSchema::new(Database::new(file)).unwrap();
+//! assert!(!schema.is_modified());
+//! assert!(schema.table("Objects").is_err());
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+};
+
+use assembly_core::{buffer::CastError, displaydoc::Display, hash::fdb_bucket};
+use thiserror::Error;
+
+use super::{
+    core::{Field, Row},
+    mem::{self, RowHeaderIter},
+    store,
+};
+
+pub use super::core::pk_hash;
+
+/// The position of a row in the original database
+///
+/// This is the index of the bucket and the index of the row within the
+/// linked list of that bucket.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RowId {
+    /// The index of the bucket
+    pub bucket: usize,
+    /// The index of the row within the bucket
+    pub index: usize,
+}
+
+/// Errors when reading or editing a [`HybridSchema`]
+#[derive(Debug, Error, Display)]
+#[non_exhaustive]
+pub enum HybridError {
+    /// Failed to read the database: {0}
+    Cast(#[from] CastError),
+    /// Failed to write the database: {0}
+    Io(#[from] io::Error),
+    /// Table {0:?} does not exist
+    NoSuchTable(String),
+    /// Row {0:?} does not exist
+    NoSuchRow(RowId),
+    /// Column {0} does not exist
+    NoSuchColumn(usize),
+    /// Cannot compute the bucket for the primary key {0}
+    UnsupportedKey(Field),
+    /// The row has {fields} fields instead of {columns}
+    FieldCount {
+        /// The number of fields in the row
+        fields: usize,
+        /// The number of columns of the table
+        columns: usize,
+    },
+}
+
+/// The edits for a single table
+#[derive(Debug, Default)]
+pub struct TableEdits {
+    updated: BTreeMap<RowId, Row>,
+    removed: BTreeSet<RowId>,
+    inserted: BTreeMap<usize, Vec<Row>>,
+}
+
+impl TableEdits {
+    /// Returns whether there are no edits
+    pub fn is_empty(&self) -> bool {
+        self.updated.is_empty() && self.removed.is_empty() && self.inserted.is_empty()
+    }
+
+    /// Returns the replaced rows
+    pub fn updated(&self) -> &BTreeMap<RowId, Row> {
+        &self.updated
+    }
+
+    /// Returns the removed rows
+    pub fn removed(&self) -> &BTreeSet<RowId> {
+        &self.removed
+    }
+
+    /// Returns the new rows, by bucket
+    pub fn inserted(&self) -> &BTreeMap<usize, Vec<Row>> {
+        &self.inserted
+    }
+
+    /// Store `row` as the replacement of `id`, or move it to `bucket`
+    fn place(&mut self, id: RowId, bucket: usize, row: Row) {
+        if bucket == id.bucket {
+            self.removed.remove(&id);
+            self.updated.insert(id, row);
+        } else {
+            self.updated.remove(&id);
+            self.removed.insert(id);
+            self.inserted.entry(bucket).or_default().push(row);
+        }
+    }
+}
+
+/// A database with copy-on-write edits
+pub struct HybridSchema<'a> {
+    buf: &'a [u8],
+    tables: mem::Tables<'a>,
+    edits: BTreeMap<String, TableEdits>,
+}
+
+impl<'a> HybridSchema<'a> {
+    /// Create a view without any edits
+    pub fn new(db: mem::Database<'a>) -> Result<Self, CastError> {
+        Ok(Self {
+            buf: db.as_bytes(),
+            tables: db.tables()?,
+            edits: BTreeMap::new(),
+        })
+    }
+
+    /// Returns the underlying tables
+    pub fn original(&self) -> mem::Tables<'a> {
+        self.tables
+    }
+
+    /// Get the table with that name
+    pub fn table(&self, name: &str) -> Result<HybridTable<'_, 'a>, HybridError> {
+        let table = self.mem_table(name)?;
+        Ok(HybridTable {
+            table,
+            edits: self.edits.get(name),
+        })
+    }
+
+    /// Iterate over all tables
+    pub fn tables(&self) -> impl Iterator<Item = Result<HybridTable<'_, 'a>, CastError>> + '_ {
+        self.tables.iter().map(move |table| {
+            let table = table?;
+            let edits = self.edits.get(table.name().as_ref());
+            Ok(HybridTable { table, edits })
+        })
+    }
+
+    /// Returns whether any table was edited
+    pub fn is_modified(&self) -> bool {
+        !self.edits.is_empty()
+    }
+
+    /// Returns the names of all edited tables
+    pub fn modified_tables(&self) -> impl Iterator<Item = &str> {
+        self.edits.keys().map(String::as_str)
+    }
+
+    /// Returns the edits for the table with that name
+    pub fn edits(&self, name: &str) -> Option<&TableEdits> {
+        self.edits.get(name)
+    }
+
+    /// Drop all edits to the table with that name
+    pub fn discard(&mut self, name: &str) -> Option<TableEdits> {
+        self.edits.remove(name)
+    }
+
+    /// Write the database including all edits
+    ///
+    /// Only the modified tables are serialized, see [`store::Database::write_patched`].
+    pub fn write<O: io::Write>(&self, out: &mut O) -> Result<(), HybridError> {
+        let mut db = store::Database::new();
+        for name in self.modified_tables() {
+            let table = self.table(name)?;
+            db.push_table(table.original().name_raw(), store::Table::from(table));
+        }
+        db.write_patched(self.buf, out)?;
+        Ok(())
+    }
+
+    fn mem_table(&self, name: &str) -> Result<mem::Table<'a>, HybridError> {
+        match self.tables.by_name(name) {
+            Some(table) => Ok(table?),
+            None => Err(HybridError::NoSuchTable(name.to_string())),
+        }
+    }
+
+    fn original_row(&self, table: &str, id: RowId) -> Result<mem::Row<'a>, HybridError> {
+        self.mem_table(table)?
+            .bucket_at(id.bucket)
+            .and_then(|b| b.row_iter().nth(id.index))
+            .ok_or(HybridError::NoSuchRow(id))
+    }
+
+    fn edits_mut(&mut self, table: &str) -> &mut TableEdits {
+        self.edits.entry(table.to_string()).or_default()
+    }
+
+    /// Replace the row at `id` in `table`
+    ///
+    /// If the new row has a primary key that belongs in a different bucket,
+    /// the original row is removed and the new one is inserted into that
+    /// bucket, so `id` no longer refers to it.
+    pub fn update_row(&mut self, table: &str, id: RowId, row: Row) -> Result<(), HybridError> {
+        let original = self.original_row(table, id)?;
+        let columns = original.field_count();
+        if row.fields().len() != columns {
+            return Err(HybridError::FieldCount {
+                fields: row.fields().len(),
+                columns,
+            });
+        }
+        let bucket = self.bucket_for(table, &row)?;
+        let edits = self.edits_mut(table);
+        edits.place(id, bucket, row);
+        Ok(())
+    }
+
+    /// Set a single field of the row at `id` in `table`
+    ///
+    /// This copies the row from the buffer if it was not edited before. Like
+    /// [`HybridSchema::update_row`], changing the primary key (column 0) may
+    /// move the row to a different bucket.
+    pub fn set_field(
+        &mut self,
+        table: &str,
+        id: RowId,
+        column: usize,
+        value: Field,
+    ) -> Result<(), HybridError> {
+        let original = self.original_row(table, id)?;
+        if column >= original.field_count() {
+            return Err(HybridError::NoSuchColumn(column));
+        }
+        let edits = self.edits_mut(table);
+        if edits.removed.contains(&id) {
+            return Err(HybridError::NoSuchRow(id));
+        }
+        let mut row = match edits.updated.get(&id) {
+            Some(row) => row.clone(),
+            None => row_to_owned(original),
+        };
+        row.fields_mut()[column] = value;
+        let bucket = self.bucket_for(table, &row)?;
+        self.edits_mut(table).place(id, bucket, row);
+        Ok(())
+    }
+
+    /// Remove the row at `id` from `table`
+    pub fn remove_row(&mut self, table: &str, id: RowId) -> Result<(), HybridError> {
+        self.original_row(table, id)?;
+        let edits = self.edits_mut(table);
+        edits.updated.remove(&id);
+        edits.removed.insert(id);
+        Ok(())
+    }
+
+    /// Add a row to `table`
+    ///
+    /// The bucket is computed from the first field of the row. Returns the
+    /// index of that bucket.
+    pub fn insert_row(&mut self, table: &str, row: Row) -> Result<usize, HybridError> {
+        let bucket = self.bucket_for(table, &row)?;
+        let edits = self.edits_mut(table);
+        edits.inserted.entry(bucket).or_default().push(row);
+        Ok(bucket)
+    }
+
+    /// Compute the bucket for the primary key (first field) of `row`
+    fn bucket_for(&self, table: &str, row: &Row) -> Result<usize, HybridError> {
+        let bucket_count = self.mem_table(table)?.bucket_count();
+        let key = row.fields().first().cloned().unwrap_or(Field::Nothing);
+        let hash = pk_hash(&key).ok_or(HybridError::UnsupportedKey(key))?;
+        Ok(match bucket_count {
+            0 => 0,
+            n => fdb_bucket(hash, n),
+        })
+    }
+}
+
+fn row_to_owned(row: mem::Row) -> Row {
+    Row::from(row.field_iter().map(Field::from).collect::<Vec<_>>())
+}
+
+/// A table in a [`HybridSchema`]
+#[derive(Copy, Clone)]
+pub struct HybridTable<'s, 'a> {
+    table: mem::Table<'a>,
+    edits: Option<&'s TableEdits>,
+}
+
+impl<'s, 'a> HybridTable<'s, 'a> {
+    /// Returns the table in the original buffer
+    pub fn original(&self) -> mem::Table<'a> {
+        self.table
+    }
+
+    /// Returns the edits to this table, if any
+    pub fn edits(&self) -> Option<&'s TableEdits> {
+        self.edits
+    }
+
+    /// Returns whether this table was edited
+    pub fn is_modified(&self) -> bool {
+        matches!(self.edits, Some(e) if !e.is_empty())
+    }
+
+    /// Returns the name of the table
+    pub fn name(&self) -> String {
+        self.table.name().into_owned()
+    }
+
+    /// Returns the number of buckets
+    pub fn bucket_count(&self) -> usize {
+        self.table.bucket_count()
+    }
+
+    /// Iterate over the current rows of the bucket at `index`
+    pub fn bucket_rows(&self, index: usize) -> BucketRows<'s, 'a> {
+        let original = self.table.bucket_at(index).map(|b| b.row_iter());
+        let inserted = self
+            .edits
+            .and_then(|e| e.inserted.get(&index))
+            .map_or(&[][..], Vec::as_slice);
+        BucketRows {
+            bucket: index,
+            next_index: 0,
+            original,
+            edits: self.edits,
+            inserted: inserted.iter(),
+        }
+    }
+
+    /// Iterate over the current rows of all buckets
+    pub fn row_iter(&self) -> RowIter<'s, 'a> {
+        RowIter {
+            table: *self,
+            next_bucket: 0,
+            current: None,
+        }
+    }
+}
+
+impl From<HybridTable<'_, '_>> for store::Table {
+    fn from(table: HybridTable<'_, '_>) -> Self {
+        let mut dest = store::Table::new(table.bucket_count());
+        for column in table.original().column_iter() {
+            dest.push_column(column.name_raw(), column.value_type());
+        }
+        for bucket in 0..table.bucket_count() {
+            for row in table.bucket_rows(bucket) {
+                dest.push_row(bucket, row.to_owned_row().fields());
+            }
+        }
+        dest
+    }
+}
+
+/// A row in a [`HybridTable`]
+#[derive(Copy, Clone)]
+pub enum HybridRow<'s, 'a> {
+    /// An unchanged row from the buffer
+    Original(RowId, mem::Row<'a>),
+    /// A row that replaces one from the buffer
+    Updated(RowId, &'s Row),
+    /// A new row
+    Inserted(&'s Row),
+}
+
+impl<'s, 'a> HybridRow<'s, 'a> {
+    /// Returns the position of the row in the original buffer
+    pub fn id(&self) -> Option<RowId> {
+        match self {
+            Self::Original(id, _) | Self::Updated(id, _) => Some(*id),
+            Self::Inserted(_) => None,
+        }
+    }
+
+    /// Returns whether this row differs from the buffer
+    pub fn is_modified(&self) -> bool {
+        !matches!(self, Self::Original(..))
+    }
+
+    /// Returns the number of fields
+    pub fn field_count(&self) -> usize {
+        match self {
+            Self::Original(_, row) => row.field_count(),
+            Self::Updated(_, row) | Self::Inserted(row) => row.fields().len(),
+        }
+    }
+
+    /// Get a copy of the field at `index`
+    pub fn field_at(&self, index: usize) -> Option<Field> {
+        match self {
+            Self::Original(_, row) => row.field_at(index).map(Field::from),
+            Self::Updated(_, row) | Self::Inserted(row) => row.fields().get(index).cloned(),
+        }
+    }
+
+    /// Create an owned copy of the row
+    pub fn to_owned_row(&self) -> Row {
+        match self {
+            Self::Original(_, row) => row_to_owned(*row),
+            Self::Updated(_, row) | Self::Inserted(row) => Row::from(row.fields().clone()),
+        }
+    }
+}
+
+/// Iterator returned by [`HybridTable::bucket_rows`]
+pub struct BucketRows<'s, 'a> {
+    bucket: usize,
+    next_index: usize,
+    original: Option<RowHeaderIter<'a>>,
+    edits: Option<&'s TableEdits>,
+    inserted: std::slice::Iter<'s, Row>,
+}
+
+impl<'s, 'a> Iterator for BucketRows<'s, 'a> {
+    type Item = HybridRow<'s, 'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(original) = &mut self.original {
+            for row in original {
+                let id = RowId {
+                    bucket: self.bucket,
+                    index: self.next_index,
+                };
+                self.next_index += 1;
+                match self.edits {
+                    Some(edits) if edits.removed.contains(&id) => continue,
+                    Some(edits) => match edits.updated.get(&id) {
+                        Some(updated) => return Some(HybridRow::Updated(id, updated)),
+                        None => return Some(HybridRow::Original(id, row)),
+                    },
+                    None => return Some(HybridRow::Original(id, row)),
+                }
+            }
+            self.original = None;
+        }
+        self.inserted.next().map(HybridRow::Inserted)
+    }
+}
+
+/// Iterator returned by [`HybridTable::row_iter`]
+pub struct RowIter<'s, 'a> {
+    table: HybridTable<'s, 'a>,
+    next_bucket: usize,
+    current: Option<BucketRows<'s, 'a>>,
+}
+
+impl<'s, 'a> Iterator for RowIter<'s, 'a> {
+    type Item = HybridRow<'s, 'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.current.as_mut().and_then(Iterator::next) {
+                return Some(row);
+            }
+            if self.next_bucket >= self.table.bucket_count() {
+                return None;
+            }
+            self.current = Some(self.table.bucket_rows(self.next_bucket));
+            self.next_bucket += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fdb::{common::ValueType, testing::SampleDatabase};
+
+    /// An `Objects` table with 4 buckets and the rows 1 to 3
+    fn objects() -> Vec<u8> {
+        let columns = [("id", ValueType::Integer), ("name", ValueType::Text)];
+        let db = SampleDatabase::new().table("Objects", &columns).buckets(4);
+        let db = (1..=3).fold(db, |db, id| {
+            db.row(vec![
+                Field::Integer(id),
+                Field::Text(format!("Object {}", id)),
+            ])
+        });
+        db.build()
+    }
+
+    #[test]
+    fn test_hybrid_edits() {
+        let buf = objects();
+
+        let mut schema = HybridSchema::new(mem::Database::new(&buf)).unwrap();
+        let first = RowId {
+            bucket: 1,
+            index: 0,
+        };
+        let text = Field::Text(String::from("Renamed"));
+        schema.set_field("Objects", first, 1, text.clone()).unwrap();
+        let removed = RowId {
+            bucket: 2,
+            index: 0,
+        };
+        schema.remove_row("Objects", removed).unwrap();
+        let new_row = Row::from(vec![Field::Integer(5), Field::Nothing]);
+        assert_eq!(schema.insert_row("Objects", new_row).unwrap(), 1);
+
+        assert_eq!(
+            schema.modified_tables().collect::<Vec<_>>(),
+            vec!["Objects"]
+        );
+        let table = schema.table("Objects").unwrap();
+        let rows: Vec<_> = table.bucket_rows(1).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].field_at(1), Some(text));
+        assert!(matches!(rows[1], HybridRow::Inserted(_)));
+        assert_eq!(table.row_iter().count(), 3);
+        assert_eq!(table.row_iter().filter(|r| !r.is_modified()).count(), 1);
+
+        let mut out = Vec::new();
+        schema.write(&mut out).unwrap();
+        assert_eq!(&out[..buf.len()][16..], &buf[16..]);
+        let tables = mem::Database::new(&out).tables().unwrap();
+        let table = tables.by_name("Objects").unwrap().unwrap();
+        let names: Vec<_> = table
+            .row_iter()
+            .map(|r| Field::from(r.field_at(1).unwrap()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                Field::Text(String::from("Renamed")),
+                Field::Nothing,
+                Field::Text(String::from("Object 3"))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_change_key() {
+        let buf = objects();
+
+        let mut schema = HybridSchema::new(mem::Database::new(&buf)).unwrap();
+        let id = |bucket| RowId { bucket, index: 0 };
+        schema
+            .set_field("Objects", id(1), 0, Field::Integer(6))
+            .unwrap();
+        let row = Row::from(vec![Field::Integer(8), Field::Nothing]);
+        schema.update_row("Objects", id(3), row).unwrap();
+        let short = Row::from(vec![Field::Integer(3)]);
+        assert!(matches!(
+            schema.update_row("Objects", id(2), short),
+            Err(HybridError::FieldCount {
+                fields: 1,
+                columns: 2
+            })
+        ));
+        assert!(schema
+            .set_field("Objects", id(1), 1, Field::Nothing)
+            .is_err());
+
+        let mut out = Vec::new();
+        schema.write(&mut out).unwrap();
+        let tables = mem::Database::new(&out).tables().unwrap();
+        let table = tables.by_name("Objects").unwrap().unwrap();
+        assert_eq!(table.row_iter().count(), 3);
+        for &(key, found) in &[(1, false), (3, false), (2, true), (6, true), (8, true)] {
+            assert_eq!(table.index_iter(key).count(), found as usize, "{}", key);
+        }
+        let row = table.index_iter(6).next().unwrap();
+        let name = Field::from(row.field_at(1).unwrap());
+        assert_eq!(name, Field::Text(String::from("Object 1")));
+    }
+}
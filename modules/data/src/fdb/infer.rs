@@ -0,0 +1,235 @@
+//! # Tighter column types from the data
+//!
+//! The FDB format only knows a few types, e.g. every integer column is 32 bit
+//! and text has no maximum length. When exporting to another database, these
+//! can be narrowed down from the values that actually occur: [`column_stats`]
+//! collects the range of numbers, the length of strings and the number of
+//! `NULL`s of every column in a single scan of the table, and
+//! [`ColumnStats::inferred`] turns that into an [`InferredType`]. The SQL
+//! dumps of the `io` module collect the same statistics from a loaded schema.
+//!
+//! Columns with fields of a different type than the column (which the format
+//! allows) are not narrowed down, so that every value still fits.
+
+use std::borrow::Cow;
+
+use super::common::{Context, Value, ValueType};
+#[cfg(feature = "fdb-core")]
+use super::core;
+#[cfg(feature = "fdb-mem")]
+use super::mem::{Field, Table};
+
+/// A SQL type for a column, see [`ColumnStats::inferred`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InferredType {
+    /// Only `NULL`
+    Null,
+    /// `true` or `false`
+    Boolean,
+    /// A 16 bit integer
+    SmallInt,
+    /// A 32 bit integer
+    Integer,
+    /// A 64 bit integer
+    BigInt,
+    /// A floating point number
+    Real,
+    /// Text with at most this many characters
+    VarChar(usize),
+    /// Text of any length
+    Text,
+}
+
+impl InferredType {
+    /// The name of the type in a `CREATE TABLE` statement
+    pub fn sql_name(&self) -> Cow<'static, str> {
+        match self {
+            InferredType::Null => Cow::Borrowed("NULL"),
+            InferredType::Boolean => Cow::Borrowed("BOOLEAN"),
+            InferredType::SmallInt => Cow::Borrowed("SMALLINT"),
+            InferredType::Integer => Cow::Borrowed("INTEGER"),
+            InferredType::BigInt => Cow::Borrowed("BIGINT"),
+            InferredType::Real => Cow::Borrowed("REAL"),
+            InferredType::VarChar(len) => Cow::Owned(format!("VARCHAR({})", len)),
+            InferredType::Text => Cow::Borrowed("TEXT"),
+        }
+    }
+
+    fn declared(value_type: ValueType) -> Self {
+        match value_type {
+            ValueType::Nothing => InferredType::Null,
+            ValueType::Integer => InferredType::Integer,
+            ValueType::Float => InferredType::Real,
+            ValueType::Text | ValueType::VarChar => InferredType::Text,
+            ValueType::Boolean => InferredType::Boolean,
+            ValueType::BigInt => InferredType::BigInt,
+        }
+    }
+}
+
+/// Statistics on the values of one column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnStats {
+    /// The name of the column
+    pub name: String,
+    /// The declared type of the column
+    pub value_type: ValueType,
+    /// The number of fields
+    pub count: usize,
+    /// The number of `NULL` fields
+    pub nulls: usize,
+    /// The number of fields of another type than the column
+    pub mismatched: usize,
+    /// The smallest integer, if there are any
+    pub min: Option<i64>,
+    /// The largest integer, if there are any
+    pub max: Option<i64>,
+    /// The number of characters of the longest string
+    pub max_len: usize,
+}
+
+/// The value of an integer field
+fn int_value<C: Context<I64 = i64>>(field: &Value<C>) -> Option<i64> {
+    match field {
+        Value::Integer(v) => Some(i64::from(*v)),
+        Value::BigInt(v) => Some(*v),
+        _ => None,
+    }
+}
+
+impl ColumnStats {
+    pub(crate) fn new(name: String, value_type: ValueType) -> Self {
+        Self {
+            name,
+            value_type,
+            count: 0,
+            nulls: 0,
+            mismatched: 0,
+            min: None,
+            max: None,
+            max_len: 0,
+        }
+    }
+
+    #[cfg(feature = "fdb-mem")]
+    fn add(&mut self, field: &Field<'_>) {
+        let len = match field {
+            Field::Text(s) | Field::VarChar(s) => s.decode().chars().count(),
+            _ => 0,
+        };
+        self.record(ValueType::from(field), int_value(field), len);
+    }
+
+    /// Add a field of a loaded table
+    #[cfg(feature = "fdb-core")]
+    pub(crate) fn add_core(&mut self, field: &core::Field) {
+        let len = match field {
+            core::Field::Text(s) | core::Field::VarChar(s) => s.chars().count(),
+            _ => 0,
+        };
+        self.record(ValueType::from(field), int_value(field), len);
+    }
+
+    /// Add a field with the given type, integer value and number of characters
+    fn record(&mut self, value_type: ValueType, int: Option<i64>, len: usize) {
+        self.count += 1;
+        if value_type == ValueType::Nothing {
+            self.nulls += 1;
+            return;
+        }
+        self.max_len = self.max_len.max(len);
+        if value_type != self.value_type {
+            self.mismatched += 1;
+        }
+        if let Some(v) = int {
+            self.min = Some(self.min.map_or(v, |m| m.min(v)));
+            self.max = Some(self.max.map_or(v, |m| m.max(v)));
+        }
+    }
+
+    /// Check whether the column has a `NULL` field or no fields at all
+    pub fn is_nullable(&self) -> bool {
+        self.nulls > 0 || self.count == 0
+    }
+
+    /// The narrowest type that fits all values of the column
+    pub fn inferred(&self) -> InferredType {
+        let declared = InferredType::declared(self.value_type);
+        if self.mismatched > 0 || self.nulls == self.count {
+            return declared;
+        }
+        let fits = |min: i64, max: i64| match (self.min, self.max) {
+            (Some(a), Some(b)) => a >= min && b <= max,
+            _ => false,
+        };
+        match declared {
+            InferredType::Integer | InferredType::BigInt
+                if fits(i16::MIN.into(), i16::MAX.into()) =>
+            {
+                InferredType::SmallInt
+            }
+            InferredType::BigInt if fits(i32::MIN.into(), i32::MAX.into()) => InferredType::Integer,
+            InferredType::Text => InferredType::VarChar(self.max_len.max(1)),
+            other => other,
+        }
+    }
+}
+
+/// Collect the [`ColumnStats`] of every column of a table
+#[cfg(feature = "fdb-mem")]
+pub fn column_stats(table: &Table<'_>) -> Vec<ColumnStats> {
+    let mut stats: Vec<_> = table
+        .column_iter()
+        .map(|c| ColumnStats::new(c.name().into_owned(), c.value_type()))
+        .collect();
+    for row in table.row_iter() {
+        for (column, field) in stats.iter_mut().zip(row.field_iter()) {
+            column.add(&field);
+        }
+    }
+    stats
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{core, mem::Database, testing::SampleDatabase};
+
+    #[test]
+    fn test_inferred_types() {
+        let columns = [
+            ("id", ValueType::Integer),
+            ("big", ValueType::BigInt),
+            ("name", ValueType::Text),
+            ("mixed", ValueType::Integer),
+        ];
+        let text = |s: &str| core::Field::Text(String::from(s));
+        let buf = SampleDatabase::new()
+            .table("Objects", &columns)
+            .row(vec![
+                core::Field::Integer(-5),
+                core::Field::BigInt(1 << 40),
+                text("Brick"),
+                core::Field::Integer(1),
+            ])
+            .row(vec![
+                core::Field::Integer(300),
+                core::Field::Nothing,
+                text("Plate"),
+                text("one"),
+            ])
+            .build();
+        let db = Database::new(&buf);
+        let table = db.tables().unwrap().get(0).unwrap().unwrap();
+        let stats = column_stats(&table);
+
+        assert_eq!(stats[0].inferred(), InferredType::SmallInt);
+        assert_eq!((stats[0].min, stats[0].max), (Some(-5), Some(300)));
+        assert_eq!(stats[1].inferred(), InferredType::BigInt);
+        assert!(stats[1].is_nullable());
+        assert_eq!(stats[2].inferred().sql_name(), "VARCHAR(5)");
+        assert!(!stats[2].is_nullable());
+        assert_eq!(stats[3].mismatched, 1);
+        assert_eq!(stats[3].inferred(), InferredType::Integer);
+    }
+}
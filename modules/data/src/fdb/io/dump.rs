@@ -0,0 +1,655 @@
+//! # SQL dumps for database servers
+//!
+//! [`write_sql_dump`] writes a [`Schema`] as a script of `CREATE TABLE` and
+//! `INSERT` statements that can be piped into `psql` or `mysql`. The text of
+//! the database is decoded from Windows-1252 when loading, so the dump is
+//! UTF-8 and tells the server so.
+//!
+//! [`write_sql_dump_with`] and [`SqlDumpSink::with_options`] take
+//! [`SqlDumpOptions`], e.g. to write the rows in an order that doesn't depend
+//! on the bucket layout of the file, to write base64 payloads as bytes, or to
+//! declare the narrowest types that fit the values (see
+//! [`infer`][crate::fdb::infer]).
+
+use std::{
+    borrow::{Borrow, Cow},
+    io::{self, Write},
+};
+
+use crate::fdb::{
+    base64::{looks_like_base64, to_hex},
+    common::{IterOrder, ValueType},
+    core::{Field, Schema},
+    float::{format_f32, FloatFormat},
+    infer::{ColumnStats, InferredType},
+    query::sort::{sort_rows_if_present, Order},
+};
+#[cfg(feature = "fdb-mem")]
+use crate::fdb::{mem, sink::RowSink};
+
+/// The number of rows in each `INSERT` statement
+const ROWS_PER_INSERT: usize = 500;
+
+/// The longest inferred `VARCHAR` in MySQL
+///
+/// MySQL limits the size of a row to 64 KiB, and a `utf8mb4` character takes
+/// up to four bytes, so longer strings are stored as `LONGTEXT`.
+const MYSQL_VARCHAR_LEN: usize = 255;
+
+/// Options for [`write_sql_dump_with`] and [`SqlDumpSink::with_options`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SqlDumpOptions {
+    /// The order of the rows of each table
+    pub order: IterOrder,
+    /// The columns to sort the rows of each table by, after `order`
+    ///
+    /// Keys for columns that a table doesn't have are skipped, see
+    /// [`sort_rows_if_present`].
+    pub sort: Vec<(String, Order)>,
+    /// Look up strings in a [`StringIndex`][crate::fdb::mem::strings::StringIndex],
+    /// see [`RowSink::index_strings`][crate::fdb::sink::RowSink::index_strings]
+    ///
+    /// This only applies to [`SqlDumpSink`], [`write_sql_dump_with`] reads
+    /// the strings from the schema.
+    pub index_strings: bool,
+    /// Write the fields of `VARCHAR` columns that only contain base64 (see
+    /// [`looks_like_base64`]) as bytes
+    ///
+    /// These columns are declared as `BYTEA` in Postgres and as `LONGBLOB` in
+    /// MySQL, and the fields are written as `'\x…'` and `X'…'` literals.
+    pub base64_as_bytes: bool,
+    /// Declare the narrowest type that fits the values of each column, see
+    /// [`ColumnStats::inferred`]
+    ///
+    /// This turns columns into `SMALLINT`, `INTEGER` or `VARCHAR(n)`. Columns
+    /// written as bytes keep their type.
+    pub infer_types: bool,
+    /// Declare the columns without `NULL` fields as `NOT NULL`
+    pub constraints: bool,
+}
+
+/// How to write one column of a table
+struct ColumnPlan {
+    name: String,
+    value_type: ValueType,
+    /// Write the base64 payloads of this column as bytes
+    bytes: bool,
+    /// The narrowest type of the values, if [`SqlDumpOptions::infer_types`] is set
+    inferred: Option<InferredType>,
+    /// Declare the column as `NOT NULL`
+    not_null: bool,
+    /// Whether all floats are finite, MySQL writes the others as `NULL`
+    finite: bool,
+}
+
+/// Plan the columns of a table, reading the fields of `rows` if needed
+fn plan_columns<I, R>(
+    columns: Vec<(String, ValueType)>,
+    rows: I,
+    options: &SqlDumpOptions,
+) -> Vec<ColumnPlan>
+where
+    I: IntoIterator<Item = R>,
+    R: IntoIterator,
+    R::Item: Borrow<Field>,
+{
+    let mut stats: Vec<_> = columns
+        .iter()
+        .map(|(name, value_type)| ColumnStats::new(name.clone(), *value_type))
+        .collect();
+    // The number of base64 strings, and whether all non-empty strings are
+    let mut base64 = vec![(0, true); columns.len()];
+    let mut finite = vec![true; columns.len()];
+    if options.base64_as_bytes || options.infer_types || options.constraints {
+        for row in rows {
+            let columns = stats.iter_mut().zip(&mut base64).zip(&mut finite);
+            for (((stats, (count, all)), finite), field) in columns.zip(row) {
+                let field = field.borrow();
+                stats.add_core(field);
+                match field {
+                    Field::VarChar(text) if looks_like_base64(text.as_bytes()) => *count += 1,
+                    Field::VarChar(text) if !text.is_empty() => *all = false,
+                    Field::Float(v) if !v.is_finite() => *finite = false,
+                    _ => {}
+                }
+            }
+        }
+    }
+    let columns = columns.into_iter().zip(stats).zip(base64).zip(finite);
+    columns
+        .map(|((((name, value_type), stats), (count, all)), finite)| {
+            let bytes =
+                options.base64_as_bytes && value_type == ValueType::VarChar && count > 0 && all;
+            ColumnPlan {
+                name,
+                value_type,
+                bytes,
+                inferred: Some(stats.inferred()).filter(|_| options.infer_types && !bytes),
+                not_null: options.constraints && !stats.is_nullable(),
+                finite,
+            }
+        })
+        .collect()
+}
+
+/// The database server that reads a dump
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SqlDialect {
+    /// PostgreSQL
+    Postgres,
+    /// MySQL or MariaDB
+    MySql,
+}
+
+impl SqlDialect {
+    fn write_ident<W: Write>(self, out: &mut W, name: &str) -> io::Result<()> {
+        let (quote, doubled) = match self {
+            SqlDialect::Postgres => ("\"", "\"\""),
+            SqlDialect::MySql => ("`", "``"),
+        };
+        write!(out, "{0}{1}{0}", quote, name.replace(quote, doubled))
+    }
+
+    fn type_name(self, column: &ColumnPlan) -> Cow<'static, str> {
+        match column.inferred {
+            // The other inferred types are the declared ones
+            Some(t @ InferredType::SmallInt) | Some(t @ InferredType::Integer) => {
+                return t.sql_name()
+            }
+            Some(t @ InferredType::VarChar(len))
+                if self == SqlDialect::Postgres || len <= MYSQL_VARCHAR_LEN =>
+            {
+                return t.sql_name()
+            }
+            _ => {}
+        }
+        Cow::Borrowed(match (self, column.value_type) {
+            (SqlDialect::Postgres, _) if column.bytes => "BYTEA",
+            (SqlDialect::MySql, _) if column.bytes => "LONGBLOB",
+            (_, ValueType::Integer) => "INTEGER",
+            (SqlDialect::Postgres, ValueType::Float) => "REAL",
+            (SqlDialect::MySql, ValueType::Float) => "FLOAT",
+            (_, ValueType::Boolean) => "BOOLEAN",
+            (_, ValueType::BigInt) => "BIGINT",
+            (SqlDialect::Postgres, _) => "TEXT",
+            // `TEXT` only holds 64 KiB in MySQL
+            (SqlDialect::MySql, _) => "LONGTEXT",
+        })
+    }
+
+    /// Check whether `column` can be declared as `NOT NULL`
+    fn not_null(self, column: &ColumnPlan) -> bool {
+        column.not_null && (self == SqlDialect::Postgres || column.finite)
+    }
+
+    fn write_text<W: Write>(self, out: &mut W, text: &str) -> io::Result<()> {
+        let mut escaped = String::with_capacity(text.len() + 2);
+        escaped.push('\'');
+        for c in text.chars() {
+            match (self, c) {
+                (_, '\'') => escaped.push_str("''"),
+                // MySQL treats backslashes as escapes by default
+                (SqlDialect::MySql, '\\') => escaped.push_str("\\\\"),
+                (SqlDialect::MySql, '\0') => escaped.push_str("\\0"),
+                (SqlDialect::MySql, '\u{1a}') => escaped.push_str("\\Z"),
+                (_, c) => escaped.push(c),
+            }
+        }
+        escaped.push('\'');
+        out.write_all(escaped.as_bytes())
+    }
+
+    fn write_value<W: Write>(self, out: &mut W, field: &Field, bytes: bool) -> io::Result<()> {
+        match field {
+            Field::VarChar(_) if bytes => match field.decode_varchar_base64() {
+                Ok(data) if self == SqlDialect::Postgres => write!(out, "'\\x{}'", to_hex(&data)),
+                Ok(data) => write!(out, "X'{}'", to_hex(&data)),
+                Err(_) => self.write_value(out, field, false),
+            },
+            Field::Nothing => out.write_all(b"NULL"),
+            Field::Integer(v) => write!(out, "{}", v),
+            Field::Float(v) if v.is_finite() => {
+                out.write_all(format_f32(*v, FloatFormat::Shortest).as_bytes())
+            }
+            Field::Float(v) => match self {
+                SqlDialect::Postgres if v.is_nan() => out.write_all(b"'NaN'"),
+                SqlDialect::Postgres if *v > 0.0 => out.write_all(b"'Infinity'"),
+                SqlDialect::Postgres => out.write_all(b"'-Infinity'"),
+                // MySQL has no representation for these
+                SqlDialect::MySql => out.write_all(b"NULL"),
+            },
+            Field::Text(v) | Field::VarChar(v) => self.write_text(out, v),
+            Field::Boolean(v) => out.write_all(if *v { b"TRUE" } else { b"FALSE" }),
+            Field::BigInt(v) => write!(out, "{}", v),
+        }
+    }
+
+    fn write_header<W: Write>(self, out: &mut W) -> io::Result<()> {
+        match self {
+            SqlDialect::Postgres => out.write_all(b"SET client_encoding = 'UTF8';\nBEGIN;\n\n"),
+            SqlDialect::MySql => out.write_all(b"SET NAMES utf8mb4;\nSTART TRANSACTION;\n\n"),
+        }
+    }
+
+    fn write_create<W: Write>(
+        self,
+        out: &mut W,
+        name: &str,
+        columns: &[ColumnPlan],
+    ) -> io::Result<()> {
+        out.write_all(b"CREATE TABLE IF NOT EXISTS ")?;
+        self.write_ident(out, name)?;
+        out.write_all(b" (")?;
+        for (index, column) in columns.iter().enumerate() {
+            out.write_all(if index > 0 { b",\n    " } else { b"\n    " })?;
+            self.write_ident(out, &column.name)?;
+            write!(out, " {}", self.type_name(column))?;
+            if self.not_null(column) {
+                out.write_all(b" NOT NULL")?;
+            }
+        }
+        out.write_all(b"\n);\n")
+    }
+
+    /// Write the row with the given `index` in the table, starting a new
+    /// `INSERT` every [`ROWS_PER_INSERT`] rows
+    fn write_row<W, I>(
+        self,
+        out: &mut W,
+        name: &str,
+        index: usize,
+        columns: &[ColumnPlan],
+        fields: I,
+    ) -> io::Result<()>
+    where
+        W: Write,
+        I: IntoIterator,
+        I::Item: Borrow<Field>,
+    {
+        if index.is_multiple_of(ROWS_PER_INSERT) {
+            if index > 0 {
+                out.write_all(b";\n")?;
+            }
+            out.write_all(b"INSERT INTO ")?;
+            self.write_ident(out, name)?;
+            out.write_all(b" VALUES\n(")?;
+        } else {
+            out.write_all(b",\n(")?;
+        }
+        for (index, (field, column)) in fields.into_iter().zip(columns).enumerate() {
+            if index > 0 {
+                out.write_all(b", ")?;
+            }
+            self.write_value(out, field.borrow(), column.bytes)?;
+        }
+        out.write_all(b")")
+    }
+
+    /// End a table with `rows` rows
+    fn write_end<W: Write>(self, out: &mut W, rows: usize) -> io::Result<()> {
+        if rows > 0 {
+            out.write_all(b";\n")?;
+        }
+        out.write_all(b"\n")
+    }
+}
+
+/// Write `schema` as a SQL script for `dialect`, see the [module documentation](self)
+///
+/// All statements are in a single transaction. The tables are created with
+/// `CREATE TABLE IF NOT EXISTS`, so that a dump can be loaded into an existing
+/// database, and the rows are inserted with up to 500 rows per statement.
+pub fn write_sql_dump<W: Write>(schema: &Schema, dialect: SqlDialect, out: W) -> io::Result<()> {
+    write_sql_dump_with(schema, dialect, &SqlDumpOptions::default(), out)
+}
+
+/// Like [`write_sql_dump`], with some [`SqlDumpOptions`]
+pub fn write_sql_dump_with<W: Write>(
+    schema: &Schema,
+    dialect: SqlDialect,
+    options: &SqlDumpOptions,
+    mut out: W,
+) -> io::Result<()> {
+    dialect.write_header(&mut out)?;
+    for table in schema {
+        let columns = table.columns().iter();
+        let columns = columns
+            .map(|c| (c.name.to_string(), c.field_type))
+            .collect();
+        let buckets = table.buckets().iter();
+        let fields = buckets.flat_map(|b| b.rows_ref().iter().map(|r| r.fields()));
+        let columns = plan_columns(columns, fields, options);
+        dialect.write_create(&mut out, table.name(), &columns)?;
+        let mut rows = 0;
+        let mut sorted = table.rows(options.order);
+        let names: Vec<_> = columns.iter().map(|c| &c.name).collect();
+        sort_rows_if_present(&mut sorted, &names, &options.sort);
+        for row in sorted {
+            dialect.write_row(&mut out, table.name(), rows, &columns, row.fields())?;
+            rows += 1;
+        }
+        dialect.write_end(&mut out, rows)?;
+    }
+    out.write_all(b"COMMIT;\n")?;
+    out.flush()
+}
+
+/// A [`RowSink`] that writes the same SQL script as [`write_sql_dump`]
+///
+/// This reads the rows from a [`mem::Database`][crate::fdb::mem::Database]
+/// instead of loading it into a [`Schema`] first.
+#[cfg(feature = "fdb-mem")]
+pub struct SqlDumpSink<W: Write> {
+    out: W,
+    dialect: SqlDialect,
+    options: SqlDumpOptions,
+    name: String,
+    columns: Vec<ColumnPlan>,
+    rows: usize,
+}
+
+#[cfg(feature = "fdb-mem")]
+impl<W: Write> SqlDumpSink<W> {
+    /// Create a new sink, writing the start of the transaction
+    pub fn new(out: W, dialect: SqlDialect) -> io::Result<Self> {
+        Self::with_options(out, dialect, SqlDumpOptions::default())
+    }
+
+    /// Like [`SqlDumpSink::new`], with some [`SqlDumpOptions`]
+    pub fn with_options(
+        mut out: W,
+        dialect: SqlDialect,
+        options: SqlDumpOptions,
+    ) -> io::Result<Self> {
+        dialect.write_header(&mut out)?;
+        Ok(Self {
+            out,
+            dialect,
+            options,
+            name: String::new(),
+            columns: Vec::new(),
+            rows: 0,
+        })
+    }
+}
+
+#[cfg(feature = "fdb-mem")]
+impl<W: Write> RowSink for SqlDumpSink<W> {
+    type Error = io::Error;
+
+    fn begin_table(&mut self, table: &mem::Table<'_>) -> io::Result<()> {
+        self.name = table.name().into_owned();
+        self.rows = 0;
+        let columns = table.column_iter();
+        let columns = columns.map(|c| (c.name().into_owned(), c.value_type()));
+        let fields = table.row_iter().map(|r| r.field_iter().map(Field::from));
+        self.columns = plan_columns(columns.collect(), fields, &self.options);
+        self.dialect
+            .write_create(&mut self.out, &self.name, &self.columns)
+    }
+
+    fn push_row(&mut self, row: mem::Row<'_>) -> io::Result<()> {
+        let fields = row.field_iter().map(Field::from);
+        let (name, columns) = (&self.name, &self.columns);
+        self.dialect
+            .write_row(&mut self.out, name, self.rows, columns, fields)?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    fn end_table(&mut self) -> io::Result<()> {
+        self.dialect.write_end(&mut self.out, self.rows)
+    }
+
+    fn order(&self) -> IterOrder {
+        self.options.order
+    }
+
+    fn index_strings(&self) -> bool {
+        self.options.index_strings
+    }
+
+    fn sort_keys(&self) -> &[(String, Order)] {
+        &self.options.sort
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.write_all(b"COMMIT;\n")?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fdb::core::{Column, Row, Table, TableDef};
+
+    #[test]
+    fn test_sql_dump() {
+        let mut table = Table::new(TableDef {
+            columns: vec![
+                Column::from(("id", ValueType::Integer)),
+                Column::from(("it's", ValueType::Text)),
+                Column::from(("scale", ValueType::Float)),
+            ],
+            name: String::from("Objects"),
+        });
+        table.buckets_mut().push(Default::default());
+        table.buckets_mut()[0].rows_mut().push(Row::from(vec![
+            Field::Integer(1),
+            Field::Text(String::from("Café 'Brick' \\ 1")),
+            Field::Float(f32::NAN),
+        ]));
+        let schema = Schema::from(vec![table]);
+
+        let mut pg = Vec::new();
+        write_sql_dump(&schema, SqlDialect::Postgres, &mut pg).unwrap();
+        let pg = String::from_utf8(pg).unwrap();
+        assert!(pg.contains("\"it's\" TEXT,"), "{}", pg);
+        assert!(
+            pg.contains("VALUES\n(1, 'Café ''Brick'' \\ 1', 'NaN');\n"),
+            "{}",
+            pg
+        );
+
+        let mut my = Vec::new();
+        write_sql_dump(&schema, SqlDialect::MySql, &mut my).unwrap();
+        let my = String::from_utf8(my).unwrap();
+        assert!(
+            my.contains("CREATE TABLE IF NOT EXISTS `Objects` ("),
+            "{}",
+            my
+        );
+        assert!(my.contains("(1, 'Café ''Brick'' \\\\ 1', NULL);"), "{}", my);
+        assert!(my.ends_with("COMMIT;\n"));
+    }
+
+    #[test]
+    #[cfg(feature = "fdb-mem")]
+    fn test_sql_dump_order() {
+        use crate::fdb::{mem::Database, sink::export_to_sink, testing::SampleDatabase};
+
+        let copy = |buckets| {
+            let db = SampleDatabase::new().table("Objects", &[("id", ValueType::Integer)]);
+            db.buckets(buckets).rows(20).build()
+        };
+        let options = SqlDumpOptions {
+            order: IterOrder::PrimaryKey,
+            index_strings: true,
+            ..SqlDumpOptions::default()
+        };
+        let dump = |buf: &[u8]| {
+            let schema = Schema::from_source(buf).unwrap();
+            let mut out = Vec::new();
+            write_sql_dump_with(&schema, SqlDialect::Postgres, &options, &mut out).unwrap();
+
+            let mut streamed = Vec::new();
+            let sink =
+                SqlDumpSink::with_options(&mut streamed, SqlDialect::Postgres, options.clone());
+            let tables = Database::new(buf).tables().unwrap();
+            export_to_sink(tables.iter(), sink.unwrap(), &()).unwrap();
+            assert_eq!(streamed, out);
+            out
+        };
+        let (small, large) = (copy(4), copy(32));
+        assert_eq!(dump(&small), dump(&large));
+        let sorted = String::from_utf8(dump(&small)).unwrap();
+        assert!(sorted.contains("VALUES\n(0),\n(1),\n(2),"), "{}", sorted);
+    }
+
+    #[test]
+    #[cfg(feature = "fdb-mem")]
+    fn test_sql_dump_sort() {
+        use crate::fdb::{mem::Database, sink::export_to_sink};
+
+        let buf = crate::fdb::testing::objects(20);
+        let options = SqlDumpOptions {
+            sort: vec![
+                (String::from("missing"), Order::Asc),
+                (String::from("id"), Order::Desc),
+            ],
+            ..SqlDumpOptions::default()
+        };
+        let schema = Schema::from_source(&buf[..]).unwrap();
+        let mut out = Vec::new();
+        write_sql_dump_with(&schema, SqlDialect::Postgres, &options, &mut out).unwrap();
+
+        let mut streamed = Vec::new();
+        let sink = SqlDumpSink::with_options(&mut streamed, SqlDialect::Postgres, options);
+        let tables = Database::new(&buf).tables().unwrap();
+        export_to_sink(tables.iter(), sink.unwrap(), &()).unwrap();
+        assert_eq!(streamed, out);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("VALUES\n(19, "), "{}", out);
+        assert!(out.contains("),\n(0, "), "{}", out);
+    }
+
+    #[test]
+    #[cfg(feature = "fdb-mem")]
+    fn test_sql_dump_bytes() {
+        use crate::fdb::{mem::Database, sink::export_to_sink, testing::SampleDatabase};
+
+        let columns = [("id", ValueType::Integer), ("data", ValueType::VarChar)];
+        let buf = SampleDatabase::new()
+            .table("Objects", &columns)
+            .row(vec![
+                Field::Integer(1),
+                Field::encode_varchar_base64(b"\x00\x01LEGO bricks"),
+            ])
+            .row(vec![Field::Integer(2), Field::Nothing])
+            .table("Names", &columns)
+            .row(vec![
+                Field::Integer(1),
+                Field::VarChar(String::from("Brick")),
+            ])
+            .build();
+        let options = SqlDumpOptions {
+            base64_as_bytes: true,
+            ..SqlDumpOptions::default()
+        };
+        let schema = Schema::from_source(&buf[..]).unwrap();
+        let dump = |dialect| {
+            let mut out = Vec::new();
+            write_sql_dump_with(&schema, dialect, &options, &mut out).unwrap();
+
+            let mut streamed = Vec::new();
+            let sink = SqlDumpSink::with_options(&mut streamed, dialect, options.clone());
+            let tables = Database::new(&buf).tables().unwrap();
+            export_to_sink(tables.iter(), sink.unwrap(), &()).unwrap();
+            assert_eq!(streamed, out);
+            String::from_utf8(out).unwrap()
+        };
+
+        let pg = dump(SqlDialect::Postgres);
+        assert!(pg.contains("\"data\" BYTEA\n"), "{}", pg);
+        assert!(
+            pg.contains("(1, '\\x00014c45474f20627269636b73')"),
+            "{}",
+            pg
+        );
+        assert!(pg.contains("(2, NULL)"), "{}", pg);
+        assert!(pg.contains("(1, 'Brick')"), "{}", pg);
+        let my = dump(SqlDialect::MySql);
+        assert!(my.contains("`data` LONGBLOB\n"), "{}", my);
+        assert!(my.contains("(1, X'00014c45474f20627269636b73')"), "{}", my);
+    }
+
+    #[test]
+    #[cfg(feature = "fdb-mem")]
+    fn test_sql_dump_infer() {
+        use crate::fdb::{mem::Database, sink::export_to_sink, testing::SampleDatabase};
+
+        let columns = [
+            ("id", ValueType::BigInt),
+            ("name", ValueType::Text),
+            ("scale", ValueType::Float),
+            ("tag", ValueType::VarChar),
+        ];
+        let long = "x".repeat(MYSQL_VARCHAR_LEN + 1);
+        let buf = SampleDatabase::new()
+            .table("Objects", &columns)
+            .row(vec![
+                Field::BigInt(1),
+                Field::Text(String::from("Brick")),
+                Field::Float(f32::NAN),
+                Field::Nothing,
+            ])
+            .row(vec![
+                Field::BigInt(40000),
+                Field::Text(String::from("Plate")),
+                Field::Float(1.0),
+                Field::VarChar(long),
+            ])
+            .build();
+        let options = SqlDumpOptions {
+            infer_types: true,
+            constraints: true,
+            ..SqlDumpOptions::default()
+        };
+        let schema = Schema::from_source(&buf[..]).unwrap();
+        let dump = |dialect| {
+            let mut out = Vec::new();
+            write_sql_dump_with(&schema, dialect, &options, &mut out).unwrap();
+
+            let mut streamed = Vec::new();
+            let sink = SqlDumpSink::with_options(&mut streamed, dialect, options.clone());
+            let tables = Database::new(&buf).tables().unwrap();
+            export_to_sink(tables.iter(), sink.unwrap(), &()).unwrap();
+            assert_eq!(streamed, out);
+            String::from_utf8(out).unwrap()
+        };
+
+        let pg = dump(SqlDialect::Postgres);
+        assert!(pg.contains("\"id\" INTEGER NOT NULL,"), "{}", pg);
+        assert!(pg.contains("\"name\" VARCHAR(5) NOT NULL,"), "{}", pg);
+        assert!(pg.contains("\"scale\" REAL NOT NULL,"), "{}", pg);
+        assert!(pg.contains("\"tag\" VARCHAR(256)\n"), "{}", pg);
+        let my = dump(SqlDialect::MySql);
+        assert!(my.contains("`scale` FLOAT,"), "{}", my);
+        assert!(my.contains("`tag` LONGTEXT\n"), "{}", my);
+
+        let plain = SqlDumpOptions::default();
+        let mut out = Vec::new();
+        write_sql_dump_with(&schema, SqlDialect::Postgres, &plain, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\"id\" BIGINT,"), "{}", out);
+        assert!(!out.contains("NOT NULL"), "{}", out);
+    }
+
+    #[test]
+    #[cfg(feature = "fdb-mem")]
+    fn test_sql_dump_sink() {
+        use crate::fdb::{mem::Database, sink::export_to_sink};
+
+        let buf = crate::fdb::testing::objects(ROWS_PER_INSERT + 1);
+        let schema = Schema::from_source(&buf[..]).unwrap();
+        let mut expected = Vec::new();
+        write_sql_dump(&schema, SqlDialect::MySql, &mut expected).unwrap();
+
+        let mut out = Vec::new();
+        let sink = SqlDumpSink::new(&mut out, SqlDialect::MySql).unwrap();
+        let tables = Database::new(&buf).tables().unwrap();
+        export_to_sink(tables.iter(), sink, &()).unwrap();
+        assert_eq!(String::from_utf8(out), String::from_utf8(expected));
+    }
+}
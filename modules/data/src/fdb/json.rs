@@ -0,0 +1,180 @@
+//! # Conversions between fields and JSON values
+//!
+//! This module implements conversions from [`mem::Field`] and [`core::Field`]
+//! to [`serde_json::Value`] and back. As JSON doesn't distinguish between
+//! integers of different size or between text and XML, the conversion to a
+//! [`core::Field`] needs a [`ValueType`] as a hint.
+//!
+//! - `NULL` is represented as `null` for every type
+//! - `INTEGER`, `FLOAT` and `BIGINT` are numbers
+//! - `TEXT` and `VARCHAR` are strings
+//! - `BOOLEAN` is a boolean
+//!
+//! Because not all JSON implementations can represent the full range of 64-bit
+//! integers, a `BIGINT` may also be read from a string.
+//!
+//! A `VARCHAR` with a binary payload can be written as `{"hex": "…"}` with
+//! [`varchar_hex`], and is read back as the same payload in base64 (see the
+//! [`base64`][super::base64] module).
+//!
+//! [`mem::Field`]: super::mem::Field
+//! [`core::Field`]: super::core::Field
+
+#[cfg(feature = "fdb-core")]
+use std::convert::TryFrom;
+
+use assembly_core::displaydoc::Display;
+use serde_json::{Number, Value as JsonValue};
+use thiserror::Error;
+
+#[cfg(feature = "fdb-core")]
+use super::core::Field;
+#[cfg(feature = "fdb-mem")]
+use super::mem;
+use super::{base64, common::ValueType, float::widen};
+
+#[derive(Debug, Display, Error, Clone, PartialEq)]
+#[non_exhaustive]
+/// Error when converting a JSON value to a field
+pub enum JsonFieldError {
+    /// Expected a value of type {0}, found {1}
+    TypeMismatch(ValueType, JsonValue),
+    /// The number {1} is out of range for {0}
+    OutOfRange(ValueType, Number),
+    /// Invalid binary payload: {0}
+    Binary(#[from] base64::Base64Error),
+}
+
+fn float(v: f32) -> JsonValue {
+    Number::from_f64(widen(v)).map_or(JsonValue::Null, JsonValue::Number)
+}
+
+#[cfg(feature = "fdb-mem")]
+impl From<&mem::Field<'_>> for JsonValue {
+    fn from(field: &mem::Field<'_>) -> Self {
+        match field {
+            mem::Field::Nothing => JsonValue::Null,
+            mem::Field::Integer(i) => JsonValue::from(*i),
+            mem::Field::Float(f) => float(*f),
+            mem::Field::Text(s) => JsonValue::String(s.decode().into_owned()),
+            mem::Field::Boolean(b) => JsonValue::Bool(*b),
+            mem::Field::BigInt(i) => JsonValue::from(*i),
+            mem::Field::VarChar(s) => JsonValue::String(s.decode().into_owned()),
+        }
+    }
+}
+
+#[cfg(feature = "fdb-core")]
+impl From<&Field> for JsonValue {
+    fn from(field: &Field) -> Self {
+        match field {
+            Field::Nothing => JsonValue::Null,
+            Field::Integer(i) => JsonValue::from(*i),
+            Field::Float(f) => float(*f),
+            Field::Text(s) => JsonValue::String(s.clone()),
+            Field::Boolean(b) => JsonValue::Bool(*b),
+            Field::BigInt(i) => JsonValue::from(*i),
+            Field::VarChar(s) => JsonValue::String(s.clone()),
+        }
+    }
+}
+
+/// Convert a `VARCHAR` with a base64 payload to `{"hex": "…"}`
+///
+/// Returns `None` if the field is not a `VARCHAR` or not valid base64.
+#[cfg(feature = "fdb-core")]
+pub fn varchar_hex(field: &Field) -> Option<JsonValue> {
+    let bytes = field.decode_varchar_base64().ok()?;
+    let mut object = serde_json::Map::new();
+    object.insert(
+        String::from("hex"),
+        JsonValue::String(base64::to_hex(&bytes)),
+    );
+    Some(JsonValue::Object(object))
+}
+
+#[cfg(feature = "fdb-core")]
+impl TryFrom<(&JsonValue, ValueType)> for Field {
+    type Error = JsonFieldError;
+
+    fn try_from((value, value_type): (&JsonValue, ValueType)) -> Result<Self, Self::Error> {
+        let mismatch = || JsonFieldError::TypeMismatch(value_type, value.clone());
+        match (value_type, value) {
+            (_, JsonValue::Null) => Ok(Field::Nothing),
+            (ValueType::Integer, JsonValue::Number(n)) => n
+                .as_i64()
+                .ok_or_else(mismatch)
+                .and_then(|i| {
+                    i32::try_from(i).map_err(|_| JsonFieldError::OutOfRange(value_type, n.clone()))
+                })
+                .map(Field::Integer),
+            (ValueType::Float, JsonValue::Number(n)) => n
+                .as_f64()
+                .map(|f| Field::Float(f as f32))
+                .ok_or_else(mismatch),
+            (ValueType::Text, JsonValue::String(s)) => Ok(Field::Text(s.clone())),
+            (ValueType::Boolean, JsonValue::Bool(b)) => Ok(Field::Boolean(*b)),
+            (ValueType::BigInt, JsonValue::Number(n)) => {
+                n.as_i64().map(Field::BigInt).ok_or_else(mismatch)
+            }
+            (ValueType::BigInt, JsonValue::String(s)) => {
+                s.parse().map(Field::BigInt).map_err(|_| mismatch())
+            }
+            (ValueType::VarChar, JsonValue::String(s)) => Ok(Field::VarChar(s.clone())),
+            (ValueType::VarChar, JsonValue::Object(o)) if o.len() == 1 => {
+                let hex = o
+                    .get("hex")
+                    .and_then(JsonValue::as_str)
+                    .ok_or_else(mismatch)?;
+                let bytes = base64::from_hex(hex.as_bytes())?;
+                Ok(Field::encode_varchar_base64(&bytes))
+            }
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let fields = [
+            (Field::Nothing, ValueType::Integer),
+            (Field::Integer(-3), ValueType::Integer),
+            (Field::Float(0.5), ValueType::Float),
+            (Field::Text("Hello".to_string()), ValueType::Text),
+            (Field::Boolean(true), ValueType::Boolean),
+            (Field::BigInt(1 << 40), ValueType::BigInt),
+            (Field::VarChar("<a/>".to_string()), ValueType::VarChar),
+        ];
+        for (field, value_type) in &fields {
+            let json = JsonValue::from(field);
+            assert_eq!(Field::try_from((&json, *value_type)).as_ref(), Ok(field));
+        }
+
+        let binary = Field::encode_varchar_base64(&[0, 1, 254, 255]);
+        let json = varchar_hex(&binary).unwrap();
+        let hex = json.as_object().unwrap().get("hex");
+        assert_eq!(hex, Some(&JsonValue::from("0001feff")));
+        assert_eq!(Field::try_from((&json, ValueType::VarChar)), Ok(binary));
+
+        let big = JsonValue::String("-9000000000".to_string());
+        assert_eq!(
+            Field::try_from((&big, ValueType::BigInt)),
+            Ok(Field::BigInt(-9_000_000_000))
+        );
+        let large = JsonValue::from(1i64 << 40);
+        assert!(matches!(
+            Field::try_from((&large, ValueType::Integer)),
+            Err(JsonFieldError::OutOfRange(ValueType::Integer, _))
+        ));
+        assert!(matches!(
+            Field::try_from((&JsonValue::Bool(false), ValueType::Text)),
+            Err(JsonFieldError::TypeMismatch(ValueType::Text, _))
+        ));
+    }
+}
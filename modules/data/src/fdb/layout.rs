@@ -0,0 +1,213 @@
+//! # The layout of a database file
+//!
+//! The file format only specifies structures and the addresses that link them,
+//! not where they need to be. The original files and the writer in
+//! [`super::store`] place them in a specific order, but third-party writers
+//! may not. This module lists all regions of a file in the order of their
+//! address, and flags the bytes that are not used by any structure (gaps) as
+//! well as structures that share bytes (overlaps).
+//!
+//! ```
+//! use assembly_data::fdb::layout;
+//!
+//! let file: &[u8] = &[0,0,0,0,8,0,0,0];
+//! let layout = layout::analyze(file).unwrap();
+//! assert_eq!(layout.regions.len(), 1);
+//! assert!(layout.gaps.is_empty());
+//! ```
+
+use std::{fmt, ops::Range};
+
+use assembly_core::buffer::CastError;
+
+use super::mem::raw::{self, RawStruct};
+
+/// A structure of the file and the bytes it occupies
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// The bytes of the structure
+    pub range: Range<u32>,
+    /// The structure
+    pub value: RawStruct,
+}
+
+/// Two regions that share some bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlap {
+    /// The index of the first region in [`Layout::regions`]
+    pub first: usize,
+    /// The index of the second region in [`Layout::regions`]
+    pub second: usize,
+}
+
+/// The regions of a file, ordered by address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    /// The size of the file
+    pub file_size: u32,
+    /// All structures, ordered by address
+    ///
+    /// Strings and integers that are referenced more than once only appear once.
+    pub regions: Vec<Region>,
+    /// The ranges of bytes that are not part of any region
+    ///
+    /// The padding up to the next multiple of 4 bytes is always a separate gap.
+    pub gaps: Vec<Range<u32>>,
+    /// The regions that share bytes with a previous region
+    pub overlaps: Vec<Overlap>,
+}
+
+/// Check whether a gap is just the padding of a string to a multiple of 4 bytes
+pub fn is_padding(gap: &Range<u32>) -> bool {
+    gap.end - gap.start < 4 && gap.end & 3 == 0
+}
+
+impl Layout {
+    /// The gaps that are not just padding (see [`is_padding`])
+    pub fn unexpected_gaps(&self) -> impl Iterator<Item = &Range<u32>> {
+        self.gaps.iter().filter(|g| !is_padding(g))
+    }
+
+    /// The number of bytes that are part of some region
+    pub fn used_bytes(&self) -> u32 {
+        self.file_size - self.gaps.iter().map(|g| g.end - g.start).sum::<u32>()
+    }
+}
+
+/// Push a gap, splitting off the padding to the next multiple of 4 bytes
+fn push_gap(gaps: &mut Vec<Range<u32>>, gap: Range<u32>) {
+    let aligned = ((gap.start + 3) & !3).min(gap.end);
+    if aligned > gap.start && aligned < gap.end {
+        gaps.push(gap.start..aligned);
+        gaps.push(aligned..gap.end);
+    } else {
+        gaps.push(gap);
+    }
+}
+
+/// Compute the layout of a database file
+///
+/// This fails if any of the structures that are referenced is out of bounds.
+pub fn analyze(buf: &[u8]) -> Result<Layout, CastError> {
+    let mut regions: Vec<_> = raw::snapshot(buf)?
+        .into_iter()
+        .map(|at| Region {
+            range: at.addr..at.addr + at.value.size(),
+            value: at.value,
+        })
+        .collect();
+    regions.sort_by_key(|r| (r.range.start, r.range.end));
+    regions.dedup();
+
+    let mut gaps = Vec::new();
+    let mut overlaps = Vec::new();
+    let mut end = 0;
+    let mut last = None;
+    for (index, region) in regions.iter().enumerate() {
+        if region.range.start > end {
+            push_gap(&mut gaps, end..region.range.start);
+        } else if let (true, Some(first)) = (region.range.start < end, last) {
+            overlaps.push(Overlap {
+                first,
+                second: index,
+            });
+        }
+        if region.range.end > end {
+            end = region.range.end;
+            last = Some(index);
+        }
+    }
+    let file_size = buf.len() as u32;
+    if file_size > end {
+        push_gap(&mut gaps, end..file_size);
+    }
+
+    Ok(Layout {
+        file_size,
+        regions,
+        gaps,
+        overlaps,
+    })
+}
+
+fn region_name(value: &RawStruct) -> &'static str {
+    match value {
+        RawStruct::Header(_) => "header",
+        RawStruct::TableHeader(_) => "table header",
+        RawStruct::TableDefHeader(_) => "table definition",
+        RawStruct::ColumnHeader(_) => "column",
+        RawStruct::TableDataHeader(_) => "table data",
+        RawStruct::BucketHeader(_) => "bucket",
+        RawStruct::RowHeaderListEntry(_) => "row list entry",
+        RawStruct::RowHeader(_) => "row header",
+        RawStruct::FieldData(_) => "field",
+        RawStruct::String(_) => "string",
+        RawStruct::I64(_) => "i64",
+    }
+}
+
+impl fmt::Display for Layout {
+    /// Prints one line per run of regions of the same kind, and one per gap
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut gaps = self.unexpected_gaps().peekable();
+        let mut regions = self.regions.iter().peekable();
+        while let Some(region) = regions.next() {
+            while let Some(gap) = gaps.next_if(|g| g.start <= region.range.start) {
+                writeln!(f, "{:08x}..{:08x} <gap>", gap.start, gap.end)?;
+            }
+            let name = region_name(&region.value);
+            let (mut count, mut end) = (1, region.range.end);
+            while let Some(next) = regions.next_if(|r| region_name(&r.value) == name) {
+                count += 1;
+                end = end.max(next.range.end);
+            }
+            writeln!(
+                f,
+                "{:08x}..{:08x} {} x{}",
+                region.range.start, end, name, count
+            )?;
+        }
+        for gap in gaps {
+            writeln!(f, "{:08x}..{:08x} <gap>", gap.start, gap.end)?;
+        }
+        for overlap in &self.overlaps {
+            let second = &self.regions[overlap.second];
+            writeln!(
+                f,
+                "{:08x} <overlap> {} / {}",
+                second.range.start,
+                region_name(&self.regions[overlap.first].value),
+                region_name(&second.value)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{common::ValueType, core::Field, testing::SampleDatabase};
+
+    #[test]
+    fn test_layout() {
+        let columns = [("id", ValueType::Integer), ("name", ValueType::Text)];
+        let mut buf = SampleDatabase::new()
+            .table("Names", &columns)
+            .buckets(2)
+            .row(vec![Field::Integer(0), Field::Text(String::from("Zero"))])
+            .build();
+
+        let layout = analyze(&buf).unwrap();
+        assert!(layout.overlaps.is_empty());
+        assert_eq!(layout.unexpected_gaps().count(), 0);
+        assert_eq!(layout.regions[0].range, 0..8);
+
+        buf.extend_from_slice(&[0; 16]);
+        let layout = analyze(&buf).unwrap();
+        let gaps: Vec<_> = layout.unexpected_gaps().cloned().collect();
+        let len = buf.len() as u32;
+        assert_eq!(gaps, vec![len - 16..len]);
+        assert!(layout.to_string().contains("<gap>"));
+    }
+}
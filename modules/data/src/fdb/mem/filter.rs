@@ -0,0 +1,65 @@
+//! # Bloom filters for primary keys
+//!
+//! Checking whether an ID is *not* in a table still needs to walk the whole
+//! bucket chain for that ID. When cross-checking large external datasets
+//! against a database, most lookups may be negative, so a [`PkFilter`] can
+//! answer those without touching the file at all.
+
+/// The number of bits per primary key
+const BITS_PER_KEY: usize = 10;
+/// The number of hash functions, optimal for [`BITS_PER_KEY`]
+const HASH_COUNT: u32 = 7;
+
+/// A bloom filter over the integer primary keys of a table
+///
+/// [`PkFilter::may_contain`] never returns `false` for a key that is in the
+/// table, but may return `true` for about 1% of the keys that are not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PkFilter {
+    bits: Vec<u64>,
+}
+
+fn mix(id: u32) -> u64 {
+    // splitmix64 finalizer
+    let mut z = u64::from(id).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl PkFilter {
+    /// Create a filter from a list of keys
+    pub fn new<I: IntoIterator<Item = u32>>(keys: I) -> Self {
+        let keys: Vec<u32> = keys.into_iter().collect();
+        let words = (keys.len() * BITS_PER_KEY).div_ceil(64);
+        let mut filter = Self {
+            bits: vec![0; words.max(1)],
+        };
+        for key in keys {
+            for bit in filter.bit_indices(key) {
+                filter.bits[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+        filter
+    }
+
+    fn bit_indices(&self, id: u32) -> impl Iterator<Item = usize> {
+        let len = (self.bits.len() * 64) as u64;
+        let hash = mix(id);
+        let (h1, h2) = (hash & 0xFFFF_FFFF, (hash >> 32) | 1);
+        (0..u64::from(HASH_COUNT)).map(move |i| (h1.wrapping_add(i * h2) % len) as usize)
+    }
+
+    /// Check whether the key may be in the table
+    ///
+    /// If this returns `false`, the key is definitely not in the table.
+    pub fn may_contain(&self, id: u32) -> bool {
+        self.bit_indices(id)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// The size of the filter in bytes
+    pub fn byte_size(&self) -> usize {
+        self.bits.len() * 8
+    }
+}
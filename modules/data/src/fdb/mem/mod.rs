@@ -8,13 +8,47 @@
 //!
 //! The only limitation is, that all references are bounded by the lifetime
 //! of the original database buffer.
+//!
+//! The accessors assume a well-formed file and may panic otherwise. For files
+//! of unknown origin, check them first with [`open::OpenOptions`].
+//!
+//! ## Thread safety
+//!
+//! The structures only contain shared references into the buffer, so they are
+//! all [`Send`] and [`Sync`] without any `unsafe impl`. A server can load the
+//! file once (e.g. into a `&'static [u8]` or an [`Arc`][std::sync::Arc]) and
+//! read from it on any number of threads. This is checked at compile time for
+//! [`Database`], [`Tables`], [`TableMap`], [`Table`], [`Column`], [`Bucket`],
+//! [`Row`], [`Field`] and their iterators.
+//!
+//! ## Embedding a database
+//!
+//! The [`include_fdb!`][crate::include_fdb] macro includes an FDB file into
+//! the binary at compile time, so that a tool can ship with its data. Because
+//! [`Database::new`] is a `const fn`, the result can be stored in a `static`.
+//!
+//! ## Memory safety
+//!
+//! All structs of the file are read through [`assembly_core::buffer`], which
+//! only creates references to types with an alignment of `1`, so the buffer
+//! may start at any address. The tests of this module can be run under
+//! [Miri](https://github.com/rust-lang/miri) to check this:
+//!
+//! ```text
+//! cargo +nightly miri test --lib fdb::mem
+//! ```
 use assembly_core::buffer::{self, Repr, LEI64};
 use buffer::CastError;
 use memchr::memchr;
 
 mod c;
+pub mod filter;
+pub mod open;
+pub mod project;
+pub mod raw;
+pub mod strings;
 use super::{
-    common::{Context, Latin1Str, Value, ValueMapperMut, ValueType},
+    common::{Context, IterOrder, Latin1Str, Latin1String, Value, ValueMapperMut, ValueType},
     file::{FDBFieldValue, FileContext, IndirectValue},
     ro::{
         buffer::{compare_bytes, Buffer},
@@ -25,10 +59,14 @@ use c::{
     FDBBucketHeaderC, FDBColumnHeaderC, FDBFieldDataC, FDBHeaderC, FDBRowHeaderC,
     FDBRowHeaderListEntryC, FDBTableDataHeaderC, FDBTableDefHeaderC, FDBTableHeaderC,
 };
+use filter::PkFilter;
 use std::{
     borrow::Cow,
+    collections::HashMap,
     convert::{Infallible, TryFrom},
+    sync::OnceLock,
 };
+use strings::StringIndex;
 
 fn get_latin1_str(buf: &[u8], offset: u32) -> &Latin1Str {
     let (_, haystack) = buf.split_at(offset as usize);
@@ -43,6 +81,27 @@ fn get_latin1_str(buf: &[u8], offset: u32) -> &Latin1Str {
     }
 }
 
+/// Include an FDB file at compile time as a [`Database<'static>`][Database]
+///
+/// The path is resolved like for [`include_bytes!`], i.e. relative to the file
+/// that contains the invocation. The file is not checked until it is read, see
+/// [`open::OpenOptions`] for that.
+///
+/// ```
+/// use assembly_data::{fdb::mem::Database, include_fdb};
+///
+/// static DB: Database<'static> = include_fdb!("../../../res/example.fdb");
+///
+/// let table = DB.tables().unwrap().by_name("Objects").unwrap().unwrap();
+/// assert_eq!(table.row_iter().count(), 3);
+/// ```
+#[macro_export]
+macro_rules! include_fdb {
+    ($path:expr) => {
+        $crate::fdb::mem::Database::new(include_bytes!($path))
+    };
+}
+
 /// A complete in-memory read-only database
 ///
 /// This struct contains a reference to the complete byte buffer of an FDB file.
@@ -53,11 +112,16 @@ pub struct Database<'a> {
 
 impl<'a> Database<'a> {
     /// Create a new database reference
-    pub fn new(buf: &'a [u8]) -> Self {
+    pub const fn new(buf: &'a [u8]) -> Self {
         let inner = Handle::new_ref(buf);
         Self { inner }
     }
 
+    /// Returns the underlying buffer
+    pub fn as_bytes(self) -> &'a [u8] {
+        self.inner.buf().as_bytes()
+    }
+
     /// Get a reference to the header
     pub fn header(self) -> Result<Header<'a>, CastError> {
         let inner = self.inner.try_map_cast(0)?;
@@ -70,6 +134,53 @@ impl<'a> Database<'a> {
         let tables = header.tables()?;
         Ok(tables)
     }
+
+    /// Returns a lookup table for tables by name, see [`TableMap`]
+    pub fn table_map(self) -> Result<TableMap<'a>, CastError> {
+        Ok(TableMap {
+            tables: self.tables()?,
+            map: OnceLock::new(),
+        })
+    }
+}
+
+/// A lookup table from table name to table
+///
+/// [`Tables::by_name`] does a binary search over the table headers, which
+/// reads the name of a table at every step. This map is built on the first
+/// lookup, thread-safely and only once, and then finds any table by a single
+/// hash lookup. Create it once, and share a reference to it between threads,
+/// since a [`Database`] is only a pointer to the buffer and can't hold it.
+pub struct TableMap<'a> {
+    tables: Tables<'a>,
+    map: OnceLock<HashMap<&'a [u8], usize>>,
+}
+
+impl<'a> TableMap<'a> {
+    fn map(&self) -> &HashMap<&'a [u8], usize> {
+        self.map.get_or_init(|| {
+            let buf = self.tables.inner.buf().as_bytes();
+            let mut map = HashMap::with_capacity(self.tables.len());
+            for (index, header) in self.tables.inner.into_raw().iter().enumerate() {
+                let def_header_addr = header.table_def_header_addr.extract();
+                let def_header = buffer::cast::<FDBTableDefHeaderC>(buf, def_header_addr);
+                let name = get_latin1_str(buf, def_header.table_name_addr.extract());
+                map.insert(name.as_bytes(), index);
+            }
+            map
+        })
+    }
+
+    /// Get a table by its name
+    pub fn by_name(&self, name: &str) -> Option<Result<Table<'a>, CastError>> {
+        let index = *self.map().get(name.as_bytes())?;
+        self.tables.get(index)
+    }
+
+    /// Returns the underlying tables array
+    pub fn tables(&self) -> Tables<'a> {
+        self.tables
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -161,6 +272,23 @@ impl<'a> Tables<'a> {
             .ok()
             .and_then(|index| self.get(index))
     }
+
+    /// Get a table by its name, ignoring case
+    ///
+    /// This compares the names like [`Latin1Str::eq_ignore_case`], so it
+    /// also ignores the case of accented letters. Unlike [`Tables::by_name`],
+    /// it needs to check every table.
+    pub fn by_name_ci(&self, name: &str) -> Option<Result<Table<'a>, CastError>> {
+        let name = Latin1String::encode(name);
+        let buf = self.inner.buf().as_bytes();
+        let index = self.inner.into_raw().iter().position(|table_header| {
+            let def_header_addr = table_header.table_def_header_addr.extract();
+            let def_header = buffer::cast::<FDBTableDefHeaderC>(buf, def_header_addr);
+            let table_name = get_latin1_str(buf, def_header.table_name_addr.extract());
+            table_name.eq_ignore_case(name.as_bytes())
+        })?;
+        self.get(index)
+    }
 }
 
 #[allow(clippy::needless_lifetimes)] // <- clippy gets this wrong, presumably because of impl trait?
@@ -240,14 +368,59 @@ impl<'a> Table<'a> {
     }
 
     /// Get a list of rows by index
+    ///
+    /// A table without buckets has no rows for any index.
     pub fn index_iter(&self, id: u32) -> impl Iterator<Item = Row<'a>> {
-        let bucket: usize = id as usize % self.bucket_count();
-        self.bucket_at(bucket).into_iter().flat_map(move |b| {
+        let bucket = (id as usize).checked_rem(self.bucket_count());
+        let bucket = bucket.and_then(|bucket| self.bucket_at(bucket));
+        bucket.into_iter().flat_map(move |b| {
             b.row_iter()
                 .filter(move |r| r.field_at(0) == Some(Field::Integer(id as i32)))
         })
     }
 
+    /// Check whether there is a row with the integer primary key `id`
+    ///
+    /// Unlike [`Table::index_iter`], this only compares the raw bytes of the
+    /// first field of each row in the bucket for `id`, without creating any
+    /// [`Field`] values. This makes it suitable for validating lots of IDs.
+    pub fn contains_pk(&self, id: u32) -> bool {
+        let buf = self.inner.mem.as_bytes();
+        let count = self.bucket_count();
+        if count == 0 {
+            return false;
+        }
+        let bucket = self.inner.raw.buckets[id as usize % count].extract();
+        let data_type = u32::from(ValueType::Integer);
+        let bytes = id.to_le_bytes();
+
+        let mut next = get_row_header_list_entry(buf, bucket.row_header_list_head_addr);
+        while let Some(entry) = next {
+            let entry = entry.extract();
+            let row = buffer::cast::<FDBRowHeaderC>(buf, entry.row_header_addr).extract();
+            if row.fields.count > 0 {
+                let field = buffer::cast::<FDBFieldDataC>(buf, row.fields.base_offset);
+                if field.data_type.extract() == data_type && field.value.0 == bytes {
+                    return true;
+                }
+            }
+            next = get_row_header_list_entry(buf, entry.row_header_list_next_addr);
+        }
+        false
+    }
+
+    /// Build a bloom filter over the integer primary keys of this table
+    ///
+    /// This reads every row once. Afterwards, [`PkFilter::may_contain`] rejects
+    /// most IDs that are not in the table without walking a bucket chain, so
+    /// it is useful to check them before calling [`Table::contains_pk`].
+    pub fn build_pk_filter(&self) -> PkFilter {
+        PkFilter::new(self.row_iter().filter_map(|r| match r.field_at(0) {
+            Some(Field::Integer(id)) => Some(id as u32),
+            _ => None,
+        }))
+    }
+
     /// Get the column at the index
     ///
     /// **Note**: This does some computation, call only once per colum if possible
@@ -286,6 +459,11 @@ impl<'a> Table<'a> {
             .map(map_bucket_header(self.inner.mem.as_bytes()))
     }
 
+    /// Returns the buffer of the file that contains this table
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.inner.mem.as_bytes()
+    }
+
     /// Get the bucket iterator
     ///
     /// **Note**: This does some computation, call only once if possible
@@ -303,9 +481,35 @@ impl<'a> Table<'a> {
     }
 
     /// Get an iterator over all rows
+    ///
+    /// The rows are visited in [`IterOrder::File`] order, use [`Table::rows`]
+    /// for a layout independent order.
     pub fn row_iter(&self) -> impl Iterator<Item = Row<'a>> {
         self.bucket_iter().map(|b| b.row_iter()).flatten()
     }
+
+    /// Get an iterator over all rows, in the given order
+    ///
+    /// Unlike [`Table::rows`], this doesn't collect the rows for
+    /// [`IterOrder::File`].
+    pub fn row_iter_in(&self, order: IterOrder) -> Box<dyn Iterator<Item = Row<'a>> + 'a> {
+        match order {
+            IterOrder::File => Box::new(self.row_iter()),
+            order => Box::new(self.rows(order).into_iter()),
+        }
+    }
+
+    /// Get all rows, in the given order
+    pub fn rows(&self, order: IterOrder) -> Vec<Row<'a>> {
+        let mut rows: Vec<Row<'a>> = self.row_iter().collect();
+        if order == IterOrder::PrimaryKey {
+            rows.sort_by(|a, b| match (a.field_at(0), b.field_at(0)) {
+                (Some(a), Some(b)) => a.sort_cmp(&b),
+                (a, b) => a.is_some().cmp(&b.is_some()),
+            });
+        }
+        rows
+    }
 }
 
 /// Reference to a column definition
@@ -378,6 +582,7 @@ impl<'a> Iterator for RowHeaderIter<'a> {
             Some(Row {
                 buf: self.buf,
                 fields,
+                strings: None,
             })
         } else {
             None
@@ -390,6 +595,7 @@ impl<'a> Iterator for RowHeaderIter<'a> {
 pub struct Row<'a> {
     buf: &'a [u8],
     fields: &'a [FDBFieldDataC],
+    strings: Option<&'a StringIndex<'a>>,
 }
 
 fn get_field<'a>(data: &'a FDBFieldDataC, buf: &'a [u8]) -> Field<'a> {
@@ -398,6 +604,24 @@ fn get_field<'a>(data: &'a FDBFieldDataC, buf: &'a [u8]) -> Field<'a> {
     get_field_raw(data_type, bytes, buf)
 }
 
+/// Like [`get_field`], but looks up strings in `strings` if there is an index
+///
+/// Falls back to [`get_field`] if the address is not in the index.
+fn get_field_indexed<'a>(
+    data: &'a FDBFieldDataC,
+    buf: &'a [u8],
+    strings: Option<&'a StringIndex<'a>>,
+) -> Field<'a> {
+    let addr = u32::from_le_bytes(data.value.0);
+    let text = || strings.and_then(|strings| strings.get(addr));
+    match ValueType::try_from(data.data_type.extract()) {
+        Ok(ValueType::Text) => text().map(Field::Text),
+        Ok(ValueType::VarChar) => text().map(Field::VarChar),
+        _ => None,
+    }
+    .unwrap_or_else(|| get_field(data, buf))
+}
+
 fn get_field_raw(data_type: ValueType, bytes: [u8; 4], buf: &[u8]) -> Field {
     match data_type {
         ValueType::Nothing => Field::Nothing,
@@ -426,20 +650,40 @@ fn get_field_raw(data_type: ValueType, bytes: [u8; 4], buf: &[u8]) -> Field {
 pub struct FieldIter<'a> {
     buf: &'a [u8],
     iter: std::slice::Iter<'a, FDBFieldDataC>,
+    strings: Option<&'a StringIndex<'a>>,
 }
 
 impl<'a> Iterator for FieldIter<'a> {
     type Item = Field<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next().map(|data| get_field(data, self.buf))
+        self.iter
+            .next()
+            .map(|data| get_field_indexed(data, self.buf, self.strings))
     }
 }
 
 impl<'a> Row<'a> {
+    /// Look up the strings of this row in `strings`
+    ///
+    /// This avoids searching for the end of each string, see [`StringIndex`].
+    /// The index is ignored if it was built for a different buffer.
+    pub fn with_strings(self, strings: &'a StringIndex<'a>) -> Self {
+        if std::ptr::eq(self.buf, strings.as_bytes()) {
+            Self {
+                strings: Some(strings),
+                ..self
+            }
+        } else {
+            self
+        }
+    }
+
     /// Get the field at the index
     pub fn field_at(&self, index: usize) -> Option<Field<'a>> {
-        self.fields.get(index).map(|data| get_field(data, self.buf))
+        self.fields
+            .get(index)
+            .map(|data| get_field_indexed(data, self.buf, self.strings))
     }
 
     /// Get the iterator over all fields
@@ -447,6 +691,7 @@ impl<'a> Row<'a> {
         FieldIter {
             iter: self.fields.iter(),
             buf: self.buf,
+            strings: self.strings,
         }
     }
 
@@ -465,6 +710,25 @@ impl<'a> IntoIterator for Row<'a> {
     }
 }
 
+/// Fails to compile if one of the reference types is not `Send + Sync`
+#[allow(dead_code)]
+fn assert_send_sync() {
+    fn check<T: Send + Sync>() {}
+    check::<Database<'static>>();
+    check::<Header<'static>>();
+    check::<Tables<'static>>();
+    check::<TableMap<'static>>();
+    check::<TableIter<'static>>();
+    check::<Table<'static>>();
+    check::<Column<'static>>();
+    check::<Bucket<'static>>();
+    check::<RowHeaderIter<'static>>();
+    check::<Row<'static>>();
+    check::<FieldIter<'static>>();
+    check::<Field<'static>>();
+    check::<StringIndex<'static>>();
+}
+
 #[derive(Debug, PartialEq)]
 /// The context for `mem::Field`
 pub struct MemContext<'a> {
@@ -504,3 +768,147 @@ impl<'a> TryFrom<Handle<'a, FDBFieldValue>> for Field<'a> {
         Ok(value.raw().map(&mut mem))
     }
 }
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{core, testing::SampleDatabase};
+
+    /// An `Objects` table with the given number of buckets and ids
+    fn objects<I: IntoIterator<Item = i32>>(buckets: usize, ids: I) -> Vec<u8> {
+        let db = SampleDatabase::new().table("Objects", &[("id", ValueType::Integer)]);
+        let db = ids.into_iter().fold(db.buckets(buckets), |db, id| {
+            db.row(vec![core::Field::Integer(id)])
+        });
+        db.build()
+    }
+
+    #[test]
+    fn test_contains_pk() {
+        let buf = objects(4, vec![1, 5, 6]);
+
+        let tables = Database::new(&buf).tables().unwrap();
+        let table = tables.by_name("Objects").unwrap().unwrap();
+        let found: Vec<_> = (0..8).filter(|id| table.contains_pk(*id)).collect();
+        assert_eq!(found, vec![1, 5, 6]);
+
+        assert!(tables.by_name("OBJECTS").is_none());
+        let table = tables.by_name_ci("OBJECTS").unwrap().unwrap();
+        assert_eq!(table.name(), "Objects");
+    }
+
+    #[test]
+    fn test_pk_filter() {
+        let buf = objects(16, (0..1000).step_by(2));
+
+        let tables = Database::new(&buf).tables().unwrap();
+        let table = tables.by_name("Objects").unwrap().unwrap();
+        let filter = table.build_pk_filter();
+        assert!((0..1000).step_by(2).all(|id| filter.may_contain(id)));
+        let false_positives = (1..1000).step_by(2).filter(|id| filter.may_contain(*id));
+        assert!(false_positives.count() < 25);
+    }
+
+    #[test]
+    fn test_table_map() {
+        let names = ["Icons", "Objects", "ZoneTable"];
+        let buf = names
+            .iter()
+            .fold(SampleDatabase::new(), |db, name| {
+                db.table(name, &[("id", ValueType::Integer)])
+            })
+            .build();
+
+        let map = Database::new(&buf).table_map().unwrap();
+        std::thread::scope(|s| {
+            for name in &["Icons", "Objects", "ZoneTable"] {
+                let map = &map;
+                s.spawn(move || {
+                    let table = map.by_name(name).unwrap().unwrap();
+                    assert_eq!(table.name(), *name);
+                });
+            }
+        });
+        assert!(map.by_name("Missions").is_none());
+    }
+
+    #[test]
+    fn test_share_between_threads() {
+        let buf = objects(4, 0..16);
+
+        let tables = Database::new(&buf).tables().unwrap();
+        let table = tables.get(0).unwrap().unwrap();
+        let sums: Vec<i32> = std::thread::scope(|s| {
+            let handles: Vec<_> = table
+                .bucket_iter()
+                .map(|bucket| {
+                    s.spawn(move || {
+                        let rows = bucket.row_iter();
+                        rows.filter_map(|r| r.field_at(0)?.into_opt_integer()).sum()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(sums.iter().sum::<i32>(), (0..16).sum::<i32>());
+        assert_eq!(sums[1], 1 + 5 + 9 + 13);
+    }
+
+    #[test]
+    fn test_rows_pk_order() {
+        let columns = [("id", ValueType::Integer), ("n", ValueType::Integer)];
+        let db = SampleDatabase::new().table("Objects", &columns).buckets(3);
+        let rows = [(4, 0), (1, 1), (3, 2), (1, 3), (2, 4)];
+        let buf = rows
+            .iter()
+            .fold(db, |db, &(id, n)| {
+                db.row(vec![core::Field::Integer(id), core::Field::Integer(n)])
+            })
+            .build();
+
+        let tables = Database::new(&buf).tables().unwrap();
+        let table = tables.by_name("Objects").unwrap().unwrap();
+        let field = |row: &Row, i| row.field_at(i).unwrap().into_opt_integer().unwrap();
+        let file: Vec<_> = table
+            .rows(IterOrder::File)
+            .iter()
+            .map(|r| field(r, 0))
+            .collect();
+        assert_eq!(file, vec![3, 4, 1, 1, 2]);
+        let sorted: Vec<_> = table
+            .rows(IterOrder::PrimaryKey)
+            .iter()
+            .map(|r| (field(r, 0), field(r, 1)))
+            .collect();
+        assert_eq!(sorted, vec![(1, 1), (1, 3), (2, 4), (3, 2), (4, 0)]);
+
+        let owned = core::Table::from(
+            core::TableDef {
+                columns: vec![("id", ValueType::Integer).into()],
+                name: String::from("Objects"),
+            },
+            core::TableData {
+                buckets: table
+                    .bucket_iter()
+                    .map(|b| {
+                        core::Bucket(
+                            b.row_iter()
+                                .map(|r| core::Row::from(vec![r.field_at(0).unwrap().into()]))
+                                .collect(),
+                        )
+                    })
+                    .collect(),
+            },
+        );
+        let owned: Vec<_> = owned
+            .rows(IterOrder::PrimaryKey)
+            .iter()
+            .map(|r| r.fields()[0].clone())
+            .collect();
+        let expected: Vec<_> = [1, 1, 2, 3, 4]
+            .iter()
+            .map(|i| core::Field::Integer(*i))
+            .collect();
+        assert_eq!(owned, expected);
+    }
+}
@@ -0,0 +1,559 @@
+//! # Strict and lenient opening of a database
+//!
+//! The accessors of [`Database`] assume that the file is well-formed, and
+//! panic when a row, field or string points outside of the buffer. That is
+//! fine for the official `CDClient`, but not for files of unknown origin.
+//!
+//! [`OpenOptions::open`] checks every structure of the file once, and returns
+//! a [`CheckedDatabase`] whose accessors never panic:
+//!
+//! - In *strict* mode (the default), the first [`Anomaly`] with a severity of
+//!   [`Severity::Error`] is returned as an error, so a server can refuse to
+//!   start with a damaged file.
+//! - In *lenient* mode, tables whose definition can't be read and rows whose
+//!   header can't be read are skipped, and fields with an unknown type or an
+//!   invalid address are read as [`Field::Nothing`][super::Field], which is
+//!   useful for forensic tools.
+//!
+//! In both modes, every other anomaly is recorded in the [`Warnings`] of
+//! [`CheckedDatabase::warnings`]. Besides the errors, these are tables that
+//! are not sorted by name (so [`Tables::by_name`] fails), which is a
+//! [`Severity::Warning`], and buckets with more than
+//! [`OpenOptions::max_chain_len`] rows, which is a [`Severity::Info`].
+//!
+//! An unreadable file header or table list is an error in both modes.
+//!
+//! ```
+//! use assembly_data::fdb::mem::open::OpenOptions;
+//!
+//! let file: &[u8] = &[1, 0, 0, 0, 8, 0, 0, 0, 0xFF, 0, 0, 0, 0xFF, 0, 0, 0];
+//! assert!(OpenOptions::new().open(file).is_err());
+//!
+//! let db = OpenOptions::new().strict(false).open(file).unwrap();
+//! assert!(db.tables().is_empty());
+//! assert_eq!(db.warnings().len(), 1);
+//! ```
+
+use std::{borrow::Cow, collections::HashSet, convert::TryFrom};
+
+use assembly_core::{
+    buffer::{try_cast, try_cast_slice, Repr, LEI64},
+    displaydoc::Display,
+};
+use memchr::memchr;
+use thiserror::Error;
+
+use super::{
+    c::{
+        FDBBucketHeaderC, FDBFieldDataC, FDBRowHeaderC, FDBRowHeaderListEntryC, FDBTableDefHeaderC,
+    },
+    get_field_raw, map_table_header, Column, Database, Field, Table, Tables,
+};
+use crate::fdb::{
+    common::{Latin1Str, ValueType},
+    warnings::{Severity, Warnings},
+};
+
+/// A structural problem in a database file
+#[derive(Debug, Clone, PartialEq, Eq, Error, Display)]
+#[non_exhaustive]
+pub enum Anomaly {
+    /// The file header or table list is out of bounds
+    Header,
+    /// Table #{index} has an invalid definition or data header
+    Table {
+        /// The index of the table in the table list
+        index: usize,
+    },
+    /// Column #{column} of table {table} has an invalid name or type
+    Column {
+        /// The name of the table
+        table: String,
+        /// The index of the column
+        column: usize,
+    },
+    /// The row list of table {table} is invalid at {addr:#x}
+    RowList {
+        /// The name of the table
+        table: String,
+        /// The address of the invalid or repeated list entry
+        addr: u32,
+    },
+    /// The row at {addr:#x} of table {table} is invalid
+    Row {
+        /// The name of the table
+        table: String,
+        /// The address of the row header
+        addr: u32,
+    },
+    /// Field #{index} of the row at {addr:#x} of table {table} has the unknown type {value_type}
+    UnknownType {
+        /// The name of the table
+        table: String,
+        /// The address of the row header
+        addr: u32,
+        /// The index of the field
+        index: usize,
+        /// The unknown type
+        value_type: u32,
+    },
+    /// Field #{index} of the row at {addr:#x} of table {table} is invalid
+    Field {
+        /// The name of the table
+        table: String,
+        /// The address of the row header
+        addr: u32,
+        /// The index of the field
+        index: usize,
+    },
+    /// Table {table} is not sorted after table {previous}
+    UnsortedTables {
+        /// The name of the previous table
+        previous: String,
+        /// The name of the table
+        table: String,
+    },
+    /// Bucket #{bucket} of table {table} has {len} rows
+    LongChain {
+        /// The name of the table
+        table: String,
+        /// The index of the bucket
+        bucket: usize,
+        /// The number of rows in the bucket
+        len: usize,
+    },
+}
+
+impl Anomaly {
+    /// Returns how serious the anomaly is
+    pub fn severity(&self) -> Severity {
+        match self {
+            Anomaly::UnsortedTables { .. } => Severity::Warning,
+            Anomaly::LongChain { .. } => Severity::Info,
+            _ => Severity::Error,
+        }
+    }
+}
+
+/// Options for opening a database, see the [module documentation](self)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct OpenOptions {
+    /// Whether to return the first error as an error
+    pub strict: bool,
+    /// The number of rows in a bucket above which it is reported
+    pub max_chain_len: usize,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self {
+            strict: true,
+            max_chain_len: 64,
+        }
+    }
+}
+
+impl OpenOptions {
+    /// Create the default (strict) options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether to return the first error as an error
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set the number of rows in a bucket above which it is reported
+    pub fn max_chain_len(mut self, max_chain_len: usize) -> Self {
+        self.max_chain_len = max_chain_len;
+        self
+    }
+
+    /// Check the structure of `buf` and open it as a database
+    pub fn open<'a>(&self, buf: &'a [u8]) -> Result<CheckedDatabase<'a>, Anomaly> {
+        let db = Database::new(buf);
+        let tables = db.tables().map_err(|_| Anomaly::Header)?;
+        let mut checker = Checker {
+            options: *self,
+            warnings: Warnings::new(),
+        };
+        let mut checked: Vec<CheckedTable<'a>> = Vec::with_capacity(tables.len());
+        for index in 0..tables.len() {
+            if let Some(table) = checker.table(tables, index)? {
+                if let Some(previous) = checked.last() {
+                    if previous.table.name_raw() > table.table.name_raw() {
+                        checker.report(Anomaly::UnsortedTables {
+                            previous: previous.name().into_owned(),
+                            table: table.name().into_owned(),
+                        })?;
+                    }
+                }
+                checked.push(table);
+            }
+        }
+        Ok(CheckedDatabase {
+            db,
+            strict: self.strict,
+            tables: checked,
+            warnings: checker.warnings,
+        })
+    }
+}
+
+/// Get the null-terminated string at `addr`
+fn try_str(buf: &[u8], addr: u32) -> Option<&Latin1Str> {
+    let haystack = buf.get(addr as usize..)?;
+    let end = memchr(0, haystack)?;
+    Some(Latin1Str::new(&haystack[..end]))
+}
+
+fn try_field<'a>(data: &FDBFieldDataC, buf: &'a [u8]) -> Option<Field<'a>> {
+    let data_type = ValueType::try_from(data.data_type.extract()).ok()?;
+    let bytes = data.value.0;
+    let addr = u32::from_le_bytes(bytes);
+    Some(match data_type {
+        ValueType::Text => Field::Text(try_str(buf, addr)?),
+        ValueType::VarChar => Field::VarChar(try_str(buf, addr)?),
+        ValueType::BigInt => Field::BigInt(try_cast::<LEI64>(buf, addr).ok()?.extract()),
+        _ => get_field_raw(data_type, bytes, buf),
+    })
+}
+
+fn try_row(buf: &[u8], addr: u32) -> Option<&[FDBFieldDataC]> {
+    let header = try_cast::<FDBRowHeaderC>(buf, addr).ok()?.extract();
+    try_cast_slice(buf, header.fields.base_offset, header.fields.count).ok()
+}
+
+struct Checker {
+    options: OpenOptions,
+    warnings: Warnings<Anomaly>,
+}
+
+impl Checker {
+    fn report(&mut self, anomaly: Anomaly) -> Result<(), Anomaly> {
+        let severity = anomaly.severity();
+        if self.options.strict && severity == Severity::Error {
+            Err(anomaly)
+        } else {
+            self.warnings.push(severity, anomaly);
+            Ok(())
+        }
+    }
+
+    fn table<'a>(
+        &mut self,
+        tables: Tables<'a>,
+        index: usize,
+    ) -> Result<Option<CheckedTable<'a>>, Anomaly> {
+        let header = tables.inner.get(index).unwrap();
+        let buf = header.buf().as_bytes();
+        let table_header = header.into_raw().extract();
+        let name_ok = try_cast::<FDBTableDefHeaderC>(buf, table_header.table_def_header_addr)
+            .map(|def| try_str(buf, def.extract().table_name_addr).is_some());
+        let table = match name_ok {
+            Ok(true) => map_table_header(header).ok(),
+            _ => None,
+        };
+        let table = match table {
+            Some(table) => table,
+            None => {
+                self.report(Anomaly::Table { index })?;
+                return Ok(None);
+            }
+        };
+
+        let name = table.name().into_owned();
+        let mut columns = Vec::with_capacity(table.column_count());
+        for (column, raw) in table.inner.raw.columns.iter().enumerate() {
+            let header = raw.extract();
+            let name_raw = try_str(buf, header.column_name_addr);
+            let domain = ValueType::try_from(header.column_data_type).ok();
+            if let (Some(name), Some(domain)) = (name_raw, domain) {
+                columns.push(Column { name, domain });
+            } else {
+                let table = name.clone();
+                self.report(Anomaly::Column { table, column })?;
+                return Ok(None);
+            }
+        }
+
+        let mut cuts = HashSet::new();
+        for (bucket, header) in table.inner.raw.buckets.iter().enumerate() {
+            let mut visited = HashSet::new();
+            let mut prev = None;
+            let mut next = header.row_header_list_head_addr.extract();
+            while next != u32::MAX {
+                let addr = next;
+                let entry = match try_cast::<FDBRowHeaderListEntryC>(buf, addr) {
+                    Ok(entry) if visited.insert(addr) => entry.extract(),
+                    Ok(_) => {
+                        // A cycle, stop after the previous entry
+                        cuts.extend(prev);
+                        let table = name.clone();
+                        self.report(Anomaly::RowList { table, addr })?;
+                        break;
+                    }
+                    Err(_) => {
+                        let table = name.clone();
+                        self.report(Anomaly::RowList { table, addr })?;
+                        break;
+                    }
+                };
+                prev = Some(addr);
+                next = entry.row_header_list_next_addr;
+                let addr = entry.row_header_addr;
+                let fields = match try_row(buf, addr) {
+                    Some(fields) => fields,
+                    None => {
+                        let table = name.clone();
+                        self.report(Anomaly::Row { table, addr })?;
+                        continue;
+                    }
+                };
+                for (index, data) in fields.iter().enumerate() {
+                    let value_type = data.data_type.extract();
+                    if ValueType::try_from(value_type).is_err() {
+                        let table = name.clone();
+                        self.report(Anomaly::UnknownType {
+                            table,
+                            addr,
+                            index,
+                            value_type,
+                        })?;
+                    } else if try_field(data, buf).is_none() {
+                        let table = name.clone();
+                        self.report(Anomaly::Field { table, addr, index })?;
+                    }
+                }
+            }
+            let len = visited.len();
+            if len > self.options.max_chain_len {
+                let table = name.clone();
+                self.report(Anomaly::LongChain { table, bucket, len })?;
+            }
+        }
+
+        Ok(Some(CheckedTable {
+            table,
+            columns,
+            cuts,
+        }))
+    }
+}
+
+/// A database that was checked by [`OpenOptions::open`]
+pub struct CheckedDatabase<'a> {
+    db: Database<'a>,
+    strict: bool,
+    tables: Vec<CheckedTable<'a>>,
+    warnings: Warnings<Anomaly>,
+}
+
+impl<'a> CheckedDatabase<'a> {
+    /// Returns the unchecked database
+    ///
+    /// After a strict open, its accessors don't panic either.
+    pub fn database(&self) -> Database<'a> {
+        self.db
+    }
+
+    /// Returns whether the database was opened in strict mode
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Returns the anomalies that were found, see the [module documentation](self)
+    pub fn warnings(&self) -> &Warnings<Anomaly> {
+        &self.warnings
+    }
+
+    /// Returns all readable tables, in file order
+    pub fn tables(&self) -> &[CheckedTable<'a>] {
+        &self.tables
+    }
+
+    /// Get a readable table by its name
+    pub fn table(&self, name: &str) -> Option<&CheckedTable<'a>> {
+        let name = name.as_bytes();
+        self.tables
+            .iter()
+            .find(|t| t.table.name_raw().as_bytes() == name)
+    }
+}
+
+/// A table of a [`CheckedDatabase`]
+pub struct CheckedTable<'a> {
+    table: Table<'a>,
+    columns: Vec<Column<'a>>,
+    /// Row list entries after which a bucket must end, because of a cycle
+    cuts: HashSet<u32>,
+}
+
+impl<'a> CheckedTable<'a> {
+    /// Returns the unchecked table
+    pub fn table(&self) -> Table<'a> {
+        self.table
+    }
+
+    /// Get the name of the table
+    pub fn name(&self) -> Cow<'a, str> {
+        self.table.inner.raw.name.decode()
+    }
+
+    /// Returns the columns of the table
+    pub fn columns(&self) -> &[Column<'a>] {
+        &self.columns
+    }
+
+    /// Get an iterator over all readable rows, in file order
+    pub fn row_iter(&self) -> CheckedRowIter<'a, '_> {
+        CheckedRowIter {
+            buf: self.table.inner.mem.as_bytes(),
+            buckets: self.table.inner.raw.buckets.iter(),
+            next: u32::MAX,
+            cuts: &self.cuts,
+        }
+    }
+}
+
+/// Struct that implements [`CheckedTable::row_iter`]
+pub struct CheckedRowIter<'a, 't> {
+    buf: &'a [u8],
+    buckets: std::slice::Iter<'a, FDBBucketHeaderC>,
+    next: u32,
+    cuts: &'t HashSet<u32>,
+}
+
+impl<'a, 't> Iterator for CheckedRowIter<'a, 't> {
+    type Item = CheckedRow<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            while self.next == u32::MAX {
+                self.next = self.buckets.next()?.row_header_list_head_addr.extract();
+            }
+            let addr = self.next;
+            let entry = match try_cast::<FDBRowHeaderListEntryC>(self.buf, addr) {
+                Ok(entry) => entry.extract(),
+                Err(_) => {
+                    self.next = u32::MAX;
+                    continue;
+                }
+            };
+            self.next = if self.cuts.contains(&addr) {
+                u32::MAX
+            } else {
+                entry.row_header_list_next_addr
+            };
+            if let Some(fields) = try_row(self.buf, entry.row_header_addr) {
+                let buf = self.buf;
+                return Some(CheckedRow { buf, fields });
+            }
+        }
+    }
+}
+
+/// A row of a [`CheckedTable`]
+///
+/// Fields that can't be read are returned as [`Field::Nothing`].
+#[derive(Copy, Clone)]
+pub struct CheckedRow<'a> {
+    buf: &'a [u8],
+    fields: &'a [FDBFieldDataC],
+}
+
+impl<'a> CheckedRow<'a> {
+    /// Get the field at the index
+    pub fn field_at(&self, index: usize) -> Option<Field<'a>> {
+        let field = self.fields.get(index)?;
+        Some(try_field(field, self.buf).unwrap_or(Field::Nothing))
+    }
+
+    /// Get an iterator over all fields
+    pub fn field_iter(&self) -> impl Iterator<Item = Field<'a>> {
+        let buf = self.buf;
+        self.fields
+            .iter()
+            .map(move |data| try_field(data, buf).unwrap_or(Field::Nothing))
+    }
+
+    /// Get the count of fields
+    pub fn field_count(&self) -> usize {
+        self.fields.len()
+    }
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{core, testing::SampleDatabase};
+
+    #[test]
+    fn test_strict_and_lenient() {
+        let columns = [("id", ValueType::Integer), ("name", ValueType::Text)];
+        let db = SampleDatabase::new().table("Objects", &columns).buckets(1);
+        let mut buf = (1..=3)
+            .fold(db, |db, id| {
+                let name = core::Field::Text(format!("Object {}", id));
+                db.row(vec![core::Field::Integer(id), name])
+            })
+            .build();
+
+        let db = OpenOptions::new().open(&buf).unwrap();
+        assert!(db.warnings().is_empty());
+        let table = db.table("Objects").unwrap();
+        assert_eq!(table.row_iter().count(), 3);
+
+        let db = OpenOptions::new().max_chain_len(2).open(&buf).unwrap();
+        let warning = db.warnings().iter().next().unwrap();
+        assert_eq!(warning.severity, Severity::Info);
+        assert_eq!(
+            warning.to_string(),
+            "info: Bucket #0 of table Objects has 3 rows"
+        );
+
+        // Point the name of the second row past the end of the file
+        let fields = {
+            let table = Database::new(&buf)
+                .tables()
+                .unwrap()
+                .get(0)
+                .unwrap()
+                .unwrap();
+            let row = table.row_iter().nth(1).unwrap();
+            row.fields.as_ptr() as usize - buf.as_ptr() as usize
+        };
+        let text = fields + 12;
+        buf[text..text + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let error = OpenOptions::new().open(&buf).err().unwrap();
+        assert!(matches!(error, Anomaly::Field { index: 1, .. }));
+
+        let db = OpenOptions::new().strict(false).open(&buf).unwrap();
+        let warnings: Vec<_> = db.warnings().iter().map(|w| &w.kind).collect();
+        assert_eq!(warnings, vec![&error]);
+        assert_eq!(db.warnings().max_severity(), Some(Severity::Error));
+        let names: Vec<_> = db.tables()[0]
+            .row_iter()
+            .map(|row| row.field_at(1).unwrap())
+            .collect();
+        assert_eq!(names[1], Field::Nothing);
+        assert_ne!(names[0], Field::Nothing);
+    }
+
+    #[test]
+    fn test_lookup_without_buckets() {
+        let buf = SampleDatabase::new()
+            .table("Empty", &[("id", ValueType::Integer)])
+            .buckets(0)
+            .build();
+
+        let db = OpenOptions::new().open(&buf).unwrap();
+        let table = db.table("Empty").unwrap().table();
+        assert_eq!(table.bucket_count(), 0);
+        assert_eq!(table.index_iter(1).count(), 0);
+        assert!(!table.contains_pk(1));
+    }
+}
@@ -0,0 +1,243 @@
+//! # Typed projection of rows
+//!
+//! Extracting a few fields from a row usually means matching on every
+//! [`Field`] and handling the cases where the type does not match. With
+//! [`Row::project`], the wanted types are written down once as a tuple:
+//!
+//! ```
+//! # use assembly_data::fdb::{common::{Latin1Str, Latin1String, ValueType}, core, mem, store};
+//! # let mut table = store::Table::new(1);
+//! # table.push_column(Latin1String::encode("id"), ValueType::Integer);
+//! # table.push_column(Latin1String::encode("name"), ValueType::Text);
+//! # table.push_column(Latin1String::encode("scale"), ValueType::Float);
+//! # table.push_row(0, &[
+//! #     core::Field::Integer(7),
+//! #     core::Field::Text(String::from("Brick")),
+//! #     core::Field::Nothing,
+//! # ]);
+//! # let mut db = store::Database::new();
+//! # db.push_table(Latin1String::encode("Objects"), table);
+//! # let mut buf = Vec::new();
+//! # db.write(&mut buf).unwrap();
+//! let tables = mem::Database::new(&buf).tables().unwrap();
+//! let table = tables.by_name("Objects").unwrap().unwrap();
+//! let row = table.row_iter().next().unwrap();
+//!
+//! let (id, name, scale) = row.project::<(i32, &Latin1Str, Option<f32>)>(&[0, 1, 2]).unwrap();
+//! assert_eq!((id, name.decode().as_ref(), scale), (7, "Brick", None));
+//! assert!(row.project::<(i32, i32)>(&[0, 1]).is_err());
+//! ```
+//!
+//! The same element types also implement [`TryFrom<Field>`], so a single field
+//! can be extracted with `i32::try_from(field)?`.
+
+use std::convert::TryFrom;
+
+use assembly_core::displaydoc::Display;
+use thiserror::Error;
+
+use super::{Field, Row};
+use crate::fdb::common::{Latin1Str, Value, ValueType};
+
+/// Errors when projecting a row
+#[derive(Error, Debug, Display, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProjectError {
+    /// Expected {expected} column indices, got {found}
+    Arity {
+        /// The number of elements in the tuple
+        expected: usize,
+        /// The number of indices
+        found: usize,
+    },
+    /// The row has no field at index {0}
+    MissingField(usize),
+    /// Expected {expected:?} at index {index}, found {found:?}
+    TypeMismatch {
+        /// The index of the field
+        index: usize,
+        /// The type that was requested
+        expected: ValueType,
+        /// The type of the field
+        found: ValueType,
+    },
+}
+
+/// A type that can be extracted from a single [`Field`]
+pub trait FromField<'a>: Sized {
+    /// The type of field that this is extracted from
+    const VALUE_TYPE: ValueType;
+
+    /// Extract the value, returning `None` if the field has the wrong type
+    fn from_field(field: Field<'a>) -> Option<Self>;
+}
+
+impl<'a> FromField<'a> for i32 {
+    const VALUE_TYPE: ValueType = ValueType::Integer;
+
+    fn from_field(field: Field<'a>) -> Option<Self> {
+        field.into_opt_integer()
+    }
+}
+
+impl<'a> FromField<'a> for f32 {
+    const VALUE_TYPE: ValueType = ValueType::Float;
+
+    fn from_field(field: Field<'a>) -> Option<Self> {
+        field.into_opt_float()
+    }
+}
+
+impl<'a> FromField<'a> for bool {
+    const VALUE_TYPE: ValueType = ValueType::Boolean;
+
+    fn from_field(field: Field<'a>) -> Option<Self> {
+        field.into_opt_boolean()
+    }
+}
+
+impl<'a> FromField<'a> for i64 {
+    const VALUE_TYPE: ValueType = ValueType::BigInt;
+
+    fn from_field(field: Field<'a>) -> Option<Self> {
+        field.into_opt_big_int()
+    }
+}
+
+/// Accepts both `TEXT` and `VARCHAR` fields
+impl<'a> FromField<'a> for &'a Latin1Str {
+    const VALUE_TYPE: ValueType = ValueType::Text;
+
+    fn from_field(field: Field<'a>) -> Option<Self> {
+        match field {
+            Value::Text(text) | Value::VarChar(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+/// Accepts both `TEXT` and `VARCHAR` fields and decodes them
+impl<'a> FromField<'a> for String {
+    const VALUE_TYPE: ValueType = ValueType::Text;
+
+    fn from_field(field: Field<'a>) -> Option<Self> {
+        <&Latin1Str>::from_field(field).map(|text| text.decode().into_owned())
+    }
+}
+
+/// Maps `NULL` to `None`
+impl<'a, T: FromField<'a>> FromField<'a> for Option<T> {
+    const VALUE_TYPE: ValueType = T::VALUE_TYPE;
+
+    fn from_field(field: Field<'a>) -> Option<Self> {
+        match field {
+            Value::Nothing => Some(None),
+            field => T::from_field(field).map(Some),
+        }
+    }
+}
+
+#[derive(Error, Debug, Display, Copy, Clone, PartialEq, Eq)]
+/// Expected {expected:?}, found {found:?}
+///
+/// This is the error when converting a [`Field`] with [`TryFrom`].
+pub struct FieldTypeError {
+    /// The type that was requested
+    pub expected: ValueType,
+    /// The type of the field
+    pub found: ValueType,
+}
+
+macro_rules! impl_try_from {
+    ($($t:ty),+) => {
+        $(
+            impl<'a> TryFrom<Field<'a>> for $t {
+                type Error = FieldTypeError;
+
+                fn try_from(field: Field<'a>) -> Result<Self, Self::Error> {
+                    let found = field.value_type();
+                    <$t>::from_field(field).ok_or(FieldTypeError {
+                        expected: <$t>::VALUE_TYPE,
+                        found,
+                    })
+                }
+            }
+        )+
+    };
+}
+
+impl_try_from!(i32, i64, f32, bool, String, &'a Latin1Str);
+
+/// A tuple of [`FromField`] types, see [`Row::project`]
+pub trait Projection<'a>: Sized {
+    /// The number of elements of the tuple
+    const ARITY: usize;
+
+    /// Extract the fields at `indices` from `row`
+    fn project(row: &Row<'a>, indices: &[usize]) -> Result<Self, ProjectError>;
+}
+
+fn extract<'a, T: FromField<'a>>(row: &Row<'a>, index: usize) -> Result<T, ProjectError> {
+    let field = row
+        .field_at(index)
+        .ok_or(ProjectError::MissingField(index))?;
+    let found = field.value_type();
+    T::from_field(field).ok_or(ProjectError::TypeMismatch {
+        index,
+        expected: T::VALUE_TYPE,
+        found,
+    })
+}
+
+macro_rules! impl_projection {
+    ($arity:literal; $($t:ident $i:tt),+) => {
+        impl<'a, $($t: FromField<'a>),+> Projection<'a> for ($($t,)+) {
+            const ARITY: usize = $arity;
+
+            fn project(row: &Row<'a>, indices: &[usize]) -> Result<Self, ProjectError> {
+                if indices.len() != Self::ARITY {
+                    return Err(ProjectError::Arity {
+                        expected: Self::ARITY,
+                        found: indices.len(),
+                    });
+                }
+                Ok(($(extract::<$t>(row, indices[$i])?,)+))
+            }
+        }
+    };
+}
+
+impl_projection!(1; A 0);
+impl_projection!(2; A 0, B 1);
+impl_projection!(3; A 0, B 1, C 2);
+impl_projection!(4; A 0, B 1, C 2, D 3);
+impl_projection!(5; A 0, B 1, C 2, D 3, E 4);
+impl_projection!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+impl_projection!(7; A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_projection!(8; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+impl<'a> Row<'a> {
+    /// Extract the fields at `indices` into a tuple
+    ///
+    /// Fails if the number of indices doesn't match the tuple, or if any of
+    /// the fields is missing or has the wrong type. See [`FromField`] for the
+    /// supported element types.
+    pub fn project<P: Projection<'a>>(&self, indices: &[usize]) -> Result<P, ProjectError> {
+        P::project(self, indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_from_field() {
+        assert_eq!(i32::try_from(Field::Integer(3)), Ok(3));
+        assert_eq!(Option::<i64>::from_field(Field::Nothing), Some(None));
+        let text = Latin1Str::new(b"Caf\xe9");
+        assert_eq!(String::try_from(Field::VarChar(text)).unwrap(), "Café");
+        let err = bool::try_from(Field::Float(1.0)).unwrap_err();
+        assert_eq!(err.to_string(), "Expected Boolean, found Float");
+    }
+}
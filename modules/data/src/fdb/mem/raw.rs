@@ -0,0 +1,325 @@
+//! # Raw access to the structures of a database file
+//!
+//! The other APIs in [`super`] hide the layout of the file behind tables, rows
+//! and fields. Tools that re-serialize a file or investigate a damaged one need
+//! to know where each structure is and what it contains, including addresses
+//! that are never followed by the higher level APIs.
+//!
+//! The functions in this module read the structs from [`crate::fdb::file`] at an
+//! address and return them together with that address. [`snapshot`] walks the
+//! whole file in the same order as the writer in [`crate::fdb::store`] and
+//! returns every structure it finds.
+//!
+//! All functions return a [`CastError`] instead of panicking when an address
+//! is out of bounds, and linked lists are checked for cycles.
+
+use std::{collections::HashSet, convert::TryFrom};
+
+use assembly_core::buffer::{try_cast, try_cast_slice, CastError, MinimallyAligned, Repr, LEI64};
+use memchr::memchr;
+
+use super::c::{
+    FDBBucketHeaderC, FDBColumnHeaderC, FDBFieldDataC, FDBHeaderC, FDBRowHeaderC,
+    FDBRowHeaderListEntryC, FDBTableDataHeaderC, FDBTableDefHeaderC, FDBTableHeaderC,
+};
+use crate::fdb::{
+    common::{Latin1Str, ValueType},
+    file::{
+        ArrayHeader, FDBBucketHeader, FDBColumnHeader, FDBFieldData, FDBHeader, FDBRowHeader,
+        FDBRowHeaderListEntry, FDBTableDataHeader, FDBTableDefHeader, FDBTableHeader,
+    },
+};
+
+/// A value that was read from an address in the file
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct At<T> {
+    /// The address of the value
+    pub addr: u32,
+    /// The value
+    pub value: T,
+}
+
+/// Read the file header
+pub fn header(buf: &[u8]) -> Result<At<FDBHeader>, CastError> {
+    let value = try_cast::<FDBHeaderC>(buf, 0)?.extract();
+    Ok(At { addr: 0, value })
+}
+
+fn array<'a, C, T>(
+    buf: &'a [u8],
+    array: ArrayHeader,
+) -> Result<impl Iterator<Item = At<T>> + 'a, CastError>
+where
+    C: MinimallyAligned + Repr<Value = T> + 'a,
+{
+    let slice = try_cast_slice::<C>(buf, array.base_offset, array.count)?;
+    let size = std::mem::size_of::<C>() as u32;
+    Ok(slice.iter().enumerate().map(move |(i, c)| At {
+        addr: array.base_offset + i as u32 * size,
+        value: c.extract(),
+    }))
+}
+
+/// Read the list of table headers
+pub fn table_headers(
+    buf: &[u8],
+    header: FDBHeader,
+) -> Result<impl Iterator<Item = At<FDBTableHeader>> + '_, CastError> {
+    array::<FDBTableHeaderC, _>(buf, header.tables)
+}
+
+/// Read the definition header of a table
+pub fn table_def_header(
+    buf: &[u8],
+    table: FDBTableHeader,
+) -> Result<At<FDBTableDefHeader>, CastError> {
+    let addr = table.table_def_header_addr;
+    let value = try_cast::<FDBTableDefHeaderC>(buf, addr)?.extract();
+    Ok(At { addr, value })
+}
+
+/// Read the data header of a table
+pub fn table_data_header(
+    buf: &[u8],
+    table: FDBTableHeader,
+) -> Result<At<FDBTableDataHeader>, CastError> {
+    let addr = table.table_data_header_addr;
+    let value = try_cast::<FDBTableDataHeaderC>(buf, addr)?.extract();
+    Ok(At { addr, value })
+}
+
+/// Read the column headers of a table definition
+pub fn column_headers(
+    buf: &[u8],
+    def: FDBTableDefHeader,
+) -> Result<impl Iterator<Item = At<FDBColumnHeader>> + '_, CastError> {
+    let list = ArrayHeader {
+        base_offset: def.column_header_list_addr,
+        count: def.column_count,
+    };
+    array::<FDBColumnHeaderC, _>(buf, list)
+}
+
+/// Read the bucket headers of a table
+pub fn bucket_headers(
+    buf: &[u8],
+    data: FDBTableDataHeader,
+) -> Result<impl Iterator<Item = At<FDBBucketHeader>> + '_, CastError> {
+    array::<FDBBucketHeaderC, _>(buf, data.buckets)
+}
+
+/// Read a single entry of the linked list of rows
+pub fn row_list_entry(buf: &[u8], addr: u32) -> Result<At<FDBRowHeaderListEntry>, CastError> {
+    let value = try_cast::<FDBRowHeaderListEntryC>(buf, addr)?.extract();
+    Ok(At { addr, value })
+}
+
+/// Read the linked list of row entries, starting at `addr`
+///
+/// The list ends at the address `0xFFFFFFFF`. Returns [`CastError::OutOfBounds`]
+/// with the address of the entry if it is out of bounds or appears twice.
+pub fn row_list(buf: &[u8], mut addr: u32) -> Result<Vec<At<FDBRowHeaderListEntry>>, CastError> {
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    while addr != u32::MAX {
+        if !seen.insert(addr) {
+            return Err(CastError::OutOfBounds { offset: addr });
+        }
+        let entry = row_list_entry(buf, addr)?;
+        addr = entry.value.row_header_list_next_addr;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Read a row header
+pub fn row_header(buf: &[u8], addr: u32) -> Result<At<FDBRowHeader>, CastError> {
+    let value = try_cast::<FDBRowHeaderC>(buf, addr)?.extract();
+    Ok(At { addr, value })
+}
+
+/// Read the fields of a row
+///
+/// Unlike the other APIs, this doesn't check the data types of the fields.
+pub fn fields(buf: &[u8], row: FDBRowHeader) -> Result<Vec<At<FDBFieldData>>, CastError> {
+    let slice = try_cast_slice::<FDBFieldDataC>(buf, row.fields.base_offset, row.fields.count)?;
+    let size = std::mem::size_of::<FDBFieldDataC>() as u32;
+    let fields = slice.iter().enumerate().map(|(i, c)| At {
+        addr: row.fields.base_offset + i as u32 * size,
+        value: FDBFieldData {
+            data_type: c.data_type.extract(),
+            value: c.value.0,
+        },
+    });
+    Ok(fields.collect())
+}
+
+/// Read a null-terminated string
+pub fn string(buf: &[u8], addr: u32) -> Result<At<&Latin1Str>, CastError> {
+    let haystack = buf
+        .get(addr as usize..)
+        .ok_or(CastError::OutOfBounds { offset: addr })?;
+    let end = memchr(0, haystack).ok_or(CastError::OutOfBounds { offset: addr })?;
+    // SAFETY: the bytes don't contain a null byte
+    let value = unsafe { Latin1Str::from_bytes_unchecked(&haystack[..end]) };
+    Ok(At { addr, value })
+}
+
+/// Read a 64-bit integer
+pub fn i64(buf: &[u8], addr: u32) -> Result<At<i64>, CastError> {
+    let value = try_cast::<LEI64>(buf, addr)?.extract();
+    Ok(At { addr, value })
+}
+
+/// A structure of the file
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RawStruct {
+    /// The file header
+    Header(FDBHeader),
+    /// An entry in the list of tables
+    TableHeader(FDBTableHeader),
+    /// The definition of a table
+    TableDefHeader(FDBTableDefHeader),
+    /// A column of a table definition
+    ColumnHeader(FDBColumnHeader),
+    /// The data header of a table
+    TableDataHeader(FDBTableDataHeader),
+    /// A bucket of a table
+    BucketHeader(FDBBucketHeader),
+    /// An entry in the linked list of rows of a bucket
+    RowHeaderListEntry(FDBRowHeaderListEntry),
+    /// A row header
+    RowHeader(FDBRowHeader),
+    /// A field of a row
+    FieldData(FDBFieldData),
+    /// A null-terminated string with the given length (excluding the terminator)
+    String(u32),
+    /// A 64-bit integer
+    I64(i64),
+}
+
+impl RawStruct {
+    /// The number of bytes of the structure, excluding padding
+    pub fn size(&self) -> u32 {
+        use std::mem::size_of;
+        (match self {
+            Self::Header(_) => size_of::<FDBHeader>(),
+            Self::TableHeader(_) => size_of::<FDBTableHeader>(),
+            Self::TableDefHeader(_) => size_of::<FDBTableDefHeader>(),
+            Self::ColumnHeader(_) => size_of::<FDBColumnHeader>(),
+            Self::TableDataHeader(_) => size_of::<FDBTableDataHeader>(),
+            Self::BucketHeader(_) => size_of::<FDBBucketHeader>(),
+            Self::RowHeaderListEntry(_) => size_of::<FDBRowHeaderListEntry>(),
+            Self::RowHeader(_) => size_of::<FDBRowHeader>(),
+            Self::FieldData(_) => size_of::<FDBFieldData>(),
+            Self::String(len) => *len as usize + 1,
+            Self::I64(_) => size_of::<i64>(),
+        }) as u32
+    }
+}
+
+/// Walk the whole file and return every structure that is referenced
+///
+/// Strings and 64-bit integers are returned every time they are referenced,
+/// so the same address may appear more than once.
+pub fn snapshot(buf: &[u8]) -> Result<Vec<At<RawStruct>>, CastError> {
+    fn push<T>(out: &mut Vec<At<RawStruct>>, at: At<T>, f: fn(T) -> RawStruct) {
+        out.push(At {
+            addr: at.addr,
+            value: f(at.value),
+        })
+    }
+
+    let mut out = Vec::new();
+    let file_header = header(buf)?;
+    push(&mut out, file_header, RawStruct::Header);
+
+    let tables: Vec<_> = table_headers(buf, file_header.value)?.collect();
+    for table in &tables {
+        push(&mut out, *table, RawStruct::TableHeader);
+    }
+
+    for table in tables {
+        let def = table_def_header(buf, table.value)?;
+        push(&mut out, def, RawStruct::TableDefHeader);
+        let name = string(buf, def.value.table_name_addr)?;
+        push(&mut out, name, |s| RawStruct::String(s.len() as u32));
+        let columns: Vec<_> = column_headers(buf, def.value)?.collect();
+        for column in &columns {
+            push(&mut out, *column, RawStruct::ColumnHeader);
+        }
+        for column in &columns {
+            let name = string(buf, column.value.column_name_addr)?;
+            push(&mut out, name, |s| RawStruct::String(s.len() as u32));
+        }
+
+        let data = table_data_header(buf, table.value)?;
+        push(&mut out, data, RawStruct::TableDataHeader);
+        let buckets: Vec<_> = bucket_headers(buf, data.value)?.collect();
+        for bucket in &buckets {
+            push(&mut out, *bucket, RawStruct::BucketHeader);
+        }
+        for bucket in buckets {
+            for entry in row_list(buf, bucket.value.row_header_list_head_addr)? {
+                push(&mut out, entry, RawStruct::RowHeaderListEntry);
+                let row = row_header(buf, entry.value.row_header_addr)?;
+                push(&mut out, row, RawStruct::RowHeader);
+                let fields = fields(buf, row.value)?;
+                for field in &fields {
+                    push(&mut out, *field, RawStruct::FieldData);
+                }
+                for field in fields {
+                    let addr = u32::from_le_bytes(field.value.value);
+                    match ValueType::try_from(field.value.data_type) {
+                        Ok(ValueType::Text) | Ok(ValueType::VarChar) => {
+                            let text = string(buf, addr)?;
+                            push(&mut out, text, |s| RawStruct::String(s.len() as u32));
+                        }
+                        Ok(ValueType::BigInt) => {
+                            push(&mut out, i64(buf, addr)?, RawStruct::I64);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{core::Field, testing::SampleDatabase};
+
+    #[test]
+    fn test_snapshot() {
+        let columns = [("id", ValueType::Integer), ("name", ValueType::Text)];
+        let buf = SampleDatabase::new()
+            .table("T", &columns)
+            .row(vec![Field::Integer(1), Field::Text(String::from("A"))])
+            .build();
+
+        let items = snapshot(&buf).unwrap();
+        assert_eq!(items[0].addr, 0);
+        assert_eq!(items[1].addr, 8);
+        assert!(matches!(items[1].value, RawStruct::TableHeader(_)));
+        let kinds = |f: fn(&RawStruct) -> bool| items.iter().filter(|i| f(&i.value)).count();
+        assert_eq!(kinds(|s| matches!(s, RawStruct::ColumnHeader(_))), 2);
+        assert_eq!(kinds(|s| matches!(s, RawStruct::FieldData(_))), 2);
+        assert_eq!(kinds(|s| matches!(s, RawStruct::String(_))), 4);
+        for item in &items {
+            assert!((item.addr + item.value.size()) as usize <= buf.len());
+        }
+
+        let mut cyclic = buf.clone();
+        let entry = items
+            .iter()
+            .find(|i| matches!(i.value, RawStruct::RowHeaderListEntry(_)))
+            .unwrap()
+            .addr as usize;
+        cyclic[entry + 4..entry + 8].copy_from_slice(&(entry as u32).to_le_bytes());
+        assert!(snapshot(&cyclic).is_err());
+    }
+}
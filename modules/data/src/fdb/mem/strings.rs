@@ -0,0 +1,101 @@
+//! # An index of string terminators
+//!
+//! Every text field in the file points to the start of a null-terminated
+//! string, so reading a field means searching for the terminator. A
+//! [`StringIndex`] finds all null bytes of the file in a single pass up front
+//! and stores them as a bitmap (one bit per byte of the file), so that the
+//! end of any string can be looked up without scanning its bytes again.
+//!
+//! This only pays off when the whole file is exported, so the index is only
+//! used by rows that were passed one with [`Row::with_strings`][super::Row::with_strings],
+//! e.g. by [`export_to_sink`][crate::fdb::sink::export_to_sink] if
+//! [`RowSink::index_strings`][crate::fdb::sink::RowSink::index_strings] is set.
+
+use memchr::memchr_iter;
+
+use crate::fdb::common::Latin1Str;
+
+/// The positions of all null bytes in a buffer
+#[derive(Debug, Clone)]
+pub struct StringIndex<'a> {
+    buf: &'a [u8],
+    nulls: Vec<u64>,
+}
+
+impl<'a> StringIndex<'a> {
+    /// Scan `buf` for null bytes
+    pub fn new(buf: &'a [u8]) -> Self {
+        let mut nulls = vec![0u64; buf.len().div_ceil(64)];
+        for pos in memchr_iter(0, buf) {
+            nulls[pos / 64] |= 1 << (pos % 64);
+        }
+        Self { buf, nulls }
+    }
+
+    /// Returns the position of the first null byte at or after `start`
+    fn next_null(&self, start: usize) -> Option<usize> {
+        let mut word = start / 64;
+        let mut bits = *self.nulls.get(word)? & (!0u64 << (start % 64));
+        while bits == 0 {
+            word += 1;
+            bits = *self.nulls.get(word)?;
+        }
+        Some(word * 64 + bits.trailing_zeros() as usize)
+    }
+
+    /// Get the string that starts at `addr`
+    ///
+    /// Like [`Buffer::string`][crate::fdb::ro::buffer::Buffer::string], a
+    /// string without a terminator extends to the end of the buffer. Returns
+    /// `None` if `addr` is out of bounds.
+    pub fn get(&self, addr: u32) -> Option<&'a Latin1Str> {
+        let start = addr as usize;
+        if start > self.buf.len() {
+            return None;
+        }
+        let end = self.next_null(start).unwrap_or(self.buf.len());
+        Some(Latin1Str::new(&self.buf[start..end]))
+    }
+
+    /// Returns the buffer this index was built for
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_index() {
+        let mut buf = vec![b'a'; 130];
+        buf[3] = 0;
+        buf[70] = 0;
+        let index = StringIndex::new(&buf);
+        assert_eq!(index.get(0).unwrap().as_bytes(), b"aaa");
+        assert_eq!(index.get(3).unwrap().as_bytes(), b"");
+        assert_eq!(index.get(4).unwrap().as_bytes().len(), 66);
+        assert_eq!(index.get(71).unwrap().as_bytes().len(), 59);
+        assert_eq!(index.get(130).unwrap().as_bytes(), b"");
+        assert!(index.get(131).is_none());
+    }
+
+    #[cfg(feature = "fdb-core")]
+    #[test]
+    fn test_with_strings() {
+        use crate::fdb::{mem, testing};
+
+        let buf = testing::objects(8);
+        let index = StringIndex::new(&buf);
+        let copy = buf.clone();
+        let other = StringIndex::new(&copy);
+        let tables = mem::Database::new(&buf).tables().unwrap();
+        let table = tables.get(0).unwrap().unwrap();
+        for row in table.row_iter() {
+            assert!(row.with_strings(&index).field_iter().eq(row.field_iter()));
+            assert!(row.with_strings(&other).field_iter().eq(row.field_iter()));
+            assert_eq!(row.with_strings(&index).field_at(1), row.field_at(1));
+        }
+    }
+}
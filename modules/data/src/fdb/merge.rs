@@ -0,0 +1,539 @@
+//! # Three-way merges of edited databases
+//!
+//! When two people edit copies of the same database, [`merge`] combines their
+//! changes with the help of the common ancestor (`base`): a row that only one
+//! side changed takes the new version, and a row that both sides changed is
+//! merged field by field. Changes that can't be combined are reported as a
+//! [`Conflict`] for a whole table, a row or a single field, and the merged
+//! schema keeps our version in that place.
+//!
+//! Rows are matched by their key, which is the first column unless
+//! [`MergeOptions::with_key`] sets other columns for a table. Rows with the
+//! same key are matched in the order they are stored. Two sides that add
+//! different rows with the same key conflict, so mods that both add objects
+//! should be passed through an [`IdRemapper`][super::remap::IdRemapper] first.
+//!
+//! The changes between two versions are listed by [`diff`], with the same
+//! matching of rows, e.g. to render a [`Changelog`][super::changelog::Changelog].
+//!
+//! ```
+//! use assembly_data::fdb::{
+//!     csv::CsvDialect,
+//!     merge::{merge, Conflict, MergeOptions},
+//!     source::{import_from_source, CsvSource},
+//! };
+//!
+//! let load = |text: &str| {
+//!     let source = CsvSource::new(CsvDialect::default()).with_table("Objects", text);
+//!     import_from_source(source, &()).unwrap()
+//! };
+//! let base = load("id,name,value\n1,brick,10\n2,plate,20\n");
+//! let ours = load("id,name,value\n1,brick,15\n2,plate,20\n");
+//! let theirs = load("id,name,value\n1,Brick,10\n2,plate,25\n3,rocket,30\n");
+//!
+//! let merged = merge(&base, &ours, &theirs, &MergeOptions::new()).unwrap();
+//! assert!(merged.conflicts.is_empty());
+//! assert_eq!(merged.schema.table("Objects").unwrap().into_iter().count(), 3);
+//!
+//! let theirs = load("id,name,value\n1,brick,12\n2,plate,20\n");
+//! let merged = merge(&base, &ours, &theirs, &MergeOptions::new()).unwrap();
+//! assert!(matches!(&merged.conflicts[..], [Conflict::Field { column, .. }] if column == "value"));
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use assembly_core::displaydoc::Display;
+use thiserror::Error;
+
+use super::core::{pk_hash, Bucket, Field, Row, Schema, Table, TableData, TableDef};
+
+/// Errors when merging
+#[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MergeError {
+    /// Table {0:?} has no key column {1:?}
+    NoSuchColumn(String, String),
+    /// Cannot compute the bucket for the primary key {1} in table {0:?}
+    UnsupportedKey(String, Field),
+}
+
+/// Settings for [`merge`]
+#[derive(Debug, Clone, Default)]
+pub struct MergeOptions {
+    keys: HashMap<String, Vec<String>>,
+}
+
+impl MergeOptions {
+    /// Create the default options, which match rows by their first column
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match the rows of `table` by the given columns
+    pub fn with_key(mut self, table: &str, columns: &[&str]) -> Self {
+        let columns = columns.iter().map(|&c| c.to_owned()).collect();
+        self.keys.insert(table.to_owned(), columns);
+        self
+    }
+
+    fn key_columns(&self, table: &Table) -> Result<Vec<usize>, MergeError> {
+        let names = match self.keys.get(table.name()) {
+            Some(names) => names,
+            None => return Ok(vec![0]),
+        };
+        let columns = table.columns();
+        let index = |name: &String| {
+            let index = columns.iter().position(|c| *c.name == **name);
+            index.ok_or_else(|| MergeError::NoSuchColumn(table.name().to_owned(), name.clone()))
+        };
+        names.iter().map(index).collect()
+    }
+}
+
+/// The key of a row: the key fields, and the position among the rows with those
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RowKey {
+    /// The values of the key columns
+    pub fields: Vec<Field>,
+    /// The number of earlier rows with the same fields
+    pub index: usize,
+}
+
+/// Formats the key as e.g. `(1, "brick")`, followed by `#1` for the second row with that key
+impl fmt::Display for RowKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for (i, field) in self.fields.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", field)?;
+        }
+        write!(f, ")")?;
+        if self.index > 0 {
+            write!(f, "#{}", self.index)?;
+        }
+        Ok(())
+    }
+}
+
+/// Why a whole table couldn't be merged
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum TableConflict {
+    /// deleted by us and changed by them
+    DeletedByUs,
+    /// changed by us and deleted by them
+    DeletedByThem,
+    /// the columns were changed
+    Columns,
+}
+
+/// A change that couldn't be merged, see the [module documentation](self)
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Conflict {
+    /// Table {table:?}: {reason}
+    Table {
+        /// The name of the table
+        table: String,
+        /// What happened to the table
+        reason: TableConflict,
+    },
+    /// Row {key} of table {table:?} was changed or added differently on both sides
+    Row {
+        /// The name of the table
+        table: String,
+        /// The key of the row
+        key: RowKey,
+        /// Our version of the row, if we didn't delete it
+        ours: Option<Row>,
+        /// Their version of the row, if they didn't delete it
+        theirs: Option<Row>,
+    },
+    /// Field {column:?} of row {key} of table {table:?} is {ours} for us and {theirs} for them
+    Field {
+        /// The name of the table
+        table: String,
+        /// The key of the row
+        key: RowKey,
+        /// The name of the column
+        column: String,
+        /// The original value
+        base: Field,
+        /// Our value
+        ours: Field,
+        /// Their value
+        theirs: Field,
+    },
+}
+
+/// The result of [`merge`]
+#[derive(Debug)]
+pub struct Merge {
+    /// The merged tables, with our version where there are conflicts
+    pub schema: Schema,
+    /// The changes that couldn't be merged
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Merge the changes from `base` to `ours` and from `base` to `theirs`
+///
+/// See the [module documentation](self) for how rows are matched.
+pub fn merge(
+    base: &Schema,
+    ours: &Schema,
+    theirs: &Schema,
+    options: &MergeOptions,
+) -> Result<Merge, MergeError> {
+    let mut schema = Schema::new();
+    let mut conflicts = Vec::new();
+    let names = ours.iter().chain(theirs.iter()).chain(base.iter());
+    let mut seen = HashSet::new();
+    for name in names.map(Table::name) {
+        if !seen.insert(name) {
+            continue;
+        }
+        let (b, o, t) = (base.table(name), ours.table(name), theirs.table(name));
+        let mut conflict = |reason| {
+            let table = name.to_owned();
+            conflicts.push(Conflict::Table { table, reason });
+        };
+        let merged = match (b, o, t) {
+            (_, Some(o), Some(t)) if same_table(o, t) => Some(clone_table(o)),
+            (Some(b), Some(o), Some(t)) if same_table(b, o) => Some(clone_table(t)),
+            (Some(b), Some(o), Some(t)) if same_table(b, t) => Some(clone_table(o)),
+            (b, Some(o), Some(t)) => {
+                let same_columns = b.is_none_or(|b| same_columns(b, o)) && same_columns(o, t);
+                if same_columns {
+                    Some(merge_rows(b, o, t, options, &mut conflicts)?)
+                } else {
+                    conflict(TableConflict::Columns);
+                    Some(clone_table(o))
+                }
+            }
+            (Some(b), Some(o), None) => {
+                if !same_table(b, o) {
+                    conflict(TableConflict::DeletedByThem);
+                    Some(clone_table(o))
+                } else {
+                    None
+                }
+            }
+            (Some(b), None, Some(t)) => {
+                if !same_table(b, t) {
+                    conflict(TableConflict::DeletedByUs);
+                }
+                None
+            }
+            (None, Some(table), None) | (None, None, Some(table)) => Some(clone_table(table)),
+            (_, None, None) => None,
+        };
+        if let Some(table) = merged {
+            schema.insert_table(table);
+        }
+    }
+    Ok(Merge { schema, conflicts })
+}
+
+/// A difference between two versions of a database, see [`diff`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Change {
+    /// A table was added
+    TableAdded {
+        /// The name of the table
+        table: String,
+        /// The number of rows of the table
+        rows: usize,
+    },
+    /// A table was removed
+    TableRemoved {
+        /// The name of the table
+        table: String,
+        /// The number of rows of the table
+        rows: usize,
+    },
+    /// The columns of a table were changed, so the rows were not compared
+    Columns {
+        /// The name of the table
+        table: String,
+    },
+    /// A row was added
+    RowAdded {
+        /// The name of the table
+        table: String,
+        /// The key of the row
+        key: RowKey,
+        /// The new row
+        row: Row,
+    },
+    /// A row was removed
+    RowRemoved {
+        /// The name of the table
+        table: String,
+        /// The key of the row
+        key: RowKey,
+        /// The old row
+        row: Row,
+    },
+    /// Some fields of a row were changed
+    RowChanged {
+        /// The name of the table
+        table: String,
+        /// The key of the row
+        key: RowKey,
+        /// The new row
+        row: Row,
+        /// The name, old value and new value of every changed field
+        fields: Vec<(String, Field, Field)>,
+    },
+}
+
+/// List the changes from `old` to `new`
+///
+/// Tables are compared in the order of their names, and rows in the order of
+/// `new`, followed by the removed rows. See the [module documentation](self)
+/// for how rows are matched.
+pub fn diff(old: &Schema, new: &Schema, options: &MergeOptions) -> Result<Vec<Change>, MergeError> {
+    let mut changes = Vec::new();
+    let count = |t: &Table| t.into_iter().count();
+    for table in new.iter() {
+        let name = table.name().to_owned();
+        let old = match old.table(table.name()) {
+            Some(old) => old,
+            None => {
+                let rows = count(table);
+                changes.push(Change::TableAdded { table: name, rows });
+                continue;
+            }
+        };
+        if !same_columns(old, table) {
+            changes.push(Change::Columns { table: name });
+            continue;
+        }
+        let columns = options.key_columns(table)?;
+        let (old_keys, before) = keyed_rows(old, &columns);
+        let (new_keys, after) = keyed_rows(table, &columns);
+        for key in new_keys {
+            let row = after[&key];
+            let change = match before.get(&key) {
+                Some(&prev) if prev == row => continue,
+                Some(&prev) => {
+                    let fields = prev.fields().iter().zip(row.fields());
+                    let fields = fields.zip(table.columns()).filter(|((a, b), _)| a != b);
+                    let fields =
+                        fields.map(|((a, b), c)| (c.name.to_string(), a.clone(), b.clone()));
+                    Change::RowChanged {
+                        table: name.clone(),
+                        key,
+                        row: row.clone(),
+                        fields: fields.collect(),
+                    }
+                }
+                None => Change::RowAdded {
+                    table: name.clone(),
+                    key,
+                    row: row.clone(),
+                },
+            };
+            changes.push(change);
+        }
+        for key in old_keys.into_iter().filter(|k| !after.contains_key(k)) {
+            let row = before[&key].clone();
+            let table = name.clone();
+            changes.push(Change::RowRemoved { table, key, row });
+        }
+    }
+    for table in old.iter().filter(|t| new.table(t.name()).is_none()) {
+        let (name, rows) = (table.name().to_owned(), count(table));
+        changes.push(Change::TableRemoved { table: name, rows });
+    }
+    Ok(changes)
+}
+
+fn same_columns(a: &Table, b: &Table) -> bool {
+    let columns = |t: &Table| {
+        let columns = t.columns().iter();
+        columns
+            .map(|c| (c.name.clone(), c.field_type))
+            .collect::<Vec<_>>()
+    };
+    columns(a) == columns(b)
+}
+
+/// Whether the tables have the same columns and rows, in any order
+fn same_table(a: &Table, b: &Table) -> bool {
+    let mut counts = HashMap::new();
+    for row in a {
+        *counts.entry(row).or_insert(0) += 1;
+    }
+    for row in b {
+        match counts.get_mut(row) {
+            Some(0) | None => return false,
+            Some(count) => *count -= 1,
+        }
+    }
+    same_columns(a, b) && counts.values().all(|&count| count == 0)
+}
+
+fn clone_table(table: &Table) -> Table {
+    let def = TableDef {
+        columns: table.columns().to_vec(),
+        name: table.name().to_owned(),
+    };
+    let buckets = table.buckets().iter();
+    let buckets = buckets.map(|b| Bucket(b.rows_ref().clone())).collect();
+    Table::from(def, TableData { buckets })
+}
+
+/// The rows of a table by key, and the keys in the order of the rows
+fn keyed_rows<'t>(table: &'t Table, columns: &[usize]) -> (Vec<RowKey>, HashMap<RowKey, &'t Row>) {
+    let mut counts = HashMap::new();
+    let mut keys = Vec::new();
+    let mut rows = HashMap::new();
+    for row in table {
+        let fields: Vec<_> = columns.iter().map(|&i| row.fields()[i].clone()).collect();
+        let count = counts.entry(fields.clone()).or_insert(0);
+        let key = RowKey {
+            fields,
+            index: *count,
+        };
+        *count += 1;
+        keys.push(key.clone());
+        rows.insert(key, row);
+    }
+    (keys, rows)
+}
+
+fn merge_rows(
+    base: Option<&Table>,
+    ours: &Table,
+    theirs: &Table,
+    options: &MergeOptions,
+    conflicts: &mut Vec<Conflict>,
+) -> Result<Table, MergeError> {
+    let name = ours.name();
+    let columns = options.key_columns(ours)?;
+    let (_, b) = base.map(|b| keyed_rows(b, &columns)).unwrap_or_default();
+    let (our_keys, o) = keyed_rows(ours, &columns);
+    let (their_keys, t) = keyed_rows(theirs, &columns);
+
+    let mut merged = Vec::new();
+    let their_keys = their_keys.into_iter().filter(|k| !o.contains_key(k));
+    for key in our_keys.into_iter().chain(their_keys) {
+        let (b, o, t) = (b.get(&key), o.get(&key), t.get(&key));
+        let row = match (b, o, t) {
+            (_, o, t) if o == t => o,
+            (b, o, t) if b == o => t,
+            (b, o, t) if b == t => o,
+            (Some(b), Some(o), Some(t)) => {
+                let fields = b.fields().iter().zip(o.fields()).zip(t.fields());
+                let fields = fields.zip(ours.columns()).map(|(((b, o), t), column)| {
+                    if o == t || b == t {
+                        o.clone()
+                    } else if b == o {
+                        t.clone()
+                    } else {
+                        conflicts.push(Conflict::Field {
+                            table: name.to_owned(),
+                            key: key.clone(),
+                            column: column.name.to_string(),
+                            base: b.clone(),
+                            ours: o.clone(),
+                            theirs: t.clone(),
+                        });
+                        o.clone()
+                    }
+                });
+                merged.push(Row::from(fields.collect::<Vec<_>>()));
+                continue;
+            }
+            (_, o, t) => {
+                conflicts.push(Conflict::Row {
+                    table: name.to_owned(),
+                    key: key.clone(),
+                    ours: o.map(|&r| r.clone()),
+                    theirs: t.map(|&r| r.clone()),
+                });
+                o
+            }
+        };
+        merged.extend(row.map(|&r| r.clone()));
+    }
+
+    let def = TableDef {
+        columns: ours.columns().to_vec(),
+        name: name.to_owned(),
+    };
+    let count = ours.buckets().len().max(1);
+    let mut buckets: Vec<_> = std::iter::repeat_with(Bucket::new).take(count).collect();
+    for row in merged {
+        let key = row.fields().first().cloned().unwrap_or(Field::Nothing);
+        let hash = match pk_hash(&key) {
+            Some(hash) => hash,
+            None => return Err(MergeError::UnsupportedKey(name.to_owned(), key)),
+        };
+        buckets[hash as usize % count].rows_mut().push(row);
+    }
+    Ok(Table::from(def, TableData { buckets }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fdb::{
+        csv::CsvDialect,
+        source::{import_from_source, CsvSource},
+    };
+
+    fn load(tables: &[(&str, &str)]) -> Schema {
+        let mut source = CsvSource::new(CsvDialect::default());
+        for (name, text) in tables {
+            source = source.with_table(name, text);
+        }
+        import_from_source(source, &()).unwrap()
+    }
+
+    #[test]
+    fn test_merge_conflicts() {
+        let base = load(&[
+            ("Objects", "id,name\n1,brick\n2,plate\n3,slope\n"),
+            ("Icons", "id,path\n1,a.dds\n"),
+            ("Skills", "id,cost\n1,5\n"),
+        ]);
+        let ours = load(&[
+            ("Objects", "id,name\n1,Brick\n2,plate\n3,Slope\n4,wall\n"),
+            ("Icons", "id,path\n1,b.dds\n"),
+        ]);
+        let theirs = load(&[
+            ("Objects", "id,name\n1,brick\n3,SLOPE\n4,door\n"),
+            ("Skills", "id,cost\n1,5\n"),
+        ]);
+        let merged = merge(&base, &ours, &theirs, &MergeOptions::new()).unwrap();
+
+        let objects = merged.schema.table("Objects").unwrap();
+        let names: HashSet<_> = objects.into_iter().map(|r| r.fields()[1].clone()).collect();
+        let expected = ["Brick", "Slope", "wall"].iter();
+        assert_eq!(
+            names,
+            expected
+                .map(|&n| Field::Text(n.into()))
+                .collect::<HashSet<_>>()
+        );
+        assert!(merged.schema.table("Skills").is_none());
+
+        let mut conflicts: Vec<_> = merged.conflicts.iter().map(ToString::to_string).collect();
+        conflicts.sort();
+        assert_eq!(
+            conflicts,
+            [
+                "Field \"name\" of row (3) of table \"Objects\" is \"Slope\" for us and \"SLOPE\" for them",
+                "Row (4) of table \"Objects\" was changed or added differently on both sides",
+                "Table \"Icons\": changed by us and deleted by them",
+            ]
+        );
+    }
+}
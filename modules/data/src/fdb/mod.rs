@@ -26,6 +26,11 @@
 //!
 //! ## Using this library
 //!
+//! The modules are split into two features, which are both enabled by default:
+//!
+//! - `fdb-mem`: the zero-copy [`mem`] and [`ro`] APIs, which don't need `nom`
+//! - `fdb-core`: the owned [`core`] model with the [`io`] loader and the [`store`] writer
+//!
 //! You can use the `mem` module to load a database from an in-memory buffer:
 //!
 //! ```
@@ -40,16 +45,76 @@
 
 #![warn(missing_docs)]
 
+#[cfg(feature = "fdb-mem")]
+pub mod analysis;
+pub mod base64;
+#[cfg(feature = "fdb-mem")]
+pub mod cache;
+#[cfg(feature = "fdb-mem")]
+pub mod chains;
+#[cfg(feature = "fdb-core")]
+pub mod changelog;
 pub mod common;
+pub mod csv;
+#[cfg(feature = "fdb-core")]
 pub mod core;
+#[cfg(feature = "fdb-mem")]
+pub mod discover;
+#[cfg(feature = "fdb-mem")]
+pub mod doc;
+#[cfg(feature = "fdb-mem")]
+pub mod encoding;
+#[cfg(all(feature = "fdb-mem", feature = "serde-derives"))]
+pub mod export;
 pub mod file;
+pub mod float;
+#[cfg(feature = "fdb-mem")]
+pub mod fmt;
+#[cfg(feature = "fdb-core")]
+pub mod hooks;
+#[cfg(all(feature = "fdb-core", feature = "fdb-mem"))]
+pub mod hybrid;
+#[cfg(any(feature = "fdb-mem", feature = "fdb-core"))]
+pub mod infer;
+#[cfg(feature = "fdb-core")]
 pub mod io;
+#[cfg(feature = "serde-derives")]
+pub mod json;
+#[cfg(feature = "fdb-mem")]
+pub mod layout;
+#[cfg(feature = "fdb-mem")]
 pub mod mem;
+#[cfg(feature = "fdb-core")]
+pub mod merge;
+pub mod names;
+#[cfg(feature = "fdb-core")]
 pub mod parser;
+#[cfg(feature = "pipeline")]
+pub mod pipeline;
+#[cfg(feature = "fdb-core")]
 pub mod query;
+#[cfg(feature = "fdb-core")]
 pub mod reader;
+#[cfg(all(feature = "fdb-core", feature = "fdb-mem"))]
+pub mod recover;
+pub mod relations;
+#[cfg(feature = "fdb-core")]
+pub mod remap;
+#[cfg(feature = "fdb-mem")]
 pub mod ro;
+#[cfg(feature = "fdb-mem")]
+pub mod sink;
+#[cfg(feature = "fdb-snapshot")]
+pub mod snapshot;
+#[cfg(feature = "fdb-core")]
+pub mod source;
+#[cfg(feature = "fdb-core")]
 pub mod store;
+#[cfg(feature = "fdb-mem")]
+pub mod stream;
+#[cfg(all(feature = "fdb-core", any(test, feature = "testing")))]
+pub mod testing;
+pub mod warnings;
 
 #[cfg(feature = "sqlite")]
 pub mod sqlite;
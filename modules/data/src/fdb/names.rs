@@ -0,0 +1,104 @@
+//! # Symbolic names for integer values
+//!
+//! Many integer columns don't store quantities, but IDs from a fixed set,
+//! such as the type of a component or an item. A [`ValueNames`] maps these
+//! values to names for each `(table, column)` pair, so that exporters and
+//! pretty-printers can show `11 (Item)` instead of just `11`.
+//!
+//! A default set for the core database of the game is available in
+//! `game::names` with the `game` feature.
+
+use std::{collections::BTreeMap, fmt};
+
+use super::common::{Context, Value};
+
+/// A registry of names for the values in integer columns
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValueNames {
+    columns: BTreeMap<(String, String), BTreeMap<i32, String>>,
+}
+
+impl ValueNames {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a name for `value` in the column of a table
+    pub fn register(&mut self, table: &str, column: &str, value: i32, name: &str) {
+        self.columns
+            .entry((table.to_string(), column.to_string()))
+            .or_default()
+            .insert(value, name.to_string());
+    }
+
+    /// Register a list of names for the column of a table
+    pub fn with_names(mut self, table: &str, column: &str, names: &[(i32, &str)]) -> Self {
+        for (value, name) in names {
+            self.register(table, column, *value, name);
+        }
+        self
+    }
+
+    /// Add all names from `other`, replacing existing ones
+    pub fn extend(&mut self, other: ValueNames) {
+        for (key, names) in other.columns {
+            self.columns.entry(key).or_default().extend(names);
+        }
+    }
+
+    /// Check whether there are names for the column of a table
+    pub fn has_column(&self, table: &str, column: &str) -> bool {
+        self.columns
+            .contains_key(&(table.to_string(), column.to_string()))
+    }
+
+    /// Check whether there are no names at all
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Get the name of a value
+    pub fn get(&self, table: &str, column: &str, value: i32) -> Option<&str> {
+        self.columns
+            .get(&(table.to_string(), column.to_string()))?
+            .get(&value)
+            .map(String::as_str)
+    }
+
+    /// Get the name for the value of a field, if it is an [`Value::Integer`]
+    pub fn name_of<T: Context>(&self, table: &str, column: &str, field: &Value<T>) -> Option<&str> {
+        self.get(table, column, field.as_integer()?)
+    }
+
+    /// Display a field, followed by its name in parentheses if it has one
+    pub fn display<'a, T: Context>(
+        &'a self,
+        table: &str,
+        column: &str,
+        field: &'a Value<T>,
+    ) -> NamedValue<'a, T> {
+        NamedValue {
+            field,
+            name: self.name_of(table, column, field),
+        }
+    }
+}
+
+/// A field with an optional name, see [`ValueNames::display`]
+pub struct NamedValue<'a, T: Context> {
+    field: &'a Value<T>,
+    name: Option<&'a str>,
+}
+
+impl<T: Context> fmt::Display for NamedValue<'_, T>
+where
+    Value<T>: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "{} ({})", self.field, name),
+            None => self.field.fmt(f),
+        }
+    }
+}
@@ -0,0 +1,591 @@
+//! # Declarative conversion pipelines
+//!
+//! A [`Pipeline`] describes a conversion in a TOML file: which files to read,
+//! which tables to keep, how to change them and which files to write. Running
+//! it with [`run_pipeline`] repeats the same conversion every time, e.g. as
+//! part of the build script of a mod.
+//!
+//! ```toml
+//! # Read the core database, then replace `Objects` with an edited copy
+//! [[input]]
+//! path = "res/cdclient.fdb"
+//!
+//! [[input]]
+//! path = "mod/Objects.csv"
+//! dialect = "excel"
+//!
+//! # Keep only some tables (a trailing `*` matches any suffix)
+//! [filter]
+//! include = ["Objects", "Component*"]
+//! exclude = ["ComponentsRegistry"]
+//!
+//! [[transform]]
+//! kind = "rename_column"
+//! table = "Objects"
+//! from = "displayName"
+//! to = "display_name"
+//!
+//! [[output]]
+//! path = "build/cdclient.fdb"
+//!
+//! [[output]]
+//! path = "build/cdclient.sql"
+//! format = "postgres"
+//! ```
+//!
+//! The steps run in this order:
+//!
+//! 1. All inputs are loaded, in order. A table replaces a table of the same
+//!    name from an earlier input.
+//! 2. The tables are filtered: with a list of `include` patterns, only
+//!    matching tables are kept, and all tables that match an `exclude` pattern
+//!    are removed.
+//! 3. The transforms are applied, in order, see [`Transform`].
+//! 4. All outputs are written, replacing existing files.
+//!
+//! The format of a file is picked from its extension (`fdb`, `sqlite`, `db`,
+//! `csv`, `tsv`, `jsonl` or `ndjson`) unless `format` is set, see
+//! [`FileFormat`]. A CSV input is read as a single table, named after the file
+//! unless `table` is set. Relative paths are relative to the directory of the
+//! pipeline file.
+//!
+//! Closures that rewrite single columns can be added in code, for the inputs
+//! with [`Pipeline::with_hook`] and for the CSV and JSON lines outputs with
+//! [`Pipeline::with_output_hook`].
+//!
+//! This module is only available with the `pipeline` feature.
+
+use std::{
+    error::Error as StdError,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use assembly_core::displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::{
+    core::{Field, RenameError, Schema, Table},
+    csv::CsvDialect,
+    hooks::ColumnHooks,
+    io::{write_sql_dump, SqlDialect},
+    mem,
+    sink::export_to_sink,
+    source::{import_from_source, CsvSource, JsonLinesSource},
+    store,
+    stream::{StreamFormat, StreamOptions, StreamSink},
+};
+
+/// Errors when loading or running a [`Pipeline`]
+#[derive(Debug, Error, Display)]
+#[non_exhaustive]
+pub enum PipelineError {
+    /// Invalid pipeline: {0}
+    Toml(#[from] toml::de::Error),
+    /// Failed to access {0:?}: {1}
+    Io(PathBuf, #[source] io::Error),
+    /// Cannot tell the format of {0:?}, please set `format`
+    UnknownFormat(PathBuf),
+    /// The format of {0:?} can't be used here
+    UnsupportedFormat(PathBuf),
+    /// Failed to load {0:?}: {1}
+    Load(PathBuf, #[source] Box<dyn StdError + Send + Sync>),
+    /// Failed to write {0:?}: {1}
+    Store(PathBuf, #[source] Box<dyn StdError + Send + Sync>),
+    /// There is no table {0:?}
+    NoSuchTable(String),
+    /// There is no column {1:?} in table {0:?}
+    NoSuchColumn(String, String),
+    /// Cannot drop the primary key {1:?} of table {0:?}
+    DropKey(String, String),
+    /// Failed to rename: {0}
+    Rename(#[from] RenameError),
+}
+
+/// The format of an input or output file
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    /// A database file (`.fdb`)
+    Fdb,
+    /// An SQLite database (`.sqlite`, `.db`), with the `sqlite` feature
+    Sqlite,
+    /// CSV (`.csv`, `.tsv`), one table per input file
+    Csv,
+    /// JSON lines (`.jsonl`, `.ndjson`)
+    Jsonl,
+    /// A Postgres dump, only as an output
+    Postgres,
+    /// A MySQL dump, only as an output
+    Mysql,
+}
+
+impl FileFormat {
+    /// Pick the format from the extension of `path`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "fdb" => Some(FileFormat::Fdb),
+            "sqlite" | "sqlite3" | "db" => Some(FileFormat::Sqlite),
+            "csv" | "tsv" => Some(FileFormat::Csv),
+            "jsonl" | "ndjson" => Some(FileFormat::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+/// One of the predefined [`CsvDialect`]s
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CsvPreset {
+    /// [`CsvDialect::default`]
+    Default,
+    /// [`CsvDialect::rfc4180`]
+    Rfc4180,
+    /// [`CsvDialect::excel`]
+    Excel,
+    /// [`CsvDialect::tsv`]
+    Tsv,
+}
+
+impl CsvPreset {
+    /// The dialect for this preset
+    pub fn dialect(self) -> CsvDialect {
+        match self {
+            CsvPreset::Default => CsvDialect::default(),
+            CsvPreset::Rfc4180 => CsvDialect::rfc4180(),
+            CsvPreset::Excel => CsvDialect::excel(),
+            CsvPreset::Tsv => CsvDialect::tsv(),
+        }
+    }
+}
+
+/// A file that is read by a [`Pipeline`]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Input {
+    /// The path of the file
+    pub path: PathBuf,
+    /// The format of the file, if not given by the extension
+    pub format: Option<FileFormat>,
+    /// The name of the table in a CSV file, instead of the file name
+    pub table: Option<String>,
+    /// The dialect of a CSV file, `tsv` for `.tsv` files and `default` otherwise
+    pub dialect: Option<CsvPreset>,
+}
+
+/// A file that is written by a [`Pipeline`]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Output {
+    /// The path of the file
+    pub path: PathBuf,
+    /// The format of the file, if not given by the extension
+    pub format: Option<FileFormat>,
+    /// The dialect of a CSV file, `tsv` for `.tsv` files and `default` otherwise
+    pub dialect: Option<CsvPreset>,
+}
+
+/// The tables that are kept by a [`Pipeline`]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Filter {
+    /// Keep only the tables that match one of these patterns, if not empty
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Remove the tables that match one of these patterns
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => pattern == name,
+    }
+}
+
+impl Filter {
+    /// Check whether the table `name` is kept
+    pub fn keeps(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| matches(p, name)))
+            && !self.exclude.iter().any(|p| matches(p, name))
+    }
+}
+
+/// A change to the tables of a [`Pipeline`], selected by `kind`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Transform {
+    /// Rename the table `from` to `to`
+    RenameTable {
+        /// The old name
+        from: String,
+        /// The new name
+        to: String,
+    },
+    /// Rename the column `from` of `table` to `to`
+    RenameColumn {
+        /// The name of the table
+        table: String,
+        /// The old name
+        from: String,
+        /// The new name
+        to: String,
+    },
+    /// Remove `column` and its fields from `table`
+    ///
+    /// The first column is the primary key, which can't be removed.
+    DropColumn {
+        /// The name of the table
+        table: String,
+        /// The name of the column
+        column: String,
+    },
+}
+
+fn table_mut<'s>(schema: &'s mut Schema, name: &str) -> Result<&'s mut Table, PipelineError> {
+    let table = schema.table_mut(name);
+    table.ok_or_else(|| PipelineError::NoSuchTable(name.to_owned()))
+}
+
+impl Transform {
+    /// Apply the transform to `schema`
+    pub fn apply(&self, schema: &mut Schema) -> Result<(), PipelineError> {
+        match self {
+            Transform::RenameTable { from, to } => schema.rename_table(from, to)?,
+            Transform::RenameColumn { table, from, to } => {
+                table_mut(schema, table)?.rename_column(from, to)?
+            }
+            Transform::DropColumn { table, column } => {
+                let t = table_mut(schema, table)?;
+                let index = t.columns().iter().position(|c| &*c.name == column);
+                match index {
+                    None => return Err(PipelineError::NoSuchColumn(table.clone(), column.clone())),
+                    Some(0) => return Err(PipelineError::DropKey(table.clone(), column.clone())),
+                    Some(index) => {
+                        t.columns_mut().remove(index);
+                        for bucket in t.buckets_mut() {
+                            for row in bucket.rows_mut() {
+                                if index < row.fields().len() {
+                                    row.fields_mut().remove(index);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What [`Pipeline::run`] did
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PipelineStats {
+    /// The number of tables that were written
+    pub tables: usize,
+    /// The number of rows that were written
+    pub rows: usize,
+    /// The number of files that were written
+    pub outputs: usize,
+}
+
+/// A conversion from input to output files, see the [module documentation](self)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pipeline {
+    /// The files to read
+    #[serde(default, rename = "input")]
+    pub inputs: Vec<Input>,
+    /// The tables to keep
+    #[serde(default)]
+    pub filter: Filter,
+    /// The changes to the tables
+    #[serde(default, rename = "transform")]
+    pub transforms: Vec<Transform>,
+    /// The files to write
+    #[serde(default, rename = "output")]
+    pub outputs: Vec<Output>,
+    /// The directory that relative paths start from
+    #[serde(skip)]
+    pub base: PathBuf,
+    /// The hooks that are applied to the inputs, see [`Pipeline::with_hook`]
+    #[serde(skip)]
+    pub hooks: ColumnHooks,
+    /// The hooks that are applied to the CSV and JSON lines outputs, see
+    /// [`Pipeline::with_output_hook`]
+    #[serde(skip)]
+    pub output_hooks: ColumnHooks,
+}
+
+fn format_of(path: &Path, format: Option<FileFormat>) -> Result<FileFormat, PipelineError> {
+    format
+        .or_else(|| FileFormat::from_path(path))
+        .ok_or_else(|| PipelineError::UnknownFormat(path.to_owned()))
+}
+
+fn dialect_of(path: &Path, preset: Option<CsvPreset>) -> CsvDialect {
+    let tsv = path.extension().is_some_and(|ext| ext == "tsv");
+    let default = if tsv {
+        CsvPreset::Tsv
+    } else {
+        CsvPreset::Default
+    };
+    preset.unwrap_or(default).dialect()
+}
+
+fn load_error<E: StdError + Send + Sync + 'static>(
+    path: &Path,
+) -> impl FnOnce(E) -> PipelineError + '_ {
+    move |e| PipelineError::Load(path.to_owned(), Box::new(e))
+}
+
+fn store_error<E: StdError + Send + Sync + 'static>(
+    path: &Path,
+) -> impl FnOnce(E) -> PipelineError + '_ {
+    move |e| PipelineError::Store(path.to_owned(), Box::new(e))
+}
+
+impl Pipeline {
+    /// Parse a pipeline, with relative paths starting from the working directory
+    pub fn from_toml(text: &str) -> Result<Self, PipelineError> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Read a pipeline file, with relative paths starting from its directory
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PipelineError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| PipelineError::Io(path.to_owned(), e))?;
+        let mut pipeline = Self::from_toml(&text)?;
+        pipeline.base = path.parent().map(Path::to_owned).unwrap_or_default();
+        Ok(pipeline)
+    }
+
+    /// Register a hook for `column` of `table`, see [`ColumnHooks`]
+    ///
+    /// The hooks are applied to every row while the inputs are read, so they
+    /// use the names of the tables and columns in the input files.
+    pub fn with_hook<F>(mut self, table: &str, column: &str, hook: F) -> Self
+    where
+        F: Fn(Field) -> Field + Send + Sync + 'static,
+    {
+        self.hooks.register(table, column, hook);
+        self
+    }
+
+    /// Register a hook for `column` of `table` in the CSV and JSON lines outputs
+    ///
+    /// The hooks are applied to every row while it is written, after the
+    /// transforms, so they use the names of the tables and columns in the
+    /// outputs. The other outputs are not changed.
+    pub fn with_output_hook<F>(mut self, table: &str, column: &str, hook: F) -> Self
+    where
+        F: Fn(Field) -> Field + Send + Sync + 'static,
+    {
+        self.output_hooks.register(table, column, hook);
+        self
+    }
+
+    fn load_input(&self, input: &Input) -> Result<Schema, PipelineError> {
+        let path = self.base.join(&input.path);
+        let io_error = |e| PipelineError::Io(path.clone(), e);
+        match format_of(&path, input.format)? {
+            FileFormat::Fdb => {
+                let file = File::open(&path).map_err(io_error)?;
+                let mut schema = Schema::from_source(file).map_err(load_error(&path))?;
+                for table in schema.iter_mut() {
+                    self.hooks.apply_table(table);
+                }
+                Ok(schema)
+            }
+            #[cfg(feature = "sqlite")]
+            FileFormat::Sqlite => {
+                use super::sqlite::{source::SqliteSource, Connection};
+
+                let conn = Connection::open(&path).map_err(load_error(&path))?;
+                let source = SqliteSource::all_tables(&conn).map_err(load_error(&path))?;
+                import_from_source(self.hooks.wrap(source), &()).map_err(load_error(&path))
+            }
+            FileFormat::Csv => {
+                let text = fs::read_to_string(&path).map_err(io_error)?;
+                let name = match &input.table {
+                    Some(name) => name.clone(),
+                    None => {
+                        let stem = path.file_stem().unwrap_or_default();
+                        stem.to_string_lossy().into_owned()
+                    }
+                };
+                let dialect = dialect_of(&path, input.dialect);
+                let source = CsvSource::new(dialect).with_table(&name, &text);
+                import_from_source(self.hooks.wrap(source), &()).map_err(load_error(&path))
+            }
+            FileFormat::Jsonl => {
+                let file = File::open(&path).map_err(io_error)?;
+                let source = JsonLinesSource::new(BufReader::new(file));
+                import_from_source(self.hooks.wrap(source), &()).map_err(load_error(&path))
+            }
+            _ => Err(PipelineError::UnsupportedFormat(path)),
+        }
+    }
+
+    fn write_output(
+        &self,
+        output: &Output,
+        schema: &Schema,
+        fdb: &[u8],
+    ) -> Result<(), PipelineError> {
+        let path = self.base.join(&output.path);
+        let io_error = |e| PipelineError::Io(path.clone(), e);
+        let create = || File::create(&path).map(BufWriter::new).map_err(io_error);
+        let db = mem::Database::new(fdb);
+        match format_of(&path, output.format)? {
+            FileFormat::Fdb => {
+                let mut out = create()?;
+                out.write_all(fdb).map_err(io_error)?;
+                out.flush().map_err(io_error)
+            }
+            #[cfg(feature = "sqlite")]
+            FileFormat::Sqlite => {
+                use super::sqlite::{try_export_db, Connection};
+
+                if path.exists() {
+                    fs::remove_file(&path).map_err(io_error)?;
+                }
+                let mut conn = Connection::open(&path).map_err(store_error(&path))?;
+                try_export_db(&mut conn, db).map_err(store_error(&path))
+            }
+            format @ FileFormat::Csv | format @ FileFormat::Jsonl => {
+                let options = StreamOptions {
+                    csv: dialect_of(&path, output.dialect),
+                    ..StreamOptions::default()
+                };
+                let format = match format {
+                    FileFormat::Csv => StreamFormat::Csv,
+                    _ => StreamFormat::JsonLines,
+                };
+                let tables = db.tables().map_err(store_error(&path))?;
+                let sink = StreamSink::new(create()?, format, options).map_err(io_error)?;
+                let sink = sink.with_hooks(self.output_hooks.clone());
+                export_to_sink(tables.iter(), sink, &()).map_err(store_error(&path))?;
+                Ok(())
+            }
+            FileFormat::Postgres => {
+                write_sql_dump(schema, SqlDialect::Postgres, create()?).map_err(io_error)
+            }
+            FileFormat::Mysql => {
+                write_sql_dump(schema, SqlDialect::MySql, create()?).map_err(io_error)
+            }
+            #[cfg(not(feature = "sqlite"))]
+            FileFormat::Sqlite => Err(PipelineError::UnsupportedFormat(path)),
+        }
+    }
+
+    /// Run the pipeline, see the [module documentation](self)
+    pub fn run(&self) -> Result<PipelineStats, PipelineError> {
+        let mut schema = Schema::new();
+        for input in &self.inputs {
+            schema.extend(self.load_input(input)?.into_tables());
+        }
+        schema.tables.retain(|name, _| self.filter.keeps(name));
+        for transform in &self.transforms {
+            transform.apply(&mut schema)?;
+        }
+
+        let mut fdb = Vec::new();
+        store::Database::from(&schema)
+            .write(&mut fdb)
+            .expect("writing to a Vec can't fail");
+        for output in &self.outputs {
+            self.write_output(output, &schema, &fdb)?;
+        }
+        Ok(PipelineStats {
+            tables: schema.table_count(),
+            rows: schema.iter().map(|t| t.into_iter().count()).sum(),
+            outputs: self.outputs.len(),
+        })
+    }
+}
+
+/// Load the pipeline file at `path` and run it
+pub fn run_pipeline<P: AsRef<Path>>(path: P) -> Result<PipelineStats, PipelineError> {
+    Pipeline::load(path)?.run()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fdb::{common::ValueType, hooks::strip_prefix};
+
+    #[test]
+    fn test_run_pipeline() {
+        let dir = std::env::temp_dir().join(format!("assembly-pipeline-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("in.fdb"), crate::fdb::testing::objects(10)).unwrap();
+        fs::write(dir.join("Icons.csv"), "id,path\n1,a.dds\n2,b.dds\n").unwrap();
+        let toml = r#"
+            [[input]]
+            path = "in.fdb"
+
+            [[input]]
+            path = "Icons.csv"
+
+            [filter]
+            exclude = ["Ic*"]
+
+            [[transform]]
+            kind = "drop_column"
+            table = "Objects"
+            column = "scale"
+
+            [[transform]]
+            kind = "rename_table"
+            from = "Objects"
+            to = "Things"
+
+            [[output]]
+            path = "out.fdb"
+
+            [[output]]
+            path = "out.txt"
+            format = "jsonl"
+        "#;
+        fs::write(dir.join("pipeline.toml"), toml).unwrap();
+
+        let pipeline = Pipeline::load(dir.join("pipeline.toml")).unwrap();
+        let pipeline = pipeline
+            .with_hook("Objects", "name", strip_prefix("name "))
+            .with_output_hook("Things", "name", |field| match field {
+                Field::Text(name) => Field::Text(format!("#{}", name)),
+                field => field,
+            });
+        let stats = pipeline.run().unwrap();
+        assert_eq!(
+            stats,
+            PipelineStats {
+                tables: 1,
+                rows: 10,
+                outputs: 2
+            }
+        );
+        let out = Schema::from_source(File::open(dir.join("out.fdb")).unwrap()).unwrap();
+        let things = out.table("Things").unwrap();
+        let columns: Vec<_> = things.columns().iter().map(|c| c.field_type).collect();
+        assert_eq!(
+            columns,
+            [ValueType::Integer, ValueType::Text, ValueType::Boolean]
+        );
+        let jsonl = fs::read_to_string(dir.join("out.txt")).unwrap();
+        assert_eq!(jsonl.lines().count(), 10);
+        assert!(jsonl.contains(r##""name":"#3""##), "{}", jsonl);
+        let mut names = things.into_iter().map(|row| row.fields()[1].clone());
+        assert!(names.clone().any(|name| name == Field::Text("3".into())));
+        assert!(names.all(|name| !name.to_string().contains('#')));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let unknown = Pipeline::from_toml("[[transform]]\nkind = \"explode\"\n");
+        assert!(matches!(unknown, Err(PipelineError::Toml(_))));
+    }
+}
@@ -0,0 +1,138 @@
+//! ## Composite indexes
+//!
+//! The buckets of a table only index the first column. Some tables are keyed
+//! by more than one column, e.g. a component ID and a slot, so a lookup by
+//! the first column still returns many rows. A [`CompositeIndex`] maps the
+//! values of any set of columns to the rows that contain them.
+use std::collections::HashMap;
+
+use assembly_core::displaydoc::Display;
+use thiserror::Error;
+
+use crate::fdb::{
+    common::Latin1String,
+    core::Field,
+    mem::{Row, Table},
+};
+
+#[derive(Error, Debug, Display)]
+/// Errors when creating a composite index
+#[non_exhaustive]
+pub enum IndexError {
+    /// Unknown column {0:?}
+    UnknownColumn(String),
+    /// No columns to index
+    NoColumns,
+}
+
+/// A hashable representation of a field
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum KeyPart {
+    Nothing,
+    Integer(i32),
+    Float(u32),
+    Text(Vec<u8>),
+    Boolean(bool),
+    BigInt(i64),
+}
+
+impl From<&Field> for KeyPart {
+    fn from(field: &Field) -> Self {
+        match field {
+            Field::Nothing => Self::Nothing,
+            Field::Integer(v) => Self::Integer(*v),
+            Field::Float(v) => Self::Float(v.to_bits()),
+            Field::Text(v) | Field::VarChar(v) => {
+                Self::Text(Latin1String::encode(v).as_bytes().to_vec())
+            }
+            Field::Boolean(v) => Self::Boolean(*v),
+            Field::BigInt(v) => Self::BigInt(*v),
+        }
+    }
+}
+
+/// An index over the values of multiple columns of a table
+///
+/// `TEXT` and `VARCHAR` values are considered equal if they contain the same
+/// string.
+pub struct CompositeIndex<'a> {
+    columns: Vec<usize>,
+    map: HashMap<Vec<KeyPart>, Vec<Row<'a>>>,
+}
+
+impl<'a> CompositeIndex<'a> {
+    /// Build an index over the columns at the given indices
+    ///
+    /// Rows that have fewer fields than needed are not part of the index.
+    pub fn new(table: &Table<'a>, columns: Vec<usize>) -> Self {
+        let mut map: HashMap<_, Vec<_>> = HashMap::new();
+        for row in table.row_iter() {
+            let key: Option<Vec<_>> = columns
+                .iter()
+                .map(|&i| row.field_at(i).map(|f| KeyPart::from(&Field::from(f))))
+                .collect();
+            if let Some(key) = key {
+                map.entry(key).or_default().push(row);
+            }
+        }
+        Self { columns, map }
+    }
+
+    /// The indices of the columns in this index
+    pub fn columns(&self) -> &[usize] {
+        &self.columns
+    }
+
+    /// The number of distinct keys
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Whether the index contains no keys
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Get all rows that match `key`, which has one value per column
+    pub fn get(&self, key: &[Field]) -> &[Row<'a>] {
+        let key: Vec<_> = key.iter().map(KeyPart::from).collect();
+        self.map.get(&key).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+impl<'a> Table<'a> {
+    /// Build a [`CompositeIndex`] over the columns with the given names
+    ///
+    /// ```
+    /// # use assembly_data::fdb::{common::{Latin1String, ValueType}, core::Field, mem, store};
+    /// let mut table = store::Table::new(1);
+    /// table.push_column(Latin1String::encode("id"), ValueType::Integer);
+    /// table.push_column(Latin1String::encode("slot"), ValueType::Integer);
+    /// table.push_row(0, &[Field::Integer(1), Field::Integer(0)]);
+    /// table.push_row(0, &[Field::Integer(1), Field::Integer(1)]);
+    /// let mut db = store::Database::new();
+    /// db.push_table(Latin1String::encode("Slots"), table);
+    /// let mut buf = Vec::new();
+    /// db.write(&mut buf).unwrap();
+    ///
+    /// let tables = mem::Database::new(&buf).tables().unwrap();
+    /// let table = tables.by_name("Slots").unwrap().unwrap();
+    /// let index = table.index_by(&["id", "slot"]).unwrap();
+    /// assert_eq!(index.get(&[Field::Integer(1), Field::Integer(1)]).len(), 1);
+    /// assert!(index.get(&[Field::Integer(1), Field::Integer(2)]).is_empty());
+    /// ```
+    pub fn index_by(&self, columns: &[&str]) -> Result<CompositeIndex<'a>, IndexError> {
+        if columns.is_empty() {
+            return Err(IndexError::NoColumns);
+        }
+        let indices = columns
+            .iter()
+            .map(|name| {
+                self.column_iter()
+                    .position(|c| c.name() == *name)
+                    .ok_or_else(|| IndexError::UnknownColumn(name.to_string()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(CompositeIndex::new(self, indices))
+    }
+}
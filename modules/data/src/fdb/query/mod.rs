@@ -6,9 +6,14 @@ use super::{
     core::Field,
 };
 use assembly_core::displaydoc::Display;
-use hsieh_hash::digest;
+use assembly_core::hash::{fdb_int_hash, fdb_text_hash};
 use thiserror::Error;
 
+#[cfg(feature = "fdb-mem")]
+pub mod index;
+pub mod sort;
+pub mod text;
+
 /// A struct that can act as a PK filter
 ///
 /// This structure works much like a pre-implemented closure
@@ -36,6 +41,7 @@ impl PrimaryKeyFilter {
 
 #[derive(Error, Debug, Display)]
 /// Errors when creating filters at runtime
+#[non_exhaustive]
 pub enum PKFilterError {
     /// Unsupported Type {0:?}
     UnsupportedType(ValueType),
@@ -45,7 +51,7 @@ pub enum PKFilterError {
 
 /// Create a text PK filter
 pub fn text_pk_filter(key: String) -> Result<PrimaryKeyFilter, PKFilterError> {
-    let hash_value = digest(key.as_bytes());
+    let hash_value = fdb_text_hash(key.as_bytes());
     let value = Field::Text(key);
     Ok(PrimaryKeyFilter { hash_value, value })
 }
@@ -53,7 +59,7 @@ pub fn text_pk_filter(key: String) -> Result<PrimaryKeyFilter, PKFilterError> {
 /// Create an integer PK filter
 pub fn integer_pk_filter(key: String) -> Result<PrimaryKeyFilter, PKFilterError> {
     let value: i32 = key.parse().map_err(PKFilterError::KeyError)?;
-    let hash_value = u32::from_ne_bytes(value.to_ne_bytes());
+    let hash_value = fdb_int_hash(value);
     Ok(PrimaryKeyFilter {
         hash_value,
         value: Field::Integer(value),
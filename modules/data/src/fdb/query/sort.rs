@@ -0,0 +1,338 @@
+//! ## Sorting rows
+//!
+//! [`sort_rows`] orders rows by a list of columns, each ascending or
+//! descending. Fields are compared by their value, not by their type:
+//!
+//! - `NULL` comes before everything else
+//! - Numbers (`INTEGER`, `BIGINT`, `FLOAT` and `BOOLEAN` as `0` or `1`) are
+//!   compared with each other by their exact value, with `NaN` after all
+//!   other numbers. Of an integer and a float with the same value, the
+//!   integer comes first, and `-0.0` comes before `0.0`.
+//! - Strings (`TEXT` and `VARCHAR`) come after all numbers and are compared
+//!   in dictionary order, see [`latin1::collate`]
+//!
+//! This works for owned rows ([`Row`]) as well as rows that borrow from
+//! a database file ([`mem::Row`][crate::fdb::mem::Row]), see [`SortRow`].
+//!
+//! The stream and SQL dump exporters take sort keys as well, e.g.
+//! [`StreamOptions::sort`][crate::fdb::stream::StreamOptions::sort]. As they
+//! apply the same keys to every table, they use [`sort_rows_if_present`],
+//! which skips the keys for columns that a table doesn't have.
+//!
+//! ```
+//! use assembly_data::fdb::{core::{Field, Row}, query::sort::{sort_rows, Order::*}};
+//!
+//! let mut rows = vec![
+//!     Row::from(vec![Field::Integer(2), Field::Text("b".into())]),
+//!     Row::from(vec![Field::Integer(1), Field::Text("a".into())]),
+//!     Row::from(vec![Field::Integer(2), Field::Text("c".into())]),
+//! ];
+//! sort_rows(&mut rows, &["id", "name"], &[("id", Asc), ("name", Desc)]).unwrap();
+//! let names: Vec<_> = rows.iter().map(|r| r.fields()[1].to_string()).collect();
+//! assert_eq!(names, ["\"a\"", "\"c\"", "\"b\""]);
+//! ```
+use std::{borrow::Cow, cmp::Ordering};
+
+use assembly_core::{displaydoc::Display, latin1};
+use thiserror::Error;
+
+use crate::fdb::{
+    common::{Latin1String, Value},
+    core::{Field, Row},
+};
+
+/// The direction of a sort key
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Order {
+    /// Smallest value first
+    Asc,
+    /// Largest value first
+    Desc,
+}
+
+/// Errors from [`sort_rows`]
+#[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SortError {
+    /// There is no column {0:?}
+    UnknownColumn(String),
+}
+
+/// The value of a field, as compared by [`sort_rows`]
+#[derive(Debug, Clone)]
+pub enum SortKey<'a> {
+    /// `NULL`, or a column that the row doesn't have
+    Null,
+    /// An integer (including booleans)
+    Int(i64),
+    /// A float
+    Float(f32),
+    /// The Latin-1 bytes of a string
+    Text(Cow<'a, [u8]>),
+}
+
+impl SortKey<'_> {
+    fn rank(&self) -> u8 {
+        match self {
+            SortKey::Null => 0,
+            SortKey::Int(_) | SortKey::Float(_) => 1,
+            SortKey::Text(_) => 2,
+        }
+    }
+}
+
+/// Compare an integer and a float that is not `NaN` by their exact values
+fn cmp_int_float(i: i64, f: f32) -> Ordering {
+    // 2^63, the first float above the range of `i64`
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    let f = f64::from(f);
+    if f >= LIMIT {
+        return Ordering::Less;
+    }
+    if f < -LIMIT {
+        return Ordering::Greater;
+    }
+    let whole = f.trunc();
+    // `whole` is in the range of `i64`, so this is exact
+    i.cmp(&(whole as i64))
+        .then_with(|| 0.0.partial_cmp(&(f - whole)).unwrap_or(Ordering::Equal))
+}
+
+/// Compare two floats by value, with `NaN` last and `-0.0` before `0.0`
+fn cmp_floats(a: f32, b: f32) -> Ordering {
+    a.is_nan().cmp(&b.is_nan()).then_with(|| a.total_cmp(&b))
+}
+
+impl Ord for SortKey<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (SortKey::Int(a), SortKey::Int(b)) => a.cmp(b),
+            (SortKey::Int(_), SortKey::Float(b)) if b.is_nan() => Ordering::Less,
+            (SortKey::Int(a), SortKey::Float(b)) => cmp_int_float(*a, *b).then(Ordering::Less),
+            (SortKey::Float(_), SortKey::Int(_)) => other.cmp(self).reverse(),
+            (SortKey::Float(a), SortKey::Float(b)) => cmp_floats(*a, *b),
+            (SortKey::Text(a), SortKey::Text(b)) => latin1::collate(a, b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for SortKey<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for SortKey<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SortKey<'_> {}
+
+impl<'a> From<&'a Field> for SortKey<'a> {
+    fn from(field: &'a Field) -> Self {
+        match field {
+            Value::Nothing => SortKey::Null,
+            Value::Integer(v) => SortKey::Int(i64::from(*v)),
+            Value::Float(v) => SortKey::Float(*v),
+            Value::Text(v) | Value::VarChar(v) => match Latin1String::encode(v) {
+                Cow::Borrowed(s) => SortKey::Text(Cow::Borrowed(s.as_bytes())),
+                Cow::Owned(s) => SortKey::Text(Cow::Owned(s.as_bytes().to_vec())),
+            },
+            Value::Boolean(v) => SortKey::Int(i64::from(*v)),
+            Value::BigInt(v) => SortKey::Int(*v),
+        }
+    }
+}
+
+#[cfg(feature = "fdb-mem")]
+impl<'a> From<crate::fdb::mem::Field<'a>> for SortKey<'a> {
+    fn from(field: crate::fdb::mem::Field<'a>) -> Self {
+        match field {
+            Value::Nothing => SortKey::Null,
+            Value::Integer(v) => SortKey::Int(i64::from(v)),
+            Value::Float(v) => SortKey::Float(v),
+            Value::Text(v) | Value::VarChar(v) => SortKey::Text(Cow::Borrowed(v.as_bytes())),
+            Value::Boolean(v) => SortKey::Int(i64::from(v)),
+            Value::BigInt(v) => SortKey::Int(v),
+        }
+    }
+}
+
+/// A row that can be sorted with [`sort_rows`]
+pub trait SortRow {
+    /// The key of the field at `index`
+    fn sort_key(&self, index: usize) -> SortKey<'_>;
+}
+
+impl<R: SortRow + ?Sized> SortRow for &R {
+    fn sort_key(&self, index: usize) -> SortKey<'_> {
+        (**self).sort_key(index)
+    }
+}
+
+impl SortRow for Row {
+    fn sort_key(&self, index: usize) -> SortKey<'_> {
+        self.fields()
+            .get(index)
+            .map_or(SortKey::Null, SortKey::from)
+    }
+}
+
+#[cfg(feature = "fdb-mem")]
+impl SortRow for crate::fdb::mem::Row<'_> {
+    fn sort_key(&self, index: usize) -> SortKey<'_> {
+        self.field_at(index).map_or(SortKey::Null, SortKey::from)
+    }
+}
+
+/// Sort `rows` by the given `(column, order)` keys, see the [module documentation](self)
+///
+/// `columns` are the names of the columns of the rows, in order. The sort is
+/// stable, so rows with equal keys keep their order.
+pub fn sort_rows<R, C>(
+    rows: &mut [R],
+    columns: &[C],
+    keys: &[(&str, Order)],
+) -> Result<(), SortError>
+where
+    R: SortRow,
+    C: AsRef<str>,
+{
+    let mut resolved = Vec::with_capacity(keys.len());
+    for &(name, order) in keys {
+        let index = columns
+            .iter()
+            .position(|c| c.as_ref() == name)
+            .ok_or_else(|| SortError::UnknownColumn(name.to_owned()))?;
+        resolved.push((index, order));
+    }
+    sort_resolved(rows, &resolved);
+    Ok(())
+}
+
+/// Like [`sort_rows`], but skips the keys for columns that are not in `columns`
+pub fn sort_rows_if_present<R, C, K>(rows: &mut [R], columns: &[C], keys: &[(K, Order)])
+where
+    R: SortRow,
+    C: AsRef<str>,
+    K: AsRef<str>,
+{
+    let resolved: Vec<_> = keys
+        .iter()
+        .filter_map(|(name, order)| {
+            let index = columns.iter().position(|c| c.as_ref() == name.as_ref())?;
+            Some((index, *order))
+        })
+        .collect();
+    sort_resolved(rows, &resolved);
+}
+
+/// Sort `rows` by the `(index, order)` keys
+fn sort_resolved<R: SortRow>(rows: &mut [R], resolved: &[(usize, Order)]) {
+    if resolved.is_empty() {
+        return;
+    }
+    rows.sort_by(|a, b| {
+        for &(index, order) in resolved {
+            let ordering = a.sort_key(index).cmp(&b.sort_key(index));
+            let ordering = match order {
+                Order::Asc => ordering,
+                Order::Desc => ordering.reverse(),
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_mixed_types() {
+        let mut rows: Vec<Row> = vec![
+            Field::Text("é".into()),
+            Field::Float(1.5),
+            Field::Nothing,
+            Field::BigInt(1),
+            Field::Text("z".into()),
+            Field::Text("E".into()),
+            Field::Float(f32::NAN),
+            Field::Boolean(false),
+            Field::Integer(2),
+        ]
+        .into_iter()
+        .map(|f| Row::from(vec![f]))
+        .collect();
+        sort_rows(&mut rows, &["v"], &[("v", Order::Asc)]).unwrap();
+        let text: Vec<_> = rows.iter().map(|r| r.fields()[0].to_string()).collect();
+        assert_eq!(
+            text,
+            ["NULL", "false", "1", "1.5", "2", "NaN", "\"E\"", "\"é\"", "\"z\""]
+        );
+
+        assert_eq!(
+            sort_rows(&mut rows, &["v"], &[("w", Order::Asc)]),
+            Err(SortError::UnknownColumn("w".into()))
+        );
+    }
+
+    #[test]
+    fn test_sort_rows_if_present() {
+        let mut rows: Vec<Row> = (0..4)
+            .map(|i| Row::from(vec![Field::Integer(i % 2), Field::Integer(i)]))
+            .collect();
+        let keys = [("x", Order::Asc), ("a", Order::Asc), ("b", Order::Desc)];
+        sort_rows_if_present(&mut rows, &["a", "b"], &keys);
+        let b: Vec<_> = rows.iter().map(|r| r.fields()[1].to_string()).collect();
+        assert_eq!(b, ["2", "0", "3", "1"]);
+    }
+
+    #[test]
+    fn test_sort_key_total_order() {
+        let keys: Vec<SortKey<'_>> = vec![
+            SortKey::Null,
+            SortKey::Int(-1),
+            SortKey::Int(0),
+            SortKey::Int(1),
+            SortKey::Int((1 << 53) + 1),
+            SortKey::Int(i64::MAX - 1),
+            SortKey::Int(i64::MAX),
+            SortKey::Int(i64::MIN),
+            SortKey::Float(0.0),
+            SortKey::Float(-0.0),
+            SortKey::Float(0.5),
+            SortKey::Float(-0.5),
+            SortKey::Float(1.0),
+            SortKey::Float(9_007_199_254_740_992.0),
+            SortKey::Float(9_223_372_036_854_775_807.0),
+            SortKey::Float(-9_223_372_036_854_775_808.0),
+            SortKey::Float(f32::INFINITY),
+            SortKey::Float(f32::NEG_INFINITY),
+            SortKey::Float(f32::NAN),
+            SortKey::Float(-f32::NAN),
+            SortKey::Text(Cow::Borrowed(b"a")),
+        ];
+        for a in &keys {
+            for b in &keys {
+                assert_eq!(a.cmp(b), b.cmp(a).reverse(), "{:?} {:?}", a, b);
+                for c in &keys {
+                    if a <= b && b <= c {
+                        assert!(a <= c, "{:?} {:?} {:?}", a, b, c);
+                    }
+                }
+            }
+        }
+        assert!(SortKey::Int(0) < SortKey::Float(-0.0));
+        assert!(SortKey::Float(-0.0) < SortKey::Float(0.0));
+        assert!(SortKey::Int((1 << 53) + 1) > SortKey::Float(9_007_199_254_740_992.0));
+        assert!(SortKey::Int(i64::MAX) < SortKey::Float(9_223_372_036_854_775_807.0));
+        assert!(SortKey::Float(-f32::NAN) > SortKey::Float(f32::INFINITY));
+    }
+}
@@ -0,0 +1,102 @@
+//! ## Text filters
+//!
+//! Decoding every string of a column to compare it with a search term
+//! allocates for every row that contains non-ASCII characters. A [`TextFilter`]
+//! encodes the search term once and then compares the raw Latin-1 bytes.
+use crate::fdb::common::{Latin1Str, Latin1String};
+
+/// The comparison of a [`TextFilter`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TextOp {
+    /// The whole string is equal to the pattern
+    Eq,
+    /// The string starts with the pattern
+    StartsWith,
+    /// The string contains the pattern
+    Contains,
+}
+
+/// A filter for `TEXT` and `VARCHAR` fields that compares raw bytes
+pub struct TextFilter {
+    pattern: Latin1String,
+    op: TextOp,
+    ignore_case: bool,
+}
+
+impl TextFilter {
+    /// Create a new filter, encoding `pattern` as Latin-1
+    pub fn new(pattern: &str, op: TextOp) -> Self {
+        Self {
+            pattern: Latin1String::encode(pattern).into_owned(),
+            op,
+            ignore_case: false,
+        }
+    }
+
+    /// Create a filter that matches strings equal to `pattern`
+    pub fn eq(pattern: &str) -> Self {
+        Self::new(pattern, TextOp::Eq)
+    }
+
+    /// Create a filter that matches strings starting with `pattern`
+    pub fn starts_with(pattern: &str) -> Self {
+        Self::new(pattern, TextOp::StartsWith)
+    }
+
+    /// Create a filter that matches strings containing `pattern`
+    pub fn contains(pattern: &str) -> Self {
+        Self::new(pattern, TextOp::Contains)
+    }
+
+    /// Ignore the case of Latin-1 letters when comparing
+    pub fn ignore_case(mut self) -> Self {
+        self.ignore_case = true;
+        self
+    }
+
+    /// Check `text` against the filter
+    pub fn matches(&self, text: &Latin1Str) -> bool {
+        let pattern = self.pattern.as_bytes();
+        match (self.op, self.ignore_case) {
+            (TextOp::Eq, false) => text.as_bytes() == pattern,
+            (TextOp::Eq, true) => text.eq_ignore_case(pattern),
+            (TextOp::StartsWith, false) => text.starts_with(pattern),
+            (TextOp::StartsWith, true) => text.starts_with_ignore_case(pattern),
+            (TextOp::Contains, false) => text.contains(pattern),
+            (TextOp::Contains, true) => text.contains_ignore_case(pattern),
+        }
+    }
+
+    /// Check a field against the filter
+    ///
+    /// Fields that are neither `TEXT` nor `VARCHAR` never match.
+    #[cfg(feature = "fdb-mem")]
+    pub fn filter(&self, field: &crate::fdb::mem::Field) -> bool {
+        use crate::fdb::common::Value;
+        match field {
+            Value::Text(text) | Value::VarChar(text) => self.matches(text),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_filter() {
+        let text = Latin1String::encode("Équipe Rouge");
+        assert!(TextFilter::eq("Équipe Rouge").matches(&text));
+        assert!(!TextFilter::eq("équipe rouge").matches(&text));
+        assert!(TextFilter::eq("équipe rouge").ignore_case().matches(&text));
+        assert!(TextFilter::starts_with("ÉQUIPE")
+            .ignore_case()
+            .matches(&text));
+        assert!(!TextFilter::starts_with("Rouge").matches(&text));
+        assert!(TextFilter::contains("Rou").matches(&text));
+        assert!(TextFilter::contains("ROUGE").ignore_case().matches(&text));
+        assert!(!TextFilter::contains("Bleu").ignore_case().matches(&text));
+        assert!(TextFilter::contains("").matches(&text));
+    }
+}
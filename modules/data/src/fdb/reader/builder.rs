@@ -16,6 +16,7 @@ use std::{
 
 /// Errors generated by the builder module
 #[derive(Debug, Display, Error)]
+#[non_exhaustive]
 pub enum BuilderError {
     /// Failed IO
     IO(#[from] io::Error),
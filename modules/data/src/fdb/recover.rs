@@ -0,0 +1,435 @@
+//! # Recovery of damaged database files
+//!
+//! Files that were only partially downloaded or stored on a damaged disk often
+//! still contain most of their tables. The other APIs stop at the first invalid
+//! address, so this module provides [`scan`], which collects everything that
+//! can still be read:
+//!
+//! 1. If the file header and table list are intact, every table that they
+//!    reference is read on its own, so one damaged table doesn't affect the others.
+//! 2. Otherwise, the whole file is searched for structures that look like table
+//!    definitions: a plausible column count, and addresses of names that are
+//!    valid identifiers.
+//! 3. Tables without a readable data header are matched with plausible data
+//!    headers found in the file, whose rows have the right number of fields.
+//!
+//! Rows are read one by one, and rows that reference invalid data are skipped
+//! and counted in [`RecoveredTable::lost_rows`]. The result can be written to a
+//! new file with [`Recovered::into_database`].
+//!
+//! This module is only available with the `fdb-core` and `fdb-mem` features.
+
+use std::{collections::HashSet, convert::TryFrom};
+
+use super::{
+    common::{Latin1String, ValueType},
+    core::Field,
+    file::{ArrayHeader, FDBFieldData, FDBTableDataHeader, FDBTableDefHeader, FDBTableHeader},
+    mem::raw,
+    store,
+};
+
+/// The maximum number of columns of a plausible table definition
+const MAX_COLUMNS: u32 = 256;
+/// The maximum number of buckets of a plausible table
+const MAX_BUCKETS: u32 = 1 << 20;
+
+/// A table that was recovered from a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveredTable {
+    /// The name of the table
+    pub name: String,
+    /// The address of the table definition header
+    pub def_addr: u32,
+    /// The address of the table data header, if one was found
+    pub data_addr: Option<u32>,
+    /// The names and types of the columns
+    pub columns: Vec<(String, ValueType)>,
+    /// The number of buckets of the table
+    pub bucket_count: usize,
+    /// The rows that could be read, with the index of their bucket
+    pub rows: Vec<(usize, Vec<Field>)>,
+    /// The number of rows that were referenced, but could not be read
+    pub lost_rows: usize,
+}
+
+/// The result of [`scan`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recovered {
+    /// Whether the file header and table list were intact
+    pub header_ok: bool,
+    /// The tables that were found
+    pub tables: Vec<RecoveredTable>,
+}
+
+impl Recovered {
+    /// The total number of rows that were recovered
+    pub fn row_count(&self) -> usize {
+        self.tables.iter().map(|t| t.rows.len()).sum()
+    }
+
+    /// The total number of rows that could not be read
+    pub fn lost_rows(&self) -> usize {
+        self.tables.iter().map(|t| t.lost_rows).sum()
+    }
+
+    /// Convert the recovered tables to a database that can be written to a file
+    ///
+    /// Tables without data get a single empty bucket.
+    pub fn into_database(self) -> store::Database {
+        let mut db = store::Database::new();
+        for table in self.tables {
+            let mut dest = store::Table::new(table.bucket_count.max(1));
+            for (name, value_type) in &table.columns {
+                dest.push_column(Latin1String::encode(name), *value_type);
+            }
+            for (bucket, fields) in &table.rows {
+                dest.push_row(*bucket, fields);
+            }
+            db.push_table(Latin1String::encode(&table.name), dest);
+        }
+        db
+    }
+}
+
+fn identifier(buf: &[u8], addr: u32) -> Option<String> {
+    let s = raw::string(buf, addr).ok()?.value;
+    let bytes = s.as_bytes();
+    let valid =
+        !bytes.is_empty() && bytes.len() < 256 && bytes.iter().all(|b| b.is_ascii_graphic());
+    if valid {
+        Some(s.decode().into_owned())
+    } else {
+        None
+    }
+}
+
+fn read_def(buf: &[u8], def: FDBTableDefHeader) -> Option<(String, Vec<(String, ValueType)>)> {
+    if def.column_count == 0 || def.column_count > MAX_COLUMNS {
+        return None;
+    }
+    let name = identifier(buf, def.table_name_addr)?;
+    let columns = raw::column_headers(buf, def)
+        .ok()?
+        .map(|c| {
+            let value_type = ValueType::try_from(c.value.column_data_type).ok()?;
+            Some((identifier(buf, c.value.column_name_addr)?, value_type))
+        })
+        .collect::<Option<_>>()?;
+    Some((name, columns))
+}
+
+fn read_field(buf: &[u8], data: FDBFieldData) -> Option<Field> {
+    let addr = u32::from_le_bytes(data.value);
+    Some(match ValueType::try_from(data.data_type).ok()? {
+        ValueType::Nothing => Field::Nothing,
+        ValueType::Integer => Field::Integer(i32::from_le_bytes(data.value)),
+        ValueType::Float => Field::Float(f32::from_le_bytes(data.value)),
+        ValueType::Text => Field::Text(raw::string(buf, addr).ok()?.value.decode().into_owned()),
+        ValueType::Boolean => Field::Boolean(data.value != [0; 4]),
+        ValueType::BigInt => Field::BigInt(raw::i64(buf, addr).ok()?.value),
+        ValueType::VarChar => {
+            Field::VarChar(raw::string(buf, addr).ok()?.value.decode().into_owned())
+        }
+    })
+}
+
+struct Rows {
+    bucket_count: usize,
+    rows: Vec<(usize, Vec<Field>)>,
+    lost_rows: usize,
+}
+
+/// The rows of a data header, before it is matched with a table definition
+struct RawRows {
+    bucket_count: usize,
+    /// The rows with their bucket, `None` if a field could not be read
+    rows: Vec<(usize, Option<Vec<Field>>)>,
+    lost_rows: usize,
+    /// The distinct field counts of the readable rows
+    field_counts: Vec<usize>,
+}
+
+impl RawRows {
+    fn into_rows(self, column_count: usize) -> Rows {
+        let mut rows = Vec::with_capacity(self.rows.len());
+        let mut lost_rows = self.lost_rows;
+        for (index, fields) in self.rows {
+            match fields.filter(|fields| fields.len() == column_count) {
+                Some(fields) => rows.push((index, fields)),
+                None => lost_rows += 1,
+            }
+        }
+        Rows {
+            bucket_count: self.bucket_count,
+            rows,
+            lost_rows,
+        }
+    }
+}
+
+/// Read all rows of a data header
+///
+/// The linked lists of rows are followed until an address repeats, so damaged
+/// lists that form a cycle are only read once.
+fn read_rows(buf: &[u8], data: FDBTableDataHeader) -> Option<RawRows> {
+    let buckets: Vec<_> = raw::bucket_headers(buf, data).ok()?.collect();
+    let mut rows = Vec::new();
+    let mut lost_rows = 0;
+    let mut visited = HashSet::new();
+    for (index, bucket) in buckets.iter().enumerate() {
+        let mut addr = bucket.value.row_header_list_head_addr;
+        while addr != u32::MAX && visited.insert(addr) {
+            let entry = match raw::row_list_entry(buf, addr) {
+                Ok(entry) => entry.value,
+                Err(_) => {
+                    lost_rows += 1;
+                    break;
+                }
+            };
+            addr = entry.row_header_list_next_addr;
+            let fields = raw::row_header(buf, entry.row_header_addr)
+                .and_then(|row| raw::fields(buf, row.value))
+                .ok()
+                .and_then(|fields| {
+                    fields
+                        .into_iter()
+                        .map(|f| read_field(buf, f.value))
+                        .collect::<Option<Vec<_>>>()
+                });
+            rows.push((index, fields));
+        }
+    }
+    let mut field_counts: Vec<_> = rows
+        .iter()
+        .filter_map(|(_, fields)| Some(fields.as_ref()?.len()))
+        .collect();
+    field_counts.sort_unstable();
+    field_counts.dedup();
+    Some(RawRows {
+        bucket_count: buckets.len(),
+        rows,
+        lost_rows,
+        field_counts,
+    })
+}
+
+fn is_plausible_data(buf: &[u8], data: FDBTableDataHeader) -> bool {
+    let count = data.buckets.count;
+    if count == 0 || count > MAX_BUCKETS || data.buckets.base_offset & 3 != 0 {
+        return false;
+    }
+    match raw::bucket_headers(buf, data) {
+        Ok(mut buckets) => buckets.all(|b| {
+            let addr = b.value.row_header_list_head_addr;
+            addr == u32::MAX || (addr & 3 == 0 && (addr as usize) < buf.len())
+        }),
+        Err(_) => false,
+    }
+}
+
+fn read_u32(buf: &[u8], addr: usize) -> Option<u32> {
+    let bytes = buf.get(addr..addr + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn scan_defs(buf: &[u8]) -> Vec<(u32, FDBTableDefHeader)> {
+    (0..buf.len().saturating_sub(11))
+        .step_by(4)
+        .filter_map(|addr| {
+            let def = FDBTableDefHeader {
+                column_count: read_u32(buf, addr)?,
+                table_name_addr: read_u32(buf, addr + 4)?,
+                column_header_list_addr: read_u32(buf, addr + 8)?,
+            };
+            read_def(buf, def).map(|_| (addr as u32, def))
+        })
+        .collect()
+}
+
+/// Find the plausible data headers in the file and read their rows
+fn scan_data(buf: &[u8], used: &HashSet<u32>) -> Vec<(u32, RawRows)> {
+    (0..buf.len().saturating_sub(7))
+        .step_by(4)
+        .filter(|addr| !used.contains(&(*addr as u32)))
+        .filter_map(|addr| {
+            let data = FDBTableDataHeader {
+                buckets: ArrayHeader {
+                    count: read_u32(buf, addr)?,
+                    base_offset: read_u32(buf, addr + 4)?,
+                },
+            };
+            if !is_plausible_data(buf, data) {
+                return None;
+            }
+            Some((addr as u32, read_rows(buf, data)?))
+        })
+        .collect()
+}
+
+/// Find the rows for a table definition among the candidate data headers
+///
+/// This prefers the closest candidate after the definition, as that is where
+/// the writers put it. The candidate that is picked is removed from the list.
+fn find_data(
+    candidates: &mut Vec<(u32, RawRows)>,
+    def_addr: u32,
+    column_count: usize,
+) -> Option<(u32, Rows)> {
+    let (pos, _) = candidates
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, rows))| rows.field_counts.binary_search(&column_count).is_ok())
+        .min_by_key(|(_, (addr, _))| (*addr < def_addr, addr.wrapping_sub(def_addr)))?;
+    let (addr, rows) = candidates.swap_remove(pos);
+    Some((addr, rows.into_rows(column_count)))
+}
+
+/// Recover as many tables and rows as possible from a (damaged) database file
+pub fn scan(buf: &[u8]) -> Recovered {
+    let headers = raw::header(buf)
+        .and_then(|h| raw::table_headers(buf, h.value).map(|t| t.collect::<Vec<_>>()))
+        .ok();
+    let header_ok = headers.is_some();
+
+    // (def address, def, data address)
+    let mut defs: Vec<(u32, FDBTableDefHeader, Option<u32>)> = Vec::new();
+    let mut complete = header_ok;
+    for t in headers.unwrap_or_default() {
+        let def = match raw::table_def_header(buf, t.value) {
+            Ok(def) if read_def(buf, def.value).is_some() => def,
+            _ => {
+                complete = false;
+                continue;
+            }
+        };
+        let data = raw::table_data_header(buf, t.value)
+            .ok()
+            .filter(|d| is_plausible_data(buf, d.value))
+            .map(|d| d.addr);
+        defs.push((def.addr, def.value, data));
+    }
+    if !complete {
+        for (addr, def) in scan_defs(buf) {
+            if !defs.iter().any(|(a, _, _)| *a == addr) {
+                defs.push((addr, def, None));
+            }
+        }
+    }
+    defs.sort_by_key(|(addr, _, _)| *addr);
+
+    let used: HashSet<u32> = defs.iter().filter_map(|(_, _, data)| *data).collect();
+    let mut candidates = if defs.iter().any(|(_, _, data)| data.is_none()) {
+        scan_data(buf, &used)
+    } else {
+        Vec::new()
+    };
+    let mut tables = Vec::new();
+    for (def_addr, def, data_addr) in defs {
+        let (name, columns) = match read_def(buf, def) {
+            Some(def) => def,
+            None => continue,
+        };
+        let column_count = columns.len();
+        let data = match data_addr {
+            Some(addr) => {
+                let table = FDBTableHeader {
+                    table_def_header_addr: def_addr,
+                    table_data_header_addr: addr,
+                };
+                raw::table_data_header(buf, table)
+                    .ok()
+                    .and_then(|d| read_rows(buf, d.value))
+                    .map(|rows| (addr, rows.into_rows(column_count)))
+            }
+            None => find_data(&mut candidates, def_addr, column_count),
+        };
+        let table = match data {
+            Some((addr, rows)) => RecoveredTable {
+                name,
+                def_addr,
+                data_addr: Some(addr),
+                columns,
+                bucket_count: rows.bucket_count,
+                rows: rows.rows,
+                lost_rows: rows.lost_rows,
+            },
+            None => RecoveredTable {
+                name,
+                def_addr,
+                data_addr: None,
+                columns,
+                bucket_count: 0,
+                rows: Vec::new(),
+                lost_rows: 0,
+            },
+        };
+        tables.push(table);
+    }
+
+    Recovered { header_ok, tables }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fdb::testing::SampleDatabase;
+
+    fn sample() -> Vec<u8> {
+        let columns = [("id", ValueType::Integer), ("label", ValueType::Text)];
+        let mut db = SampleDatabase::new();
+        for (name, count) in &[("Alpha", 3), ("Beta", 5)] {
+            db = db.table(name, &columns).buckets(4);
+            for id in 0..*count {
+                let label = Field::Text(format!("{}{}", name, id));
+                db = db.row(vec![Field::Integer(id), label]);
+            }
+        }
+        db.build()
+    }
+
+    #[test]
+    fn test_scan_intact() {
+        let buf = sample();
+        let recovered = scan(&buf);
+        assert!(recovered.header_ok);
+        assert_eq!(recovered.tables.len(), 2);
+        assert_eq!(recovered.row_count(), 8);
+        assert_eq!(recovered.lost_rows(), 0);
+    }
+
+    #[test]
+    fn test_scan_damaged_header() {
+        let mut buf = sample();
+        // Point the table list out of bounds
+        buf[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        let recovered = scan(&buf);
+        assert!(!recovered.header_ok);
+        let names: Vec<_> = recovered.tables.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["Alpha", "Beta"]);
+        assert_eq!(recovered.row_count(), 8);
+
+        let mut out = Vec::new();
+        recovered.into_database().write(&mut out).unwrap();
+        assert_eq!(scan(&out).row_count(), 8);
+    }
+
+    #[test]
+    fn test_scan_row_cycle() {
+        let mut buf = sample();
+        let header = raw::header(&buf).unwrap().value;
+        let table = raw::table_headers(&buf, header).unwrap().next().unwrap();
+        let data = raw::table_data_header(&buf, table.value).unwrap().value;
+        let head = raw::bucket_headers(&buf, data)
+            .unwrap()
+            .map(|b| b.value.row_header_list_head_addr)
+            .find(|addr| *addr != u32::MAX)
+            .unwrap();
+        // Let the first entry of a bucket point to itself
+        let next = head as usize + 4;
+        buf[next..next + 4].copy_from_slice(&head.to_le_bytes());
+        let recovered = scan(&buf);
+        assert_eq!(recovered.tables[0].rows.len(), 3);
+        assert_eq!(recovered.tables[1].rows.len(), 5);
+    }
+}
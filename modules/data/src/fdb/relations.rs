@@ -0,0 +1,290 @@
+//! # Relationships between tables
+//!
+//! The database has no declared foreign keys, but many integer columns refer to
+//! the primary key of another table, e.g. `ComponentsRegistry.id` to
+//! `Objects.id`. A [`Relations`] set holds such links, either from a known
+//! configuration like [`Relations::cdclient`], or discovered from the data with
+//! `Relations::discover` (see the `discover` module).
+//!
+//! The set can be rendered as a [DOT] graph with [`Relations::write_dot`], or
+//! as [GraphML] with [`Relations::write_graphml`], to get an overview of the
+//! structure of the database.
+//!
+//! ```
+//! use assembly_data::fdb::relations::Relations;
+//!
+//! let mut dot = Vec::new();
+//! Relations::cdclient().write_dot(&mut dot).unwrap();
+//! let dot = String::from_utf8(dot).unwrap();
+//! assert!(dot.contains("\"ComponentsRegistry\" -> \"Objects\" [label=\"id\"];"));
+//! ```
+//!
+//! [DOT]: https://graphviz.org/doc/info/lang.html
+//! [GraphML]: http://graphml.graphdrawing.org/
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    io::{self, Write},
+};
+
+/// A column that refers to a column of another table
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ForeignKey {
+    /// The name of the table with the reference
+    pub table: String,
+    /// The name of the column with the reference
+    pub column: String,
+    /// The name of the referenced table
+    pub target_table: String,
+    /// The name of the referenced column
+    pub target_column: String,
+}
+
+impl ForeignKey {
+    /// Create a new foreign key
+    pub fn new(table: &str, column: &str, target_table: &str, target_column: &str) -> Self {
+        Self {
+            table: table.to_string(),
+            column: column.to_string(),
+            target_table: target_table.to_string(),
+            target_column: target_column.to_string(),
+        }
+    }
+
+    /// Get the label of the edge, e.g. `behaviorID` or `skillID → SkillID`
+    fn label(&self) -> String {
+        if self.column == self.target_column {
+            self.column.clone()
+        } else {
+            format!("{} \u{2192} {}", self.column, self.target_column)
+        }
+    }
+}
+
+/// Formats the key as `table.column -> target_table.target_column`
+impl fmt::Display for ForeignKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{} -> {}.{}",
+            self.table, self.column, self.target_table, self.target_column
+        )
+    }
+}
+
+/// The well-known relationships of the `CDClient`
+const CDCLIENT: &[(&str, &str, &str, &str)] = &[
+    ("ComponentsRegistry", "id", "Objects", "id"),
+    ("Missions", "offer_objectID", "Objects", "id"),
+    ("Missions", "target_objectID", "Objects", "id"),
+    ("MissionTasks", "id", "Missions", "id"),
+    ("MissionText", "id", "Missions", "id"),
+    (
+        "SkillBehavior",
+        "behaviorID",
+        "BehaviorTemplate",
+        "behaviorID",
+    ),
+    (
+        "BehaviorParameter",
+        "behaviorID",
+        "BehaviorTemplate",
+        "behaviorID",
+    ),
+    (
+        "BehaviorTemplate",
+        "templateID",
+        "BehaviorTemplateName",
+        "templateID",
+    ),
+    ("ObjectSkills", "objectTemplate", "Objects", "id"),
+    ("ObjectSkills", "skillID", "SkillBehavior", "skillID"),
+    ("ItemSets", "skillSetWith2", "ItemSetSkills", "SkillSetID"),
+    ("ItemSets", "skillSetWith3", "ItemSetSkills", "SkillSetID"),
+    ("ItemSets", "skillSetWith4", "ItemSetSkills", "SkillSetID"),
+    ("ItemSets", "skillSetWith5", "ItemSetSkills", "SkillSetID"),
+    ("ItemSets", "skillSetWith6", "ItemSetSkills", "SkillSetID"),
+    ("ItemSetSkills", "SkillID", "SkillBehavior", "skillID"),
+    ("InventoryComponent", "itemid", "Objects", "id"),
+    ("LootTable", "itemid", "Objects", "id"),
+    (
+        "LootMatrix",
+        "LootTableIndex",
+        "LootTableIndex",
+        "LootTableIndex",
+    ),
+];
+
+/// A set of foreign keys
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Relations {
+    keys: BTreeSet<ForeignKey>,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Relations {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create the set of well-known relationships of the `CDClient`
+    pub fn cdclient() -> Self {
+        CDCLIENT
+            .iter()
+            .map(|(t, c, tt, tc)| ForeignKey::new(t, c, tt, tc))
+            .collect()
+    }
+
+    /// Add a foreign key, returning whether it was new
+    pub fn insert(&mut self, key: ForeignKey) -> bool {
+        self.keys.insert(key)
+    }
+
+    /// Get the number of foreign keys
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Check whether the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Iterate over the foreign keys, ordered by table and column
+    pub fn iter(&self) -> impl Iterator<Item = &ForeignKey> {
+        self.keys.iter()
+    }
+
+    /// Get the names of all tables that take part in a relationship
+    pub fn tables(&self) -> BTreeSet<&str> {
+        let names = self
+            .keys
+            .iter()
+            .flat_map(|k| vec![&k.table, &k.target_table]);
+        names.map(String::as_str).collect()
+    }
+
+    /// Render the set as a DOT graph, with one node per table
+    pub fn write_dot<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "digraph cdclient {{")?;
+        writeln!(out, "    rankdir=LR;")?;
+        writeln!(out, "    node [shape=box];")?;
+        for table in self.tables() {
+            writeln!(out, "    \"{}\";", table.replace('"', "\\\""))?;
+        }
+        for key in &self.keys {
+            writeln!(
+                out,
+                "    \"{}\" -> \"{}\" [label=\"{}\"];",
+                key.table.replace('"', "\\\""),
+                key.target_table.replace('"', "\\\""),
+                key.label().replace('"', "\\\"")
+            )?;
+        }
+        writeln!(out, "}}")
+    }
+
+    /// Render the set as a GraphML document, with one node per table
+    pub fn write_graphml<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(
+            out,
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">"
+        )?;
+        writeln!(
+            out,
+            "  <key id=\"column\" for=\"edge\" attr.name=\"column\" attr.type=\"string\"/>"
+        )?;
+        writeln!(
+            out,
+            "  <key id=\"target_column\" for=\"edge\" attr.name=\"target_column\" attr.type=\"string\"/>"
+        )?;
+        writeln!(out, "  <graph id=\"cdclient\" edgedefault=\"directed\">")?;
+        for table in self.tables() {
+            writeln!(out, "    <node id=\"{}\"/>", escape(table))?;
+        }
+        for (index, key) in self.keys.iter().enumerate() {
+            writeln!(
+                out,
+                "    <edge id=\"e{}\" source=\"{}\" target=\"{}\">",
+                index,
+                escape(&key.table),
+                escape(&key.target_table)
+            )?;
+            writeln!(
+                out,
+                "      <data key=\"column\">{}</data>",
+                escape(&key.column)
+            )?;
+            writeln!(
+                out,
+                "      <data key=\"target_column\">{}</data>",
+                escape(&key.target_column)
+            )?;
+            writeln!(out, "    </edge>")?;
+        }
+        writeln!(out, "  </graph>")?;
+        writeln!(out, "</graphml>")
+    }
+}
+
+impl std::iter::FromIterator<ForeignKey> for Relations {
+    fn from_iter<I: IntoIterator<Item = ForeignKey>>(iter: I) -> Self {
+        Self {
+            keys: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<ForeignKey> for Relations {
+    fn extend<I: IntoIterator<Item = ForeignKey>>(&mut self, iter: I) {
+        self.keys.extend(iter)
+    }
+}
+
+#[cfg(all(test, feature = "fdb-core", feature = "fdb-mem"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{common::ValueType, core::Field, mem, testing::SampleDatabase};
+
+    #[test]
+    fn test_discover() {
+        let columns = [("id", ValueType::Integer), ("count", ValueType::Integer)];
+        let db = SampleDatabase::new().table("Objects", &columns);
+        let db = (1..=5).fold(db, |db, id| {
+            db.row(vec![Field::Integer(id), Field::Integer(3)])
+        });
+        let columns = [
+            ("id", ValueType::Integer),
+            ("component_id", ValueType::Integer),
+        ];
+        let db = db.table("ComponentsRegistry", &columns);
+        let db = [(1, 100), (2, 200), (3, 300)]
+            .iter()
+            .fold(db, |db, &(id, c)| {
+                db.row(vec![Field::Integer(id), Field::Integer(c)])
+            });
+        let buf = db.build();
+        let tables = mem::Database::new(&buf).tables().unwrap();
+
+        let relations = Relations::discover(tables, 0.9).unwrap();
+        let keys: Vec<String> = relations.iter().map(ToString::to_string).collect();
+        assert_eq!(keys, vec!["ComponentsRegistry.id -> Objects.id"]);
+
+        let mut graphml = Vec::new();
+        relations.write_graphml(&mut graphml).unwrap();
+        let graphml = String::from_utf8(graphml).unwrap();
+        assert!(
+            graphml.contains("<edge id=\"e0\" source=\"ComponentsRegistry\" target=\"Objects\">")
+        );
+        assert!(graphml.contains("<node id=\"Objects\"/>"));
+    }
+}
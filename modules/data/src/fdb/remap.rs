@@ -0,0 +1,328 @@
+//! # Moving ids out of the way when merging mods
+//!
+//! Two mods that both add objects usually pick the same ids for them, e.g. the
+//! first ids after the end of the original `CDClient`. An [`IdRemapper`] keeps
+//! track of the ids that are already taken in every referenced column of a
+//! set of [`Relations`], and gives the conflicting rows of the next mod new ids
+//! from the range after the highest one. Every column that refers to a changed
+//! id is rewritten as well, so that the rows of a mod still belong together.
+//! The result can then be added to the base with [`merge_schema`].
+//!
+//! ```
+//! use assembly_data::fdb::{
+//!     core::Field,
+//!     csv::CsvDialect,
+//!     relations::{ForeignKey, Relations},
+//!     remap::{merge_schema, IdRemapper},
+//!     source::{import_from_source, CsvSource},
+//! };
+//!
+//! let load = |objects: &str, registry: &str| {
+//!     let source = CsvSource::new(CsvDialect::default())
+//!         .with_table("Objects", objects)
+//!         .with_table("ComponentsRegistry", registry);
+//!     import_from_source(source, &()).unwrap()
+//! };
+//! let mut base = load("id,name\n1,brick\n2,plate\n", "id,component_type\n1,2\n2,2\n");
+//! let mut other = load("id,name\n2,rocket\n", "id,component_type\n2,7\n");
+//!
+//! let mut relations = Relations::new();
+//! relations.insert(ForeignKey::new("ComponentsRegistry", "id", "Objects", "id"));
+//! let mut remapper = IdRemapper::new(relations);
+//! remapper.reserve(&base);
+//! let ids = remapper.remap(&mut other).unwrap();
+//! assert_eq!(ids.get("Objects", "id", 2), Some(3));
+//!
+//! merge_schema(&mut base, other).unwrap();
+//! let registry = base.table("ComponentsRegistry").unwrap();
+//! let mut rows = registry.buckets().iter().flat_map(|b| b.rows_ref());
+//! assert!(rows.any(|r| r.fields() == &[Field::Integer(3), Field::Integer(7)]));
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use assembly_core::displaydoc::Display;
+use thiserror::Error;
+
+use super::{
+    core::{pk_hash, Bucket, Field, Schema, Table},
+    relations::Relations,
+};
+
+/// Errors when remapping or merging
+#[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RemapError {
+    /// There are no more free ids for {0}.{1}
+    Exhausted(String, String),
+    /// The columns of table {0:?} don't match
+    ColumnMismatch(String),
+    /// Cannot compute the bucket for the primary key {1} in table {0:?}
+    UnsupportedKey(String, Field),
+}
+
+/// The new ids that were assigned by [`IdRemapper::remap`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdMap {
+    ids: BTreeMap<(String, String), HashMap<i32, i32>>,
+}
+
+impl IdMap {
+    /// Get the new id for `old` in `column` of `table`, if it was changed
+    pub fn get(&self, table: &str, column: &str, old: i32) -> Option<i32> {
+        let key = (table.to_owned(), column.to_owned());
+        self.ids.get(&key)?.get(&old).copied()
+    }
+
+    /// Get the number of changed ids
+    pub fn len(&self) -> usize {
+        self.ids.values().map(HashMap::len).sum()
+    }
+
+    /// Check whether no id was changed
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over the changed ids as `(table, column, old, new)`
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str, i32, i32)> {
+        self.ids.iter().flat_map(|((table, column), ids)| {
+            let mut ids: Vec<_> = ids.iter().map(|(&old, &new)| (old, new)).collect();
+            ids.sort_unstable();
+            ids.into_iter()
+                .map(move |(old, new)| (table.as_str(), column.as_str(), old, new))
+        })
+    }
+}
+
+/// Assigns non-conflicting ids, see the [module documentation](self)
+///
+/// The referenced columns are the target columns of the relations, e.g.
+/// `Objects.id`. Only integer ids are changed.
+#[derive(Debug, Clone)]
+pub struct IdRemapper {
+    relations: Relations,
+    taken: BTreeMap<(String, String), BTreeSet<i32>>,
+}
+
+impl IdRemapper {
+    /// Create a remapper for the references in `relations`, with no ids taken
+    pub fn new(relations: Relations) -> Self {
+        let taken = relations
+            .iter()
+            .map(|k| {
+                (
+                    (k.target_table.clone(), k.target_column.clone()),
+                    BTreeSet::new(),
+                )
+            })
+            .collect();
+        Self { relations, taken }
+    }
+
+    /// Mark all ids in the referenced columns of `schema` as taken
+    pub fn reserve(&mut self, schema: &Schema) {
+        for ((table, column), taken) in &mut self.taken {
+            taken.extend(column_ids(schema, table, column));
+        }
+    }
+
+    /// Mark some ids in `column` of `table` as taken, e.g. a range used by another mod
+    pub fn reserve_ids<I: IntoIterator<Item = i32>>(&mut self, table: &str, column: &str, ids: I) {
+        let key = (table.to_owned(), column.to_owned());
+        self.taken.entry(key).or_default().extend(ids);
+    }
+
+    /// Give the rows of `schema` with taken ids new ones, and update all references
+    ///
+    /// For every referenced column, the conflicting ids are replaced by the ids
+    /// after the highest one that is taken or used in `schema`, in ascending
+    /// order. Afterwards, all ids of `schema` are taken, so that further mods
+    /// can be remapped against the merged result. Rows that change their
+    /// primary key are moved to the matching bucket.
+    pub fn remap(&mut self, schema: &mut Schema) -> Result<IdMap, RemapError> {
+        let mut map = IdMap::default();
+        for ((table, column), taken) in &self.taken {
+            let used = column_ids(schema, table, column);
+            let mut next = match taken.iter().chain(&used).max() {
+                Some(max) => max.checked_add(1),
+                None => continue,
+            };
+            let mut ids = HashMap::new();
+            for &old in used.intersection(taken) {
+                let new =
+                    next.ok_or_else(|| RemapError::Exhausted(table.clone(), column.clone()))?;
+                ids.insert(old, new);
+                next = new.checked_add(1);
+            }
+            if !ids.is_empty() {
+                map.ids.insert((table.clone(), column.clone()), ids);
+            }
+        }
+
+        for ((table, column), ids) in &map.ids {
+            if let Some(table) = schema.table_mut(table) {
+                rewrite_column(table, column, ids);
+            }
+            let references = self.relations.iter();
+            let references = references.filter(|k| k.target_table == *table);
+            for key in references.filter(|k| k.target_column == *column) {
+                if let Some(table) = schema.table_mut(&key.table) {
+                    rewrite_column(table, &key.column, ids);
+                }
+            }
+        }
+
+        self.reserve(schema);
+        Ok(map)
+    }
+}
+
+/// The integer ids in `column` of `table`
+fn column_ids(schema: &Schema, table: &str, column: &str) -> BTreeSet<i32> {
+    let table = match schema.table(table) {
+        Some(table) => table,
+        None => return BTreeSet::new(),
+    };
+    let index = match table.columns().iter().position(|c| &*c.name == column) {
+        Some(index) => index,
+        None => return BTreeSet::new(),
+    };
+    let rows = table.buckets().iter().flat_map(Bucket::rows_ref);
+    rows.filter_map(|row| match row.fields().get(index) {
+        Some(Field::Integer(id)) => Some(*id),
+        _ => None,
+    })
+    .collect()
+}
+
+/// Replace the integer ids in `column` of `table` through `ids`
+fn rewrite_column(table: &mut Table, column: &str, ids: &HashMap<i32, i32>) {
+    let index = match table.columns().iter().position(|c| &*c.name == column) {
+        Some(index) => index,
+        None => return,
+    };
+    let mut moved = Vec::new();
+    for bucket in table.buckets_mut() {
+        let rows = std::mem::take(bucket.rows_mut());
+        for mut row in rows {
+            let new = match row.fields().get(index) {
+                Some(Field::Integer(old)) => ids.get(old).copied(),
+                _ => None,
+            };
+            if let Some(new) = new {
+                row.fields_mut()[index] = Field::Integer(new);
+                if index == 0 {
+                    moved.push(row);
+                    continue;
+                }
+            }
+            bucket.rows_mut().push(row);
+        }
+    }
+    let buckets = table.buckets_mut();
+    for row in moved {
+        let hash = pk_hash(&row.fields()[0]).expect("integer keys have a hash");
+        let len = buckets.len();
+        buckets[hash as usize % len].rows_mut().push(row);
+    }
+}
+
+/// Add all rows of `other` to `base`, returning the number of rows
+///
+/// Tables that are only in `other` are moved over as they are. For tables in
+/// both, the columns must have the same names, and the rows are added to the
+/// buckets of `base` for their primary key. This doesn't check for duplicate
+/// keys, so `other` should be passed through [`IdRemapper::remap`] first.
+pub fn merge_schema(base: &mut Schema, other: Schema) -> Result<usize, RemapError> {
+    let mut count = 0;
+    for table in other.into_tables() {
+        let target = match base.table_mut(table.name()) {
+            Some(target) => target,
+            None => {
+                count += table
+                    .buckets()
+                    .iter()
+                    .map(|b| b.rows_ref().len())
+                    .sum::<usize>();
+                base.insert_table(table);
+                continue;
+            }
+        };
+        let names = |t: &Table| {
+            t.columns()
+                .iter()
+                .map(|c| c.name.clone())
+                .collect::<Vec<_>>()
+        };
+        if names(target) != names(&table) {
+            return Err(RemapError::ColumnMismatch(table.name().to_owned()));
+        }
+        let name = table.name().to_owned();
+        let buckets = target.buckets_mut();
+        for row in table.into_buckets().into_iter().flat_map(Bucket::rows) {
+            let key = row.fields().first().cloned().unwrap_or(Field::Nothing);
+            let hash = match pk_hash(&key) {
+                Some(hash) => hash,
+                None => return Err(RemapError::UnsupportedKey(name, key)),
+            };
+            if buckets.is_empty() {
+                buckets.push(Bucket::new());
+            }
+            let index = hash as usize % buckets.len();
+            buckets[index].rows_mut().push(row);
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fdb::{
+        csv::CsvDialect,
+        source::{import_from_source, CsvSource},
+    };
+
+    fn load(objects: &str, skills: &str) -> Schema {
+        let source = CsvSource::new(CsvDialect::default())
+            .with_table("Objects", objects)
+            .with_table("ObjectSkills", skills);
+        import_from_source(source, &()).unwrap()
+    }
+
+    #[test]
+    fn test_remap_two_mods() {
+        let mut remapper = IdRemapper::new(Relations::cdclient());
+        let mut base = load("id,name\n1,brick\n", "objectTemplate,skillID\n1,10\n");
+        remapper.reserve(&base);
+
+        let mut first = load("id,name\n2,a\n3,b\n", "objectTemplate,skillID\n3,11\n");
+        let ids = remapper.remap(&mut first).unwrap();
+        assert!(ids.is_empty());
+        merge_schema(&mut base, first).unwrap();
+
+        let mut second = load("id,name\n2,c\n3,d\n9,e\n", "objectTemplate,skillID\n3,12\n");
+        let ids = remapper.remap(&mut second).unwrap();
+        let changed: Vec<_> = ids.iter().collect();
+        assert_eq!(
+            changed,
+            [("Objects", "id", 2, 10), ("Objects", "id", 3, 11)]
+        );
+        assert_eq!(merge_schema(&mut base, second).unwrap(), 4);
+
+        let objects = base.table("Objects").unwrap();
+        for row in objects.buckets().iter().flat_map(Bucket::rows_ref) {
+            let hash = pk_hash(&row.fields()[0]).unwrap() as usize;
+            let bucket = &objects.buckets()[hash % objects.buckets().len()];
+            assert!(bucket.rows_ref().contains(row));
+        }
+        let skills = base.table("ObjectSkills").unwrap();
+        let rows = skills.buckets().iter().flat_map(Bucket::rows_ref);
+        let skill = rows
+            .map(|r| r.fields().clone())
+            .find(|f| f[1] == Field::Integer(12));
+        assert_eq!(skill.unwrap()[0], Field::Integer(11));
+    }
+}
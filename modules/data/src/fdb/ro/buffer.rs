@@ -8,13 +8,12 @@ use crate::fdb::{
     },
 };
 use assembly_core::{
-    buffer::{CastError, MinimallyAligned},
+    buffer::{try_read, CastError, MinimallyAligned, Pod},
     displaydoc::Display,
 };
-use bytemuck::from_bytes;
 use std::{
     cmp::Ordering,
-    convert::TryInto,
+    convert::{TryFrom, TryInto},
     fmt,
     mem::size_of,
     ops::{Deref, Range},
@@ -23,6 +22,7 @@ use thiserror::Error;
 
 #[derive(Error, Debug, Display, Clone, PartialEq, Eq)]
 /// Error for handling a buffer
+#[non_exhaustive]
 pub enum BufferError {
     /// index out of bounds {0:?}
     OutOfBounds(Range<usize>),
@@ -72,8 +72,10 @@ pub(crate) fn compare_bytes(bytes: &[u8], name_bytes: &[u8]) -> Ordering {
 
 /// Get a reference to a type at the given address of this buffer
 ///
-/// This functions checks whether the offset and alignment is valid
-pub fn get_at<T>(buf: &[u8], addr: usize) -> Res<&T> {
+/// This functions checks whether the offset and alignment is valid. `T` must
+/// be [`Pod`], because the bytes may contain any bit pattern. Use [`read_at`]
+/// for addresses that may not be aligned.
+pub fn get_at<T: Pod>(buf: &[u8], addr: usize) -> Res<&T> {
     let base = buf.as_ptr();
     let len = buf.len();
     let size = std::mem::size_of::<T>();
@@ -96,8 +98,9 @@ pub fn get_at<T>(buf: &[u8], addr: usize) -> Res<&T> {
 
 /// Get a reference to a slice at the given address of this buffer
 ///
-/// This functions checks whether the offset and alignment is valid
-pub fn get_slice_at<T>(buf: &[u8], addr: usize, count: usize) -> Res<&[T]> {
+/// This functions checks whether the offset and alignment is valid. Like for
+/// [`get_at`], `T` must be [`Pod`].
+pub fn get_slice_at<T: Pod>(buf: &[u8], addr: usize, count: usize) -> Res<&[T]> {
     let base = buf.as_ptr();
     let len = buf.len();
     let size = std::mem::size_of::<T>();
@@ -123,6 +126,16 @@ pub fn get_slice_at<T>(buf: &[u8], addr: usize, count: usize) -> Res<&[T]> {
     Ok(unsafe { &*(std::ptr::slice_from_raw_parts(start as *const T, count)) })
 }
 
+/// Copy a value out of the buffer at the given address
+///
+/// Unlike [`get_at`], this never creates a reference into the buffer, so the
+/// address does not need to be aligned, see [`try_read`].
+pub fn read_at<T: Pod>(buf: &[u8], addr: usize) -> Res<T> {
+    let out_of_bounds = || BufferError::OutOfBounds(addr..addr.saturating_add(size_of::<T>()));
+    let offset = u32::try_from(addr).map_err(|_| out_of_bounds())?;
+    try_read(buf, offset).map_err(|_| out_of_bounds())
+}
+
 /// Get the database header
 #[cfg(target_endian = "little")]
 pub fn header_ref(buf: &[u8]) -> Res<&FDBHeader> {
@@ -131,7 +144,7 @@ pub fn header_ref(buf: &[u8]) -> Res<&FDBHeader> {
 
 /// Get the header of the file.
 pub fn header(buf: &[u8], _: ()) -> Res<FDBHeader> {
-    Ok(*header_ref(buf)?)
+    read_at(buf, 0)
 }
 
 /// Get the table slice
@@ -144,10 +157,7 @@ pub fn table_headers<'a>(buf: &'a [u8], header: &'a FDBHeader) -> Res<&'a [FDBTa
 }
 
 /// Get the table definition reference
-pub fn table_definition_ref(
-    buf: &[u8],
-    header: FDBTableHeader,
-) -> Res<&FDBTableDefHeader> {
+pub fn table_definition_ref(buf: &[u8], header: FDBTableHeader) -> Res<&FDBTableDefHeader> {
     get_at(buf, header.table_def_header_addr as usize)
 }
 
@@ -158,12 +168,12 @@ pub fn table_data_ref(buf: &[u8], header: FDBTableHeader) -> Res<&FDBTableDataHe
 
 /// Get the table definition header
 pub fn table_definition(buf: &[u8], header: FDBTableHeader) -> Res<FDBTableDefHeader> {
-    table_definition_ref(buf, header).map(|x| *x)
+    read_at(buf, header.table_def_header_addr as usize)
 }
 
 /// Get the table data header
 pub fn table_data(buf: &[u8], header: FDBTableHeader) -> Res<FDBTableDataHeader> {
-    table_data_ref(buf, header).map(|x| *x)
+    read_at(buf, header.table_data_header_addr as usize)
 }
 
 /// Compares the name given by `bytes` with the one referenced in `table_header`
@@ -180,7 +190,7 @@ pub fn cmp_table_header_name(buf: &[u8], bytes: &[u8], table_header: FDBTableHea
 
 impl<'a> Buffer<'a> {
     /// Creates a new instance.
-    pub fn new(buf: &'a [u8]) -> Self {
+    pub const fn new(buf: &'a [u8]) -> Self {
         Self(buf)
     }
 
@@ -255,8 +265,7 @@ impl<'a> Buffer<'a> {
 
     /// Get the table data header at the given addr.
     pub fn table_data_header(self, addr: u32) -> Res<FDBTableDataHeader> {
-        let buf = self.get_len_at(addr as usize, 8)?;
-        Ok(*from_bytes(buf))
+        read_at(self.0, addr as usize)
     }
 
     /// Get the `FDBRowHeader` list entry at the given addr.
@@ -271,8 +280,7 @@ impl<'a> Buffer<'a> {
 
     /// Get the `FDBRowHeader` at the given addr.
     pub fn row_header(self, addr: u32) -> Res<FDBRowHeader> {
-        let buf = self.get_len_at(addr as usize, 8)?;
-        Ok(*from_bytes(buf))
+        read_at(self.0, addr as usize)
     }
 }
 
@@ -284,3 +292,29 @@ impl<'a> fmt::Debug for Buffer<'a> {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_unaligned() {
+        // backed by `u32`s, so that address 4 is aligned and address 7 is not
+        let mut words = [0u32; 4];
+        let bytes: &mut [u8] = bytemuck::cast_slice_mut(&mut words[..]);
+        bytes[7..11].copy_from_slice(&7u32.to_le_bytes());
+        bytes[11..15].copy_from_slice(&9u32.to_le_bytes());
+        let bytes = &*bytes;
+        let row = Buffer::new(bytes).row_header(7).unwrap();
+        assert_eq!((row.fields.count, row.fields.base_offset), (7, 9));
+        assert_eq!(
+            get_at::<FDBRowHeader>(bytes, 7),
+            Err(BufferError::Unaligned(7))
+        );
+        assert!(get_at::<FDBHeader>(bytes, 4).is_ok());
+        assert_eq!(
+            read_at::<FDBRowHeader>(bytes, 12),
+            Err(BufferError::OutOfBounds(12..20))
+        );
+    }
+}
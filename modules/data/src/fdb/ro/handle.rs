@@ -69,6 +69,7 @@ where
 
 #[derive(Debug, Display, Clone, PartialEq, Eq)]
 /// The different kinds of [`BaseError`]s
+#[non_exhaustive]
 pub enum BaseErrorKind {
     /// Unimplemented
     Unimplemented,
@@ -162,7 +163,7 @@ pub type Database<'a> = Handle<'a, ()>;
 
 impl<'a> Database<'a> {
     /// Create a new database handle
-    pub fn new_ref(mem: &'a [u8]) -> Self {
+    pub const fn new_ref(mem: &'a [u8]) -> Self {
         Self {
             mem: Buffer::new(mem),
             raw: (),
@@ -1,4 +1,8 @@
 //! Read-Only low level access to a database file
+//!
+//! A [`Handle`] is a [`Buffer`] reference and a value, so it is [`Send`] and
+//! [`Sync`] whenever the value is. An [`ArcHandle`] is [`Send`] and [`Sync`]
+//! if the owned buffer is, which allows moving it into a `'static` thread.
 
 use std::{ops::Deref, sync::Arc};
 
@@ -12,6 +16,17 @@ pub mod buffer;
 pub mod handle;
 pub mod slice;
 
+/// Fails to compile if the handle types are not `Send + Sync`
+#[allow(dead_code)]
+fn assert_send_sync() {
+    fn check<T: Send + Sync>() {}
+    check::<Buffer<'static>>();
+    check::<Handle<'static, ()>>();
+    check::<RefHandle<'static, [u8]>>();
+    check::<ArcHandle<Vec<u8>, ()>>();
+    check::<ArcHandle<Box<[u8]>, u32>>();
+}
+
 /// An owned, atomically-reference counted handle to a database
 pub type ArcHandle<B, T> = BaseHandle<Arc<B>, T>;
 
@@ -0,0 +1,216 @@
+//! # Pluggable destinations for exports
+//!
+//! A [`RowSink`] receives the tables of a database one after the other, as a
+//! call to [`RowSink::begin_table`], one call to [`RowSink::push_row`] per row
+//! and a call to [`RowSink::end_table`]. [`export_to_sink`] is the driver that
+//! reads the tables and makes these calls, so that a new destination (e.g. an
+//! upload to a server) only needs to implement the trait. The rows are passed
+//! in the [`IterOrder`] of [`RowSink::order`], so a sink can ask for an output
+//! that doesn't depend on the bucket layout of the file, and then by the
+//! [`RowSink::sort_keys`] (with the `fdb-core` feature). If
+//! [`RowSink::index_strings`] is set, the rows look up their strings in a
+//! [`StringIndex`] that is built once for the whole file.
+//!
+//! The exporters in this crate are sinks as well:
+//!
+//! - [`StreamSink`][super::stream::StreamSink] for CSV and JSON lines
+//! - [`SqliteSink`][super::sqlite::SqliteSink] for SQLite, with the `sqlite` feature
+//! - [`SqlDumpSink`][super::io::SqlDumpSink] for Postgres and MySQL dumps, with
+//!   the `fdb-core` feature
+//!
+//! ```
+//! use assembly_data::fdb::{mem::{Database, Row, Table}, sink::{export_to_sink, RowSink}};
+//!
+//! /// Counts the rows of every table
+//! struct Counter(Vec<(String, usize)>);
+//!
+//! impl RowSink for Counter {
+//!     type Error = std::convert::Infallible;
+//!
+//!     fn begin_table(&mut self, table: &Table<'_>) -> Result<(), Self::Error> {
+//!         self.0.push((table.name().into_owned(), 0));
+//!         Ok(())
+//!     }
+//!
+//!     fn push_row(&mut self, _row: Row<'_>) -> Result<(), Self::Error> {
+//!         self.0.last_mut().unwrap().1 += 1;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let file: &[u8] = &[0, 0, 0, 0, 8, 0, 0, 0];
+//! let tables = Database::new(file).tables().unwrap();
+//! let mut counter = Counter(Vec::new());
+//! let rows = export_to_sink(tables.iter(), &mut counter, &()).unwrap();
+//! assert_eq!(rows, 0);
+//! ```
+
+use assembly_core::{buffer::CastError, displaydoc::Display, progress::ProgressSink};
+use thiserror::Error;
+
+#[cfg(feature = "fdb-core")]
+use super::query::sort::{sort_rows_if_present, Order};
+use super::{
+    common::IterOrder,
+    mem::{strings::StringIndex, Row, Table},
+};
+
+/// A destination for the rows of an export, see the [module documentation](self)
+pub trait RowSink {
+    /// The error when writing to the sink
+    type Error;
+
+    /// Start a new table, with the name and columns of `table`
+    ///
+    /// Sinks that need to know about all rows in advance (e.g. to infer the
+    /// type of a column) may read them from `table` here.
+    fn begin_table(&mut self, table: &Table<'_>) -> Result<(), Self::Error>;
+
+    /// Add a row to the current table
+    fn push_row(&mut self, row: Row<'_>) -> Result<(), Self::Error>;
+
+    /// End the current table
+    fn end_table(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// The order in which [`export_to_sink`] passes the rows of a table
+    ///
+    /// For any order other than [`IterOrder::File`], the rows of each table
+    /// are collected and sorted before the first call to [`RowSink::push_row`].
+    fn order(&self) -> IterOrder {
+        IterOrder::File
+    }
+
+    /// Whether [`export_to_sink`] should build a [`StringIndex`] for the rows
+    ///
+    /// This scans the whole file once before the first table, which only
+    /// pays off if most of the file is exported.
+    fn index_strings(&self) -> bool {
+        false
+    }
+
+    /// The columns that [`export_to_sink`] sorts the rows of each table by
+    ///
+    /// The rows are sorted with [`sort_rows_if_present`] after they were put
+    /// in the [`RowSink::order`], so keys for columns that a table doesn't
+    /// have are skipped.
+    #[cfg(feature = "fdb-core")]
+    fn sort_keys(&self) -> &[(String, Order)] {
+        &[]
+    }
+
+    /// End the export, after the last table
+    ///
+    /// This is only called if all tables were written successfully.
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<S: RowSink + ?Sized> RowSink for &mut S {
+    type Error = S::Error;
+
+    fn begin_table(&mut self, table: &Table<'_>) -> Result<(), Self::Error> {
+        (**self).begin_table(table)
+    }
+
+    fn push_row(&mut self, row: Row<'_>) -> Result<(), Self::Error> {
+        (**self).push_row(row)
+    }
+
+    fn end_table(&mut self) -> Result<(), Self::Error> {
+        (**self).end_table()
+    }
+
+    fn order(&self) -> IterOrder {
+        (**self).order()
+    }
+
+    fn index_strings(&self) -> bool {
+        (**self).index_strings()
+    }
+
+    #[cfg(feature = "fdb-core")]
+    fn sort_keys(&self) -> &[(String, Order)] {
+        (**self).sort_keys()
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        (**self).finish()
+    }
+}
+
+/// Errors from [`export_to_sink`]
+#[derive(Debug, Error, Display)]
+#[non_exhaustive]
+pub enum SinkError<E: std::error::Error + 'static> {
+    /// Failed to read a table: {0}
+    Cast(#[from] CastError),
+    /// Failed to write to the sink: {0}
+    Sink(#[source] E),
+    /// The export was cancelled
+    Cancelled,
+}
+
+/// The rows of `table` in the order of `sink`
+fn sorted_rows<'a, S: RowSink>(
+    table: &Table<'a>,
+    sink: &S,
+) -> Box<dyn Iterator<Item = Row<'a>> + 'a> {
+    #[cfg(feature = "fdb-core")]
+    if !sink.sort_keys().is_empty() {
+        let columns: Vec<_> = table.column_iter().map(|c| c.name()).collect();
+        let mut rows = table.rows(sink.order());
+        sort_rows_if_present(&mut rows, &columns, sink.sort_keys());
+        return Box::new(rows.into_iter());
+    }
+    table.row_iter_in(sink.order())
+}
+
+/// Write the given tables to `sink`, returning the number of rows
+///
+/// One step is reported to `progress` per table. If the number of tables is
+/// known in advance, it is passed to [`ProgressSink::start`]. If `progress`
+/// asks to stop, [`SinkError::Cancelled`] is returned before the next table
+/// and [`RowSink::finish`] is not called.
+pub fn export_to_sink<'a, I, S, P>(
+    tables: I,
+    mut sink: S,
+    progress: &P,
+) -> Result<u64, SinkError<S::Error>>
+where
+    I: IntoIterator<Item = Result<Table<'a>, CastError>>,
+    S: RowSink,
+    S::Error: std::error::Error + 'static,
+    P: ProgressSink + ?Sized,
+{
+    let tables = tables.into_iter();
+    if let (_, Some(total)) = tables.size_hint() {
+        progress.start("tables", total as u64);
+    }
+    let mut rows = 0;
+    let mut strings = None;
+    for table in tables {
+        if progress.is_cancelled() {
+            return Err(SinkError::Cancelled);
+        }
+        let table = table?;
+        if sink.index_strings() && strings.is_none() {
+            strings = Some(StringIndex::new(table.as_bytes()));
+        }
+        sink.begin_table(&table).map_err(SinkError::Sink)?;
+        for row in sorted_rows(&table, &sink) {
+            let row = match &strings {
+                Some(strings) => row.with_strings(strings),
+                None => row,
+            };
+            sink.push_row(row).map_err(SinkError::Sink)?;
+            rows += 1;
+        }
+        sink.end_table().map_err(SinkError::Sink)?;
+        progress.advance(1);
+    }
+    sink.finish().map_err(SinkError::Sink)?;
+    Ok(rows)
+}
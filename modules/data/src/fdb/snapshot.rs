@@ -0,0 +1,428 @@
+//! # Immutable snapshots of a database, archived with `rkyv`
+//!
+//! Tools that process the same database over and over spend most of their
+//! time decoding it again. A [`Snapshot`] stores the tables, columns and
+//! fields of a [`Schema`] (or directly of a [`mem::Database`]) in the layout
+//! of a [`CompactSchema`], i.e. with flat lists of fields and the row and
+//! bucket boundaries as offsets. It can be written to a file with
+//! [`Snapshot::to_bytes`] and later used in place with [`check_snapshot`],
+//! without parsing or copying anything.
+//!
+//! The bytes passed to [`check_snapshot`] need to be aligned to 16 bytes. This
+//! is the case for an [`AlignedVec`] and for a memory map of a file, but not
+//! necessarily for a `Vec<u8>`.
+//!
+//! This module requires the `fdb-snapshot` feature.
+//!
+//! ```
+//! use assembly_data::fdb::{
+//!     core::{compact::CompactField, Column, Field, Row, Schema, Table, TableDef},
+//!     common::ValueType,
+//!     snapshot::{check_snapshot, Snapshot},
+//! };
+//!
+//! let def = TableDef {
+//!     columns: vec![Column::from(("id", ValueType::Integer))],
+//!     name: String::from("Objects"),
+//! };
+//! let mut table = Table::new(def);
+//! table.buckets_mut().push(Default::default());
+//! table.buckets_mut()[0].rows_mut().push(Row::from(vec![Field::Integer(5)]));
+//! let schema = Schema::from(vec![table]);
+//!
+//! let bytes = Snapshot::from(&schema).to_bytes().unwrap();
+//! let snapshot = check_snapshot(&bytes).unwrap();
+//! let table = snapshot.table("Objects").unwrap();
+//! assert_eq!(table.row(0).unwrap().get(0), Some(CompactField::Integer(5)));
+//! ```
+//!
+//! [`CompactSchema`]: super::core::compact::CompactSchema
+//! [`mem::Database`]: super::mem::Database
+
+use std::{convert::TryFrom, ops::Range};
+
+use assembly_core::displaydoc::Display;
+use rkyv::{AlignedVec, Archive, Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    common::{Value, ValueType},
+    core::{compact::CompactField, Field, Schema},
+};
+#[cfg(feature = "fdb-mem")]
+use super::{mem, mem::Database};
+#[cfg(feature = "fdb-mem")]
+use assembly_core::buffer::CastError;
+
+/// Errors when writing or checking a [`Snapshot`]
+#[derive(Debug, Error, Display)]
+#[non_exhaustive]
+pub enum SnapshotError {
+    /// Failed to serialize the snapshot: {0}
+    Serialize(String),
+    /// The snapshot is invalid: {0}
+    Invalid(String),
+}
+
+/// A field of a [`Snapshot`]
+#[derive(Debug, Clone, PartialEq, Archive, Serialize, Deserialize)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub enum SnapshotField {
+    /// The NULL value
+    Nothing,
+    /// A 32 bit integer
+    Integer(i32),
+    /// A 32 bit IEEE floating point number
+    Float(f32),
+    /// A string
+    Text(String),
+    /// A boolean
+    Boolean(bool),
+    /// A 64 bit integer
+    BigInt(i64),
+    /// A (XML?) string
+    VarChar(String),
+}
+
+impl From<&Field> for SnapshotField {
+    fn from(field: &Field) -> Self {
+        match field {
+            Field::Nothing => Self::Nothing,
+            Field::Integer(v) => Self::Integer(*v),
+            Field::Float(v) => Self::Float(*v),
+            Field::Text(v) => Self::Text(v.clone()),
+            Field::Boolean(v) => Self::Boolean(*v),
+            Field::BigInt(v) => Self::BigInt(*v),
+            Field::VarChar(v) => Self::VarChar(v.clone()),
+        }
+    }
+}
+
+impl<'a> From<&'a ArchivedSnapshotField> for CompactField<'a> {
+    fn from(field: &'a ArchivedSnapshotField) -> Self {
+        match field {
+            ArchivedSnapshotField::Nothing => Value::Nothing,
+            ArchivedSnapshotField::Integer(v) => Value::Integer(*v),
+            ArchivedSnapshotField::Float(v) => Value::Float(*v),
+            ArchivedSnapshotField::Text(v) => Value::Text(v.as_str()),
+            ArchivedSnapshotField::Boolean(v) => Value::Boolean(*v),
+            ArchivedSnapshotField::BigInt(v) => Value::BigInt(*v),
+            ArchivedSnapshotField::VarChar(v) => Value::VarChar(v.as_str()),
+        }
+    }
+}
+
+/// A column of a [`SnapshotTable`]
+#[derive(Debug, Clone, PartialEq, Eq, Archive, Serialize, Deserialize)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct SnapshotColumn {
+    /// The name of the column
+    pub name: String,
+    /// The [`ValueType`] of the column, as stored in the file
+    pub value_type: u8,
+}
+
+impl ArchivedSnapshotColumn {
+    /// Returns the name of the column
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Returns the type of the column, if it is known
+    pub fn value_type(&self) -> Option<ValueType> {
+        ValueType::try_from(u32::from(self.value_type)).ok()
+    }
+}
+
+fn offset(value: usize) -> u32 {
+    u32::try_from(value).expect("table exceeds 2^32 fields")
+}
+
+/// A table of a [`Snapshot`]
+#[derive(Debug, Clone, PartialEq, Archive, Serialize, Deserialize)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct SnapshotTable {
+    name: String,
+    columns: Vec<SnapshotColumn>,
+    /// Index of the first row of each bucket, followed by the row count
+    buckets: Vec<u32>,
+    /// Index of the first field of each row, followed by the field count
+    rows: Vec<u32>,
+    fields: Vec<SnapshotField>,
+}
+
+impl SnapshotTable {
+    fn new(name: String, columns: Vec<SnapshotColumn>) -> Self {
+        Self {
+            name,
+            columns,
+            buckets: Vec::new(),
+            rows: Vec::new(),
+            fields: Vec::new(),
+        }
+    }
+
+    fn push_bucket(&mut self) {
+        self.buckets.push(offset(self.rows.len()));
+    }
+
+    fn push_row<I: IntoIterator<Item = SnapshotField>>(&mut self, fields: I) {
+        self.rows.push(offset(self.fields.len()));
+        self.fields.extend(fields);
+    }
+
+    fn finish(mut self) -> Self {
+        self.buckets.push(offset(self.rows.len()));
+        self.rows.push(offset(self.fields.len()));
+        self
+    }
+
+    /// Returns the name of the table
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl ArchivedSnapshotTable {
+    /// Returns the name of the table
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Returns the columns of the table
+    pub fn columns(&self) -> &[ArchivedSnapshotColumn] {
+        &self.columns[..]
+    }
+
+    /// Returns the number of buckets
+    pub fn bucket_count(&self) -> usize {
+        self.buckets.len().saturating_sub(1)
+    }
+
+    /// Returns the number of rows
+    pub fn row_count(&self) -> usize {
+        self.rows.len().saturating_sub(1)
+    }
+
+    fn range(offsets: &[u32], index: usize) -> Range<usize> {
+        offsets[index] as usize..offsets[index + 1] as usize
+    }
+
+    /// Get the row at `index`, counting over all buckets
+    pub fn row(&self, index: usize) -> Option<SnapshotRow<'_>> {
+        if index < self.row_count() {
+            let fields = self.fields.get(Self::range(&self.rows, index))?;
+            Some(SnapshotRow { fields })
+        } else {
+            None
+        }
+    }
+
+    /// Iterate over all rows in the table
+    pub fn row_iter(&self) -> impl Iterator<Item = SnapshotRow<'_>> {
+        (0..self.row_count()).filter_map(move |i| self.row(i))
+    }
+
+    /// Iterate over the rows of the bucket at `index`
+    pub fn bucket(&self, index: usize) -> impl Iterator<Item = SnapshotRow<'_>> {
+        let range = if index < self.bucket_count() {
+            Self::range(&self.buckets, index)
+        } else {
+            0..0
+        };
+        range.filter_map(move |i| self.row(i))
+    }
+
+    /// Iterate over the rows with the integer primary key `id`
+    ///
+    /// Like in the FDB file, this only looks at the bucket for `id`.
+    pub fn index_iter(&self, id: u32) -> impl Iterator<Item = SnapshotRow<'_>> {
+        let bucket = match self.bucket_count() {
+            0 => 0,
+            count => id as usize % count,
+        };
+        self.bucket(bucket)
+            .filter(move |r| r.get(0) == Some(CompactField::Integer(id as i32)))
+    }
+}
+
+/// A row of an archived [`SnapshotTable`]
+#[derive(Debug, Copy, Clone)]
+pub struct SnapshotRow<'a> {
+    fields: &'a [ArchivedSnapshotField],
+}
+
+impl<'a> SnapshotRow<'a> {
+    /// Returns the number of fields
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns whether the row has no fields
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Get the field at `index`
+    pub fn get(&self, index: usize) -> Option<CompactField<'a>> {
+        self.fields.get(index).map(CompactField::from)
+    }
+
+    /// Iterate over the fields
+    pub fn field_iter(self) -> impl Iterator<Item = CompactField<'a>> {
+        self.fields.iter().map(CompactField::from)
+    }
+}
+
+/// All tables of a database, see the [module documentation](self)
+#[derive(Debug, Clone, Default, PartialEq, Archive, Serialize, Deserialize)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct Snapshot {
+    /// The tables, ordered by name
+    tables: Vec<SnapshotTable>,
+}
+
+impl Snapshot {
+    fn from_tables(mut tables: Vec<SnapshotTable>) -> Self {
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
+        Self { tables }
+    }
+
+    /// Create a snapshot of the tables in a memory-mapped database
+    #[cfg(feature = "fdb-mem")]
+    pub fn from_database(db: Database<'_>) -> Result<Self, CastError> {
+        let mut tables = Vec::new();
+        for table in db.tables()?.iter() {
+            tables.push(snapshot_mem_table(&table?));
+        }
+        Ok(Self::from_tables(tables))
+    }
+
+    /// Returns the tables, ordered by name
+    pub fn tables(&self) -> &[SnapshotTable] {
+        &self.tables
+    }
+
+    /// Serialize the snapshot
+    pub fn to_bytes(&self) -> Result<AlignedVec, SnapshotError> {
+        rkyv::to_bytes::<_, 4096>(self).map_err(|e| SnapshotError::Serialize(e.to_string()))
+    }
+}
+
+#[cfg(feature = "fdb-mem")]
+fn snapshot_mem_table(table: &mem::Table<'_>) -> SnapshotTable {
+    let columns = table
+        .column_iter()
+        .map(|c| SnapshotColumn {
+            name: c.name().into_owned(),
+            value_type: u8::from(c.value_type()),
+        })
+        .collect();
+    let mut snapshot = SnapshotTable::new(table.name().into_owned(), columns);
+    for bucket in table.bucket_iter() {
+        snapshot.push_bucket();
+        for row in bucket.row_iter() {
+            let fields = row.field_iter().map(|f| match f {
+                mem::Field::Text(v) => SnapshotField::Text(v.decode().into_owned()),
+                mem::Field::VarChar(v) => SnapshotField::VarChar(v.decode().into_owned()),
+                mem::Field::Nothing => SnapshotField::Nothing,
+                mem::Field::Integer(v) => SnapshotField::Integer(v),
+                mem::Field::Float(v) => SnapshotField::Float(v),
+                mem::Field::Boolean(v) => SnapshotField::Boolean(v),
+                mem::Field::BigInt(v) => SnapshotField::BigInt(v),
+            });
+            snapshot.push_row(fields);
+        }
+    }
+    snapshot.finish()
+}
+
+impl From<&Schema> for Snapshot {
+    fn from(schema: &Schema) -> Self {
+        let tables = schema.tables.values().map(|table| {
+            let columns = table
+                .columns()
+                .iter()
+                .map(|c| SnapshotColumn {
+                    name: c.name.to_string(),
+                    value_type: u8::from(c.field_type),
+                })
+                .collect();
+            let mut snapshot = SnapshotTable::new(table.name().to_owned(), columns);
+            for bucket in table.buckets() {
+                snapshot.push_bucket();
+                for row in bucket.rows_ref() {
+                    snapshot.push_row(row.fields().iter().map(SnapshotField::from));
+                }
+            }
+            snapshot.finish()
+        });
+        Self::from_tables(tables.collect())
+    }
+}
+
+impl ArchivedSnapshot {
+    /// Returns the number of tables
+    pub fn table_count(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// Get the table with that name, if it exists
+    pub fn table(&self, name: &str) -> Option<&ArchivedSnapshotTable> {
+        let index = self
+            .tables
+            .binary_search_by(|t| t.name.as_str().cmp(name))
+            .ok()?;
+        self.tables.get(index)
+    }
+
+    /// Iterate over all tables, ordered by name
+    pub fn tables(&self) -> impl Iterator<Item = &ArchivedSnapshotTable> {
+        self.tables.iter()
+    }
+}
+
+/// Validate the bytes of a snapshot and return a reference to it
+///
+/// The bytes need to be aligned to 16 bytes, see the
+/// [module documentation](self).
+pub fn check_snapshot(bytes: &[u8]) -> Result<&ArchivedSnapshot, SnapshotError> {
+    rkyv::check_archived_root::<Snapshot>(bytes).map_err(|e| SnapshotError::Invalid(e.to_string()))
+}
+
+#[cfg(all(test, feature = "fdb-mem"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{core, testing::SampleDatabase};
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let columns = [("id", ValueType::Integer), ("name", ValueType::Text)];
+        let db = SampleDatabase::new().table("Objects", &columns).buckets(4);
+        let db = (0..10).fold(db, |db, id| {
+            let name = format!("Brick {}", id);
+            db.row(vec![core::Field::Integer(id), core::Field::Text(name)])
+        });
+        let buf = db.build();
+
+        let bytes = Snapshot::from_database(Database::new(&buf))
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        let snapshot = check_snapshot(&bytes).unwrap();
+        let table = snapshot.table("Objects").unwrap();
+        assert_eq!(table.bucket_count(), 4);
+        assert_eq!(table.row_count(), 10);
+        assert_eq!(table.columns()[1].value_type(), Some(ValueType::Text));
+        let row = table.index_iter(6).next().unwrap();
+        assert_eq!(row.get(1), Some(CompactField::Text("Brick 6")));
+
+        let mut truncated = AlignedVec::new();
+        truncated.extend_from_slice(&bytes[..bytes.len() - 8]);
+        assert!(check_snapshot(&truncated).is_err());
+    }
+}
@@ -0,0 +1,663 @@
+//! # Pluggable origins for imports
+//!
+//! A [`RowSource`] is the counterpart of a [`RowSink`][super::sink::RowSink]:
+//! it yields tables one after the other, each as a [`TableDef`] followed by its
+//! rows. [`import_from_source`] collects them into a [`Schema`], placing every
+//! row in the bucket for its primary key, which can then be written to a file
+//! with the [`store`][super::store] module.
+//!
+//! The adapters in this crate read the formats that the exporters write:
+//!
+//! - [`CsvSource`] reads one table per CSV text, in any [`CsvDialect`]
+//! - [`JsonLinesSource`] reads JSON lines with a `"$table"` key, with the
+//!   `serde-derives` feature
+//! - [`SqliteSource`][super::sqlite::source::SqliteSource] reads the results of
+//!   queries on a SQLite database, with the `sqlite` feature
+//!
+//! CSV and JSON don't store the types of columns, so these adapters pick the
+//! narrowest type that fits all values of a column: `INTEGER`, `BIGINT`,
+//! `FLOAT`, `BOOLEAN` or `TEXT`, or `NOTHING` if all values are `NULL`.
+//!
+//! ```
+//! use assembly_data::fdb::{
+//!     common::{Latin1String, ValueType},
+//!     core::Field,
+//!     csv::CsvDialect,
+//!     source::{import_from_source, CsvSource},
+//!     store,
+//! };
+//!
+//! let source = CsvSource::new(CsvDialect::default())
+//!     .with_table("Objects", "id,name,scale\n1,Brick,0.5\n2,Plate,\n");
+//! let schema = import_from_source(source, &()).unwrap();
+//! let objects = schema.table("Objects").unwrap();
+//! assert_eq!(objects.columns()[2].field_type, ValueType::Float);
+//! assert_eq!(objects.buckets().len(), 2);
+//! assert_eq!(objects.buckets()[1].rows_ref()[0].fields()[1], Field::Text("Brick".into()));
+//!
+//! let mut db = store::Database::new();
+//! db.push_table(Latin1String::encode("Objects"), store::Table::from(objects));
+//! let mut out = Vec::new();
+//! db.write(&mut out).unwrap();
+//! ```
+
+use std::{collections::VecDeque, vec};
+
+use assembly_core::{displaydoc::Display, progress::ProgressSink};
+use thiserror::Error;
+
+use super::{
+    common::ValueType,
+    core::{pk_hash, Bucket, Column, Field, Row, Schema, Table, TableData, TableDef},
+    csv::{CsvDialect, CsvError},
+    float::parse_f32,
+};
+
+/// An origin of the rows for an import, see the [module documentation](self)
+pub trait RowSource {
+    /// The error when reading from the source
+    type Error;
+
+    /// Start the next table, or return `None` if there are no more
+    fn next_table(&mut self) -> Result<Option<TableDef>, Self::Error>;
+
+    /// Get the next row of the current table, or `None` at its end
+    fn next_row(&mut self) -> Result<Option<Row>, Self::Error>;
+}
+
+impl<S: RowSource + ?Sized> RowSource for &mut S {
+    type Error = S::Error;
+
+    fn next_table(&mut self) -> Result<Option<TableDef>, Self::Error> {
+        (**self).next_table()
+    }
+
+    fn next_row(&mut self) -> Result<Option<Row>, Self::Error> {
+        (**self).next_row()
+    }
+}
+
+/// Errors from [`import_from_source`]
+#[derive(Debug, Error, Display)]
+#[non_exhaustive]
+pub enum SourceError<E: std::error::Error + 'static> {
+    /// Failed to read from the source: {0}
+    Source(#[source] E),
+    /// Row {row} of table {table:?} has {fields} fields instead of {columns}
+    FieldCount {
+        /// The name of the table
+        table: String,
+        /// The index of the row
+        row: usize,
+        /// The number of fields in the row
+        fields: usize,
+        /// The number of columns of the table
+        columns: usize,
+    },
+    /// Cannot compute the bucket for the primary key {1} in table {0:?}
+    UnsupportedKey(String, Field),
+    /// Table {0:?} has no columns
+    NoColumns(String),
+    /// Table {0:?} appears again with different columns
+    ColumnMismatch(String),
+    /// The import was cancelled
+    Cancelled,
+}
+
+/// Read all tables from `source` into a [`Schema`]
+///
+/// Every table gets the smallest power of two of buckets that is at least the
+/// number of its rows, and every row is placed in the bucket for the hash of
+/// its first field (see [`pk_hash`]), so tables without columns are rejected.
+/// One step per table is reported to `progress`, which can cancel the import
+/// before the next table.
+///
+/// If the source yields a table with the same name more than once, its rows
+/// are appended to the earlier one. The column names need to match, and so do
+/// the types, except that a column of type `NOTHING` (i.e. only `NULL`s) takes
+/// the type of the other one.
+pub fn import_from_source<S, P>(
+    mut source: S,
+    progress: &P,
+) -> Result<Schema, SourceError<S::Error>>
+where
+    S: RowSource,
+    S::Error: std::error::Error + 'static,
+    P: ProgressSink + ?Sized,
+{
+    let mut tables: Vec<(TableDef, Vec<Row>)> = Vec::new();
+    while let Some(def) = source.next_table().map_err(SourceError::Source)? {
+        if progress.is_cancelled() {
+            return Err(SourceError::Cancelled);
+        }
+        if def.columns.is_empty() {
+            return Err(SourceError::NoColumns(def.name));
+        }
+        let index = match tables.iter().position(|(d, _)| d.name == def.name) {
+            Some(index) => {
+                merge_columns(&mut tables[index].0, def)?;
+                index
+            }
+            None => {
+                tables.push((def, Vec::new()));
+                tables.len() - 1
+            }
+        };
+        let (def, rows) = &mut tables[index];
+        while let Some(row) = source.next_row().map_err(SourceError::Source)? {
+            if row.fields().len() != def.columns.len() {
+                return Err(SourceError::FieldCount {
+                    table: def.name.clone(),
+                    row: rows.len(),
+                    fields: row.fields().len(),
+                    columns: def.columns.len(),
+                });
+            }
+            rows.push(row);
+        }
+        progress.advance(1);
+    }
+
+    let mut schema = Schema::new();
+    for (def, rows) in tables {
+        let count = rows.len().next_power_of_two();
+        let mut buckets: Vec<_> = std::iter::repeat_with(Bucket::new).take(count).collect();
+        for row in rows {
+            let key = &row.fields()[0];
+            let hash = match pk_hash(key) {
+                Some(hash) => hash,
+                None => return Err(SourceError::UnsupportedKey(def.name, key.clone())),
+            };
+            let index = hash as usize % buckets.len();
+            buckets[index].rows_mut().push(row);
+        }
+        schema.insert_table(Table::from(def, TableData { buckets }));
+    }
+    Ok(schema)
+}
+
+/// Check that `def` has the same columns as `into`, see [`import_from_source`]
+fn merge_columns<E>(into: &mut TableDef, def: TableDef) -> Result<(), SourceError<E>>
+where
+    E: std::error::Error + 'static,
+{
+    if into.columns.len() != def.columns.len() {
+        return Err(SourceError::ColumnMismatch(def.name));
+    }
+    for (column, other) in into.columns.iter_mut().zip(&def.columns) {
+        if column.name != other.name {
+            return Err(SourceError::ColumnMismatch(def.name));
+        }
+        match (column.field_type, other.field_type) {
+            (a, b) if a == b => {}
+            (ValueType::Nothing, b) => column.field_type = b,
+            (_, ValueType::Nothing) => {}
+            _ => return Err(SourceError::ColumnMismatch(def.name)),
+        }
+    }
+    Ok(())
+}
+
+/// The rows of a table that was read in advance
+#[derive(Debug, Default)]
+struct Buffered {
+    rows: vec::IntoIter<Row>,
+}
+
+impl Buffered {
+    fn next_row(&mut self) -> Option<Row> {
+        self.rows.next()
+    }
+}
+
+/// Pick the narrowest column type for some text values
+fn infer_text_type<'a, I>(values: I) -> ValueType
+where
+    I: Iterator<Item = &'a str> + Clone,
+{
+    let all = |f: fn(&str) -> bool| values.clone().all(f);
+    if values.clone().next().is_none() {
+        ValueType::Nothing
+    } else if all(|v| v.parse::<i32>().is_ok()) {
+        ValueType::Integer
+    } else if all(|v| v.parse::<i64>().is_ok()) {
+        ValueType::BigInt
+    } else if all(|v| parse_f32(v).is_ok()) {
+        ValueType::Float
+    } else if all(|v| v == "true" || v == "false") {
+        ValueType::Boolean
+    } else {
+        ValueType::Text
+    }
+}
+
+/// Parse a text value as a field of a column of type `value_type`
+fn parse_text_field(value: &str, value_type: ValueType) -> Field {
+    match value_type {
+        ValueType::Integer => value.parse().map_or(Field::Nothing, Field::Integer),
+        ValueType::BigInt => value.parse().map_or(Field::Nothing, Field::BigInt),
+        ValueType::Float => parse_f32(value).map_or(Field::Nothing, Field::Float),
+        ValueType::Boolean => Field::Boolean(value == "true"),
+        _ => Field::Text(value.to_owned()),
+    }
+}
+
+/// A [`RowSource`] for CSV, with one text per table
+///
+/// The first record of every text is the header with the column names, as
+/// written by [`StreamFormat::Csv`][super::stream::StreamFormat::Csv]. Empty
+/// lines are skipped, unless the table has a single column.
+#[derive(Debug)]
+pub struct CsvSource {
+    dialect: CsvDialect,
+    tables: VecDeque<(String, String)>,
+    current: Buffered,
+}
+
+impl CsvSource {
+    /// Create a new source without tables
+    pub fn new(dialect: CsvDialect) -> Self {
+        Self {
+            dialect,
+            tables: VecDeque::new(),
+            current: Buffered::default(),
+        }
+    }
+
+    /// Add a table called `name` with the records in `text`
+    pub fn with_table(mut self, name: &str, text: &str) -> Self {
+        self.tables.push_back((name.to_owned(), text.to_owned()));
+        self
+    }
+}
+
+impl RowSource for CsvSource {
+    type Error = CsvError;
+
+    fn next_table(&mut self) -> Result<Option<TableDef>, CsvError> {
+        let (name, text) = match self.tables.pop_front() {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+        let mut records = self.dialect.read_records(&text)?.into_iter();
+        let header = records.next().unwrap_or_default();
+        let records: Vec<_> = records
+            .filter(|r| {
+                header.len() < 2 || r.len() > 1 || r[0].as_ref().is_some_and(|v| !v.is_empty())
+            })
+            .collect();
+        let mut columns = Vec::with_capacity(header.len());
+        for (index, name) in header.into_iter().enumerate() {
+            let values = records.iter().filter_map(|r| r.get(index)?.as_deref());
+            let name = name.unwrap_or_default();
+            columns.push(Column::from((name.as_str(), infer_text_type(values))));
+        }
+        let rows: Vec<_> = records
+            .into_iter()
+            .map(|record| {
+                let fields = record.iter().enumerate().map(|(index, value)| {
+                    match (value, columns.get(index)) {
+                        (Some(value), Some(column)) => parse_text_field(value, column.field_type),
+                        (Some(value), None) => Field::Text(value.clone()),
+                        (None, _) => Field::Nothing,
+                    }
+                });
+                Row::from(fields.collect::<Vec<_>>())
+            })
+            .collect();
+        self.current.rows = rows.into_iter();
+        Ok(Some(TableDef { columns, name }))
+    }
+
+    fn next_row(&mut self) -> Result<Option<Row>, CsvError> {
+        Ok(self.current.next_row())
+    }
+}
+
+#[cfg(feature = "serde-derives")]
+pub use self::json::{JsonLinesError, JsonLinesSource};
+
+#[cfg(feature = "serde-derives")]
+mod json {
+    use std::{
+        convert::TryFrom,
+        fmt,
+        io::{self, BufRead},
+    };
+
+    use assembly_core::displaydoc::Display;
+    use serde::{
+        de::{MapAccess, Visitor},
+        Deserialize, Deserializer,
+    };
+    use serde_json::Value as JsonValue;
+    use thiserror::Error;
+
+    use super::{Buffered, RowSource};
+    use crate::fdb::{
+        common::ValueType,
+        core::{Column, Field, Row, TableDef},
+    };
+
+    /// The entries of a JSON object, in their original order
+    type Entries = Vec<(String, JsonValue)>;
+
+    /// A JSON object, with the keys in their original order
+    struct Object(Entries);
+
+    impl<'de> Deserialize<'de> for Object {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct ObjectVisitor;
+
+            impl<'de> Visitor<'de> for ObjectVisitor {
+                type Value = Object;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a JSON object")
+                }
+
+                fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Object, A::Error> {
+                    let mut entries = Vec::new();
+                    while let Some(entry) = map.next_entry()? {
+                        entries.push(entry);
+                    }
+                    Ok(Object(entries))
+                }
+            }
+
+            deserializer.deserialize_map(ObjectVisitor)
+        }
+    }
+
+    /// Errors from [`JsonLinesSource`]
+    #[derive(Debug, Error, Display)]
+    #[non_exhaustive]
+    pub enum JsonLinesError {
+        /// Failed to read the input: {0}
+        Io(#[from] io::Error),
+        /// Invalid JSON in line {0}: {1}
+        Json(usize, #[source] serde_json::Error),
+        /// Line {0} has no `"$table"` key with a string
+        MissingTable(usize),
+    }
+
+    /// Pick the narrowest column type for some JSON values
+    fn infer_json_type<'a, I>(values: I) -> ValueType
+    where
+        I: IntoIterator<Item = &'a JsonValue>,
+    {
+        let mut value_type = ValueType::Nothing;
+        for value in values {
+            let this = match value {
+                JsonValue::Null => continue,
+                JsonValue::Bool(_) => ValueType::Boolean,
+                JsonValue::Number(n) => match n.as_i64() {
+                    Some(i) if i32::try_from(i).is_ok() => ValueType::Integer,
+                    Some(_) => ValueType::BigInt,
+                    None => ValueType::Float,
+                },
+                _ => ValueType::Text,
+            };
+            value_type = match (value_type, this) {
+                (ValueType::Nothing, this) => this,
+                (a, b) if a == b => a,
+                (ValueType::Integer, ValueType::BigInt)
+                | (ValueType::BigInt, ValueType::Integer) => ValueType::BigInt,
+                (ValueType::Integer, ValueType::Float)
+                | (ValueType::Float, ValueType::Integer)
+                | (ValueType::BigInt, ValueType::Float)
+                | (ValueType::Float, ValueType::BigInt) => ValueType::Float,
+                _ => ValueType::Text,
+            };
+        }
+        value_type
+    }
+
+    fn json_field(value: &JsonValue, value_type: ValueType) -> Field {
+        match (value, value_type) {
+            (JsonValue::Null, _) => Field::Nothing,
+            (JsonValue::Bool(b), ValueType::Boolean) => Field::Boolean(*b),
+            (JsonValue::Number(n), ValueType::Integer) => n
+                .as_i64()
+                .map_or(Field::Nothing, |i| Field::Integer(i as i32)),
+            (JsonValue::Number(n), ValueType::BigInt) => {
+                n.as_i64().map_or(Field::Nothing, Field::BigInt)
+            }
+            (JsonValue::Number(n), ValueType::Float) => n
+                .as_f64()
+                .map_or(Field::Nothing, |f| Field::Float(f as f32)),
+            (JsonValue::String(s), _) => Field::Text(s.clone()),
+            (value, _) => Field::Text(value.to_string()),
+        }
+    }
+
+    /// A [`RowSource`] for JSON lines
+    ///
+    /// Every line is an object with the name of the table in `"$table"` and
+    /// one key per column, as written by
+    /// [`StreamFormat::JsonLines`][crate::fdb::stream::StreamFormat::JsonLines].
+    /// Consecutive lines with the same table form a table, with the columns
+    /// of its first line, and [`import_from_source`](super::import_from_source)
+    /// appends the rows of later runs of lines for that table. Empty lines are
+    /// skipped.
+    pub struct JsonLinesSource<R> {
+        lines: io::Lines<R>,
+        line: usize,
+        pending: Option<(String, Entries)>,
+        current: Buffered,
+    }
+
+    impl<R: BufRead> JsonLinesSource<R> {
+        /// Create a new source reading from `reader`
+        pub fn new(reader: R) -> Self {
+            Self {
+                lines: reader.lines(),
+                line: 0,
+                pending: None,
+                current: Buffered::default(),
+            }
+        }
+
+        fn read_object(&mut self) -> Result<Option<(String, Entries)>, JsonLinesError> {
+            if let Some(object) = self.pending.take() {
+                return Ok(Some(object));
+            }
+            for line in &mut self.lines {
+                let line = line?;
+                self.line += 1;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let Object(mut entries) =
+                    serde_json::from_str(&line).map_err(|e| JsonLinesError::Json(self.line, e))?;
+                let index = entries
+                    .iter()
+                    .position(|(k, v)| k == "$table" && v.is_string());
+                let table = match index.map(|i| entries.remove(i).1) {
+                    Some(JsonValue::String(table)) => table,
+                    _ => return Err(JsonLinesError::MissingTable(self.line)),
+                };
+                return Ok(Some((table, entries)));
+            }
+            Ok(None)
+        }
+    }
+
+    impl<R: BufRead> RowSource for JsonLinesSource<R> {
+        type Error = JsonLinesError;
+
+        fn next_table(&mut self) -> Result<Option<TableDef>, JsonLinesError> {
+            let (name, first) = match self.read_object()? {
+                Some(object) => object,
+                None => return Ok(None),
+            };
+            let names: Vec<_> = first.iter().map(|(k, _)| k.clone()).collect();
+            let mut objects = vec![first];
+            while let Some((table, entries)) = self.read_object()? {
+                if table != name {
+                    self.pending = Some((table, entries));
+                    break;
+                }
+                objects.push(entries);
+            }
+
+            let value = |object: &[(String, JsonValue)], name: &str| -> Option<JsonValue> {
+                object
+                    .iter()
+                    .find(|(k, _)| k == name)
+                    .map(|(_, v)| v.clone())
+            };
+            let values: Vec<Vec<JsonValue>> = objects
+                .iter()
+                .map(|object| {
+                    let values = names
+                        .iter()
+                        .map(|n| value(object, n).unwrap_or(JsonValue::Null));
+                    values.collect()
+                })
+                .collect();
+            let columns: Vec<_> = names
+                .iter()
+                .enumerate()
+                .map(|(index, name)| {
+                    let value_type = infer_json_type(values.iter().map(|v| &v[index]));
+                    Column::from((name.as_str(), value_type))
+                })
+                .collect();
+            let rows: Vec<_> = values
+                .iter()
+                .map(|values| {
+                    let fields = values.iter().zip(&columns);
+                    let fields = fields.map(|(v, c)| json_field(v, c.field_type));
+                    Row::from(fields.collect::<Vec<_>>())
+                })
+                .collect();
+            self.current.rows = rows.into_iter();
+            Ok(Some(TableDef { columns, name }))
+        }
+
+        fn next_row(&mut self) -> Result<Option<Row>, JsonLinesError> {
+            Ok(self.current.next_row())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_source() {
+        let text = "id;name;big;flag\r\n2;\"a;b\";4294967296;true\r\n1;;1;false\r\n\r\n";
+        let dialect = CsvDialect {
+            delimiter: ';',
+            ..CsvDialect::rfc4180()
+        };
+        let source = CsvSource::new(dialect).with_table("Things", text);
+        let schema = import_from_source(source, &()).unwrap();
+        let table = schema.table("Things").unwrap();
+        let types: Vec<_> = table.columns().iter().map(|c| c.field_type).collect();
+        let expected = [
+            ValueType::Integer,
+            ValueType::Text,
+            ValueType::BigInt,
+            ValueType::Boolean,
+        ];
+        assert_eq!(types, expected);
+        assert_eq!(table.buckets().len(), 2);
+        let row = &table.buckets()[0].rows_ref()[0];
+        assert_eq!(
+            row.fields(),
+            &[
+                Field::Integer(2),
+                Field::Text("a;b".into()),
+                Field::BigInt(1 << 32),
+                Field::Boolean(true)
+            ]
+        );
+        assert_eq!(table.buckets()[1].rows_ref()[0].fields()[1], Field::Nothing);
+
+        let bad = CsvSource::new(CsvDialect::default()).with_table("Bad", "id,name\n1\n");
+        assert!(matches!(
+            import_from_source(bad, &()),
+            Err(SourceError::FieldCount {
+                row: 0,
+                fields: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "fdb-mem", feature = "serde-derives"))]
+    fn test_json_lines_round_trip() {
+        use crate::fdb::{
+            mem::Database,
+            query::sort::{sort_rows, Order},
+            stream::{export_streaming, StreamFormat},
+        };
+
+        let buf = crate::fdb::testing::objects(20);
+        let mut jsonl = Vec::new();
+        export_streaming(Database::new(&buf), StreamFormat::JsonLines, &mut jsonl).unwrap();
+        let imported = import_from_source(JsonLinesSource::new(&jsonl[..]), &()).unwrap();
+        let original = Schema::from_source(&buf[..]).unwrap();
+        for schema in [&imported, &original].iter() {
+            let table = schema.table("Objects").unwrap();
+            let columns = table.columns().iter();
+            let columns: Vec<_> = columns.map(|c| (&*c.name, c.field_type)).collect();
+            assert_eq!(columns[3], ("enabled", ValueType::Boolean));
+        }
+        let rows = |schema: &Schema| {
+            let table = schema.table("Objects").unwrap();
+            let mut rows: Vec<Row> = table.into_iter().cloned().collect();
+            sort_rows(&mut rows, &["id"], &[("id", Order::Asc)]).unwrap();
+            rows
+        };
+        assert_eq!(rows(&imported), rows(&original));
+    }
+
+    #[test]
+    #[cfg(feature = "serde-derives")]
+    fn test_json_lines_no_columns() {
+        let source = JsonLinesSource::new(&b"{\"$table\":\"T\"}\n"[..]);
+        let result = import_from_source(source, &());
+        assert!(matches!(result, Err(SourceError::NoColumns(t)) if t == "T"));
+    }
+
+    #[test]
+    fn test_duplicate_tables() {
+        let source = CsvSource::new(CsvDialect::default())
+            .with_table("Objects", "id,name\n1,\n2,\n")
+            .with_table("Other", "id\n7\n")
+            .with_table("Objects", "id,name\n3,Brick\n");
+        let schema = import_from_source(source, &()).unwrap();
+        let objects = schema.table("Objects").unwrap();
+        assert_eq!(objects.columns()[1].field_type, ValueType::Text);
+        let mut ids: Vec<_> = objects.into_iter().map(|r| r.fields()[0].clone()).collect();
+        ids.sort_by_key(|f| f.to_string());
+        assert_eq!(
+            ids,
+            [1, 2, 3]
+                .iter()
+                .map(|&i| Field::Integer(i))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(objects.buckets().len(), 4);
+
+        let mismatch = CsvSource::new(CsvDialect::default())
+            .with_table("Objects", "id,name\n1,a\n")
+            .with_table("Objects", "id,name\n2,3\n");
+        let result = import_from_source(mismatch, &());
+        assert!(matches!(result, Err(SourceError::ColumnMismatch(t)) if t == "Objects"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde-derives")]
+    fn test_json_lines_interleaved() {
+        let text = "{\"$table\":\"A\",\"id\":1}\n{\"$table\":\"B\",\"id\":2}\n{\"$table\":\"A\",\"id\":3}\n";
+        let schema = import_from_source(JsonLinesSource::new(text.as_bytes()), &()).unwrap();
+        assert_eq!(schema.table("A").unwrap().into_iter().count(), 2);
+        assert_eq!(schema.table("B").unwrap().into_iter().count(), 1);
+    }
+}
@@ -0,0 +1,639 @@
+//! # SQLite conversions and tooling
+
+use std::{
+    borrow::Cow,
+    fmt::Write,
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering},
+        mpsc,
+    },
+    thread,
+};
+
+use assembly_core::{buffer::CastError, progress::ProgressSink};
+use rusqlite::{
+    ffi,
+    types::{ToSqlOutput, Value},
+    ToSql,
+};
+pub use rusqlite::{Connection, Error, Result};
+
+#[cfg(feature = "fdb-core")]
+pub mod source;
+#[cfg(feature = "fdb-core")]
+pub mod sync;
+
+use super::{
+    common::{IterOrder, ValueType},
+    encoding::audit_table,
+    float::{format_f32, widen, FloatFormat},
+    infer::column_stats,
+    mem::{strings::StringIndex, Database, Field, Row, Table},
+    sink::{export_to_sink, RowSink, SinkError},
+};
+
+fn sql_value(field: &Field<'_>, floats: FloatFormat) -> Value {
+    match *field {
+        Field::Nothing => Value::Null,
+        Field::Integer(i) => Value::Integer(i.into()),
+        Field::Float(f) => match floats {
+            FloatFormat::Shortest => Value::Real(widen(f)),
+            FloatFormat::HexBits => Value::Text(format_f32(f, floats)),
+        },
+        Field::Text(s) => Value::Text(s.decode().into_owned()),
+        Field::Boolean(b) => Value::Integer(if b { 1 } else { 0 }),
+        Field::BigInt(i) => Value::Integer(i),
+        Field::VarChar(b) => Value::Text(b.decode().into_owned()),
+    }
+}
+
+impl<'a> ToSql for Field<'a> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::Owned(sql_value(self, FloatFormat::Shortest)))
+    }
+}
+
+/// Options for [`try_export_db_with_options`]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ExportOptions {
+    /// Write the fields of `VARCHAR` columns that only contain base64 (see
+    /// [`ColumnEncoding::is_base64`]) as a decoded `BLOB`
+    ///
+    /// [`ColumnEncoding::is_base64`]: super::encoding::ColumnEncoding::is_base64
+    pub base64_as_blob: bool,
+    /// Use the narrowest type for every column that fits its values, e.g.
+    /// `SMALLINT` or `VARCHAR(16)`, see [`ColumnStats::inferred`]
+    ///
+    /// [`ColumnStats::inferred`]: super::infer::ColumnStats::inferred
+    pub infer_types: bool,
+    /// Add `NOT NULL` to the columns that don't contain any `NULL`
+    pub constraints: bool,
+    /// How to write `FLOAT` fields
+    ///
+    /// With [`FloatFormat::HexBits`], the fields are stored as `TEXT`, even
+    /// though the column is declared as `REAL`.
+    pub floats: FloatFormat,
+    /// Commit after this many rows instead of once at the end, if not `0`
+    ///
+    /// This keeps the journal small, but a failed export leaves the tables
+    /// partially filled.
+    pub batch_size: usize,
+    /// Switch the database to write-ahead logging, which is faster on disk
+    pub wal: bool,
+    /// The order in which the rows of each table are inserted
+    ///
+    /// With [`IterOrder::PrimaryKey`], the rows of a table get the same
+    /// `rowid`s for every file with the same rows.
+    pub order: IterOrder,
+    /// The number of threads to write the tables with, if more than `1`
+    ///
+    /// The tables are split between the threads by their number of rows. Each
+    /// thread writes its tables into a separate SQLite file in a new directory
+    /// in the temporary folder, which are then attached to `conn` one by one and copied with
+    /// `INSERT INTO … SELECT`. The tables are created in a first transaction,
+    /// and each file is copied in a transaction of its own.
+    pub threads: usize,
+    /// Look up strings in a [`StringIndex`] that is built once for the file,
+    /// see [`RowSink::index_strings`]
+    pub index_strings: bool,
+}
+
+/// Try to export a database to a SQL connection
+///
+/// This function does the following:
+///
+/// 1. `BEGIN`s a transaction
+/// 2. For every table:
+///   a. Run `CREATE TABLE IF NOT EXISTS`
+///   b. Prepares an `INSERT` statement
+///   c. Runs the insert with data from every row
+/// 3. `COMMIT`s the transaction
+pub fn try_export_db(conn: &mut Connection, db: Database) -> rusqlite::Result<()> {
+    try_export_db_with_progress(conn, db, &())
+}
+
+/// Like [`try_export_db`], but reports one step per table to `progress`
+///
+/// If `progress` asks to stop, the transaction is rolled back and an
+/// `SQLITE_INTERRUPT` error is returned.
+pub fn try_export_db_with_progress<P>(
+    conn: &mut Connection,
+    db: Database,
+    progress: &P,
+) -> rusqlite::Result<()>
+where
+    P: ProgressSink + ?Sized,
+{
+    try_export_db_with_options(conn, db, &ExportOptions::default(), progress)
+}
+
+/// Like [`try_export_db_with_progress`], with some [`ExportOptions`]
+///
+/// With [`ExportOptions::threads`], the tables are written in parallel, see
+/// [`ExportOptions`] for how this works.
+pub fn try_export_db_with_options<P>(
+    conn: &mut Connection,
+    db: Database,
+    options: &ExportOptions,
+    progress: &P,
+) -> rusqlite::Result<()>
+where
+    P: ProgressSink + ?Sized,
+{
+    if options.wal {
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")?;
+    }
+    if options.threads > 1 {
+        return export_parallel(conn, db, options, progress);
+    }
+
+    let tables = db.tables().map_err(cast_error)?;
+    let sink = SqliteSink::new(conn, *options)?;
+    match export_to_sink(tables.iter(), sink, progress) {
+        Ok(_) => Ok(()),
+        Err(SinkError::Sink(e)) => Err(e),
+        Err(SinkError::Cast(e)) => Err(cast_error(e)),
+        Err(SinkError::Cancelled) => {
+            conn.execute("ROLLBACK", rusqlite::params![])?;
+            Err(cancelled())
+        }
+    }
+}
+
+fn cast_error(e: CastError) -> Error {
+    Error::ToSqlConversionFailure(Box::new(e))
+}
+
+fn cancelled() -> Error {
+    Error::SqliteFailure(
+        ffi::Error::new(ffi::SQLITE_INTERRUPT),
+        Some(String::from("Export was cancelled")),
+    )
+}
+
+/// The queries for one table
+struct TablePlan {
+    name: String,
+    create_query: String,
+    insert_query: String,
+    blobs: Vec<bool>,
+}
+
+impl TablePlan {
+    fn new(table: &Table<'_>, options: &ExportOptions) -> Self {
+        let mut blobs = vec![false; table.column_count()];
+        if options.base64_as_blob {
+            let encoding = audit_table(table);
+            for (index, column) in table.column_iter().enumerate() {
+                let name = column.name();
+                blobs[index] = column.value_type() == ValueType::VarChar
+                    && encoding
+                        .columns
+                        .iter()
+                        .any(|c| c.name == name && c.is_base64());
+            }
+        }
+        let stats = if options.infer_types || options.constraints {
+            column_stats(table)
+        } else {
+            Vec::new()
+        };
+
+        let mut create_query = format!("CREATE TABLE IF NOT EXISTS \"{}\"\n(\n", table.name());
+        let mut insert_query = format!("INSERT INTO \"{}\" (", table.name());
+        let mut first = true;
+        for (index, col) in table.column_iter().enumerate() {
+            if first {
+                first = false;
+            } else {
+                writeln!(create_query, ",").unwrap();
+                write!(insert_query, ", ").unwrap();
+            }
+            let typ = match col.value_type() {
+                ValueType::Nothing => "NULL",
+                ValueType::Integer => "INTEGER",
+                ValueType::Float => "REAL",
+                ValueType::Text => "TEXT",
+                ValueType::Boolean => "INTEGER",
+                ValueType::BigInt => "INTEGER",
+                ValueType::VarChar => "BLOB",
+            };
+            let typ = match stats.get(index) {
+                Some(stats) if options.infer_types && !blobs[index] => stats.inferred().sql_name(),
+                _ => Cow::Borrowed(typ),
+            };
+            write!(create_query, "    [{}] {}", col.name(), typ).unwrap();
+            if options.constraints && stats.get(index).is_some_and(|s| !s.is_nullable()) {
+                create_query.push_str(" NOT NULL");
+            }
+            write!(insert_query, "[{}]", col.name()).unwrap();
+        }
+        create_query.push_str(");");
+        insert_query.push_str(") VALUES (?1");
+        for i in 2..=table.column_count() {
+            write!(insert_query, ", ?{}", i).unwrap();
+        }
+        insert_query.push_str(");");
+        Self {
+            name: table.name().into_owned(),
+            create_query,
+            insert_query,
+            blobs,
+        }
+    }
+}
+
+/// Insert a row of the table of `plan`, in the open transaction of `conn`
+///
+/// `pending` counts the rows since the last `COMMIT`, for [`ExportOptions::batch_size`].
+fn insert_row(
+    conn: &Connection,
+    row: Row<'_>,
+    plan: &TablePlan,
+    options: &ExportOptions,
+    pending: &mut usize,
+) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare_cached(&plan.insert_query)?;
+    if !plan.blobs.contains(&true) && options.floats == FloatFormat::Shortest {
+        stmt.execute(row)?;
+    } else {
+        let values = row.field_iter().zip(&plan.blobs).map(|(field, blob)| {
+            match field.decode_varchar_base64() {
+                Ok(bytes) if *blob => Value::Blob(bytes),
+                _ => sql_value(&field, options.floats),
+            }
+        });
+        stmt.execute(values)?;
+    }
+    *pending += 1;
+    if options.batch_size > 0 && *pending >= options.batch_size {
+        conn.execute_batch("COMMIT; BEGIN;")?;
+        *pending = 0;
+    }
+    Ok(())
+}
+
+/// Insert all rows of `table`, in the open transaction of `conn`
+fn insert_rows(
+    conn: &Connection,
+    table: &Table<'_>,
+    plan: &TablePlan,
+    options: &ExportOptions,
+    strings: Option<&StringIndex<'_>>,
+    pending: &mut usize,
+) -> rusqlite::Result<()> {
+    for row in table.row_iter_in(options.order) {
+        let row = match strings {
+            Some(strings) => row.with_strings(strings),
+            None => row,
+        };
+        insert_row(conn, row, plan, options, pending)?;
+    }
+    Ok(())
+}
+
+/// A [`RowSink`] that writes to a SQLite connection
+///
+/// The sink starts a transaction when it is created, which is committed by
+/// [`RowSink::finish`]. All [`ExportOptions`] apply, except for
+/// [`ExportOptions::wal`] and [`ExportOptions::threads`].
+pub struct SqliteSink<'c> {
+    conn: &'c Connection,
+    options: ExportOptions,
+    plan: Option<TablePlan>,
+    pending: usize,
+}
+
+impl<'c> SqliteSink<'c> {
+    /// Create a new sink and `BEGIN` a transaction on `conn`
+    pub fn new(conn: &'c Connection, options: ExportOptions) -> rusqlite::Result<Self> {
+        conn.execute("BEGIN", rusqlite::params![])?;
+        Ok(Self {
+            conn,
+            options,
+            plan: None,
+            pending: 0,
+        })
+    }
+}
+
+impl RowSink for SqliteSink<'_> {
+    type Error = Error;
+
+    fn begin_table(&mut self, table: &Table<'_>) -> rusqlite::Result<()> {
+        let plan = TablePlan::new(table, &self.options);
+        self.conn.execute(&plan.create_query, rusqlite::params![])?;
+        self.plan = Some(plan);
+        Ok(())
+    }
+
+    fn push_row(&mut self, row: Row<'_>) -> rusqlite::Result<()> {
+        let plan = self.plan.as_ref().expect("row outside of a table");
+        insert_row(self.conn, row, plan, &self.options, &mut self.pending)
+    }
+
+    fn end_table(&mut self) -> rusqlite::Result<()> {
+        self.plan = None;
+        Ok(())
+    }
+
+    fn order(&self) -> IterOrder {
+        self.options.order
+    }
+
+    fn index_strings(&self) -> bool {
+        self.options.index_strings
+    }
+
+    fn finish(&mut self) -> rusqlite::Result<()> {
+        self.conn.execute("COMMIT", rusqlite::params![])?;
+        Ok(())
+    }
+}
+
+/// Split the tables into `count` lists with about the same number of rows
+fn partition(tables: &[Table<'_>], count: usize) -> Vec<Vec<usize>> {
+    let mut order: Vec<_> = tables
+        .iter()
+        .enumerate()
+        .map(|(index, table)| (table.row_iter().count(), index))
+        .collect();
+    order.sort_unstable_by(|a, b| b.cmp(a));
+    let mut parts = vec![(0, Vec::new()); count];
+    for (rows, index) in order {
+        let part = parts.iter_mut().min_by_key(|(total, _)| *total).unwrap();
+        part.0 += rows;
+        part.1.push(index);
+    }
+    parts.into_iter().map(|(_, indices)| indices).collect()
+}
+
+fn export_parallel<P>(
+    conn: &mut Connection,
+    db: Database,
+    options: &ExportOptions,
+    progress: &P,
+) -> rusqlite::Result<()>
+where
+    P: ProgressSink + ?Sized,
+{
+    let tables = db.tables().map_err(cast_error)?.iter();
+    let tables = tables.collect::<std::result::Result<Vec<_>, _>>();
+    let tables = tables.map_err(cast_error)?;
+    progress.start("tables", tables.len() as u64);
+    let plans: Vec<_> = tables.iter().map(|t| TablePlan::new(t, options)).collect();
+    let strings = options
+        .index_strings
+        .then(|| StringIndex::new(db.as_bytes()));
+
+    conn.execute("BEGIN", rusqlite::params![])?;
+    for plan in &plans {
+        conn.execute(&plan.create_query, rusqlite::params![])?;
+    }
+    conn.execute("COMMIT", rusqlite::params![])?;
+
+    let parts = partition(&tables, options.threads.min(tables.len()).max(1));
+    let dir = temp_dir().map_err(io_error)?;
+    let paths: Vec<_> = (0..parts.len())
+        .map(|n| dir.join(format!("part-{}.sqlite", n)))
+        .collect();
+
+    let cancel = AtomicBool::new(false);
+    let (done, rx) = mpsc::channel();
+    let result = thread::scope(|scope| {
+        let workers: Vec<_> = parts
+            .iter()
+            .zip(&paths)
+            .map(|(indices, path)| {
+                let (done, cancel, tables, plans) = (done.clone(), &cancel, &tables, &plans);
+                let strings = strings.as_ref();
+                scope.spawn(move || -> rusqlite::Result<()> {
+                    let part = Connection::open(path)?;
+                    part.execute_batch(
+                        "PRAGMA journal_mode = OFF; PRAGMA synchronous = OFF; BEGIN;",
+                    )?;
+                    let options = ExportOptions {
+                        batch_size: 0,
+                        ..*options
+                    };
+                    for &index in indices {
+                        if cancel.load(AtomicOrdering::Relaxed) {
+                            return Err(cancelled());
+                        }
+                        part.execute(&plans[index].create_query, rusqlite::params![])?;
+                        let (table, plan) = (&tables[index], &plans[index]);
+                        insert_rows(&part, table, plan, &options, strings, &mut 0)?;
+                        let _ = done.send(());
+                    }
+                    part.execute_batch("COMMIT;")
+                })
+            })
+            .collect();
+        drop(done);
+        for () in rx {
+            progress.advance(1);
+            if progress.is_cancelled() {
+                cancel.store(true, AtomicOrdering::Relaxed);
+            }
+        }
+        workers
+            .into_iter()
+            .map(|w| w.join().expect("export thread panicked"))
+            .collect::<rusqlite::Result<Vec<()>>>()
+    });
+
+    let result = result.and_then(|_| {
+        for (indices, path) in parts.iter().zip(&paths) {
+            let path = path.to_string_lossy();
+            conn.execute("ATTACH DATABASE ?1 AS part", rusqlite::params![path])?;
+            conn.execute("BEGIN", rusqlite::params![])?;
+            for &index in indices {
+                let name = &plans[index].name;
+                let copy = format!("INSERT INTO main.\"{0}\" SELECT * FROM part.\"{0}\";", name);
+                conn.execute(&copy, rusqlite::params![])?;
+            }
+            conn.execute("COMMIT", rusqlite::params![])?;
+            conn.execute("DETACH DATABASE part", rusqlite::params![])?;
+        }
+        Ok(())
+    });
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
+fn io_error(e: std::io::Error) -> Error {
+    Error::ToSqlConversionFailure(Box::new(e))
+}
+
+/// Create a new directory for the parts of one parallel export
+fn temp_dir() -> std::io::Result<PathBuf> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    loop {
+        let n = NEXT.fetch_add(1, AtomicOrdering::Relaxed);
+        let name = format!("assembly-export-{}-{}", std::process::id(), n);
+        let dir = std::env::temp_dir().join(name);
+        match fs::create_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{core, testing::SampleDatabase};
+
+    #[test]
+    fn test_export_base64_as_blob() {
+        let columns = [("id", ValueType::Integer), ("data", ValueType::VarChar)];
+        let data = core::Field::encode_varchar_base64(b"binary payload");
+        let buf = SampleDatabase::new()
+            .table("Payloads", &columns)
+            .row(vec![core::Field::Integer(1), data])
+            .build();
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        let options = ExportOptions {
+            base64_as_blob: true,
+            ..ExportOptions::default()
+        };
+        try_export_db_with_options(&mut conn, Database::new(&buf), &options, &()).unwrap();
+        let blob: Vec<u8> = conn
+            .query_row("SELECT data FROM Payloads", rusqlite::params![], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(blob, b"binary payload");
+    }
+
+    #[test]
+    fn test_export_infer_types() {
+        let buf = crate::fdb::testing::objects(10);
+        let mut conn = Connection::open_in_memory().unwrap();
+        let options = ExportOptions {
+            infer_types: true,
+            constraints: true,
+            ..ExportOptions::default()
+        };
+        try_export_db_with_options(&mut conn, Database::new(&buf), &options, &()).unwrap();
+        let sql: String = conn
+            .query_row(
+                "SELECT sql FROM sqlite_master WHERE name = 'Objects'",
+                rusqlite::params![],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(sql.contains("[id] SMALLINT NOT NULL"), "{}", sql);
+        assert!(sql.contains("[name] VARCHAR(6) NOT NULL"), "{}", sql);
+    }
+
+    #[test]
+    fn test_export_order() {
+        let rowids = |buckets, threads| {
+            let db = SampleDatabase::new().table("Objects", &[("id", ValueType::Integer)]);
+            let buf = db.buckets(buckets).rows(20).build();
+            let mut conn = Connection::open_in_memory().unwrap();
+            let options = ExportOptions {
+                order: IterOrder::PrimaryKey,
+                threads,
+                ..ExportOptions::default()
+            };
+            try_export_db_with_options(&mut conn, Database::new(&buf), &options, &()).unwrap();
+            let mut stmt = conn
+                .prepare("SELECT id FROM Objects ORDER BY rowid")
+                .unwrap();
+            let ids = stmt
+                .query_map(rusqlite::params![], |row| row.get(0))
+                .unwrap();
+            ids.collect::<rusqlite::Result<Vec<i32>>>().unwrap()
+        };
+        let expected: Vec<i32> = (0..20).collect();
+        assert_eq!(rowids(4, 1), expected);
+        assert_eq!(rowids(32, 1), expected);
+        assert_eq!(rowids(4, 2), expected);
+    }
+
+    #[test]
+    fn test_export_cancelled() {
+        /// Cancels the export after the first table
+        struct CancelAfterOne(AtomicUsize);
+
+        impl ProgressSink for CancelAfterOne {
+            fn advance(&self, steps: u64) {
+                self.0.fetch_add(steps as usize, AtomicOrdering::Relaxed);
+            }
+
+            fn is_cancelled(&self) -> bool {
+                self.0.load(AtomicOrdering::Relaxed) > 0
+            }
+        }
+
+        let buf = SampleDatabase::new()
+            .table("Objects", &[("id", ValueType::Integer)])
+            .rows(5)
+            .table("Icons", &[("id", ValueType::Integer)])
+            .rows(5)
+            .build();
+        let mut conn = Connection::open_in_memory().unwrap();
+        let progress = CancelAfterOne(AtomicUsize::new(0));
+        let result = try_export_db_with_progress(&mut conn, Database::new(&buf), &progress);
+        assert!(result.is_err());
+        assert_eq!(progress.0.load(AtomicOrdering::Relaxed), 1);
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'",
+                rusqlite::params![],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_export_parallel() {
+        let mut sample = SampleDatabase::new();
+        for (index, name) in ["Objects", "Missions", "Icons"].iter().enumerate() {
+            sample = sample
+                .table(
+                    name,
+                    &[("id", ValueType::Integer), ("scale", ValueType::Float)],
+                )
+                .rows(10 * (index + 1));
+        }
+        let buf = sample.build();
+        let options = ExportOptions {
+            threads: 2,
+            batch_size: 7,
+            ..ExportOptions::default()
+        };
+        // two exports at the same time must not share their temporary files
+        let conns: Vec<_> = thread::scope(|scope| {
+            let export = || {
+                let mut conn = Connection::open_in_memory().unwrap();
+                try_export_db_with_options(&mut conn, Database::new(&buf), &options, &()).unwrap();
+                conn
+            };
+            let workers: Vec<_> = (0..2).map(|_| scope.spawn(export)).collect();
+            workers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+        for conn in &conns {
+            for (name, rows) in &[("Objects", 10), ("Missions", 20), ("Icons", 30)] {
+                let query = format!("SELECT COUNT(*), SUM(id) FROM {}", name);
+                let (count, sum): (i64, i64) = conn
+                    .query_row(&query, rusqlite::params![], |row| {
+                        Ok((row.get(0)?, row.get(1)?))
+                    })
+                    .unwrap();
+                assert_eq!((count, sum), (*rows, rows * (rows - 1) / 2), "{}", name);
+            }
+        }
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        let broken = Database::new(&buf[..12]);
+        assert!(try_export_db_with_options(&mut conn, broken, &options, &()).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+//! # Reading tables back from SQLite
+//!
+//! [`SqliteSource`] is a [`RowSource`] that yields the results of queries on a
+//! SQLite connection as tables, e.g. to turn an export that was edited in
+//! SQLite back into a database file with
+//! [`import_from_source`][crate::fdb::source::import_from_source].
+
+use std::{collections::VecDeque, convert::TryFrom, vec};
+
+use rusqlite::{types::Value, Connection};
+
+use crate::fdb::{
+    common::ValueType,
+    core::{Column, Field, Row, TableDef},
+    float::parse_f32,
+    source::RowSource,
+};
+
+/// Map the declared type of a column to a [`ValueType`]
+///
+/// This understands the types written by [`try_export_db`][super::try_export_db],
+/// including the inferred ones such as `SMALLINT` or `VARCHAR(16)`.
+fn decl_value_type(decl: &str) -> Option<ValueType> {
+    let decl = decl.to_ascii_uppercase();
+    let value_type = if decl == "NULL" {
+        ValueType::Nothing
+    } else if decl == "BIGINT" {
+        ValueType::BigInt
+    } else if decl.contains("BOOL") {
+        ValueType::Boolean
+    } else if decl.contains("INT") {
+        ValueType::Integer
+    } else if decl.contains("CHAR") || decl.contains("CLOB") || decl.contains("TEXT") {
+        ValueType::Text
+    } else if decl.contains("BLOB") {
+        ValueType::VarChar
+    } else if decl.contains("REAL") || decl.contains("FLOA") || decl.contains("DOUB") {
+        ValueType::Float
+    } else {
+        return None;
+    };
+    Some(value_type)
+}
+
+/// Pick a [`ValueType`] for a column without a declared type
+fn infer_value_type<'a, I: Iterator<Item = &'a Value>>(values: I) -> ValueType {
+    let mut value_type = ValueType::Nothing;
+    for value in values {
+        value_type = match (value_type, value) {
+            (_, Value::Null) => continue,
+            (ValueType::Nothing, Value::Integer(i)) | (ValueType::Integer, Value::Integer(i)) => {
+                match i32::try_from(*i) {
+                    Ok(_) => ValueType::Integer,
+                    Err(_) => ValueType::BigInt,
+                }
+            }
+            (ValueType::Nothing, Value::Real(_)) => ValueType::Float,
+            (ValueType::Nothing, Value::Text(_)) => ValueType::Text,
+            (ValueType::Nothing, Value::Blob(_)) => ValueType::VarChar,
+            (value_type, _) => value_type,
+        };
+    }
+    value_type
+}
+
+fn field(value: Value, value_type: ValueType) -> Field {
+    match (value, value_type) {
+        (Value::Null, _) => Field::Nothing,
+        (Value::Integer(i), ValueType::Boolean) => Field::Boolean(i != 0),
+        (Value::Integer(i), ValueType::BigInt) => Field::BigInt(i),
+        (Value::Integer(i), ValueType::Float) => Field::Float(i as f32),
+        (Value::Integer(i), _) => i32::try_from(i).map_or(Field::BigInt(i), Field::Integer),
+        (Value::Real(f), _) => Field::Float(f as f32),
+        (Value::Text(s), ValueType::VarChar) => Field::VarChar(s),
+        // Written with `FloatFormat::HexBits`
+        (Value::Text(s), ValueType::Float) => parse_f32(&s).map_or(Field::Text(s), Field::Float),
+        (Value::Text(s), _) => Field::Text(s),
+        (Value::Blob(b), _) => Field::encode_varchar_base64(&b),
+    }
+}
+
+/// A [`RowSource`] for the results of queries on a SQLite connection
+///
+/// The types of the columns are taken from their declared types if there are
+/// any, and picked from the values otherwise. `BLOB`s are stored as base64 in
+/// `VARCHAR` fields.
+pub struct SqliteSource<'c> {
+    conn: &'c Connection,
+    queries: VecDeque<(String, String)>,
+    rows: vec::IntoIter<Row>,
+}
+
+impl<'c> SqliteSource<'c> {
+    /// Create a new source without tables
+    pub fn new(conn: &'c Connection) -> Self {
+        Self {
+            conn,
+            queries: VecDeque::new(),
+            rows: Vec::new().into_iter(),
+        }
+    }
+
+    /// Create a new source for all tables of `conn`, in the order they were created
+    pub fn all_tables(conn: &'c Connection) -> rusqlite::Result<Self> {
+        let mut stmt = conn.prepare(
+            "SELECT name FROM sqlite_master \
+             WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY rowid",
+        )?;
+        let names = stmt.query_map(rusqlite::params![], |row| row.get::<_, String>(0))?;
+        let mut source = Self::new(conn);
+        for name in names {
+            let name = name?;
+            let query = format!("SELECT * FROM \"{}\"", name.replace('"', "\"\""));
+            source = source.with_query(&name, &query);
+        }
+        Ok(source)
+    }
+
+    /// Add a table called `name` with the results of `query`
+    pub fn with_query(mut self, name: &str, query: &str) -> Self {
+        self.queries.push_back((name.to_owned(), query.to_owned()));
+        self
+    }
+}
+
+impl RowSource for SqliteSource<'_> {
+    type Error = rusqlite::Error;
+
+    fn next_table(&mut self) -> rusqlite::Result<Option<TableDef>> {
+        let (name, query) = match self.queries.pop_front() {
+            Some(query) => query,
+            None => return Ok(None),
+        };
+        let mut stmt = self.conn.prepare(&query)?;
+        let declared: Vec<_> = stmt
+            .columns()
+            .iter()
+            .map(|c| (c.name().to_owned(), c.decl_type().and_then(decl_value_type)))
+            .collect();
+        let count = declared.len();
+        let values = stmt.query_map(rusqlite::params![], |row| {
+            (0..count).map(|i| row.get::<_, Value>(i)).collect()
+        })?;
+        let values = values.collect::<rusqlite::Result<Vec<Vec<Value>>>>()?;
+
+        let columns: Vec<_> = declared
+            .into_iter()
+            .enumerate()
+            .map(|(index, (name, value_type))| {
+                let value_type = value_type
+                    .unwrap_or_else(|| infer_value_type(values.iter().map(|v| &v[index])));
+                Column::from((name.as_str(), value_type))
+            })
+            .collect();
+        let rows: Vec<_> = values
+            .into_iter()
+            .map(|values| {
+                let fields = values.into_iter().zip(&columns);
+                Row::from(
+                    fields
+                        .map(|(v, c)| field(v, c.field_type))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        self.rows = rows.into_iter();
+        Ok(Some(TableDef { columns, name }))
+    }
+
+    fn next_row(&mut self) -> rusqlite::Result<Option<Row>> {
+        Ok(self.rows.next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fdb::{
+        mem::Database,
+        source::import_from_source,
+        sqlite::{try_export_db_with_options, ExportOptions},
+    };
+
+    #[test]
+    fn test_sqlite_round_trip() {
+        let buf = crate::fdb::testing::objects(10);
+        let mut conn = Connection::open_in_memory().unwrap();
+        let options = ExportOptions {
+            infer_types: true,
+            ..ExportOptions::default()
+        };
+        try_export_db_with_options(&mut conn, Database::new(&buf), &options, &()).unwrap();
+
+        let source = SqliteSource::all_tables(&conn).unwrap();
+        let imported = import_from_source(source, &()).unwrap();
+        let objects = imported.table("Objects").unwrap();
+        let columns: Vec<_> = objects.columns().iter().map(|c| c.field_type).collect();
+        let expected = [
+            ValueType::Integer,
+            ValueType::Text,
+            ValueType::Float,
+            ValueType::Boolean,
+        ];
+        assert_eq!(columns, expected);
+
+        let original = crate::fdb::core::Schema::from_source(&buf[..]).unwrap();
+        for row in original.table("Objects").unwrap() {
+            let id = row.fields()[0].clone().into_opt_integer().unwrap();
+            let bucket = &objects.buckets()[id as usize % objects.buckets().len()];
+            assert!(bucket.rows_ref().contains(row), "{:?}", row);
+        }
+    }
+}
@@ -0,0 +1,360 @@
+//! # Incremental sync of edits into an SQLite export
+//!
+//! [`try_export_db`][super::try_export_db] writes the rows of every table in
+//! file order, so the `n`-th row of a table gets the rowid `n + 1`. This module
+//! uses that to replay only the edited rows of a [`HybridSchema`] into such an
+//! export, instead of exporting the whole database again after every change.
+//!
+//! A [`SyncState`] remembers what earlier calls to [`sync_edits`] wrote, so each
+//! call only touches the rows that changed since, including edits that were
+//! discarded in the meantime. New rows get rowids after both the original rows
+//! and the highest rowid in the table, so that they never take the rowid of a
+//! deleted original row, which SQLite would otherwise hand out again.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use assembly_core::{buffer::CastError, displaydoc::Display};
+use rusqlite::{types::Value, Connection};
+use thiserror::Error;
+
+use crate::fdb::{
+    core::Field,
+    float::widen,
+    hybrid::{HybridSchema, HybridTable, RowId},
+};
+
+/// Errors from [`sync_edits`]
+#[derive(Debug, Error, Display)]
+#[non_exhaustive]
+pub enum SyncError {
+    /// Failed to read the database: {0}
+    Cast(#[from] CastError),
+    /// Failed to write to SQLite: {0}
+    Sqlite(#[from] rusqlite::Error),
+}
+
+/// The number of statements run by [`sync_edits`]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SyncStats {
+    /// Rows from the original database that were written or restored
+    pub upserted: usize,
+    /// Rows that were deleted
+    pub deleted: usize,
+    /// New rows that were added
+    pub inserted: usize,
+}
+
+/// What was written for one table
+#[derive(Debug, Clone, Default)]
+struct TableState {
+    /// Original rows as last written, `None` if deleted
+    rows: BTreeMap<RowId, Option<Vec<Field>>>,
+    /// New rows as last written, with their rowid
+    inserted: Vec<(i64, Vec<Field>)>,
+}
+
+/// The edits that were written by earlier calls to [`sync_edits`]
+#[derive(Debug, Clone, Default)]
+pub struct SyncState {
+    tables: BTreeMap<String, TableState>,
+}
+
+impl SyncState {
+    /// Create a state for an export without edits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether no edits have been written
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+}
+
+fn sql_value(field: &Field) -> Value {
+    match field {
+        Field::Nothing => Value::Null,
+        Field::Integer(i) => Value::Integer((*i).into()),
+        Field::Float(f) => Value::Real(widen(*f)),
+        Field::Text(s) | Field::VarChar(s) => Value::Text(s.clone()),
+        Field::Boolean(b) => Value::Integer(if *b { 1 } else { 0 }),
+        Field::BigInt(i) => Value::Integer(*i),
+    }
+}
+
+/// The statements for one table
+struct Queries {
+    upsert: String,
+    insert: String,
+    delete: String,
+    max_rowid: String,
+}
+
+impl Queries {
+    fn new(table: &HybridTable<'_, '_>) -> Self {
+        let name = table.name();
+        let mut columns = Vec::new();
+        let mut values = Vec::new();
+        for (i, column) in table.original().column_iter().enumerate() {
+            columns.push(format!("[{}]", column.name()));
+            values.push(format!("?{}", i + 1));
+        }
+        let columns = columns.join(", ");
+        let values = values.join(", ");
+        let mut targets = format!("\"{}\" (rowid", name);
+        let mut params = format!("?{}", table.original().column_count() + 1);
+        if !columns.is_empty() {
+            write!(targets, ", {}", columns).unwrap();
+            write!(params, ", {}", values).unwrap();
+        }
+        Self {
+            upsert: format!("INSERT OR REPLACE INTO {}) VALUES ({});", targets, params),
+            insert: format!("INSERT INTO {}) VALUES ({});", targets, params),
+            delete: format!("DELETE FROM \"{}\" WHERE rowid = ?1;", name),
+            max_rowid: format!("SELECT max(rowid) FROM \"{}\";", name),
+        }
+    }
+}
+
+fn sync_table(
+    conn: &Connection,
+    table: HybridTable<'_, '_>,
+    state: &mut TableState,
+    stats: &mut SyncStats,
+) -> Result<(), SyncError> {
+    let original = table.original();
+    let mut offsets = Vec::with_capacity(original.bucket_count());
+    let mut offset = 0;
+    for bucket in original.bucket_iter() {
+        offsets.push(offset);
+        offset += bucket.row_iter().count() as i64;
+    }
+    let rowid = |id: RowId| offsets[id.bucket] + id.index as i64 + 1;
+    let queries = Queries::new(&table);
+
+    let mut wanted: BTreeMap<RowId, Option<Vec<Field>>> = BTreeMap::new();
+    if let Some(edits) = table.edits() {
+        for (id, row) in edits.updated() {
+            wanted.insert(*id, Some(row.fields().clone()));
+        }
+        for id in edits.removed() {
+            wanted.insert(*id, None);
+        }
+    }
+    // Edits that were written before, but are gone now, restore the original
+    let restore: Vec<RowId> = state
+        .rows
+        .keys()
+        .filter(|id| !wanted.contains_key(id))
+        .copied()
+        .collect();
+    for id in restore {
+        state.rows.remove(&id);
+        let row = original.bucket_at(id.bucket);
+        if let Some(row) = row.and_then(|b| b.row_iter().nth(id.index)) {
+            let fields = row.field_iter().map(|f| sql_value(&Field::from(f)));
+            conn.execute(
+                &queries.upsert,
+                fields.chain(Some(Value::Integer(rowid(id)))),
+            )?;
+            stats.upserted += 1;
+        }
+    }
+
+    for (id, fields) in wanted {
+        if state.rows.get(&id) == Some(&fields) {
+            continue;
+        }
+        match &fields {
+            Some(fields) => {
+                let params = fields.iter().map(sql_value);
+                conn.execute(
+                    &queries.upsert,
+                    params.chain(Some(Value::Integer(rowid(id)))),
+                )?;
+                stats.upserted += 1;
+            }
+            None => {
+                conn.execute(&queries.delete, Some(rowid(id)))?;
+                stats.deleted += 1;
+            }
+        }
+        state.rows.insert(id, fields);
+    }
+
+    let inserted: Vec<&[Field]> = table
+        .edits()
+        .into_iter()
+        .flat_map(|e| e.inserted().values())
+        .flatten()
+        .map(|row| row.fields().as_slice())
+        .collect();
+    let same = state
+        .inserted
+        .iter()
+        .zip(&inserted)
+        .take_while(|((_, old), new)| old.as_slice() == **new)
+        .count();
+    for (old, _) in state.inserted.drain(same..) {
+        conn.execute(&queries.delete, Some(old))?;
+        stats.deleted += 1;
+    }
+    if inserted.len() > same {
+        let max: Option<i64> =
+            conn.query_row(&queries.max_rowid, rusqlite::params![], |row| row.get(0))?;
+        let first = max.unwrap_or(0).max(offset) + 1;
+        for (next, fields) in (first..).zip(&inserted[same..]) {
+            let params = fields.iter().map(sql_value);
+            conn.execute(&queries.insert, params.chain(Some(Value::Integer(next))))?;
+            state.inserted.push((next, fields.to_vec()));
+            stats.inserted += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Replay the edits of `schema` into `conn`
+///
+/// `conn` must contain an export of the original database of `schema`, made
+/// with [`try_export_db`][super::try_export_db], and `state` must hold what
+/// earlier calls wrote to it, starting with [`SyncState::new`]. All changes
+/// are made in a single transaction, and `state` is only updated if it is
+/// committed.
+pub fn sync_edits(
+    conn: &mut Connection,
+    schema: &HybridSchema<'_>,
+    state: &mut SyncState,
+) -> Result<SyncStats, SyncError> {
+    let mut next = state.clone();
+    let mut stats = SyncStats::default();
+    let tx = conn.transaction()?;
+    for table in schema.tables() {
+        let table = table?;
+        let name = table.name();
+        if !table.is_modified() && !next.tables.contains_key(&name) {
+            continue;
+        }
+        let table_state = next.tables.entry(name.clone()).or_default();
+        sync_table(&tx, table, table_state, &mut stats)?;
+        if table_state.rows.is_empty() && table_state.inserted.is_empty() {
+            next.tables.remove(&name);
+        }
+    }
+    tx.commit()?;
+    *state = next;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fdb::{
+        common::ValueType, core::Row, mem::Database, sqlite::try_export_db, testing::SampleDatabase,
+    };
+
+    /// An `Objects` table with the given number of buckets and `count` rows
+    fn objects(buckets: usize, count: i32) -> Vec<u8> {
+        let columns = [("id", ValueType::Integer), ("name", ValueType::Text)];
+        let db = SampleDatabase::new().table("Objects", &columns);
+        let db = (1..=count).fold(db.buckets(buckets), |db, id| {
+            db.row(vec![
+                Field::Integer(id),
+                Field::Text(format!("Object {}", id)),
+            ])
+        });
+        db.build()
+    }
+
+    fn names(conn: &Connection) -> Vec<(i64, Option<String>)> {
+        let mut stmt = conn
+            .prepare("SELECT id, name FROM Objects ORDER BY rowid")
+            .unwrap();
+        let rows = stmt
+            .query_map(rusqlite::params![], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        rows.map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn test_sync_edits() {
+        let buf = objects(2, 4);
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        try_export_db(&mut conn, Database::new(&buf)).unwrap();
+        let before = names(&conn);
+
+        let mut schema = HybridSchema::new(Database::new(&buf)).unwrap();
+        let mut state = SyncState::new();
+        let second = RowId {
+            bucket: 0,
+            index: 1,
+        };
+        let text = Field::Text(String::from("Renamed"));
+        schema.set_field("Objects", second, 1, text).unwrap();
+        schema
+            .remove_row(
+                "Objects",
+                RowId {
+                    bucket: 1,
+                    index: 0,
+                },
+            )
+            .unwrap();
+        let row = Row::from(vec![Field::Integer(5), Field::Nothing]);
+        schema.insert_row("Objects", row).unwrap();
+
+        let stats = sync_edits(&mut conn, &schema, &mut state).unwrap();
+        assert_eq!(
+            stats,
+            SyncStats {
+                upserted: 1,
+                deleted: 1,
+                inserted: 1
+            }
+        );
+        let mut expected = before.clone();
+        expected[1].1 = Some(String::from("Renamed"));
+        expected.remove(2);
+        expected.push((5, None));
+        assert_eq!(names(&conn), expected);
+
+        let stats = sync_edits(&mut conn, &schema, &mut state).unwrap();
+        assert_eq!(stats, SyncStats::default());
+
+        schema.discard("Objects");
+        let stats = sync_edits(&mut conn, &schema, &mut state).unwrap();
+        assert_eq!(stats.upserted, 2);
+        assert_eq!(stats.deleted, 1);
+        assert_eq!(names(&conn), before);
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn test_insert_after_deleting_last_row() {
+        let buf = objects(1, 3);
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        try_export_db(&mut conn, Database::new(&buf)).unwrap();
+        let before = names(&conn);
+
+        let mut schema = HybridSchema::new(Database::new(&buf)).unwrap();
+        let mut state = SyncState::new();
+        let last = RowId {
+            bucket: 0,
+            index: 2,
+        };
+        schema.remove_row("Objects", last).unwrap();
+        sync_edits(&mut conn, &schema, &mut state).unwrap();
+        let row = Row::from(vec![Field::Integer(4), Field::Nothing]);
+        schema.insert_row("Objects", row).unwrap();
+        sync_edits(&mut conn, &schema, &mut state).unwrap();
+        assert_eq!(
+            names(&conn),
+            [before[0].clone(), before[1].clone(), (4, None)]
+        );
+
+        schema.discard("Objects");
+        sync_edits(&mut conn, &schema, &mut state).unwrap();
+        assert_eq!(names(&conn), before);
+    }
+}
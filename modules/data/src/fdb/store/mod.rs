@@ -46,7 +46,7 @@ use self::writer::WriteLE;
 
 use super::{
     common::{Context, Latin1Str, Latin1String, Value, ValueMapperMut, ValueType},
-    core::OwnedContext,
+    core::{self, OwnedContext},
     file::{
         ArrayHeader, FDBBucketHeader, FDBColumnHeader, FDBFieldData, FDBHeader, FDBRowHeader,
         FDBRowHeaderListEntry, FDBTableDataHeader, FDBTableDefHeader, FDBTableHeader,
@@ -55,7 +55,7 @@ use super::{
 
 mod writer;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "fdb-mem"))]
 mod tests;
 
 /// The whole database
@@ -125,6 +125,63 @@ impl Database {
 
         Ok(())
     }
+
+    /// Write `original`, with the tables in `self` replacing those of the same name
+    ///
+    /// This is only available with the `fdb-mem` feature.
+    ///
+    /// The original buffer is copied as-is, except for the entries of the table
+    /// header list, and the replacement tables are appended at the end. This means
+    /// that unchanged tables are never re-serialized, but also that the space of
+    /// the replaced tables is not reclaimed. Use [`Database::write`] with all tables
+    /// to create a compact file.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if one of the tables does not
+    /// exist in `original` or if the result would exceed 4 GiB.
+    #[cfg(feature = "fdb-mem")]
+    pub fn write_patched<O: io::Write>(&self, original: &[u8], out: &mut O) -> io::Result<()> {
+        let invalid_data = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+        let tables = super::mem::Database::new(original)
+            .tables()
+            .map_err(invalid_data)?;
+        let list_start = u32::from_le_bytes(original[4..8].try_into().unwrap()) as usize;
+        let header_size = size_of::<FDBTableHeader>();
+
+        let base_offset = (original.len() + 3) & !3;
+        let mut start = u32::try_from(base_offset).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "original exceeds 4 GiB")
+        })?;
+        let mut patches = BTreeMap::new();
+        for (table_name, table) in &self.tables {
+            let index = tables
+                .iter()
+                .position(|t| matches!(t, Ok(t) if t.name_raw() == &**table_name))
+                .ok_or_else(|| {
+                    let msg = format!("table {:?} does not exist", table_name.decode());
+                    io::Error::new(io::ErrorKind::InvalidInput, msg)
+                })?;
+            let len = table.compute_size(table_name);
+            let header_addr = list_start + index * header_size;
+            let mut header = Vec::with_capacity(header_size);
+            Table::write_header(&mut start, &len, &mut header)?;
+            patches.insert(header_addr, header);
+        }
+
+        let mut pos = 0;
+        for (&addr, header) in &patches {
+            out.write_all(&original[pos..addr])?;
+            out.write_all(header)?;
+            pos = addr + header_size;
+        }
+        out.write_all(&original[pos..])?;
+        out.write_all(&[0; 3][..base_offset - original.len()])?;
+
+        let mut start = base_offset as u32;
+        for (table_name, table) in &self.tables {
+            start = table.write(table_name, start, out)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -247,8 +304,15 @@ impl Table {
         len: &TableSize,
         out: &mut IO,
     ) -> io::Result<()> {
+        let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "file exceeds 4 GiB");
+        let add = |addr: u32, len: usize| {
+            u32::try_from(len)
+                .ok()
+                .and_then(|len| addr.checked_add(len))
+                .ok_or_else(too_large)
+        };
         let table_def_header_addr = *start;
-        let table_data_header_addr = *start + u32::try_from(len.def).unwrap();
+        let table_data_header_addr = add(*start, len.def)?;
 
         FDBTableHeader {
             table_def_header_addr,
@@ -256,7 +320,7 @@ impl Table {
         }
         .write_le(out)?;
 
-        *start = table_data_header_addr + u32::try_from(len.data).unwrap();
+        *start = add(table_data_header_addr, len.data)?;
         Ok(())
     }
 
@@ -435,6 +499,31 @@ impl Table {
     }
 }
 
+impl From<&core::Table> for Table {
+    fn from(table: &core::Table) -> Self {
+        let mut dest = Table::new(table.buckets().len());
+        for column in table.columns() {
+            dest.push_column(Latin1String::encode(&column.name), column.field_type);
+        }
+        for (index, bucket) in table.buckets().iter().enumerate() {
+            for row in bucket.rows_ref() {
+                dest.push_row(index, row.fields());
+            }
+        }
+        dest
+    }
+}
+
+impl From<&core::Schema> for Database {
+    fn from(schema: &core::Schema) -> Self {
+        let mut dest = Database::new();
+        for table in schema {
+            dest.push_table(Latin1String::encode(table.name()), Table::from(table));
+        }
+        dest
+    }
+}
+
 /// A single column
 pub struct Column {
     name: Latin1String,
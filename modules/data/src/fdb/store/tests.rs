@@ -346,3 +346,48 @@ fn test_write_tables_with_data() {
     );
     assert!(rows3.next().is_none());
 }
+
+fn objects(ids: &[i32]) -> Table {
+    let mut table = Table::new(4);
+    table.push_column(Latin1String::encode("id"), ValueType::Integer);
+    table.push_column(Latin1String::encode("name"), ValueType::Text);
+    for &id in ids {
+        let name = core::Field::Text(format!("Object {}", id));
+        table.push_row(id as usize, &[core::Field::Integer(id), name]);
+    }
+    table
+}
+
+#[test]
+fn test_write_patched() {
+    let mut db = Database::new();
+    db.push_table(Latin1String::encode("Objects"), objects(&[1, 2, 3]));
+    let mut buf = Vec::new();
+    db.write(&mut buf).unwrap();
+
+    // Key 1 (bucket 1) is changed to 6 (bucket 2)
+    let mut patch = Database::new();
+    patch.push_table(Latin1String::encode("Objects"), objects(&[6, 2, 3]));
+    let mut out = Vec::new();
+    patch.write_patched(&buf, &mut out).unwrap();
+
+    let odb = mem::Database::new(&out);
+    let otb = odb.tables().unwrap();
+    let objects = otb.by_name("Objects").unwrap().unwrap();
+    assert_eq!(objects.index_iter(1).count(), 0);
+    let row = objects.index_iter(6).next().expect("row with key 6");
+    assert_eq!(row.field_at(0), Some(mem::Field::Integer(6)));
+
+    let mut missing = Database::new();
+    missing.push_table(Latin1String::encode("Missing"), Table::new(0));
+    let err = missing.write_patched(&buf, &mut Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_write_header_overflow() {
+    let len = TableSize { def: 16, data: 0 };
+    let mut start = u32::MAX - 8;
+    let err = Table::write_header(&mut start, &len, &mut Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
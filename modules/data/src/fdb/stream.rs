@@ -0,0 +1,711 @@
+//! # Streaming exports as CSV or JSON lines
+//!
+//! The functions in this module write the rows of a database to any
+//! [`io::Write`], e.g. a file, a socket or [`io::stdout`], with the following
+//! contract:
+//!
+//! - Rows are written while they are read. Nothing is collected per table, so
+//!   memory use doesn't depend on the size of the database.
+//! - Output goes through a single buffer of [`BUFFER_SIZE`] bytes, so no call
+//!   to the writer gets more than that. A slow writer (e.g. a pipe to a
+//!   program that reads slowly) only blocks the export, it doesn't make it
+//!   buffer more.
+//! - The first error of the writer stops the export and is returned. When
+//!   writing to stdout, an [`io::ErrorKind::BrokenPipe`] means that the reader
+//!   went away (e.g. `| head`), which the caller may want to treat as success.
+//!
+//! In [`StreamFormat::JsonLines`], every row is a JSON object on its own line,
+//! with the name of the table in `"$table"` and one key per column. In
+//! [`StreamFormat::Csv`], every table is written as a header with the column
+//! names and one record per row, and tables are separated by an empty line.
+//!
+//! [`export_tables_streaming_with`] takes [`StreamOptions`] for the details:
+//!
+//! - `FLOAT` fields are written as the shortest decimal that reads back as the
+//!   same value, or as their bits, see [`FloatFormat`]. In JSON, these are
+//!   strings like `"0x3e99999a"`.
+//! - The CSV output follows a [`CsvDialect`], e.g. with `\r\n` line endings
+//!   or tabs instead of commas.
+//! - The rows of each table are written in an [`IterOrder`]. Use
+//!   [`IterOrder::PrimaryKey`] for an output that is the same for every file
+//!   with the same rows, no matter how they are spread over the buckets.
+//! - In JSON, the fields of `VARCHAR` columns with base64 payloads can be
+//!   written as `{"hex": "…"}`, which is read back as the same base64 string.
+//!
+//! The export is a [`StreamSink`], see [`sink`][super::sink] for how to write
+//! to other destinations. With the `fdb-core` feature, the sink can rewrite
+//! fields with [`ColumnHooks`][super::hooks::ColumnHooks] while they are
+//! written, see [`StreamSink::with_hooks`].
+//!
+//! ```
+//! use assembly_data::fdb::{mem::Database, stream::{export_streaming, StreamFormat}};
+//!
+//! let file: &[u8] = &[0, 0, 0, 0, 8, 0, 0, 0];
+//! let mut out = Vec::new();
+//! let stats = export_streaming(Database::new(file), StreamFormat::JsonLines, &mut out).unwrap();
+//! assert_eq!(stats.rows, 0);
+//! ```
+
+#[cfg(feature = "fdb-core")]
+use std::borrow::Cow;
+use std::{
+    fmt::Write as _,
+    io::{self, BufWriter, Write},
+};
+
+use assembly_core::{buffer::CastError, displaydoc::Display};
+use thiserror::Error;
+
+use super::{
+    base64,
+    common::{IterOrder, ValueType},
+    csv::{CsvDialect, Quoting},
+    encoding::audit_table,
+    float::{DisplayFloat, FloatFormat},
+    mem::{Database, Field, Row, Table},
+    sink::{export_to_sink, RowSink, SinkError},
+};
+#[cfg(feature = "fdb-core")]
+use super::{
+    common::{Latin1Str, Latin1String},
+    core::Field as CoreField,
+    hooks::{ColumnHooks, Hook},
+    query::sort::Order,
+};
+
+/// The size of the output buffer of [`export_streaming`]
+pub const BUFFER_SIZE: usize = 8 * 1024;
+
+/// The output format of [`export_streaming`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// Comma separated values, with one block per table
+    Csv,
+    /// One JSON object per row
+    JsonLines,
+}
+
+/// Options for [`export_tables_streaming_with`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamOptions {
+    /// How to write `FLOAT` fields
+    pub floats: FloatFormat,
+    /// The CSV format, for [`StreamFormat::Csv`]
+    pub csv: CsvDialect,
+    /// The order of the rows of each table
+    pub order: IterOrder,
+    /// The columns to sort the rows of each table by, after `order`
+    ///
+    /// Keys for columns that a table doesn't have are skipped, see
+    /// [`RowSink::sort_keys`].
+    #[cfg(feature = "fdb-core")]
+    pub sort: Vec<(String, Order)>,
+    /// Write the fields of `VARCHAR` columns that only contain base64 (see
+    /// [`ColumnEncoding::is_base64`]) as `{"hex": "…"}` in JSON lines
+    ///
+    /// This is the same object as [`json::varchar_hex`][crate::fdb::json::varchar_hex].
+    /// The CSV output is not affected, as a hex string couldn't be told apart
+    /// from text there.
+    ///
+    /// [`ColumnEncoding::is_base64`]: super::encoding::ColumnEncoding::is_base64
+    pub varchar_hex: bool,
+    /// Look up strings in a [`StringIndex`][crate::fdb::mem::strings::StringIndex],
+    /// see [`RowSink::index_strings`]
+    pub index_strings: bool,
+}
+
+/// Errors when streaming an export
+#[derive(Debug, Error, Display)]
+#[non_exhaustive]
+pub enum StreamError {
+    /// Failed to write the output: {0}
+    Io(#[from] io::Error),
+    /// Failed to read a table: {0}
+    Cast(#[from] CastError),
+}
+
+/// The amount of data written by [`export_streaming`]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    /// The number of tables
+    pub tables: usize,
+    /// The number of rows
+    pub rows: u64,
+    /// The number of bytes
+    pub bytes: u64,
+}
+
+/// Counts the bytes that were accepted by the inner writer
+struct Counter<W> {
+    inner: W,
+    bytes: u64,
+}
+
+impl<W: Write> Write for Counter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.bytes += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn write_json_str<W: Write>(out: &mut W, text: &str) -> io::Result<()> {
+    out.write_all(b"\"")?;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        let escape = match c {
+            '"' => "\\\"",
+            '\\' => "\\\\",
+            '\n' => "\\n",
+            '\r' => "\\r",
+            '\t' => "\\t",
+            c if (c as u32) < 0x20 => "",
+            _ => continue,
+        };
+        out.write_all(&text.as_bytes()[start..index])?;
+        if escape.is_empty() {
+            write!(out, "\\u{:04x}", c as u32)?;
+        } else {
+            out.write_all(escape.as_bytes())?;
+        }
+        start = index + c.len_utf8();
+    }
+    out.write_all(&text.as_bytes()[start..])?;
+    out.write_all(b"\"")
+}
+
+fn write_json_field<W: Write>(
+    out: &mut W,
+    field: &Field<'_>,
+    floats: FloatFormat,
+) -> io::Result<()> {
+    match *field {
+        Field::Integer(i) => write!(out, "{}", i),
+        Field::Float(f) if floats == FloatFormat::HexBits => {
+            write!(out, "\"{}\"", DisplayFloat(f, floats))
+        }
+        Field::Float(f) if f.is_finite() => write!(out, "{}", DisplayFloat(f, floats)),
+        Field::Text(s) | Field::VarChar(s) => write_json_str(out, &s.decode()),
+        Field::Boolean(b) => write!(out, "{}", b),
+        Field::BigInt(i) => write!(out, "{}", i),
+        _ => out.write_all(b"null"),
+    }
+}
+
+/// Append a field without special characters, quoted only if all fields are
+fn push_csv_plain(record: &mut String, csv: &CsvDialect, value: impl std::fmt::Display) {
+    let quoted = csv.quoting == Quoting::Always;
+    if quoted {
+        record.push(csv.quote);
+    }
+    write!(record, "{}", value).unwrap();
+    if quoted {
+        record.push(csv.quote);
+    }
+}
+
+fn push_csv_field(record: &mut String, csv: &CsvDialect, field: &Field<'_>, floats: FloatFormat) {
+    match *field {
+        Field::Nothing => csv.write_null(record),
+        Field::Integer(i) => push_csv_plain(record, csv, i),
+        Field::Float(f) => push_csv_plain(record, csv, DisplayFloat(f, floats)),
+        Field::Text(s) | Field::VarChar(s) => csv.write_field(record, &s.decode()),
+        Field::Boolean(b) => push_csv_plain(record, csv, b),
+        Field::BigInt(i) => push_csv_plain(record, csv, i),
+    }
+}
+
+/// Encode the text of a field that was changed by a hook
+#[cfg(feature = "fdb-core")]
+fn encode_text(field: &CoreField) -> Option<Cow<'_, Latin1Str>> {
+    match field {
+        CoreField::Text(s) | CoreField::VarChar(s) => Some(Latin1String::encode(s)),
+        _ => None,
+    }
+}
+
+/// Borrow a field that was changed by a hook, with the text from [`encode_text`]
+#[cfg(feature = "fdb-core")]
+fn mem_field<'f>(field: &CoreField, text: &'f Option<Cow<'_, Latin1Str>>) -> Field<'f> {
+    match (field, text) {
+        (CoreField::Text(_), Some(text)) => Field::Text(text),
+        (CoreField::VarChar(_), Some(text)) => Field::VarChar(text),
+        (CoreField::Integer(i), _) => Field::Integer(*i),
+        (CoreField::Float(f), _) => Field::Float(*f),
+        (CoreField::Boolean(b), _) => Field::Boolean(*b),
+        (CoreField::BigInt(i), _) => Field::BigInt(*i),
+        _ => Field::Nothing,
+    }
+}
+
+/// A [`RowSink`] that writes CSV or JSON lines, see the [module documentation](self)
+///
+/// This is what [`export_tables_streaming_with`] uses, for use with
+/// [`export_to_sink`] directly, e.g. to report progress.
+pub struct StreamSink<W: Write> {
+    out: BufWriter<Counter<W>>,
+    format: StreamFormat,
+    options: StreamOptions,
+    name: String,
+    columns: Vec<String>,
+    hex: Vec<bool>,
+    record: String,
+    stats: StreamStats,
+    #[cfg(feature = "fdb-core")]
+    hooks: ColumnHooks,
+    /// The hooks for the columns of the current table
+    #[cfg(feature = "fdb-core")]
+    current: Vec<(usize, Hook)>,
+}
+
+impl<W: Write> StreamSink<W> {
+    /// Create a new sink, writing the byte order mark of the CSV dialect if needed
+    pub fn new(out: W, format: StreamFormat, options: StreamOptions) -> io::Result<Self> {
+        let counter = Counter {
+            inner: out,
+            bytes: 0,
+        };
+        let mut out = BufWriter::with_capacity(BUFFER_SIZE, counter);
+        if format == StreamFormat::Csv && options.csv.bom {
+            out.write_all("\u{feff}".as_bytes())?;
+        }
+        Ok(Self {
+            out,
+            format,
+            options,
+            name: String::new(),
+            columns: Vec::new(),
+            hex: Vec::new(),
+            record: String::new(),
+            stats: StreamStats::default(),
+            #[cfg(feature = "fdb-core")]
+            hooks: ColumnHooks::new(),
+            #[cfg(feature = "fdb-core")]
+            current: Vec::new(),
+        })
+    }
+
+    /// Write one row of the current table
+    fn write_row<'f>(&mut self, fields: impl Iterator<Item = Field<'f>>) -> io::Result<()> {
+        self.stats.rows += 1;
+        let floats = self.options.floats;
+        let out = &mut self.out;
+        match self.format {
+            StreamFormat::Csv => {
+                let csv = &self.options.csv;
+                let record = &mut self.record;
+                record.clear();
+                for (index, field) in fields.enumerate() {
+                    if index > 0 {
+                        csv.write_delimiter(record);
+                    }
+                    push_csv_field(record, csv, &field, floats);
+                }
+                csv.end_record(record);
+                out.write_all(record.as_bytes())
+            }
+            StreamFormat::JsonLines => {
+                out.write_all(b"{\"$table\":")?;
+                write_json_str(out, &self.name)?;
+                let columns = self.columns.iter().zip(&self.hex);
+                for ((column, hex), field) in columns.zip(fields) {
+                    out.write_all(b",")?;
+                    write_json_str(out, column)?;
+                    out.write_all(b":")?;
+                    match field.decode_varchar_base64() {
+                        Ok(bytes) if *hex => {
+                            write!(out, "{{\"hex\":\"{}\"}}", base64::to_hex(&bytes))?
+                        }
+                        _ => write_json_field(out, &field, floats)?,
+                    }
+                }
+                out.write_all(b"}\n")
+            }
+        }
+    }
+
+    /// Apply `hooks` to the fields of every row before it is written
+    ///
+    /// The hooks use the names of the tables and columns of the export. Text
+    /// that a hook returns is written as it would be stored in a database
+    /// file, i.e. characters that are not in Windows-1252 are replaced.
+    #[cfg(feature = "fdb-core")]
+    pub fn with_hooks(mut self, hooks: ColumnHooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// The amount of data written so far
+    ///
+    /// The bytes only include what was passed on to the writer, which is all
+    /// of them after [`RowSink::finish`].
+    pub fn stats(&self) -> StreamStats {
+        StreamStats {
+            bytes: self.out.get_ref().bytes,
+            ..self.stats
+        }
+    }
+}
+
+impl<W: Write> RowSink for StreamSink<W> {
+    type Error = io::Error;
+
+    fn begin_table(&mut self, table: &Table<'_>) -> io::Result<()> {
+        self.stats.tables += 1;
+        #[cfg(feature = "fdb-core")]
+        {
+            let columns: Vec<_> = table.column_iter().map(|c| c.name()).collect();
+            let hooks = self.hooks.resolve_columns(&table.name(), &columns);
+            self.current = hooks.into_iter().map(|(i, h)| (i, h.clone())).collect();
+        }
+        match self.format {
+            StreamFormat::Csv => {
+                let csv = &self.options.csv;
+                let record = &mut self.record;
+                record.clear();
+                if self.stats.tables > 1 {
+                    csv.end_record(record);
+                }
+                for (index, column) in table.column_iter().enumerate() {
+                    if index > 0 {
+                        csv.write_delimiter(record);
+                    }
+                    csv.write_field(record, &column.name());
+                }
+                csv.end_record(record);
+                self.out.write_all(record.as_bytes())
+            }
+            StreamFormat::JsonLines => {
+                self.name = table.name().into_owned();
+                self.columns = table.column_iter().map(|c| c.name().into_owned()).collect();
+                self.hex = vec![false; self.columns.len()];
+                if self.options.varchar_hex {
+                    let encoding = audit_table(table);
+                    for (hex, column) in self.hex.iter_mut().zip(table.column_iter()) {
+                        let name = column.name();
+                        *hex = column.value_type() == ValueType::VarChar
+                            && encoding
+                                .columns
+                                .iter()
+                                .any(|c| c.name == name && c.is_base64());
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn push_row(&mut self, row: Row<'_>) -> io::Result<()> {
+        #[cfg(feature = "fdb-core")]
+        if !self.current.is_empty() {
+            let mut fields: Vec<_> = row.field_iter().map(CoreField::from).collect();
+            ColumnHooks::apply_fields(&self.current, &mut fields);
+            let text: Vec<_> = fields.iter().map(encode_text).collect();
+            let fields = fields.iter().zip(&text).map(|(f, t)| mem_field(f, t));
+            return self.write_row(fields);
+        }
+        self.write_row(row.field_iter())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    fn order(&self) -> IterOrder {
+        self.options.order
+    }
+
+    fn index_strings(&self) -> bool {
+        self.options.index_strings
+    }
+
+    #[cfg(feature = "fdb-core")]
+    fn sort_keys(&self) -> &[(String, Order)] {
+        &self.options.sort
+    }
+}
+
+/// Export all tables of `db` to `out`, see the [module documentation](self)
+pub fn export_streaming<W: Write>(
+    db: Database<'_>,
+    format: StreamFormat,
+    out: W,
+) -> Result<StreamStats, StreamError> {
+    let tables = db.tables()?;
+    export_tables_streaming(tables.iter(), format, out)
+}
+
+/// Export the given tables to `out`, see the [module documentation](self)
+pub fn export_tables_streaming<'a, W, I>(
+    tables: I,
+    format: StreamFormat,
+    out: W,
+) -> Result<StreamStats, StreamError>
+where
+    W: Write,
+    I: IntoIterator<Item = Result<Table<'a>, CastError>>,
+{
+    export_tables_streaming_with(tables, format, &StreamOptions::default(), out)
+}
+
+/// Like [`export_tables_streaming`], with some [`StreamOptions`]
+pub fn export_tables_streaming_with<'a, W, I>(
+    tables: I,
+    format: StreamFormat,
+    options: &StreamOptions,
+    out: W,
+) -> Result<StreamStats, StreamError>
+where
+    W: Write,
+    I: IntoIterator<Item = Result<Table<'a>, CastError>>,
+{
+    let mut sink = StreamSink::new(out, format, options.clone())?;
+    export_to_sink(tables, &mut sink, &()).map_err(|e| match e {
+        SinkError::Cast(e) => StreamError::Cast(e),
+        SinkError::Sink(e) => StreamError::Io(e),
+        SinkError::Cancelled => unreachable!("nothing cancels the export"),
+    })?;
+    Ok(sink.stats())
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{common::ValueType, core, testing::SampleDatabase};
+
+    fn database(rows: i32) -> Vec<u8> {
+        let columns = [
+            ("id", ValueType::Integer),
+            ("name", ValueType::Text),
+            ("scale", ValueType::Float),
+        ];
+        let db = SampleDatabase::new().table("Objects", &columns).buckets(64);
+        let db = (0..rows).fold(db, |db, id| {
+            db.row(vec![
+                core::Field::Integer(id),
+                core::Field::Text(format!("Brick \"{}\", red", id)),
+                core::Field::Float(0.5),
+            ])
+        });
+        db.build()
+    }
+
+    #[test]
+    fn test_formats() {
+        let buf = database(1);
+        let db = Database::new(&buf);
+
+        let mut csv = Vec::new();
+        export_streaming(db, StreamFormat::Csv, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv, "id,name,scale\n0,\"Brick \"\"0\"\", red\",0.5\n");
+
+        let mut jsonl = Vec::new();
+        let stats = export_streaming(db, StreamFormat::JsonLines, &mut jsonl).unwrap();
+        let jsonl = String::from_utf8(jsonl).unwrap();
+        assert_eq!(
+            jsonl,
+            "{\"$table\":\"Objects\",\"id\":0,\"name\":\"Brick \\\"0\\\", red\",\"scale\":0.5}\n"
+        );
+        assert_eq!(stats.bytes, jsonl.len() as u64);
+
+        let tables = db.tables().unwrap();
+        let options = StreamOptions {
+            floats: FloatFormat::HexBits,
+            csv: CsvDialect::tsv(),
+            ..StreamOptions::default()
+        };
+        let mut bits = Vec::new();
+        export_tables_streaming_with(tables.iter(), StreamFormat::JsonLines, &options, &mut bits)
+            .unwrap();
+        let bits = String::from_utf8(bits).unwrap();
+        assert!(bits.ends_with(",\"scale\":\"0x3f000000\"}\n"), "{}", bits);
+
+        let mut tsv = Vec::new();
+        export_tables_streaming_with(tables.iter(), StreamFormat::Csv, &options, &mut tsv).unwrap();
+        let tsv = String::from_utf8(tsv).unwrap();
+        assert_eq!(tsv, "id\tname\tscale\n0\tBrick \"0\", red\t0x3f000000\n");
+    }
+
+    /// Accepts at most 7 bytes per call, like a pipe that is read slowly
+    struct SlowWriter {
+        out: Vec<u8>,
+        largest_call: usize,
+    }
+
+    impl Write for SlowWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.largest_call = self.largest_call.max(buf.len());
+            let len = buf.len().min(7);
+            self.out.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_slow_writer() {
+        let buf = database(5000);
+        let db = Database::new(&buf);
+        let mut expected = Vec::new();
+        export_streaming(db, StreamFormat::JsonLines, &mut expected).unwrap();
+
+        let mut slow = SlowWriter {
+            out: Vec::new(),
+            largest_call: 0,
+        };
+        let stats = export_streaming(db, StreamFormat::JsonLines, &mut slow).unwrap();
+        assert_eq!(stats.rows, 5000);
+        assert_eq!(slow.out, expected);
+        assert!(slow.largest_call <= BUFFER_SIZE);
+    }
+
+    #[test]
+    fn test_offset_past_4gib() {
+        let buf = database(100);
+        let db = Database::new(&buf);
+        let mut expected = Vec::new();
+        export_streaming(db, StreamFormat::Csv, &mut expected).unwrap();
+
+        // Start the count just below 4 GiB, as if that much was written before
+        let base = u64::from(u32::MAX) - 3;
+        let mut sink =
+            StreamSink::new(Vec::new(), StreamFormat::Csv, StreamOptions::default()).unwrap();
+        sink.out.get_mut().bytes = base;
+        export_to_sink(db.tables().unwrap().iter(), &mut sink, &()).unwrap();
+        let stats = sink.stats();
+        assert_eq!(stats.rows, 100);
+        assert_eq!(stats.bytes, base + expected.len() as u64);
+        assert!(stats.bytes > u64::from(u32::MAX));
+        assert_eq!(sink.out.get_ref().inner, expected);
+    }
+
+    #[test]
+    fn test_order_independent_of_buckets() {
+        use crate::fdb::testing::SampleDatabase;
+
+        let columns = [("id", ValueType::Integer), ("name", ValueType::Text)];
+        let copy = |buckets| {
+            let db = SampleDatabase::new().table("Objects", &columns);
+            db.buckets(buckets).rows(20).build()
+        };
+        let (small, large) = (copy(4), copy(32));
+        let export = |buf: &[u8], format, order| {
+            let options = StreamOptions {
+                order,
+                ..StreamOptions::default()
+            };
+            let tables = Database::new(buf).tables().unwrap();
+            let mut out = Vec::new();
+            export_tables_streaming_with(tables.iter(), format, &options, &mut out).unwrap();
+            out
+        };
+        for &format in &[StreamFormat::Csv, StreamFormat::JsonLines] {
+            let file_order = export(&small, format, IterOrder::File);
+            assert_ne!(file_order, export(&large, format, IterOrder::File));
+            let sorted = export(&small, format, IterOrder::PrimaryKey);
+            assert_eq!(sorted, export(&large, format, IterOrder::PrimaryKey));
+            assert_ne!(sorted, file_order);
+        }
+    }
+
+    #[test]
+    fn test_index_strings() {
+        let buf = crate::fdb::testing::objects(50);
+        let export = |format, index_strings| {
+            let options = StreamOptions {
+                index_strings,
+                ..StreamOptions::default()
+            };
+            let tables = Database::new(&buf).tables().unwrap();
+            let mut out = Vec::new();
+            export_tables_streaming_with(tables.iter(), format, &options, &mut out).unwrap();
+            out
+        };
+        for &format in &[StreamFormat::Csv, StreamFormat::JsonLines] {
+            assert_eq!(export(format, true), export(format, false));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde-derives")]
+    fn test_varchar_hex() {
+        use std::convert::TryFrom;
+
+        use crate::fdb::{json::varchar_hex, testing::SampleDatabase};
+
+        let columns = [("id", ValueType::Integer), ("data", ValueType::VarChar)];
+        let payload = core::Field::encode_varchar_base64(b"\x00\x01LEGO bricks\xff");
+        let buf = SampleDatabase::new()
+            .table("Objects", &columns)
+            .row(vec![core::Field::Integer(1), payload.clone()])
+            .row(vec![core::Field::Integer(2), core::Field::Nothing])
+            .build();
+        let options = StreamOptions {
+            varchar_hex: true,
+            order: IterOrder::PrimaryKey,
+            ..StreamOptions::default()
+        };
+        let tables = Database::new(&buf).tables().unwrap();
+        let mut out = Vec::new();
+        let format = StreamFormat::JsonLines;
+        export_tables_streaming_with(tables.iter(), format, &options, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<serde_json::Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines[0]["data"], varchar_hex(&payload).unwrap());
+        let field = core::Field::try_from((&lines[0]["data"], ValueType::VarChar)).unwrap();
+        assert_eq!(field, payload);
+        assert!(lines[1]["data"].is_null());
+    }
+
+    /// Counts the bytes and fails after a limit, like a closed pipe
+    struct Sink {
+        bytes: u64,
+        limit: u64,
+    }
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.bytes >= self.limit {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            let len = buf.len().min(4096);
+            self.bytes += len as u64;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Pipes more than 2 GiB through a slow writer
+    ///
+    /// This takes a while, run it with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_multi_gb() {
+        let buf = database(100_000);
+        let db = Database::new(&buf);
+        let tables = db.tables().unwrap();
+        let limit = 2 << 30;
+        let mut sink = Sink { bytes: 0, limit };
+        let repeated = std::iter::repeat_with(|| tables.get(0).unwrap());
+        let result = export_tables_streaming(repeated, StreamFormat::Csv, &mut sink);
+        match result {
+            Err(StreamError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::BrokenPipe),
+            _ => panic!("expected the sink to stop the export"),
+        }
+        assert!(sink.bytes >= limit);
+    }
+}
@@ -0,0 +1,218 @@
+//! # Golden files for regression tests
+//!
+//! Instead of comparing complete outputs, a regression test stores the digest
+//! of an output (e.g. a [`schema_dump`] or a [`jsonl_export`]) in a small
+//! golden file and compares later runs against it. To record the digests after
+//! an intended change, run the tests with `ASSEMBLY_BLESS=1`.
+//!
+//! The golden files for the synthetic databases of this module are part of the
+//! repository in `res/golden`. Contributors with a copy of the game can point
+//! [`FDB_FIXTURE_VAR`] to its `cdclient.fdb` to run the same checks against
+//! it. As the digests depend on the version of the client, the golden files
+//! for it are written to the folder in [`GOLDEN_DIR_VAR`] and not committed.
+//!
+//! ```
+//! use assembly_data::fdb::{mem::Database, testing::{golden, objects}};
+//!
+//! let buf = objects(10);
+//! let dump = golden::schema_dump(Database::new(&buf)).unwrap();
+//! assert!(dump.starts_with("Objects: 10 rows, 16 buckets\n"));
+//! assert_eq!(golden::digest(b"").len(), 16);
+//! ```
+
+use std::{
+    env,
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use assembly_core::{buffer::CastError, displaydoc::Display};
+use thiserror::Error;
+
+use crate::fdb::{
+    mem::Database,
+    stream::{export_streaming, StreamError, StreamFormat},
+};
+
+/// The variable with the path of an FDB file from the game
+pub const FDB_FIXTURE_VAR: &str = "ASSEMBLY_FDB_FIXTURE";
+/// The variable with the folder for the golden files of external fixtures
+pub const GOLDEN_DIR_VAR: &str = "ASSEMBLY_GOLDEN_DIR";
+/// The variable that makes [`Golden::check`] record digests instead
+pub const BLESS_VAR: &str = "ASSEMBLY_BLESS";
+
+/// Errors from [`Golden::check`]
+#[derive(Debug, Error, Display)]
+#[non_exhaustive]
+pub enum GoldenError {
+    /// Failed to access the golden file: {0}
+    Io(#[from] io::Error),
+    /// No golden file for {0:?}, run with `ASSEMBLY_BLESS=1` to create it
+    Missing(String),
+    /// The digest of {name:?} changed from {expected} to {actual}
+    Mismatch {
+        /// The name of the output
+        name: String,
+        /// The digest in the golden file
+        expected: String,
+        /// The digest of the output
+        actual: String,
+    },
+}
+
+/// Compute the 64-bit FNV-1a hash of `data`, as 16 hex digits
+///
+/// This is not a cryptographic hash, but it is stable across platforms and
+/// versions of Rust, which is all that golden files need.
+pub fn digest(data: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in data {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// List the tables of a database with their row and bucket counts and columns
+pub fn schema_dump(db: Database<'_>) -> Result<String, CastError> {
+    let mut out = String::new();
+    for table in db.tables()?.iter() {
+        let table = table?;
+        let rows = table.row_iter().count();
+        let buckets = table.bucket_count();
+        writeln!(out, "{}: {} rows, {} buckets", table.name(), rows, buckets).unwrap();
+        for column in table.column_iter() {
+            writeln!(out, "  {} {}", column.name(), column.value_type()).unwrap();
+        }
+    }
+    Ok(out)
+}
+
+/// Export all rows of a database as JSON lines, see [`StreamFormat::JsonLines`]
+pub fn jsonl_export(db: Database<'_>) -> Result<Vec<u8>, StreamError> {
+    let mut out = Vec::new();
+    export_streaming(db, StreamFormat::JsonLines, &mut out)?;
+    Ok(out)
+}
+
+/// The path of the FDB file in [`FDB_FIXTURE_VAR`], if it is set
+pub fn fdb_fixture() -> Option<PathBuf> {
+    env::var_os(FDB_FIXTURE_VAR).map(PathBuf::from)
+}
+
+/// A folder of golden files
+#[derive(Debug, Clone)]
+pub struct Golden {
+    dir: PathBuf,
+    bless: bool,
+}
+
+impl Golden {
+    /// Use the golden files in `dir`
+    ///
+    /// If [`BLESS_VAR`] is set, [`Golden::check`] records the digests instead.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            bless: env::var_os(BLESS_VAR).is_some(),
+        }
+    }
+
+    /// Use the golden files in [`GOLDEN_DIR_VAR`], if it is set
+    pub fn from_env() -> Option<Self> {
+        env::var_os(GOLDEN_DIR_VAR).map(Self::new)
+    }
+
+    /// Set whether to record the digests instead of comparing them
+    pub fn bless(mut self, bless: bool) -> Self {
+        self.bless = bless;
+        self
+    }
+
+    /// The path of the golden file for `name`
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.digest", name))
+    }
+
+    /// Compare the digest of `output` with the golden file for `name`
+    pub fn check(&self, name: &str, output: &[u8]) -> Result<(), GoldenError> {
+        let actual = digest(output);
+        let path = self.path(name);
+        if self.bless {
+            fs::create_dir_all(&self.dir)?;
+            fs::write(&path, format!("{}\n", actual))?;
+            return Ok(());
+        }
+        let expected = match fs::read_to_string(&path) {
+            Ok(text) => text.trim().to_owned(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(GoldenError::Missing(name.to_owned()))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if expected == actual {
+            Ok(())
+        } else {
+            Err(GoldenError::Mismatch {
+                name: name.to_owned(),
+                expected,
+                actual,
+            })
+        }
+    }
+
+    /// Check the [`schema_dump`] and the [`jsonl_export`] of a database
+    ///
+    /// The golden files are called `<prefix>.schema` and `<prefix>.jsonl`.
+    pub fn check_database(&self, prefix: &str, db: Database<'_>) -> Result<(), GoldenError> {
+        let dump = schema_dump(db).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.check(&format!("{}.schema", prefix), dump.as_bytes())?;
+        let export = jsonl_export(db).map_err(io::Error::other)?;
+        self.check(&format!("{}.jsonl", prefix), &export)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fdb::testing::objects;
+
+    fn repo_golden() -> Golden {
+        Golden::new(concat!(env!("CARGO_MANIFEST_DIR"), "/res/golden"))
+    }
+
+    #[test]
+    fn test_golden_sample() {
+        let buf = objects(100);
+        repo_golden()
+            .check_database("objects-100", Database::new(&buf))
+            .unwrap();
+
+        let dir = env::temp_dir().join(format!("assembly-golden-{}", std::process::id()));
+        let golden = Golden::new(&dir).bless(false);
+        assert!(matches!(
+            golden.check("a", b"A"),
+            Err(GoldenError::Missing(_))
+        ));
+        golden.clone().bless(true).check("a", b"A").unwrap();
+        golden.check("a", b"A").unwrap();
+        assert!(matches!(
+            golden.check("a", b"B"),
+            Err(GoldenError::Mismatch { .. })
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Checks the database in [`FDB_FIXTURE_VAR`] against [`GOLDEN_DIR_VAR`]
+    #[test]
+    fn test_golden_client() {
+        let (path, golden) = match (fdb_fixture(), Golden::from_env()) {
+            (Some(path), Some(golden)) => (path, golden),
+            _ => return,
+        };
+        let buf = fs::read(&path).unwrap();
+        let prefix = path.file_stem().unwrap().to_string_lossy();
+        golden.check_database(&prefix, Database::new(&buf)).unwrap();
+    }
+}
@@ -0,0 +1,226 @@
+//! # Small FDB files for tests and examples
+//!
+//! The game files are copyrighted, so tests and documentation can't rely on
+//! them. This module builds small but valid FDB files in memory instead,
+//! using the [`store`] writer. The rows are placed into the buckets for the
+//! hash of their first field, like in the game files, so lookups by primary
+//! key work as expected.
+//!
+//! This module is available in the tests of this crate and with the
+//! `testing` feature. See [`golden`] for regression tests with the outputs of
+//! these files, or of a file from the game.
+//!
+//! ```
+//! use assembly_data::fdb::{
+//!     common::ValueType,
+//!     core::Field,
+//!     mem::Database,
+//!     testing::SampleDatabase,
+//! };
+//!
+//! let buf = SampleDatabase::new()
+//!     .table("Objects", &[("id", ValueType::Integer), ("name", ValueType::Text)])
+//!     .row(vec![Field::Integer(1), Field::Text(String::from("Brick"))])
+//!     .build();
+//!
+//! let db = Database::new(&buf);
+//! let table = db.tables().unwrap().by_name("Objects").unwrap().unwrap();
+//! assert_eq!(table.index_iter(1).count(), 1);
+//! ```
+
+use assembly_core::hash::{fdb_bucket, fdb_int_hash, fdb_text_hash};
+
+use super::{
+    common::{Latin1String, ValueType},
+    core::Field,
+    store,
+};
+
+#[cfg(feature = "fdb-mem")]
+pub mod golden;
+
+struct SampleTable {
+    name: String,
+    columns: Vec<(String, ValueType)>,
+    /// The rows, with the bucket to put them into if not the one for their hash
+    rows: Vec<(Option<usize>, Vec<Field>)>,
+    bucket_count: Option<usize>,
+}
+
+impl SampleTable {
+    fn into_store(self) -> store::Table {
+        let default = self.rows.len().next_power_of_two();
+        let bucket_count = self.bucket_count.unwrap_or(default);
+        let mut table = store::Table::new(bucket_count);
+        for (name, value_type) in self.columns {
+            table.push_column(Latin1String::encode(&name), value_type);
+        }
+        for (bucket, row) in self.rows {
+            let hash = match row.first() {
+                Some(Field::Integer(i)) => fdb_int_hash(*i),
+                Some(Field::Text(s)) | Some(Field::VarChar(s)) => {
+                    fdb_text_hash(Latin1String::encode(s).as_bytes())
+                }
+                _ => 0,
+            };
+            let bucket = bucket.unwrap_or_else(|| fdb_bucket(hash, bucket_count));
+            table.push_row(bucket, &row);
+        }
+        table
+    }
+}
+
+/// A builder for an FDB file, see the [module documentation](self)
+#[derive(Default)]
+pub struct SampleDatabase {
+    tables: Vec<SampleTable>,
+}
+
+impl SampleDatabase {
+    /// Create a builder without any tables
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a table with the given columns
+    ///
+    /// The following calls to [`SampleDatabase::row`] add rows to this table.
+    pub fn table(mut self, name: &str, columns: &[(&str, ValueType)]) -> Self {
+        self.tables.push(SampleTable {
+            name: name.to_owned(),
+            columns: columns.iter().map(|(n, t)| (n.to_string(), *t)).collect(),
+            rows: Vec::new(),
+            bucket_count: None,
+        });
+        self
+    }
+
+    /// Set the number of buckets of the last table
+    ///
+    /// By default, this is the number of rows rounded up to a power of two.
+    /// A table without buckets can't have any rows.
+    pub fn buckets(mut self, bucket_count: usize) -> Self {
+        self.last_table().bucket_count = Some(bucket_count);
+        self
+    }
+
+    /// Add a row to the last table
+    ///
+    /// ## Panics
+    ///
+    /// Panics if no table was added yet.
+    pub fn row(mut self, fields: Vec<Field>) -> Self {
+        self.last_table().rows.push((None, fields));
+        self
+    }
+
+    /// Add a row to the bucket with the given index of the last table
+    ///
+    /// This ignores the hash of the first field, e.g. to test how broken files
+    /// are handled.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if no table was added yet.
+    pub fn row_in_bucket(mut self, bucket: usize, fields: Vec<Field>) -> Self {
+        self.last_table().rows.push((Some(bucket), fields));
+        self
+    }
+
+    /// Add `count` generated rows to the last table
+    ///
+    /// The value of each field is derived from the index of the row and the
+    /// type of its column, so integer primary keys are unique.
+    pub fn rows(mut self, count: usize) -> Self {
+        let table = self.last_table();
+        for index in 0..count {
+            let row = table
+                .columns
+                .iter()
+                .map(|(name, value_type)| sample_field(name, *value_type, index))
+                .collect();
+            table.rows.push((None, row));
+        }
+        self
+    }
+
+    fn last_table(&mut self) -> &mut SampleTable {
+        self.tables
+            .last_mut()
+            .expect("call `SampleDatabase::table` first")
+    }
+
+    /// Write the FDB file into a buffer
+    pub fn build(self) -> Vec<u8> {
+        let mut db = store::Database::new();
+        for table in self.tables {
+            let name = Latin1String::encode(&table.name).into_owned();
+            db.push_table(name, table.into_store());
+        }
+        let mut buf = Vec::with_capacity(db.compute_size());
+        db.write(&mut buf).expect("writing to a Vec doesn't fail");
+        buf
+    }
+}
+
+fn sample_field(column: &str, value_type: ValueType, index: usize) -> Field {
+    match value_type {
+        ValueType::Nothing => Field::Nothing,
+        ValueType::Integer => Field::Integer(index as i32),
+        ValueType::Float => Field::Float(index as f32 / 2.0),
+        ValueType::Text => Field::Text(format!("{} {}", column, index)),
+        ValueType::Boolean => Field::Boolean(index % 2 == 1),
+        ValueType::BigInt => Field::BigInt(index as i64 * 1_000_000_007),
+        ValueType::VarChar => Field::VarChar(format!("<{} index=\"{}\"/>", column, index)),
+    }
+}
+
+/// A database with one `Objects` table of `rows` generated rows
+///
+/// The table has the columns `id` (`INTEGER`), `name` (`TEXT`), `scale`
+/// (`FLOAT`) and `enabled` (`BOOLEAN`).
+pub fn objects(rows: usize) -> Vec<u8> {
+    SampleDatabase::new()
+        .table(
+            "Objects",
+            &[
+                ("id", ValueType::Integer),
+                ("name", ValueType::Text),
+                ("scale", ValueType::Float),
+                ("enabled", ValueType::Boolean),
+            ],
+        )
+        .rows(rows)
+        .build()
+}
+
+#[cfg(all(test, feature = "fdb-mem"))]
+mod tests {
+    use super::*;
+    use crate::fdb::mem::{Database, Field as MemField};
+
+    #[test]
+    fn test_sample_database() {
+        let buf = SampleDatabase::new()
+            .table("Zones", &[("name", ValueType::Text)])
+            .buckets(3)
+            .rows(5)
+            .table("Objects", &[("id", ValueType::Integer)])
+            .build();
+        let db = Database::new(&buf);
+        let tables = db.tables().unwrap();
+        assert_eq!(tables.len(), 2);
+        let zones = tables.by_name("Zones").unwrap().unwrap();
+        assert_eq!(zones.bucket_count(), 3);
+        assert_eq!(zones.row_iter().count(), 5);
+        let objects_table = tables.by_name("Objects").unwrap().unwrap();
+        assert_eq!(objects_table.row_iter().count(), 0);
+
+        let buf = objects(100);
+        let tables = Database::new(&buf).tables().unwrap();
+        let table = tables.get(0).unwrap().unwrap();
+        assert_eq!(table.bucket_count(), 128);
+        let row = table.index_iter(42).next().unwrap();
+        assert_eq!(row.field_at(3), Some(MemField::Boolean(false)));
+    }
+}
@@ -0,0 +1,135 @@
+//! # Collecting warnings
+//!
+//! Code that reads or converts a database in a lenient way, like
+//! [`mem::open`][super::mem::open], doesn't stop at the first problem. Instead,
+//! it records what it skipped or noticed in a [`Warnings`] sink, with a
+//! [`Severity`] for each entry, so that the caller can report them afterwards.
+//!
+//! ```
+//! use assembly_data::fdb::warnings::{Severity, Warnings};
+//!
+//! let mut warnings = Warnings::new();
+//! warnings.push(Severity::Info, "bucket 3 has 70 rows");
+//! warnings.push(Severity::Error, "row 12 is out of bounds");
+//!
+//! assert_eq!(warnings.max_severity(), Some(Severity::Error));
+//! assert_eq!(warnings.at_least(Severity::Warning).count(), 1);
+//! assert_eq!(warnings.iter().next().unwrap().to_string(), "info: bucket 3 has 70 rows");
+//! ```
+
+use std::{fmt, slice, vec};
+
+/// How serious a warning is, ordered from least to most serious
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Something unusual that doesn't affect the data, e.g. a slow lookup
+    Info,
+    /// Something that affects some APIs, but no data was lost
+    Warning,
+    /// Some data could not be read and was skipped
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A single entry of [`Warnings`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning<T> {
+    /// How serious the warning is
+    pub severity: Severity,
+    /// What happened
+    pub kind: T,
+}
+
+/// Formats the warning as `severity: kind`
+impl<T: fmt::Display> fmt::Display for Warning<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.kind)
+    }
+}
+
+/// A list of warnings, in the order they were recorded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warnings<T> {
+    items: Vec<Warning<T>>,
+}
+
+impl<T> Default for Warnings<T> {
+    fn default() -> Self {
+        Self { items: Vec::new() }
+    }
+}
+
+impl<T> Warnings<T> {
+    /// Create an empty list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a warning
+    pub fn push(&mut self, severity: Severity, kind: T) {
+        self.items.push(Warning { severity, kind });
+    }
+
+    /// Get the number of warnings
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Check whether there are no warnings
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Iterate over all warnings
+    pub fn iter(&self) -> slice::Iter<'_, Warning<T>> {
+        self.items.iter()
+    }
+
+    /// Iterate over the warnings with a severity of at least `min`
+    pub fn at_least(&self, min: Severity) -> impl Iterator<Item = &Warning<T>> {
+        self.items.iter().filter(move |w| w.severity >= min)
+    }
+
+    /// Get the number of warnings with exactly the given severity
+    pub fn count(&self, severity: Severity) -> usize {
+        self.items.iter().filter(|w| w.severity == severity).count()
+    }
+
+    /// Get the highest severity of all warnings, if there are any
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.items.iter().map(|w| w.severity).max()
+    }
+}
+
+impl<T> Extend<Warning<T>> for Warnings<T> {
+    fn extend<I: IntoIterator<Item = Warning<T>>>(&mut self, iter: I) {
+        self.items.extend(iter)
+    }
+}
+
+impl<T> IntoIterator for Warnings<T> {
+    type Item = Warning<T>;
+    type IntoIter = vec::IntoIter<Warning<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Warnings<T> {
+    type Item = &'a Warning<T>;
+    type IntoIter = slice::Iter<'a, Warning<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
@@ -0,0 +1,39 @@
+//! # Animation groups
+//!
+//! The `animationGroupIDs` column of the `RenderComponent` table contains a
+//! comma separated list of IDs, which reference the `animationGroupID` column
+//! of the `Animations` table.
+
+use super::{parse_i32, split_list, ParseResult};
+
+/// A list of animation group IDs
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AnimationGroups(Vec<i32>);
+
+impl AnimationGroups {
+    /// Parse a list like `1,2,3`
+    ///
+    /// An empty string is an empty list.
+    pub fn parse(text: &str) -> ParseResult<Self> {
+        split_list(text)
+            .map(parse_i32)
+            .collect::<ParseResult<_>>()
+            .map(AnimationGroups)
+    }
+
+    /// Get the IDs
+    pub fn ids(&self) -> &[i32] {
+        &self.0
+    }
+
+    /// Check whether the list contains the ID
+    pub fn contains(&self, id: i32) -> bool {
+        self.0.contains(&id)
+    }
+}
+
+impl From<AnimationGroups> for Vec<i32> {
+    fn from(groups: AnimationGroups) -> Self {
+        groups.0
+    }
+}
@@ -0,0 +1,311 @@
+//! # Skill behavior trees
+//!
+//! A skill in `SkillBehavior` points to a root behavior. Each behavior has a
+//! template in `BehaviorTemplate` (named in `BehaviorTemplateName`) and a list
+//! of numeric parameters in `BehaviorParameter`. Some of these parameters are
+//! the IDs of other behaviors, e.g. `action` or `on_success`, which turns the
+//! behaviors into a graph.
+//!
+//! [`load_skill`] and [`BehaviorTree::load`] follow these links and load every
+//! reachable behavior exactly once, so cycles in the data are not a problem.
+//! Use [`BehaviorTree::has_cycle`] to check whether the graph is a tree.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use assembly_core::buffer::CastError;
+
+use super::rows::Columns;
+use crate::fdb::mem::{Field, Table, Tables};
+
+/// Check whether a parameter with this name refers to another behavior
+///
+/// This matches names like `action`, `miss action`, `behavior 1` or
+/// `on_success`.
+pub fn is_link_parameter(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.contains("action") || name.starts_with("behavior") || name.starts_with("on_")
+}
+
+/// A single behavior, with its template and parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct Behavior {
+    /// The ID of the behavior
+    pub id: i32,
+    /// The ID of the template, e.g. `1` for a basic attack
+    pub template: Option<i32>,
+    /// The name of the template
+    pub template_name: Option<String>,
+    /// The effect that is played
+    pub effect_id: Option<i32>,
+    /// The handle of the effect
+    pub effect_handle: Option<String>,
+    /// The parameters that are not links, by name
+    pub parameters: BTreeMap<String, f32>,
+    /// The links to other behaviors, by parameter name
+    pub links: BTreeMap<String, i32>,
+}
+
+/// All behaviors reachable from a root behavior
+#[derive(Debug, Clone, PartialEq)]
+pub struct BehaviorTree {
+    /// The ID of the root behavior
+    pub root: i32,
+    /// The behaviors, by ID
+    pub behaviors: BTreeMap<i32, Behavior>,
+    /// IDs that are linked, but have no row in `BehaviorTemplate`
+    pub missing: BTreeSet<i32>,
+}
+
+/// A skill with its behavior tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skill {
+    /// The ID of the skill
+    pub id: i32,
+    /// The imagination needed to cast the skill
+    pub imagination_cost: i32,
+    /// The cooldown in seconds
+    pub cooldown: Option<f32>,
+    /// The behaviors of the skill
+    pub tree: BehaviorTree,
+}
+
+struct BehaviorTables<'a> {
+    templates: Option<(Table<'a>, Columns)>,
+    names: Option<(Table<'a>, Columns)>,
+    parameters: Option<(Table<'a>, Columns)>,
+}
+
+fn with_columns(table: Option<Table<'_>>) -> Option<(Table<'_>, Columns)> {
+    table.map(|t| {
+        let columns = Columns::new(&t);
+        (t, columns)
+    })
+}
+
+impl<'a> BehaviorTables<'a> {
+    fn new(tables: Tables<'a>) -> Result<Self, CastError> {
+        Ok(Self {
+            templates: with_columns(tables.by_name("BehaviorTemplate").transpose()?),
+            names: with_columns(tables.by_name("BehaviorTemplateName").transpose()?),
+            parameters: with_columns(tables.by_name("BehaviorParameter").transpose()?),
+        })
+    }
+
+    fn template_name(&self, template: i32) -> Option<String> {
+        let (table, cols) = self.names.as_ref()?;
+        let row = table
+            .index_iter(template as u32)
+            .find(|r| cols.int(*r, "templateID") == Some(template))?;
+        cols.text(row, "name")
+    }
+
+    fn load(&self, id: i32) -> Option<Behavior> {
+        let (table, cols) = self.templates.as_ref()?;
+        let row = table
+            .index_iter(id as u32)
+            .find(|r| cols.int(*r, "behaviorID") == Some(id))?;
+        let template = cols.int(row, "templateID");
+        let mut behavior = Behavior {
+            id,
+            template,
+            template_name: template.and_then(|t| self.template_name(t)),
+            effect_id: cols.id(row, "effectID"),
+            effect_handle: cols.text(row, "effectHandle"),
+            parameters: BTreeMap::new(),
+            links: BTreeMap::new(),
+        };
+        if let Some((table, cols)) = &self.parameters {
+            for row in table.index_iter(id as u32) {
+                if cols.int(row, "behaviorID") != Some(id) {
+                    continue;
+                }
+                let name = match cols.text(row, "parameterID") {
+                    Some(name) => name,
+                    None => continue,
+                };
+                let value = match cols.field(row, "value") {
+                    Some(Field::Float(v)) => v,
+                    Some(Field::Integer(v)) => v as f32,
+                    _ => continue,
+                };
+                if is_link_parameter(&name) && value > 0.0 && value.fract() == 0.0 {
+                    behavior.links.insert(name, value as i32);
+                } else {
+                    behavior.parameters.insert(name, value);
+                }
+            }
+        }
+        Some(behavior)
+    }
+}
+
+impl BehaviorTree {
+    /// Load all behaviors that are reachable from `root`
+    pub fn load(tables: Tables<'_>, root: i32) -> Result<Self, CastError> {
+        let source = BehaviorTables::new(tables)?;
+        let mut tree = BehaviorTree {
+            root,
+            behaviors: BTreeMap::new(),
+            missing: BTreeSet::new(),
+        };
+        let mut queue = VecDeque::new();
+        queue.push_back(root);
+        while let Some(id) = queue.pop_front() {
+            if tree.behaviors.contains_key(&id) || tree.missing.contains(&id) {
+                continue;
+            }
+            match source.load(id) {
+                Some(behavior) => {
+                    queue.extend(behavior.links.values().copied());
+                    tree.behaviors.insert(id, behavior);
+                }
+                None => {
+                    tree.missing.insert(id);
+                }
+            }
+        }
+        Ok(tree)
+    }
+
+    /// Get the behaviors that `id` links to, by parameter name
+    pub fn children(&self, id: i32) -> impl Iterator<Item = (&str, i32)> {
+        self.behaviors
+            .get(&id)
+            .into_iter()
+            .flat_map(|b| b.links.iter().map(|(name, id)| (name.as_str(), *id)))
+    }
+
+    /// Check whether a behavior can be reached from itself
+    pub fn has_cycle(&self) -> bool {
+        // 1 = on the current path, 2 = done
+        let mut state: BTreeMap<i32, u8> = BTreeMap::new();
+        let mut stack = vec![(self.root, false)];
+        while let Some((id, finished)) = stack.pop() {
+            if finished {
+                state.insert(id, 2);
+                continue;
+            }
+            match state.get(&id) {
+                Some(1) => return true,
+                Some(_) => continue,
+                None => {}
+            }
+            state.insert(id, 1);
+            stack.push((id, true));
+            for (_, child) in self.children(id) {
+                match state.get(&child) {
+                    Some(1) => return true,
+                    Some(_) => {}
+                    None => stack.push((child, false)),
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Load the skill `id` from `SkillBehavior`, with its behavior tree
+///
+/// Returns `None` if there is no `SkillBehavior` table, or no row for `id` in
+/// it.
+pub fn load_skill(tables: Tables<'_>, id: i32) -> Result<Option<Skill>, CastError> {
+    let skills = match tables.by_name("SkillBehavior").transpose()? {
+        Some(table) => table,
+        None => return Ok(None),
+    };
+    let cols = Columns::new(&skills);
+    let row = match skills
+        .index_iter(id as u32)
+        .find(|r| cols.int(*r, "skillID") == Some(id))
+    {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    let root = cols.int(row, "behaviorID").unwrap_or_default();
+    Ok(Some(Skill {
+        id,
+        imagination_cost: cols.int(row, "imaginationcost").unwrap_or_default(),
+        cooldown: cols.field(row, "cooldown").and_then(|f| f.into_opt_float()),
+        tree: BehaviorTree::load(tables, root)?,
+    }))
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{common::ValueType, core::Field, mem, testing::SampleDatabase};
+
+    #[test]
+    fn test_load_skill() {
+        use ValueType::{Float, Integer, Text};
+        let int = Field::Integer;
+        let param =
+            |id: i32, name: &str, v: f32| vec![int(id), Field::Text(name.into()), Field::Float(v)];
+
+        let buf = SampleDatabase::new()
+            .table(
+                "SkillBehavior",
+                &[
+                    ("skillID", Integer),
+                    ("behaviorID", Integer),
+                    ("imaginationcost", Integer),
+                ],
+            )
+            .row(vec![int(1), int(10), int(3)])
+            .table(
+                "BehaviorTemplate",
+                &[
+                    ("behaviorID", Integer),
+                    ("templateID", Integer),
+                    ("effectID", Integer),
+                ],
+            )
+            .row(vec![int(10), int(1), int(0)])
+            .row(vec![int(11), int(2), int(55)])
+            .table(
+                "BehaviorTemplateName",
+                &[("templateID", Integer), ("name", Text)],
+            )
+            .row(vec![int(1), Field::Text("BasicAttack".into())])
+            .table(
+                "BehaviorParameter",
+                &[
+                    ("behaviorID", Integer),
+                    ("parameterID", Text),
+                    ("value", Float),
+                ],
+            )
+            .row(param(10, "on_success", 11.0))
+            .row(param(10, "min damage", 2.0))
+            .row(param(11, "action", 10.0))
+            .row(param(11, "behavior 1", 99.0))
+            .row(param(11, "radius", 1.5))
+            .build();
+        let tables = mem::Database::new(&buf).tables().unwrap();
+
+        let skill = load_skill(tables, 1).unwrap().unwrap();
+        assert_eq!(skill.imagination_cost, 3);
+        let tree = &skill.tree;
+        assert_eq!(tree.root, 10);
+        assert_eq!(
+            tree.behaviors.keys().copied().collect::<Vec<_>>(),
+            vec![10, 11]
+        );
+        assert_eq!(tree.missing.iter().copied().collect::<Vec<_>>(), vec![99]);
+
+        let root = &tree.behaviors[&10];
+        assert_eq!(root.template_name.as_deref(), Some("BasicAttack"));
+        assert_eq!(root.effect_id, None);
+        assert_eq!(root.parameters["min damage"], 2.0);
+        assert_eq!(
+            tree.children(10).collect::<Vec<_>>(),
+            vec![("on_success", 11)]
+        );
+        assert_eq!(tree.behaviors[&11].effect_id, Some(55));
+        assert!(tree.has_cycle());
+
+        let sub = BehaviorTree::load(tables, 11).unwrap();
+        assert_eq!(sub.behaviors.len(), 2);
+        assert!(load_skill(tables, 2).unwrap().is_none());
+    }
+}
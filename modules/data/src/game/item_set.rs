@@ -0,0 +1,175 @@
+//! # Item sets
+//!
+//! An item set lists its items in the `ItemSets` table, and the skills that
+//! are unlocked by wearing 2 to 6 of them in `ItemSetSkills`. The name of the
+//! set is in the locale (`ItemSets_{id}_kitName`), the names of the items
+//! under `Objects_{lot}_name`. [`load_item_set`] joins these into a single
+//! [`ItemSet`].
+
+use assembly_core::buffer::CastError;
+
+use super::{
+    parse_i32,
+    rows::{translate, Columns, Locale},
+    split_list,
+};
+use crate::{fdb::mem::Tables, xml::locale_key::LocaleKey};
+
+/// An item of a set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemSetItem {
+    /// The object template (LOT) of the item
+    pub lot: i32,
+    /// The translated name
+    pub name: Option<String>,
+}
+
+/// The skills unlocked by wearing a number of items of a set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemSetBonus {
+    /// The number of items that need to be equipped
+    pub pieces: u8,
+    /// The ID of the skill set in `ItemSetSkills`
+    pub skill_set: i32,
+    /// The IDs of the skills
+    pub skills: Vec<i32>,
+}
+
+/// An item set with its items and bonus skills
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemSet {
+    /// The ID of the set
+    pub id: i32,
+    /// The faction kit this set belongs to, if any
+    pub kit_type: Option<i32>,
+    /// The rank of the kit
+    pub kit_rank: Option<i32>,
+    /// The items, in the order of the table
+    pub items: Vec<ItemSetItem>,
+    /// The bonus skills, by number of items
+    pub bonuses: Vec<ItemSetBonus>,
+    /// The translated name
+    pub name: Option<String>,
+}
+
+/// Load the item set `id` with its skills, and its names from the `locale`
+///
+/// Returns `None` if there is no `ItemSets` table, or no row for `id` in it.
+/// Items that can't be parsed are skipped.
+pub fn load_item_set(
+    tables: Tables<'_>,
+    id: i32,
+    locale: Option<&Locale>,
+) -> Result<Option<ItemSet>, CastError> {
+    let sets = match tables.by_name("ItemSets").transpose()? {
+        Some(table) => table,
+        None => return Ok(None),
+    };
+    let cols = Columns::new(&sets);
+    let row = match sets
+        .index_iter(id as u32)
+        .find(|row| cols.int(*row, "setID") == Some(id))
+    {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+
+    let items = cols
+        .text(row, "itemIDs")
+        .as_deref()
+        .map(split_list)
+        .into_iter()
+        .flatten()
+        .filter_map(|text| parse_i32(text).ok())
+        .map(|lot| ItemSetItem {
+            lot,
+            name: translate(locale, &LocaleKey::ObjectName(lot as u32)),
+        })
+        .collect();
+
+    let skills = tables.by_name("ItemSetSkills").transpose()?;
+    let bonuses = (2..=6)
+        .filter_map(|pieces| {
+            let skill_set = cols.id(row, &format!("skillSetWith{}", pieces))?;
+            let skills = match &skills {
+                Some(table) => {
+                    let skill_cols = Columns::new(table);
+                    table
+                        .index_iter(skill_set as u32)
+                        .filter(|r| skill_cols.int(*r, "SkillSetID") == Some(skill_set))
+                        .filter_map(|r| skill_cols.int(r, "SkillID"))
+                        .collect()
+                }
+                None => Vec::new(),
+            };
+            Some(ItemSetBonus {
+                pieces,
+                skill_set,
+                skills,
+            })
+        })
+        .collect();
+
+    Ok(Some(ItemSet {
+        id,
+        kit_type: cols.id(row, "kitType"),
+        kit_rank: cols.id(row, "kitRank"),
+        items,
+        bonuses,
+        name: translate(locale, &LocaleKey::ItemSetName(id as u32)),
+    }))
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{common::ValueType, core::Field, mem, testing::SampleDatabase};
+
+    #[test]
+    fn test_load_item_set() {
+        let buf = SampleDatabase::new()
+            .table(
+                "ItemSets",
+                &[
+                    ("setID", ValueType::Integer),
+                    ("itemIDs", ValueType::Text),
+                    ("skillSetWith2", ValueType::Integer),
+                    ("skillSetWith3", ValueType::Integer),
+                ],
+            )
+            .row(vec![
+                Field::Integer(1),
+                Field::Text("7415, 7416,7417".into()),
+                Field::Integer(-1),
+                Field::Integer(9),
+            ])
+            .table(
+                "ItemSetSkills",
+                &[
+                    ("SkillSetID", ValueType::Integer),
+                    ("SkillID", ValueType::Integer),
+                ],
+            )
+            .row(vec![Field::Integer(9), Field::Integer(394)])
+            .row(vec![Field::Integer(9), Field::Integer(581)])
+            .build();
+        let tables = mem::Database::new(&buf).tables().unwrap();
+
+        let mut locale = Locale::new();
+        locale.insert("ItemSets_1_kitName".into(), "Knight".into());
+        locale.insert("Objects_7416_name".into(), "Knight Helm".into());
+
+        let set = load_item_set(tables, 1, Some(&locale)).unwrap().unwrap();
+        assert_eq!(set.name.as_deref(), Some("Knight"));
+        let lots: Vec<_> = set.items.iter().map(|i| i.lot).collect();
+        assert_eq!(lots, vec![7415, 7416, 7417]);
+        assert_eq!(set.items[1].name.as_deref(), Some("Knight Helm"));
+        assert_eq!(set.bonuses.len(), 1);
+        assert_eq!(set.bonuses[0].pieces, 3);
+        let mut skill_ids = set.bonuses[0].skills.clone();
+        skill_ids.sort_unstable();
+        assert_eq!(skill_ids, vec![394, 581]);
+
+        assert!(load_item_set(tables, 2, None).unwrap().is_none());
+    }
+}
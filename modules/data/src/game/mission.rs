@@ -0,0 +1,220 @@
+//! # Missions
+//!
+//! A mission is spread over the `Missions` table, its tasks in `MissionTasks`
+//! and its texts in the locale (`Missions_{id}_name`, `MissionText_{id}_*` and
+//! `MissionTasks_{uid}_description`). [`load_mission`] joins these into a
+//! single [`Mission`].
+
+use std::collections::BTreeMap;
+
+use assembly_core::buffer::CastError;
+
+use super::rows::{translate, Columns, Locale};
+use crate::{
+    fdb::mem::Tables,
+    xml::locale_key::{LocaleKey, MissionTextState},
+};
+
+/// An item that is given as a reward
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MissionReward {
+    /// The object template (LOT) of the item
+    pub lot: i32,
+    /// The number of items
+    pub count: i32,
+}
+
+/// A task of a mission, from the `MissionTasks` table
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissionTask {
+    /// The unique ID of the task
+    pub uid: i32,
+    /// The type of the task, e.g. `0` for smashing objects
+    pub task_type: i32,
+    /// The target of the task, e.g. an object template
+    pub target: Option<i32>,
+    /// Additional targets, as a list of IDs
+    pub target_group: Option<String>,
+    /// How often the task needs to be done
+    pub target_value: i32,
+    /// The translated description
+    pub description: Option<String>,
+}
+
+/// A mission with its tasks and texts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mission {
+    /// The ID of the mission
+    pub id: i32,
+    /// The category, e.g. `Avant Gardens`
+    pub defined_type: Option<String>,
+    /// The sub-category
+    pub defined_subtype: Option<String>,
+    /// The object template that offers the mission
+    pub offer_object: Option<i32>,
+    /// The object template that accepts the mission
+    pub target_object: Option<i32>,
+    /// The missions that need to be completed first, as written in the table
+    pub prerequisites: Option<String>,
+    /// Whether this is a mission, as opposed to an achievement
+    pub is_mission: bool,
+    /// Whether the mission can be done more than once
+    pub repeatable: bool,
+    /// The coins given on completion
+    pub reward_currency: i32,
+    /// The items given on completion
+    pub rewards: Vec<MissionReward>,
+    /// The tasks, in the order of the table
+    pub tasks: Vec<MissionTask>,
+    /// The translated name
+    pub name: Option<String>,
+    /// The translated texts for each state
+    pub texts: BTreeMap<MissionTextState, String>,
+}
+
+/// Load the mission `id` with its tasks, and its texts from the `locale`
+///
+/// Returns `None` if there is no `Missions` table, or no row for `id` in it.
+pub fn load_mission(
+    tables: Tables<'_>,
+    id: i32,
+    locale: Option<&Locale>,
+) -> Result<Option<Mission>, CastError> {
+    let missions = match tables.by_name("Missions").transpose()? {
+        Some(table) => table,
+        None => return Ok(None),
+    };
+    let cols = Columns::new(&missions);
+    let row = match missions
+        .index_iter(id as u32)
+        .find(|row| cols.int(*row, "id") == Some(id))
+    {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+
+    let rewards = (1..=4)
+        .filter_map(|i| {
+            let lot = cols.id(row, &format!("reward_item{}", i))?;
+            let count = cols.int(row, &format!("reward_item{}_count", i));
+            Some(MissionReward {
+                lot,
+                count: count.filter(|&c| c > 0).unwrap_or(1),
+            })
+        })
+        .collect();
+
+    let mut tasks = Vec::new();
+    if let Some(table) = tables.by_name("MissionTasks").transpose()? {
+        let task_cols = Columns::new(&table);
+        for task in table.index_iter(id as u32) {
+            if task_cols.int(task, "id") != Some(id) {
+                continue;
+            }
+            let uid = task_cols.int(task, "uid").unwrap_or_default();
+            tasks.push(MissionTask {
+                uid,
+                task_type: task_cols.int(task, "taskType").unwrap_or_default(),
+                target: task_cols.id(task, "target"),
+                target_group: task_cols.text(task, "targetGroup"),
+                target_value: task_cols.int(task, "targetValue").unwrap_or_default(),
+                description: translate(locale, &LocaleKey::MissionTaskDescription(uid as u32)),
+            });
+        }
+    }
+
+    let texts = MissionTextState::ALL
+        .iter()
+        .filter_map(|&state| {
+            let text = translate(locale, &LocaleKey::MissionText(id as u32, state))?;
+            Some((state, text))
+        })
+        .collect();
+
+    Ok(Some(Mission {
+        id,
+        defined_type: cols.text(row, "defined_type"),
+        defined_subtype: cols.text(row, "defined_subtype"),
+        offer_object: cols.id(row, "offer_objectID"),
+        target_object: cols.id(row, "target_objectID"),
+        prerequisites: cols.text(row, "prereqMissionID"),
+        is_mission: cols.bool(row, "isMission"),
+        repeatable: cols.bool(row, "repeatable"),
+        reward_currency: cols.int(row, "reward_currency").unwrap_or_default(),
+        rewards,
+        tasks,
+        name: translate(locale, &LocaleKey::MissionName(id as u32)),
+        texts,
+    }))
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::{common::ValueType, core::Field, mem, testing::SampleDatabase};
+
+    #[test]
+    fn test_load_mission() {
+        let int = Field::Integer;
+        let buf = SampleDatabase::new()
+            .table(
+                "Missions",
+                &[
+                    ("id", ValueType::Integer),
+                    ("defined_type", ValueType::Text),
+                    ("offer_objectID", ValueType::Integer),
+                    ("reward_item1", ValueType::Integer),
+                    ("reward_item1_count", ValueType::Integer),
+                    ("reward_item2", ValueType::Integer),
+                    ("isMission", ValueType::Boolean),
+                ],
+            )
+            .row(vec![
+                int(173),
+                Field::Text("Avant Gardens".into()),
+                int(-1),
+                int(6326),
+                int(0),
+                int(-1),
+                Field::Boolean(true),
+            ])
+            .table(
+                "MissionTasks",
+                &[
+                    ("id", ValueType::Integer),
+                    ("taskType", ValueType::Integer),
+                    ("targetValue", ValueType::Integer),
+                    ("uid", ValueType::Integer),
+                ],
+            )
+            .row(vec![int(173), int(0), int(10), int(42)])
+            .row(vec![int(1), int(0), int(1), int(43)])
+            .build();
+        let tables = mem::Database::new(&buf).tables().unwrap();
+
+        let mut locale = Locale::new();
+        locale.insert("Missions_173_name".into(), "Smash it".into());
+        locale.insert("MissionText_173_offer".into(), "Please?".into());
+        locale.insert("MissionTasks_42_description".into(), "Smash 10".into());
+
+        let mission = load_mission(tables, 173, Some(&locale)).unwrap().unwrap();
+        assert_eq!(mission.defined_type.as_deref(), Some("Avant Gardens"));
+        assert_eq!(mission.offer_object, None);
+        assert!(mission.is_mission && !mission.repeatable);
+        assert_eq!(
+            mission.rewards,
+            vec![MissionReward {
+                lot: 6326,
+                count: 1
+            }]
+        );
+        assert_eq!(mission.tasks.len(), 1);
+        assert_eq!(mission.tasks[0].target_value, 10);
+        assert_eq!(mission.tasks[0].description.as_deref(), Some("Smash 10"));
+        assert_eq!(mission.name.as_deref(), Some("Smash it"));
+        assert_eq!(mission.texts[&MissionTextState::Offer], "Please?");
+        assert_eq!(mission.texts.len(), 1);
+
+        assert!(load_mission(tables, 1, None).unwrap().is_none());
+    }
+}
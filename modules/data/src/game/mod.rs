@@ -0,0 +1,71 @@
+//! # Typed views of structured text fields in the core database
+//!
+//! Many columns in the `CDClient` contain strings that encode more than one
+//! value, such as the path and options of a render asset, a list of animation
+//! groups or the parameters of a skill. This module contains parsers that turn
+//! these strings into rust values, as well as the default names for values of
+//! well-known ID columns.
+//!
+//! With the `fdb-mem` feature, the [`mission`], [`item_set`] and [`behavior`]
+//! modules load higher-level models that join multiple tables and the locale.
+//!
+//! This module is only available with the `game` feature.
+
+#![warn(missing_docs)]
+
+pub mod anim;
+#[cfg(feature = "fdb-mem")]
+pub mod behavior;
+#[cfg(feature = "fdb-mem")]
+pub mod item_set;
+#[cfg(feature = "fdb-mem")]
+pub mod mission;
+pub mod names;
+pub mod render;
+#[cfg(feature = "fdb-mem")]
+mod rows;
+pub mod skill;
+
+#[cfg(feature = "fdb-mem")]
+pub use rows::Locale;
+
+use std::num::{ParseFloatError, ParseIntError};
+
+use assembly_core::displaydoc::Display;
+use thiserror::Error;
+
+#[derive(Debug, Error, Display, PartialEq)]
+/// Errors when parsing a structured text field
+#[non_exhaustive]
+pub enum ParseError {
+    /// The string is empty
+    Empty,
+    /// Invalid integer {0:?}: {1}
+    Integer(String, #[source] ParseIntError),
+    /// Invalid float {0:?}: {1}
+    Float(String, #[source] ParseFloatError),
+    /// Expected `key=value`, got {0:?}
+    MissingValue(String),
+    /// Expected 1 or 3 components, got {0}
+    ComponentCount(usize),
+}
+
+/// Result when parsing a structured text field
+pub type ParseResult<T> = Result<T, ParseError>;
+
+fn parse_i32(text: &str) -> ParseResult<i32> {
+    text.parse()
+        .map_err(|e| ParseError::Integer(text.to_owned(), e))
+}
+
+fn parse_f32(text: &str) -> ParseResult<f32> {
+    text.parse()
+        .map_err(|e| ParseError::Float(text.to_owned(), e))
+}
+
+/// Split a list at `,` or `:`, trimming whitespace and skipping empty entries
+fn split_list(text: &str) -> impl Iterator<Item = &str> {
+    text.split(&[',', ':'][..])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+}
@@ -0,0 +1,82 @@
+//! # Names for well-known values in the core database
+//!
+//! This is the default set of [`ValueNames`] for the `CDClient`. It covers the
+//! component types in `ComponentsRegistry` and the item types of the
+//! `ItemComponent`.
+
+use crate::fdb::names::ValueNames;
+
+/// The names of the well-known component types
+pub const COMPONENT_TYPES: &[(i32, &str)] = &[
+    (1, "ControllablePhysics"),
+    (2, "Render"),
+    (3, "SimplePhysics"),
+    (4, "Character"),
+    (5, "Script"),
+    (6, "Bouncer"),
+    (7, "Destructible"),
+    (9, "Skill"),
+    (10, "Spawner"),
+    (11, "Item"),
+    (16, "Vendor"),
+    (17, "Inventory"),
+    (23, "Collectible"),
+    (26, "Pet"),
+    (35, "Minifig"),
+    (40, "PhantomPhysics"),
+    (48, "Rebuild"),
+    (53, "Package"),
+    (60, "BaseCombatAI"),
+];
+
+/// The names of the item types
+pub const ITEM_TYPES: &[(i32, &str)] = &[
+    (1, "Brick"),
+    (2, "Hat"),
+    (3, "Hair"),
+    (4, "Neck"),
+    (5, "LeftHand"),
+    (6, "RightHand"),
+    (7, "Legs"),
+    (8, "LeftTrinket"),
+    (9, "RightTrinket"),
+    (10, "Behavior"),
+    (11, "Property"),
+    (12, "Model"),
+    (13, "Collectible"),
+    (14, "Consumable"),
+    (15, "Chest"),
+    (16, "Egg"),
+    (17, "PetFood"),
+    (18, "QuestObject"),
+    (19, "PetInventoryItem"),
+    (20, "Package"),
+    (21, "LootModel"),
+    (22, "Vehicle"),
+    (23, "LupModel"),
+    (24, "Mount"),
+];
+
+/// The default names for the `CDClient`
+pub fn cdclient() -> ValueNames {
+    ValueNames::new()
+        .with_names("ComponentsRegistry", "component_type", COMPONENT_TYPES)
+        .with_names("ItemComponent", "itemType", ITEM_TYPES)
+}
+
+#[cfg(all(test, feature = "fdb-core"))]
+mod tests {
+    use super::*;
+    use crate::fdb::core::Field;
+
+    #[test]
+    fn test_cdclient_names() {
+        let names = cdclient();
+        assert_eq!(names.get("ItemComponent", "itemType", 2), Some("Hat"));
+        let field = Field::Integer(11);
+        let shown = names.display("ComponentsRegistry", "component_type", &field);
+        assert_eq!(shown.to_string(), "11 (Item)");
+        let shown = names.display("ComponentsRegistry", "component_id", &field);
+        assert_eq!(shown.to_string(), "11");
+    }
+}
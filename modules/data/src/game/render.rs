@@ -0,0 +1,128 @@
+//! # Render assets and scales
+//!
+//! The `render_asset` column of the `RenderComponent` table contains a
+//! path to a model (`*.nif`) or an actor (`*.kfm`), relative to the `res`
+//! folder of the client. Some entries carry a `res\` or `.\` prefix,
+//! use forward slashes or mixed case.
+
+use assembly_core::types::Vector3f;
+
+use super::{parse_f32, split_list, ParseError, ParseResult};
+
+/// The kind of file referenced by a [`RenderAsset`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AssetKind {
+    /// A Gamebryo model (`*.nif`)
+    Model,
+    /// A Gamebryo actor file (`*.kfm`)
+    Actor,
+    /// A particle effect (`*.psb`)
+    Effect,
+    /// Any other file
+    Other,
+}
+
+/// A normalized render asset path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderAsset {
+    path: String,
+    kind: AssetKind,
+}
+
+impl RenderAsset {
+    /// Parse a render asset string
+    ///
+    /// The resulting path is lowercase, uses `\` as a separator and is
+    /// relative to the `res` folder.
+    pub fn parse(text: &str) -> ParseResult<Self> {
+        let mut path = text.trim().replace('/', "\\").to_ascii_lowercase();
+        for prefix in &[".\\", "\\", "res\\"] {
+            if path.starts_with(prefix) {
+                path.replace_range(..prefix.len(), "");
+            }
+        }
+        if path.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        let kind = match path.rsplit('.').next() {
+            Some("nif") => AssetKind::Model,
+            Some("kfm") => AssetKind::Actor,
+            Some("psb") => AssetKind::Effect,
+            _ => AssetKind::Other,
+        };
+        Ok(Self { path, kind })
+    }
+
+    /// The normalized path, relative to the `res` folder
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The first folder of the path, e.g. `mesh` or `animations`
+    pub fn root(&self) -> Option<&str> {
+        self.path.split_once('\\').map(|(root, _)| root)
+    }
+
+    /// The kind of the asset
+    pub fn kind(&self) -> AssetKind {
+        self.kind
+    }
+}
+
+/// A scale, as used in config strings
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Scale {
+    /// The same factor for all axes
+    Uniform(f32),
+    /// One factor per axis
+    PerAxis(Vector3f),
+}
+
+impl Scale {
+    /// Parse a scale from either `s` or `x,y,z` (or `x:y:z`)
+    pub fn parse(text: &str) -> ParseResult<Self> {
+        let parts = split_list(text)
+            .map(parse_f32)
+            .collect::<ParseResult<Vec<f32>>>()?;
+        match parts[..] {
+            [] => Err(ParseError::Empty),
+            [s] => Ok(Scale::Uniform(s)),
+            [x, y, z] => Ok(Scale::PerAxis(Vector3f { x, y, z })),
+            _ => Err(ParseError::ComponentCount(parts.len())),
+        }
+    }
+
+    /// Get the factors for each axis
+    pub fn to_vector(self) -> Vector3f {
+        match self {
+            Scale::Uniform(s) => Vector3f { x: s, y: s, z: s },
+            Scale::PerAxis(v) => v,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_asset() {
+        let asset = RenderAsset::parse("Res/Mesh\\Env\\Tree_01.NIF").unwrap();
+        assert_eq!(asset.path(), "mesh\\env\\tree_01.nif");
+        assert_eq!(asset.root(), Some("mesh"));
+        assert_eq!(asset.kind(), AssetKind::Model);
+        assert_eq!(RenderAsset::parse(" "), Err(ParseError::Empty));
+    }
+
+    #[test]
+    fn test_scale() {
+        assert_eq!(Scale::parse("1.5"), Ok(Scale::Uniform(1.5)));
+        let v = Vector3f {
+            x: 1.0,
+            y: 2.0,
+            z: 0.5,
+        };
+        assert_eq!(Scale::parse("1:2:0.5"), Ok(Scale::PerAxis(v)));
+        assert_eq!(Scale::parse("1,2"), Err(ParseError::ComponentCount(2)));
+    }
+}
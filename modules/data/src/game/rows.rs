@@ -0,0 +1,58 @@
+//! Access to the fields of a row by column name
+
+use std::collections::BTreeMap;
+
+use crate::{
+    fdb::mem::{Field, Row, Table},
+    xml::locale_key::LocaleKey,
+};
+
+/// A map from locale key to the translated text
+pub type Locale = BTreeMap<String, String>;
+
+/// The column names of a table
+pub(crate) struct Columns {
+    names: Vec<String>,
+}
+
+impl Columns {
+    pub(crate) fn new(table: &Table<'_>) -> Self {
+        let names = table.column_iter().map(|c| c.name().into_owned()).collect();
+        Self { names }
+    }
+
+    pub(crate) fn field<'a>(&self, row: Row<'a>, name: &str) -> Option<Field<'a>> {
+        let index = self.names.iter().position(|n| n == name)?;
+        row.field_at(index)
+    }
+
+    pub(crate) fn int(&self, row: Row<'_>, name: &str) -> Option<i32> {
+        self.field(row, name)?.into_opt_integer()
+    }
+
+    /// Get a positive ID, treating `0` and `-1` as none
+    pub(crate) fn id(&self, row: Row<'_>, name: &str) -> Option<i32> {
+        self.int(row, name).filter(|&id| id > 0)
+    }
+
+    pub(crate) fn bool(&self, row: Row<'_>, name: &str) -> bool {
+        match self.field(row, name) {
+            Some(Field::Boolean(b)) => b,
+            Some(Field::Integer(i)) => i != 0,
+            _ => false,
+        }
+    }
+
+    /// Get a non-empty string
+    pub(crate) fn text(&self, row: Row<'_>, name: &str) -> Option<String> {
+        let text = match self.field(row, name)? {
+            Field::Text(s) | Field::VarChar(s) => s.decode().into_owned(),
+            _ => return None,
+        };
+        Some(text).filter(|s| !s.trim().is_empty())
+    }
+}
+
+pub(crate) fn translate(locale: Option<&Locale>, key: &LocaleKey) -> Option<String> {
+    locale?.get(&key.to_string()).cloned()
+}
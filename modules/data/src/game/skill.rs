@@ -0,0 +1,68 @@
+//! # Skill parameter strings
+//!
+//! Some skill related columns contain a list of named numeric parameters,
+//! written as `name=value` pairs that are separated by `;` or `,`.
+
+use super::{parse_f32, ParseError, ParseResult};
+
+/// A single named parameter
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkillParameter {
+    /// The name of the parameter
+    pub name: String,
+    /// The value of the parameter
+    pub value: f32,
+}
+
+/// A list of skill parameters, in the order of the input
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SkillParameters(Vec<SkillParameter>);
+
+impl SkillParameters {
+    /// Parse a string like `range=10;delay=0.5`
+    ///
+    /// An empty string is an empty list.
+    pub fn parse(text: &str) -> ParseResult<Self> {
+        text.split(&[';', ','][..])
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|entry| {
+                let (name, value) = entry
+                    .split_once('=')
+                    .ok_or_else(|| ParseError::MissingValue(entry.to_owned()))?;
+                Ok(SkillParameter {
+                    name: name.trim().to_owned(),
+                    value: parse_f32(value.trim())?,
+                })
+            })
+            .collect::<ParseResult<_>>()
+            .map(SkillParameters)
+    }
+
+    /// Get the value of the first parameter with that name
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.0.iter().find(|p| p.name == name).map(|p| p.value)
+    }
+
+    /// Iterate over all parameters
+    pub fn iter(&self) -> std::slice::Iter<'_, SkillParameter> {
+        self.0.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skill_parameters() {
+        let params = SkillParameters::parse("range = 10; delay=0.5;").unwrap();
+        assert_eq!(params.get("range"), Some(10.0));
+        assert_eq!(params.get("delay"), Some(0.5));
+        assert_eq!(params.iter().count(), 2);
+        assert_eq!(
+            SkillParameters::parse("range"),
+            Err(ParseError::MissingValue(String::from("range")))
+        );
+    }
+}
@@ -1,4 +1,6 @@
 //! The Database parts of `assembly`
 
 pub mod fdb;
+#[cfg(feature = "game")]
+pub mod game;
 pub mod xml;
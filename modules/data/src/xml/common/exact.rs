@@ -12,6 +12,7 @@ use thiserror::Error;
 
 /// The errors for this module
 #[derive(Debug, Display, Error)]
+#[non_exhaustive]
 pub enum Error {
     /// Malformed XML
     Xml(#[from] XmlError),
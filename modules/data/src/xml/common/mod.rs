@@ -10,6 +10,7 @@ pub mod exact;
 
 /// A general error type
 #[derive(Debug, Display, Error)]
+#[non_exhaustive]
 pub enum XmlError {
     /// Failed to read the next XML event
     Reader(#[from] quick_xml::Error),
@@ -6,10 +6,13 @@
 
 use assembly_core::displaydoc::Display;
 use quick_xml::{events::Event, Reader};
-use std::{collections::HashMap, error::Error, fmt, io::BufRead, str::FromStr};
+use std::{collections::HashMap, error::Error, io::BufRead, str::FromStr};
 
 use super::common::{expect_elem, expect_named_elem, XmlError};
 
+#[cfg(feature = "serde-derives")]
+use std::fmt;
+
 #[cfg(feature = "serde-derives")]
 use serde::{
     de::{self, Unexpected, Visitor},
@@ -67,6 +70,7 @@ pub enum ValueType {
     DateTime,
 }
 
+#[cfg(feature = "serde-derives")]
 impl<'de> Deserialize<'de> for ValueType {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
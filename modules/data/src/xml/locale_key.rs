@@ -0,0 +1,237 @@
+//! # The keys of the `locale.xml` phrases
+//!
+//! Most phrases belong to a row in the database and have keys of the form
+//! `{table}_{id}_{column}`, e.g. `Objects_1727_name`. A [`LocaleKey`] builds
+//! these keys without string concatenation, and classifies existing keys:
+//!
+//! ```
+//! use assembly_data::xml::locale_key::{LocaleKey, MissionTextState};
+//!
+//! assert_eq!(LocaleKey::ObjectName(1727).to_string(), "Objects_1727_name");
+//!
+//! let key = LocaleKey::parse("MissionText_173_in_progress");
+//! assert_eq!(key, LocaleKey::MissionText(173, MissionTextState::InProgress));
+//! assert_eq!(key.table(), Some("MissionText"));
+//! ```
+
+use std::fmt;
+
+/// A column of the `MissionText` table that is localized
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MissionTextState {
+    /// `accept_chat_bubble`
+    AcceptChatBubble,
+    /// `chat_state_1` to `chat_state_4`
+    ChatState(u8),
+    /// `completion_succeed_tip`
+    CompletionSucceedTip,
+    /// `in_progress`
+    InProgress,
+    /// `offer`
+    Offer,
+    /// `ready_to_complete`
+    ReadyToComplete,
+}
+
+impl MissionTextState {
+    /// All states, in the order of the columns
+    pub const ALL: [Self; 9] = [
+        Self::AcceptChatBubble,
+        Self::ChatState(1),
+        Self::ChatState(2),
+        Self::ChatState(3),
+        Self::ChatState(4),
+        Self::CompletionSucceedTip,
+        Self::InProgress,
+        Self::Offer,
+        Self::ReadyToComplete,
+    ];
+
+    /// Parse the name of the column
+    pub fn from_column(column: &str) -> Option<Self> {
+        match column {
+            "accept_chat_bubble" => Some(Self::AcceptChatBubble),
+            "completion_succeed_tip" => Some(Self::CompletionSucceedTip),
+            "in_progress" => Some(Self::InProgress),
+            "offer" => Some(Self::Offer),
+            "ready_to_complete" => Some(Self::ReadyToComplete),
+            _ => match column.strip_prefix("chat_state_")?.parse() {
+                Ok(n @ 1..=4) => Some(Self::ChatState(n)),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl fmt::Display for MissionTextState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AcceptChatBubble => f.write_str("accept_chat_bubble"),
+            Self::ChatState(n) => write!(f, "chat_state_{}", n),
+            Self::CompletionSucceedTip => f.write_str("completion_succeed_tip"),
+            Self::InProgress => f.write_str("in_progress"),
+            Self::Offer => f.write_str("offer"),
+            Self::ReadyToComplete => f.write_str("ready_to_complete"),
+        }
+    }
+}
+
+/// A key of a phrase in the locale
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LocaleKey {
+    /// `Objects_{lot}_name`
+    ObjectName(u32),
+    /// `Objects_{lot}_description`
+    ObjectDescription(u32),
+    /// `Missions_{id}_name`
+    MissionName(u32),
+    /// `MissionText_{id}_{state}`
+    MissionText(u32, MissionTextState),
+    /// `MissionTasks_{uid}_description`
+    MissionTaskDescription(u32),
+    /// `ItemSets_{id}_kitName`
+    ItemSetName(u32),
+    /// Any other `{table}_{id}_{column}`
+    Table {
+        /// The name of the table
+        table: String,
+        /// The primary key of the row
+        id: u32,
+        /// The name of the column
+        column: String,
+    },
+    /// A key that doesn't belong to a row, e.g. `UI_OK`
+    Other(String),
+}
+
+impl LocaleKey {
+    /// Get the name of the table, if the key belongs to a row
+    pub fn table(&self) -> Option<&str> {
+        match self {
+            Self::ObjectName(_) | Self::ObjectDescription(_) => Some("Objects"),
+            Self::MissionName(_) => Some("Missions"),
+            Self::MissionText(..) => Some("MissionText"),
+            Self::MissionTaskDescription(_) => Some("MissionTasks"),
+            Self::ItemSetName(_) => Some("ItemSets"),
+            Self::Table { table, .. } => Some(table),
+            Self::Other(_) => None,
+        }
+    }
+
+    /// Get the primary key of the row, if the key belongs to one
+    pub fn id(&self) -> Option<u32> {
+        match self {
+            Self::ObjectName(id)
+            | Self::ObjectDescription(id)
+            | Self::MissionName(id)
+            | Self::MissionText(id, _)
+            | Self::MissionTaskDescription(id)
+            | Self::ItemSetName(id)
+            | Self::Table { id, .. } => Some(*id),
+            Self::Other(_) => None,
+        }
+    }
+
+    /// Classify a key
+    ///
+    /// Keys of rows in tables without a typed variant become
+    /// [`LocaleKey::Table`], everything else is [`LocaleKey::Other`].
+    pub fn parse(key: &str) -> Self {
+        Self::parse_row(key).unwrap_or_else(|| Self::Other(key.to_string()))
+    }
+
+    fn parse_row(key: &str) -> Option<Self> {
+        let mut parts = key.splitn(3, '_');
+        let table = parts.next()?;
+        let id_text = parts.next()?;
+        let column = parts.next()?;
+        if table.is_empty() || column.is_empty() {
+            return None;
+        }
+        let id: u32 = id_text.parse().ok()?;
+        if id.to_string() != id_text {
+            // e.g. leading zeros, which would not round-trip
+            return None;
+        }
+        let mission_text = match table {
+            "MissionText" => MissionTextState::from_column(column),
+            _ => None,
+        };
+        Some(match (table, column, mission_text) {
+            (_, _, Some(state)) => Self::MissionText(id, state),
+            ("Objects", "name", _) => Self::ObjectName(id),
+            ("Objects", "description", _) => Self::ObjectDescription(id),
+            ("Missions", "name", _) => Self::MissionName(id),
+            ("MissionTasks", "description", _) => Self::MissionTaskDescription(id),
+            ("ItemSets", "kitName", _) => Self::ItemSetName(id),
+            _ => Self::Table {
+                table: table.to_string(),
+                id,
+                column: column.to_string(),
+            },
+        })
+    }
+}
+
+impl From<&str> for LocaleKey {
+    fn from(key: &str) -> Self {
+        Self::parse(key)
+    }
+}
+
+impl fmt::Display for LocaleKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ObjectName(id) => write!(f, "Objects_{}_name", id),
+            Self::ObjectDescription(id) => write!(f, "Objects_{}_description", id),
+            Self::MissionName(id) => write!(f, "Missions_{}_name", id),
+            Self::MissionText(id, state) => write!(f, "MissionText_{}_{}", id, state),
+            Self::MissionTaskDescription(id) => write!(f, "MissionTasks_{}_description", id),
+            Self::ItemSetName(id) => write!(f, "ItemSets_{}_kitName", id),
+            Self::Table { table, id, column } => write!(f, "{}_{}_{}", table, id, column),
+            Self::Other(key) => f.write_str(key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let keys = [
+            "Objects_1727_name",
+            "Objects_1727_description",
+            "Missions_173_name",
+            "MissionText_173_chat_state_3",
+            "MissionText_173_unknown_column",
+            "MissionTasks_9_description",
+            "ItemSets_2_kitName",
+            "Preconditions_42_FailureReason",
+            "Objects_007_name",
+            "UI_OK",
+            "Objects",
+        ];
+        for key in keys.iter() {
+            assert_eq!(LocaleKey::parse(key).to_string(), *key);
+        }
+        assert_eq!(
+            LocaleKey::parse("MissionText_173_chat_state_3"),
+            LocaleKey::MissionText(173, MissionTextState::ChatState(3))
+        );
+        assert_eq!(
+            LocaleKey::parse("Preconditions_42_FailureReason").table(),
+            Some("Preconditions")
+        );
+        assert_eq!(
+            LocaleKey::parse("MissionText_173_chat_state_5").id(),
+            Some(173)
+        );
+        assert_eq!(
+            LocaleKey::parse("Objects_007_name"),
+            LocaleKey::Other("Objects_007_name".to_string())
+        );
+        assert_eq!(LocaleKey::parse("UI_OK").table(), None);
+    }
+}
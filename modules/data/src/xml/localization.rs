@@ -2,3 +2,534 @@
 //!
 //! This is used in:
 //! - the `locale/locale.xml` file
+//!
+//! The file lists the available locales, followed by one `<phrase>` per key
+//! with a `<translation>` for each locale. As the file is large, the writers
+//! in this module stream their output instead of building the document in
+//! memory:
+//!
+//! - [`LocaleWriter`] writes a new file from a sequence of [`Phrase`]s
+//! - [`patch_locale`] copies an existing file, replacing and inserting the
+//!   phrases of a [`LocalePatch`]
+//!
+//! [`read_locale`] loads the texts of a single locale by key.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, BufRead, Seek, SeekFrom, Write},
+};
+
+use assembly_core::displaydoc::Display;
+use quick_xml::{
+    events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event},
+    Reader, Writer,
+};
+use thiserror::Error;
+
+/// The errors for this module
+#[derive(Debug, Display, Error)]
+#[non_exhaustive]
+pub enum LocaleError {
+    /// Failed to read or write XML
+    Xml(#[from] quick_xml::Error),
+    /// Failed to seek in the source
+    Io(#[from] io::Error),
+    /// Expected {expected} phrases, found {actual}
+    Count {
+        /// The number of phrases announced in the header
+        expected: usize,
+        /// The number of phrases that were written
+        actual: usize,
+    },
+    /// Missing attribute `{0}`
+    MissingAttribute(&'static str),
+}
+
+/// The result type for this module
+pub type Result<T> = std::result::Result<T, LocaleError>;
+
+/// A single phrase, with its translations by locale
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Phrase {
+    /// The key, e.g. `Objects_1727_name`
+    pub id: String,
+    /// The text for each locale, e.g. `en_US`
+    pub translations: BTreeMap<String, String>,
+}
+
+impl Phrase {
+    /// Create a phrase without translations
+    pub fn new<S: Into<String>>(id: S) -> Self {
+        Self {
+            id: id.into(),
+            translations: BTreeMap::new(),
+        }
+    }
+
+    /// Add a translation
+    pub fn with<L: Into<String>, T: Into<String>>(mut self, locale: L, text: T) -> Self {
+        self.translations.insert(locale.into(), text.into());
+        self
+    }
+}
+
+fn write_phrase<W: Write>(writer: &mut Writer<W>, phrase: &Phrase) -> Result<()> {
+    let start = BytesStart::borrowed_name(b"phrase").with_attributes(vec![("id", &*phrase.id)]);
+    writer.write_event(Event::Start(start))?;
+    for (locale, text) in &phrase.translations {
+        let start = BytesStart::borrowed_name(b"translation")
+            .with_attributes(vec![("locale", locale.as_str())]);
+        writer.write_event(Event::Start(start))?;
+        writer.write_event(Event::Text(BytesText::from_plain_str(text)))?;
+        writer.write_event(Event::End(BytesEnd::borrowed(b"translation")))?;
+    }
+    writer.write_event(Event::End(BytesEnd::borrowed(b"phrase")))?;
+    Ok(())
+}
+
+/// A streaming writer for a new `locale.xml` file
+///
+/// The number of phrases needs to be known in advance, because it is part of
+/// the header.
+///
+/// ```
+/// use assembly_data::xml::localization::{LocaleWriter, Phrase};
+///
+/// let mut writer = LocaleWriter::new(Vec::new(), &["en_US"], 1).unwrap();
+/// writer.write_phrase(&Phrase::new("Hello").with("en_US", "Hello & welcome")).unwrap();
+/// let bytes = writer.finish().unwrap();
+/// let text = String::from_utf8(bytes).unwrap();
+/// assert!(text.contains("<translation locale=\"en_US\">Hello &amp; welcome</translation>"));
+/// ```
+pub struct LocaleWriter<W: Write> {
+    writer: Writer<W>,
+    expected: usize,
+    actual: usize,
+}
+
+impl<W: Write> LocaleWriter<W> {
+    /// Write the header with the locales and the number of phrases
+    pub fn new(out: W, locales: &[&str], count: usize) -> Result<Self> {
+        let mut writer = Writer::new(out);
+        let decl = BytesDecl::new(b"1.0", Some(b"UTF-8"), None);
+        writer.write_event(Event::Decl(decl))?;
+        writer.write(b"\n")?;
+        let start =
+            BytesStart::borrowed_name(b"localization").with_attributes(vec![("version", "1.2")]);
+        writer.write_event(Event::Start(start))?;
+        writer.write(b"\n")?;
+        let num_locales = locales.len().to_string();
+        let start =
+            BytesStart::borrowed_name(b"locales").with_attributes(vec![("count", &*num_locales)]);
+        writer.write_event(Event::Start(start))?;
+        for locale in locales {
+            writer.write_event(Event::Start(BytesStart::borrowed_name(b"locale")))?;
+            writer.write_event(Event::Text(BytesText::from_plain_str(locale)))?;
+            writer.write_event(Event::End(BytesEnd::borrowed(b"locale")))?;
+        }
+        writer.write_event(Event::End(BytesEnd::borrowed(b"locales")))?;
+        writer.write(b"\n")?;
+        let num_phrases = count.to_string();
+        let start =
+            BytesStart::borrowed_name(b"phrases").with_attributes(vec![("count", &*num_phrases)]);
+        writer.write_event(Event::Start(start))?;
+        Ok(Self {
+            writer,
+            expected: count,
+            actual: 0,
+        })
+    }
+
+    /// Write the next phrase
+    pub fn write_phrase(&mut self, phrase: &Phrase) -> Result<()> {
+        if self.actual == self.expected {
+            return Err(LocaleError::Count {
+                expected: self.expected,
+                actual: self.actual + 1,
+            });
+        }
+        self.writer.write(b"\n")?;
+        write_phrase(&mut self.writer, phrase)?;
+        self.actual += 1;
+        Ok(())
+    }
+
+    /// Close the document and return the output
+    ///
+    /// Fails if fewer phrases were written than announced.
+    pub fn finish(mut self) -> Result<W> {
+        if self.actual != self.expected {
+            return Err(LocaleError::Count {
+                expected: self.expected,
+                actual: self.actual,
+            });
+        }
+        self.writer.write(b"\n")?;
+        let end = BytesEnd::borrowed(b"phrases");
+        self.writer.write_event(Event::End(end))?;
+        self.writer.write(b"\n")?;
+        let end = BytesEnd::borrowed(b"localization");
+        self.writer.write_event(Event::End(end))?;
+        self.writer.write(b"\n")?;
+        Ok(self.writer.into_inner())
+    }
+}
+
+/// A set of phrases to replace or insert with [`patch_locale`]
+#[derive(Debug, Clone, Default)]
+pub struct LocalePatch {
+    phrases: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl LocalePatch {
+    /// Create an empty patch
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the text of `id` for `locale`
+    pub fn insert<I, L, T>(&mut self, id: I, locale: L, text: T)
+    where
+        I: Into<String>,
+        L: Into<String>,
+        T: Into<String>,
+    {
+        let phrase = self.phrases.entry(id.into()).or_default();
+        phrase.insert(locale.into(), text.into());
+    }
+
+    /// Set all translations of a phrase
+    pub fn insert_phrase(&mut self, phrase: Phrase) {
+        let entry = self.phrases.entry(phrase.id).or_default();
+        entry.extend(phrase.translations);
+    }
+
+    /// Get the number of phrases in the patch
+    pub fn len(&self) -> usize {
+        self.phrases.len()
+    }
+
+    /// Check whether the patch is empty
+    pub fn is_empty(&self) -> bool {
+        self.phrases.is_empty()
+    }
+}
+
+/// The number of phrases changed by [`patch_locale`]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PatchStats {
+    /// Phrases that existed in the source
+    pub replaced: usize,
+    /// Phrases that were added at the end
+    pub inserted: usize,
+}
+
+fn phrase_id<B: BufRead>(start: &BytesStart, xml: &Reader<B>) -> Result<String> {
+    for attr in start.attributes() {
+        let attr = attr?;
+        if attr.key == b"id" {
+            return Ok(attr.unescape_and_decode_value(xml)?);
+        }
+    }
+    Err(LocaleError::MissingAttribute("id"))
+}
+
+/// Find the phrases of the patch that exist in the source
+fn existing_ids<B: BufRead>(source: B, patch: &LocalePatch) -> Result<BTreeSet<String>> {
+    let mut xml = Reader::from_reader(source);
+    let mut buf = Vec::new();
+    let mut found = BTreeSet::new();
+    loop {
+        match xml.read_event(&mut buf)? {
+            Event::Start(start) | Event::Empty(start) if start.name() == b"phrase" => {
+                let id = phrase_id(&start, &xml)?;
+                if patch.phrases.contains_key(&id) {
+                    found.insert(id);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(found)
+}
+
+fn translation_locale<B: BufRead>(start: &BytesStart, xml: &Reader<B>) -> Result<String> {
+    for attr in start.attributes() {
+        let attr = attr?;
+        if attr.key == b"locale" {
+            return Ok(attr.unescape_and_decode_value(xml)?);
+        }
+    }
+    Err(LocaleError::MissingAttribute("locale"))
+}
+
+/// Read the translations of a phrase, up to and including `</phrase>`
+fn read_translations<B: BufRead>(
+    xml: &mut Reader<B>,
+    buf: &mut Vec<u8>,
+    translations: &mut BTreeMap<String, String>,
+) -> Result<()> {
+    let mut locale = None;
+    loop {
+        match xml.read_event(buf)? {
+            Event::Start(start) if start.name() == b"translation" => {
+                let value = translation_locale(&start, xml)?;
+                translations.entry(value.clone()).or_default();
+                locale = Some(value);
+            }
+            Event::Empty(start) if start.name() == b"translation" => {
+                let value = translation_locale(&start, xml)?;
+                translations.entry(value).or_default();
+            }
+            Event::Text(text) => {
+                if let Some(locale) = &locale {
+                    let text = text.unescape_and_decode(xml)?;
+                    translations
+                        .entry(locale.clone())
+                        .or_default()
+                        .push_str(&text);
+                }
+            }
+            Event::End(end) if end.name() == b"translation" => locale = None,
+            Event::End(end) if end.name() == b"phrase" => break,
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(())
+}
+
+/// Read the texts of `locale` from a `locale.xml` file, by key
+///
+/// Phrases without a translation for `locale` are skipped.
+pub fn read_locale<B: BufRead>(source: B, locale: &str) -> Result<BTreeMap<String, String>> {
+    let mut xml = Reader::from_reader(source);
+    let mut buf = Vec::new();
+    let mut texts = BTreeMap::new();
+    loop {
+        match xml.read_event(&mut buf)? {
+            Event::Start(start) if start.name() == b"phrase" => {
+                let id = phrase_id(&start, &xml)?;
+                let mut translations = BTreeMap::new();
+                buf.clear();
+                read_translations(&mut xml, &mut buf, &mut translations)?;
+                if let Some(text) = translations.remove(locale) {
+                    texts.insert(id, text);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(texts)
+}
+
+/// Copy the `<phrases>` tag, adding `inserted` to its `count`
+fn phrases_start<B: BufRead>(
+    start: &BytesStart,
+    xml: &Reader<B>,
+    inserted: usize,
+) -> Result<BytesStart<'static>> {
+    let mut copy = BytesStart::owned_name(b"phrases".to_vec());
+    for attr in start.attributes() {
+        let attr = attr?;
+        if attr.key == b"count" {
+            let count = xml.decode(&attr.value).trim().parse::<usize>();
+            let count = count.unwrap_or(0) + inserted;
+            copy.push_attribute(("count", count.to_string().as_str()));
+        } else {
+            copy.push_attribute(attr);
+        }
+    }
+    Ok(copy)
+}
+
+/// Write the phrases of the patch that are not in the source
+fn write_new_phrases<W: Write>(
+    writer: &mut Writer<W>,
+    patch: &LocalePatch,
+    existing: &BTreeSet<String>,
+) -> Result<()> {
+    let new = patch
+        .phrases
+        .iter()
+        .filter(|(id, _)| !existing.contains(*id));
+    for (id, translations) in new {
+        let phrase = Phrase {
+            id: id.clone(),
+            translations: translations.clone(),
+        };
+        write_phrase(writer, &phrase)?;
+        writer.write(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Copy a `locale.xml` file from `source` to `out`, applying `patch`
+///
+/// Phrases of the patch that exist in the source keep the translations for
+/// locales that are not in the patch. All other phrases of the patch are
+/// appended to the list of phrases, and its `count` is updated. Everything
+/// else is copied as is.
+///
+/// The source is read twice, once to find the phrases that need to be
+/// inserted, and once to copy it.
+pub fn patch_locale<R, W>(mut source: R, out: W, patch: &LocalePatch) -> Result<PatchStats>
+where
+    R: BufRead + Seek,
+    W: Write,
+{
+    let existing = existing_ids(&mut source, patch)?;
+    source.seek(SeekFrom::Start(0))?;
+
+    let stats = PatchStats {
+        replaced: existing.len(),
+        inserted: patch.len() - existing.len(),
+    };
+    let mut xml = Reader::from_reader(source);
+    let mut writer = Writer::new(out);
+    let mut buf = Vec::new();
+    loop {
+        let event = xml.read_event(&mut buf)?;
+        match &event {
+            Event::Start(start) if start.name() == b"phrases" => {
+                let copy = phrases_start(start, &xml, stats.inserted)?;
+                writer.write_event(Event::Start(copy))?;
+            }
+            Event::Empty(start) if start.name() == b"phrases" => {
+                let copy = phrases_start(start, &xml, stats.inserted)?;
+                writer.write_event(Event::Start(copy))?;
+                writer.write(b"\n")?;
+                write_new_phrases(&mut writer, patch, &existing)?;
+                writer.write_event(Event::End(BytesEnd::borrowed(b"phrases")))?;
+            }
+            Event::Start(start) if start.name() == b"phrase" => {
+                let id = phrase_id(start, &xml)?;
+                match patch.phrases.get(&id) {
+                    Some(translations) => {
+                        let mut phrase = Phrase::new(id);
+                        buf.clear();
+                        read_translations(&mut xml, &mut buf, &mut phrase.translations)?;
+                        phrase.translations.extend(translations.clone());
+                        write_phrase(&mut writer, &phrase)?;
+                    }
+                    None => writer.write_event(&event)?,
+                }
+            }
+            Event::Empty(start) if start.name() == b"phrase" => {
+                let id = phrase_id(start, &xml)?;
+                match patch.phrases.get(&id) {
+                    Some(translations) => {
+                        let phrase = Phrase {
+                            id,
+                            translations: translations.clone(),
+                        };
+                        write_phrase(&mut writer, &phrase)?;
+                    }
+                    None => writer.write_event(&event)?,
+                }
+            }
+            Event::End(end) if end.name() == b"phrases" => {
+                write_new_phrases(&mut writer, patch, &existing)?;
+                writer.write_event(&event)?;
+            }
+            Event::Eof => break,
+            _ => writer.write_event(&event)?,
+        }
+        buf.clear();
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_patch_locale() {
+        let mut writer = LocaleWriter::new(Vec::new(), &["en_US", "de_DE"], 2).unwrap();
+        let hello = Phrase::new("Hello")
+            .with("en_US", "Hello")
+            .with("de_DE", "Hallo");
+        writer.write_phrase(&hello).unwrap();
+        writer
+            .write_phrase(&Phrase::new("Bye").with("en_US", "Bye"))
+            .unwrap();
+        assert!(writer.write_phrase(&hello).is_err());
+        let source = writer.finish().unwrap();
+
+        let mut patch = LocalePatch::new();
+        patch.insert("Hello", "de_DE", "Guten <Tag>");
+        patch.insert_phrase(Phrase::new("New").with("en_US", "New"));
+        let mut out = Vec::new();
+        let stats = patch_locale(Cursor::new(&source), &mut out, &patch).unwrap();
+        assert_eq!(
+            stats,
+            PatchStats {
+                replaced: 1,
+                inserted: 1
+            }
+        );
+
+        let text = String::from_utf8(out).unwrap();
+        let expected = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<localization version=\"1.2\">
+<locales count=\"2\"><locale>en_US</locale><locale>de_DE</locale></locales>
+<phrases count=\"3\">
+<phrase id=\"Hello\"><translation locale=\"de_DE\">Guten &lt;Tag&gt;</translation><translation locale=\"en_US\">Hello</translation></phrase>
+<phrase id=\"Bye\"><translation locale=\"en_US\">Bye</translation></phrase>
+<phrase id=\"New\"><translation locale=\"en_US\">New</translation></phrase>
+</phrases>
+</localization>
+";
+        assert_eq!(text, expected);
+
+        let texts = read_locale(Cursor::new(&text), "de_DE").unwrap();
+        assert_eq!(texts.len(), 1);
+        assert_eq!(texts["Hello"], "Guten <Tag>");
+    }
+
+    #[test]
+    fn test_patch_locale_empty_tags() {
+        let source = "<localization version=\"1.2\">
+<locales count=\"1\"><locale>en_US</locale></locales>
+<phrases count=\"2\">
+<phrase id=\"Blank\"/>
+<phrase id=\"Kept\"><translation locale=\"en_US\"/></phrase>
+</phrases>
+</localization>
+";
+        let mut patch = LocalePatch::new();
+        patch.insert("Blank", "en_US", "Filled");
+        patch.insert("New", "en_US", "New");
+        let mut out = Vec::new();
+        let stats = patch_locale(Cursor::new(source), &mut out, &patch).unwrap();
+        assert_eq!(
+            stats,
+            PatchStats {
+                replaced: 1,
+                inserted: 1
+            }
+        );
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("<phrases count=\"3\">"));
+        let texts = read_locale(Cursor::new(&text), "en_US").unwrap();
+        assert_eq!(texts["Blank"], "Filled");
+        assert_eq!(texts["Kept"], "");
+        assert_eq!(texts["New"], "New");
+
+        let source = "<localization><phrases count=\"0\"/></localization>";
+        let mut out = Vec::new();
+        patch_locale(Cursor::new(source), &mut out, &patch).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("<phrases count=\"2\">"));
+        let texts = read_locale(Cursor::new(&text), "en_US").unwrap();
+        assert_eq!(texts.len(), 2);
+        assert_eq!(texts["Blank"], "Filled");
+    }
+}
@@ -15,6 +15,7 @@ pub mod database;
 pub mod env_data;
 pub mod hud;
 pub mod lego_primitive;
+pub mod locale_key;
 pub mod localization;
 pub mod modular_build;
 pub mod module_info;
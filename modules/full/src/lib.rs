@@ -1,16 +1,28 @@
 //! This crate is a collection of parsers and data types
 //! To enable reading of data from LEGO Universe game files.
+//!
+//! See the [`prelude`] for the most important types.
+
+#[cfg(feature = "modpack")]
+pub mod modpack;
+#[cfg(all(feature = "core", feature = "data", feature = "pack"))]
+pub mod ops;
+pub mod prelude;
 
 #[cfg(feature = "core")]
 pub use assembly_core as core;
-#[cfg(feature = "data")]
+#[cfg(any(feature = "fdb-core", feature = "fdb-mem"))]
 pub use assembly_data::fdb;
+#[cfg(feature = "game")]
+pub use assembly_data::game;
 #[cfg(feature = "data")]
 pub use assembly_data::xml;
 #[cfg(feature = "maps")]
 pub use assembly_maps::luz;
 #[cfg(feature = "maps")]
 pub use assembly_maps::lvl;
+#[cfg(feature = "maps")]
+pub use assembly_maps::terrain;
 #[cfg(feature = "pack")]
 pub use assembly_pack::pk;
 #[cfg(feature = "pack")]
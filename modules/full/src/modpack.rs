@@ -0,0 +1,487 @@
+//! # Packages for content updates
+//!
+//! A [`ModPackage`] bundles the changes of a mod or a server update: a patch
+//! for the database, new files for the pack files, and a name and version.
+//! It is stored as a pack file with these entries:
+//!
+//! - `manifest`: a text file with the name, version, and the size and SHA-256
+//!   hash of every other entry
+//! - `manifest.sig`: optional, the ed25519 public key (32 bytes) and the
+//!   signature of the manifest (64 bytes)
+//! - `patch.fdb`: optional, a database file with the rows to add or replace
+//! - `files/<path>`: one entry for every new file, by its canonical path
+//!
+//! [`ModPackage::read`] checks all hashes against the manifest, so a valid
+//! signature of the manifest (see [`ModPackage::verify`], with the `signing`
+//! feature) covers the whole package.
+//!
+//! The patch is applied with [`ModPackage::apply_patch`]: every row of the
+//! patch replaces the rows with the same primary key, and tables that don't
+//! exist yet are added. The files are added to a new pack file with
+//! [`ModPackage::write_entries`].
+//!
+//! This module requires the `modpack` feature.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::{self, BufRead, Read, Seek, Write},
+};
+
+use assembly_core::{displaydoc::Display, reader::FileError};
+use assembly_data::fdb::{
+    core::{pk_hash, Bucket, Field, Row, Schema, Table},
+    store,
+};
+use assembly_pack::{
+    crc::calculate_crc_normalized,
+    path::normalize,
+    pk::{
+        file::PKEntry,
+        reader::{PackFile, StreamError},
+        writer::PackWriter,
+    },
+};
+#[cfg(feature = "signing")]
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+const MANIFEST: &str = "manifest";
+const SIGNATURE: &str = "manifest.sig";
+const PATCH: &str = "patch.fdb";
+const FILES: &str = "files/";
+const HEADER: &str = "assembly-mod 1";
+
+/// Errors when reading, writing or applying a [`ModPackage`]
+#[derive(Debug, Error, Display)]
+#[non_exhaustive]
+pub enum ModError {
+    /// I/O error: {0}
+    Io(#[from] io::Error),
+    /// Failed to read package: {0}
+    File(#[from] FileError),
+    /// Failed to read entry {0:?}: {1:?}
+    Stream(String, StreamError),
+    /// Invalid manifest: {0}
+    Manifest(String),
+    /// Missing entry {0:?}
+    MissingEntry(String),
+    /// The hash of entry {0:?} doesn't match
+    Hash(String),
+    /// The package is not signed by the expected key
+    Unsigned,
+    /// The signature of the package is invalid
+    Signature,
+    /// The columns of table {0:?} don't match
+    Columns(String),
+    /// Cannot compute the bucket for key {1} in table {0:?}
+    Key(String, Field),
+}
+
+fn sha256(data: &[u8]) -> String {
+    let hash = Sha256::digest(data);
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Check that a value fits on one line of the manifest
+///
+/// Control characters are rejected, as a newline would start a new entry and
+/// a trailing `\r` would be lost when reading.
+fn check_value(key: &str, value: &str) -> Result<(), ModError> {
+    if value.chars().any(char::is_control) {
+        let msg = format!("{} {:?} contains control characters", key, value);
+        return Err(ModError::Manifest(msg));
+    }
+    Ok(())
+}
+
+/// The manifest as it is stored, with the public key and signature
+#[derive(Debug, Clone)]
+struct Signed {
+    manifest: Vec<u8>,
+    public_key: [u8; 32],
+    signature: [u8; 64],
+}
+
+/// A set of changes to the database and pack files, see the [module documentation](self)
+///
+/// ```
+/// use assembly::modpack::ModPackage;
+///
+/// let package = ModPackage::new("Bricks", "1.0").with_file("res/textures/brick.dds", b"DDS ".to_vec());
+/// let bytes = package.write(std::io::Cursor::new(Vec::new())).unwrap();
+/// let read = ModPackage::read(bytes).unwrap();
+/// assert_eq!(read.version, "1.0");
+/// assert_eq!(read.files().count(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ModPackage {
+    /// The name of the package
+    pub name: String,
+    /// The version of the package
+    pub version: String,
+    patch: Option<Vec<u8>>,
+    files: BTreeMap<String, Vec<u8>>,
+    signed: Option<Signed>,
+}
+
+impl ModPackage {
+    /// Create an empty package
+    pub fn new(name: &str, version: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            patch: None,
+            files: BTreeMap::new(),
+            signed: None,
+        }
+    }
+
+    /// Set the tables and rows to add to or replace in the database
+    pub fn with_patch(mut self, patch: &Schema) -> Self {
+        let mut fdb = Vec::new();
+        store::Database::from(patch)
+            .write(&mut fdb)
+            .expect("writing to a Vec can't fail");
+        self.patch = Some(fdb);
+        self
+    }
+
+    /// Add a file for the pack files, with a path relative to the client folder
+    pub fn with_file(mut self, path: &str, data: Vec<u8>) -> Self {
+        self.files.insert(normalize(path), data);
+        self
+    }
+
+    /// Get the files by their canonical path
+    pub fn files(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.files.iter().map(|(p, d)| (p.as_str(), d.as_slice()))
+    }
+
+    /// Get the patch for the database, if there is one
+    pub fn patch(&self) -> Result<Option<Schema>, ModError> {
+        match &self.patch {
+            Some(fdb) => Ok(Some(Schema::from_source(&fdb[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the public key that the package claims to be signed with
+    ///
+    /// This is only set for packages that were read, and not checked until
+    /// [`ModPackage::verify`] is called.
+    pub fn signer(&self) -> Option<[u8; 32]> {
+        self.signed.as_ref().map(|s| s.public_key)
+    }
+
+    fn manifest(&self) -> Result<Vec<u8>, ModError> {
+        check_value("name", &self.name)?;
+        check_value("version", &self.version)?;
+        let mut text = format!("{}\nname {}\nversion {}\n", HEADER, self.name, self.version);
+        if let Some(fdb) = &self.patch {
+            text += &format!("patch {} {}\n", fdb.len(), sha256(fdb));
+        }
+        for (path, data) in &self.files {
+            check_value("path", path)?;
+            text += &format!("file {} {} {}\n", data.len(), sha256(data), path);
+        }
+        Ok(text.into_bytes())
+    }
+
+    fn write_with<W, F>(&self, out: W, sign: F) -> Result<W, ModError>
+    where
+        W: Write,
+        F: FnOnce(&[u8]) -> Option<Vec<u8>>,
+    {
+        let manifest = self.manifest()?;
+        let mut writer = PackWriter::new(out)?;
+        writer.append_entry(MANIFEST, &manifest[..])?;
+        if let Some(signature) = sign(&manifest) {
+            writer.append_entry_with(SIGNATURE, &signature[..], false)?;
+        }
+        if let Some(fdb) = &self.patch {
+            writer.append_entry(PATCH, &fdb[..])?;
+        }
+        for (path, data) in &self.files {
+            writer.append_entry(&format!("{}{}", FILES, path), &data[..])?;
+        }
+        Ok(writer.finish()?)
+    }
+
+    /// Write the package without a signature, and return the inner stream
+    pub fn write<W: Write>(&self, out: W) -> Result<W, ModError> {
+        self.write_with(out, |_| None)
+    }
+
+    /// Write the package with a signature by `key`, and return the inner stream
+    #[cfg(feature = "signing")]
+    pub fn write_signed<W: Write>(&self, out: W, key: &SigningKey) -> Result<W, ModError> {
+        use ed25519_dalek::Signer;
+        self.write_with(out, |manifest| {
+            let mut signature = key.verifying_key().to_bytes().to_vec();
+            signature.extend_from_slice(&key.sign(manifest).to_bytes());
+            Some(signature)
+        })
+    }
+
+    /// Read a package and check the hashes of all entries
+    ///
+    /// The signature is not checked, see [`ModPackage::verify`].
+    pub fn read<R: BufRead + Seek>(mut reader: R) -> Result<Self, ModError> {
+        let mut pack = PackFile::open(&mut reader);
+        pack.check_magic()?;
+        let header = pack.get_header()?;
+        let entries: BTreeMap<u32, PKEntry> = pack
+            .get_entry_list(header.file_list_base_addr)?
+            .into_iter()
+            .map(|e| (e.crc, e))
+            .collect();
+        let mut read_entry = |name: &str| -> Result<Vec<u8>, ModError> {
+            let entry = entries
+                .get(&calculate_crc_normalized(name.as_bytes()))
+                .ok_or_else(|| ModError::MissingEntry(name.to_owned()))?;
+            let mut data = Vec::new();
+            pack.get_file_data(entry.clone())
+                .map_err(|e| ModError::Stream(name.to_owned(), e))?
+                .read_to_end(&mut data)?;
+            Ok(data)
+        };
+        let check = |name: &str, data: &[u8], size: &str, hash: &str| {
+            if data.len().to_string() == size && sha256(data) == hash {
+                Ok(())
+            } else {
+                Err(ModError::Hash(name.to_owned()))
+            }
+        };
+
+        let manifest = read_entry(MANIFEST)?;
+        let text = std::str::from_utf8(&manifest)
+            .map_err(|_| ModError::Manifest("not valid UTF-8".to_owned()))?;
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(ModError::Manifest("unknown format".to_owned()));
+        }
+        let mut package = Self::new("", "");
+        for line in lines {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            check_value(key, value)?;
+            match (key, value.splitn(3, ' ').collect::<Vec<_>>().as_slice()) {
+                ("name", _) => package.name = value.to_owned(),
+                ("version", _) => package.version = value.to_owned(),
+                ("patch", [size, hash]) => {
+                    let fdb = read_entry(PATCH)?;
+                    check(PATCH, &fdb, size, hash)?;
+                    package.patch = Some(fdb);
+                }
+                ("file", [size, hash, path]) => {
+                    let name = format!("{}{}", FILES, path);
+                    let data = read_entry(&name)?;
+                    check(&name, &data, size, hash)?;
+                    package.files.insert((*path).to_owned(), data);
+                }
+                _ => return Err(ModError::Manifest(format!("invalid line {:?}", line))),
+            }
+        }
+
+        match read_entry(SIGNATURE) {
+            Ok(signature) if signature.len() == 96 => {
+                let mut signed = Signed {
+                    manifest,
+                    public_key: [0; 32],
+                    signature: [0; 64],
+                };
+                signed.public_key.copy_from_slice(&signature[..32]);
+                signed.signature.copy_from_slice(&signature[32..]);
+                package.signed = Some(signed);
+            }
+            Ok(_) => return Err(ModError::Signature),
+            Err(ModError::MissingEntry(_)) => {}
+            Err(e) => return Err(e),
+        }
+        Ok(package)
+    }
+
+    /// Check that the package was read with a valid signature by `key`
+    #[cfg(feature = "signing")]
+    pub fn verify(&self, key: &VerifyingKey) -> Result<(), ModError> {
+        let signed = match &self.signed {
+            Some(signed) if signed.public_key == key.to_bytes() => signed,
+            _ => return Err(ModError::Unsigned),
+        };
+        let signature = ed25519_dalek::Signature::from_bytes(&signed.signature);
+        key.verify_strict(&signed.manifest, &signature)
+            .map_err(|_| ModError::Signature)
+    }
+
+    /// Apply the patch to `schema`, returning the number of rows that were added
+    ///
+    /// Rows of the patch replace all rows of the table with the same primary
+    /// key. Tables of the patch that are not in `schema` are added.
+    pub fn apply_patch(&self, schema: &mut Schema) -> Result<usize, ModError> {
+        let patch = match self.patch()? {
+            Some(patch) => patch,
+            None => return Ok(0),
+        };
+        let mut count = 0;
+        for table in patch.into_tables() {
+            let target = match schema.table_mut(table.name()) {
+                Some(target) => target,
+                None => {
+                    count += (&table).into_iter().count();
+                    schema.insert_table(table);
+                    continue;
+                }
+            };
+            let names = |t: &Table| {
+                t.columns()
+                    .iter()
+                    .map(|c| c.name.clone())
+                    .collect::<Vec<_>>()
+            };
+            if names(target) != names(&table) {
+                return Err(ModError::Columns(table.name().to_owned()));
+            }
+            let name = table.name().to_owned();
+            let rows: Vec<Row> = table
+                .into_buckets()
+                .into_iter()
+                .flat_map(Bucket::rows)
+                .collect();
+            let keys: HashSet<Field> = rows
+                .iter()
+                .filter_map(|r| r.fields().first().cloned())
+                .collect();
+            let buckets = target.buckets_mut();
+            for bucket in buckets.iter_mut() {
+                bucket
+                    .rows_mut()
+                    .retain(|r| r.fields().first().is_none_or(|k| !keys.contains(k)));
+            }
+            if buckets.is_empty() {
+                buckets.push(Bucket::new());
+            }
+            let len = buckets.len();
+            for row in rows {
+                let key = row.fields().first().cloned().unwrap_or(Field::Nothing);
+                let hash = pk_hash(&key).ok_or_else(|| ModError::Key(name.clone(), key))?;
+                buckets[hash as usize % len].rows_mut().push(row);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Add the files to a pack file, returning the number of entries
+    pub fn write_entries<W: Write>(&self, writer: &mut PackWriter<W>) -> Result<usize, ModError> {
+        for (path, data) in &self.files {
+            writer.append_entry(path, &data[..])?;
+        }
+        Ok(self.files.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assembly_data::fdb::common::ValueType;
+    use assembly_data::fdb::core::{Column, TableData, TableDef};
+    use std::io::Cursor;
+
+    fn schema(rows: &[(i32, &str)]) -> Schema {
+        let def = TableDef {
+            columns: vec![
+                Column::from(("id", ValueType::Integer)),
+                Column::from(("name", ValueType::Text)),
+            ],
+            name: "Objects".to_owned(),
+        };
+        let mut buckets: Vec<_> = std::iter::repeat_with(Bucket::new).take(4).collect();
+        for &(id, name) in rows {
+            let row = Row::from(vec![Field::Integer(id), Field::Text(name.to_owned())]);
+            buckets[id as usize % 4].rows_mut().push(row);
+        }
+        vec![Table::from(def, TableData { buckets })].into()
+    }
+
+    #[test]
+    fn test_round_trip_and_apply() {
+        let package = ModPackage::new("Bricks", "1.0")
+            .with_patch(&schema(&[(2, "Plate"), (5, "Slope")]))
+            .with_file("res/textures/brick.dds", b"DDS ".to_vec());
+        let bytes = package.write(Cursor::new(Vec::new())).unwrap();
+        let read = ModPackage::read(Cursor::new(bytes.get_ref())).unwrap();
+        assert_eq!(read.name, "Bricks");
+        assert_eq!(read.signer(), None);
+
+        let mut db = schema(&[(1, "brick"), (2, "plate")]);
+        assert_eq!(read.apply_patch(&mut db).unwrap(), 2);
+        let names: HashSet<_> = db
+            .table("Objects")
+            .unwrap()
+            .into_iter()
+            .map(|r| r.fields()[1].clone())
+            .collect();
+        let expected = ["brick", "Plate", "Slope"].iter();
+        assert_eq!(
+            names,
+            expected
+                .map(|&n| Field::Text(n.into()))
+                .collect::<HashSet<_>>()
+        );
+
+        let mut pk = PackWriter::new(Vec::new()).unwrap();
+        assert_eq!(read.write_entries(&mut pk).unwrap(), 1);
+        let crc = calculate_crc_normalized(b"client\\res\\textures\\brick.dds");
+        assert!(pk.entries().any(|e| e.crc == crc));
+
+        let mut tampered = PackWriter::new(Cursor::new(Vec::new())).unwrap();
+        tampered
+            .append_entry(MANIFEST, &package.manifest().unwrap()[..])
+            .unwrap();
+        tampered
+            .append_entry(PATCH, &package.patch.unwrap()[..])
+            .unwrap();
+        let name = "files/client\\res\\textures\\brick.dds";
+        tampered.append_entry(name, &b"XDS "[..]).unwrap();
+        let tampered = tampered.finish().unwrap().into_inner();
+        let result = ModPackage::read(Cursor::new(tampered));
+        assert!(matches!(result, Err(ModError::Hash(_))), "{:?}", result);
+    }
+
+    #[test]
+    fn test_manifest_control_characters() {
+        let write = |package: ModPackage| package.write(Cursor::new(Vec::new()));
+        for (name, version, path) in &[
+            ("Bricks\nfile 1 00 a.txt", "1.0", "a.txt"),
+            ("Bricks", "1.0\r", "a.txt"),
+            ("Bricks", "1.0", "a\n.txt"),
+            ("Bricks", "1.0", "a\u{7f}.txt"),
+        ] {
+            let package = ModPackage::new(name, version).with_file(path, b"a".to_vec());
+            assert!(matches!(write(package), Err(ModError::Manifest(_))));
+        }
+
+        let mut forged = PackWriter::new(Cursor::new(Vec::new())).unwrap();
+        let manifest = format!("{}\nname Bricks\x1b[2J\nversion 1.0\n", HEADER);
+        forged.append_entry(MANIFEST, manifest.as_bytes()).unwrap();
+        let forged = forged.finish().unwrap().into_inner();
+        let result = ModPackage::read(Cursor::new(forged));
+        assert!(matches!(result, Err(ModError::Manifest(_))), "{:?}", result);
+    }
+
+    #[cfg(feature = "signing")]
+    #[test]
+    fn test_signed() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let package = ModPackage::new("Bricks", "1.0").with_file("res/a.txt", b"a".to_vec());
+        let bytes = package.write_signed(Cursor::new(Vec::new()), &key).unwrap();
+        let read = ModPackage::read(Cursor::new(bytes.into_inner())).unwrap();
+        read.verify(&key.verifying_key()).unwrap();
+        let other = SigningKey::from_bytes(&[8; 32]).verifying_key();
+        assert!(matches!(read.verify(&other), Err(ModError::Unsigned)));
+        assert!(matches!(
+            package.verify(&key.verifying_key()),
+            Err(ModError::Unsigned)
+        ));
+    }
+}
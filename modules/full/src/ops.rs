@@ -0,0 +1,384 @@
+//! # One call operations for command line tools
+//!
+//! The functions in this module combine the parsers and writers of the other
+//! modules into the operations that tools usually offer, e.g. converting a
+//! database or extracting a pack file. They take paths and options, report
+//! their progress to a [`ProgressSink`] and return a summary of what they did,
+//! so that a binary only needs to parse its arguments and print the result.
+//!
+//! This module requires the `core`, `data` and `pack` features.
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fs,
+    io::{self, BufReader},
+    path::{Component, Path, PathBuf},
+};
+
+use assembly_core::{
+    buffer::CastError, displaydoc::Display, progress::ProgressSink, reader::FileError,
+};
+use assembly_data::fdb::{
+    mem::Database,
+    sqlite::{self, try_export_db_with_options, Connection, ExportOptions},
+};
+use assembly_pack::{
+    crc::calculate_crc_normalized,
+    path::{normalize, strip_client_res},
+    pk::reader::{PackFile, StreamError},
+    pki::{core::PackIndexFile, io::LoadError},
+    verify::{verify_with_progress, VerifyReport},
+};
+use thiserror::Error;
+
+/// Errors from the operations in this module
+#[derive(Debug, Error, Display)]
+#[non_exhaustive]
+pub enum OpsError {
+    /// Failed to read or write a file: {0}
+    Io(#[from] io::Error),
+    /// Failed to read a pack file: {0}
+    File(#[source] FileError),
+    /// Failed to load a pack index file: {0:?}
+    Index(LoadError),
+    /// Failed to read a file from a pack file: {0:?}
+    Stream(StreamError),
+    /// The database file is invalid: {0}
+    Database(#[from] CastError),
+    /// Failed to write to the SQLite database: {0}
+    Sqlite(#[source] sqlite::Error),
+    /// The operation was cancelled by the progress sink
+    Cancelled,
+}
+
+impl From<FileError> for OpsError {
+    fn from(e: FileError) -> Self {
+        match e {
+            FileError::Cancelled => OpsError::Cancelled,
+            e => OpsError::File(e),
+        }
+    }
+}
+
+/// The result of [`export_sqlite`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ExportSummary {
+    /// The number of tables
+    pub tables: usize,
+    /// The number of rows
+    pub rows: usize,
+}
+
+/// Convert the FDB file at `src` into the SQLite database at `dest`
+///
+/// The tables are created if they don't exist yet. Progress is reported with
+/// one step per table.
+pub fn export_sqlite<P, Q, S>(
+    src: P,
+    dest: Q,
+    options: &ExportOptions,
+    progress: &S,
+) -> Result<ExportSummary, OpsError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    S: ProgressSink + ?Sized,
+{
+    let buf = fs::read(src)?;
+    let db = Database::new(&buf);
+    let tables = db.tables()?;
+    let mut summary = ExportSummary {
+        tables: tables.len(),
+        rows: 0,
+    };
+    for table in tables.iter() {
+        summary.rows += table?.row_iter().count();
+    }
+
+    let mut conn = Connection::open(dest).map_err(OpsError::Sqlite)?;
+    match try_export_db_with_options(&mut conn, db, options, progress) {
+        Ok(()) => Ok(summary),
+        Err(_) if progress.is_cancelled() => Err(OpsError::Cancelled),
+        Err(e) => Err(OpsError::Sqlite(e)),
+    }
+}
+
+/// The result of [`extract_pack`]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ExtractSummary {
+    /// The number of files that were written
+    pub files: usize,
+    /// The number of files that were written with their name
+    pub named: usize,
+    /// The number of bytes that were written
+    pub bytes: u64,
+}
+
+/// Turn a canonical resource path into a path relative to the output folder
+///
+/// Returns `None` for paths that could leave the folder, see [`archive_path`].
+fn output_path(name: &str) -> Option<PathBuf> {
+    let name = strip_client_res(name).unwrap_or(name);
+    archive_path(Path::new(""), name)
+}
+
+/// Extract all files of the pack file at `src` into the folder `dest`
+///
+/// Pack files only contain the CRC of the path of each file, so `names` is
+/// used to find the path for each entry, relative to the `client/res` folder.
+/// All other files are written to `dest/unknown/<crc>`. Progress is reported
+/// with one step per file.
+pub fn extract_pack<P, Q, N, S>(
+    src: P,
+    dest: Q,
+    names: &[N],
+    progress: &S,
+) -> Result<ExtractSummary, OpsError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    N: AsRef<str>,
+    S: ProgressSink + ?Sized,
+{
+    let dest = dest.as_ref();
+    let names: BTreeMap<u32, PathBuf> = names
+        .iter()
+        .map(|name| normalize(name.as_ref()))
+        .filter_map(|name| {
+            Some((
+                calculate_crc_normalized(name.as_bytes()),
+                output_path(&name)?,
+            ))
+        })
+        .collect();
+
+    let mut reader = BufReader::new(fs::File::open(src)?);
+    let mut pack = PackFile::open(&mut reader);
+    pack.check_magic()?;
+    let header = pack.get_header()?;
+    let entries = pack.get_entry_list(header.file_list_base_addr)?;
+
+    let mut summary = ExtractSummary::default();
+    progress.start("files", entries.len() as u64);
+    for entry in entries {
+        if progress.is_cancelled() {
+            return Err(OpsError::Cancelled);
+        }
+        let path = match names.get(&entry.crc) {
+            Some(name) => {
+                summary.named += 1;
+                dest.join(name)
+            }
+            None => dest.join("unknown").join(entry.crc.to_string()),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut data = pack.get_file_data(entry).map_err(OpsError::Stream)?;
+        let mut file = fs::File::create(&path)?;
+        summary.bytes += io::copy(&mut data, &mut file)?;
+        summary.files += 1;
+        progress.advance(1);
+    }
+    Ok(summary)
+}
+
+/// The result of [`verify`][assembly_pack::verify::verify] for one pack file
+#[derive(Debug)]
+pub struct PackResult {
+    /// The path of the pack file, as listed in the index
+    pub path: String,
+    /// The report, or the error that prevented the check
+    pub report: Result<VerifyReport, FileError>,
+}
+
+/// The result of [`verify_client`]
+#[derive(Debug, Default)]
+pub struct ClientReport {
+    /// One result for each pack file in the index
+    pub packs: Vec<PackResult>,
+}
+
+impl ClientReport {
+    /// Check whether all pack files were found and have no issues
+    pub fn is_ok(&self) -> bool {
+        self.packs
+            .iter()
+            .all(|p| matches!(&p.report, Ok(r) if r.is_ok()))
+    }
+}
+
+/// Turn the path of a pack file from the index into a path below `root`
+///
+/// Returns `None` for paths that could leave `root`, i.e. ones with `..`
+/// segments, drive prefixes or absolute segments, and for empty segments.
+fn archive_path(root: &Path, path: &str) -> Option<PathBuf> {
+    let mut result = root.to_path_buf();
+    for segment in path.split(['\\', '/']) {
+        let mut components = Path::new(segment).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) => result.push(segment),
+            _ => return None,
+        }
+    }
+    Some(result)
+}
+
+/// Verify all pack files of the client installed at `root`
+///
+/// This loads the index from `versions/primary.pki` and checks every pack
+/// file that it lists. Pack files with a path outside of `root` are reported
+/// with an error instead of being opened. Progress is reported with one phase
+/// per pack file and one step per entry.
+pub fn verify_client<P, S>(root: P, progress: &S) -> Result<ClientReport, OpsError>
+where
+    P: AsRef<Path>,
+    S: ProgressSink + ?Sized,
+{
+    let root = root.as_ref();
+    let pki_file = fs::File::open(root.join("versions").join("primary.pki"))?;
+    let pki = PackIndexFile::try_from(pki_file).map_err(OpsError::Index)?;
+
+    let mut report = ClientReport::default();
+    for archive in &pki.archives {
+        let result = match archive_path(root, &archive.path) {
+            Some(pk_path) => verify_with_progress(&pk_path, &pki, progress),
+            None => Err(FileError::IO(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the pack file is outside of the client folder",
+            ))),
+        };
+        if let Err(FileError::Cancelled) = result {
+            return Err(OpsError::Cancelled);
+        }
+        report.packs.push(PackResult {
+            path: archive.path.clone(),
+            report: result,
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assembly_data::fdb::{common::ValueType, testing::SampleDatabase};
+    use assembly_pack::{pk::writer::PackWriter, pki::core::PackFileRef};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("assembly-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_extract_pack() {
+        let dir = temp_dir("ops-extract");
+        let pk_path = dir.join("test.pk");
+        let mut writer = PackWriter::new(fs::File::create(&pk_path).unwrap()).unwrap();
+        writer
+            .append_entry("client/res/readme.txt", &b"Hello"[..])
+            .unwrap();
+        writer.append_entry("res/other.txt", &b"World"[..]).unwrap();
+        writer.finish().unwrap();
+
+        let out = dir.join("out");
+        let names = ["res/readme.txt", "../../escape.txt"];
+        let summary = extract_pack(&pk_path, &out, &names, &()).unwrap();
+        assert_eq!(summary.files, 2);
+        assert_eq!(summary.named, 1);
+        assert_eq!(summary.bytes, 10);
+        assert_eq!(fs::read(out.join("readme.txt")).unwrap(), b"Hello");
+        let crc = calculate_crc_normalized(b"client\\res\\other.txt");
+        let unknown = out.join("unknown").join(crc.to_string());
+        assert_eq!(fs::read(unknown).unwrap(), b"World");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_output_path() {
+        let path = output_path("client\\res\\textures\\brick.dds").unwrap();
+        assert_eq!(path, Path::new("textures").join("brick.dds"));
+        for name in &[
+            "..\\escape.txt",
+            "a\\\\b",
+            "a\\.\\b",
+            "/etc/passwd",
+            "a/../../b",
+        ] {
+            assert_eq!(output_path(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_export_sqlite() {
+        let dir = temp_dir("ops-export");
+        let columns = [("id", ValueType::Integer), ("name", ValueType::Text)];
+        let buf = SampleDatabase::new().table("Objects", &columns).rows(3);
+        let fdb_path = dir.join("cdclient.fdb");
+        fs::write(&fdb_path, buf.build()).unwrap();
+
+        let sqlite_path = dir.join("cdclient.sqlite");
+        let options = ExportOptions::default();
+        let summary = export_sqlite(&fdb_path, &sqlite_path, &options, &()).unwrap();
+        assert_eq!(summary, ExportSummary { tables: 1, rows: 3 });
+        let conn = Connection::open(&sqlite_path).unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM Objects", Vec::<i32>::new(), |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(count, 3);
+        drop(conn);
+
+        fs::write(&fdb_path, b"\x01\0\0\0\xff\xff\xff\xff").unwrap();
+        let result = export_sqlite(&fdb_path, dir.join("broken.sqlite"), &options, &());
+        assert!(matches!(result, Err(OpsError::Database(_))), "{:?}", result);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_client() {
+        let root = temp_dir("ops-verify");
+        let mut pki = PackIndexFile {
+            archives: Vec::new(),
+            files: BTreeMap::new(),
+        };
+        pki.insert("client\\res\\a.txt", "client\\res\\pack\\a.pk", true);
+        let outside = root.join("evil.pk");
+        for path in &["..\\evil.pk", "client\\..\\..\\evil.pk"] {
+            pki.archives.push(PackFileRef {
+                path: path.to_string(),
+            });
+        }
+        pki.archives.push(PackFileRef {
+            path: outside.to_string_lossy().into_owned(),
+        });
+        fs::create_dir_all(root.join("versions")).unwrap();
+        let mut pki_file = fs::File::create(root.join("versions/primary.pki")).unwrap();
+        pki.write(&mut pki_file).unwrap();
+        drop(pki_file);
+
+        fs::create_dir_all(root.join("client/res/pack")).unwrap();
+        let pk_file = fs::File::create(root.join("client/res/pack/a.pk")).unwrap();
+        let mut writer = PackWriter::new(pk_file).unwrap();
+        writer.append_entry("client/res/a.txt", &b"A"[..]).unwrap();
+        writer.finish().unwrap();
+
+        let report = verify_client(&root, &()).unwrap();
+        assert_eq!(report.packs.len(), 4);
+        let first = report.packs[0].report.as_ref().unwrap();
+        assert!(first.is_ok(), "{:?}", first);
+        assert_eq!(first.checked, 1);
+        for pack in &report.packs[1..] {
+            match &pack.report {
+                Err(FileError::IO(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidInput),
+                other => panic!("{}: {:?}", pack.path, other),
+            }
+        }
+        assert!(!report.is_ok());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
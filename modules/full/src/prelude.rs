@@ -0,0 +1,38 @@
+//! # The most important types, with consistent names
+//!
+//! This module re-exports the entry points for all file formats, so that a
+//! single `use assembly::prelude::*;` is enough to get started:
+//!
+//! - [`Database`] for in-memory access to a `*.fdb` file, [`Schema`] to load it completely
+//! - [`PackIndex`] and [`PackIndexRef`] for `*.pki` files, [`PackFile`] for `*.pk` files
+//! - [`ZoneFile`] for `*.luz` files, [`Level`] for `*.lvl` files and [`Terrain`] for `*.raw` files
+//!
+//! Each item is only available if the feature for the respective crate is enabled.
+
+#[cfg(feature = "core")]
+pub use assembly_core::{
+    progress::ProgressSink,
+    reader::{FileError, FileResult, ParseError},
+    source::ByteSource,
+    types::{ObjectID, Placement3D, Quaternion, Vector3f},
+};
+
+#[cfg(any(feature = "fdb-core", feature = "fdb-mem"))]
+pub use assembly_data::fdb::common::{Latin1Str, Latin1String, ValueType};
+#[cfg(feature = "fdb-core")]
+pub use assembly_data::fdb::core::{Field, Schema};
+#[cfg(all(feature = "fdb-core", feature = "fdb-mem"))]
+pub use assembly_data::fdb::hybrid::HybridSchema;
+#[cfg(feature = "fdb-mem")]
+pub use assembly_data::fdb::mem::Database;
+
+#[cfg(feature = "pack")]
+pub use assembly_pack::{
+    crc::{calculate_crc, calculate_crc_normalized},
+    pk::reader::PackFile,
+    pki::{core::PackIndexFile as PackIndex, mem::PackIndexRef},
+    sd0::stream::SegmentedStream,
+};
+
+#[cfg(feature = "maps")]
+pub use assembly_maps::{luz::core::ZoneFile, lvl::file::Level, terrain::Terrain};
@@ -0,0 +1,336 @@
+//! # Differences between two versions of a zone
+//!
+//! The objects of a zone are placed in the level files of its scenes. To
+//! review a modified zone, [`ZoneDiff::compute`] compares the scenes of two
+//! versions, and [`LevelDiff::compute`] compares the objects of two versions
+//! of a single scene by their object ID.
+//!
+//! The [`std::fmt::Display`] implementations print a line based report.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use assembly_core::{
+    ldf::LDF,
+    num_traits::ToPrimitive,
+    types::{ObjectID, Vector3f},
+};
+
+#[cfg(feature = "serde-derives")]
+use serde::Serialize;
+
+use crate::lvl::file::{Level, Object};
+
+/// Differences in position smaller than this are ignored
+pub const POSITION_EPSILON: f32 = 1e-4;
+
+/// Get the object ID as a single number, with the scope in the upper half
+pub fn object_key(id: &ObjectID) -> u64 {
+    (u64::from(id.scope) << 32) | u64::from(id.id)
+}
+
+/// A setting that was added, removed or changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(Serialize))]
+pub struct SettingChange {
+    /// The name of the setting
+    pub key: String,
+    /// The old value, in the `type:value` text format
+    pub old: Option<String>,
+    /// The new value, in the `type:value` text format
+    pub new: Option<String>,
+}
+
+/// The differences between two versions of one object
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(Serialize))]
+pub struct ObjectChange {
+    /// The object ID, see [`object_key`]
+    pub id: u64,
+    /// The old and new object template, if it changed
+    pub lot: Option<(u32, u32)>,
+    /// The old and new position, if the object moved
+    pub moved: Option<(Vector3f, Vector3f)>,
+    /// Whether the rotation changed
+    pub rotated: bool,
+    /// The old and new scale, if it changed
+    pub scaled: Option<(f32, f32)>,
+    /// The changed settings, ordered by key
+    pub settings: Vec<SettingChange>,
+}
+
+impl ObjectChange {
+    /// Check whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.lot.is_none()
+            && self.moved.is_none()
+            && !self.rotated
+            && self.scaled.is_none()
+            && self.settings.is_empty()
+    }
+}
+
+fn distance_sq(a: &Vector3f, b: &Vector3f) -> f32 {
+    let (x, y, z) = (a.x - b.x, a.y - b.y, a.z - b.z);
+    x * x + y * y + z * z
+}
+
+fn diff_settings(old: &LDF, new: &LDF) -> Vec<SettingChange> {
+    let keys: BTreeSet<&String> = old.map.keys().chain(new.map.keys()).collect();
+    keys.into_iter()
+        .filter_map(|key| {
+            let (a, b) = (old.map.get(key), new.map.get(key));
+            if a == b {
+                return None;
+            }
+            Some(SettingChange {
+                key: key.clone(),
+                old: a.map(ToString::to_string),
+                new: b.map(ToString::to_string),
+            })
+        })
+        .collect()
+}
+
+fn diff_object(old: &Object<LDF>, new: &Object<LDF>) -> ObjectChange {
+    let (old_lot, new_lot) = (old.lot.to_u32(), new.lot.to_u32());
+    let (r1, r2) = (&old.rotation, &new.rotation);
+    ObjectChange {
+        id: object_key(&new.obj_id),
+        lot: match (old_lot, new_lot) {
+            (Some(a), Some(b)) if a != b => Some((a, b)),
+            _ => None,
+        },
+        moved: if distance_sq(&old.position, &new.position) > POSITION_EPSILON * POSITION_EPSILON {
+            Some((old.position, new.position))
+        } else {
+            None
+        },
+        rotated: (r1.x, r1.y, r1.z, r1.w) != (r2.x, r2.y, r2.z, r2.w),
+        scaled: if old.scale != new.scale {
+            Some((old.scale, new.scale))
+        } else {
+            None
+        },
+        settings: diff_settings(&old.settings, &new.settings),
+    }
+}
+
+fn by_id(level: &Level) -> BTreeMap<u64, &Object<LDF>> {
+    let objects = level.objects.iter();
+    objects.map(|o| (object_key(&o.obj_id), o)).collect()
+}
+
+/// The differences between two versions of a level
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(Serialize))]
+pub struct LevelDiff {
+    /// The IDs of the objects that only exist in the new version
+    pub added: Vec<u64>,
+    /// The IDs of the objects that only exist in the old version
+    pub removed: Vec<u64>,
+    /// The objects that exist in both versions, but are different
+    pub changed: Vec<ObjectChange>,
+}
+
+impl LevelDiff {
+    /// Compare the objects of two versions of a level
+    pub fn compute(old: &Level, new: &Level) -> Self {
+        let (old, new) = (by_id(old), by_id(new));
+        let mut diff = LevelDiff::default();
+        for (id, object) in &new {
+            match old.get(id) {
+                None => diff.added.push(*id),
+                Some(prev) => {
+                    let change = diff_object(prev, object);
+                    if !change.is_empty() {
+                        diff.changed.push(change);
+                    }
+                }
+            }
+        }
+        diff.removed = old
+            .keys()
+            .filter(|id| !new.contains_key(id))
+            .copied()
+            .collect();
+        diff
+    }
+
+    /// Check whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for LevelDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for id in &self.added {
+            writeln!(f, "+ {}", id)?;
+        }
+        for id in &self.removed {
+            writeln!(f, "- {}", id)?;
+        }
+        for change in &self.changed {
+            writeln!(f, "~ {}", change.id)?;
+            if let Some((a, b)) = change.lot {
+                writeln!(f, "    lot: {} -> {}", a, b)?;
+            }
+            if let Some((a, b)) = &change.moved {
+                let (a, b) = ((a.x, a.y, a.z), (b.x, b.y, b.z));
+                writeln!(f, "    position: {:?} -> {:?}", a, b)?;
+            }
+            if change.rotated {
+                writeln!(f, "    rotation changed")?;
+            }
+            if let Some((a, b)) = change.scaled {
+                writeln!(f, "    scale: {} -> {}", a, b)?;
+            }
+            for setting in &change.settings {
+                let old = setting.old.as_deref().unwrap_or("(none)");
+                let new = setting.new.as_deref().unwrap_or("(none)");
+                writeln!(f, "    {}: {} -> {}", setting.key, old, new)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The differences between two versions of a zone, by scene ID
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(Serialize))]
+pub struct ZoneDiff {
+    /// The scenes that only exist in the new version
+    pub added_scenes: Vec<u32>,
+    /// The scenes that only exist in the old version
+    pub removed_scenes: Vec<u32>,
+    /// The scenes that exist in both versions and have changed objects
+    pub scenes: BTreeMap<u32, LevelDiff>,
+}
+
+impl ZoneDiff {
+    /// Compare two versions of a zone, given the levels of their scenes
+    ///
+    /// The scene IDs are the `id` of the [`crate::luz::core::SceneRef`]s.
+    pub fn compute<'a, O, N>(old: O, new: N) -> Self
+    where
+        O: IntoIterator<Item = (u32, &'a Level)>,
+        N: IntoIterator<Item = (u32, &'a Level)>,
+    {
+        let old: BTreeMap<u32, &Level> = old.into_iter().collect();
+        let new: BTreeMap<u32, &Level> = new.into_iter().collect();
+        let mut diff = ZoneDiff::default();
+        for (id, level) in &new {
+            match old.get(id) {
+                None => diff.added_scenes.push(*id),
+                Some(prev) => {
+                    let scene = LevelDiff::compute(prev, level);
+                    if !scene.is_empty() {
+                        diff.scenes.insert(*id, scene);
+                    }
+                }
+            }
+        }
+        diff.removed_scenes = old
+            .keys()
+            .filter(|id| !new.contains_key(id))
+            .copied()
+            .collect();
+        diff
+    }
+
+    /// Check whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.added_scenes.is_empty() && self.removed_scenes.is_empty() && self.scenes.is_empty()
+    }
+}
+
+impl fmt::Display for ZoneDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for id in &self.added_scenes {
+            writeln!(f, "+ scene {}", id)?;
+        }
+        for id in &self.removed_scenes {
+            writeln!(f, "- scene {}", id)?;
+        }
+        for (id, scene) in &self.scenes {
+            writeln!(f, "scene {}:", id)?;
+            scene.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assembly_core::num_traits::FromPrimitive;
+    use assembly_core::types::{ObjectTemplate, Quaternion};
+
+    fn object(id: u32, x: f32, settings: &str) -> Object<LDF> {
+        Object {
+            obj_id: ObjectID::new(0, id),
+            lot: ObjectTemplate::from_u32(1000).unwrap(),
+            asset_type: None,
+            value_1: None,
+            position: Vector3f::new(x, 0.0, 0.0),
+            rotation: Quaternion::new(0.0, 0.0, 0.0, 1.0),
+            scale: 1.0,
+            settings: settings.parse().unwrap(),
+            extra: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_level_diff() {
+        let old = Level {
+            env: None,
+            objects: vec![
+                object(1, 0.0, "a=1:1"),
+                object(2, 0.0, "a=1:1"),
+                object(3, 0.0, "a=1:1"),
+            ],
+        };
+        let new = Level {
+            env: None,
+            objects: vec![
+                object(1, 0.0, "a=1:1"),
+                object(2, 5.0, "a=1:2\nb=0:x"),
+                object(4, 0.0, "a=1:1"),
+            ],
+        };
+        let diff = LevelDiff::compute(&old, &new);
+        assert_eq!(
+            (diff.added.clone(), diff.removed.clone()),
+            (vec![4], vec![3])
+        );
+        assert_eq!(diff.changed.len(), 1);
+        let change = &diff.changed[0];
+        assert!(change.moved.is_some() && !change.rotated && change.lot.is_none());
+        assert_eq!(
+            change.settings,
+            vec![
+                SettingChange {
+                    key: "a".to_string(),
+                    old: Some("1:1".to_string()),
+                    new: Some("1:2".to_string()),
+                },
+                SettingChange {
+                    key: "b".to_string(),
+                    old: None,
+                    new: Some("0:x".to_string()),
+                },
+            ]
+        );
+
+        let zone = ZoneDiff::compute(vec![(1, &old), (2, &old)], vec![(1, &new), (3, &new)]);
+        assert_eq!(
+            (zone.added_scenes.clone(), zone.removed_scenes.clone()),
+            (vec![3], vec![2])
+        );
+        let report = zone.to_string();
+        assert!(report.contains("scene 1:\n+ 4\n- 3\n~ 2\n"));
+        assert!(report.contains("    a: 1:1 -> 1:2\n"));
+        assert!(ZoneDiff::compute(vec![(1, &old)], vec![(1, &old)]).is_empty());
+    }
+}
@@ -1,3 +1,9 @@
+pub mod diff;
 pub mod luz;
 pub mod lvl;
+#[cfg(feature = "minimap")]
+pub mod minimap;
+pub mod nav;
 pub mod raw;
+pub mod scripts;
+pub mod terrain;
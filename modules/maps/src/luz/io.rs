@@ -1,6 +1,7 @@
 use super::core::ZoneFile;
 use super::parser;
-use assembly_core::nom::{error::Error as NomError, error::ErrorKind, Err as NomErr};
+use assembly_core::reader::{ParseError, ParseOffset};
+use assembly_core::source::ByteSource;
 use displaydoc::Display;
 use std::convert::TryFrom;
 use std::io::Read;
@@ -14,28 +15,12 @@ pub enum LoadError {
     FileOpen(io::Error),
     /// Failed to read from the file
     Read(io::Error),
-    /// Missing bytes
-    Incomplete,
-    /// Failed to parse (recoverable)
-    ParseError(ErrorKind),
-    /// Failed to parse (fatal)
-    ParseFailure(ErrorKind),
+    /// Failed to parse: {0}
+    Parse(#[from] ParseError),
 }
 
 type LoadResult<T> = Result<T, LoadError>;
 
-// Generates a LoadError from a nom error
-impl From<NomErr<NomError<&[u8]>>> for LoadError {
-    fn from(e: NomErr<NomError<&[u8]>>) -> LoadError {
-        match e {
-            // Need to translate the error here, as this lives longer than the input
-            NomErr::Incomplete(_) => LoadError::Incomplete,
-            NomErr::Error(e) => LoadError::ParseError(e.code),
-            NomErr::Failure(e) => LoadError::ParseFailure(e.code),
-        }
-    }
-}
-
 pub trait TryFromLUZ<T>
 where
     T: Read,
@@ -64,6 +49,15 @@ impl TryFrom<fs::File> for ZoneFile<Vec<u8>> {
     }
 }
 
+impl ZoneFile<Vec<u8>> {
+    /// Load a zone file from any [`ByteSource`]
+    pub fn from_source<S: ByteSource>(mut source: S) -> LoadResult<Self> {
+        let bytes = source.read_bytes().map_err(LoadError::Read)?;
+        let (_rest, zone_file) = parser::parse_zone_file(&bytes).at_offset(&bytes)?;
+        Ok(zone_file)
+    }
+}
+
 impl<T> TryFromLUZ<T> for ZoneFile<Vec<u8>>
 where
     T: Read,
@@ -76,6 +70,7 @@ where
             .map_err(LoadError::Read)
             .and_then(|_| {
                 parser::parse_zone_file(&bytes)
+                    .at_offset(&bytes)
                     .map_err(LoadError::from)
                     .map(|r| r.1)
             })
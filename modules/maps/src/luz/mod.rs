@@ -1,6 +1,6 @@
 //! # The zone/world (`*.luz`) file format
 //!
-//! This module can be used to read the zone/world file format
+//! This module can be used to read and write the zone/world file format
 //! used in the game LEGO Universe.
 
 /// Data definitions for zone files
@@ -11,3 +11,5 @@ pub mod io;
 pub mod parser;
 /// Module for reading the path data in a zone file
 pub mod paths;
+/// Writing of zone files
+pub mod writer;
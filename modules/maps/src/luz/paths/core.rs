@@ -23,7 +23,7 @@ impl PathVersion {
 
 /// Type of this path
 #[cfg_attr(feature = "serde-derives", derive(Serialize))]
-#[derive(Debug, FromPrimitive, ToPrimitive)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive, ToPrimitive)]
 pub enum PathType {
     Movement,
     MovingPlatform,
@@ -312,6 +312,74 @@ pub enum Path {
     Rail(PathVariantRail),
 }
 
+impl<D, W> PathVariant<D, W> {
+    /// Get the positions of all waypoints
+    pub fn positions(&self) -> impl Iterator<Item = &Vector3f> + '_ {
+        self.waypoints.iter().map(|w| &w.position)
+    }
+}
+
+macro_rules! with_variant {
+    ($path:expr, $v:ident => $e:expr) => {
+        match $path {
+            Path::Movement($v) => $e,
+            Path::MovingPlatform($v) => $e,
+            Path::Property($v) => $e,
+            Path::Camera($v) => $e,
+            Path::Spawner($v) => $e,
+            Path::Showcase($v) => $e,
+            Path::Race($v) => $e,
+            Path::Rail($v) => $e,
+        }
+    };
+}
+
+impl Path {
+    /// Get the type of this path
+    pub fn path_type(&self) -> PathType {
+        match self {
+            Path::Movement(_) => PathType::Movement,
+            Path::MovingPlatform(_) => PathType::MovingPlatform,
+            Path::Property(_) => PathType::Property,
+            Path::Camera(_) => PathType::Camera,
+            Path::Spawner(_) => PathType::Spawner,
+            Path::Showcase(_) => PathType::Showcase,
+            Path::Race(_) => PathType::Race,
+            Path::Rail(_) => PathType::Rail,
+        }
+    }
+
+    /// Get the common header of this path
+    pub fn header(&self) -> &PathHeader {
+        with_variant!(self, p => &p.header)
+    }
+
+    /// Get the name of this path
+    pub fn name(&self) -> &str {
+        &self.header().path_name
+    }
+
+    /// Get the number of waypoints
+    pub fn waypoint_count(&self) -> usize {
+        with_variant!(self, p => p.waypoints.len())
+    }
+
+    /// Get the positions of all waypoints, regardless of the path type
+    pub fn positions(&self) -> Vec<Vector3f> {
+        with_variant!(self, p => p.positions().copied().collect())
+    }
+
+    /// Get the config of the waypoint at `index`, if the path type has one
+    pub fn waypoint_config(&self, index: usize) -> Option<&WaypointConfig> {
+        match self {
+            Path::Movement(p) => p.waypoints.get(index).map(|w| &w.data.config),
+            Path::Spawner(p) => p.waypoints.get(index).map(|w| &w.data.config),
+            Path::Rail(p) => p.waypoints.get(index).map(|w| &w.data.config),
+            _ => None,
+        }
+    }
+}
+
 /// All paths in a zone
 #[derive(Debug)]
 #[cfg_attr(feature = "serde-derives", derive(Serialize))]
@@ -319,3 +387,134 @@ pub struct ZonePaths {
     pub version: ZonePathsVersion,
     pub paths: Vec<Path>,
 }
+
+impl ZonePaths {
+    /// Get a path by its name
+    pub fn get(&self, name: &str) -> Option<&Path> {
+        self.paths.iter().find(|p| p.name() == name)
+    }
+
+    /// Get all paths of the given type
+    pub fn of_type(&self, path_type: PathType) -> impl Iterator<Item = &Path> + '_ {
+        self.paths
+            .iter()
+            .filter(move |p| p.path_type() == path_type)
+    }
+
+    /// Get all spawner paths
+    pub fn spawners(&self) -> impl Iterator<Item = &PathVariantSpawner> + '_ {
+        self.paths.iter().filter_map(|p| match p {
+            Path::Spawner(s) => Some(s),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assembly_core::num_traits::FromPrimitive;
+
+    fn header(name: &str) -> PathHeader {
+        PathHeader {
+            version: PathVersion(18),
+            path_name: name.to_owned(),
+            value_1: 0,
+            path_composition: PathComposition::Line,
+        }
+    }
+
+    fn rotation() -> Quaternion {
+        Quaternion::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    fn sample() -> ZonePaths {
+        let mut config = WaypointConfig::new();
+        config.insert("delay".to_owned(), "2".to_owned());
+        let movement = PathVariant {
+            header: header("Patrol"),
+            path_data: PathDataMovement {},
+            waypoints: vec![
+                PathWaypointVariant {
+                    position: Vector3f::new(1.0, 0.0, 0.0),
+                    data: PathWaypointDataMovement { config },
+                },
+                PathWaypointVariant {
+                    position: Vector3f::new(2.0, 0.0, 0.0),
+                    data: PathWaypointDataMovement {
+                        config: WaypointConfig::new(),
+                    },
+                },
+            ],
+        };
+        let spawner = PathVariant {
+            header: header("Spawns"),
+            path_data: PathDataSpawner {
+                spawned_lot: ObjectTemplate::from_u32(6326).unwrap(),
+                respawn_time: 10,
+                max_to_spawn: u32::MAX,
+                min_to_spawn: 1,
+                spawner_obj_id: ObjectID::new(0, 70000),
+                activate_network_on_load: true,
+            },
+            waypoints: vec![PathWaypointVariant {
+                position: Vector3f::new(0.0, 5.0, 0.0),
+                data: PathWaypointDataSpawner {
+                    rotation: rotation(),
+                    config: WaypointConfig::new(),
+                },
+            }],
+        };
+        let camera = PathVariant {
+            header: header("Intro"),
+            path_data: PathDataCamera {
+                next_path: String::new(),
+                value_1: None,
+            },
+            waypoints: vec![PathWaypointVariant {
+                position: Vector3f::new(0.0, 0.0, 3.0),
+                data: PathWaypointDataCamera {
+                    rotation: rotation(),
+                    time: 1.0,
+                    value_5: 0.0,
+                    tension: 0.0,
+                    continuity: 0.0,
+                    bias: 0.0,
+                },
+            }],
+        };
+        ZonePaths {
+            version: ZonePathsVersion(1),
+            paths: vec![
+                Path::Movement(movement),
+                Path::Spawner(spawner),
+                Path::Camera(camera),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_path_accessors() {
+        let paths = sample();
+        let patrol = paths.get("Patrol").unwrap();
+        assert_eq!(patrol.path_type(), PathType::Movement);
+        assert_eq!(patrol.waypoint_count(), 2);
+        let xs: Vec<f32> = patrol.positions().iter().map(|p| p.x).collect();
+        assert_eq!(xs, vec![1.0, 2.0]);
+        assert_eq!(patrol.waypoint_config(0).unwrap()["delay"], "2");
+        assert!(patrol.waypoint_config(2).is_none());
+        assert!(paths.get("Missing").is_none());
+
+        let intro = paths.get("Intro").unwrap();
+        assert_eq!(intro.name(), "Intro");
+        assert!(intro.waypoint_config(0).is_none());
+        assert_eq!(intro.positions()[0].z, 3.0);
+
+        let spawners: Vec<_> = paths.spawners().collect();
+        assert_eq!(spawners.len(), 1);
+        assert_eq!(spawners[0].path_data.spawner_obj_id.id, 70000);
+        assert_eq!(spawners[0].positions().next().unwrap().y, 5.0);
+        assert_eq!(paths.of_type(PathType::Camera).count(), 1);
+        assert_eq!(paths.of_type(PathType::Rail).count(), 0);
+    }
+}
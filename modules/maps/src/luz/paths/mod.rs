@@ -2,3 +2,5 @@
 pub mod core;
 /// Parsing functions for path data
 pub mod parser;
+/// Writing functions for path data
+pub mod writer;
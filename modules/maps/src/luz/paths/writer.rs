@@ -0,0 +1,205 @@
+//! # Writing the path data of a zone file
+//!
+//! These functions are the inverse of the ones in the `parser` module. The
+//! optional fields of each path are written according to its [`PathVersion`].
+
+use super::core::*;
+use assembly_core::num_traits::ToPrimitive;
+use assembly_core::writer::{
+    write_f32, write_object_id, write_object_template, write_quat, write_quat_wxyz, write_u32,
+    write_u32_wstring, write_u8_bool, write_u8_wstring, write_vec3f, write_world_id,
+};
+use std::convert::TryFrom;
+use std::io::{self, ErrorKind, Write};
+
+fn missing(field: &str, version: PathVersion) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidInput,
+        format!("{} is required for path version {:?}", field, version),
+    )
+}
+
+fn write_enum<W: Write, T: ToPrimitive>(out: &mut W, value: &T) -> io::Result<()> {
+    write_u32(out, value.to_u32().unwrap_or_default())
+}
+
+fn write_len<W: Write>(out: &mut W, len: usize) -> io::Result<()> {
+    let len = u32::try_from(len).map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))?;
+    write_u32(out, len)
+}
+
+/// Write the config of a waypoint, ordered by key
+pub fn write_waypoint_config<W: Write>(out: &mut W, config: &WaypointConfig) -> io::Result<()> {
+    let mut entries: Vec<_> = config.iter().collect();
+    entries.sort();
+    write_len(out, entries.len())?;
+    for (key, value) in entries {
+        write_u8_wstring(out, key)?;
+        write_u8_wstring(out, value)?;
+    }
+    Ok(())
+}
+
+fn write_header<W: Write>(out: &mut W, header: &PathHeader, path_type: PathType) -> io::Result<()> {
+    write_enum(out, &header.version)?;
+    write_u8_wstring(out, &header.path_name)?;
+    write_enum(out, &path_type)?;
+    write_u32(out, header.value_1)?;
+    write_enum(out, &header.path_composition)
+}
+
+fn write_waypoints<W, D, P, F>(out: &mut W, path: &PathVariant<D, P>, f: F) -> io::Result<()>
+where
+    W: Write,
+    F: Fn(&mut W, usize) -> io::Result<()>,
+{
+    write_len(out, path.waypoints.len())?;
+    for (index, waypoint) in path.waypoints.iter().enumerate() {
+        write_vec3f(out, &waypoint.position)?;
+        f(out, index)?;
+    }
+    Ok(())
+}
+
+fn write_moving_platform<W: Write>(
+    out: &mut W,
+    path: &PathVariantMovingPlatform,
+) -> io::Result<()> {
+    let version = path.header.version;
+    let data = &path.path_data;
+    if version.min(18) {
+        let something = data
+            .something
+            .ok_or_else(|| missing("something", version))?;
+        out.write_all(&[something])?;
+    } else if version.min(13) {
+        let sound = data.platform_travel_sound.as_deref();
+        write_u8_wstring(out, sound.ok_or_else(|| missing("travel sound", version))?)?;
+    }
+    write_waypoints(out, path, |out, index| {
+        let data = &path.waypoints[index].data;
+        write_quat(out, &data.rotation)?;
+        write_u8_bool(out, data.lock_player)?;
+        write_f32(out, data.speed)?;
+        write_f32(out, data.wait)?;
+        if version.min(13) {
+            let sounds = data
+                .sounds
+                .as_ref()
+                .ok_or_else(|| missing("sounds", version))?;
+            write_u8_wstring(out, &sounds.depart_sound)?;
+            write_u8_wstring(out, &sounds.arrive_sound)?;
+        }
+        Ok(())
+    })
+}
+
+fn write_property<W: Write>(out: &mut W, path: &PathVariantProperty) -> io::Result<()> {
+    let data = &path.path_data;
+    write_u32(out, data.value_1)?;
+    write_u32(out, data.price)?;
+    write_u32(out, data.rental_time)?;
+    write_world_id(out, &data.associated_map)?;
+    write_u32(out, data.value_2)?;
+    write_u8_wstring(out, &data.display_name)?;
+    write_u32_wstring(out, &data.display_description)?;
+    write_u32(out, data.value_3)?;
+    write_u32(out, data.clone_limit)?;
+    write_f32(out, data.reputation_multiplier)?;
+    write_enum(out, &data.rental_time_unit)?;
+    write_enum(out, &data.achievement_required)?;
+    write_vec3f(out, &data.player_zone_coordinate)?;
+    write_f32(out, data.max_build_height)?;
+    write_waypoints(out, path, |_, _| Ok(()))
+}
+
+fn write_camera<W: Write>(out: &mut W, path: &PathVariantCamera) -> io::Result<()> {
+    let version = path.header.version;
+    write_u8_wstring(out, &path.path_data.next_path)?;
+    if version.min(14) {
+        let value_1 = path
+            .path_data
+            .value_1
+            .ok_or_else(|| missing("value_1", version))?;
+        out.write_all(&[value_1])?;
+    }
+    write_waypoints(out, path, |out, index| {
+        let data = &path.waypoints[index].data;
+        write_quat(out, &data.rotation)?;
+        for value in &[
+            data.time,
+            data.value_5,
+            data.tension,
+            data.continuity,
+            data.bias,
+        ] {
+            write_f32(out, *value)?;
+        }
+        Ok(())
+    })
+}
+
+fn write_spawner<W: Write>(out: &mut W, path: &PathVariantSpawner) -> io::Result<()> {
+    let data = &path.path_data;
+    write_object_template(out, &data.spawned_lot)?;
+    write_u32(out, data.respawn_time)?;
+    write_u32(out, data.max_to_spawn)?;
+    write_u32(out, data.min_to_spawn)?;
+    write_object_id(out, &data.spawner_obj_id)?;
+    write_u8_bool(out, data.activate_network_on_load)?;
+    write_waypoints(out, path, |out, index| {
+        let data = &path.waypoints[index].data;
+        write_quat(out, &data.rotation)?;
+        write_waypoint_config(out, &data.config)
+    })
+}
+
+fn write_race<W: Write>(out: &mut W, path: &PathVariantRace) -> io::Result<()> {
+    write_waypoints(out, path, |out, index| {
+        let data = &path.waypoints[index].data;
+        write_quat(out, &data.rotation)?;
+        out.write_all(&[data.value_1, data.value_2])?;
+        write_f32(out, data.value_3)?;
+        write_f32(out, data.value_4)?;
+        write_f32(out, data.value_5)
+    })
+}
+
+fn write_rail<W: Write>(out: &mut W, path: &PathVariantRail) -> io::Result<()> {
+    let version = path.header.version;
+    write_waypoints(out, path, |out, index| {
+        let data = &path.waypoints[index].data;
+        write_quat_wxyz(out, &data.rotation)?;
+        if version.min(17) {
+            write_f32(out, data.speed.ok_or_else(|| missing("speed", version))?)?;
+        }
+        write_waypoint_config(out, &data.config)
+    })
+}
+
+/// Write a single path
+pub fn write_path<W: Write>(out: &mut W, path: &Path) -> io::Result<()> {
+    write_header(out, path.header(), path.path_type())?;
+    match path {
+        Path::Movement(p) => write_waypoints(out, p, |out, index| {
+            write_waypoint_config(out, &p.waypoints[index].data.config)
+        }),
+        Path::MovingPlatform(p) => write_moving_platform(out, p),
+        Path::Property(p) => write_property(out, p),
+        Path::Camera(p) => write_camera(out, p),
+        Path::Spawner(p) => write_spawner(out, p),
+        Path::Showcase(p) => write_waypoints(out, p, |_, _| Ok(())),
+        Path::Race(p) => write_race(out, p),
+        Path::Rail(p) => write_rail(out, p),
+    }
+}
+
+/// Write all paths of a zone
+pub fn write_zone_paths<W: Write>(out: &mut W, paths: &ZonePaths) -> io::Result<()> {
+    write_enum(out, &paths.version)?;
+    write_len(out, paths.paths.len())?;
+    for path in &paths.paths {
+        write_path(out, path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,298 @@
+//! # Writing zone files
+//!
+//! A [`ZoneFile`] can be written in the format of its own [`FileVersion`] with
+//! [`ZoneFile::write`], or converted to another version with
+//! [`ZoneFile::write_version`]. Fields that a newer version requires, but
+//! that an older file doesn't have, are filled with neutral defaults.
+//!
+//! ```
+//! use assembly_maps::luz::{core::ZoneFile, parser::parse_zone_file};
+//!
+//! let bytes = [
+//!     0x26, 0, 0, 0, 1, 0, 0, 0, 0xe9, 0x03, 0, 0, // version, revision, world
+//!     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // spawn position
+//!     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80, 0x3f, // spawn rotation
+//!     0, 0, 0, 0, 0, 0, 0, 0, // no scenes, four empty strings
+//!     0, 0, 0, 0, 0, 0, 0, 0, // no transitions, no path data
+//! ];
+//! let (_, zone) = parse_zone_file(&bytes).unwrap();
+//!
+//! let mut out = Vec::new();
+//! zone.write(&mut out).unwrap();
+//! assert_eq!(&out[..], &bytes[..]);
+//! ```
+
+use super::core::{
+    FileVersion, PathData, SceneRef, SceneTransition, SceneTransitionInfo, SceneTransitionPoint,
+    ZoneFile,
+};
+use super::paths::{core::ZonePaths, writer::write_zone_paths};
+use assembly_core::types::{Placement3D, Quaternion, Vector3f};
+use assembly_core::writer::{write_quat, write_u32, write_u8_string, write_vec3f, write_world_id};
+use std::convert::TryFrom;
+use std::io::{self, ErrorKind, Write};
+
+/// Path data that can be written to a zone file
+pub trait WritePathData: PathData {
+    /// Write the path data, without the length prefix
+    fn write_path_data<W: Write>(&self, out: &mut W) -> io::Result<()>;
+}
+
+impl WritePathData for Vec<u8> {
+    fn write_path_data<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(self)
+    }
+}
+
+impl WritePathData for ZonePaths {
+    fn write_path_data<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write_zone_paths(out, self)
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, msg)
+}
+
+fn write_len<W: Write>(out: &mut W, len: usize) -> io::Result<()> {
+    write_u32(out, u32::try_from(len).map_err(|e| invalid(e.to_string()))?)
+}
+
+fn write_scene_ref<W: Write>(out: &mut W, scene: &SceneRef) -> io::Result<()> {
+    write_u8_string(out, &scene.file_name)?;
+    write_u32(out, scene.id)?;
+    write_u32(out, scene.layer)?;
+    write_u8_string(out, &scene.name)?;
+    out.write_all(&[0; 3])
+}
+
+fn write_transition_point<W: Write>(out: &mut W, point: &SceneTransitionPoint) -> io::Result<()> {
+    out.write_all(&point.scene_id.to_le_bytes())?;
+    write_vec3f(out, &point.point)
+}
+
+fn write_transition<W: Write>(
+    out: &mut W,
+    transition: &SceneTransition,
+    version: FileVersion,
+) -> io::Result<()> {
+    if version.id() < 0x25 {
+        write_u8_string(out, transition.name.as_deref().unwrap_or_default())?;
+    }
+    let five_points = version.id() > 0x21 && version.id() < 0x27;
+    let points: &[SceneTransitionPoint] = match &transition.points {
+        SceneTransitionInfo::Point2(p) if !five_points => p,
+        SceneTransitionInfo::Point5(p) if five_points => p,
+        _ => {
+            let msg = format!(
+                "wrong number of transition points for version {}",
+                version.id()
+            );
+            return Err(invalid(msg));
+        }
+    };
+    for point in points {
+        write_transition_point(out, point)?;
+    }
+    Ok(())
+}
+
+impl<P: WritePathData> ZoneFile<P> {
+    /// Write the zone file in the format of its own version
+    pub fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        self.write_version(out, self.file_version)
+    }
+
+    /// Write the zone file in the format of `version`
+    ///
+    /// Returns an error of kind [`ErrorKind::InvalidInput`] if the data can't
+    /// be represented in that version, e.g. because the scene transitions have
+    /// the wrong number of points.
+    pub fn write_version<W: Write>(&self, out: &mut W, version: FileVersion) -> io::Result<()> {
+        write_u32(out, version.id())?;
+        if version.min(0x24) {
+            write_u32(out, self.file_revision.unwrap_or_default())?;
+        }
+        write_world_id(out, &self.world_id)?;
+        if version.min(0x26) {
+            let spawn = self.spawn_point.unwrap_or(Placement3D {
+                pos: Vector3f::new(0.0, 0.0, 0.0),
+                rot: Quaternion::new(0.0, 0.0, 0.0, 1.0),
+            });
+            write_vec3f(out, &spawn.pos)?;
+            write_quat(out, &spawn.rot)?;
+        }
+        if version.min(0x25) {
+            write_len(out, self.scene_refs.len())?;
+        } else {
+            let count = u8::try_from(self.scene_refs.len()).map_err(|e| invalid(e.to_string()))?;
+            out.write_all(&[count])?;
+        }
+        for scene in &self.scene_refs {
+            write_scene_ref(out, scene)?;
+        }
+        write_u8_string(out, &self.something)?;
+        write_u8_string(out, &self.map_filename)?;
+        write_u8_string(out, &self.map_name)?;
+        write_u8_string(out, &self.map_description)?;
+        if version.min(0x20) {
+            let transitions = self.scene_transitions.as_deref().unwrap_or_default();
+            write_len(out, transitions.len())?;
+            for transition in transitions {
+                write_transition(out, transition, version)?;
+            }
+        }
+        if version.min(0x23) {
+            let mut data = Vec::new();
+            if let Some(path_data) = &self.path_data {
+                path_data.write_path_data(&mut data)?;
+            }
+            write_len(out, data.len())?;
+            out.write_all(&data)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::luz::parser::parse_zone_file;
+    use crate::luz::paths::core::*;
+    use assembly_core::num_traits::FromPrimitive;
+    use assembly_core::types::{ObjectID, ObjectTemplate, WorldID};
+    use std::collections::HashMap;
+
+    fn header(name: &str, version: u32) -> PathHeader {
+        PathHeader {
+            version: PathVersion::from_u32(version).unwrap(),
+            path_name: name.to_string(),
+            value_1: 0,
+            path_composition: PathComposition::Line,
+        }
+    }
+
+    fn config() -> WaypointConfig {
+        let mut config = HashMap::new();
+        config.insert("delay".to_string(), "1.5".to_string());
+        config
+    }
+
+    fn zone() -> ZoneFile<ZonePaths> {
+        let pos = Vector3f::new(1.0, 2.0, 3.0);
+        let rot = Quaternion::new(0.0, 0.0, 0.0, 1.0);
+        let spawner = PathVariantSpawner {
+            header: header("spawner", 18),
+            path_data: PathDataSpawner {
+                spawned_lot: ObjectTemplate::from_u32(6316).unwrap(),
+                respawn_time: 10,
+                max_to_spawn: u32::MAX,
+                min_to_spawn: 1,
+                spawner_obj_id: ObjectID::new(0, 1234),
+                activate_network_on_load: true,
+            },
+            waypoints: vec![PathWaypointVariant {
+                position: pos,
+                data: PathWaypointDataSpawner {
+                    rotation: rot,
+                    config: config(),
+                },
+            }],
+        };
+        let platform = PathVariantMovingPlatform {
+            header: header("platform", 18),
+            path_data: PathDataMovingPlatform {
+                something: Some(1),
+                platform_travel_sound: None,
+            },
+            waypoints: vec![PathWaypointVariant {
+                position: pos,
+                data: PathWaypointDataMovingPlatform {
+                    rotation: rot,
+                    lock_player: false,
+                    speed: 2.0,
+                    wait: 0.5,
+                    sounds: Some(PathWaypointDataMovingPlatformSounds {
+                        arrive_sound: "arrive".to_string(),
+                        depart_sound: "depart".to_string(),
+                    }),
+                },
+            }],
+        };
+        let rail = PathVariantRail {
+            header: header("rail", 17),
+            path_data: PathDataRail {},
+            waypoints: vec![PathWaypointVariant {
+                position: pos,
+                data: PathWaypointDataRail {
+                    rotation: rot,
+                    speed: Some(3.0),
+                    config: config(),
+                },
+            }],
+        };
+        ZoneFile {
+            file_version: FileVersion::from(0x29),
+            file_revision: Some(7),
+            world_id: WorldID::from_u32(1100).unwrap(),
+            spawn_point: Some(Placement3D { pos, rot }),
+            scene_refs: vec![SceneRef {
+                file_name: "global.lvl".to_string(),
+                id: 0,
+                layer: 0,
+                name: "Global".to_string(),
+            }],
+            something: String::new(),
+            map_filename: "nd_avant_gardens.raw".to_string(),
+            map_name: "Avant Gardens".to_string(),
+            map_description: String::new(),
+            scene_transitions: Some(vec![SceneTransition {
+                name: None,
+                points: SceneTransitionInfo::Point2([
+                    SceneTransitionPoint {
+                        scene_id: 0,
+                        point: pos,
+                    },
+                    SceneTransitionPoint {
+                        scene_id: 1,
+                        point: pos,
+                    },
+                ]),
+            }]),
+            path_data: Some(ZonePaths {
+                version: ZonePathsVersion::from_u32(18).unwrap(),
+                paths: vec![
+                    Path::Spawner(spawner),
+                    Path::MovingPlatform(platform),
+                    Path::Rail(rail),
+                ],
+            }),
+        }
+    }
+
+    #[test]
+    fn test_write_zone() {
+        let zone = zone();
+        let mut bytes = Vec::new();
+        zone.write(&mut bytes).unwrap();
+
+        let (rest, parsed) = parse_zone_file(&bytes).unwrap();
+        assert!(rest.is_empty());
+        let parsed = parsed.parse_paths().map_err(|e| e.1).unwrap();
+        assert_eq!(format!("{:?}", parsed), format!("{:?}", zone));
+
+        // an older version has a single byte scene count and named transitions
+        let mut old = Vec::new();
+        zone.write_version(&mut old, FileVersion::from(0x21))
+            .unwrap();
+        let (_, parsed) = parse_zone_file(&old).unwrap();
+        assert_eq!(parsed.scene_refs.len(), 1);
+        assert_eq!(
+            parsed.scene_transitions.unwrap()[0].name.as_deref(),
+            Some("")
+        );
+        assert!(zone
+            .write_version(&mut Vec::new(), FileVersion::from(0x22))
+            .is_err());
+    }
+}
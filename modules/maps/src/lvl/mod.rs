@@ -1,8 +1,13 @@
 //! # The level (`*.lvl`) file format
 //!
-//! This module can be used to read the level file format
+//! This module can be used to read and write the level file format
 //! used in the game LEGO Universe.
 
 pub mod file;
 pub mod parser;
+#[cfg(feature = "xml")]
+pub mod prefab;
 pub mod reader;
+pub mod spatial;
+pub mod spawn;
+pub mod writer;
@@ -0,0 +1,292 @@
+//! # XML object files (prefabs)
+//!
+//! Some mods ship groups of objects as XML instead of level files. This module
+//! reads them into the same [`Object`] structures as the objects of a level,
+//! so that they can be processed in the same way, e.g. with
+//! [`Prefab::spawn_templates`].
+//!
+//! ```xml
+//! <prefab name="camp">
+//!   <object id="70000" lot="6326" pos="1 2 3" rot="0 0 0 1" scale="1">
+//!     <setting key="spawntemplate" type="1" value="-1"/>
+//!   </object>
+//! </prefab>
+//! ```
+//!
+//! The `scope`, `rot` and `scale` attributes of an object are optional, the
+//! `type` of a setting is a [`ValueType`] number. Unknown elements are skipped.
+//!
+//! This module is only available with the `xml` feature.
+
+use std::{collections::BTreeMap, io::BufRead, str::FromStr};
+
+use assembly_core::{
+    displaydoc::Display,
+    ldf::{LDFError, Value, ValueType, LDF},
+    num_traits::FromPrimitive,
+    types::{ObjectID, ObjectTemplate, Quaternion, Vector3f},
+};
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use thiserror::Error;
+
+use super::{
+    file::{Level, Object},
+    spawn::SpawnTemplate,
+};
+
+/// Error when reading a prefab
+#[derive(Debug, Display, Error)]
+pub enum PrefabError {
+    /// Malformed XML: {0}
+    Xml(#[from] quick_xml::Error),
+    /// Missing attribute `{1}` on `<{0}>`
+    MissingAttribute(&'static str, &'static str),
+    /// Invalid value {value:?} for attribute `{attr}`
+    InvalidAttribute {
+        /// The name of the attribute
+        attr: &'static str,
+        /// The value of the attribute
+        value: String,
+    },
+    /// `<setting>` outside of an `<object>`
+    OrphanSetting,
+    /// Invalid setting: {0}
+    Setting(#[from] LDFError),
+}
+
+/// The result type for this module
+pub type Result<T> = std::result::Result<T, PrefabError>;
+
+/// A group of objects from an XML object file
+#[derive(Debug)]
+pub struct Prefab {
+    /// The `name` attribute of the root element
+    pub name: Option<String>,
+    /// The objects, in the order of the file
+    pub objects: Vec<Object<LDF>>,
+}
+
+struct Attributes {
+    elem: &'static str,
+    map: BTreeMap<Vec<u8>, String>,
+}
+
+impl Attributes {
+    fn read<B: BufRead>(elem: &'static str, start: &BytesStart, xml: &Reader<B>) -> Result<Self> {
+        let mut map = BTreeMap::new();
+        for attr in start.attributes() {
+            let attr = attr?;
+            let value = attr.unescape_and_decode_value(xml)?;
+            map.insert(attr.key.to_vec(), value);
+        }
+        Ok(Self { elem, map })
+    }
+
+    fn get(&self, attr: &'static str) -> Option<&str> {
+        self.map.get(attr.as_bytes()).map(String::as_str)
+    }
+
+    fn require(&self, attr: &'static str) -> Result<&str> {
+        self.get(attr)
+            .ok_or(PrefabError::MissingAttribute(self.elem, attr))
+    }
+
+    fn parse<T: FromStr>(&self, attr: &'static str, default: Option<T>) -> Result<T> {
+        let value = match (self.get(attr), default) {
+            (None, Some(default)) => return Ok(default),
+            (value, _) => value.ok_or(PrefabError::MissingAttribute(self.elem, attr))?,
+        };
+        value.trim().parse().map_err(|_| invalid(attr, value))
+    }
+
+    fn floats<const N: usize>(&self, attr: &'static str) -> Result<Option<[f32; N]>> {
+        let value = match self.get(attr) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let mut out = [0.0; N];
+        let mut parts = value
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty());
+        for slot in out.iter_mut() {
+            let part = parts.next().ok_or_else(|| invalid(attr, value))?;
+            *slot = part.parse().map_err(|_| invalid(attr, value))?;
+        }
+        match parts.next() {
+            Some(_) => Err(invalid(attr, value)),
+            None => Ok(Some(out)),
+        }
+    }
+}
+
+fn invalid(attr: &'static str, value: &str) -> PrefabError {
+    PrefabError::InvalidAttribute {
+        attr,
+        value: value.to_string(),
+    }
+}
+
+fn read_object(attrs: &Attributes) -> Result<Object<LDF>> {
+    let lot: u32 = attrs.parse("lot", None)?;
+    let [x, y, z] = attrs
+        .floats("pos")?
+        .ok_or(PrefabError::MissingAttribute("object", "pos"))?;
+    let [rx, ry, rz, rw] = attrs.floats("rot")?.unwrap_or([0.0, 0.0, 0.0, 1.0]);
+    Ok(Object {
+        obj_id: ObjectID::new(attrs.parse("scope", Some(0))?, attrs.parse("id", None)?),
+        lot: ObjectTemplate::from_u32(lot).ok_or_else(|| invalid("lot", &lot.to_string()))?,
+        asset_type: None,
+        value_1: None,
+        position: Vector3f::new(x, y, z),
+        rotation: Quaternion::new(rx, ry, rz, rw),
+        scale: attrs.parse("scale", Some(1.0))?,
+        settings: LDF {
+            map: BTreeMap::new(),
+        },
+        extra: Vec::new(),
+    })
+}
+
+fn read_setting(attrs: &Attributes, settings: &mut LDF) -> Result<()> {
+    let key = attrs.require("key")?;
+    let text = attrs.require("value")?;
+    let id: u8 = attrs.parse("type", None)?;
+    let ty = ValueType::from_id(id).ok_or_else(|| LDFError::UnknownType {
+        key: key.to_string(),
+        ty: id.to_string(),
+    })?;
+    let value = Value::parse(ty, text).ok_or_else(|| LDFError::InvalidValue {
+        key: key.to_string(),
+        ty,
+        value: text.to_string(),
+    })?;
+    settings.map.insert(key.to_string(), value);
+    Ok(())
+}
+
+impl Prefab {
+    /// Read a prefab from an XML document
+    pub fn from_reader<B: BufRead>(reader: B) -> Result<Self> {
+        let mut xml = Reader::from_reader(reader);
+        xml.trim_text(true);
+        let mut buf = Vec::new();
+        let mut prefab = Prefab {
+            name: None,
+            objects: Vec::new(),
+        };
+        let mut depth = 0usize;
+        // The object whose `<object>` element is currently open
+        let mut current: Option<Object<LDF>> = None;
+        loop {
+            let event = xml.read_event(&mut buf)?;
+            let (start, empty) = match &event {
+                Event::Start(start) => (start, false),
+                Event::Empty(start) => (start, true),
+                Event::End(_) => {
+                    depth -= 1;
+                    if depth == 1 {
+                        prefab.objects.extend(current.take());
+                    }
+                    buf.clear();
+                    continue;
+                }
+                Event::Eof => break,
+                _ => {
+                    buf.clear();
+                    continue;
+                }
+            };
+            match (depth, start.name()) {
+                (0, _) => {
+                    let attrs = Attributes::read("prefab", start, &xml)?;
+                    prefab.name = attrs.get("name").map(str::to_string);
+                }
+                (1, b"object") => {
+                    let object = read_object(&Attributes::read("object", start, &xml)?)?;
+                    if empty {
+                        prefab.objects.push(object);
+                    } else {
+                        current = Some(object);
+                    }
+                }
+                (2, b"setting") => {
+                    let attrs = Attributes::read("setting", start, &xml)?;
+                    let object = current.as_mut().ok_or(PrefabError::OrphanSetting)?;
+                    read_setting(&attrs, &mut object.settings)?;
+                }
+                (_, b"setting") => return Err(PrefabError::OrphanSetting),
+                _ => {}
+            }
+            if !empty {
+                depth += 1;
+            }
+            buf.clear();
+        }
+        Ok(prefab)
+    }
+
+    /// Get the spawn templates of all objects
+    pub fn spawn_templates(&self) -> impl Iterator<Item = SpawnTemplate<'_>> {
+        self.objects.iter().map(SpawnTemplate::from)
+    }
+
+    /// Turn the prefab into a level without an environment
+    pub fn into_level(self) -> Level {
+        Level {
+            env: None,
+            objects: self.objects,
+        }
+    }
+}
+
+impl FromStr for Prefab {
+    type Err = PrefabError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_reader(s.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefab() {
+        let text = r#"<?xml version="1.0"?>
+            <prefab name="camp">
+              <!-- a comment -->
+              <object id="70000" lot="6326" pos="1 2 3" rot="0, 0.5, 0, 0.5">
+                <setting key="custom_script_server" type="0" value="scripts\a.lua"/>
+                <setting key="spawntemplate" type="1" value="-1"/>
+                <editor color="red"/>
+              </object>
+              <object id="70001" scope="2" lot="31" pos="0 0 0" scale="2.5"/>
+            </prefab>"#;
+        let prefab: Prefab = text.parse().unwrap();
+        assert_eq!(prefab.name.as_deref(), Some("camp"));
+        let templates: Vec<_> = prefab.spawn_templates().collect();
+        assert_eq!(templates.len(), 2);
+        assert_eq!(templates[0].lot, 6326);
+        assert_eq!(templates[0].transform.rot.y, 0.5);
+        assert_eq!(
+            templates[0].settings.to_string(),
+            "custom_script_server=0:scripts\\a.lua\nspawntemplate=1:-1"
+        );
+        assert_eq!((templates[1].object_id.scope, templates[1].scale), (2, 2.5));
+
+        let bad = r#"<prefab><object id="1" lot="1" pos="0 0 0"><setting key="a" type="7" value="yes"/></object></prefab>"#;
+        assert!(matches!(
+            bad.parse::<Prefab>(),
+            Err(PrefabError::Setting(LDFError::InvalidValue { .. }))
+        ));
+        let bad = r#"<prefab><object id="1" lot="1" pos="0 0"/></prefab>"#;
+        assert!(matches!(
+            bad.parse::<Prefab>(),
+            Err(PrefabError::InvalidAttribute { attr: "pos", .. })
+        ));
+    }
+}
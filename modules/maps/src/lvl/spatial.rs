@@ -0,0 +1,362 @@
+//! # Spatial queries for placed objects
+//!
+//! A [`SpatialIndex`] sorts the objects of a level into a uniform grid, so that
+//! servers can find the objects near a player without looking at every object
+//! of the level. It borrows the objects and is cheap to build, so it can be
+//! built on demand with [`Level::spatial_index`].
+//!
+//! ```
+//! use assembly_maps::lvl::{file::Level, spatial::Aabb};
+//! use assembly_core::types::Vector3f;
+//!
+//! let level = Level { env: None, objects: Vec::new() };
+//! let index = level.spatial_index(64.0);
+//! let area = Aabb::around(Vector3f::new(0.0, 0.0, 0.0), 100.0);
+//! assert!(index.objects_within(&area).is_empty());
+//! assert!(index.nearest(Vector3f::new(0.0, 0.0, 0.0), 5).is_empty());
+//! ```
+
+use std::collections::HashMap;
+
+use assembly_core::{ldf::LDF, types::Vector3f};
+
+use super::file::{Level, Object};
+
+/// An axis aligned bounding box
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    /// The corner with the smallest coordinates
+    pub min: Vector3f,
+    /// The corner with the largest coordinates
+    pub max: Vector3f,
+}
+
+impl Aabb {
+    /// Create a box from two opposite corners
+    pub fn new(a: Vector3f, b: Vector3f) -> Self {
+        Self {
+            min: Vector3f::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: Vector3f::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    /// Create a cube with the given center and half side length
+    pub fn around(center: Vector3f, radius: f32) -> Self {
+        let r = Vector3f::new(radius, radius, radius);
+        Self {
+            min: Vector3f::new(center.x - r.x, center.y - r.y, center.z - r.z),
+            max: Vector3f::new(center.x + r.x, center.y + r.y, center.z + r.z),
+        }
+    }
+
+    /// Check whether the point is inside of the box, including the border
+    pub fn contains(&self, p: &Vector3f) -> bool {
+        (self.min.x..=self.max.x).contains(&p.x)
+            && (self.min.y..=self.max.y).contains(&p.y)
+            && (self.min.z..=self.max.z).contains(&p.z)
+    }
+}
+
+type Cell = (i32, i32, i32);
+
+fn distance_sq(a: &Vector3f, b: &Vector3f) -> f32 {
+    let (x, y, z) = (a.x - b.x, a.y - b.y, a.z - b.z);
+    x * x + y * y + z * z
+}
+
+/// A uniform grid over the positions of objects
+#[derive(Debug, Clone)]
+pub struct SpatialIndex<'a, S> {
+    objects: &'a [Object<S>],
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<usize>>,
+    bounds: Option<(Cell, Cell)>,
+}
+
+impl<'a, S> SpatialIndex<'a, S> {
+    /// Build the grid, with cubic cells of side length `cell_size`
+    ///
+    /// Panics if `cell_size` is not positive.
+    pub fn new(objects: &'a [Object<S>], cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "cell size must be positive");
+        let mut index = Self {
+            objects,
+            cell_size,
+            cells: HashMap::new(),
+            bounds: None,
+        };
+        for (i, object) in objects.iter().enumerate() {
+            let cell = index.cell(&object.position);
+            index.cells.entry(cell).or_default().push(i);
+            index.bounds = Some(match index.bounds {
+                None => (cell, cell),
+                Some((lo, hi)) => (
+                    (lo.0.min(cell.0), lo.1.min(cell.1), lo.2.min(cell.2)),
+                    (hi.0.max(cell.0), hi.1.max(cell.1), hi.2.max(cell.2)),
+                ),
+            });
+        }
+        index
+    }
+
+    fn cell(&self, p: &Vector3f) -> Cell {
+        let f = |v: f32| (v / self.cell_size).floor() as i32;
+        (f(p.x), f(p.y), f(p.z))
+    }
+
+    /// Get the number of objects
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Check whether there are no objects
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Get all objects whose position is inside of `area`
+    ///
+    /// The objects are returned in the order of the level.
+    pub fn objects_within(&self, area: &Aabb) -> Vec<&'a Object<S>> {
+        let (bounds_lo, bounds_hi) = match self.bounds {
+            Some(bounds) => bounds,
+            None => return Vec::new(),
+        };
+        let (lo, hi) = (self.cell(&area.min), self.cell(&area.max));
+        let mut found = Vec::new();
+        for x in lo.0.max(bounds_lo.0)..=hi.0.min(bounds_hi.0) {
+            for y in lo.1.max(bounds_lo.1)..=hi.1.min(bounds_hi.1) {
+                for z in lo.2.max(bounds_lo.2)..=hi.2.min(bounds_hi.2) {
+                    if let Some(cell) = self.cells.get(&(x, y, z)) {
+                        let objects = cell.iter().copied();
+                        found.extend(objects.filter(|&i| area.contains(&self.objects[i].position)));
+                    }
+                }
+            }
+        }
+        found.sort_unstable();
+        found.into_iter().map(|i| &self.objects[i]).collect()
+    }
+
+    /// Get the `n` objects closest to `pos`, with their distance
+    ///
+    /// The objects are ordered by distance, ties are broken by the order of the
+    /// level. The cells are searched in rings around `pos`, starting at the
+    /// first ring that touches the grid. If the rings would visit more cells
+    /// than there are occupied ones, all objects are compared instead.
+    pub fn nearest(&self, pos: Vector3f, n: usize) -> Vec<(&'a Object<S>, f32)> {
+        let (lo, hi) = match self.bounds {
+            Some((lo, hi)) if n > 0 => (widen(lo), widen(hi)),
+            _ => return Vec::new(),
+        };
+        let center = widen(self.cell(&pos));
+        let axes = [
+            (center.0, lo.0, hi.0),
+            (center.1, lo.1, hi.1),
+            (center.2, lo.2, hi.2),
+        ];
+        let gap = |&(c, lo, hi): &Axis| (lo - c).max(c - hi).max(0);
+        let first_ring = axes.iter().map(gap).max().unwrap_or(0);
+        let max_ring = axes.iter().map(|&(c, lo, hi)| (c - lo).max(hi - c)).max();
+        let max_ring = max_ring.unwrap_or(0);
+
+        let budget = self.cells.len() as u128;
+        let mut visited = 0u128;
+        let mut candidates: Vec<(f32, usize)> = Vec::new();
+        for ring in first_ring..=max_ring {
+            let cells = box_cells(&axes, ring) - box_cells(&axes, ring - 1);
+            let (start, end) = clip(axes[0], ring);
+            visited += cells.max((end - start + 1) as u128);
+            if visited > budget {
+                candidates = (0..self.objects.len())
+                    .map(|i| (distance_sq(&pos, &self.objects[i].position), i))
+                    .collect();
+                break;
+            }
+            self.visit_ring(&axes, ring, |i| {
+                let d = distance_sq(&pos, &self.objects[i].position);
+                candidates.push((d, i));
+            });
+            // Everything outside of this ring is at least `ring * cell_size` away
+            if candidates.len() >= n {
+                candidates.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+                let reach = ring as f32 * self.cell_size;
+                if candidates[n - 1].0 <= reach * reach {
+                    break;
+                }
+            }
+        }
+        candidates.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        candidates.truncate(n);
+        let objects = candidates.into_iter();
+        objects.map(|(d, i)| (&self.objects[i], d.sqrt())).collect()
+    }
+
+    /// Call `f` for every object in the cells at chebyshev distance `ring`
+    /// from the center, only visiting the cells on the surface of the ring
+    /// that are inside of the grid
+    fn visit_ring<F: FnMut(usize)>(&self, axes: &[Axis; 3], ring: i64, mut f: F) {
+        let [(cx, ..), (cy, ..), _] = *axes;
+        let [xs, ys, zs] = [0, 1, 2].map(|i| clip(axes[i], ring));
+        let edges = |(c, lo, hi): Axis| {
+            let mut edges = vec![c - ring, c + ring];
+            edges.dedup();
+            edges.retain(|v| (lo..=hi).contains(v));
+            edges
+        };
+        let (y_edges, z_edges) = (edges(axes[1]), edges(axes[2]));
+        let mut visit = |x: i64, y: i64, z: i64| {
+            // all coordinates are inside of the bounds, which are `i32`s
+            let cell = (x as i32, y as i32, z as i32);
+            if let Some(objects) = self.cells.get(&cell) {
+                objects.iter().copied().for_each(&mut f);
+            }
+        };
+        for x in xs.0..=xs.1 {
+            if (x - cx).abs() == ring {
+                for y in ys.0..=ys.1 {
+                    (zs.0..=zs.1).for_each(|z| visit(x, y, z));
+                }
+                continue;
+            }
+            for &y in &y_edges {
+                (zs.0..=zs.1).for_each(|z| visit(x, y, z));
+            }
+            if z_edges.is_empty() {
+                continue;
+            }
+            for y in ys.0..=ys.1 {
+                if (y - cy).abs() != ring {
+                    z_edges.iter().for_each(|&z| visit(x, y, z));
+                }
+            }
+        }
+    }
+}
+
+/// The center, lower and upper bound of the search along one axis
+type Axis = (i64, i64, i64);
+
+fn widen(cell: Cell) -> (i64, i64, i64) {
+    (i64::from(cell.0), i64::from(cell.1), i64::from(cell.2))
+}
+
+/// The part of `c - ring..=c + ring` that is inside of the bounds
+fn clip((c, lo, hi): Axis, ring: i64) -> (i64, i64) {
+    ((c - ring).max(lo), (c + ring).min(hi))
+}
+
+/// The number of cells within chebyshev distance `ring` that are inside of the bounds
+fn box_cells(axes: &[Axis; 3], ring: i64) -> u128 {
+    if ring < 0 {
+        return 0;
+    }
+    let len = |axis: &Axis| {
+        let (start, end) = clip(*axis, ring);
+        (end - start + 1).max(0) as u128
+    };
+    axes.iter().map(len).product()
+}
+
+impl Level {
+    /// Build a [`SpatialIndex`] over the objects of this level
+    pub fn spatial_index(&self, cell_size: f32) -> SpatialIndex<'_, LDF> {
+        SpatialIndex::new(&self.objects, cell_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assembly_core::num_traits::FromPrimitive;
+    use assembly_core::types::{ObjectID, ObjectTemplate, Quaternion};
+
+    fn object(id: u32, x: f32, z: f32) -> Object<()> {
+        Object {
+            obj_id: ObjectID::new(0, id),
+            lot: ObjectTemplate::from_u32(1).unwrap(),
+            asset_type: None,
+            value_1: None,
+            position: Vector3f::new(x, 0.0, z),
+            rotation: Quaternion::new(0.0, 0.0, 0.0, 1.0),
+            scale: 1.0,
+            settings: (),
+            extra: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_spatial_index() {
+        let objects: Vec<_> = (0..100)
+            .map(|i| object(i, (i % 10) as f32 * 7.5, (i / 10) as f32 * -7.5))
+            .collect();
+        let index = SpatialIndex::new(&objects, 16.0);
+
+        let area = Aabb::new(
+            Vector3f::new(0.0, -1.0, 0.0),
+            Vector3f::new(15.0, 1.0, -7.5),
+        );
+        let ids: Vec<u32> = index
+            .objects_within(&area)
+            .iter()
+            .map(|o| o.obj_id.id)
+            .collect();
+        assert_eq!(ids, vec![0, 1, 2, 10, 11, 12]);
+
+        // compare with a linear search
+        let pos = Vector3f::new(40.0, 3.0, -40.0);
+        let mut expected: Vec<_> = objects
+            .iter()
+            .map(|o| (distance_sq(&pos, &o.position), o.obj_id.id))
+            .collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let nearest: Vec<u32> = index
+            .nearest(pos, 7)
+            .iter()
+            .map(|(o, _)| o.obj_id.id)
+            .collect();
+        let expected: Vec<u32> = expected.iter().take(7).map(|(_, id)| *id).collect();
+        assert_eq!(nearest, expected);
+        assert_eq!(index.nearest(pos, 1000).len(), 100);
+
+        let far = index.nearest(Vector3f::new(1000.0, 0.0, 1000.0), 1);
+        assert_eq!(far[0].0.obj_id.id, 9);
+    }
+
+    #[test]
+    fn test_nearest_sparse() {
+        let mut objects: Vec<_> = (0..50)
+            .map(|i| {
+                let mut o = object(i, (i * 37 % 101) as f32 * 3.1, (i * 53 % 97) as f32 * -2.7);
+                o.position.y = (i * 11 % 13) as f32 * 4.3;
+                o
+            })
+            .collect();
+        objects.push(object(50, 1.0e6, -1.0e6));
+        let index = SpatialIndex::new(&objects, 1.0);
+        for &(x, y, z) in &[(0.0, 0.0, 0.0), (150.0, 20.0, -130.0), (-5.0e5, 9.0, 2.0e5)] {
+            let pos = Vector3f::new(x, y, z);
+            let mut expected: Vec<_> = objects
+                .iter()
+                .map(|o| (distance_sq(&pos, &o.position), o.obj_id.id))
+                .collect();
+            expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let expected: Vec<u32> = expected.iter().take(5).map(|(_, id)| *id).collect();
+            let nearest = index.nearest(pos, 5);
+            let nearest: Vec<u32> = nearest.iter().map(|(o, _)| o.obj_id.id).collect();
+            assert_eq!(nearest, expected);
+        }
+
+        // cells at the limits of `i32` must not overflow
+        let extreme = vec![object(0, -1.0e12, 0.0), object(1, 1.0e12, 0.0)];
+        let index = SpatialIndex::new(&extreme, 0.001);
+        assert_eq!(
+            index.nearest(Vector3f::new(5.0e11, 0.0, 0.0), 1)[0]
+                .0
+                .obj_id
+                .id,
+            1
+        );
+        assert_eq!(index.nearest(Vector3f::new(0.0, 0.0, 0.0), 2).len(), 2);
+    }
+}
@@ -0,0 +1,207 @@
+//! # Spawn templates for level objects
+//!
+//! This module turns the objects of a [`Level`] into [`SpawnTemplate`]s, which
+//! contain everything a server needs to create the object. With the `fdb`
+//! feature, the components of the object template (LOT) can be looked up in
+//! the core database.
+
+use assembly_core::{
+    ldf::LDF,
+    num_traits::ToPrimitive,
+    types::{ObjectID, Placement3D},
+};
+
+use super::file::{Level, Object};
+
+/// The data needed to spawn a single object
+#[derive(Debug, Clone)]
+pub struct SpawnTemplate<'a> {
+    /// The ID of the object
+    pub object_id: &'a ObjectID,
+    /// The object template (LOT)
+    pub lot: u32,
+    /// The position and rotation
+    pub transform: Placement3D,
+    /// The uniform scale
+    pub scale: f32,
+    /// The config data of the object
+    pub settings: &'a LDF,
+}
+
+impl<'a> From<&'a Object<LDF>> for SpawnTemplate<'a> {
+    fn from(object: &'a Object<LDF>) -> Self {
+        SpawnTemplate {
+            object_id: &object.obj_id,
+            lot: object.lot.to_u32().unwrap_or_default(),
+            transform: Placement3D {
+                pos: object.position,
+                rot: object.rotation,
+            },
+            scale: object.scale,
+            settings: &object.settings,
+        }
+    }
+}
+
+impl Level {
+    /// Get an iterator over the spawn templates of all objects in the level
+    pub fn objects(&self) -> impl Iterator<Item = SpawnTemplate<'_>> {
+        self.objects.iter().map(SpawnTemplate::from)
+    }
+}
+
+#[cfg(feature = "fdb")]
+mod fdb {
+    use assembly_core::buffer::CastError;
+    use assembly_data::fdb::{
+        common::Value,
+        mem::{Row, Tables},
+    };
+
+    use super::SpawnTemplate;
+
+    /// A component of an object template, from the `ComponentsRegistry` table
+    ///
+    /// This is only available with the `fdb` feature.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub struct ComponentRef {
+        /// The type of the component
+        pub component_type: i32,
+        /// The ID of the row in the table for the component type
+        pub component_id: i32,
+    }
+
+    fn component_ref(row: Row<'_>) -> Option<ComponentRef> {
+        match (row.field_at(1), row.field_at(2)) {
+            (Some(Value::Integer(component_type)), Some(Value::Integer(component_id))) => {
+                Some(ComponentRef {
+                    component_type,
+                    component_id,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Get all components of the object template `lot`
+    ///
+    /// Returns an empty list if there is no `ComponentsRegistry` table.
+    pub fn components(tables: Tables<'_>, lot: u32) -> Result<Vec<ComponentRef>, CastError> {
+        match tables.by_name("ComponentsRegistry").transpose()? {
+            Some(table) => Ok(table.index_iter(lot).filter_map(component_ref).collect()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    impl SpawnTemplate<'_> {
+        /// Get all components of the object template of this object
+        ///
+        /// This is only available with the `fdb` feature.
+        pub fn components(&self, tables: Tables<'_>) -> Result<Vec<ComponentRef>, CastError> {
+            components(tables, self.lot)
+        }
+    }
+}
+
+#[cfg(feature = "fdb")]
+pub use fdb::{components, ComponentRef};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assembly_core::{
+        num_traits::FromPrimitive,
+        types::{ObjectTemplate, Quaternion, Vector3f},
+    };
+
+    fn object(id: u32, lot: u32, settings: &str) -> Object<LDF> {
+        Object {
+            obj_id: ObjectID::new(1 << 10, id),
+            lot: ObjectTemplate::from_u32(lot).unwrap(),
+            asset_type: None,
+            value_1: None,
+            position: Vector3f::new(1.0, 2.0, 3.0),
+            rotation: Quaternion::new(0.0, 0.5, 0.0, 0.5),
+            scale: 2.0,
+            settings: settings.parse().unwrap(),
+            extra: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_objects() {
+        let level = Level {
+            env: None,
+            objects: vec![
+                object(70000, 6326, "spawntemplate=1:-1"),
+                object(70001, 31, "renderDisabled=7:1"),
+            ],
+        };
+        let templates: Vec<_> = level.objects().collect();
+        assert_eq!(templates.len(), 2);
+        let first = &templates[0];
+        assert_eq!(
+            (first.object_id.scope, first.object_id.id),
+            (1 << 10, 70000)
+        );
+        assert_eq!((first.lot, first.scale), (6326, 2.0));
+        assert_eq!(first.transform.pos.z, 3.0);
+        assert_eq!(first.transform.rot.y, 0.5);
+        assert_eq!(first.settings.to_string(), "spawntemplate=1:-1");
+        assert_eq!(templates[1].lot, 31);
+    }
+
+    #[cfg(feature = "fdb")]
+    #[test]
+    fn test_components() {
+        use assembly_data::fdb::{
+            common::ValueType, core::Field, mem::Database, testing::SampleDatabase,
+        };
+
+        let columns = [
+            ("id", ValueType::Integer),
+            ("component_type", ValueType::Integer),
+            ("component_id", ValueType::Integer),
+        ];
+        let buf = SampleDatabase::new()
+            .table("ComponentsRegistry", &columns)
+            .row(vec![
+                Field::Integer(31),
+                Field::Integer(1),
+                Field::Integer(0),
+            ])
+            .row(vec![
+                Field::Integer(31),
+                Field::Integer(2),
+                Field::Integer(7),
+            ])
+            .row(vec![
+                Field::Integer(32),
+                Field::Integer(5),
+                Field::Integer(9),
+            ])
+            .build();
+        let tables = Database::new(&buf).tables().unwrap();
+
+        let object = object(1, 31, "renderDisabled=7:1");
+        let template = SpawnTemplate::from(&object);
+        let components = template.components(tables).unwrap();
+        assert_eq!(
+            components,
+            vec![
+                ComponentRef {
+                    component_type: 1,
+                    component_id: 0
+                },
+                ComponentRef {
+                    component_type: 2,
+                    component_id: 7
+                },
+            ]
+        );
+
+        let buf = SampleDatabase::new().build();
+        let tables = Database::new(&buf).tables().unwrap();
+        assert!(super::components(tables, 31).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,316 @@
+//! # Low level writing
+//!
+//! The [`LevelWriter`] is the counterpart of the [`super::reader::LevelReader`].
+//! It writes the file meta chunk (1000), the environment chunk (2000) and the
+//! objects chunk (2001) in the format of the requested version. The third
+//! section of the environment chunk is not parsed by this crate, so it is
+//! never written.
+use super::file::*;
+
+use assembly_core::writer::{
+    write_f32, write_object_id, write_object_template, write_quat, write_quat_wxyz, write_u32,
+    write_u32_string, write_u32_wstring, write_u8_bool, write_vec3f,
+};
+
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::io::{self, ErrorKind, Write};
+
+const ALIGN: usize = 16;
+
+/// A low level writer class
+pub struct LevelWriter<W> {
+    inner: W,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, msg)
+}
+
+fn missing(field: &str, version: u32) -> io::Error {
+    invalid(format!("{} is required for version {}", field, version))
+}
+
+fn to_u32(value: usize) -> io::Result<u32> {
+    u32::try_from(value).map_err(|e| invalid(e.to_string()))
+}
+
+fn pad(buf: &mut Vec<u8>) {
+    let len = buf.len().div_ceil(ALIGN) * ALIGN;
+    buf.resize(len, 0);
+}
+
+fn patch_u32(buf: &mut [u8], at: usize, value: u32) {
+    buf[at..at + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Start a chunk, returning its start and the start of its data
+fn begin_chunk(buf: &mut Vec<u8>, id: u32, version: ChunkVersion) -> io::Result<(usize, usize)> {
+    let start = buf.len();
+    buf.extend_from_slice(b"CHNK");
+    write_u32(buf, id)?;
+    buf.extend_from_slice(&version.header.to_le_bytes());
+    buf.extend_from_slice(&version.data.to_le_bytes());
+    write_u32(buf, 0)?; // size
+    write_u32(buf, 0)?; // offset
+    pad(buf);
+    let data = buf.len();
+    patch_u32(buf, start + 16, to_u32(data)?);
+    Ok((start, data))
+}
+
+fn end_chunk(buf: &mut Vec<u8>, start: usize) -> io::Result<()> {
+    pad(buf);
+    let size = to_u32(buf.len() - start)?;
+    patch_u32(buf, start + 12, size);
+    Ok(())
+}
+
+fn write_color<W: Write>(out: &mut W, color: &Color) -> io::Result<()> {
+    write_f32(out, color.red)?;
+    write_f32(out, color.green)?;
+    write_f32(out, color.blue)
+}
+
+fn write_section1<W: Write>(out: &mut W, version: u32, sec: &Section1) -> io::Result<()> {
+    if version >= 45 {
+        write_f32(out, sec.value1.ok_or_else(|| missing("value1", version))?)?;
+    }
+    write_color(out, &sec.value2)?;
+    write_color(out, &sec.value3)?;
+    write_color(out, &sec.value4)?;
+    write_vec3f(out, &sec.value5)?;
+    if version >= 31 {
+        let value6 = sec
+            .value6
+            .as_ref()
+            .ok_or_else(|| missing("value6", version))?;
+        match (&value6.value1, version >= 39) {
+            (Section1_39::Before { value1, value2 }, false) => {
+                write_f32(out, *value1)?;
+                write_f32(out, *value2)?;
+            }
+            (Section1_39::After { values, array }, true) => {
+                for value in values.iter() {
+                    write_f32(out, *value)?;
+                }
+                if version >= 40 {
+                    write_u32(out, to_u32(array.len())?)?;
+                    for entry in array {
+                        write_u32(out, entry.id)?;
+                        write_f32(out, entry.float1)?;
+                        write_f32(out, entry.float2)?;
+                    }
+                }
+            }
+            _ => return Err(invalid(format!("value6 doesn't match version {}", version))),
+        }
+        write_color(out, &value6.value2)?;
+    }
+    if version >= 36 {
+        let value7 = sec
+            .value7
+            .as_ref()
+            .ok_or_else(|| missing("value7", version))?;
+        write_color(out, value7)?;
+    }
+    if version < 42 {
+        let value8 = sec
+            .value8
+            .as_ref()
+            .ok_or_else(|| missing("value8", version))?;
+        write_vec3f(out, &value8.pos)?;
+        if version >= 33 {
+            write_quat(
+                out,
+                value8.rot.as_ref().ok_or_else(|| missing("rot", version))?,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn write_object_extra<W: Write>(out: &mut W, extra: &ObjectExtra) -> io::Result<()> {
+    out.write_all(&extra.field_1a)?;
+    out.write_all(&extra.field_1b)?;
+    write_u32(out, extra.field_2)?;
+    write_u8_bool(out, extra.field_3)?;
+    for value in &extra.field_4 {
+        write_u32(out, *value)?;
+    }
+    out.write_all(&extra.field_5)
+}
+
+/// Write a single object in the format of `version`
+///
+/// The settings are written in their text form.
+pub fn write_object<W: Write, S: Display>(
+    out: &mut W,
+    version: u32,
+    object: &Object<S>,
+) -> io::Result<()> {
+    write_object_id(out, &object.obj_id)?;
+    write_object_template(out, &object.lot)?;
+    if version >= 0x26 {
+        write_u32(out, object.asset_type.unwrap_or_default())?;
+    }
+    if version >= 0x20 {
+        write_u32(out, object.value_1.unwrap_or_default())?;
+    }
+    write_vec3f(out, &object.position)?;
+    write_quat_wxyz(out, &object.rotation)?;
+    write_f32(out, object.scale)?;
+    write_u32_wstring(out, &object.settings.to_string())?;
+    if version >= 0x07 {
+        write_u32(out, to_u32(object.extra.len())?)?;
+        for extra in &object.extra {
+            write_object_extra(out, extra)?;
+        }
+    }
+    Ok(())
+}
+
+impl<W> LevelWriter<W> {
+    /// Create a new writer
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    /// Get back the underlying writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> LevelWriter<W> {
+    /// Write a complete level file in the format of `version`
+    ///
+    /// Returns an error of kind [`ErrorKind::InvalidInput`] if the environment
+    /// doesn't have the fields that `version` requires.
+    pub fn write_level_file(
+        &mut self,
+        level: &Level,
+        version: u32,
+        revision: u32,
+    ) -> io::Result<()> {
+        let mut buf = Vec::new();
+        let version_1 = ChunkVersion { header: 1, data: 1 };
+
+        let (meta_start, meta_data) = begin_chunk(&mut buf, 1000, version_1)?;
+        write_u32(&mut buf, version)?;
+        write_u32(&mut buf, revision)?;
+        buf.extend_from_slice(&[0; 12]); // chunk offsets
+        end_chunk(&mut buf, meta_start)?;
+
+        if let Some(env) = &level.env {
+            let (start, data) = begin_chunk(&mut buf, 2000, version_1)?;
+            patch_u32(&mut buf, meta_data + 8, to_u32(start)?);
+            buf.extend_from_slice(&[0; 12]); // section addresses
+            let section1_address = to_u32(buf.len())?;
+            patch_u32(&mut buf, data, section1_address);
+            write_section1(&mut buf, version, &env.sec1)?;
+            let sky_address = to_u32(buf.len())?;
+            patch_u32(&mut buf, data + 4, sky_address);
+            for file in &env.sky.files {
+                write_u32_string(&mut buf, file)?;
+            }
+            end_chunk(&mut buf, start)?;
+        }
+
+        let (start, _) = begin_chunk(&mut buf, 2001, version_1)?;
+        patch_u32(&mut buf, meta_data + 12, to_u32(start)?);
+        write_u32(&mut buf, to_u32(level.objects.len())?)?;
+        for object in &level.objects {
+            write_object(&mut buf, version, object)?;
+        }
+        end_chunk(&mut buf, start)?;
+
+        self.inner.write_all(&buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lvl::reader::LevelReader;
+    use assembly_core::ldf::LDF;
+    use assembly_core::num_traits::FromPrimitive;
+    use assembly_core::types::{ObjectID, ObjectTemplate, Quaternion, Vector3f};
+    use std::io::Cursor;
+
+    fn color(v: f32) -> Color {
+        Color {
+            red: v,
+            green: v,
+            blue: v,
+        }
+    }
+
+    #[test]
+    fn test_write_level() {
+        let settings: LDF = "custom_script_server=0:scripts\\a.lua\nspawntemplate=1:-1"
+            .parse()
+            .unwrap();
+        let level = Level {
+            env: Some(Environment {
+                sec1: Section1 {
+                    value1: Some(1.0),
+                    value2: color(0.1),
+                    value3: color(0.2),
+                    value4: color(0.3),
+                    value5: Vector3f::new(0.0, -1.0, 0.0),
+                    value6: Some(Section1_31 {
+                        value1: Section1_39::After {
+                            values: Box::new([0.5; 12]),
+                            array: vec![Section1_40 {
+                                id: 1,
+                                float1: 2.0,
+                                float2: 3.0,
+                            }],
+                        },
+                        value2: color(0.4),
+                    }),
+                    value7: Some(color(0.5)),
+                    value8: None,
+                },
+                sky: SkySection {
+                    files: [
+                        "mesh\\env\\sky.nif".to_string(),
+                        String::new(),
+                        String::new(),
+                        String::new(),
+                        String::new(),
+                        String::new(),
+                    ],
+                },
+            }),
+            objects: vec![Object {
+                obj_id: ObjectID::new(0, 70000),
+                lot: ObjectTemplate::from_u32(6326).unwrap(),
+                asset_type: Some(0),
+                value_1: Some(0),
+                position: Vector3f::new(1.0, 2.0, 3.0),
+                rotation: Quaternion::new(0.0, 0.0, 0.0, 1.0),
+                scale: 1.0,
+                settings,
+                extra: Vec::new(),
+            }],
+        };
+
+        let mut writer = LevelWriter::new(Vec::new());
+        writer.write_level_file(&level, 45, 2).unwrap();
+        let bytes = writer.into_inner();
+
+        let read = LevelReader::new(Cursor::new(&bytes))
+            .read_level_file()
+            .unwrap();
+        assert_eq!(format!("{:?}", read), format!("{:?}", level));
+
+        let mut again = LevelWriter::new(Vec::new());
+        again.write_level_file(&read, 45, 2).unwrap();
+        assert_eq!(again.into_inner(), bytes);
+
+        let mut writer = LevelWriter::new(Vec::new());
+        assert!(writer.write_level_file(&level, 38, 2).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+//! # Overview images of zones
+//!
+//! This module rasterizes the color maps of a [`Terrain`] into an image and
+//! marks the positions of objects on top of it. The image can be saved as a
+//! PNG file.
+//!
+//! Each color map entry is interpreted as the little-endian bytes `R, G, B, A`.
+//! Chunks without a color map are shaded by their height instead.
+//!
+//! This module is only available with the `minimap` feature.
+
+use std::{convert::TryFrom, io::Write};
+
+use assembly_core::types::Vector3f;
+use displaydoc::Display;
+use thiserror::Error;
+
+use crate::terrain::{Chunk, Terrain};
+
+/// The color used for object markers
+pub const MARKER_COLOR: [u8; 4] = [255, 0, 0, 255];
+
+/// Errors from [`render`]
+#[derive(Debug, Display, Error, Copy, Clone, PartialEq)]
+pub enum RenderError {
+    /// The resolution {0} is not a positive number
+    InvalidResolution(f32),
+    /// An image of {0} by {1} pixels is too large
+    TooLarge(f64, f64),
+}
+
+/// An RGBA image
+#[derive(Debug, Clone)]
+pub struct Image {
+    /// The width in pixels
+    pub width: u32,
+    /// The height in pixels
+    pub height: u32,
+    /// The pixels, row by row, 4 bytes each
+    pub rgba: Vec<u8>,
+}
+
+impl Image {
+    /// Create a transparent image, if the number of bytes fits in a `usize`
+    fn new(width: u32, height: u32) -> Option<Self> {
+        let len = usize::try_from(width).ok()?;
+        let len = len.checked_mul(usize::try_from(height).ok()?)?;
+        Some(Image {
+            width,
+            height,
+            rgba: vec![0; len.checked_mul(4)?],
+        })
+    }
+
+    /// The offset of the pixel at `(x, y)` in `rgba`
+    fn offset(&self, x: u32, y: u32) -> usize {
+        (y as usize * self.width as usize + x as usize) * 4
+    }
+
+    /// Get the pixel at `(x, y)`
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x < self.width && y < self.height {
+            let i = self.offset(x, y);
+            let mut px = [0; 4];
+            px.copy_from_slice(&self.rgba[i..(i + 4)]);
+            Some(px)
+        } else {
+            None
+        }
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        if x < self.width && y < self.height {
+            let i = self.offset(x, y);
+            self.rgba[i..(i + 4)].copy_from_slice(&color);
+        }
+    }
+
+    /// Encode the image as a PNG file
+    pub fn write_png<W: Write>(&self, out: W) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(out, self.width, self.height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgba)
+    }
+}
+
+fn chunk_color(chunk: &Chunk, x: f32, z: f32, (min_h, max_h): (f32, f32)) -> [u8; 4] {
+    let map = &chunk.color_map;
+    if map.size > 0 && map.data.len() >= (map.size * map.size) as usize {
+        let extent_x = chunk.header.width.saturating_sub(1).max(1) as f32 * chunk.scale();
+        let extent_z = chunk.header.height.saturating_sub(1).max(1) as f32 * chunk.scale();
+        let u = ((x - chunk.header.pos_x) / extent_x).clamp(0.0, 1.0);
+        let v = ((z - chunk.header.pos_z) / extent_z).clamp(0.0, 1.0);
+        let last = (map.size - 1) as f32;
+        let (col, row) = ((u * last).round() as u32, (v * last).round() as u32);
+        map.data[(row * map.size + col) as usize].to_le_bytes()
+    } else {
+        let h = chunk.height_at(x, z).unwrap_or(min_h);
+        let range = (max_h - min_h).max(f32::EPSILON);
+        let shade = (((h - min_h) / range) * 255.0) as u8;
+        [shade, shade, shade, 255]
+    }
+}
+
+/// Render `terrain` with `pixels_per_unit` resolution and mark all `objects`
+///
+/// Fails if `pixels_per_unit` is not a positive finite number, or if the
+/// image would be wider or higher than `u32::MAX` pixels or not fit in memory.
+pub fn render<I>(terrain: &Terrain, objects: I, pixels_per_unit: f32) -> Result<Image, RenderError>
+where
+    I: IntoIterator<Item = Vector3f>,
+{
+    if !(pixels_per_unit.is_finite() && pixels_per_unit > 0.0) {
+        return Err(RenderError::InvalidResolution(pixels_per_unit));
+    }
+    let (min_x, min_z, max_x, max_z) = terrain.bounds();
+    let pixels_along = |min: f32, max: f32| {
+        let len = (f64::from(max) - f64::from(min)) * f64::from(pixels_per_unit);
+        len.ceil().max(1.0)
+    };
+    let (width, height) = (pixels_along(min_x, max_x), pixels_along(min_z, max_z));
+    let too_large = RenderError::TooLarge(width, height);
+    if width > f64::from(u32::MAX) || height > f64::from(u32::MAX) {
+        return Err(too_large);
+    }
+    let (width, height) = (width as u32, height as u32);
+    let heights = terrain
+        .chunks
+        .iter()
+        .flat_map(|c| c.heights.iter().copied())
+        .fold((f32::MAX, f32::MIN), |(lo, hi), h| (lo.min(h), hi.max(h)));
+
+    let mut image = Image::new(width, height).ok_or(too_large)?;
+    for y in 0..height {
+        for x in 0..width {
+            let wx = min_x + (x as f32 + 0.5) / pixels_per_unit;
+            let wz = min_z + (y as f32 + 0.5) / pixels_per_unit;
+            if let Some(chunk) = terrain.chunk_at(wx, wz) {
+                image.set_pixel(x, y, chunk_color(chunk, wx, wz, heights));
+            }
+        }
+    }
+
+    for pos in objects {
+        let px = ((pos.x - min_x) * pixels_per_unit).floor();
+        let py = ((pos.z - min_z) * pixels_per_unit).floor();
+        if px < 0.0 || py < 0.0 {
+            continue;
+        }
+        let (px, py) = (px as u32, py as u32);
+        for y in py.saturating_sub(1)..=(py + 1) {
+            for x in px.saturating_sub(1)..=(px + 1) {
+                image.set_pixel(x, y, MARKER_COLOR);
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::file::{HeightMapHeader, TerrainHeader};
+    use crate::terrain::ColorMap;
+
+    #[test]
+    fn test_render() {
+        let chunk = Chunk {
+            index: 0,
+            header: HeightMapHeader {
+                width: 3,
+                height: 3,
+                pos_x: 0.0,
+                pos_z: 0.0,
+                _1: 0,
+                _2: 0,
+                _3: 0,
+                _4: 0,
+                _5: 2.0,
+            },
+            heights: vec![0.0; 9],
+            color_map: ColorMap {
+                size: 1,
+                data: vec![u32::from_le_bytes([0, 128, 0, 255])],
+            },
+            light_map: Vec::new(),
+            blend_color_map: ColorMap::default(),
+            blend_map: Vec::new(),
+        };
+        let terrain = Terrain {
+            header: TerrainHeader {
+                version: 0x20,
+                value_1: 0,
+                value_2: 0,
+                chunk_count: 1,
+                width_in_chunks: 1,
+                height_in_chunks: 1,
+            },
+            chunks: vec![chunk],
+        };
+        let object = Vector3f {
+            x: 3.0,
+            y: 0.0,
+            z: 3.0,
+        };
+        let image = render(&terrain, vec![object], 2.0).unwrap();
+        assert_eq!((image.width, image.height), (8, 8));
+        assert_eq!(image.pixel(0, 0), Some([0, 128, 0, 255]));
+        assert_eq!(image.pixel(6, 6), Some(MARKER_COLOR));
+
+        let mut png = Vec::new();
+        image.write_png(&mut png).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+
+        for ppu in [0.0, -1.0, f32::NAN] {
+            let err = render(&terrain, None, ppu).unwrap_err();
+            assert!(matches!(err, RenderError::InvalidResolution(_)));
+        }
+        // 4 units at 2^30 pixels per unit don't fit in a `u32`
+        let err = render(&terrain, None, (1u32 << 30) as f32).unwrap_err();
+        assert_eq!(err, RenderError::TooLarge(4294967296.0, 4294967296.0));
+        assert!(Image::new(u32::MAX, u32::MAX).is_none());
+    }
+}
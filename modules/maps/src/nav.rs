@@ -0,0 +1,335 @@
+//! # Walkability grids from terrain and objects
+//!
+//! This module samples a [`Terrain`] on a regular grid and marks every cell as
+//! walkable or blocked. The decision is made by a [`WalkabilityRule`], which
+//! gets the height, slope and obstacles of the cell, so the algorithm can be
+//! replaced without touching the sampling.
+//!
+//! The resulting [`WalkGrid`] can be written as a PGM image or as a compact
+//! binary file for use in a pathfinding prototype.
+
+use std::io::{self, Write};
+
+use assembly_core::{displaydoc::Display, types::Vector3f};
+use thiserror::Error;
+
+use crate::lvl::spawn::SpawnTemplate;
+use crate::terrain::Terrain;
+
+/// A round obstacle on the XZ plane
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Obstacle {
+    /// The center of the obstacle
+    pub position: Vector3f,
+    /// The radius of the obstacle
+    pub radius: f32,
+}
+
+impl Obstacle {
+    /// Create one obstacle per object, with `radius` multiplied by the object's scale
+    pub fn from_objects<'a, I>(objects: I, radius: f32) -> Vec<Obstacle>
+    where
+        I: IntoIterator<Item = SpawnTemplate<'a>>,
+    {
+        objects
+            .into_iter()
+            .map(|o| Obstacle {
+                position: o.transform.pos,
+                radius: radius * o.scale,
+            })
+            .collect()
+    }
+
+    fn covers(&self, x: f32, z: f32) -> bool {
+        let dx = x - self.position.x;
+        let dz = z - self.position.z;
+        dx * dx + dz * dz <= self.radius * self.radius
+    }
+}
+
+/// The information about a single cell that a [`WalkabilityRule`] can use
+#[derive(Debug, Copy, Clone)]
+pub struct CellInfo {
+    /// The X coordinate of the cell center
+    pub x: f32,
+    /// The Z coordinate of the cell center
+    pub z: f32,
+    /// The terrain height, if there is terrain at that position
+    pub height: Option<f32>,
+    /// The largest height difference to a neighbor, divided by the cell size
+    pub slope: f32,
+    /// The number of obstacles that cover the cell center
+    pub obstacles: usize,
+}
+
+/// Decides whether a cell is walkable
+pub trait WalkabilityRule {
+    /// Check the cell
+    fn is_walkable(&self, cell: &CellInfo) -> bool;
+}
+
+impl<F> WalkabilityRule for F
+where
+    F: Fn(&CellInfo) -> bool,
+{
+    fn is_walkable(&self, cell: &CellInfo) -> bool {
+        self(cell)
+    }
+}
+
+/// The default rule: cells with terrain, a limited slope and no obstacles are walkable
+#[derive(Debug, Copy, Clone)]
+pub struct SlopeRule {
+    /// The maximum slope (rise over run)
+    pub max_slope: f32,
+}
+
+impl Default for SlopeRule {
+    fn default() -> Self {
+        // 45 degrees
+        SlopeRule { max_slope: 1.0 }
+    }
+}
+
+impl WalkabilityRule for SlopeRule {
+    fn is_walkable(&self, cell: &CellInfo) -> bool {
+        cell.height.is_some() && cell.slope <= self.max_slope && cell.obstacles == 0
+    }
+}
+
+/// A grid of walkable cells
+#[derive(Debug, Clone)]
+pub struct WalkGrid {
+    /// The X coordinate of the first cell center
+    pub origin_x: f32,
+    /// The Z coordinate of the first cell center
+    pub origin_z: f32,
+    /// The size of a cell
+    pub cell_size: f32,
+    /// The number of cells along the X axis
+    pub width: u32,
+    /// The number of cells along the Z axis
+    pub height: u32,
+    /// Whether a cell is walkable, row by row (`z` major)
+    pub cells: Vec<bool>,
+}
+
+/// Magic bytes of the binary grid format
+pub const GRID_MAGIC: &[u8; 4] = b"WALK";
+
+/// Errors from [`WalkGrid::build`]
+#[derive(Debug, Display, Error, Copy, Clone, PartialEq)]
+pub enum GridError {
+    /// The cell size {0} is not a positive number
+    InvalidCellSize(f32),
+    /// A grid of {0} by {1} cells is too large
+    TooLarge(f64, f64),
+}
+
+impl WalkGrid {
+    /// Sample `terrain` every `cell_size` units and apply `rule` to every cell
+    ///
+    /// Fails if `cell_size` is not a positive finite number, or if the grid
+    /// would have more than `u32::MAX` cells.
+    pub fn build<R, I>(
+        terrain: &Terrain,
+        obstacles: I,
+        cell_size: f32,
+        rule: &R,
+    ) -> Result<WalkGrid, GridError>
+    where
+        R: WalkabilityRule + ?Sized,
+        I: IntoIterator<Item = Obstacle>,
+    {
+        if !(cell_size.is_finite() && cell_size > 0.0) {
+            return Err(GridError::InvalidCellSize(cell_size));
+        }
+        let (min_x, min_z, max_x, max_z) = terrain.bounds();
+        let cells_along = |min: f32, max: f32| {
+            let len = (f64::from(max) - f64::from(min)) / f64::from(cell_size);
+            len.floor().max(0.0) + 1.0
+        };
+        let (width, height) = (cells_along(min_x, max_x), cells_along(min_z, max_z));
+        if width * height > f64::from(u32::MAX) {
+            return Err(GridError::TooLarge(width, height));
+        }
+        let (width, height) = (width as u32, height as u32);
+        let obstacles: Vec<Obstacle> = obstacles.into_iter().collect();
+
+        let mut cells = Vec::with_capacity((width * height) as usize);
+        for row in 0..height {
+            for col in 0..width {
+                let x = min_x + col as f32 * cell_size;
+                let z = min_z + row as f32 * cell_size;
+                let h = terrain.height_at(x, z);
+                let slope = h.map_or(0.0, |h| {
+                    [
+                        (cell_size, 0.0),
+                        (-cell_size, 0.0),
+                        (0.0, cell_size),
+                        (0.0, -cell_size),
+                    ]
+                    .iter()
+                    .filter_map(|(dx, dz)| terrain.height_at(x + dx, z + dz))
+                    .map(|n| (n - h).abs() / cell_size)
+                    .fold(0.0, f32::max)
+                });
+                let info = CellInfo {
+                    x,
+                    z,
+                    height: h,
+                    slope,
+                    obstacles: obstacles.iter().filter(|o| o.covers(x, z)).count(),
+                };
+                cells.push(rule.is_walkable(&info));
+            }
+        }
+
+        Ok(WalkGrid {
+            origin_x: min_x,
+            origin_z: min_z,
+            cell_size,
+            width,
+            height,
+            cells,
+        })
+    }
+
+    /// Check whether the cell at column `col` and row `row` is walkable
+    pub fn is_walkable(&self, col: u32, row: u32) -> bool {
+        col < self.width && row < self.height && self.cells[(row * self.width + col) as usize]
+    }
+
+    /// Write the grid as a binary PGM image (white is walkable)
+    pub fn write_pgm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "P5\n{} {}\n255\n", self.width, self.height)?;
+        let bytes: Vec<u8> = self
+            .cells
+            .iter()
+            .map(|&c| if c { 255 } else { 0 })
+            .collect();
+        out.write_all(&bytes)
+    }
+
+    /// Write the grid in a compact binary format
+    ///
+    /// The format is [`GRID_MAGIC`], followed by the origin (x, z) and the cell size
+    /// as little-endian `f32`, the width and height as little-endian `u32` and
+    /// one bit per cell, row by row, with the lowest bit first.
+    pub fn write_binary<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(GRID_MAGIC)?;
+        out.write_all(&self.origin_x.to_le_bytes())?;
+        out.write_all(&self.origin_z.to_le_bytes())?;
+        out.write_all(&self.cell_size.to_le_bytes())?;
+        out.write_all(&self.width.to_le_bytes())?;
+        out.write_all(&self.height.to_le_bytes())?;
+        for chunk in self.cells.chunks(8) {
+            let byte = chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |acc, (i, &c)| acc | (u8::from(c) << i));
+            out.write_all(&[byte])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::file::{HeightMapHeader, TerrainHeader};
+    use crate::terrain::{Chunk, ColorMap};
+
+    /// A terrain of `size` by `size` samples, 1 unit apart
+    fn terrain(size: u32, height: impl Fn(u32, u32) -> f32) -> Terrain {
+        let heights = (0..size)
+            .flat_map(|row| (0..size).map(move |col| (col, row)))
+            .map(|(col, row)| height(col, row))
+            .collect();
+        let header = HeightMapHeader {
+            width: size,
+            height: size,
+            pos_x: 0.0,
+            pos_z: 0.0,
+            _1: 0,
+            _2: 0,
+            _3: 0,
+            _4: 0,
+            _5: 1.0,
+        };
+        let chunk = Chunk {
+            index: 0,
+            header,
+            heights,
+            color_map: ColorMap::default(),
+            light_map: Vec::new(),
+            blend_color_map: ColorMap::default(),
+            blend_map: Vec::new(),
+        };
+        Terrain {
+            header: TerrainHeader {
+                version: 0x20,
+                value_1: 0,
+                value_2: 0,
+                chunk_count: 1,
+                width_in_chunks: 1,
+                height_in_chunks: 1,
+            },
+            chunks: vec![chunk],
+        }
+    }
+
+    #[test]
+    fn test_walk_grid() {
+        let flat = terrain(5, |_, _| 0.0);
+        let obstacle = Obstacle {
+            position: Vector3f::new(2.0, 0.0, 2.0),
+            radius: 0.5,
+        };
+        let grid = WalkGrid::build(&flat, vec![obstacle], 1.0, &SlopeRule::default()).unwrap();
+        assert_eq!((grid.width, grid.height), (5, 5));
+        assert!(grid.is_walkable(0, 0));
+        assert!(grid.is_walkable(4, 4));
+        assert!(!grid.is_walkable(2, 2));
+        assert!(!grid.is_walkable(5, 0));
+
+        // a ramp along the diagonal is walkable, a wall across it is not
+        let ramp = terrain(5, |col, row| (col + row) as f32 * 0.5);
+        let grid = WalkGrid::build(&ramp, None, 1.0, &SlopeRule::default()).unwrap();
+        assert!(grid.cells.iter().all(|&c| c));
+        let wall = terrain(5, |col, row| if col + row >= 4 { 10.0 } else { 0.0 });
+        let grid = WalkGrid::build(&wall, None, 1.0, &SlopeRule::default()).unwrap();
+        let blocked: Vec<_> = (0..5).filter(|&i| !grid.is_walkable(i, 4 - i)).collect();
+        assert_eq!(blocked, [0, 1, 2, 3, 4]);
+        assert!(grid.is_walkable(0, 0));
+        assert!(grid.is_walkable(4, 4));
+
+        let mut binary = Vec::new();
+        grid.write_binary(&mut binary).unwrap();
+        assert_eq!(binary.len(), 4 + 5 * 4 + 25_usize.div_ceil(8));
+    }
+
+    #[test]
+    fn test_walk_grid_degenerate() {
+        let flat = terrain(2, |_, _| 0.0);
+        let rule = SlopeRule::default();
+        for &size in &[0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let result = WalkGrid::build(&flat, None, size, &rule);
+            assert!(matches!(result, Err(GridError::InvalidCellSize(_))));
+        }
+        let result = WalkGrid::build(&flat, None, f32::MIN_POSITIVE, &rule);
+        assert!(matches!(result, Err(GridError::TooLarge(..))));
+
+        // a single sample, and no terrain at all
+        let point = terrain(1, |_, _| 3.0);
+        let grid = WalkGrid::build(&point, None, 1.0, &rule).unwrap();
+        assert_eq!(
+            (grid.width, grid.height, grid.cells.clone()),
+            (1, 1, vec![true])
+        );
+        let mut empty = terrain(1, |_, _| 0.0);
+        empty.chunks.clear();
+        let grid = WalkGrid::build(&empty, None, 1.0, &rule).unwrap();
+        assert_eq!((grid.width, grid.height, grid.cells), (1, 1, vec![false]));
+    }
+}
@@ -0,0 +1,273 @@
+//! # Inventory of referenced scripts
+//!
+//! Objects reference `*.lua` scripts in two places: the `ScriptComponent` table
+//! of the core database and the `custom_script_server` / `custom_script_client`
+//! config of objects placed in a level. This module collects these references
+//! so that tools can check which scripts are used and where.
+//!
+//! Reading from the database requires the `fdb` feature.
+
+use std::collections::BTreeMap;
+
+use assembly_core::ldf::{Value, LDF};
+
+use crate::lvl::file::Level;
+
+/// Where a script is executed
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ScriptSide {
+    /// A server script
+    Server,
+    /// A client script
+    Client,
+}
+
+/// Where a script is referenced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptSource {
+    /// A row of the `ScriptComponent` table
+    Component {
+        /// The ID of the row
+        id: i32,
+    },
+    /// The config of an object in a level
+    Object {
+        /// The ID of the object, i.e. `(scope << 32) | id`
+        object_id: u64,
+        /// The object template (LOT)
+        lot: u32,
+    },
+}
+
+/// A single reference to a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptRef {
+    /// The path of the script, as given in the source
+    pub path: String,
+    /// Whether this is a server or client script
+    pub side: ScriptSide,
+    /// Where the script is referenced
+    pub source: ScriptSource,
+}
+
+/// The LDF key for an object specific server script
+pub const LDF_SERVER_SCRIPT: &str = "custom_script_server";
+/// The LDF key for an object specific client script
+pub const LDF_CLIENT_SCRIPT: &str = "custom_script_client";
+
+/// A list of script references
+#[derive(Debug, Clone, Default)]
+pub struct ScriptInventory {
+    refs: Vec<ScriptRef>,
+}
+
+/// Normalize a script path to lowercase with `\` as a separator
+pub fn normalize_script_path(path: &str) -> String {
+    path.trim().replace('/', "\\").to_ascii_lowercase()
+}
+
+fn ldf_str<'a>(ldf: &'a LDF, key: &str) -> Option<&'a str> {
+    match ldf.map.get(key) {
+        Some(Value::String(s)) | Some(Value::Bytes(s)) if !s.trim().is_empty() => Some(s),
+        _ => None,
+    }
+}
+
+impl ScriptInventory {
+    /// Create a new, empty inventory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a single reference, ignoring empty paths
+    pub fn push(&mut self, path: &str, side: ScriptSide, source: ScriptSource) {
+        if !path.trim().is_empty() {
+            self.refs.push(ScriptRef {
+                path: path.to_owned(),
+                side,
+                source,
+            });
+        }
+    }
+
+    /// Add the scripts referenced by the objects in a level
+    pub fn add_level(&mut self, level: &Level) {
+        for object in level.objects() {
+            let object_id =
+                (u64::from(object.object_id.scope) << 32) | u64::from(object.object_id.id);
+            let source = ScriptSource::Object {
+                object_id,
+                lot: object.lot,
+            };
+            if let Some(path) = ldf_str(object.settings, LDF_SERVER_SCRIPT) {
+                self.push(path, ScriptSide::Server, source.clone());
+            }
+            if let Some(path) = ldf_str(object.settings, LDF_CLIENT_SCRIPT) {
+                self.push(path, ScriptSide::Client, source);
+            }
+        }
+    }
+
+    /// Get all references
+    pub fn refs(&self) -> &[ScriptRef] {
+        &self.refs
+    }
+
+    /// Get the normalized paths of all scripts, with the number of references
+    pub fn paths(&self) -> BTreeMap<(ScriptSide, String), usize> {
+        let mut paths = BTreeMap::new();
+        for r in &self.refs {
+            *paths
+                .entry((r.side, normalize_script_path(&r.path)))
+                .or_insert(0) += 1;
+        }
+        paths
+    }
+}
+
+#[cfg(feature = "fdb")]
+mod fdb {
+    use assembly_core::buffer::CastError;
+    use assembly_data::fdb::{common::Value, mem::Tables};
+
+    use super::{ScriptInventory, ScriptSide, ScriptSource};
+
+    impl ScriptInventory {
+        /// Add the scripts from the `ScriptComponent` table
+        ///
+        /// This is only available with the `fdb` feature.
+        pub fn add_script_components(&mut self, tables: Tables<'_>) -> Result<(), CastError> {
+            let table = match tables.by_name("ScriptComponent").transpose()? {
+                Some(table) => table,
+                None => return Ok(()),
+            };
+            let index_of = |name: &str| table.column_iter().position(|c| c.name() == name);
+            let server = index_of("script_name");
+            let client = index_of("client_script_name");
+            for row in table.row_iter() {
+                let id = match row.field_at(0) {
+                    Some(Value::Integer(id)) => id,
+                    _ => continue,
+                };
+                let columns = [(server, ScriptSide::Server), (client, ScriptSide::Client)];
+                for (index, side) in columns.iter() {
+                    if let Some(Value::Text(path)) = index.and_then(|i| row.field_at(i)) {
+                        self.push(&path.decode(), *side, ScriptSource::Component { id });
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assembly_core::{
+        num_traits::FromPrimitive,
+        types::{ObjectID, ObjectTemplate, Quaternion, Vector3f},
+    };
+
+    use crate::lvl::file::Object;
+
+    fn object(id: u32, settings: &str) -> Object<LDF> {
+        Object {
+            obj_id: ObjectID::new(2, id),
+            lot: ObjectTemplate::from_u32(6326).unwrap(),
+            asset_type: None,
+            value_1: None,
+            position: Vector3f::new(0.0, 0.0, 0.0),
+            rotation: Quaternion::new(0.0, 0.0, 0.0, 1.0),
+            scale: 1.0,
+            settings: settings.parse().unwrap(),
+            extra: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_add_level() {
+        let level = Level {
+            env: None,
+            objects: vec![
+                object(1, "custom_script_server=0:scripts/ai/A.lua\ncustom_script_client=0:scripts\\client\\b.lua"),
+                object(2, "custom_script_server=0:Scripts\\AI\\a.lua"),
+                object(3, "custom_script_server=0: \nspawntemplate=1:-1"),
+            ],
+        };
+        let mut inventory = ScriptInventory::new();
+        inventory.add_level(&level);
+        assert_eq!(inventory.refs().len(), 3);
+        assert_eq!(
+            inventory.refs()[0],
+            ScriptRef {
+                path: "scripts/ai/A.lua".to_owned(),
+                side: ScriptSide::Server,
+                source: ScriptSource::Object {
+                    object_id: (2 << 32) | 1,
+                    lot: 6326
+                },
+            }
+        );
+
+        let paths = inventory.paths();
+        assert_eq!(paths.len(), 2);
+        let server = (ScriptSide::Server, "scripts\\ai\\a.lua".to_owned());
+        assert_eq!(paths[&server], 2);
+        let client = (ScriptSide::Client, "scripts\\client\\b.lua".to_owned());
+        assert_eq!(paths[&client], 1);
+    }
+
+    #[cfg(feature = "fdb")]
+    #[test]
+    fn test_add_script_components() {
+        use assembly_data::fdb::{
+            common::ValueType, core::Field, mem::Database, testing::SampleDatabase,
+        };
+
+        let columns = [
+            ("id", ValueType::Integer),
+            ("script_name", ValueType::Text),
+            ("client_script_name", ValueType::Text),
+        ];
+        let text = |s: &str| Field::Text(s.to_owned());
+        let buf = SampleDatabase::new()
+            .table("ScriptComponent", &columns)
+            .row(vec![Field::Integer(1), text("scripts\\a.lua"), text("")])
+            .row(vec![
+                Field::Integer(2),
+                Field::Nothing,
+                text("scripts\\c.lua"),
+            ])
+            .build();
+        let tables = Database::new(&buf).tables().unwrap();
+        let mut inventory = ScriptInventory::new();
+        inventory.add_script_components(tables).unwrap();
+        let mut refs: Vec<_> = inventory
+            .refs()
+            .iter()
+            .map(|r| (r.path.as_str(), r.side, r.source.clone()))
+            .collect();
+        refs.sort_by_key(|r| r.0);
+        assert_eq!(
+            refs,
+            vec![
+                (
+                    "scripts\\a.lua",
+                    ScriptSide::Server,
+                    ScriptSource::Component { id: 1 }
+                ),
+                (
+                    "scripts\\c.lua",
+                    ScriptSide::Client,
+                    ScriptSource::Component { id: 2 }
+                ),
+            ]
+        );
+
+        let buf = SampleDatabase::new().build();
+        let tables = Database::new(&buf).tables().unwrap();
+        inventory.add_script_components(tables).unwrap();
+        assert_eq!(inventory.refs().len(), 2);
+    }
+}
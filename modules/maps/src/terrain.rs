@@ -0,0 +1,316 @@
+//! # Terrain data and height queries
+//!
+//! This module loads a complete terrain (`*.raw`) file using the low level
+//! functions in [`crate::raw`] and provides height queries on top of it.
+//!
+//! A terrain file is a grid of chunks. Each chunk has a height map with
+//! `width * height` samples, starting at `(pos_x, pos_z)` in world space and
+//! spaced `scale` units apart, as well as a color map, a light map, a second
+//! color map for the texture blending and a blend map. The light map and the
+//! blend map are embedded DDS files.
+//!
+//! The spacing of the samples is not named in the format. It is read from the
+//! last field of the [`HeightMapHeader`] (`_5`), see [`Chunk::scale`].
+//!
+//! Only files with a version of at least `0x20` are supported.
+
+use std::io::{self, Read};
+
+use assembly_core::reader::{FileError, FileResult};
+use byteorder::{ReadBytesExt, LE};
+
+use crate::raw::file::{HeightMapHeader, TerrainHeader};
+use crate::raw::reader::TerrainReader;
+
+/// A square map of RGBA colors
+#[derive(Debug, Clone, Default)]
+pub struct ColorMap {
+    /// The length of one side
+    pub size: u32,
+    /// The colors, row by row
+    pub data: Vec<u32>,
+}
+
+/// A single chunk of the terrain
+#[derive(Debug)]
+pub struct Chunk {
+    /// The index of this chunk
+    pub index: u32,
+    /// The dimensions and position of the height map
+    pub header: HeightMapHeader,
+    /// The height samples, row by row (`z` major)
+    pub heights: Vec<f32>,
+    /// The color map
+    pub color_map: ColorMap,
+    /// The light map (a DDS file)
+    pub light_map: Vec<u8>,
+    /// The texture blending color map
+    pub blend_color_map: ColorMap,
+    /// The blend map (a DDS file)
+    pub blend_map: Vec<u8>,
+}
+
+/// A complete terrain file
+#[derive(Debug)]
+pub struct Terrain {
+    /// The file header
+    pub header: TerrainHeader,
+    /// All chunks of the file
+    pub chunks: Vec<Chunk>,
+}
+
+fn skip<R: Read>(reader: &mut R, len: u64) -> io::Result<()> {
+    let copied = io::copy(&mut reader.take(len), &mut io::sink())?;
+    if copied < len {
+        Err(io::ErrorKind::UnexpectedEof.into())
+    } else {
+        Ok(())
+    }
+}
+
+fn read_color_map<R: Read>(reader: &mut R) -> FileResult<ColorMap> {
+    let data = reader.read_color_map_data()?;
+    let size = (data.len() as f64).sqrt() as u32;
+    Ok(ColorMap { size, data })
+}
+
+impl Chunk {
+    /// Read a chunk from the reader
+    ///
+    /// Fails if the scale of the height map (see [`Chunk::scale`]) is not a
+    /// positive finite number.
+    pub fn read<R: Read>(reader: &mut R) -> FileResult<Chunk> {
+        let index = reader.read_terrain_chunk()?.index;
+        let header = reader.read_height_map_header()?;
+        if !(header._5.is_finite() && header._5 > 0.0) {
+            return Err(FileError::Custom(
+                "Height map scale is not a positive number",
+            ));
+        }
+        let heights = reader.read_height_map_data(header.width, header.height)?;
+        let color_map = read_color_map(reader)?;
+        let light_map = reader.read_embedded_file()?;
+        let blend_color_map = read_color_map(reader)?;
+        let _flag = reader.read_u8()?;
+        let blend_map = reader.read_embedded_file()?;
+
+        // The remaining data is not exposed yet, but we need to skip it. It is:
+        // - a `u32` count of records with 9 `u32`/`f32` values each
+        // - one byte for every entry of the color map
+        // - a `u32` count of `u16` values
+        // - if that count is not zero, 32 bytes followed by 16 lists of `u16`
+        //   values, each prefixed with its `u16` length
+        let point_count = reader.read_u32::<LE>()?;
+        skip(reader, u64::from(point_count) * 9 * 4)?;
+        skip(
+            reader,
+            u64::from(color_map.size) * u64::from(color_map.size),
+        )?;
+        let end_count = reader.read_u32::<LE>()?;
+        skip(reader, u64::from(end_count) * 2)?;
+        if end_count != 0 {
+            skip(reader, 32)?;
+            for _ in 0..16 {
+                let count = reader.read_u16::<LE>()?;
+                skip(reader, u64::from(count) * 2)?;
+            }
+        }
+
+        Ok(Chunk {
+            index,
+            header,
+            heights,
+            color_map,
+            light_map,
+            blend_color_map,
+            blend_map,
+        })
+    }
+
+    /// The distance between two height samples
+    ///
+    /// This is the last field of the [`HeightMapHeader`], which is not named
+    /// in the format. Using it as the spacing of the samples is an assumption
+    /// of this module, [`Chunk::read`] only checks that it is positive.
+    pub fn scale(&self) -> f32 {
+        self.header._5
+    }
+
+    /// Get the height sample at column `col` and row `row`
+    pub fn sample(&self, col: u32, row: u32) -> Option<f32> {
+        if col < self.header.width && row < self.header.height {
+            let index = (row * self.header.width + col) as usize;
+            self.heights.get(index).copied()
+        } else {
+            None
+        }
+    }
+
+    /// Check whether the world position is covered by this chunk
+    pub fn contains(&self, x: f32, z: f32) -> bool {
+        let (fx, fz) = self.local(x, z);
+        let max_x = self.header.width.saturating_sub(1) as f32;
+        let max_z = self.header.height.saturating_sub(1) as f32;
+        (0.0..=max_x).contains(&fx) && (0.0..=max_z).contains(&fz)
+    }
+
+    fn local(&self, x: f32, z: f32) -> (f32, f32) {
+        let scale = self.scale();
+        (
+            (x - self.header.pos_x) / scale,
+            (z - self.header.pos_z) / scale,
+        )
+    }
+
+    /// Get the height at a world position, interpolated between the samples
+    ///
+    /// Returns `None` if the position is not within this chunk.
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        if !self.contains(x, z) {
+            return None;
+        }
+        let (fx, fz) = self.local(x, z);
+        let (col, row) = (fx.floor() as u32, fz.floor() as u32);
+        let (tx, tz) = (fx - col as f32, fz - row as f32);
+
+        let h00 = self.sample(col, row)?;
+        let h10 = self.sample(col + 1, row).unwrap_or(h00);
+        let h01 = self.sample(col, row + 1).unwrap_or(h00);
+        let h11 = self.sample(col + 1, row + 1).unwrap_or(h10);
+
+        let h0 = h00 + (h10 - h00) * tx;
+        let h1 = h01 + (h11 - h01) * tx;
+        Some(h0 + (h1 - h0) * tz)
+    }
+}
+
+impl Terrain {
+    /// Read a complete terrain file
+    pub fn read<R: Read>(reader: &mut R) -> FileResult<Terrain> {
+        let header = reader.read_terrain_header()?;
+        if header.version < 0x20 {
+            return Err(FileError::Custom("Unsupported terrain version"));
+        }
+        let chunks = (0..header.chunk_count)
+            .map(|_| Chunk::read(reader))
+            .collect::<FileResult<Vec<_>>>()?;
+        Ok(Terrain { header, chunks })
+    }
+
+    /// Get the area covered by the terrain as `(min_x, min_z, max_x, max_z)`
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        let mut iter = self.chunks.iter().map(|c| {
+            let scale = c.scale();
+            let x1 = c.header.pos_x + c.header.width.saturating_sub(1) as f32 * scale;
+            let z1 = c.header.pos_z + c.header.height.saturating_sub(1) as f32 * scale;
+            (c.header.pos_x, c.header.pos_z, x1, z1)
+        });
+        let first = iter.next().unwrap_or((0.0, 0.0, 0.0, 0.0));
+        iter.fold(first, |(a, b, c, d), (e, f, g, h)| {
+            (a.min(e), b.min(f), c.max(g), d.max(h))
+        })
+    }
+
+    /// Get the chunk that contains the world position
+    pub fn chunk_at(&self, x: f32, z: f32) -> Option<&Chunk> {
+        self.chunks.iter().find(|c| c.contains(x, z))
+    }
+
+    /// Get the height of the terrain at a world position
+    ///
+    /// Returns `None` if the position is outside of the terrain.
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        self.chunk_at(x, z).and_then(|c| c.height_at(x, z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk() -> Chunk {
+        Chunk {
+            index: 0,
+            header: HeightMapHeader {
+                width: 2,
+                height: 2,
+                pos_x: 10.0,
+                pos_z: 20.0,
+                _1: 0,
+                _2: 0,
+                _3: 0,
+                _4: 0,
+                _5: 4.0,
+            },
+            heights: vec![0.0, 4.0, 8.0, 12.0],
+            color_map: ColorMap::default(),
+            light_map: Vec::new(),
+            blend_color_map: ColorMap::default(),
+            blend_map: Vec::new(),
+        }
+    }
+
+    /// Write a chunk with a 2x2 height map and some data in every section
+    fn chunk_bytes(scale: f32) -> Vec<u8> {
+        let mut out = Vec::new();
+        let u32s = |out: &mut Vec<u8>, values: &[u32]| {
+            values.iter().for_each(|v| out.extend(&v.to_le_bytes()));
+        };
+        u32s(&mut out, &[7, 2, 2]); // index, width, height
+        out.extend(&10.0f32.to_le_bytes());
+        out.extend(&20.0f32.to_le_bytes());
+        u32s(&mut out, &[0; 4]);
+        out.extend(&scale.to_le_bytes());
+        for h in &[0.0f32, 4.0, 8.0, 12.0] {
+            out.extend(&h.to_le_bytes());
+        }
+        u32s(&mut out, &[1, 0xff00_ff00]); // color map
+        u32s(&mut out, &[3]); // light map
+        out.extend(b"DDS");
+        u32s(&mut out, &[0]); // blend color map
+        out.push(1); // flag
+        u32s(&mut out, &[0]); // blend map
+        u32s(&mut out, &[1]); // points
+        u32s(&mut out, &[0; 9]);
+        out.push(0); // one byte per color
+        u32s(&mut out, &[1]); // end data
+        out.extend(&[0; 2 + 32]);
+        for _ in 0..16 {
+            out.extend(&[1, 0, 0, 0]);
+        }
+        out
+    }
+
+    #[test]
+    fn test_read_chunk() {
+        let mut bytes = chunk_bytes(4.0);
+        bytes.extend(b"next");
+        let mut reader = &bytes[..];
+        let chunk = Chunk::read(&mut reader).unwrap();
+        assert_eq!(reader, b"next");
+        assert_eq!(chunk.index, 7);
+        assert_eq!(chunk.scale(), 4.0);
+        assert_eq!(chunk.height_at(12.0, 22.0), Some(6.0));
+        assert_eq!(chunk.color_map.data, vec![0xff00_ff00]);
+        assert_eq!(chunk.light_map, b"DDS");
+
+        for scale in &[0.0, -1.0, f32::NAN] {
+            let bytes = chunk_bytes(*scale);
+            assert!(Chunk::read(&mut &bytes[..]).is_err());
+        }
+        let bytes = chunk_bytes(4.0);
+        assert!(Chunk::read(&mut &bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_height_at() {
+        let chunk = chunk();
+        assert_eq!(chunk.height_at(10.0, 20.0), Some(0.0));
+        assert_eq!(chunk.height_at(14.0, 20.0), Some(4.0));
+        assert_eq!(chunk.height_at(10.0, 24.0), Some(8.0));
+        assert_eq!(chunk.height_at(12.0, 22.0), Some(6.0));
+        assert_eq!(chunk.height_at(14.0, 24.0), Some(12.0));
+        assert_eq!(chunk.height_at(9.0, 20.0), None);
+        assert_eq!(chunk.height_at(10.0, 24.5), None);
+    }
+}
@@ -0,0 +1,38 @@
+use assembly_pack::pki::core::PackIndexFile;
+use assembly_pack::verify::verify;
+use std::convert::TryFrom;
+use std::env;
+
+fn print_usage(program: &str) {
+    println!("Usage: {} PKI PK...", program);
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    if args.len() <= 2 {
+        print_usage(&program);
+        return Ok(());
+    }
+
+    let pki = PackIndexFile::try_from(args[1].as_str())
+        .map_err(|e| anyhow::anyhow!("Failed to load index: {:?}", e))?;
+    for pk_path in &args[2..] {
+        let report = verify(pk_path, &pki)?;
+        println!(
+            "{}: {} entries, {} failed, {} missing",
+            pk_path,
+            report.checked,
+            report.failures.len(),
+            report.missing.len()
+        );
+        for failure in &report.failures {
+            println!("{:10} {:?}", failure.crc, failure.issues);
+        }
+        for crc in &report.missing {
+            println!("{:10} missing", crc);
+        }
+    }
+    Ok(())
+}
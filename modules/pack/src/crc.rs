@@ -0,0 +1,30 @@
+//! # The CRC used for paths in pack files
+//!
+//! Files in pack index (`*.pki`) and pack (`*.pk`) files are identified
+//! by a CRC-32 of their path relative to the installation folder. The path is
+//! normalized to lowercase with `\` as a separator before hashing.
+//!
+//! [`calculate_crc`] hashes the stored name as-is, while
+//! [`calculate_crc_normalized`] first brings the path into the canonical form
+//! of [`crate::path::normalize`], so `res/a.txt` and `client\res\A.txt` have
+//! the same CRC. Use the latter to look up a path from user input.
+//!
+//! The implementation lives in [`assembly_core::hash`].
+
+use crate::path::normalize_bytes;
+
+pub use assembly_core::hash::{CRC_FXOR, CRC_INIT, CRC_POLY};
+
+/// Calculate the CRC for a path
+///
+/// This is the same as [`assembly_core::hash::pk_crc`].
+pub fn calculate_crc(path: &[u8]) -> u32 {
+    assembly_core::hash::pk_crc(path)
+}
+
+/// Calculate the CRC for the canonical form of a path
+///
+/// See [`crate::path::normalize`].
+pub fn calculate_crc_normalized(path: &[u8]) -> u32 {
+    calculate_crc(&normalize_bytes(path))
+}
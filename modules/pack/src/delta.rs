@@ -0,0 +1,206 @@
+//! # Differential pack updates
+//!
+//! To patch a client, only the files that changed between two versions need
+//! to be distributed. A [`PackDelta`] compares the entries of the old and new
+//! pack files by the CRC of their path and the MD5 hash of their content.
+//! The new or changed entries can then be copied into a single delta pack
+//! with a [`PackWriter`], and the pack index can be updated to point to it.
+//!
+//! ```
+//! use assembly_pack::{delta::PackDelta, pk::writer::PackWriter, pki::core::PackIndexFile};
+//!
+//! let mut old = PackWriter::new(Vec::new()).unwrap();
+//! old.append_entry("a.txt", &b"a"[..]).unwrap();
+//! old.append_entry("b.txt", &b"b"[..]).unwrap();
+//! let mut new = PackWriter::new(Vec::new()).unwrap();
+//! new.append_entry("a.txt", &b"a"[..]).unwrap();
+//! new.append_entry("b.txt", &b"B"[..]).unwrap();
+//!
+//! let delta = PackDelta::compute(old.entries(), new.entries());
+//! assert_eq!(delta.changed.len(), 1);
+//! assert!(delta.added.is_empty() && delta.removed.is_empty());
+//!
+//! let mut pki = PackIndexFile::default();
+//! delta.update_index(&mut pki, "client\\res\\pack\\delta.pk", new.entries());
+//! assert_eq!(pki.files.len(), 1);
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, Seek, Write};
+
+use assembly_core::reader::FileResult;
+
+use crate::pk::{file::PKEntry, reader::PackFile, writer::PackWriter};
+use crate::pki::core::PackIndexFile;
+
+/// The difference between two sets of pack entries
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackDelta {
+    /// The CRCs of entries that only exist in the new set
+    pub added: BTreeSet<u32>,
+    /// The CRCs of entries whose content is different in the new set
+    pub changed: BTreeSet<u32>,
+    /// The CRCs of entries that only exist in the old set
+    pub removed: BTreeSet<u32>,
+}
+
+fn content_key(entry: &PKEntry) -> (u32, &str) {
+    (
+        entry.orig_file_size,
+        entry.orig_file_hash.trim_end_matches('\0'),
+    )
+}
+
+impl PackDelta {
+    /// Compare the entries of the old and new pack files
+    ///
+    /// Entries are compared by the size and hash of their uncompressed data,
+    /// so an entry that is only compressed differently is not a change.
+    pub fn compute<'a, O, N>(old: O, new: N) -> Self
+    where
+        O: IntoIterator<Item = &'a PKEntry>,
+        N: IntoIterator<Item = &'a PKEntry>,
+    {
+        let old: BTreeMap<u32, &PKEntry> = old.into_iter().map(|e| (e.crc, e)).collect();
+        let mut delta = PackDelta::default();
+        let mut seen = BTreeSet::new();
+        for entry in new {
+            seen.insert(entry.crc);
+            match old.get(&entry.crc) {
+                None => {
+                    delta.added.insert(entry.crc);
+                }
+                Some(prev) if content_key(prev) != content_key(entry) => {
+                    delta.changed.insert(entry.crc);
+                }
+                Some(_) => {}
+            }
+        }
+        delta.removed = old
+            .keys()
+            .filter(|crc| !seen.contains(crc))
+            .copied()
+            .collect();
+        delta
+    }
+
+    /// Check whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    /// Check whether the entry needs to be part of the delta pack
+    pub fn needs(&self, crc: u32) -> bool {
+        self.added.contains(&crc) || self.changed.contains(&crc)
+    }
+
+    /// Copy the new and changed entries of one of the new pack files to `writer`
+    ///
+    /// `entries` is the entry list of `pack`. Call this once for each of the
+    /// new pack files. Returns the number of entries that were copied.
+    pub fn copy_entries<T, W>(
+        &self,
+        pack: &mut PackFile<'_, T>,
+        entries: &[PKEntry],
+        writer: &mut PackWriter<W>,
+    ) -> FileResult<usize>
+    where
+        T: Seek + BufRead,
+        W: Write,
+    {
+        let mut count = 0;
+        for entry in entries.iter().filter(|e| self.needs(e.crc)) {
+            let stream = pack.get_file_stream(entry.clone());
+            writer.append_raw(entry, stream)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Update the pack index for the delta pack
+    ///
+    /// New and changed files are moved to `delta_pack`, which is added to the
+    /// list of archives if needed, and removed files are removed from the index.
+    /// `new` must contain the entries of the new pack files, to look up
+    /// whether each file is compressed.
+    pub fn update_index<'a, N>(&self, pki: &mut PackIndexFile, delta_pack: &str, new: N)
+    where
+        N: IntoIterator<Item = &'a PKEntry>,
+    {
+        for entry in new.into_iter().filter(|e| self.needs(e.crc)) {
+            pki.insert_crc(entry.crc, delta_pack, entry.is_compressed[0] > 0);
+        }
+        for crc in &self.removed {
+            pki.files.remove(crc);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crc::calculate_crc;
+    use std::io::{Cursor, Read};
+
+    fn pack(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = PackWriter::new(Vec::new()).unwrap();
+        for (path, data) in files {
+            writer.append_entry(path, data.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn entries(bytes: &mut Cursor<Vec<u8>>) -> Vec<PKEntry> {
+        let mut pack = PackFile::open(bytes);
+        let header = pack.get_header().unwrap();
+        pack.get_entry_list(header.file_list_base_addr).unwrap()
+    }
+
+    #[test]
+    fn test_delta_pack() {
+        let mut old = Cursor::new(pack(&[("a", "1"), ("b", "2"), ("c", "3")]));
+        let mut new = Cursor::new(pack(&[("a", "1"), ("b", "two"), ("d", "4")]));
+        let (old_entries, new_entries) = (entries(&mut old), entries(&mut new));
+        let delta = PackDelta::compute(&old_entries, &new_entries);
+        assert_eq!(delta.added, [calculate_crc(b"d")].iter().copied().collect());
+        assert_eq!(
+            delta.changed,
+            [calculate_crc(b"b")].iter().copied().collect()
+        );
+        assert_eq!(
+            delta.removed,
+            [calculate_crc(b"c")].iter().copied().collect()
+        );
+
+        let mut writer = PackWriter::new(Vec::new()).unwrap();
+        let mut pack_file = PackFile::open(&mut new);
+        let count = delta
+            .copy_entries(&mut pack_file, &new_entries, &mut writer)
+            .unwrap();
+        assert_eq!(count, 2);
+        let mut delta_pack = Cursor::new(writer.finish().unwrap());
+        let delta_entries = entries(&mut delta_pack);
+        let mut pack_file = PackFile::open(&mut delta_pack);
+        let b = delta_entries
+            .iter()
+            .find(|e| e.crc == calculate_crc(b"b"))
+            .unwrap();
+        let mut data = Vec::new();
+        let mut stream = pack_file.get_file_data(b.clone()).unwrap();
+        stream.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"two");
+
+        let mut pki = PackIndexFile::default();
+        pki.insert("a", "old.pk", true);
+        pki.insert("b", "old.pk", true);
+        pki.insert("c", "old.pk", true);
+        delta.update_index(&mut pki, "delta.pk", &new_entries);
+        assert_eq!(pki.files.len(), 3);
+        assert_eq!(pki.get("b").unwrap().pack_file, 1);
+        assert!(pki.get("c").is_none());
+
+        let mut bytes = Vec::new();
+        pki.write(&mut bytes).unwrap();
+        assert_eq!(PackIndexFile::from_source(Cursor::new(bytes)).unwrap(), pki);
+    }
+}
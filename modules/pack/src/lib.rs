@@ -1,3 +1,8 @@
+pub mod crc;
+pub mod delta;
+pub mod path;
 pub mod pk;
 pub mod pki;
 pub mod sd0;
+pub mod verify;
+pub mod vfs;
@@ -0,0 +1,97 @@
+//! # Paths of resources
+//!
+//! The game refers to the same file in different ways: with `/` or `\` as a
+//! separator, in any case and either relative to the installation folder
+//! (`client\res\textures\a.dds`) or relative to the client folder
+//! (`res\textures\a.dds`). Before such a path can be hashed or compared, it
+//! needs to be brought into a canonical form with [`normalize`].
+//!
+//! ```
+//! use assembly_pack::path::normalize;
+//!
+//! assert_eq!(normalize("res/Textures//A.dds"), "client\\res\\textures\\a.dds");
+//! assert_eq!(normalize("./CLIENT/res/textures/a.dds"), "client\\res\\textures\\a.dds");
+//! ```
+
+/// The separator of the canonical form
+pub const SEPARATOR: u8 = b'\\';
+
+/// The prefix of all resources in the canonical form
+pub const CLIENT_RES_PREFIX: &str = "client\\res\\";
+
+const RES_PREFIX: &[u8] = b"res\\";
+
+/// Bring a path into the canonical form, see [`normalize`]
+///
+/// Only ASCII bytes are changed or removed, so valid UTF-8 stays valid.
+pub fn normalize_bytes(path: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(path.len() + 7);
+    for &b in path {
+        let b = match b {
+            b'/' => SEPARATOR,
+            b => b.to_ascii_lowercase(),
+        };
+        if b == SEPARATOR {
+            // drop leading, repeated and `.\` segments
+            if out.is_empty() || out.last() == Some(&SEPARATOR) {
+                continue;
+            }
+            if out == b"." || out.ends_with(b"\\.") {
+                out.pop();
+                continue;
+            }
+        }
+        out.push(b);
+    }
+    if out.starts_with(RES_PREFIX) {
+        out.splice(0..0, b"client\\".iter().copied());
+    }
+    out
+}
+
+/// Bring a path into the canonical form
+///
+/// - `/` is replaced with `\`
+/// - ASCII letters are converted to lowercase
+/// - leading, repeated and `.` segments are removed
+/// - a path starting with `res\` gets the `client\` prefix
+///
+/// Two paths refer to the same resource if and only if their canonical forms
+/// are equal. This is the form that is hashed by
+/// [`crate::crc::calculate_crc_normalized`].
+pub fn normalize(path: &str) -> String {
+    String::from_utf8(normalize_bytes(path.as_bytes())).expect("only ASCII bytes are changed")
+}
+
+/// Get the part of a canonical path after `client\res\`, if it has that prefix
+pub fn strip_client_res(path: &str) -> Option<&str> {
+    path.strip_prefix(CLIENT_RES_PREFIX)
+}
+
+/// Get the lowercase file name, i.e. the last segment, of a path
+pub fn file_name(path: &str) -> String {
+    let name = path.rsplit(&['/', '\\'][..]).next().unwrap_or(path);
+    name.to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let canonical = "client\\res\\macros\\aaa.txt";
+        assert_eq!(normalize(canonical), canonical);
+        assert_eq!(normalize("client/res/macros/AAA.txt"), canonical);
+        assert_eq!(normalize("\\res\\\\macros\\.\\aaa.txt"), canonical);
+        assert_eq!(normalize("versions/index.txt"), "versions\\index.txt");
+        assert_eq!(normalize("résumé/A"), "résumé\\a");
+        assert_eq!(strip_client_res(canonical), Some("macros\\aaa.txt"));
+        assert_eq!(file_name("client/res/pack/A.pk"), "a.pk");
+        let crc = crate::crc::calculate_crc_normalized(b"res/macros/aaa.txt");
+        assert_eq!(crc, 0x5ef5bc71);
+        let raw = crate::crc::calculate_crc(b"res/a.txt");
+        assert_eq!(raw, assembly_core::hash::pk_crc(b"res/a.txt"));
+        assert_eq!(raw, 0x9f6efb92);
+    }
+}
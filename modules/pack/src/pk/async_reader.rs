@@ -0,0 +1,154 @@
+//! # Async reader for PK files
+//!
+//! This is the counterpart of [`super::reader::PackFile`] for sources that
+//! implement `AsyncRead + AsyncSeek`. File data is read into memory, because
+//! the `sd0` decoder works on blocking streams.
+//!
+//! This module is only available with the `async-tokio` feature.
+
+use std::convert::TryFrom;
+use std::io::{Cursor, Read, SeekFrom};
+
+use assembly_core::{
+    nom::Finish,
+    reader::{FileError, FileResult, ParseAt},
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+
+use super::file::{PKEntry, PKHeader};
+use super::parser;
+use crate::sd0::stream::SegmentedStream;
+
+/// A low level async pack file reader
+pub struct AsyncPackFile<'a, T> {
+    inner: &'a mut T,
+}
+
+impl<'a, T> AsyncPackFile<'a, T>
+where
+    T: AsyncRead + AsyncSeek + Unpin,
+{
+    /// Open a file from a stream
+    pub fn open(inner: &'a mut T) -> Self {
+        AsyncPackFile { inner }
+    }
+
+    /// Check for the magic bytes at the beginning of the file
+    pub async fn check_magic(&mut self) -> FileResult<()> {
+        let mut magic_bytes: [u8; 4] = [0; 4];
+        self.inner.seek(SeekFrom::Start(0)).await?;
+        self.inner.read_exact(&mut magic_bytes).await?;
+        let (_rest, _magic) = parser::parse_pk_magic(&magic_bytes)
+            .finish()
+            .at(0, &magic_bytes)?;
+        Ok(())
+    }
+
+    /// Load the header from the end of the file
+    pub async fn get_header(&mut self) -> FileResult<PKHeader> {
+        let mut header_bytes: [u8; 8] = [0; 8];
+        let addr = self.inner.seek(SeekFrom::End(-8)).await?;
+        self.inner.read_exact(&mut header_bytes).await?;
+        let (_rest, header) = parser::parse_pk_header(&header_bytes)
+            .finish()
+            .at(addr, &header_bytes)?;
+        Ok(header)
+    }
+
+    /// Get a list of all entries
+    pub async fn get_entry_list(&mut self, addr: u32) -> FileResult<Vec<PKEntry>> {
+        let mut bytes: Vec<u8> = Vec::new();
+        let addr = self.inner.seek(SeekFrom::Start(u64::from(addr))).await?;
+        self.inner.read_to_end(&mut bytes).await?;
+        let (_rest, entry_list) = parser::parse_pk_entry_list(&bytes)
+            .finish()
+            .at(addr, &bytes)?;
+        Ok(entry_list)
+    }
+
+    /// Get the raw (possibly compressed) data of an entry
+    pub async fn get_file_raw(&mut self, entry: &PKEntry) -> FileResult<Vec<u8>> {
+        let size = if entry.is_compressed[0] == 0 {
+            entry.orig_file_size
+        } else {
+            entry.compr_file_size
+        };
+        let size = usize::try_from(size).map_err(FileError::Count)?;
+        let mut bytes = vec![0; size];
+        let addr = u64::from(entry.file_data_addr);
+        self.inner.seek(SeekFrom::Start(addr)).await?;
+        self.inner.read_exact(&mut bytes).await?;
+        Ok(bytes)
+    }
+
+    /// Get the (decompressed) data of an entry
+    pub async fn get_file_data(&mut self, entry: &PKEntry) -> FileResult<Vec<u8>> {
+        let raw = self.get_file_raw(entry).await?;
+        if entry.is_compressed[0] == 0 {
+            Ok(raw)
+        } else {
+            let mut stream = SegmentedStream::try_from(Cursor::new(raw))
+                .map_err(|_| FileError::Custom("Invalid sd0 stream"))?;
+            let mut data = Vec::with_capacity(entry.orig_file_size as usize);
+            stream.read_to_end(&mut data)?;
+            Ok(data)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pk::writer::PackWriter;
+    use crate::pki::{core::PackIndexFile, io::load_async};
+    use std::future::Future;
+    use std::task::{Context, Poll, Waker};
+
+    /// Run a future on sources that never block, like a `Cursor`
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn test_async_pack_file() {
+        let mut writer = PackWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.append_entry("a.txt", &b"Hello World"[..]).unwrap();
+        writer
+            .append_entry_with("b.txt", &b"Uncompressed"[..], false)
+            .unwrap();
+        let mut cursor = writer.finish().unwrap();
+
+        let entries = block_on(async {
+            let mut pack = AsyncPackFile::open(&mut cursor);
+            pack.check_magic().await?;
+            let header = pack.get_header().await?;
+            let entries = pack.get_entry_list(header.file_list_base_addr).await?;
+            let mut data = Vec::new();
+            for entry in &entries {
+                data.push((entry.crc, pack.get_file_data(entry).await?));
+            }
+            FileResult::Ok(data)
+        })
+        .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().any(|(_, d)| d == b"Hello World"));
+        assert!(entries.iter().any(|(_, d)| d == b"Uncompressed"));
+
+        let mut invalid = Cursor::new(b"not a pack file".to_vec());
+        let result = block_on(AsyncPackFile::open(&mut invalid).check_magic());
+        assert!(result.is_err());
+
+        let mut pki = PackIndexFile::default();
+        pki.insert("a.txt", "client\\res\\pack\\a.pk", true);
+        let mut bytes = Vec::new();
+        pki.write(&mut bytes).unwrap();
+        let loaded = block_on(load_async(&mut Cursor::new(bytes))).unwrap();
+        assert_eq!(loaded, pki);
+    }
+}
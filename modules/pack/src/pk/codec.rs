@@ -0,0 +1,128 @@
+//! # Transformations of the stored data
+//!
+//! The original pack files store the data of each entry either as-is or as an
+//! `sd0` stream. Some community forks additionally obfuscate that data, e.g.
+//! with a XOR key. An [`EntryCodec`] describes such a transformation, so that
+//! it can be plugged into [`super::reader::PackFile::get_file_data_with`] and
+//! [`super::writer::PackWriter::with_codec`].
+//!
+//! The codec is applied to the bytes as they are stored in the file, i.e. it
+//! is undone before `sd0` decompression when reading, and applied after `sd0`
+//! compression when writing. The compressed size and hash of an entry refer
+//! to the encoded bytes.
+
+use std::io::{Read, Result as IoResult, Write};
+
+/// A reversible transformation of the stored bytes of an entry
+pub trait EntryCodec {
+    /// Decode `buf` in place
+    ///
+    /// `offset` is the position of `buf[0]` in the stored data of the entry
+    /// with the given `crc`.
+    fn decode(&self, crc: u32, offset: u64, buf: &mut [u8]);
+
+    /// Encode `buf` in place, the inverse of [`EntryCodec::decode`]
+    fn encode(&self, crc: u32, offset: u64, buf: &mut [u8]);
+}
+
+/// A codec that XORs the data with a repeating key
+///
+/// The key starts over at the beginning of every entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XorCodec {
+    key: Vec<u8>,
+}
+
+impl XorCodec {
+    /// Create a new codec, or `None` if the key is empty
+    pub fn new(key: Vec<u8>) -> Option<Self> {
+        if key.is_empty() {
+            None
+        } else {
+            Some(Self { key })
+        }
+    }
+
+    fn apply(&self, offset: u64, buf: &mut [u8]) {
+        let len = self.key.len() as u64;
+        for (i, b) in buf.iter_mut().enumerate() {
+            *b ^= self.key[((offset + i as u64) % len) as usize];
+        }
+    }
+}
+
+impl EntryCodec for XorCodec {
+    fn decode(&self, _crc: u32, offset: u64, buf: &mut [u8]) {
+        self.apply(offset, buf)
+    }
+
+    fn encode(&self, _crc: u32, offset: u64, buf: &mut [u8]) {
+        self.apply(offset, buf)
+    }
+}
+
+/// A reader that decodes the data of an entry
+pub struct DecodeReader<'c, R> {
+    inner: R,
+    codec: &'c dyn EntryCodec,
+    crc: u32,
+    offset: u64,
+}
+
+impl<'c, R> DecodeReader<'c, R> {
+    /// Wrap the reader for the stored data of the entry with `crc`
+    pub fn new(inner: R, codec: &'c dyn EntryCodec, crc: u32) -> Self {
+        Self {
+            inner,
+            codec,
+            crc,
+            offset: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for DecodeReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let n = self.inner.read(buf)?;
+        self.codec.decode(self.crc, self.offset, &mut buf[..n]);
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+/// A writer that encodes the data of an entry
+pub struct EncodeWriter<'c, W> {
+    inner: W,
+    codec: &'c dyn EntryCodec,
+    crc: u32,
+    offset: u64,
+    buf: Vec<u8>,
+}
+
+impl<'c, W> EncodeWriter<'c, W> {
+    /// Wrap the writer for the stored data of the entry with `crc`
+    pub fn new(inner: W, codec: &'c dyn EntryCodec, crc: u32) -> Self {
+        Self {
+            inner,
+            codec,
+            crc,
+            offset: 0,
+            buf: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for EncodeWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.buf.clear();
+        self.buf.extend_from_slice(buf);
+        self.codec.encode(self.crc, self.offset, &mut self.buf);
+        self.inner.write_all(&self.buf)?;
+        self.offset += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
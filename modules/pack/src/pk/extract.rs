@@ -0,0 +1,195 @@
+//! # Parallel extraction of pack file entries
+//!
+//! The entries in a pack file are independent of each other and decompressing
+//! them is CPU bound, so this module spreads the work over a thread pool.
+//!
+//! Rayon splits the list of entries into parts that are processed
+//! independently, and each part opens its own handle to the pack file. There
+//! are usually a few parts per thread, so the number of open handles is not
+//! bounded by the number of threads, but the file is never shared between two
+//! threads.
+//!
+//! This module is only available with the `parallel` feature.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use assembly_core::progress::ProgressSink;
+use rayon::prelude::*;
+use rayon::{ThreadPoolBuildError, ThreadPoolBuilder};
+
+use super::file::PKEntry;
+use super::reader::{PackFile, StreamError};
+
+/// Errors when extracting a single entry
+#[derive(Debug)]
+pub enum ExtractError {
+    /// The pack file could not be opened
+    Open(io::Error),
+    /// The data stream could not be created
+    Stream(StreamError),
+    /// The sink failed to consume the data
+    Sink(io::Error),
+    /// The extraction was cancelled before this entry
+    Cancelled,
+}
+
+impl Display for ExtractError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractError::Open(e) => write!(f, "Failed to open pack file: {}", e),
+            ExtractError::Stream(e) => write!(f, "Failed to read entry: {:?}", e),
+            ExtractError::Sink(e) => write!(f, "Failed to write entry: {}", e),
+            ExtractError::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl Error for ExtractError {}
+
+/// The result of extracting a single entry
+#[derive(Debug)]
+pub struct Extracted {
+    /// The CRC of the entry
+    pub crc: u32,
+    /// Whether the extraction was successful
+    pub result: Result<(), ExtractError>,
+}
+
+fn extract_one<F>(
+    reader: &mut Result<BufReader<File>, io::Error>,
+    entry: PKEntry,
+    sink: &F,
+) -> Extracted
+where
+    F: Fn(&PKEntry, &mut dyn Read) -> io::Result<()> + Sync,
+{
+    let crc = entry.crc;
+    let result = match reader {
+        Ok(reader) => PackFile::open(reader)
+            .get_file_data(entry.clone())
+            .map_err(ExtractError::Stream)
+            .and_then(|mut data| sink(&entry, &mut data).map_err(ExtractError::Sink)),
+        Err(e) => Err(ExtractError::Open(io::Error::new(e.kind(), e.to_string()))),
+    };
+    Extracted { crc, result }
+}
+
+/// Extract `entries` from the pack file at `pk_path` in parallel
+///
+/// At most `concurrency` threads are used, or one per CPU if it is `0`. The
+/// `sink` is called with every entry and a reader for its (decompressed) data.
+/// The returned list has one result per entry, in the same order as `entries`.
+pub fn extract_parallel<P, F>(
+    pk_path: P,
+    entries: Vec<PKEntry>,
+    concurrency: usize,
+    sink: F,
+) -> Result<Vec<Extracted>, ThreadPoolBuildError>
+where
+    P: AsRef<Path>,
+    F: Fn(&PKEntry, &mut dyn Read) -> io::Result<()> + Sync,
+{
+    extract_parallel_with_progress(pk_path, entries, concurrency, sink, &())
+}
+
+/// Like [`extract_parallel`], but reports one step per entry to `progress`
+///
+/// Once `progress` asks to stop, all remaining entries fail with
+/// [`ExtractError::Cancelled`].
+pub fn extract_parallel_with_progress<P, F, S>(
+    pk_path: P,
+    entries: Vec<PKEntry>,
+    concurrency: usize,
+    sink: F,
+    progress: &S,
+) -> Result<Vec<Extracted>, ThreadPoolBuildError>
+where
+    P: AsRef<Path>,
+    F: Fn(&PKEntry, &mut dyn Read) -> io::Result<()> + Sync,
+    S: ProgressSink + Sync + ?Sized,
+{
+    let pk_path = pk_path.as_ref();
+    let pool = ThreadPoolBuilder::new().num_threads(concurrency).build()?;
+    progress.start("entries", entries.len() as u64);
+    Ok(pool.install(|| {
+        entries
+            .into_par_iter()
+            .map_init(
+                || File::open(pk_path).map(BufReader::new),
+                |reader, entry| {
+                    if progress.is_cancelled() {
+                        return Extracted {
+                            crc: entry.crc,
+                            result: Err(ExtractError::Cancelled),
+                        };
+                    }
+                    let extracted = extract_one(reader, entry, &sink);
+                    progress.advance(1);
+                    extracted
+                },
+            )
+            .collect()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pk::writer::PackWriter;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_extract_parallel() {
+        let dir = std::env::temp_dir().join(format!("assembly-extract-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pk_path = dir.join("test.pk");
+        let mut writer = PackWriter::new(File::create(&pk_path).unwrap()).unwrap();
+        for index in 0..8 {
+            let data = format!("File {}", index).repeat(index + 1);
+            let path = format!("{}.txt", index);
+            writer
+                .append_entry_with(&path, data.as_bytes(), index % 2 == 0)
+                .unwrap();
+        }
+        let entries: Vec<PKEntry> = writer.entries().cloned().collect();
+        writer.finish().unwrap();
+
+        let output = Mutex::new(Vec::new());
+        let sink = |entry: &PKEntry, data: &mut dyn Read| {
+            let mut text = String::new();
+            data.read_to_string(&mut text)?;
+            output.lock().unwrap().push((entry.crc, text));
+            Ok(())
+        };
+        let results = extract_parallel(&pk_path, entries.clone(), 2, sink).unwrap();
+        let crcs: Vec<_> = results.iter().map(|e| e.crc).collect();
+        let expected: Vec<_> = entries.iter().map(|e| e.crc).collect();
+        assert_eq!(crcs, expected);
+        assert!(results.iter().all(|e| e.result.is_ok()));
+        let output = output.into_inner().unwrap();
+        assert_eq!(output.len(), 8);
+        for (crc, text) in output {
+            let entry = entries.iter().find(|e| e.crc == crc).unwrap();
+            assert_eq!(text.len() as u32, entry.orig_file_size);
+            assert!(text.starts_with("File "));
+        }
+
+        let cancelled = AtomicBool::new(true);
+        let results =
+            extract_parallel_with_progress(&pk_path, entries.clone(), 2, |_, _| Ok(()), &cancelled)
+                .unwrap();
+        assert!(results
+            .iter()
+            .all(|e| matches!(e.result, Err(ExtractError::Cancelled))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        let first = entries[..1].to_vec();
+        let results = extract_parallel(&pk_path, first, 1, |_, _| Ok(())).unwrap();
+        assert!(matches!(results[0].result, Err(ExtractError::Open(_))));
+    }
+}
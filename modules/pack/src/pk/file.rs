@@ -7,7 +7,7 @@ pub struct PKHeader {
 }
 
 /// An entry for a single file
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PKEntry {
     pub crc: u32,
     #[allow(dead_code)]
@@ -18,16 +18,23 @@
 //! the PKI (Pack-Index) file.
 //!
 //! ## This module
-//! This module contains datastructures, parsers, readers and and possibly
-//! writers to (de)serialize, analyze and manipulate the pack archive files.
+//! This module contains datastructures, parsers, readers and writers
+//! to (de)serialize, analyze and manipulate the pack archive files.
 //!
 //! Rough guidelines on which API to use is as follows:
 //!
 //! * Use `PackFile` to walk through the raw data
 //! * Use `PackLoader` for an efficient representation of the data
 //! * Use `PackData` for a datastructure that you can manipulate and write back easily
+//! * Use `PackWriter` to create a new pack file from a list of streams
 
 //pub mod core;
+#[cfg(feature = "async-tokio")]
+pub mod async_reader;
+pub mod codec;
+#[cfg(feature = "parallel")]
+pub mod extract;
 pub mod file;
 pub mod parser;
 pub mod reader;
+pub mod writer;
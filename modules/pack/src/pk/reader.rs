@@ -1,5 +1,6 @@
 //! # Low level reader for PK files
 
+use super::codec::{DecodeReader, EntryCodec};
 use super::file::{PKEntry, PKHeader};
 use super::parser;
 
@@ -143,6 +144,24 @@ where
             Box::new(file_stream)
         })
     }
+
+    /// Like [`PackFile::get_file_data`], but decodes the stored data with `codec` first
+    pub fn get_file_data_with<'c, 'b: 'c>(
+        &'b mut self,
+        entry: PKEntry,
+        codec: &'c dyn EntryCodec,
+    ) -> Result<Box<dyn Read + 'c>, StreamError> {
+        let is_compr = entry.is_compressed[0] > 0;
+        let crc = entry.crc;
+        let file_stream = DecodeReader::new(self.get_file_stream(entry), codec, crc);
+        Ok(if is_compr {
+            let compr_stream =
+                SegmentedStream::try_from(file_stream).map_err(StreamError::Segmented)?;
+            Box::new(compr_stream)
+        } else {
+            Box::new(file_stream)
+        })
+    }
 }
 
 impl<'b, 'a, T> PackEntryAccessor<'b, 'a, T>
@@ -0,0 +1,409 @@
+//! # Streaming writer for PK files
+//!
+//! The [`PackWriter`] writes the data of every entry as soon as it is
+//! appended, reading from the source in small blocks and compressing it one
+//! `sd0` chunk at a time. Only the entry list is kept in memory until
+//! [`PackWriter::finish`] writes it to the end of the file, so large audio or
+//! video resources can be packed with constant memory.
+
+use std::collections::BTreeMap;
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+
+use super::codec::{EncodeWriter, EntryCodec};
+use super::file::PKEntry;
+use crate::crc::calculate_crc_normalized;
+use crate::sd0::stream::SegmentedEncoder;
+
+/// The magic bytes at the start of a pack file
+pub const MAGIC: [u8; 7] = [b'n', b'd', b'p', b'k', 0x01, 0xff, 0x00];
+
+/// The bytes that follow the data of every entry
+pub const DATA_TERMINATOR: [u8; 5] = [0xff, 0x00, 0x00, 0xdd, 0x00];
+
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// A writer that hashes and counts everything written to the inner stream
+struct HashWriter<W> {
+    inner: W,
+    hash: md5::Context,
+    size: u64,
+}
+
+impl<W: Write> HashWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hash: md5::Context::new(),
+            size: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let n = self.inner.write(buf)?;
+        self.hash.consume(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+fn to_u32(value: u64) -> IoResult<u32> {
+    use std::convert::TryFrom;
+    u32::try_from(value).map_err(|e| IoError::new(ErrorKind::InvalidData, e))
+}
+
+/// Copy `reader` to `out` in blocks, returning the size and hash of the data
+fn copy_hashed<R: Read, W: Write>(reader: &mut R, out: &mut W) -> IoResult<(u64, md5::Digest)> {
+    let mut hash = md5::Context::new();
+    let mut size = 0u64;
+    let mut block = vec![0; BLOCK_SIZE];
+    loop {
+        let n = match reader.read(&mut block) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hash.consume(&block[..n]);
+        size += n as u64;
+        out.write_all(&block[..n])?;
+    }
+    Ok((size, hash.compute()))
+}
+
+/// Write the stored data of an entry to `out`, returning the size and hash
+/// of the original data
+fn encode_entry<R: Read, W: Write>(
+    out: &mut W,
+    codec: Option<&dyn EntryCodec>,
+    crc: u32,
+    reader: &mut R,
+    compress: bool,
+) -> IoResult<(u64, md5::Digest)> {
+    let mut sink: Box<dyn Write + '_> = match codec {
+        Some(codec) => Box::new(EncodeWriter::new(out, codec, crc)),
+        None => Box::new(out),
+    };
+    if compress {
+        let mut encoder = SegmentedEncoder::new(&mut sink)?;
+        let result = copy_hashed(reader, &mut encoder)?;
+        encoder.finish()?;
+        Ok(result)
+    } else {
+        copy_hashed(reader, &mut sink)
+    }
+}
+
+/// A writer for pack files that streams the entries
+///
+/// ```
+/// use assembly_pack::pk::writer::PackWriter;
+///
+/// let mut writer = PackWriter::new(Vec::new()).unwrap();
+/// let entry = writer.append_entry("client/res/readme.txt", &b"Hello"[..]).unwrap();
+/// assert_eq!(entry.orig_file_size, 5);
+/// let bytes = writer.finish().unwrap();
+/// assert_eq!(&bytes[..4], b"ndpk");
+/// ```
+pub struct PackWriter<W> {
+    inner: W,
+    offset: u64,
+    entries: BTreeMap<u32, PKEntry>,
+    codec: Option<Box<dyn EntryCodec>>,
+}
+
+impl<W: Write> PackWriter<W> {
+    /// Create a new writer, writing the magic bytes to `inner`
+    pub fn new(mut inner: W) -> IoResult<Self> {
+        inner.write_all(&MAGIC)?;
+        Ok(Self {
+            inner,
+            offset: MAGIC.len() as u64,
+            entries: BTreeMap::new(),
+            codec: None,
+        })
+    }
+
+    /// Encode the stored data of all entries that are appended from now on
+    pub fn with_codec(mut self, codec: Box<dyn EntryCodec>) -> Self {
+        self.codec = Some(codec);
+        self
+    }
+
+    /// The entries that were written so far, ordered by CRC
+    pub fn entries(&self) -> impl Iterator<Item = &PKEntry> {
+        self.entries.values()
+    }
+
+    /// Append a compressed entry for `path`, reading the data from `reader`
+    pub fn append_entry<R: Read>(&mut self, path: &str, reader: R) -> IoResult<&PKEntry> {
+        self.append_entry_with(path, reader, true)
+    }
+
+    /// Append an entry for `path`, reading the data from `reader`
+    ///
+    /// Fails with [`ErrorKind::AlreadyExists`] if there already is an entry
+    /// with the same CRC. If reading from `reader` fails, the data that was
+    /// written up to that point stays in the file without an entry, and
+    /// further entries can still be appended.
+    pub fn append_entry_with<R: Read>(
+        &mut self,
+        path: &str,
+        mut reader: R,
+        compress: bool,
+    ) -> IoResult<&PKEntry> {
+        let crc = calculate_crc_normalized(path.as_bytes());
+        if self.entries.contains_key(&crc) {
+            let msg = format!("duplicate entry {:?} (crc {})", path, crc);
+            return Err(IoError::new(ErrorKind::AlreadyExists, msg));
+        }
+
+        let file_data_addr = to_u32(self.offset)?;
+        let mut out = HashWriter::new(&mut self.inner);
+        let result = encode_entry(&mut out, self.codec.as_deref(), crc, &mut reader, compress);
+        let (compr_size, compr_hash) = (out.size, out.hash.compute());
+        self.offset += compr_size;
+        let (orig_size, orig_hash) = result?;
+        self.write_terminator()?;
+
+        let entry = PKEntry {
+            crc,
+            left: u32::MAX,
+            right: u32::MAX,
+            orig_file_size: to_u32(orig_size)?,
+            orig_file_hash: format!("{:x}", orig_hash),
+            compr_file_size: to_u32(compr_size)?,
+            compr_file_hash: format!("{:x}", compr_hash),
+            file_data_addr,
+            is_compressed: [compress as u8, 0, 0, 0],
+        };
+        Ok(self.entries.entry(crc).or_insert(entry))
+    }
+
+    /// Append an entry from another pack file, copying its stored data verbatim
+    ///
+    /// `reader` must return the stored (possibly compressed) data of `entry`,
+    /// e.g. from [`super::reader::PackFile::get_file_stream`]. The sizes, hashes
+    /// and compression flag are taken from `entry`, and no codec is applied.
+    /// Like in [`PackWriter::append_entry_with`], a failed copy leaves the
+    /// writer usable.
+    pub fn append_raw<R: Read>(&mut self, entry: &PKEntry, reader: R) -> IoResult<&PKEntry> {
+        let crc = entry.crc;
+        if self.entries.contains_key(&crc) {
+            let msg = format!("duplicate entry with crc {}", crc);
+            return Err(IoError::new(ErrorKind::AlreadyExists, msg));
+        }
+        let size = if entry.is_compressed[0] == 0 {
+            entry.orig_file_size
+        } else {
+            entry.compr_file_size
+        };
+        let file_data_addr = to_u32(self.offset)?;
+        let mut out = HashWriter::new(&mut self.inner);
+        let result = std::io::copy(&mut reader.take(u64::from(size)), &mut out);
+        self.offset += out.size;
+        let copied = result?;
+        if copied != u64::from(size) {
+            let msg = format!("expected {} bytes for crc {}, got {}", size, crc, copied);
+            return Err(IoError::new(ErrorKind::UnexpectedEof, msg));
+        }
+        self.write_terminator()?;
+
+        let entry = PKEntry {
+            file_data_addr,
+            ..entry.clone()
+        };
+        Ok(self.entries.entry(crc).or_insert(entry))
+    }
+
+    fn write_terminator(&mut self) -> IoResult<()> {
+        let mut out = HashWriter::new(&mut self.inner);
+        let result = out.write_all(&DATA_TERMINATOR);
+        self.offset += out.size;
+        result
+    }
+
+    /// Write the entry list and the header, and return the inner stream
+    pub fn finish(mut self) -> IoResult<W> {
+        let file_list_base_addr = to_u32(self.offset)?;
+        let mut entries: Vec<PKEntry> = std::mem::take(&mut self.entries).into_values().collect();
+        let count = entries.len();
+        for (entry, (left, right)) in entries.iter_mut().zip(tree_links(count)) {
+            entry.left = left;
+            entry.right = right;
+        }
+
+        self.inner.write_all(&to_u32(count as u64)?.to_le_bytes())?;
+        for entry in &entries {
+            write_entry(&mut self.inner, entry)?;
+        }
+        self.inner.write_all(&file_list_base_addr.to_le_bytes())?;
+        self.inner.write_all(&0u32.to_le_bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/// Compute the `(left, right)` indices that turn a sorted list of `count`
+/// entries into a binary search tree with the root at `count / 2`
+///
+/// Missing children are `u32::MAX`. Pack index files use the same layout.
+pub(crate) fn tree_links(count: usize) -> Vec<(u32, u32)> {
+    fn link(links: &mut [(u32, u32)], start: usize, end: usize) -> u32 {
+        if start >= end {
+            return u32::MAX;
+        }
+        let mid = (start + end) / 2;
+        links[mid] = (link(links, start, mid), link(links, mid + 1, end));
+        mid as u32
+    }
+    let mut links = vec![(u32::MAX, u32::MAX); count];
+    link(&mut links, 0, count);
+    links
+}
+
+fn write_hash<W: Write>(out: &mut W, hash: &str) -> IoResult<()> {
+    let mut bytes = [0u8; 36];
+    let len = hash.len().min(32);
+    bytes[..len].copy_from_slice(&hash.as_bytes()[..len]);
+    out.write_all(&bytes)
+}
+
+fn write_entry<W: Write>(out: &mut W, entry: &PKEntry) -> IoResult<()> {
+    out.write_all(&entry.crc.to_le_bytes())?;
+    out.write_all(&entry.left.to_le_bytes())?;
+    out.write_all(&entry.right.to_le_bytes())?;
+    out.write_all(&entry.orig_file_size.to_le_bytes())?;
+    write_hash(out, &entry.orig_file_hash)?;
+    out.write_all(&entry.compr_file_size.to_le_bytes())?;
+    write_hash(out, &entry.compr_file_hash)?;
+    out.write_all(&entry.file_data_addr.to_le_bytes())?;
+    out.write_all(&entry.is_compressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pk::reader::PackFile;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_write_and_read() {
+        let big: Vec<u8> = (0..300_000).map(|i| (i % 7) as u8).collect();
+        let mut writer = PackWriter::new(Vec::new()).unwrap();
+        writer.append_entry("res/big.bin", &big[..]).unwrap();
+        writer
+            .append_entry_with("res/small.txt", &b"small"[..], false)
+            .unwrap();
+        assert!(writer.append_entry("RES\\BIG.BIN", &b""[..]).is_err());
+        let bytes = writer.finish().unwrap();
+
+        let mut cursor = Cursor::new(bytes);
+        let mut pack = PackFile::open(&mut cursor);
+        pack.check_magic().unwrap();
+        let header = pack.get_header().unwrap();
+        let entries = pack.get_entry_list(header.file_list_base_addr).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].crc < entries[1].crc);
+        assert_eq!(entries[1].left, 0);
+
+        for entry in entries {
+            let expected: &[u8] = if entry.crc == calculate_crc_normalized(b"res/big.bin") {
+                &big
+            } else {
+                b"small"
+            };
+            let hash = format!("{:x}", md5::compute(expected));
+            assert_eq!(entry.orig_file_hash.trim_end_matches('\0'), hash);
+            let mut data = Vec::new();
+            pack.get_file_data(entry)
+                .unwrap()
+                .read_to_end(&mut data)
+                .unwrap();
+            assert_eq!(data, expected);
+        }
+    }
+
+    #[test]
+    fn test_codec() {
+        use crate::pk::codec::XorCodec;
+
+        assert_eq!(XorCodec::new(Vec::new()), None);
+        let codec = XorCodec::new(vec![0x5a, 0xa5, 0x33]).unwrap();
+        let writer = PackWriter::new(Vec::new()).unwrap();
+        let mut writer = writer.with_codec(Box::new(codec.clone()));
+        writer.append_entry("a.txt", &b"compressed"[..]).unwrap();
+        writer
+            .append_entry_with("b.txt", &b"plain"[..], false)
+            .unwrap();
+        let bytes = writer.finish().unwrap();
+        assert!(!bytes.windows(5).any(|w| w == b"plain"));
+
+        let mut cursor = Cursor::new(bytes);
+        let mut pack = PackFile::open(&mut cursor);
+        let header = pack.get_header().unwrap();
+        let entries = pack.get_entry_list(header.file_list_base_addr).unwrap();
+        let mut texts = Vec::new();
+        for entry in entries {
+            let mut text = String::new();
+            pack.get_file_data_with(entry, &codec)
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap();
+            texts.push(text);
+        }
+        texts.sort();
+        assert_eq!(texts, ["compressed", "plain"]);
+    }
+
+    struct Failing;
+
+    impl Read for Failing {
+        fn read(&mut self, _buf: &mut [u8]) -> IoResult<usize> {
+            Err(IoError::other("broken source"))
+        }
+    }
+
+    #[test]
+    fn test_failing_reader() {
+        let mut writer = PackWriter::new(Vec::new()).unwrap();
+        let data = [7u8; 100];
+        let broken = (&data[..]).chain(Failing);
+        assert!(writer.append_entry_with("a.txt", broken, false).is_err());
+        let entry = PKEntry {
+            crc: calculate_crc_normalized(b"b.txt"),
+            left: 0,
+            right: 0,
+            orig_file_size: 200,
+            orig_file_hash: String::new(),
+            compr_file_size: 200,
+            compr_file_hash: String::new(),
+            file_data_addr: 0,
+            is_compressed: [0; 4],
+        };
+        let broken = (&data[..]).chain(Failing);
+        assert!(writer.append_raw(&entry, broken).is_err());
+        writer
+            .append_entry_with("c.txt", &b"intact"[..], false)
+            .unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let mut cursor = Cursor::new(bytes);
+        let mut pack = PackFile::open(&mut cursor);
+        let header = pack.get_header().unwrap();
+        let entries = pack.get_entry_list(header.file_list_base_addr).unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = entries.into_iter().next().unwrap();
+        let mut text = String::new();
+        pack.get_file_data(entry)
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "intact");
+    }
+}
@@ -1,18 +1,219 @@
 //! Public data structures for pack index files
 use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
 
-#[derive(Debug)]
+use crate::crc::calculate_crc_normalized;
+use crate::path::normalize;
+
+#[cfg(feature = "serde-derives")]
+use serde::{Deserialize, Serialize};
+
+/// A reference to a pack file (`*.pk`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(Serialize, Deserialize))]
 pub struct PackFileRef {
+    /// The path of the pack file, relative to the client folder
     pub path: String,
 }
 
-#[derive(Debug, Copy, Clone)]
+impl fmt::Display for PackFileRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.path)
+    }
+}
+
+/// The entry for a single file in the index
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(Serialize, Deserialize))]
 pub struct FileRef {
+    /// The flags of the file, non-zero if the file is compressed
     pub category: u32,
+    /// The index of the pack file in [`PackIndexFile::archives`]
     pub pack_file: u32,
 }
 
+impl FileRef {
+    /// Check whether the file is stored compressed (as `sd0`)
+    pub fn is_compressed(&self) -> bool {
+        self.category != 0
+    }
+}
+
+impl fmt::Display for FileRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pack #{}", self.pack_file)?;
+        if self.is_compressed() {
+            write!(f, " (compressed)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The contents of a pack index file (`*.pki`)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde-derives", derive(Serialize, Deserialize))]
 pub struct PackIndexFile {
+    /// The list of pack files
     pub archives: Vec<PackFileRef>,
+    /// The files, by CRC of their path
     pub files: BTreeMap<u32, FileRef>,
 }
+
+/// An inconsistency in a [`PackIndexFile`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsistencyError {
+    /// A file references a pack file that does not exist
+    MissingArchive {
+        /// The CRC of the file
+        crc: u32,
+        /// The index of the pack file
+        pack_file: u32,
+    },
+    /// The same pack file is listed more than once
+    DuplicateArchive(String),
+}
+
+impl Error for ConsistencyError {}
+
+impl fmt::Display for ConsistencyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsistencyError::MissingArchive { crc, pack_file } => {
+                write!(f, "File {} references missing pack #{}", crc, pack_file)
+            }
+            ConsistencyError::DuplicateArchive(path) => {
+                write!(f, "Pack file {:?} is listed more than once", path)
+            }
+        }
+    }
+}
+
+impl PackIndexFile {
+    /// Get the pack file that the entry references
+    pub fn archive(&self, file_ref: &FileRef) -> Option<&PackFileRef> {
+        self.archives.get(file_ref.pack_file as usize)
+    }
+
+    /// Get the entry for a path
+    ///
+    /// The path is brought into its canonical form before hashing.
+    pub fn get(&self, path: &str) -> Option<&FileRef> {
+        self.files.get(&calculate_crc_normalized(path.as_bytes()))
+    }
+
+    /// Add or replace the entry for `path`
+    ///
+    /// The pack file `pack_name` is added to the list of archives if it isn't
+    /// in there yet. Returns the previous entry for the path, if any.
+    pub fn insert(&mut self, path: &str, pack_name: &str, compressed: bool) -> Option<FileRef> {
+        self.insert_crc(
+            calculate_crc_normalized(path.as_bytes()),
+            pack_name,
+            compressed,
+        )
+    }
+
+    /// Like [`PackIndexFile::insert`], for a file that is only known by its CRC
+    pub fn insert_crc(&mut self, crc: u32, pack_name: &str, compressed: bool) -> Option<FileRef> {
+        let pack_key = normalize(pack_name);
+        let pack_file = match self
+            .archives
+            .iter()
+            .position(|a| normalize(&a.path) == pack_key)
+        {
+            Some(index) => index,
+            None => {
+                self.archives.push(PackFileRef {
+                    path: pack_name.to_owned(),
+                });
+                self.archives.len() - 1
+            }
+        };
+        let file_ref = FileRef {
+            category: u32::from(compressed),
+            pack_file: pack_file as u32,
+        };
+        self.files.insert(crc, file_ref)
+    }
+
+    /// Remove the entry for `path`, returning it if it was present
+    ///
+    /// This does not remove the pack file from the list of archives.
+    pub fn remove(&mut self, path: &str) -> Option<FileRef> {
+        self.files
+            .remove(&calculate_crc_normalized(path.as_bytes()))
+    }
+
+    /// Check that all files reference an existing pack file and that
+    /// no pack file is listed twice.
+    pub fn check_consistency(&self) -> Result<(), ConsistencyError> {
+        let mut seen = Vec::with_capacity(self.archives.len());
+        for archive in &self.archives {
+            let key = normalize(&archive.path);
+            if seen.contains(&key) {
+                return Err(ConsistencyError::DuplicateArchive(archive.path.clone()));
+            }
+            seen.push(key);
+        }
+        for (&crc, file_ref) in &self.files {
+            if self.archive(file_ref).is_none() {
+                return Err(ConsistencyError::MissingArchive {
+                    crc,
+                    pack_file: file_ref.pack_file,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PackIndexFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} archives:", self.archives.len())?;
+        for (index, archive) in self.archives.iter().enumerate() {
+            writeln!(f, "#{}: {}", index, archive)?;
+        }
+        writeln!(f, "{} files:", self.files.len())?;
+        for (crc, file_ref) in &self.files {
+            write!(f, "{:10}: ", crc)?;
+            match self.archive(file_ref) {
+                Some(archive) => write!(f, "{}", archive)?,
+                None => write!(f, "{}", file_ref)?,
+            }
+            if file_ref.is_compressed() {
+                write!(f, " (compressed)")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_remove() {
+        let mut pki = PackIndexFile::default();
+        assert_eq!(pki.insert("res/a.txt", "client/res/pack/a.pk", true), None);
+        assert_eq!(
+            pki.insert("res/b.txt", "client\\res\\pack\\A.pk", false),
+            None
+        );
+        assert_eq!(pki.archives.len(), 1);
+        assert!(pki.get("RES\\A.TXT").unwrap().is_compressed());
+        assert!(pki.check_consistency().is_ok());
+
+        assert!(pki.remove("res/a.txt").is_some());
+        assert_eq!(pki.get("res/a.txt"), None);
+        assert_eq!(pki.files.len(), 1);
+
+        pki.archives.clear();
+        assert!(matches!(
+            pki.check_consistency(),
+            Err(ConsistencyError::MissingArchive { pack_file: 0, .. })
+        ));
+    }
+}
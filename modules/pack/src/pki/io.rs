@@ -1,32 +1,26 @@
 use std::convert::TryFrom;
 use std::fs::File;
-use std::io::{BufReader, Error as IoError, Read};
+use std::io::{BufReader, Error as IoError, ErrorKind, Result as IoResult, Write};
 
 use super::core::PackIndexFile;
 use super::parser;
+use crate::pk::writer::tree_links;
 
-use assembly_core::nom::{self, error::ErrorKind, Err as NomErr};
+use assembly_core::reader::{ParseError, ParseOffset};
+use assembly_core::source::ByteSource;
 
 #[derive(Debug)]
 pub enum LoadError {
     FileOpen(IoError),
     Read(IoError),
-    Incomplete,
-    ParseError(ErrorKind),
-    ParseFailure(ErrorKind),
+    Parse(ParseError),
 }
 
 type LoadResult<T> = Result<T, LoadError>;
 
-// Generates a LoadError from a nom error
-impl From<NomErr<nom::error::Error<&[u8]>>> for LoadError {
-    fn from(e: NomErr<nom::error::Error<&[u8]>>) -> LoadError {
-        match e {
-            // Need to translate the error here, as this lives longer than the input
-            NomErr::Incomplete(_) => LoadError::Incomplete,
-            NomErr::Error(e) => LoadError::ParseError(e.code),
-            NomErr::Failure(e) => LoadError::ParseFailure(e.code),
-        }
+impl From<ParseError> for LoadError {
+    fn from(e: ParseError) -> LoadError {
+        LoadError::Parse(e)
     }
 }
 
@@ -43,10 +37,58 @@ impl TryFrom<File> for PackIndexFile {
     type Error = LoadError;
 
     fn try_from(file: File) -> LoadResult<PackIndexFile> {
-        let mut buf = BufReader::new(file);
-        let mut bytes: Vec<u8> = Vec::new();
-        buf.read_to_end(&mut bytes).map_err(LoadError::Read)?;
-        let (_rest, pki_file) = parser::parse_pki_file(&bytes)?;
+        PackIndexFile::from_source(BufReader::new(file))
+    }
+}
+
+impl PackIndexFile {
+    /// Load a pack index file from any [`ByteSource`]
+    pub fn from_source<S: ByteSource>(mut source: S) -> LoadResult<PackIndexFile> {
+        let bytes = source.read_bytes().map_err(LoadError::Read)?;
+        let (_rest, pki_file) = parser::parse_pki_file(&bytes).at_offset(&bytes)?;
         Ok(pki_file)
     }
 }
+
+fn to_u32(value: usize) -> IoResult<u32> {
+    u32::try_from(value).map_err(|e| IoError::new(ErrorKind::InvalidData, e))
+}
+
+impl PackIndexFile {
+    /// Write the pack index file to `out`
+    pub fn write<W: Write>(&self, out: &mut W) -> IoResult<()> {
+        out.write_all(&3u32.to_le_bytes())?;
+        out.write_all(&to_u32(self.archives.len())?.to_le_bytes())?;
+        for archive in &self.archives {
+            out.write_all(&to_u32(archive.path.len())?.to_le_bytes())?;
+            out.write_all(archive.path.as_bytes())?;
+        }
+        out.write_all(&to_u32(self.files.len())?.to_le_bytes())?;
+        let links = tree_links(self.files.len());
+        for ((crc, file_ref), (left, right)) in self.files.iter().zip(links) {
+            for value in &[*crc, left, right, file_ref.pack_file, file_ref.category] {
+                out.write_all(&value.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Load a pack index file from an async reader
+///
+/// This is only available with the `async-tokio` feature.
+#[cfg(feature = "async-tokio")]
+pub async fn load_async<R>(reader: &mut R) -> LoadResult<PackIndexFile>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    use tokio::io::AsyncReadExt;
+
+    let mut bytes: Vec<u8> = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .await
+        .map_err(LoadError::Read)?;
+    let (_rest, pki_file) = parser::parse_pki_file(&bytes).at_offset(&bytes)?;
+    Ok(pki_file)
+}
@@ -0,0 +1,239 @@
+//! # Zero-copy access to pack index files
+//!
+//! This module mirrors the `fdb::mem` API of the `assembly-data` crate: a
+//! [`PackIndexRef`] borrows the complete file and decodes entries only when they
+//! are requested, so lookups do not allocate.
+//!
+//! ```
+//! use assembly_pack::pki::mem::PackIndexRef;
+//!
+//! let file: &[u8] = &[3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+//! let pki = PackIndexRef::new(file).unwrap();
+//!
+//! assert_eq!(pki.archive_count(), 0);
+//! assert_eq!(pki.get("res/a.txt"), None);
+//! ```
+
+use std::{borrow::Cow, convert::TryInto};
+
+use assembly_core::{
+    nom::{
+        bytes::complete::{tag, take},
+        multi::length_data,
+        number::complete::le_u32,
+        IResult,
+    },
+    reader::{ParseError, ParseOffset},
+};
+
+use super::core::{FileRef, PackFileRef, PackIndexFile};
+use crate::crc::calculate_crc_normalized;
+
+const FILE_ENTRY_SIZE: usize = 20;
+
+/// A reference to a pack file entry
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ArchiveRef<'a> {
+    path: &'a [u8],
+}
+
+impl<'a> ArchiveRef<'a> {
+    /// Returns the undecoded path
+    pub fn path_raw(&self) -> &'a [u8] {
+        self.path
+    }
+
+    /// Returns the path, relative to the client folder
+    pub fn path(&self) -> Cow<'a, str> {
+        String::from_utf8_lossy(self.path)
+    }
+}
+
+/// Iterator over the pack files in a [`PackIndexRef`]
+#[derive(Debug, Clone)]
+pub struct ArchiveIter<'a> {
+    remaining: usize,
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for ArchiveIter<'a> {
+    type Item = ArchiveRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        // The list was checked in `PackIndexRef::new`
+        let (rest, path) = length_data::<_, _, (), _>(le_u32)(self.rest).ok()?;
+        self.remaining -= 1;
+        self.rest = rest;
+        Some(ArchiveRef { path })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+/// The entry for a single file, with its CRC
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    /// The CRC of the path of the file
+    pub crc: u32,
+    /// The location of the file
+    pub file_ref: FileRef,
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..(at + 4)].try_into().unwrap())
+}
+
+/// The number of pack files, the list of pack files and the list of files
+type Header<'a> = (usize, &'a [u8], &'a [u8]);
+
+fn parse_header(input: &[u8]) -> IResult<&[u8], Header<'_>> {
+    let (input, _version) = tag(u32::to_le_bytes(3))(input)?;
+    let (mut rest, archive_count) = le_u32(input)?;
+    let archives = rest;
+    for _ in 0..archive_count {
+        rest = length_data(le_u32)(rest)?.0;
+    }
+    let archives = &archives[..(archives.len() - rest.len())];
+    let (rest, file_count) = le_u32(rest)?;
+    let (rest, files) = take(file_count as usize * FILE_ENTRY_SIZE)(rest)?;
+    Ok((rest, (archive_count as usize, archives, files)))
+}
+
+/// A borrowed pack index file
+#[derive(Debug, Copy, Clone)]
+pub struct PackIndexRef<'a> {
+    archive_count: usize,
+    archives: &'a [u8],
+    files: &'a [u8],
+}
+
+impl<'a> PackIndexRef<'a> {
+    /// Check the structure of `buf` and create a new reference
+    pub fn new(buf: &'a [u8]) -> Result<Self, ParseError> {
+        let (_rest, (archive_count, archives, files)) = parse_header(buf).at_offset(buf)?;
+        Ok(Self {
+            archive_count,
+            archives,
+            files,
+        })
+    }
+
+    /// Returns the number of pack files
+    pub fn archive_count(&self) -> usize {
+        self.archive_count
+    }
+
+    /// Iterate over all pack files
+    pub fn archive_iter(&self) -> ArchiveIter<'a> {
+        ArchiveIter {
+            remaining: self.archive_count,
+            rest: self.archives,
+        }
+    }
+
+    /// Get the pack file at `index`
+    ///
+    /// **Note**: Pack files have a variable size, so this is linear in `index`
+    pub fn archive_at(&self, index: usize) -> Option<ArchiveRef<'a>> {
+        self.archive_iter().nth(index)
+    }
+
+    /// Get the pack file that the entry references
+    pub fn archive(&self, file_ref: &FileRef) -> Option<ArchiveRef<'a>> {
+        self.archive_at(file_ref.pack_file as usize)
+    }
+
+    /// Returns the number of files
+    pub fn file_count(&self) -> usize {
+        self.files.len() / FILE_ENTRY_SIZE
+    }
+
+    /// Get the file entry at `index`
+    pub fn file_at(&self, index: usize) -> Option<FileEntry> {
+        let start = index.checked_mul(FILE_ENTRY_SIZE)?;
+        let bytes = self.files.get(start..(start + FILE_ENTRY_SIZE))?;
+        Some(FileEntry {
+            crc: read_u32(bytes, 0),
+            file_ref: FileRef {
+                pack_file: read_u32(bytes, 12),
+                category: read_u32(bytes, 16),
+            },
+        })
+    }
+
+    /// Iterate over all file entries
+    pub fn file_iter(&self) -> impl Iterator<Item = FileEntry> + 'a {
+        let this = *self;
+        (0..self.file_count()).filter_map(move |index| this.file_at(index))
+    }
+
+    /// Get the entry for a CRC
+    ///
+    /// The entries in a pack index file are sorted by CRC, so this is a binary search.
+    pub fn get_by_crc(&self, crc: u32) -> Option<FileRef> {
+        let (mut low, mut high) = (0, self.file_count());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let entry = self.file_at(mid)?;
+            match entry.crc.cmp(&crc) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Some(entry.file_ref),
+            }
+        }
+        None
+    }
+
+    /// Get the entry for a path
+    ///
+    /// The path is brought into its canonical form before hashing.
+    pub fn get(&self, path: &str) -> Option<FileRef> {
+        self.get_by_crc(calculate_crc_normalized(path.as_bytes()))
+    }
+
+    /// Create an owned copy of the whole file
+    pub fn to_owned(&self) -> PackIndexFile {
+        PackIndexFile {
+            archives: self
+                .archive_iter()
+                .map(|a| PackFileRef {
+                    path: a.path().into_owned(),
+                })
+                .collect(),
+            files: self.file_iter().map(|e| (e.crc, e.file_ref)).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pki_ref() {
+        let mut file = vec![3, 0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0];
+        file.extend_from_slice(b"a.pk");
+        file.extend_from_slice(&2u32.to_le_bytes());
+        for (crc, category) in &[(5u32, 0u32), (calculate_crc_normalized(b"res/a.txt"), 1)] {
+            for v in &[*crc, u32::MAX, u32::MAX, 0, *category] {
+                file.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        let pki = PackIndexRef::new(&file).unwrap();
+        assert_eq!(pki.archive_at(0).unwrap().path(), "a.pk");
+        assert_eq!(pki.file_count(), 2);
+        let file_ref = pki.get("RES\\A.TXT").unwrap();
+        assert!(file_ref.is_compressed());
+        assert_eq!(pki.archive(&file_ref).unwrap().path_raw(), b"a.pk");
+        assert_eq!(pki.get_by_crc(6), None);
+        assert_eq!(pki.to_owned().files.len(), 2);
+
+        let err = PackIndexRef::new(&file[..30]).unwrap_err();
+        assert_eq!(err.offset(), Some(20));
+    }
+}
@@ -6,4 +6,6 @@
 
 pub mod core;
 pub mod io;
+pub mod mem;
 pub mod parser;
+pub mod tree;
@@ -0,0 +1,198 @@
+//! # Reconstructing the file tree of an index
+//!
+//! A pack index only stores the CRC of each path. To browse it like a
+//! directory, the names have to come from somewhere else: usually from a
+//! patcher manifest, which lists every file of the client, or from a
+//! dictionary of known names. A [`FileTree`] collects the names that match
+//! a CRC in the index and keeps track of the files that are still unknown.
+//!
+//! ```
+//! use assembly_pack::pki::{core::PackIndexFile, tree::FileTree};
+//!
+//! let mut pki = PackIndexFile::default();
+//! pki.insert("res/mesh/a.nif", "client/res/pack/mesh.pk", true);
+//! pki.insert("res/mesh/b.nif", "client/res/pack/mesh.pk", true);
+//! pki.insert("res/ui/c.gfx", "client/res/pack/ui.pk", true);
+//!
+//! let mut tree = FileTree::new(&pki);
+//! tree.add_manifest("[version]\n1,abc,test\n[files]\nres/mesh/a.nif,10,abc\n");
+//! tree.add_names(&["res/ui/c.gfx", "res/unrelated.txt"]);
+//!
+//! assert_eq!(tree.list_prefix("mesh/"), vec!["client\\res\\mesh\\a.nif"]);
+//! assert_eq!(tree.list_dir("res"), vec!["mesh\\", "ui\\"]);
+//! assert_eq!(tree.unknown().count(), 1);
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::core::{FileRef, PackIndexFile};
+use crate::crc::calculate_crc;
+use crate::path::{normalize, strip_client_res, SEPARATOR};
+
+/// The names of the files in a pack index, as far as they are known
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileTree {
+    files: BTreeMap<u32, FileRef>,
+    names: BTreeMap<String, u32>,
+    known: BTreeMap<u32, String>,
+}
+
+/// Get the names of all files in a patcher manifest
+///
+/// These are the first comma-separated field of every line in the `[files]`
+/// section.
+pub fn manifest_names(text: &str) -> impl Iterator<Item = &str> {
+    let mut in_files = false;
+    text.lines().filter_map(move |line| {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_files = line == "[files]";
+            None
+        } else if in_files && !line.is_empty() {
+            line.split(',').next()
+        } else {
+            None
+        }
+    })
+}
+
+impl FileTree {
+    /// Create a tree for the files in `pki`, with no names yet
+    pub fn new(pki: &PackIndexFile) -> Self {
+        Self {
+            files: pki.files.clone(),
+            names: BTreeMap::new(),
+            known: BTreeMap::new(),
+        }
+    }
+
+    /// Add the names of the files in a patcher manifest, see [`manifest_names`]
+    ///
+    /// Returns the number of files in the index that were named.
+    pub fn add_manifest(&mut self, text: &str) -> usize {
+        self.add_names(manifest_names(text))
+    }
+
+    /// Add the names from a dictionary of known paths
+    ///
+    /// Paths that aren't in the index are ignored. Returns the number of files
+    /// in the index that were named.
+    pub fn add_names<I>(&mut self, names: I) -> usize
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut count = 0;
+        for name in names {
+            let name = normalize(name.as_ref());
+            let crc = calculate_crc(name.as_bytes());
+            if self.files.contains_key(&crc) && !self.known.contains_key(&crc) {
+                self.known.insert(crc, name.clone());
+                self.names.insert(name, crc);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Get the name of a file, if it is known
+    pub fn name_of(&self, crc: u32) -> Option<&str> {
+        self.known.get(&crc).map(String::as_str)
+    }
+
+    /// Iterate over the files with a known name, in order of the name
+    pub fn named(&self) -> impl Iterator<Item = (&str, &FileRef)> {
+        self.names
+            .iter()
+            .map(move |(name, crc)| (name.as_str(), &self.files[crc]))
+    }
+
+    /// Iterate over the CRCs of the files whose name is not known
+    pub fn unknown(&self) -> impl Iterator<Item = u32> + '_ {
+        self.files
+            .keys()
+            .copied()
+            .filter(move |crc| !self.known.contains_key(crc))
+    }
+
+    fn matches(name: &str, prefix: &str) -> bool {
+        match strip_client_res(name) {
+            Some(rest) if rest.starts_with(prefix) => true,
+            _ => name.starts_with(prefix),
+        }
+    }
+
+    /// List the canonical paths of the named files that start with `prefix`
+    ///
+    /// The prefix may also be relative to `client\res\`, so `mesh/` matches
+    /// `client\res\mesh\a.nif`.
+    pub fn list_prefix(&self, prefix: &str) -> Vec<String> {
+        let prefix = normalize(prefix);
+        self.names
+            .keys()
+            .filter(|name| Self::matches(name, &prefix))
+            .cloned()
+            .collect()
+    }
+
+    /// List the entries directly in the directory `dir`
+    ///
+    /// Subdirectories end with a `\`. Like [`FileTree::list_prefix`], `dir`
+    /// may be relative to `client\res\`.
+    pub fn list_dir(&self, dir: &str) -> Vec<String> {
+        let prefix = match dir {
+            "" => String::new(),
+            dir => normalize(&format!("{}\\", dir)),
+        };
+        let mut entries = BTreeSet::new();
+        for name in self.names.keys() {
+            let rest = match name.strip_prefix(&prefix) {
+                Some(rest) => rest,
+                None => match strip_client_res(name).and_then(|r| r.strip_prefix(&prefix)) {
+                    Some(rest) => rest,
+                    None => continue,
+                },
+            };
+            let entry = match rest.find(SEPARATOR as char) {
+                Some(end) => &rest[..=end],
+                None => rest,
+            };
+            entries.insert(entry.to_string());
+        }
+        entries.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crc::calculate_crc_normalized;
+
+    #[test]
+    fn test_file_tree() {
+        let mut pki = PackIndexFile::default();
+        for name in &["res/a.txt", "res/mesh/b.nif", "res/mesh/sub/c.nif", "x.dat"] {
+            pki.insert(name, "client/res/pack/a.pk", true);
+        }
+        let mut tree = FileTree::new(&pki);
+        assert_eq!(tree.add_names(&["client/res/a.txt", "res/mesh/b.nif"]), 2);
+        assert_eq!(tree.add_names(&["res/A.txt"]), 0);
+        let manifest = "[files]\nres/mesh/sub/c.nif,1,abc,1,abc,abc\n";
+        assert_eq!(tree.add_manifest(manifest), 1);
+
+        assert_eq!(
+            tree.name_of(calculate_crc_normalized(b"res/mesh/b.nif")),
+            Some("client\\res\\mesh\\b.nif")
+        );
+        assert_eq!(
+            tree.unknown().collect::<Vec<_>>(),
+            vec![calculate_crc_normalized(b"x.dat")]
+        );
+        assert_eq!(tree.list_prefix("mesh\\sub").len(), 1);
+        assert_eq!(tree.list_prefix("client/res/").len(), 3);
+        assert_eq!(tree.list_dir(""), vec!["client\\"]);
+        assert_eq!(tree.list_dir("client/res"), vec!["a.txt", "mesh\\"]);
+        assert_eq!(tree.list_dir("mesh"), vec!["b.nif", "sub\\"]);
+        assert_eq!(tree.named().count(), 3);
+    }
+}
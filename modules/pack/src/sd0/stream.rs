@@ -2,11 +2,11 @@
 //!
 //!
 use assembly_core::borrow::Oom;
-use libflate::deflate::Decoder as ZlibDecoder;
+use libflate::zlib::{Decoder as ZlibDecoder, Encoder as ZlibEncoder};
 use std::convert::{From, TryFrom};
 use std::error::Error;
 use std::fmt::{Display, Formatter, Result as FmtResult};
-use std::io::{BufReader, Error as IoError, ErrorKind, Read, Result as IoResult};
+use std::io::{BufReader, Error as IoError, ErrorKind, Read, Result as IoResult, Write};
 use std::num::TryFromIntError;
 
 /// # Error type for segmented streams
@@ -20,6 +20,12 @@ pub enum SegmentedError {
     ZlibMissing,
 }
 
+/// The magic bytes at the start of every sd0 stream
+pub const MAGIC: [u8; 5] = [b's', b'd', b'0', 0x01, 0xff];
+
+/// The maximum number of uncompressed bytes per chunk
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
 /// Result with segmented error
 pub type SegmentedResult<T> = Result<T, SegmentedError>;
 
@@ -86,7 +92,8 @@ where
                 //println!("CHNK: {}", size);
                 self.chunk_remain = usize::try_from(size)?;
                 let buf_read = BufReader::new(self);
-                Ok(Some(ZlibDecoder::new(buf_read)))
+                let decoder = ZlibDecoder::new(buf_read).map_err(SegmentedError::Read)?;
+                Ok(Some(decoder))
             }
             Err(_) => Ok(None),
         }
@@ -137,7 +144,7 @@ where
     fn check_magic(inner: &mut T) -> SegmentedResult<()> {
         let mut magic: [u8; 5] = [0; 5];
         inner.read_exact(&mut magic).map_err(SegmentedError::Read)?;
-        if magic == [b's', b'd', b'0', 0x01, 0xff] {
+        if magic == MAGIC {
             Ok(())
         } else {
             Err(SegmentedError::MagicMismatch(magic))
@@ -201,3 +208,84 @@ where
         }
     }
 }
+
+/// # `Write`-Stream wrapper for sd0
+///
+/// This structure compresses everything that is written to it into an inner
+/// stream. The data is buffered until a chunk of [`CHUNK_SIZE`] bytes is full,
+/// so the memory use does not depend on the size of the file.
+///
+/// Call [`SegmentedEncoder::finish`] to write the last chunk.
+pub struct SegmentedEncoder<W: Write> {
+    inner: W,
+    chunk: Vec<u8>,
+}
+
+impl<W: Write> SegmentedEncoder<W> {
+    /// Create a new encoder, writing the magic bytes to `inner`
+    pub fn new(mut inner: W) -> IoResult<Self> {
+        inner.write_all(&MAGIC)?;
+        Ok(Self {
+            inner,
+            chunk: Vec::with_capacity(CHUNK_SIZE),
+        })
+    }
+
+    fn write_chunk(&mut self) -> IoResult<()> {
+        let mut encoder = ZlibEncoder::new(Vec::new())?;
+        encoder.write_all(&self.chunk)?;
+        let data = encoder.finish().into_result()?;
+        let size =
+            u32::try_from(data.len()).map_err(|e| IoError::new(ErrorKind::InvalidData, e))?;
+        self.inner.write_all(&size.to_le_bytes())?;
+        self.inner.write_all(&data)?;
+        self.chunk.clear();
+        Ok(())
+    }
+
+    /// Write the last chunk and return the inner stream
+    pub fn finish(mut self) -> IoResult<W> {
+        if !self.chunk.is_empty() {
+            self.write_chunk()?;
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for SegmentedEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let len = buf.len().min(CHUNK_SIZE - self.chunk.len());
+        self.chunk.extend_from_slice(&buf[..len]);
+        if self.chunk.len() == CHUNK_SIZE {
+            self.write_chunk()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_roundtrip() {
+        let data: Vec<u8> = (0..CHUNK_SIZE * 2 + 100).map(|i| (i % 251) as u8).collect();
+        let mut encoder = SegmentedEncoder::new(Vec::new()).unwrap();
+        encoder.write_all(&data).unwrap();
+        let encoded = encoder.finish().unwrap();
+        assert_eq!(encoded[..5], MAGIC);
+
+        let mut decoded = Vec::new();
+        SegmentedStream::try_from(Cursor::new(encoded))
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+}
@@ -0,0 +1,282 @@
+//! # Verifying pack files
+//!
+//! This module checks the content of a pack (`*.pk`) file against the sizes and
+//! MD5 hashes that are stored with every entry, and against the pack index
+//! (`*.pki`) file that is supposed to list all of them.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Read, Seek};
+use std::path::Path;
+
+#[cfg(feature = "serde-derives")]
+use serde::Serialize;
+
+use assembly_core::progress::ProgressSink;
+use assembly_core::reader::{FileError, FileResult};
+
+use crate::path::file_name;
+use crate::pk::{file::PKEntry, reader::PackFile};
+use crate::pki::core::PackIndexFile;
+use crate::sd0::stream::SegmentedStream;
+
+/// A problem with a single entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(Serialize))]
+pub enum Issue {
+    /// The data could not be read or decompressed
+    Unreadable(String),
+    /// The size of the (compressed) data does not match the entry
+    SizeMismatch {
+        /// Whether this is about the compressed data
+        compressed: bool,
+        /// The size in the entry
+        expected: u32,
+        /// The actual size
+        actual: u64,
+    },
+    /// The hash of the (compressed) data does not match the entry
+    HashMismatch {
+        /// Whether this is about the compressed data
+        compressed: bool,
+        /// The hash in the entry
+        expected: String,
+        /// The actual hash
+        actual: String,
+    },
+    /// The file is not listed in the index
+    NotInIndex,
+    /// The index lists the file for another pack file
+    WrongArchive(Option<String>),
+    /// The index and the pack file disagree on whether the file is compressed
+    CompressionMismatch,
+}
+
+/// The issues for a single entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(Serialize))]
+pub struct EntryReport {
+    /// The CRC of the path of the file
+    pub crc: u32,
+    /// The list of issues, never empty
+    pub issues: Vec<Issue>,
+}
+
+/// The result of [`verify`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde-derives", derive(Serialize))]
+pub struct VerifyReport {
+    /// The index of the pack file in [`PackIndexFile::archives`], if it was found
+    pub archive: Option<u32>,
+    /// The number of entries that were checked
+    pub checked: usize,
+    /// The entries with at least one issue
+    pub failures: Vec<EntryReport>,
+    /// The CRCs of files that the index assigns to this pack file, but that are missing
+    pub missing: Vec<u32>,
+}
+
+impl VerifyReport {
+    /// Check whether no issues were found
+    pub fn is_ok(&self) -> bool {
+        self.archive.is_some() && self.failures.is_empty() && self.missing.is_empty()
+    }
+}
+
+fn md5_hex(data: &[u8]) -> String {
+    format!("{:x}", md5::compute(data))
+}
+
+fn check_data(compressed: bool, size: u32, hash: &str, data: &[u8], issues: &mut Vec<Issue>) {
+    if data.len() as u64 != u64::from(size) {
+        issues.push(Issue::SizeMismatch {
+            compressed,
+            expected: size,
+            actual: data.len() as u64,
+        });
+    }
+    let expected = hash.trim_end_matches('\0').to_ascii_lowercase();
+    let actual = md5_hex(data);
+    if actual != expected {
+        issues.push(Issue::HashMismatch {
+            compressed,
+            expected,
+            actual,
+        });
+    }
+}
+
+fn check_entry<T>(pack: &mut PackFile<'_, T>, entry: PKEntry, issues: &mut Vec<Issue>)
+where
+    T: Seek + BufRead,
+{
+    let is_compressed = entry.is_compressed[0] > 0;
+    let (orig_size, orig_hash) = (entry.orig_file_size, entry.orig_file_hash.clone());
+    let (compr_size, compr_hash) = (entry.compr_file_size, entry.compr_file_hash.clone());
+
+    let mut raw = Vec::new();
+    if let Err(e) = pack.get_file_stream(entry).read_to_end(&mut raw) {
+        issues.push(Issue::Unreadable(e.to_string()));
+        return;
+    }
+
+    if is_compressed {
+        check_data(true, compr_size, &compr_hash, &raw, issues);
+        let mut data = Vec::new();
+        let result = SegmentedStream::try_from(Cursor::new(&raw))
+            .map_err(|e| e.to_string())
+            .and_then(|mut s| s.read_to_end(&mut data).map_err(|e| e.to_string()));
+        match result {
+            Ok(_) => check_data(false, orig_size, &orig_hash, &data, issues),
+            Err(e) => issues.push(Issue::Unreadable(e)),
+        }
+    } else {
+        check_data(false, orig_size, &orig_hash, &raw, issues);
+    }
+}
+
+/// Verify all entries of a pack file against their checksums and the index
+///
+/// The pack file is identified in the index by its file name. Errors that
+/// affect a single entry are collected in the report, only errors with the
+/// file itself or its list of entries are returned as an error.
+pub fn verify<P: AsRef<Path>>(pk_path: P, pki: &PackIndexFile) -> FileResult<VerifyReport> {
+    verify_with_progress(pk_path, pki, &())
+}
+
+/// Like [`verify`], but reports one step per entry to `progress`
+///
+/// Returns [`FileError::Cancelled`] if `progress` asks to stop.
+pub fn verify_with_progress<P, S>(
+    pk_path: P,
+    pki: &PackIndexFile,
+    progress: &S,
+) -> FileResult<VerifyReport>
+where
+    P: AsRef<Path>,
+    S: ProgressSink + ?Sized,
+{
+    let pk_path = pk_path.as_ref();
+    let pk_name = pk_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_ascii_lowercase());
+    let archive = pki
+        .archives
+        .iter()
+        .position(|a| Some(file_name(&a.path)) == pk_name)
+        .map(|index| index as u32);
+
+    let file = File::open(pk_path)?;
+    let mut reader = BufReader::new(file);
+    let mut pack = PackFile::open(&mut reader);
+    pack.check_magic()?;
+    let header = pack.get_header()?;
+    let entries = pack.get_entry_list(header.file_list_base_addr)?;
+
+    let mut report = VerifyReport {
+        archive,
+        ..VerifyReport::default()
+    };
+    let mut seen = Vec::with_capacity(entries.len());
+    progress.start("entries", entries.len() as u64);
+    for entry in entries {
+        if progress.is_cancelled() {
+            return Err(FileError::Cancelled);
+        }
+        let crc = entry.crc;
+        let mut issues = Vec::new();
+        match pki.files.get(&crc) {
+            None => issues.push(Issue::NotInIndex),
+            Some(file_ref) => {
+                if Some(file_ref.pack_file) != archive {
+                    let other = pki.archive(file_ref).map(|a| a.path.clone());
+                    issues.push(Issue::WrongArchive(other));
+                }
+                if file_ref.is_compressed() != (entry.is_compressed[0] > 0) {
+                    issues.push(Issue::CompressionMismatch);
+                }
+            }
+        }
+        check_entry(&mut pack, entry, &mut issues);
+        seen.push(crc);
+        report.checked += 1;
+        if !issues.is_empty() {
+            report.failures.push(EntryReport { crc, issues });
+        }
+        progress.advance(1);
+    }
+
+    if let Some(archive) = archive {
+        seen.sort_unstable();
+        report.missing = pki
+            .files
+            .iter()
+            .filter(|(crc, f)| f.pack_file == archive && seen.binary_search(crc).is_err())
+            .map(|(crc, _)| *crc)
+            .collect();
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crc::calculate_crc;
+    use crate::pk::writer::PackWriter;
+
+    #[test]
+    fn test_verify() {
+        let dir = std::env::temp_dir().join(format!("assembly-verify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pk_path = dir.join("a.pk");
+        let mut writer = PackWriter::new(File::create(&pk_path).unwrap()).unwrap();
+        writer.append_entry("a.txt", &b"Hello World"[..]).unwrap();
+        writer
+            .append_entry_with("b.txt", &b"Uncompressed"[..], false)
+            .unwrap();
+        writer
+            .append_entry_with("c.txt", &b"Other"[..], false)
+            .unwrap();
+        writer.append_entry("d.txt", &b"Unlisted"[..]).unwrap();
+        writer.finish().unwrap();
+
+        let mut bytes = std::fs::read(&pk_path).unwrap();
+        let pos = bytes
+            .windows(12)
+            .position(|w| w == b"Uncompressed")
+            .unwrap();
+        bytes[pos] = b'u';
+        std::fs::write(&pk_path, bytes).unwrap();
+
+        let mut pki = PackIndexFile::default();
+        pki.insert("a.txt", "client\\res\\pack\\a.pk", true);
+        pki.insert("b.txt", "client\\res\\pack\\a.pk", true);
+        pki.insert("c.txt", "client\\res\\pack\\other.pk", false);
+        pki.insert("e.txt", "client\\res\\pack\\a.pk", false);
+
+        let report = verify(&pk_path, &pki).unwrap();
+        assert_eq!(report.archive, Some(0));
+        assert_eq!(report.checked, 4);
+        assert_eq!(report.missing, vec![calculate_crc(b"e.txt")]);
+        let issues = |path: &[u8]| {
+            let crc = calculate_crc(path);
+            let failure = report.failures.iter().find(|f| f.crc == crc);
+            failure.map(|f| f.issues.clone()).unwrap_or_default()
+        };
+        assert_eq!(issues(b"a.txt"), vec![]);
+        let b = issues(b"b.txt");
+        assert_eq!(b[0], Issue::CompressionMismatch);
+        assert!(matches!(
+            b[1],
+            Issue::HashMismatch {
+                compressed: false,
+                ..
+            }
+        ));
+        let other = Some("client\\res\\pack\\other.pk".to_owned());
+        assert_eq!(issues(b"c.txt"), vec![Issue::WrongArchive(other)]);
+        assert_eq!(issues(b"d.txt"), vec![Issue::NotInIndex]);
+        assert!(!report.is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,118 @@
+//! # Loose files in a directory tree
+
+use std::fs::{self, File};
+use std::io::{BufReader, Result as IoResult};
+use std::path::{Component, Path, PathBuf};
+
+use super::{not_found, ReadSeek, ResFs};
+use crate::path::normalize;
+
+/// The resources in the installation folder of a client
+///
+/// Lookups are case-insensitive, even if the underlying file system is not.
+#[derive(Debug, Clone)]
+pub struct DirFs {
+    root: PathBuf,
+}
+
+impl DirFs {
+    /// Create a source for the installation folder `root`
+    ///
+    /// A resource `client\res\a.txt` is expected at `root/client/res/a.txt`.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Get the installation folder
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Find the file for a resource on disk
+    ///
+    /// Returns `None` for paths that could leave the root, i.e. ones with `..`
+    /// segments, drive prefixes or absolute segments.
+    pub fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let mut current = self.root.clone();
+        for segment in normalize(path).split('\\') {
+            let mut components = Path::new(segment).components();
+            match (components.next(), components.next()) {
+                (Some(Component::Normal(_)), None) => {}
+                _ => return None,
+            }
+            let exact = current.join(segment);
+            current = if exact.exists() {
+                exact
+            } else {
+                fs::read_dir(&current)
+                    .ok()?
+                    .filter_map(Result::ok)
+                    .find(|e| e.file_name().to_string_lossy().to_ascii_lowercase() == segment)?
+                    .path()
+            };
+        }
+        Some(current).filter(|p| p.is_file())
+    }
+
+    fn walk(&self, dir: &Path, prefix: &str, out: &mut Vec<String>) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let name = entry.file_name().to_string_lossy().to_ascii_lowercase();
+            let path = format!("{}{}", prefix, name);
+            let dir_path = format!("{}\\", path);
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => self.walk(&entry.path(), &dir_path, out),
+                Ok(_) => out.push(path),
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+impl ResFs for DirFs {
+    fn open(&self, path: &str) -> IoResult<Box<dyn ReadSeek>> {
+        let file = self.resolve(path).ok_or_else(|| not_found(path))?;
+        Ok(Box::new(BufReader::new(File::open(file)?)))
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.resolve(path).is_some()
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Vec<String> {
+        let prefix = normalize(prefix);
+        let mut paths = Vec::new();
+        self.walk(&self.root, "", &mut paths);
+        paths.retain(|p| p.starts_with(&prefix));
+        paths.sort();
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_outside_root() {
+        let base = std::env::temp_dir().join(format!("assembly-dirfs-{}", std::process::id()));
+        let root = base.join("install");
+        fs::create_dir_all(root.join("client/res")).unwrap();
+        fs::write(root.join("client/res/a.txt"), "inside").unwrap();
+        fs::write(base.join("secret.txt"), "outside").unwrap();
+
+        let fs = DirFs::new(&root);
+        assert!(fs.resolve("res/a.txt").is_some());
+        assert!(fs.resolve("../secret.txt").is_none());
+        assert!(fs.resolve("res/../../secret.txt").is_none());
+        let absolute = base.join("secret.txt");
+        assert!(fs.resolve(absolute.to_str().unwrap()).is_none());
+        assert!(!fs.exists("..\\secret.txt"));
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
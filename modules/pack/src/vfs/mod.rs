@@ -0,0 +1,113 @@
+//! # A virtual file system for the resources
+//!
+//! The resources of a client are either stored as loose files in the
+//! installation folder or in pack (`*.pk`) archives, depending on how the
+//! client was installed and patched. The [`ResFs`] trait abstracts over this,
+//! so that tools can read a resource by its path without knowing where it is
+//! stored.
+//!
+//! All paths are brought into the canonical form of [`crate::path::normalize`],
+//! and [`ResFs::list_prefix`] returns paths in that form.
+//!
+//! ```
+//! use assembly_pack::pk::writer::PackWriter;
+//! use assembly_pack::vfs::{pack::PackFs, Overlay, ResFs};
+//! use std::io::Read;
+//!
+//! let mut writer = PackWriter::new(Vec::new()).unwrap();
+//! writer.append_entry("res/readme.txt", &b"Hello"[..]).unwrap();
+//! let mut pack = PackFs::from_bytes(writer.finish().unwrap()).unwrap();
+//! pack.add_names(&["res/readme.txt"]);
+//!
+//! let mut fs = Overlay::new();
+//! fs.push(Box::new(pack));
+//!
+//! let mut text = String::new();
+//! fs.open("client/res/README.txt").unwrap().read_to_string(&mut text).unwrap();
+//! assert_eq!(text, "Hello");
+//! assert_eq!(fs.list_prefix("res/"), vec!["client\\res\\readme.txt"]);
+//! ```
+
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Seek};
+
+pub mod dir;
+pub mod overlay;
+pub mod pack;
+
+pub use overlay::{Overlay, Priority};
+
+/// A stream that can be read and seeked
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// A source of resources
+pub trait ResFs {
+    /// Open the resource at `path`
+    ///
+    /// Returns an error of kind [`ErrorKind::NotFound`] if it doesn't exist.
+    fn open(&self, path: &str) -> IoResult<Box<dyn ReadSeek>>;
+
+    /// Check whether the resource at `path` exists
+    fn exists(&self, path: &str) -> bool;
+
+    /// List the canonical paths of all known resources that start with `prefix`
+    fn list_prefix(&self, prefix: &str) -> Vec<String>;
+}
+
+fn not_found(path: &str) -> IoError {
+    IoError::new(
+        ErrorKind::NotFound,
+        format!("resource {:?} not found", path),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dir::DirFs, pack::PackFs, *};
+    use crate::pk::writer::PackWriter;
+    use std::fs;
+
+    fn read(fs: &dyn ResFs, path: &str) -> String {
+        let mut text = String::new();
+        fs.open(path).unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn test_overlay() {
+        let root = std::env::temp_dir().join(format!("assembly-vfs-{}", std::process::id()));
+        fs::create_dir_all(root.join("client/res/Textures")).unwrap();
+        fs::write(root.join("client/res/Textures/A.dds"), "loose").unwrap();
+
+        let mut writer = PackWriter::new(Vec::new()).unwrap();
+        writer
+            .append_entry("res/textures/a.dds", &b"packed"[..])
+            .unwrap();
+        writer
+            .append_entry("res/textures/b.dds", &b"packed"[..])
+            .unwrap();
+        let mut pack = PackFs::from_bytes(writer.finish().unwrap()).unwrap();
+        pack.add_names(&["res/textures/a.dds", "res/textures/b.dds", "res/c.dds"]);
+
+        let mut fs = Overlay::new();
+        fs.mount(Box::new(pack), Priority::BASE_PACK);
+        fs.mount(Box::new(DirFs::new(&root)), Priority::LOOSE);
+
+        assert_eq!(read(&fs, "res/textures/a.dds"), "loose");
+        assert_eq!(read(&fs, "res/textures/b.dds"), "packed");
+        assert!(!fs.exists("res/c.dds"));
+        assert_eq!(
+            fs.open("res/c.dds").err().unwrap().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            fs.list_prefix("client/res/textures/"),
+            vec![
+                "client\\res\\textures\\a.dds",
+                "client\\res\\textures\\b.dds"
+            ]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,185 @@
+//! # Layered sources
+//!
+//! The client looks up every resource in a fixed order: loose files in the
+//! installation folder come first, then the pack files of patches and finally
+//! the pack files of the base installation. An [`Overlay`] models this with a
+//! [`Priority`] for each mounted source. A source shadows all sources with a
+//! lower priority, and among sources with the same priority the one that was
+//! mounted last wins, so that a newer patch overrides an older one.
+
+use std::collections::BTreeSet;
+use std::io::Result as IoResult;
+
+use super::{not_found, ReadSeek, ResFs};
+
+/// The priority of a mounted source, higher priorities shadow lower ones
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Priority(pub i32);
+
+impl Priority {
+    /// The pack files of the base installation
+    pub const BASE_PACK: Priority = Priority(0);
+    /// The pack files of patches
+    pub const PATCH_PACK: Priority = Priority(100);
+    /// Loose files in the installation folder
+    pub const LOOSE: Priority = Priority(200);
+}
+
+struct Layer {
+    priority: Priority,
+    source: Box<dyn ResFs>,
+}
+
+/// A stack of sources, ordered by priority
+#[derive(Default)]
+pub struct Overlay {
+    layers: Vec<Layer>,
+}
+
+impl Overlay {
+    /// Create an empty overlay
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mount a source above all existing sources with the same or a lower priority
+    pub fn mount(&mut self, source: Box<dyn ResFs>, priority: Priority) {
+        let index = self
+            .layers
+            .iter()
+            .position(|l| l.priority <= priority)
+            .unwrap_or(self.layers.len());
+        self.layers.insert(index, Layer { priority, source });
+    }
+
+    /// Add a source below all existing ones
+    ///
+    /// It gets the lowest priority in the overlay, or [`Priority::BASE_PACK`]
+    /// if that is lower.
+    pub fn push(&mut self, source: Box<dyn ResFs>) {
+        let priority = match self.layers.last() {
+            Some(last) => last.priority.min(Priority::BASE_PACK),
+            None => Priority::BASE_PACK,
+        };
+        self.layers.push(Layer { priority, source });
+    }
+
+    /// Get the number of sources
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Check whether there are no sources
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Iterate over the sources, from the highest to the lowest priority
+    pub fn layers(&self) -> impl Iterator<Item = (Priority, &dyn ResFs)> {
+        self.layers.iter().map(|l| (l.priority, l.source.as_ref()))
+    }
+
+    /// Get the index in [`Overlay::layers`] of the source that provides `path`
+    pub fn resolve(&self, path: &str) -> Option<usize> {
+        self.layers.iter().position(|l| l.source.exists(path))
+    }
+}
+
+impl ResFs for Overlay {
+    fn open(&self, path: &str) -> IoResult<Box<dyn ReadSeek>> {
+        match self.resolve(path) {
+            Some(index) => self.layers[index].source.open(path),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.resolve(path).is_some()
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Vec<String> {
+        let mut paths = BTreeSet::new();
+        for layer in &self.layers {
+            paths.extend(layer.source.list_prefix(prefix));
+        }
+        paths.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::normalize;
+    use std::collections::BTreeMap;
+    use std::io::{Cursor, Read};
+
+    struct MemFs(BTreeMap<String, &'static str>);
+
+    impl MemFs {
+        fn new(files: &[(&str, &'static str)]) -> Box<Self> {
+            let files = files.iter().map(|(p, d)| (normalize(p), *d)).collect();
+            Box::new(MemFs(files))
+        }
+    }
+
+    impl ResFs for MemFs {
+        fn open(&self, path: &str) -> IoResult<Box<dyn ReadSeek>> {
+            let data = self
+                .0
+                .get(&normalize(path))
+                .ok_or_else(|| not_found(path))?;
+            Ok(Box::new(Cursor::new(data.as_bytes())))
+        }
+
+        fn exists(&self, path: &str) -> bool {
+            self.0.contains_key(&normalize(path))
+        }
+
+        fn list_prefix(&self, prefix: &str) -> Vec<String> {
+            let prefix = normalize(prefix);
+            self.0
+                .keys()
+                .filter(|p| p.starts_with(&prefix))
+                .cloned()
+                .collect()
+        }
+    }
+
+    fn read(fs: &Overlay, path: &str) -> String {
+        let mut text = String::new();
+        fs.open(path).unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let mut fs = Overlay::new();
+        fs.mount(MemFs::new(&[("res/a", "loose")]), Priority::LOOSE);
+        fs.mount(
+            MemFs::new(&[("res/a", "base"), ("res/b", "base"), ("res/c", "base")]),
+            Priority::BASE_PACK,
+        );
+        fs.mount(
+            MemFs::new(&[("res/a", "patch 1"), ("res/b", "patch 1")]),
+            Priority::PATCH_PACK,
+        );
+        fs.mount(MemFs::new(&[("res/b", "patch 2")]), Priority::PATCH_PACK);
+        fs.push(MemFs::new(&[("res/c", "fallback"), ("res/d", "fallback")]));
+
+        // loose files override packs, newer patches override older ones
+        assert_eq!(read(&fs, "res/a"), "loose");
+        assert_eq!(read(&fs, "res/b"), "patch 2");
+        assert_eq!(read(&fs, "res/c"), "base");
+        assert_eq!(read(&fs, "res/d"), "fallback");
+        assert_eq!(fs.resolve("res/b"), Some(1));
+        assert_eq!(fs.resolve("res/e"), None);
+
+        let priorities: Vec<_> = fs.layers().map(|(p, _)| p).collect();
+        let order = [200, 100, 100, 0, 0];
+        assert_eq!(
+            priorities,
+            order.iter().map(|&p| Priority(p)).collect::<Vec<_>>()
+        );
+        assert_eq!(fs.list_prefix("res/").len(), 4);
+    }
+}
@@ -0,0 +1,129 @@
+//! # The files in a pack archive
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{
+    BufRead, BufReader, Cursor, Error as IoError, ErrorKind, Read, Result as IoResult, Seek,
+};
+use std::path::{Path, PathBuf};
+
+use assembly_core::reader::FileResult;
+
+use super::{not_found, ReadSeek, ResFs};
+use crate::crc::{calculate_crc, calculate_crc_normalized};
+use crate::path::normalize;
+use crate::pk::file::PKEntry;
+use crate::pk::reader::{PackFile, StreamError};
+use crate::sd0::stream::SegmentedError;
+
+enum Source {
+    File(PathBuf),
+    Memory(Vec<u8>),
+}
+
+/// The resources in a pack (`*.pk`) file
+///
+/// Pack files only store the CRC of each path, so [`ResFs::list_prefix`] only
+/// returns the paths that were registered with [`PackFs::add_names`].
+pub struct PackFs {
+    source: Source,
+    entries: BTreeMap<u32, PKEntry>,
+    names: BTreeMap<String, u32>,
+}
+
+fn load_entries<T: Seek + BufRead>(inner: &mut T) -> FileResult<BTreeMap<u32, PKEntry>> {
+    let mut pack = PackFile::open(inner);
+    pack.check_magic()?;
+    let header = pack.get_header()?;
+    let entries = pack.get_entry_list(header.file_list_base_addr)?;
+    Ok(entries.into_iter().map(|e| (e.crc, e)).collect())
+}
+
+fn read_entry<T: Seek + BufRead>(inner: &mut T, entry: &PKEntry) -> IoResult<Vec<u8>> {
+    let mut pack = PackFile::open(inner);
+    let mut data = Vec::with_capacity(entry.orig_file_size as usize);
+    match pack.get_file_data(entry.clone()) {
+        Ok(mut stream) => stream.read_to_end(&mut data)?,
+        Err(StreamError::Segmented(SegmentedError::Read(e))) => return Err(e),
+        Err(StreamError::Segmented(e)) => {
+            return Err(IoError::new(ErrorKind::InvalidData, e.to_string()))
+        }
+    };
+    Ok(data)
+}
+
+impl PackFs {
+    /// Load the list of entries of the pack file at `path`
+    ///
+    /// The file is opened again whenever a resource is read.
+    pub fn open<P: AsRef<Path>>(path: P) -> FileResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let entries = load_entries(&mut BufReader::new(File::open(&path)?))?;
+        Ok(Self::with_entries(Source::File(path), entries))
+    }
+
+    /// Load the list of entries of a pack file in memory
+    pub fn from_bytes(bytes: Vec<u8>) -> FileResult<Self> {
+        let entries = load_entries(&mut Cursor::new(&bytes))?;
+        Ok(Self::with_entries(Source::Memory(bytes), entries))
+    }
+
+    fn with_entries(source: Source, entries: BTreeMap<u32, PKEntry>) -> Self {
+        Self {
+            source,
+            entries,
+            names: BTreeMap::new(),
+        }
+    }
+
+    /// Get the entries of the pack file, by CRC
+    pub fn entries(&self) -> &BTreeMap<u32, PKEntry> {
+        &self.entries
+    }
+
+    /// Register known paths for listing
+    ///
+    /// Paths that are not in this pack file are ignored. Returns the
+    /// number of paths that were added.
+    pub fn add_names<S: AsRef<str>>(&mut self, names: &[S]) -> usize {
+        let mut count = 0;
+        for name in names {
+            let name = normalize(name.as_ref());
+            let crc = calculate_crc(name.as_bytes());
+            if self.entries.contains_key(&crc) && self.names.insert(name, crc).is_none() {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Read the data of an entry
+    pub fn read(&self, entry: &PKEntry) -> IoResult<Vec<u8>> {
+        match &self.source {
+            Source::File(path) => read_entry(&mut BufReader::new(File::open(path)?), entry),
+            Source::Memory(bytes) => read_entry(&mut Cursor::new(bytes), entry),
+        }
+    }
+}
+
+impl ResFs for PackFs {
+    fn open(&self, path: &str) -> IoResult<Box<dyn ReadSeek>> {
+        let crc = calculate_crc_normalized(path.as_bytes());
+        let entry = self.entries.get(&crc).ok_or_else(|| not_found(path))?;
+        Ok(Box::new(Cursor::new(self.read(entry)?)))
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.entries
+            .contains_key(&calculate_crc_normalized(path.as_bytes()))
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Vec<String> {
+        let prefix = normalize(prefix);
+        let range = self.names.range(prefix.clone()..);
+        range
+            .take_while(|(name, _)| name.starts_with(&prefix))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+}